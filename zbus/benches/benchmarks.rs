@@ -0,0 +1,58 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use std::{os::unix::net::UnixStream, thread};
+
+use zbus::{Connection, Guid, Message};
+
+fn fixed_body(c: &mut Criterion) {
+    let call = Message::method(Some(":1.42"), None, "/", None, "Get", &()).unwrap();
+    let reply = Message::method_reply(None, &call, &(-42i32, 42u64)).unwrap();
+
+    c.bench_function("fixed_body_serde", |b| {
+        b.iter(|| {
+            let body: (i32, u64) = black_box(&reply).body().unwrap();
+            black_box(body);
+        })
+    });
+    c.bench_function("fixed_body_direct", |b| {
+        b.iter(|| {
+            let body: (i32, u64) = black_box(&reply).body_fixed().unwrap();
+            black_box(body);
+        })
+    });
+}
+
+fn send_batch(c: &mut Criterion) {
+    let (p0, p1) = UnixStream::pair().unwrap();
+    let guid = Guid::generate();
+    let server = thread::spawn(move || Connection::new_unix_server(p0, &guid).unwrap());
+    let client = Connection::new_unix_client(p1, false).unwrap();
+    // Keep the server connection around, so the signals get read from the socket.
+    let _server = server.join().unwrap();
+    let signals = || {
+        (0..500u32)
+            .map(|i| Message::signal(None, None, "/", "org.zbus.Bench", "Burst", &i).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    c.bench_function("send_500_signals_one_by_one", |b| {
+        b.iter_batched(
+            signals,
+            |msgs| {
+                for msg in msgs {
+                    client.send_message(msg).unwrap();
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("send_500_signals_batch", |b| {
+        b.iter_batched(
+            signals,
+            |msgs| client.send_batch(msgs).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, fixed_body, send_batch);
+criterion_main!(benches);
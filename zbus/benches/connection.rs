@@ -0,0 +1,253 @@
+//! Benchmarks of connections, over a peer-to-peer pair in the same process.
+//!
+//! Run with `cargo bench --features test-bus --bench connection`. Besides the timings, the number
+//! of allocations per iteration of each scenario is printed, counting those of all threads (i-e
+//! including the other side of the connection).
+
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput,
+};
+use futures_util::StreamExt;
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use zbus::{
+    dbus_interface, test_bus::p2p_pair, Connection, Message, MessageFlags, ObjectServer, Proxy,
+};
+
+// Counts the allocations of the whole process.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// Print the average number of allocations of `f`, over a fixed number of runs.
+fn count_allocations(name: &str, mut f: impl FnMut()) {
+    const RUNS: usize = 100;
+
+    // Warm up, for the one-time allocations (e.g of the queues) not to be counted.
+    f();
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    for _ in 0..RUNS {
+        f();
+    }
+    let allocations = ALLOCATIONS.load(Ordering::SeqCst) - before;
+
+    println!(
+        "{}: {:.1} allocations per iteration",
+        name,
+        allocations as f64 / RUNS as f64
+    );
+}
+
+const PATH: &str = "/org/zbus/Bench";
+const INTERFACE: &str = "org.zbus.Bench";
+
+struct Bench {
+    count: u32,
+    done: Rc<Cell<bool>>,
+}
+
+#[dbus_interface(name = "org.zbus.Bench")]
+impl Bench {
+    fn ping(&self) -> u32 {
+        self.count
+    }
+
+    fn upload(&self, data: Vec<u8>) -> u32 {
+        data.len() as u32
+    }
+
+    fn quit(&self) {
+        self.done.set(true);
+    }
+
+    #[dbus_interface(property)]
+    fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+// A client connection to a `Bench` object, served on a thread of its own until `quit`.
+fn bench_client() -> (Connection, thread::JoinHandle<()>) {
+    let (server, client) = p2p_pair().unwrap();
+    let server_thread = thread::spawn(move || {
+        let mut object_server = ObjectServer::new(&server);
+        let done = Rc::new(Cell::new(false));
+        let iface = Bench {
+            count: 42,
+            done: done.clone(),
+        };
+        object_server.at(PATH, iface).unwrap();
+
+        while !done.get() {
+            object_server.try_handle_next().unwrap();
+        }
+    });
+
+    (client, server_thread)
+}
+
+fn quit(client: Connection, server_thread: thread::JoinHandle<()>) {
+    client
+        .call_method(None, PATH, Some(INTERFACE), "Quit", &())
+        .unwrap();
+    server_thread.join().unwrap();
+}
+
+fn method_call(c: &mut Criterion) {
+    let (client, server_thread) = bench_client();
+    let ping = || {
+        let reply = client
+            .call_method(None, PATH, Some(INTERFACE), "Ping", &())
+            .unwrap();
+        black_box(reply.body::<u32>().unwrap());
+    };
+
+    count_allocations("method_call_round_trip", ping);
+    c.bench_function("method_call_round_trip", |b| b.iter(ping));
+
+    quit(client, server_thread);
+}
+
+fn no_reply_calls(c: &mut Criterion) {
+    const CALLS: u64 = 500;
+
+    let (server, client) = p2p_pair().unwrap();
+    // Nothing but reading the calls is done on the other side, as there's no reply to send.
+    let server_thread = thread::spawn(move || {
+        while let Ok(msg) = server.receive_message() {
+            if msg.header().unwrap().member().unwrap() == Some("Quit") {
+                break;
+            }
+        }
+    });
+    // There's no API to set the flags, so they're set in the encoded message.
+    let call = |member| {
+        let msg = Message::method(None, None, PATH, Some(INTERFACE), member, &42u32).unwrap();
+        let mut bytes = msg.as_bytes().to_vec();
+        bytes[2] |= MessageFlags::NoReplyExpected as u8;
+
+        Message::from_raw_parts(bytes, vec![]).unwrap()
+    };
+    let calls = || (0..CALLS).map(|_| call("Notify")).collect::<Vec<_>>();
+    let send = |calls: Vec<Message>| {
+        for msg in calls {
+            client.send_message(msg).unwrap();
+        }
+    };
+
+    count_allocations("no_reply_calls", || send(vec![call("Notify")]));
+    let mut group = c.benchmark_group("no_reply_calls");
+    group.throughput(Throughput::Elements(CALLS));
+    group.bench_function("send", |b| {
+        b.iter_batched(calls, send, BatchSize::SmallInput)
+    });
+    group.finish();
+
+    client.send_message(call("Quit")).unwrap();
+    server_thread.join().unwrap();
+}
+
+fn large_body(c: &mut Criterion) {
+    const SIZE: usize = 1024 * 1024;
+
+    let (client, server_thread) = bench_client();
+    let data = vec![77u8; SIZE];
+    let upload = || {
+        let reply = client
+            .call_method(None, PATH, Some(INTERFACE), "Upload", &data)
+            .unwrap();
+        assert_eq!(reply.body::<u32>().unwrap() as usize, SIZE);
+    };
+
+    count_allocations("large_body_1MiB", upload);
+    let mut group = c.benchmark_group("large_body");
+    group.throughput(Throughput::Bytes(SIZE as u64));
+    group.sample_size(20);
+    group.bench_function("1MiB", |b| b.iter(upload));
+    group.finish();
+
+    quit(client, server_thread);
+}
+
+fn signal_fan_out(c: &mut Criterion) {
+    let mut group = c.benchmark_group("signal_fan_out");
+
+    for n in &[1, 4, 16] {
+        let (receiver, emitter) = p2p_pair().unwrap();
+        let mut streams = async_io::block_on(async {
+            let mut streams = vec![];
+            for _ in 0..*n {
+                streams.push(receiver.inner().stream().await);
+            }
+
+            streams
+        });
+        let mut emit_and_receive = || {
+            emitter
+                .emit_signal(None, PATH, INTERFACE, "Tick", &42u32)
+                .unwrap();
+            async_io::block_on(async {
+                for stream in &mut streams {
+                    black_box(stream.next().await.unwrap().unwrap());
+                }
+            });
+        };
+
+        count_allocations(&format!("signal_fan_out/{}", n), &mut emit_and_receive);
+        group.bench_with_input(BenchmarkId::from_parameter(n), n, |b, _| {
+            b.iter(&mut emit_and_receive)
+        });
+    }
+
+    group.finish();
+}
+
+// `Proxy` doesn't cache properties (yet), so each `Get` is a round-trip to the service.
+fn property_get(c: &mut Criterion) {
+    let (client, server_thread) = bench_client();
+    let proxy = Proxy::new(&client, "org.zbus.Bench", PATH, INTERFACE).unwrap();
+    let get = || {
+        black_box(proxy.get_property::<u32>("Count").unwrap());
+    };
+
+    count_allocations("property_get_uncached", get);
+    c.bench_function("property_get_uncached", |b| b.iter(get));
+
+    drop(proxy);
+    quit(client, server_thread);
+}
+
+criterion_group!(
+    benches,
+    method_call,
+    no_reply_calls,
+    large_body,
+    signal_fan_out,
+    property_get
+);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+// A service meant to be run by systemd, e.g through a unit like:
+//
+//   [Service]
+//   Type=dbus
+//   BusName=org.zbus.ActivatableExample
+//   ExecStart=/path/to/activatable-service
+//   WatchdogSec=10
+//
+// and possibly started on demand by the bus, through a service file with `SystemdService=` set to
+// the unit. Either way, the service is considered ready as soon as it owns its name, so the name is
+// only requested once the object is served. With `Type=notify`, `READY=1` is what counts instead.
+//
+// Try it with:
+//
+//   busctl --user call org.zbus.ActivatableExample /org/zbus/Counter org.zbus.Counter1 Increment
+#![forbid(unsafe_code)]
+
+use std::error::Error;
+
+use zbus::{
+    dbus_interface,
+    sd_notify::{self, Watchdog},
+    ConnectionBuilder, ObjectServer,
+};
+
+struct Counter {
+    count: u32,
+}
+
+#[dbus_interface(name = "org.zbus.Counter1")]
+impl Counter {
+    /// Increment the counter, returning its new value.
+    fn increment(&mut self) -> u32 {
+        self.count += 1;
+
+        self.count
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let connection = ConnectionBuilder::session()
+        .name("org.zbus.ActivatableExample")
+        .request_name_on_build(false)
+        .build()?;
+
+    // Keeps systemd from restarting the service, as long as the connection is running.
+    let _watchdog = Watchdog::start(connection.inner());
+
+    let mut object_server = ObjectServer::new(&connection);
+    object_server.at("/org/zbus/Counter", Counter { count: 0 })?;
+
+    // Everything is in place: let the peers in.
+    connection.request_registered_names()?;
+    sd_notify::ready()?;
+
+    loop {
+        if let Err(err) = object_server.try_handle_next() {
+            eprintln!("{}", err);
+        }
+    }
+}
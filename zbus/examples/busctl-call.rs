@@ -0,0 +1,85 @@
+// A tiny clone of `busctl call`, calling a method with arguments given on the command line.
+//
+// Usage: busctl-call [--system] DESTINATION PATH INTERFACE METHOD [SIGNATURE [ARGUMENT...]]
+//
+// The types of the arguments are only known at runtime, from the signature. Only basic types are
+// supported, e.g:
+//
+//   busctl-call org.freedesktop.DBus /org/freedesktop/DBus org.freedesktop.DBus GetNameOwner s \
+//       org.freedesktop.DBus
+#![forbid(unsafe_code)]
+
+use std::{convert::TryFrom, env, error::Error, process};
+
+use zbus::{Connection, Proxy};
+use zvariant::{ObjectPath, Signature, Value};
+
+const USAGE: &str =
+    "usage: busctl-call [--system] DESTINATION PATH INTERFACE METHOD [SIGNATURE [ARGUMENT...]]";
+
+fn parse_arg(c: char, arg: &str) -> Result<Value<'_>, Box<dyn Error>> {
+    let value = match c {
+        'y' => Value::from(arg.parse::<u8>()?),
+        'b' => Value::from(arg.parse::<bool>()?),
+        'n' => Value::from(arg.parse::<i16>()?),
+        'q' => Value::from(arg.parse::<u16>()?),
+        'i' => Value::from(arg.parse::<i32>()?),
+        'u' => Value::from(arg.parse::<u32>()?),
+        'x' => Value::from(arg.parse::<i64>()?),
+        't' => Value::from(arg.parse::<u64>()?),
+        'd' => Value::from(arg.parse::<f64>()?),
+        's' => Value::from(arg),
+        'o' => Value::from(ObjectPath::try_from(arg)?),
+        'g' => Value::from(Signature::try_from(arg)?),
+        c => return Err(format!("unsupported type `{}`, only basic types are", c).into()),
+    };
+
+    Ok(value)
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let system = args.first().map(String::as_str) == Some("--system");
+    if system {
+        args.remove(0);
+    }
+    if args.len() < 4 {
+        return Err(USAGE.into());
+    }
+
+    let signature = args.get(4).map(String::as_str).unwrap_or("");
+    let values = args.get(5..).unwrap_or(&[]);
+    if signature.chars().count() != values.len() {
+        return Err(format!("{} arguments expected for `{}`", signature.len(), signature).into());
+    }
+    let values = signature
+        .chars()
+        .zip(values)
+        .map(|(c, arg)| parse_arg(c, arg))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let conn = if system {
+        Connection::new_system()?
+    } else {
+        Connection::new_session()?
+    };
+    let proxy = Proxy::new(&conn, &args[0], args[1].as_str(), &args[2])?;
+    let reply = proxy.call_with_values(&args[3], &values)?;
+
+    // The reply as `busctl` shows it: its signature, then each of its arguments.
+    let body = reply.body_structure()?;
+    let signature = body.full_signature();
+    println!("{}", &signature[1..signature.len() - 1]);
+    for field in body.fields() {
+        print!("{}", field.to_pretty_string());
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,91 @@
+// A D-Bus client driven by a hand-rolled poll(2) loop, as a foreign event loop would drive it.
+//
+// It connects to the session bus, calls `Hello` and `GetId` on the bus, prints the replies and
+// exits. Only `unix:path=` bus addresses are supported, for brevity.
+#![forbid(unsafe_code)]
+
+use std::{
+    env,
+    error::Error,
+    os::unix::{io::AsRawFd, net::UnixStream},
+};
+
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+};
+use zbus::{raw, Message, MessageType};
+
+// Enqueue a call of `method` on the bus, returning its serial number.
+fn call(conn: &mut raw::Connection<UnixStream>, method: &str) -> Result<u32, Box<dyn Error>> {
+    let mut msg = Message::method(
+        None,
+        Some("org.freedesktop.DBus"),
+        "/org/freedesktop/DBus",
+        Some("org.freedesktop.DBus"),
+        method,
+        &(),
+    )?;
+    let serial = conn.assign_serial_num(&mut msg)?;
+    conn.enqueue_message(msg);
+
+    Ok(serial)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let address = env::var("DBUS_SESSION_BUS_ADDRESS")?;
+    let path = address
+        .split(';')
+        .find_map(|addr| addr.strip_prefix("unix:path="))
+        .ok_or("only `unix:path=` addresses are supported")?;
+    let path = path.split(',').next().unwrap();
+
+    let socket = UnixStream::connect(path)?;
+    socket.set_nonblocking(true)?;
+    // The handshake is the only blocking part.
+    let mut conn = raw::Connection::new_client(socket)?;
+
+    // `Hello` must be the first call on a bus. Both calls are only sent from the loop.
+    let mut pending = vec![
+        (call(&mut conn, "Hello")?, "unique name"),
+        (call(&mut conn, "GetId")?, "bus ID"),
+    ];
+
+    while !pending.is_empty() {
+        let mut flags = PollFlags::POLLIN;
+        if conn.needs_write() {
+            flags |= PollFlags::POLLOUT;
+        }
+        let mut fds = [PollFd::new(conn.as_raw_fd(), flags)];
+        match poll(&mut fds, -1) {
+            Err(nix::Error::Sys(Errno::EINTR)) => continue,
+            res => res?,
+        };
+        let revents = fds[0].revents().unwrap_or_else(PollFlags::empty);
+
+        if revents.contains(PollFlags::POLLOUT) {
+            conn.process_writable()?;
+        }
+        if revents.intersects(PollFlags::POLLIN | PollFlags::POLLHUP | PollFlags::POLLERR) {
+            for msg in conn.process_readable()? {
+                let header = msg.header()?;
+                let reply_serial = match header.message_type()? {
+                    MessageType::MethodReturn | MessageType::Error => header.reply_serial()?,
+                    // e.g the `NameAcquired` signal following `Hello`.
+                    _ => None,
+                };
+                let i = match pending.iter().position(|(s, _)| Some(*s) == reply_serial) {
+                    Some(i) => i,
+                    None => continue,
+                };
+                let (_, what) = pending.remove(i);
+                if header.message_type()? == MessageType::Error {
+                    return Err(format!("failed to get the {}: {}", what, msg).into());
+                }
+                println!("{}: {}", what, msg.body::<&str>()?);
+            }
+        }
+    }
+
+    Ok(())
+}
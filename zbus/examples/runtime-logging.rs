@@ -0,0 +1,45 @@
+// A service whose logging filter can be changed at runtime, over D-Bus.
+//
+// The logs of zbus go through `tracing_subscriber`, with an `EnvFilter` that can be reloaded. The
+// initial filter is taken from `RUST_LOG`. Try changing it with:
+//
+//   busctl --user call org.zbus.LoggingExample /org/zbus/Logging org.zbus.Logging1 \
+//       SetFilter s "zbus::object_server=trace,zbus::handshake=debug"
+//
+// and calling any method of the service, e.g `GetFilter`, to see the object server logs.
+#![forbid(unsafe_code)]
+
+use std::{env, error::Error};
+
+use tracing_subscriber::{fmt, prelude::*, reload, EnvFilter};
+use zbus::{fdo, logging::Logging, Connection, ObjectServer};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let filter = env::var("RUST_LOG").unwrap_or_else(|_| "info".into());
+    let (filter_layer, handle) = reload::Layer::new(EnvFilter::try_new(&filter)?);
+    // This also forwards the `log` records of zbus to `tracing`.
+    tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(fmt::layer())
+        .init();
+
+    let connection = Connection::new_session()?;
+    fdo::DBusProxy::new(&connection)?.request_name(
+        "org.zbus.LoggingExample",
+        fdo::RequestNameFlags::ReplaceExisting.into(),
+    )?;
+
+    let mut object_server = ObjectServer::new(&connection);
+    let logging = Logging::new(filter, move |filter: &str| -> Result<(), Box<dyn Error>> {
+        handle.reload(EnvFilter::try_new(filter)?)?;
+
+        Ok(())
+    });
+    object_server.at("/org/zbus/Logging", logging)?;
+
+    loop {
+        if let Err(err) = object_server.try_handle_next() {
+            eprintln!("{}", err);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// A purely synchronous D-Bus service and client, without any async runtime involved.
+//
+// The service is served over a peer-to-peer connection so that no bus is needed: the client calls
+// a method on the service, prints the reply and exits. `cargo test` runs the same exchange.
+#![forbid(unsafe_code)]
+
+use std::{cell::Cell, error::Error, os::unix::net::UnixStream, rc::Rc, sync::mpsc, thread};
+
+use zbus::{dbus_interface, dbus_proxy, Connection, Guid, ObjectServer};
+
+struct Greeter {
+    done: Rc<Cell<bool>>,
+}
+
+#[dbus_interface(name = "org.zbus.SyncGreeter1")]
+impl Greeter {
+    fn say_hello(&self, name: &str) -> String {
+        self.done.set(true);
+
+        format!("Hello {}!", name)
+    }
+}
+
+#[dbus_proxy(
+    interface = "org.zbus.SyncGreeter1",
+    default_service = "org.zbus.SyncGreeter",
+    default_path = "/org/zbus/SyncGreeter"
+)]
+trait SyncGreeter {
+    fn say_hello(&self, name: &str) -> zbus::Result<String>;
+}
+
+// Serve the greeter on a thread of its own and greet `name` through it.
+fn greet(name: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let (p0, p1) = UnixStream::pair()?;
+    let guid = Guid::generate();
+    let (ready_tx, ready_rx) = mpsc::channel();
+
+    let server_thread = thread::spawn(move || -> zbus::Result<()> {
+        let conn = Connection::new_unix_server(p0, &guid)?;
+        let mut object_server = ObjectServer::new(&conn);
+        let done = Rc::new(Cell::new(false));
+        let greeter = Greeter { done: done.clone() };
+        object_server.at("/org/zbus/SyncGreeter", greeter)?;
+        // Messages that arrive before the object server is created won't be dispatched to it.
+        ready_tx.send(()).expect("client went away");
+
+        while !done.get() {
+            object_server.try_handle_next()?;
+        }
+
+        Ok(())
+    });
+
+    let conn = Connection::new_unix_client(p1, false)?;
+    ready_rx.recv()?;
+    let reply = SyncGreeterProxy::new(&conn)?.say_hello(name)?;
+
+    server_thread.join().expect("server thread panicked")?;
+
+    Ok(reply)
+}
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    println!("{}", greet("zbus")?);
+
+    Ok(())
+}
+
+#[test]
+fn serves_and_answers() {
+    // The server thread only returns once it has handled the call.
+    assert_eq!(greet("zbus").unwrap(), "Hello zbus!");
+    assert_eq!(greet("again").unwrap(), "Hello again!");
+}
@@ -1,85 +1,477 @@
+#[cfg(feature = "launchd")]
+use crate::launchd;
+#[cfg(feature = "tcp-transport")]
+use crate::tcp::{self, TcpFamily};
+#[cfg(feature = "unixexec-transport")]
+use crate::unixexec::{self, UnixexecStream};
 use crate::{raw::Socket, Error, Result};
 use async_io::Async;
 use nix::unistd::Uid;
-use std::{collections::HashMap, env, ffi::OsString, os::unix::net::UnixStream, str::FromStr};
+#[cfg(feature = "unix-transport")]
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsString,
+    net::{IpAddr, TcpStream},
+    os::unix::{ffi::OsStringExt, net::UnixListener},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+#[cfg(feature = "unix-transport")]
+use std::{iter, os::unix::ffi::OsStrExt, os::unix::net::UnixStream};
+
+// The transports of the addresses we know of, whether they're enabled or not.
+const TRANSPORTS: &[&str] = &["unix", "tcp", "unixexec", "launchd"];
 
 /// A bus address
 #[derive(Debug, PartialEq)]
 pub(crate) enum Address {
     /// A path on the filesystem
+    #[cfg(feature = "unix-transport")]
     Unix(OsString),
+    /// A directory in which to create a randomly named socket (listen-only)
+    #[cfg(feature = "unix-transport")]
+    UnixDir(OsString),
+    /// Same as `UnixDir` but an abstract socket may be created instead (listen-only)
+    #[cfg(feature = "unix-transport")]
+    UnixTmpDir(OsString),
+    /// A TCP host and port, optionally restricted to an IP family (connect-only)
+    #[cfg(feature = "tcp-transport")]
+    Tcp {
+        host: String,
+        port: u16,
+        family: Option<TcpFamily>,
+    },
+    /// A program to execute, talking over its stdin and stdout (connect-only)
+    #[cfg(feature = "unixexec-transport")]
+    Unixexec {
+        path: OsString,
+        argv0: Option<OsString>,
+        args: Vec<OsString>,
+    },
+    /// A unix socket whose path is in the given launchd environment variable (connect-only)
+    #[cfg(feature = "launchd")]
+    Launchd { env: OsString },
+}
+
+/// The socket options of the connections over `tcp` addresses.
+///
+/// All of them are left to the OS defaults, unless set through the `ConnectionBuilder`. Without the
+/// `tcp-transport` feature, they're accepted but never used.
+#[derive(Debug, Default)]
+#[cfg_attr(not(feature = "tcp-transport"), allow(dead_code))]
+pub(crate) struct TcpOptions {
+    pub(crate) nodelay: Option<bool>,
+    pub(crate) keepalive: Option<Duration>,
+    pub(crate) keepalive_interval: Option<Duration>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) source_address: Option<IpAddr>,
+    pub(crate) bind_device: Option<String>,
+    pub(crate) map_stream: Option<fn(TcpStream) -> TcpStream>,
+}
+
+/// A list of bus addresses, separated by `;`.
+///
+/// As per the specification, a client should try to connect to each address in order, until one
+/// works.
+#[derive(Debug)]
+pub(crate) struct AddressList(Vec<(String, Result<Address>)>);
+
+impl AddressList {
+    /// Get the address list for session socket respecting the DBUS_SESSION_BUS_ADDRESS
+    /// environment variable. If it's not set, we fall back to /run/user/UID/bus, or to the socket
+    /// of the launchd agent on macOS.
+    pub(crate) fn session() -> Result<Self> {
+        match env::var("DBUS_SESSION_BUS_ADDRESS") {
+            Ok(val) => Self::from_str(&val),
+            _ if cfg!(target_os = "macos") => {
+                Self::from_str("launchd:env=DBUS_LAUNCHD_SESSION_BUS_SOCKET")
+            }
+            _ => {
+                let uid = Uid::current();
+                let path = format!("unix:path=/run/user/{}/bus", uid);
+
+                Self::from_str(&path)
+            }
+        }
+    }
+
+    /// Get the address list for system bus respecting the DBUS_SYSTEM_BUS_ADDRESS environment
+    /// variable. If it's not set, we fall back to /var/run/dbus/system_bus_socket
+    pub(crate) fn system() -> Result<Self> {
+        match env::var("DBUS_SYSTEM_BUS_ADDRESS") {
+            Ok(val) => Self::from_str(&val),
+            _ => Self::from_str("unix:path=/var/run/dbus/system_bus_socket"),
+        }
+    }
+
+    /// Get the address list for the bus that started us through D-Bus activation, from the
+    /// DBUS_STARTER_ADDRESS environment variable. If only DBUS_STARTER_BUS_TYPE is set, we fall
+    /// back to the session or system bus accordingly.
+    pub(crate) fn starter() -> Result<Self> {
+        if let Ok(val) = env::var("DBUS_STARTER_ADDRESS") {
+            return Self::from_str(&val);
+        }
+
+        match env::var("DBUS_STARTER_BUS_TYPE") {
+            Ok(ty) if ty == "session" => Self::session(),
+            Ok(ty) if ty == "system" => Self::system(),
+            Ok(ty) => Err(Error::Address(format!("unknown starter bus type `{}`", ty))),
+            Err(_) => Err(Error::Address(
+                "no starter bus: neither DBUS_STARTER_ADDRESS nor DBUS_STARTER_BUS_TYPE is set"
+                    .into(),
+            )),
+        }
+    }
+
+    /// Connect to the first address that works, returning the stream along with the address.
+    ///
+    /// If all the addresses fail, the error of the only address is returned as is. For multiple
+    /// addresses, an `Error::Address` listing the error for each of them is returned.
+    pub(crate) async fn connect(self, tcp: &TcpOptions) -> Result<(Stream, String)> {
+        let mut errors = vec![];
+        for (s, address) in self {
+            let res = match address {
+                Ok(address) => address.connect(tcp).await,
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok(stream) => return Ok((stream, s)),
+                Err(e) => errors.push((s, e)),
+            }
+        }
+
+        if errors.len() == 1 {
+            return Err(errors.pop().expect("no error").1);
+        }
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|(s, e)| format!("`{}`: {}", s, e))
+            .collect();
+
+        Err(Error::Address(format!(
+            "failed to connect to any of the addresses: {}",
+            errors.join("; ")
+        )))
+    }
+}
+
+impl IntoIterator for AddressList {
+    /// An address along with its textual form, or the error from parsing it.
+    type Item = (String, Result<Address>);
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl FromStr for AddressList {
+    type Err = Error;
+
+    /// Parse a `;`-separated list of D-Bus addresses.
+    ///
+    /// Only fails if the list is empty: an unparsable address is kept in the list along with its
+    /// error, so that the next ones can still be tried.
+    fn from_str(addresses: &str) -> Result<Self> {
+        let list: Vec<_> = addresses
+            .split(';')
+            .filter(|s| !s.is_empty())
+            .map(|s| (s.to_string(), Address::from_str(s)))
+            .collect();
+        if list.is_empty() {
+            return Err(Error::Address("address list is empty".into()));
+        }
+
+        Ok(Self(list))
+    }
 }
 
 #[derive(Debug)]
 pub(crate) enum Stream {
+    #[cfg(feature = "unix-transport")]
     Unix(Async<UnixStream>),
+    #[cfg(feature = "tcp-transport")]
+    Tcp(Async<TcpStream>),
+    #[cfg(feature = "unixexec-transport")]
+    Unixexec(Async<UnixexecStream>),
 }
 
 impl Stream {
     pub(crate) fn into_boxed(self) -> Result<Async<Box<dyn Socket>>> {
         match self {
             // FIXME: easier/more direct way to do this?
+            #[cfg(feature = "unix-transport")]
             Stream::Unix(s) => Ok(Async::new(Box::new(s.into_inner()?) as Box<dyn Socket>)?),
+            #[cfg(feature = "tcp-transport")]
+            Stream::Tcp(s) => Ok(Async::new(Box::new(s.into_inner()?) as Box<dyn Socket>)?),
+            #[cfg(feature = "unixexec-transport")]
+            Stream::Unixexec(s) => Ok(Async::new(Box::new(s.into_inner()?) as Box<dyn Socket>)?),
         }
     }
 }
 
 impl Address {
-    pub(crate) async fn connect(&self) -> Result<Stream> {
+    // `tcp` is only used by the `tcp` transport.
+    #[cfg_attr(not(feature = "tcp-transport"), allow(unused_variables))]
+    pub(crate) async fn connect(&self, tcp: &TcpOptions) -> Result<Stream> {
         match self {
+            #[cfg(all(
+                feature = "unix-transport",
+                any(target_os = "android", target_os = "linux")
+            ))]
+            Address::Unix(p) if p.as_bytes().first() == Some(&0) => {
+                // std doesn't support abstract sockets.
+                let stream = abstract_socket(&p.as_bytes()[1..], |fd, addr| {
+                    nix::sys::socket::connect(fd, addr)
+                })?;
+
+                Ok(Stream::Unix(Async::new(stream)?))
+            }
+            #[cfg(feature = "unix-transport")]
             Address::Unix(p) => Async::<UnixStream>::connect(p)
                 .await
                 .map(Stream::Unix)
                 .map_err(Error::Io),
+            #[cfg(feature = "unix-transport")]
+            Address::UnixDir(_) | Address::UnixTmpDir(_) => Err(Error::Address(
+                "`dir` and `tmpdir` addresses can only be listened on, not connected to".into(),
+            )),
+            #[cfg(feature = "tcp-transport")]
+            Address::Tcp { host, port, family } => tcp::connect(host, *port, *family, tcp)
+                .await
+                .map(Stream::Tcp),
+            #[cfg(feature = "unixexec-transport")]
+            Address::Unixexec { path, argv0, args } => {
+                unixexec::connect(path, argv0.as_ref(), args).map(Stream::Unixexec)
+            }
+            #[cfg(feature = "launchd")]
+            Address::Launchd { env } => {
+                let path = launchd::socket_path(env).await?;
+
+                Async::<UnixStream>::connect(path)
+                    .await
+                    .map(Stream::Unix)
+                    .map_err(Error::Io)
+            }
         }
     }
 
-    /// Get the address for session socket respecting the DBUS_SESSION_BUS_ADDRESS environment
-    /// variable. If we don't recognize the value (or it's not set) we fall back to
-    /// /run/user/UID/bus
-    pub(crate) fn session() -> Result<Self> {
-        match env::var("DBUS_SESSION_BUS_ADDRESS") {
-            Ok(val) => Self::from_str(&val),
-            _ => {
-                let uid = Uid::current();
-                let path = format!("unix:path=/run/user/{}/bus", uid);
+    // Create a listening socket for the address. Returns it, along with the concrete address of
+    // the socket and the path of the socket file (if any).
+    pub(crate) fn listen(&self) -> Result<(UnixListener, String, Option<PathBuf>)> {
+        match self {
+            #[cfg(all(
+                feature = "unix-transport",
+                any(target_os = "android", target_os = "linux")
+            ))]
+            Address::Unix(p) if p.as_bytes().first() == Some(&0) => {
+                let name = &p.as_bytes()[1..];
+                let listener = abstract_socket(name, |fd, addr| {
+                    nix::sys::socket::bind(fd, addr)?;
+                    nix::sys::socket::listen(fd, 128)
+                })?;
+                let address = format!("unix:abstract={}", String::from_utf8_lossy(name));
 
-                Self::from_str(&path)
+                Ok((listener, address, None))
             }
-        }
-    }
+            #[cfg(feature = "unix-transport")]
+            Address::Unix(p) => {
+                let listener = UnixListener::bind(p)?;
+                let address = format!("unix:path={}", p.to_string_lossy());
 
-    /// Get the address for system bus respecting the DBUS_SYSTEM_BUS_ADDRESS environment
-    /// variable. If we don't recognize the value (or it's not set) we fall back to
-    /// /var/run/dbus/system_bus_socket
-    pub(crate) fn system() -> Result<Self> {
-        match env::var("DBUS_SYSTEM_BUS_ADDRESS") {
-            Ok(val) => Self::from_str(&val),
-            _ => Self::from_str("unix:path=/var/run/dbus/system_bus_socket"),
+                Ok((listener, address, Some(p.into())))
+            }
+            #[cfg(feature = "unix-transport")]
+            Address::UnixTmpDir(dir) if cfg!(any(target_os = "android", target_os = "linux")) => {
+                let mut name = OsString::from("\0");
+                name.push(random_socket_path(dir));
+
+                Address::Unix(name).listen()
+            }
+            #[cfg(feature = "unix-transport")]
+            Address::UnixDir(dir) | Address::UnixTmpDir(dir) => {
+                Address::Unix(random_socket_path(dir).into()).listen()
+            }
+            #[cfg(feature = "tcp-transport")]
+            Address::Tcp { .. } => Err(Error::Address(
+                "`tcp` addresses can only be connected to, not listened on".into(),
+            )),
+            #[cfg(feature = "unixexec-transport")]
+            Address::Unixexec { .. } => Err(Error::Address(
+                "`unixexec` addresses can only be connected to, not listened on".into(),
+            )),
+            #[cfg(feature = "launchd")]
+            Address::Launchd { .. } => Err(Error::Address(
+                "`launchd` addresses can only be connected to, not listened on".into(),
+            )),
         }
     }
 
     // Helper for FromStr
-    fn from_unix(opts: HashMap<&str, &str>) -> Result<Self> {
-        let path = if let Some(abs) = opts.get("abstract") {
-            if opts.get("path").is_some() {
-                return Err(Error::Address(
-                    "`path` and `abstract` cannot be specified together".into(),
-                ));
-            }
+    #[cfg(feature = "unix-transport")]
+    fn from_unix(opts: HashMap<&str, OsString>) -> Result<Self> {
+        let kinds = ["path", "abstract", "dir", "tmpdir"];
+        if kinds.iter().filter(|k| opts.contains_key(*k)).count() > 1 {
+            let msg = if opts.contains_key("path") && opts.contains_key("abstract") {
+                "`path` and `abstract` cannot be specified together"
+            } else {
+                "only one of `path`, `abstract`, `dir` or `tmpdir` can be specified"
+            };
+
+            return Err(Error::Address(msg.into()));
+        }
+
+        let address = if let Some(abs) = opts.get("abstract") {
             let mut s = OsString::from("\0");
             s.push(abs);
-            s
+            Address::Unix(s)
         } else if let Some(path) = opts.get("path") {
-            OsString::from(path)
+            Address::Unix(path.clone())
+        } else if let Some(dir) = opts.get("dir") {
+            Address::UnixDir(dir.clone())
+        } else if let Some(dir) = opts.get("tmpdir") {
+            Address::UnixTmpDir(dir.clone())
         } else {
             return Err(Error::Address(
                 "unix address is missing path or abstract".to_owned(),
             ));
         };
 
-        Ok(Address::Unix(path))
+        Ok(address)
+    }
+
+    // Helper for FromStr
+    #[cfg(feature = "tcp-transport")]
+    fn from_tcp(opts: HashMap<&str, OsString>) -> Result<Self> {
+        let host = tcp_value(&opts, "host")?
+            .ok_or_else(|| Error::Address("tcp address is missing `host`".into()))?
+            .to_owned();
+        let port = tcp_value(&opts, "port")?
+            .ok_or_else(|| Error::Address("tcp address is missing `port`".into()))?;
+        let port = port
+            .parse()
+            .map_err(|_| Error::Address(format!("invalid tcp port `{}`", port)))?;
+        let family = match tcp_value(&opts, "family")? {
+            None => None,
+            Some("ipv4") => Some(TcpFamily::Ipv4),
+            Some("ipv6") => Some(TcpFamily::Ipv6),
+            Some(family) => {
+                return Err(Error::Address(format!(
+                    "invalid tcp address family `{}`",
+                    family
+                )))
+            }
+        };
+
+        Ok(Address::Tcp { host, port, family })
+    }
+
+    // Helper for FromStr
+    #[cfg(feature = "unixexec-transport")]
+    fn from_unixexec(mut opts: HashMap<&str, OsString>) -> Result<Self> {
+        let path = opts
+            .remove("path")
+            .ok_or_else(|| Error::Address("unixexec address is missing `path`".into()))?;
+        let argv0 = opts.remove("argv0");
+        // The arguments are `argv1`, `argv2`.. up to the first missing one.
+        let mut args = vec![];
+        while let Some(arg) = opts.remove(format!("argv{}", args.len() + 1).as_str()) {
+            args.push(arg);
+        }
+
+        Ok(Address::Unixexec { path, argv0, args })
+    }
+
+    // Helper for FromStr
+    #[cfg(feature = "launchd")]
+    fn from_launchd(mut opts: HashMap<&str, OsString>) -> Result<Self> {
+        let env = opts
+            .remove("env")
+            .ok_or_else(|| Error::Address("launchd address is missing `env`".into()))?;
+
+        Ok(Address::Launchd { env })
+    }
+}
+
+// The value of `key` in the options of a tcp address, which must be UTF-8.
+#[cfg(feature = "tcp-transport")]
+fn tcp_value<'o>(opts: &'o HashMap<&str, OsString>, key: &str) -> Result<Option<&'o str>> {
+    opts.get(key)
+        .map(|v| {
+            v.to_str()
+                .ok_or_else(|| Error::Address(format!("tcp address `{}` is not valid UTF-8", key)))
+        })
+        .transpose()
+}
+
+// A path for a new socket in `dir`, named like the reference implementation does.
+#[cfg(feature = "unix-transport")]
+fn random_socket_path(dir: &OsString) -> PathBuf {
+    let mut rng = thread_rng();
+    let suffix: String = iter::repeat(())
+        .map(|_| char::from(rng.sample(Alphanumeric)))
+        .take(10)
+        .collect();
+
+    PathBuf::from(dir).join(format!("dbus-{}", suffix))
+}
+
+// Create a unix stream socket and `setup` it for the abstract socket `name`.
+#[cfg(all(
+    feature = "unix-transport",
+    any(target_os = "android", target_os = "linux")
+))]
+fn abstract_socket<S, F>(name: &[u8], setup: F) -> Result<S>
+where
+    S: std::os::unix::io::FromRawFd,
+    F: FnOnce(std::os::unix::io::RawFd, &nix::sys::socket::SockAddr) -> nix::Result<()>,
+{
+    use nix::sys::socket::{socket, AddressFamily, SockAddr, SockFlag, SockType, UnixAddr};
+
+    let addr = SockAddr::Unix(UnixAddr::new_abstract(name)?);
+    let fd = socket(
+        AddressFamily::Unix,
+        SockType::Stream,
+        SockFlag::SOCK_CLOEXEC,
+        None,
+    )?;
+    if let Err(e) = setup(fd, &addr) {
+        let _ = nix::unistd::close(fd);
+
+        return Err(e.into());
     }
+
+    // SAFETY: `fd` is a valid socket that we own.
+    Ok(unsafe { S::from_raw_fd(fd) })
+}
+
+// Decode the `%XX` escapes of an address value.
+fn decode_percents(value: &str) -> Result<OsString> {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+    while let Some(b) = bytes.next() {
+        if b != b'%' {
+            decoded.push(b);
+
+            continue;
+        }
+
+        let hex: Vec<u8> = bytes.by_ref().take(2).collect();
+        if hex.len() != 2 || !hex.iter().all(u8::is_ascii_hexdigit) {
+            return Err(Error::Address(format!(
+                "invalid percent escape in `{}`",
+                value
+            )));
+        }
+        // Can't fail, as checked above.
+        let hex = std::str::from_utf8(&hex).expect("invalid hex digits");
+        decoded.push(u8::from_str_radix(hex, 16).expect("invalid hex digits"));
+    }
+
+    Ok(OsString::from_vec(decoded))
 }
 
 impl FromStr for Address {
@@ -94,7 +486,7 @@ impl FromStr for Address {
         let mut options = HashMap::new();
         for kv in address[col + 1..].split(',') {
             let (k, v) = match kv.find('=') {
-                Some(eq) => (&kv[..eq], &kv[eq + 1..]),
+                Some(eq) => (&kv[..eq], decode_percents(&kv[eq + 1..])?),
                 None => return Err(Error::Address("missing = when parsing key/value".into())),
             };
             if options.insert(k, v).is_some() {
@@ -106,7 +498,18 @@ impl FromStr for Address {
         }
 
         match transport {
+            #[cfg(feature = "unix-transport")]
             "unix" => Self::from_unix(options),
+            #[cfg(feature = "tcp-transport")]
+            "tcp" => Self::from_tcp(options),
+            #[cfg(feature = "unixexec-transport")]
+            "unixexec" => Self::from_unixexec(options),
+            #[cfg(feature = "launchd")]
+            "launchd" => Self::from_launchd(options),
+            _ if TRANSPORTS.contains(&transport) => Err(Error::Address(format!(
+                "transport '{}' disabled at compile time",
+                transport
+            ))),
             _ => Err(Error::Address(format!(
                 "unsupported transport '{}'",
                 transport
@@ -118,8 +521,18 @@ impl FromStr for Address {
 #[cfg(test)]
 mod tests {
     use super::Address;
+    #[cfg(feature = "unix-transport")]
+    use super::AddressList;
+    #[cfg(feature = "tcp-transport")]
+    use super::TcpFamily;
     use crate::Error;
+    #[cfg(feature = "unix-transport")]
+    use crate::{Connection, ConnectionBuilder, Listener};
+    #[cfg(feature = "unix-transport")]
+    use ntest::timeout;
     use std::str::FromStr;
+    #[cfg(feature = "unix-transport")]
+    use std::{env, thread};
     use test_env_log::test;
 
     #[test]
@@ -140,10 +553,19 @@ mod tests {
             Error::Address(e) => assert_eq!(e, "Key `opt` specified multiple times"),
             _ => panic!(),
         }
-        match Address::from_str("tcp:host=localhost").unwrap_err() {
-            Error::Address(e) => assert_eq!(e, "unsupported transport 'tcp'"),
+        match Address::from_str("nonce-tcp:host=localhost").unwrap_err() {
+            Error::Address(e) => assert_eq!(e, "unsupported transport 'nonce-tcp'"),
+            _ => panic!(),
+        }
+        match Address::from_str("foo:opt=%2").unwrap_err() {
+            Error::Address(e) => assert_eq!(e, "invalid percent escape in `%2`"),
             _ => panic!(),
         }
+    }
+
+    #[test]
+    #[cfg(feature = "unix-transport")]
+    fn parse_unix_addresses() {
         match Address::from_str("unix:foo=blah").unwrap_err() {
             Error::Address(e) => assert_eq!(e, "unix address is missing path or abstract"),
             _ => panic!(),
@@ -162,5 +584,242 @@ mod tests {
             Address::Unix("/tmp/dbus-foo".into()),
             Address::from_str("unix:path=/tmp/dbus-foo,guid=123").unwrap()
         );
+        match Address::from_str("unix:dir=/tmp,tmpdir=/tmp").unwrap_err() {
+            Error::Address(e) => assert_eq!(
+                e,
+                "only one of `path`, `abstract`, `dir` or `tmpdir` can be specified"
+            ),
+            _ => panic!(),
+        }
+        assert_eq!(
+            Address::UnixDir("/tmp".into()),
+            Address::from_str("unix:dir=/tmp").unwrap()
+        );
+        assert_eq!(
+            Address::UnixTmpDir("/tmp".into()),
+            Address::from_str("unix:tmpdir=/tmp").unwrap()
+        );
+        assert_eq!(
+            Address::Unix("/tmp/dbus foo,bar;".into()),
+            Address::from_str("unix:path=/tmp/dbus%20foo%2cbar%3B").unwrap()
+        );
+        match Address::from_str("unix:path=/tmp/dbus%2").unwrap_err() {
+            Error::Address(e) => assert_eq!(e, "invalid percent escape in `/tmp/dbus%2`"),
+            _ => panic!(),
+        }
+        match Address::from_str("unix:path=/tmp/dbus%+1").unwrap_err() {
+            Error::Address(e) => assert_eq!(e, "invalid percent escape in `/tmp/dbus%+1`"),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "tcp-transport")]
+    fn parse_tcp_addresses() {
+        assert_eq!(
+            Address::Tcp {
+                host: "localhost".into(),
+                port: 4142,
+                family: None
+            },
+            Address::from_str("tcp:host=localhost,port=4142").unwrap()
+        );
+        assert_eq!(
+            Address::Tcp {
+                host: "::1".into(),
+                port: 4142,
+                family: Some(TcpFamily::Ipv6)
+            },
+            Address::from_str("tcp:host=::1,port=4142,family=ipv6").unwrap()
+        );
+        match Address::from_str("tcp:host=localhost").unwrap_err() {
+            Error::Address(e) => assert_eq!(e, "tcp address is missing `port`"),
+            _ => panic!(),
+        }
+        match Address::from_str("tcp:host=localhost,port=65536").unwrap_err() {
+            Error::Address(e) => assert_eq!(e, "invalid tcp port `65536`"),
+            _ => panic!(),
+        }
+        match Address::from_str("tcp:host=localhost,port=4142,family=unix").unwrap_err() {
+            Error::Address(e) => assert_eq!(e, "invalid tcp address family `unix`"),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "unixexec-transport")]
+    fn parse_unixexec_addresses() {
+        assert_eq!(
+            Address::Unixexec {
+                path: "/usr/bin/ssh".into(),
+                argv0: Some("ssh".into()),
+                args: vec!["-xT".into(), "host name".into()],
+            },
+            Address::from_str("unixexec:path=/usr/bin/ssh,argv0=ssh,argv1=-xT,argv2=host%20name")
+                .unwrap()
+        );
+        // Arguments after a gap are ignored.
+        assert_eq!(
+            Address::Unixexec {
+                path: "proxy".into(),
+                argv0: None,
+                args: vec!["a".into()],
+            },
+            Address::from_str("unixexec:path=proxy,argv1=a,argv3=c").unwrap()
+        );
+        match Address::from_str("unixexec:argv0=ssh").unwrap_err() {
+            Error::Address(e) => assert_eq!(e, "unixexec address is missing `path`"),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "launchd")]
+    fn parse_launchd_addresses() {
+        assert_eq!(
+            Address::Launchd {
+                env: "DBUS_LAUNCHD_SESSION_BUS_SOCKET".into()
+            },
+            Address::from_str("launchd:env=DBUS_LAUNCHD_SESSION_BUS_SOCKET").unwrap()
+        );
+        match Address::from_str("launchd:path=/tmp").unwrap_err() {
+            Error::Address(e) => assert_eq!(e, "launchd address is missing `env`"),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn disabled_transports() {
+        let transports = [
+            (cfg!(feature = "unix-transport"), "unix:path=/tmp/dbus-foo"),
+            (
+                cfg!(feature = "tcp-transport"),
+                "tcp:host=localhost,port=4142",
+            ),
+            (
+                cfg!(feature = "unixexec-transport"),
+                "unixexec:path=/usr/bin/ssh",
+            ),
+            (
+                cfg!(feature = "launchd"),
+                "launchd:env=DBUS_LAUNCHD_SESSION_BUS_SOCKET",
+            ),
+        ];
+        for (_, address) in transports.iter().filter(|(enabled, _)| !enabled) {
+            let transport = &address[..address.find(':').unwrap()];
+            match Address::from_str(address).unwrap_err() {
+                Error::Address(e) => assert_eq!(
+                    e,
+                    format!("transport '{}' disabled at compile time", transport)
+                ),
+                e => panic!("unexpected error: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "unix-transport")]
+    fn parse_dbus_address_lists() {
+        match AddressList::from_str(";").unwrap_err() {
+            Error::Address(e) => assert_eq!(e, "address list is empty"),
+            _ => panic!(),
+        }
+
+        let list = AddressList::from_str(
+            "unix:path=/tmp/dbus%3bfoo;nonce-tcp:host=localhost;unix:abstract=%2Fbar,guid=123;",
+        )
+        .unwrap();
+        let list: Vec<_> = list.into_iter().collect();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0].0, "unix:path=/tmp/dbus%3bfoo");
+        assert_eq!(
+            list[0].1.as_ref().unwrap(),
+            &Address::Unix("/tmp/dbus;foo".into())
+        );
+        assert_eq!(list[1].0, "nonce-tcp:host=localhost");
+        match list[1].1.as_ref().unwrap_err() {
+            Error::Address(e) => assert_eq!(e, "unsupported transport 'nonce-tcp'"),
+            _ => panic!(),
+        }
+        assert_eq!(list[2].1.as_ref().unwrap(), &Address::Unix("\0/bar".into()));
+    }
+
+    #[test]
+    #[timeout(15000)]
+    #[cfg(feature = "unix-transport")]
+    fn connect_address_list() {
+        let dir = std::env::temp_dir().join(format!("zbus-address-list-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let listener = Listener::bind(&format!("unix:dir={}", dir.display())).unwrap();
+        let bad = format!("unix:path={}/nonexistent", dir.display());
+        let good = listener.address().to_string();
+
+        let server_thread = thread::spawn(move || listener.accept().unwrap());
+        // The first address is stale, and the second one is unsupported.
+        let addresses = format!("{};nonce-tcp:host=localhost,port=1;{}", bad, good);
+        let conn = Connection::new_for_address(&addresses, false).unwrap();
+        assert_eq!(conn.address(), Some(good.as_str()));
+        drop(server_thread.join().unwrap());
+
+        // The errors of all the addresses are reported.
+        match Connection::new_for_address(&format!("{};nonce-tcp:host=localhost", bad), false) {
+            Err(Error::Address(e)) => {
+                let prefix = format!("failed to connect to any of the addresses: `{}`: ", bad);
+                assert!(e.starts_with(&prefix), "{}", e);
+                assert!(
+                    e.ends_with(
+                        "; `nonce-tcp:host=localhost`: address error: unsupported transport 'nonce-tcp'"
+                    ),
+                    "{}",
+                    e
+                );
+            }
+            res => panic!("unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[timeout(15000)]
+    #[cfg(feature = "unix-transport")]
+    fn starter() {
+        let dir = env::temp_dir().join(format!("zbus-starter-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let listener = Listener::bind(&format!("unix:dir={}", dir.display())).unwrap();
+        let address = listener.address().to_string();
+
+        let server_thread = thread::spawn(move || listener.accept().unwrap());
+        // The address takes precedence over the bus type.
+        env::set_var("DBUS_STARTER_ADDRESS", &address);
+        env::set_var("DBUS_STARTER_BUS_TYPE", "system");
+        let conn = ConnectionBuilder::starter().p2p().build().unwrap();
+        assert_eq!(conn.address(), Some(address.as_str()));
+        drop(server_thread.join().unwrap());
+
+        let addresses = |list: AddressList| list.into_iter().map(|(s, _)| s).collect::<Vec<_>>();
+        env::remove_var("DBUS_STARTER_ADDRESS");
+        assert_eq!(
+            addresses(AddressList::starter().unwrap()),
+            addresses(AddressList::system().unwrap())
+        );
+        env::set_var("DBUS_STARTER_BUS_TYPE", "session");
+        assert_eq!(
+            addresses(AddressList::starter().unwrap()),
+            addresses(AddressList::session().unwrap())
+        );
+
+        env::set_var("DBUS_STARTER_BUS_TYPE", "user");
+        match AddressList::starter().unwrap_err() {
+            Error::Address(e) => assert_eq!(e, "unknown starter bus type `user`"),
+            e => panic!("unexpected error: {}", e),
+        }
+        env::remove_var("DBUS_STARTER_BUS_TYPE");
+        match Connection::new_starter().map(|_| ()).unwrap_err() {
+            Error::Address(e) => assert!(e.starts_with("no starter bus"), "{}", e),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,40 @@
+use futures_core::future::BoxFuture;
+
+use crate::Result;
+
+/// Asynchronous cleanup of resources tied to a connection.
+///
+/// Signal streams, and proxies with connected signal handlers, hold signal subscriptions on their
+/// connection, along with their match rules on the bus. Removing those takes a method call to the
+/// bus, which `Drop` has no way to wait for without blocking. Blocking in `Drop` is not an option:
+/// these types are often dropped from async tasks, where it would stall (or deadlock) the
+/// executor, or from the worker threads of runtimes like tokio, which panic on nested
+/// `block_on` calls. Hence, dropping them never blocks: the cleanup is queued onto the
+/// [connection executor] instead, and any error from it is ignored.
+///
+/// When the `internal-executor` feature is disabled, the queued cleanup only runs once the
+/// connection executor gets to tick. Call `async_drop` instead, to do the cleanup right away and
+/// know how it went:
+///
+/// ```no_run
+///# use std::error::Error;
+///# async_io::block_on(async {
+/// use zbus::{azync::Connection, fdo::AsyncDBusProxy, AsyncDrop};
+///
+/// let conn = Connection::new_session().await?;
+/// let proxy = AsyncDBusProxy::new(&conn)?;
+/// let stream = proxy.receive_name_owner_changed().await?;
+///
+/// // The match rule of the stream is removed from the bus once this returns.
+/// stream.async_drop().await?;
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+///# });
+/// ```
+///
+/// [connection executor]: azync/struct.Connection.html#method.executor
+pub trait AsyncDrop {
+    /// Release the resources held by `self`, waiting for it to complete.
+    fn async_drop<'d>(self) -> BoxFuture<'d, Result<()>>
+    where
+        Self: 'd;
+}
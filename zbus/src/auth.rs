@@ -0,0 +1,645 @@
+use std::{fmt, path::PathBuf};
+
+use nix::unistd::Uid;
+
+use crate::{
+    keyring::{Keyring, DEFAULT_CONTEXT},
+    utils::random,
+    Error, Result,
+};
+
+/// The answer of an [`AuthMechanism`] to its peer.
+///
+/// [`AuthMechanism`]: trait.AuthMechanism.html
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuthResponse {
+    /// Send this data to the peer.
+    ///
+    /// The first response of a client is sent along with the `AUTH` command, as its initial
+    /// response. Any other is sent in a `DATA` command.
+    Data(Vec<u8>),
+    /// Go on without any data.
+    ///
+    /// A server accepts the client with this. A client sends an `AUTH` command without initial
+    /// response, or an empty `DATA` command.
+    Ok,
+    /// Give up on the authentication with this mechanism.
+    ///
+    /// A server rejects the client with this, which is then free to try another mechanism. A
+    /// client cancels the authentication, and goes on with its next mechanism, if any.
+    Reject,
+}
+
+/// A [SASL mechanism] of the authentication of connections.
+///
+/// Besides the built-in `EXTERNAL` and `DBUS_COOKIE_SHA1` mechanisms, clients can use their own
+/// ones through [`ConnectionBuilder::auth_mechanism`]. The same goes for the server side of
+/// peer-to-peer connections, which only supports `EXTERNAL` otherwise, or `DBUS_COOKIE_SHA1` over
+/// TCP. On both sides, the custom mechanisms come before the built-in ones.
+///
+/// The handshake is driven step by step, as the socket gets ready, so the mechanisms answer
+/// synchronously. They shouldn't block for long.
+///
+/// # Example
+///
+/// A client proving it knows a shared secret, in the clear:
+///
+/// ```
+/// use zbus::{AuthMechanism, AuthResponse};
+///
+/// #[derive(Debug)]
+/// struct Secret(Vec<u8>);
+///
+/// impl AuthMechanism for Secret {
+///     fn name(&self) -> &str {
+///         "X_SECRET"
+///     }
+///
+///     fn challenge(&mut self, data: Option<&[u8]>) -> AuthResponse {
+///         match data {
+///             // The initial response of the `AUTH` command.
+///             None => AuthResponse::Data(self.0.clone()),
+///             // The server wouldn't ask for more.
+///             Some(_) => AuthResponse::Reject,
+///         }
+///     }
+/// }
+/// ```
+///
+/// [SASL mechanism]: https://dbus.freedesktop.org/doc/dbus-specification.html#auth-mechanisms
+/// [`ConnectionBuilder::auth_mechanism`]: struct.ConnectionBuilder.html#method.auth_mechanism
+pub trait AuthMechanism: fmt::Debug + Send + Sync {
+    /// The name of the mechanism, as sent in the `AUTH` and `REJECTED` commands.
+    fn name(&self) -> &str;
+
+    /// Answer the peer.
+    ///
+    /// A client is first called with `None` when starting the authentication with this
+    /// mechanism, then with the data of each `DATA` command of the server.
+    ///
+    /// A server is called with the initial response of the `AUTH` command of the client (`None`
+    /// if there's none), then with the data of each `DATA` command of the client. Clients may
+    /// start over with a new `AUTH` command after a rejection, so mechanisms keeping state between
+    /// the calls should be ready for that.
+    fn challenge(&mut self, data: Option<&[u8]>) -> AuthResponse;
+
+    /// Why the mechanism answered [`AuthResponse::Reject`], if it knows.
+    ///
+    /// When all its mechanisms are rejected, a client fails with the first error given here, if
+    /// any, rather than a generic [`Error::Handshake`]. This returns `None` by default.
+    ///
+    /// [`AuthResponse::Reject`]: enum.AuthResponse.html#variant.Reject
+    /// [`Error::Handshake`]: enum.Error.html#variant.Handshake
+    fn take_error(&mut self) -> Option<Error> {
+        None
+    }
+}
+
+/// The mechanisms of clients, `custom` ones first.
+pub(crate) fn client_mechanisms(
+    custom: Vec<Box<dyn AuthMechanism>>,
+) -> Vec<Box<dyn AuthMechanism>> {
+    let mut mechanisms = custom;
+    mechanisms.push(Box::new(External::client()));
+    mechanisms.push(Box::new(CookieSha1::client()));
+
+    mechanisms
+}
+
+/// The mechanisms of servers, `custom` ones first.
+///
+/// With the user ID of the peer, clients of that user are accepted through `EXTERNAL`. Without,
+/// as the socket doesn't pass credentials, clients of the same user are accepted through
+/// `DBUS_COOKIE_SHA1`.
+pub(crate) fn server_mechanisms(
+    custom: Vec<Box<dyn AuthMechanism>>,
+    client_uid: Option<u32>,
+) -> Vec<Box<dyn AuthMechanism>> {
+    let mut mechanisms = custom;
+    match client_uid {
+        Some(client_uid) => mechanisms.push(Box::new(External::server(client_uid))),
+        None => mechanisms.push(Box::new(CookieSha1::server())),
+    }
+
+    mechanisms
+}
+
+// The `EXTERNAL` mechanism, with the credentials passed along the socket.
+//
+// The client only tells its user ID, which the server checks against that of the peer.
+#[derive(Debug)]
+pub(crate) struct External {
+    // The user ID of the peer, server-side.
+    client_uid: Option<u32>,
+}
+
+impl External {
+    pub(crate) fn client() -> Self {
+        Self { client_uid: None }
+    }
+
+    pub(crate) fn server(client_uid: u32) -> Self {
+        Self {
+            client_uid: Some(client_uid),
+        }
+    }
+}
+
+impl AuthMechanism for External {
+    fn name(&self) -> &str {
+        "EXTERNAL"
+    }
+
+    fn challenge(&mut self, data: Option<&[u8]>) -> AuthResponse {
+        match (self.client_uid, data) {
+            (None, None) => AuthResponse::Data(Uid::current().to_string().into()),
+            (Some(client_uid), Some(data)) => {
+                let uid = std::str::from_utf8(data)
+                    .ok()
+                    .and_then(|uid| uid.parse::<u32>().ok());
+                if uid == Some(client_uid) {
+                    AuthResponse::Ok
+                } else {
+                    AuthResponse::Reject
+                }
+            }
+            _ => AuthResponse::Reject,
+        }
+    }
+}
+
+/// The `ANONYMOUS` mechanism, for servers that don't care about who their clients are.
+///
+/// It isn't used unless registered, through [`ConnectionBuilder::auth_mechanism`], on either
+/// side.
+///
+/// [`ConnectionBuilder::auth_mechanism`]: struct.ConnectionBuilder.html#method.auth_mechanism
+#[derive(Debug)]
+pub struct Anonymous;
+
+impl AuthMechanism for Anonymous {
+    fn name(&self) -> &str {
+        "ANONYMOUS"
+    }
+
+    // The client sends a trace of who it is, which the server ignores.
+    fn challenge(&mut self, data: Option<&[u8]>) -> AuthResponse {
+        match data {
+            None => AuthResponse::Data(b"zbus".to_vec()),
+            Some(_) => AuthResponse::Ok,
+        }
+    }
+}
+
+/// The `DBUS_COOKIE_SHA1` mechanism, for peers of the same user sharing a home directory.
+///
+/// The server keeps secret cookies in a keyring of its user, `~/.dbus-keyrings` by default, and
+/// challenges the client to prove it can read them. Unlike `EXTERNAL`, this doesn't rely on the
+/// socket passing credentials, so this is the mechanism of connections over TCP.
+///
+/// Clients try it after `EXTERNAL` by default, and the server side of connections over TCP
+/// accepts it. Registering one through [`ConnectionBuilder::auth_mechanism`] gives it another
+/// keyring directory, e.g one shared over the network:
+///
+/// ```no_run
+///# use std::error::Error;
+///#
+/// use zbus::{ConnectionBuilder, CookieSha1};
+///
+/// let conn = ConnectionBuilder::address("tcp:host=192.168.1.2,port=4242")
+///     .auth_mechanism(CookieSha1::client().keyring_dir("/mnt/home/user/.dbus-keyrings"))
+///     .build()?;
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+///
+/// The keyring directory is ignored if other users can read or write it. On the server side, it
+/// is created if missing, and cookies are added to and expired from it as needed, as the reference
+/// implementation does. Failing clients get a [`CookieError`] telling why.
+///
+/// [`ConnectionBuilder::auth_mechanism`]: struct.ConnectionBuilder.html#method.auth_mechanism
+/// [`CookieError`]: enum.CookieError.html
+#[derive(Debug)]
+pub struct CookieSha1 {
+    keyring_dir: Option<PathBuf>,
+    side: CookieSide,
+    error: Option<Error>,
+}
+
+#[derive(Debug)]
+enum CookieSide {
+    Client,
+    // With the server challenge and the cookie of the ongoing authentication.
+    Server(Option<(String, String)>),
+}
+
+impl CookieSha1 {
+    /// The client side of the mechanism.
+    pub fn client() -> Self {
+        Self {
+            keyring_dir: None,
+            side: CookieSide::Client,
+            error: None,
+        }
+    }
+
+    /// The server side of the mechanism, accepting clients of the same user.
+    pub fn server() -> Self {
+        Self {
+            keyring_dir: None,
+            side: CookieSide::Server(None),
+            error: None,
+        }
+    }
+
+    /// Use the keyrings of `dir`, rather than `~/.dbus-keyrings`.
+    pub fn keyring_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.keyring_dir = Some(dir.into());
+        self
+    }
+
+    fn keyring(&self, context: &str) -> Result<Keyring> {
+        Keyring::new(self.keyring_dir.as_deref(), context)
+    }
+
+    // Answer the `context id server_challenge` data of the server.
+    fn client_answer(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let data = std::str::from_utf8(data)
+            .map_err(|_| Error::Handshake("Invalid cookie challenge".into()))?;
+        let mut split = data.split_ascii_whitespace();
+        let context = split
+            .next()
+            .ok_or_else(|| Error::Handshake("Missing cookie context name".into()))?;
+        let id = split
+            .next()
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| Error::Handshake("Missing cookie ID".into()))?;
+        let server_challenge = split
+            .next()
+            .ok_or_else(|| Error::Handshake("Missing cookie challenge".into()))?;
+
+        let cookie = self.keyring(context)?.lookup(id)?;
+        let client_challenge = random_hex(16);
+        let sha1 = cookie_sha1(server_challenge, &client_challenge, &cookie);
+
+        Ok(format!("{} {}", client_challenge, sha1).into())
+    }
+
+    // Challenge the client claiming to be the user `uid`.
+    fn server_challenge(&self, uid: &[u8]) -> Result<(String, String, Vec<u8>)> {
+        let uid = std::str::from_utf8(uid)
+            .ok()
+            .and_then(|uid| uid.parse::<u32>().ok());
+        if uid != Some(Uid::current().as_raw()) {
+            return Err(Error::Handshake("Cookie client of another user".into()));
+        }
+
+        let cookie = self.keyring(DEFAULT_CONTEXT)?.current_cookie()?;
+        let challenge = random_hex(16);
+        let data = format!("{} {} {}", DEFAULT_CONTEXT, cookie.id, challenge);
+
+        Ok((challenge, cookie.secret, data.into()))
+    }
+}
+
+impl AuthMechanism for CookieSha1 {
+    fn name(&self) -> &str {
+        "DBUS_COOKIE_SHA1"
+    }
+
+    fn challenge(&mut self, data: Option<&[u8]>) -> AuthResponse {
+        let pending = match &mut self.side {
+            CookieSide::Client => {
+                return match data {
+                    None => AuthResponse::Data(Uid::current().to_string().into()),
+                    // Without any cookie to answer with, the next mechanism is our best bet.
+                    Some(data) => match self.client_answer(data) {
+                        Ok(answer) => AuthResponse::Data(answer),
+                        Err(e) => {
+                            self.error.get_or_insert(e);
+
+                            AuthResponse::Reject
+                        }
+                    },
+                };
+            }
+            CookieSide::Server(pending) => pending.take(),
+        };
+
+        match (data, pending) {
+            // Ask for the initial response.
+            (None, _) => AuthResponse::Data(vec![]),
+            // The `client_challenge sha1` answer to our challenge.
+            (Some(data), Some((server_challenge, cookie))) if data.contains(&b' ') => {
+                let answer = String::from_utf8_lossy(data);
+                let mut split = answer.split(' ');
+                let accepted = match (split.next(), split.next(), split.next()) {
+                    (Some(client_challenge), Some(sha1), None) => {
+                        !client_challenge.is_empty()
+                            && constant_time_eq(
+                                sha1.as_bytes(),
+                                cookie_sha1(&server_challenge, client_challenge, &cookie)
+                                    .as_bytes(),
+                            )
+                    }
+                    _ => false,
+                };
+
+                if accepted {
+                    AuthResponse::Ok
+                } else {
+                    AuthResponse::Reject
+                }
+            }
+            // The user of a new client.
+            (Some(uid), _) => match self.server_challenge(uid) {
+                Ok((server_challenge, cookie, data)) => {
+                    self.side = CookieSide::Server(Some((server_challenge, cookie)));
+
+                    AuthResponse::Data(data)
+                }
+                Err(e) => {
+                    self.error.get_or_insert(e);
+
+                    AuthResponse::Reject
+                }
+            },
+        }
+    }
+
+    fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+}
+
+// The proof of knowing `cookie`, for the given challenges.
+fn cookie_sha1(server_challenge: &str, client_challenge: &str, cookie: &str) -> String {
+    let sec = format!("{}:{}:{}", server_challenge, client_challenge, cookie);
+
+    sha1::Sha1::from(sec).hexdigest()
+}
+
+// Whether `a` and `b` are equal, in a time that doesn't depend on where they differ, so that
+// clients can't guess the expected answer byte by byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn random_hex(len: usize) -> String {
+    let bytes: Vec<u8> = std::iter::repeat_with(random::<u8>).take(len).collect();
+
+    hex::encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::unix::net::UnixStream,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    use ntest::timeout;
+    use test_env_log::test;
+
+    use super::*;
+    use crate::{ConnectionBuilder, CookieError, Guid};
+
+    const MECHANISM: &str = "X_ZBUS_TOY";
+
+    // The proof of knowing `secret`, for the challenge `nonce`.
+    fn proof(nonce: &[u8], secret: &str) -> Vec<u8> {
+        let sec = format!("{}:{}", String::from_utf8_lossy(nonce), secret);
+
+        sha1::Sha1::from(sec).hexdigest().into()
+    }
+
+    // A toy challenge-response mechanism: the server sends a nonce, to be hashed with a shared
+    // secret by the client.
+    #[derive(Debug)]
+    struct ToyClient {
+        secret: &'static str,
+    }
+
+    impl AuthMechanism for ToyClient {
+        fn name(&self) -> &str {
+            MECHANISM
+        }
+
+        fn challenge(&mut self, data: Option<&[u8]>) -> AuthResponse {
+            match data {
+                None => AuthResponse::Ok,
+                Some(nonce) => AuthResponse::Data(proof(nonce, self.secret)),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct ToyServer {
+        secret: &'static str,
+        nonce: Option<String>,
+        accepted: Arc<AtomicBool>,
+    }
+
+    impl AuthMechanism for ToyServer {
+        fn name(&self) -> &str {
+            MECHANISM
+        }
+
+        fn challenge(&mut self, data: Option<&[u8]>) -> AuthResponse {
+            match (data, self.nonce.take()) {
+                (None, _) => {
+                    let nonce = random_hex(16);
+                    self.nonce = Some(nonce.clone());
+
+                    AuthResponse::Data(nonce.into())
+                }
+                (Some(data), Some(nonce)) if data == &proof(nonce.as_bytes(), self.secret)[..] => {
+                    self.accepted.store(true, Ordering::SeqCst);
+
+                    AuthResponse::Ok
+                }
+                _ => AuthResponse::Reject,
+            }
+        }
+    }
+
+    // Authenticate a client knowing `client_secret` to a server knowing "sesame", returning
+    // whether the toy mechanism was the one accepting the client.
+    fn authenticate(client_secret: &'static str) -> bool {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let accepted = Arc::new(AtomicBool::new(false));
+
+        let server = ToyServer {
+            secret: "sesame",
+            nonce: None,
+            accepted: accepted.clone(),
+        };
+        let server_thread = thread::spawn(move || {
+            let conn = ConnectionBuilder::unix_stream(p1)
+                .server(&Guid::generate())
+                .auth_mechanism(server)
+                .build()
+                .unwrap();
+            let msg = conn.receive_message().unwrap();
+            assert_eq!(msg.header().unwrap().member().unwrap(), Some("Hello"));
+        });
+
+        let client = ToyClient {
+            secret: client_secret,
+        };
+        let conn = ConnectionBuilder::unix_stream(p0)
+            .p2p()
+            .auth_mechanism(client)
+            .build()
+            .unwrap();
+        conn.emit_signal(None, "/", "org.zbus.Auth", "Hello", &())
+            .unwrap();
+        server_thread.join().unwrap();
+
+        accepted.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn custom_mechanism() {
+        assert!(authenticate("sesame"));
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn custom_mechanism_fallback() {
+        // Once rejected, the client goes on with `EXTERNAL`.
+        assert!(!authenticate("open"));
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn anonymous() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let server_thread = thread::spawn(move || {
+            ConnectionBuilder::unix_stream(p1)
+                .server(&Guid::generate())
+                .auth_mechanism(Anonymous)
+                .build()
+                .unwrap()
+        });
+
+        ConnectionBuilder::unix_stream(p0)
+            .p2p()
+            .auth_mechanism(Anonymous)
+            .build()
+            .unwrap();
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    fn cookie_sha1_math() {
+        // `sha1("server_challenge:client_challenge:cookie")`, in lowercase hex.
+        assert_eq!(
+            cookie_sha1(
+                "4bd63a8a7ba3d6b7eba9d74f8a4fa1ef",
+                "a5c13f02a3b6ec3ad32b9e9f5e4d5e6f",
+                "9e5a4d3bd3b6d3a1ec6b5b7c8e0f2a1d3c5e7f9a0b1c2d3e",
+            ),
+            "0a399445cedcbe42b0f432880d139502be947600"
+        );
+        assert_eq!(
+            cookie_sha1("61bd0c7e", "1f", "00"),
+            "b43d09ff50036fecb6e25f07d174a0780f5bc41d"
+        );
+    }
+
+    #[test]
+    fn constant_time_eq() {
+        assert!(super::constant_time_eq(b"", b""));
+        assert!(super::constant_time_eq(b"b43d09ff", b"b43d09ff"));
+        assert!(!super::constant_time_eq(b"b43d09ff", b"b43d09fe"));
+        assert!(!super::constant_time_eq(b"b43d09ff", b"c43d09ff"));
+        assert!(!super::constant_time_eq(b"b43d09ff", b"b43d09f"));
+    }
+
+    fn keyring_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("zbus-cookie-sha1-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        dir
+    }
+
+    #[test]
+    fn cookie_sha1() {
+        let dir = keyring_dir("mechanism");
+        let mut client = CookieSha1::client().keyring_dir(&dir);
+        let mut server = CookieSha1::server().keyring_dir(&dir);
+
+        let uid = match client.challenge(None) {
+            AuthResponse::Data(uid) => uid,
+            response => panic!("unexpected response: {:?}", response),
+        };
+        let challenge = match server.challenge(Some(&uid)) {
+            AuthResponse::Data(challenge) => challenge,
+            response => panic!("unexpected response: {:?}", response),
+        };
+        assert!(challenge.starts_with(b"org_freedesktop_general "));
+        let answer = match client.challenge(Some(&challenge)) {
+            AuthResponse::Data(answer) => answer,
+            response => panic!("unexpected response: {:?}", response),
+        };
+        assert_eq!(server.challenge(Some(&answer)), AuthResponse::Ok);
+
+        // A replayed answer doesn't match a new challenge.
+        assert!(matches!(
+            server.challenge(Some(&uid)),
+            AuthResponse::Data(_)
+        ));
+        assert_eq!(server.challenge(Some(&answer)), AuthResponse::Reject);
+
+        // Nor are clients of other users accepted.
+        let other = (Uid::current().as_raw() + 1).to_string();
+        assert_eq!(
+            server.challenge(Some(other.as_bytes())),
+            AuthResponse::Reject
+        );
+        assert!(server.take_error().is_some());
+
+        // The cookie isn't there anymore.
+        std::fs::remove_file(dir.join("org_freedesktop_general")).unwrap();
+        assert_eq!(client.challenge(Some(&challenge)), AuthResponse::Reject);
+        match client.take_error() {
+            Some(Error::Cookie(CookieError::NotFound { context, .. })) => {
+                assert_eq!(context, "org_freedesktop_general")
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // Others could have read it.
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(client.challenge(Some(&challenge)), AuthResponse::Reject);
+        match client.take_error() {
+            Some(Error::Cookie(CookieError::PermissionsTooOpen(path))) => assert_eq!(path, dir),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        assert_eq!(server.challenge(Some(&uid)), AuthResponse::Reject);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn external() {
+        let uid = Uid::current().as_raw();
+        let mut server = External::server(uid);
+        match External::client().challenge(None) {
+            AuthResponse::Data(data) => assert_eq!(server.challenge(Some(&data)), AuthResponse::Ok),
+            response => panic!("unexpected response: {:?}", response),
+        }
+
+        let mut server = External::server(uid + 1);
+        let response = server.challenge(Some(uid.to_string().as_bytes()));
+        assert_eq!(response, AuthResponse::Reject);
+        assert_eq!(server.challenge(None), AuthResponse::Reject);
+    }
+}
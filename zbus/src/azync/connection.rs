@@ -1,4 +1,3 @@
-use async_broadcast::{broadcast, InactiveReceiver, Sender as Broadcaster};
 use async_channel::{bounded, Receiver, Sender};
 use async_executor::Executor;
 #[cfg(feature = "internal-executor")]
@@ -6,10 +5,13 @@ use async_io::block_on;
 use async_io::Async;
 use async_lock::{Mutex, MutexGuard};
 use async_task::Task;
-use once_cell::sync::OnceCell;
+use event_listener::Event;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::de::DeserializeOwned;
 use static_assertions::assert_impl_all;
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
+    any::Any,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
     convert::TryInto,
     future::ready,
     hash::{Hash, Hasher},
@@ -18,30 +20,57 @@ use std::{
         io::{AsRawFd, RawFd},
         net::UnixStream,
     },
+    panic::AssertUnwindSafe,
     pin::Pin,
     sync::{
         self,
-        atomic::{AtomicU32, Ordering::SeqCst},
-        Arc,
+        atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering::SeqCst},
+        mpsc, Arc, Weak,
     },
     task::{Context, Poll},
+    time::{Duration, Instant},
 };
-use zvariant::ObjectPath;
+use zvariant::{ObjectPath, Type, Value};
 
-use futures_core::{stream, Future};
+use futures_core::{future::BoxFuture, stream, Future};
 use futures_util::{
+    future::{poll_fn, FutureExt},
     sink::SinkExt,
     stream::{select as stream_select, StreamExt},
 };
 
+use super::fanout::{Fanout, OverflowPolicy, Queue};
+#[cfg(any(test, feature = "test-util"))]
+use crate::test_util::SerialAllocator;
 use crate::{
-    azync::Authenticated,
-    fdo,
+    azync::{AsyncSocket, Authenticated},
+    credentials::CredentialsCache,
+    fdo, handshake,
+    in_memory::{self, InMemoryOptions},
+    logging,
+    peer_stats::PeerStatsTracker,
     raw::{Connection as RawConnection, Socket},
-    Error, Guid, Message, MessageError, MessageType, Result,
+    utils::parse_args,
+    AsyncDrop, ConnectionCredentials, EndianSig, Error, Guid, Message, MessageBuilder,
+    MessageError, MessageFlags, MessageType, PeerStats, Priority, RawBody, Result,
+    NATIVE_ENDIAN_SIG,
 };
 
 const DEFAULT_MAX_QUEUED: usize = 64;
+const DEFAULT_OUT_QUEUE_CAPACITY: usize = 64;
+// The `NameOwnerChanged` signals of the names going away, to evict them from the peer stats.
+const VANISHING_NAMES_RULE: &str = "type='signal',sender='org.freedesktop.DBus',\
+                                    interface='org.freedesktop.DBus',\
+                                    member='NameOwnerChanged',arg2=''";
+
+// A job run on the connection's serialization worker thread.
+type SerializationJob = Box<dyn FnOnce() + Send>;
+
+type SharedConnection = Mutex<Weak<ConnectionInner<AsyncSocket>>>;
+
+// The connections handed out by `Connection::shared_session` and `Connection::shared_system`.
+static SHARED_SESSION: Lazy<SharedConnection> = Lazy::new(|| Mutex::new(Weak::new()));
+static SHARED_SYSTEM: Lazy<SharedConnection> = Lazy::new(|| Mutex::new(Weak::new()));
 
 const FDO_DBUS_SERVICE: &str = "org.freedesktop.DBus";
 const FDO_DBUS_INTERFACE: &str = "org.freedesktop.DBus";
@@ -50,15 +79,19 @@ const FDO_DBUS_MATCH_RULE_EXCEMPT_SIGNALS: [&str; 2] = ["NameAcquired", "NameLos
 
 #[derive(Debug, Hash, Eq, PartialEq)]
 struct SignalInfo<'s> {
-    sender: &'s str,
+    // `None` for a signal from any sender.
+    sender: Option<&'s str>,
     path: ObjectPath<'s>,
     interface: &'s str,
     signal_name: &'s str,
+    // The first argument of the signal, to only subscribe to the emissions about it. Not checked
+    // by `matches`.
+    arg0: Option<&'s str>,
 }
 
 impl<'s> SignalInfo<'s> {
     fn new<E>(
-        sender: &'s str,
+        sender: Option<&'s str>,
         path: impl TryInto<ObjectPath<'s>, Error = E>,
         interface: &'s str,
         signal_name: &'s str,
@@ -71,6 +104,7 @@ impl<'s> SignalInfo<'s> {
             path: path.try_into().map_err(Into::into)?,
             interface,
             signal_name,
+            arg0: None,
         })
     }
 
@@ -80,14 +114,22 @@ impl<'s> SignalInfo<'s> {
         }
 
         // FIXME: Use the API to create this once we've it (issue#69).
+        let sender = self
+            .sender
+            .map(|sender| format!("sender='{}',", sender))
+            .unwrap_or_default();
+        let arg0 = self
+            .arg0
+            .map(|arg0| format!(",arg0='{}'", arg0))
+            .unwrap_or_default();
         Some(format!(
-            "type='signal',sender='{}',path_namespace='{}',interface='{}',member='{}'",
-            self.sender, self.path, self.interface, self.signal_name,
+            "type='signal',{}path_namespace='{}',interface='{}',member='{}'{}",
+            sender, self.path, self.interface, self.signal_name, arg0,
         ))
     }
 
     fn match_rule_excempt(&self) -> bool {
-        self.sender == FDO_DBUS_SERVICE
+        self.sender == Some(FDO_DBUS_SERVICE)
             && self.interface == FDO_DBUS_INTERFACE
             && self.path.as_str() == FDO_DBUS_PATH
             && FDO_DBUS_MATCH_RULE_EXCEMPT_SIGNALS.contains(&self.signal_name)
@@ -99,6 +141,80 @@ impl<'s> SignalInfo<'s> {
 
         hasher.finish()
     }
+
+    // The same signal on `self.path` and each of its ancestors, starting from `self.path` itself.
+    fn namespaces(&self) -> impl Iterator<Item = SignalInfo<'_>> {
+        std::iter::successors(Some(self.path.as_str()), |path| match path.rfind('/') {
+            Some(0) if path.len() > 1 => Some("/"),
+            Some(i) if i > 0 => Some(&path[..i]),
+            _ => None,
+        })
+        .map(move |path| SignalInfo {
+            sender: self.sender,
+            path: ObjectPath::from_str_unchecked(path),
+            interface: self.interface,
+            signal_name: self.signal_name,
+            arg0: self.arg0,
+        })
+    }
+
+    // Checks if `msg` is this signal. `sender` must be a unique name, or `None` to not check the
+    // sender at all.
+    fn matches(&self, msg: &Message, sender: Option<&str>) -> bool {
+        if msg.primary_header().msg_type() != MessageType::Signal {
+            return false;
+        }
+        let header = match msg.header() {
+            Ok(header) => header,
+            Err(_) => return false,
+        };
+        let path_matches = match header.path() {
+            Ok(Some(path)) => {
+                let (path, namespace) = (path.as_str(), self.path.as_str());
+
+                path == namespace
+                    || namespace == "/"
+                    || (path.starts_with(namespace) && path.as_bytes()[namespace.len()] == b'/')
+            }
+            _ => false,
+        };
+
+        path_matches
+            && header.interface() == Ok(Some(self.interface))
+            && header.member() == Ok(Some(self.signal_name))
+            && (sender.is_none() || header.sender() == Ok(sender))
+    }
+}
+
+// Frees the serial number of a method call once its reply is in, or the call is cancelled.
+struct PendingReply<'c> {
+    conn: &'c Connection,
+    serial: u32,
+}
+
+impl Drop for PendingReply<'_> {
+    fn drop(&mut self) {
+        self.conn
+            .0
+            .pending_replies
+            .lock()
+            .expect("lock poisoned")
+            .remove(&self.serial);
+    }
+}
+
+// Drops a signal subscription, in case the future owning it is cancelled.
+struct SubscriptionGuard<'c> {
+    conn: &'c Connection,
+    subscription_id: Option<u64>,
+}
+
+impl Drop for SubscriptionGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.subscription_id.take() {
+            self.conn.queue_unsubscribe_signal(id);
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -112,12 +228,28 @@ struct ConnectionInner<S> {
     server_guid: Guid,
     cap_unix_fd: bool,
     bus_conn: bool,
+    // The byte order of the messages we create.
+    endian_sig: EndianSig,
     unique_name: OnceCell<String>,
-
-    raw_in_conn: Arc<Mutex<RawConnection<Async<S>>>>,
-    raw_out_conn: Arc<sync::Mutex<RawConnection<Async<S>>>>,
+    // Held while saying `Hello`, and notified once we've got `unique_name`.
+    hello_lock: Mutex<()>,
+    unique_name_acquired: Event,
+    // The address we connected to, if any.
+    address: Option<String>,
+
+    raw_in_conn: Arc<Mutex<RawConnection<S>>>,
+    raw_out_conn: Arc<sync::Mutex<RawConnection<S>>>,
+    // The number of messages `try_send_message` lets wait in `raw_out_conn`.
+    out_queue_capacity: AtomicUsize,
+    // Set while a task writes out the messages left in `raw_out_conn` by `try_send_message`.
+    background_flush: Arc<AtomicBool>,
     // Serial number for next outgoing message
     serial: AtomicU32,
+    // The serial numbers of the method calls waiting for their reply, not to be reused until then.
+    pending_replies: sync::Mutex<HashSet<u32>>,
+    // Where serial numbers come from instead of `serial`, if set up through the builder.
+    #[cfg(any(test, feature = "test-util"))]
+    serial_allocator: OnceCell<SerialAllocator>,
 
     // Our executor
     executor: Arc<Executor<'static>>,
@@ -125,13 +257,37 @@ struct ConnectionInner<S> {
     // Message receiver task
     msg_receiver_task: sync::Mutex<Option<Task<()>>>,
 
-    // We're using sync Mutex here as we don't intend to keep it locked while awaiting.
-    msg_receiver: sync::RwLock<InactiveReceiver<Arc<Message>>>,
+    // The queues of the message streams.
+    fanout: Arc<Fanout>,
 
     // Receiver side of the error channel
     error_receiver: Receiver<Error>,
 
+    // Set once the socket is found to be closed.
+    closed: Arc<AtomicBool>,
+
+    // When the last message was received, for the liveness pings to only be sent when it's quiet.
+    last_activity: Arc<sync::Mutex<Instant>>,
+
+    // The timeout of the liveness ping the peer didn't answer, once the connection was given up on.
+    failure: OnceCell<Duration>,
+
+    // The task sending the liveness pings, if enabled through `ConnectionBuilder::liveness_ping`.
+    liveness_task: sync::Mutex<Option<Task<()>>>,
+
     signal_subscriptions: Mutex<HashMap<u64, SignalSubscription>>,
+
+    // Sender side of the serialization worker's job queue, once the worker is started.
+    serialization_worker: sync::Mutex<Option<mpsc::Sender<SerializationJob>>>,
+
+    // The per-peer traffic accounting, if enabled.
+    peer_stats: Arc<PeerStatsTracker>,
+
+    // The credentials of the peers looked up through `credentials_of`.
+    credentials: Arc<CredentialsCache>,
+
+    // The well-known names given to `ConnectionBuilder::name`.
+    registered_names: sync::Mutex<Vec<String>>,
 }
 
 // FIXME: Should really use [`AsyncDrop`] for `ConnectionInner` when we've something like that to
@@ -141,33 +297,63 @@ struct ConnectionInner<S> {
 
 #[derive(Debug)]
 struct MessageReceiverTask<S> {
-    raw_in_conn: Arc<Mutex<RawConnection<Async<S>>>>,
+    raw_in_conn: Arc<Mutex<RawConnection<S>>>,
 
-    // Message broadcaster.
-    msg_sender: Broadcaster<Arc<Message>>,
+    // The queues of the message streams.
+    fanout: Arc<Fanout>,
 
     // Sender side of the error channel
     error_sender: Sender<Error>,
+
+    closed: Arc<AtomicBool>,
+
+    last_activity: Arc<sync::Mutex<Instant>>,
+
+    peer_stats: Arc<PeerStatsTracker>,
+
+    credentials: Arc<CredentialsCache>,
 }
 
-type DynSocketConnection = RawConnection<Async<Box<dyn Socket>>>;
+type DynSocketConnection = RawConnection<AsyncSocket>;
 
-impl MessageReceiverTask<Box<dyn Socket>> {
+impl MessageReceiverTask<AsyncSocket> {
     fn new(
         raw_in_conn: Arc<Mutex<DynSocketConnection>>,
-        msg_sender: Broadcaster<Arc<Message>>,
+        fanout: Arc<Fanout>,
         error_sender: Sender<Error>,
+        closed: Arc<AtomicBool>,
+        last_activity: Arc<sync::Mutex<Instant>>,
+        peer_stats: Arc<PeerStatsTracker>,
+        credentials: Arc<CredentialsCache>,
     ) -> Arc<Self> {
         Arc::new(Self {
             raw_in_conn,
-            msg_sender,
+            fanout,
             error_sender,
+            closed,
+            last_activity,
+            peer_stats,
+            credentials,
         })
     }
 
     fn spawn(self: Arc<Self>, executor: &Executor<'_>) -> Task<()> {
         executor.spawn(async move {
-            self.receive_msg().await;
+            let closed = self.closed.clone();
+            let error_sender = self.error_sender.clone();
+
+            // A panic would otherwise silently stop the flow of messages, so turn it into a
+            // connection error the message streams get to see.
+            if let Err(panic) = AssertUnwindSafe(self.receive_msg()).catch_unwind().await {
+                closed.store(true, SeqCst);
+                let e = io::Error::new(
+                    ErrorKind::Other,
+                    format!("message receiver task panicked: {}", panic_message(&*panic)),
+                );
+                log::error!(target: logging::CONNECTION, "{}", e);
+                // Ignoring errors. See comment in `receive_msg`.
+                let _ = error_sender.send(Error::Io(e)).await;
+            }
         })
     }
 
@@ -186,7 +372,18 @@ impl MessageReceiverTask<Box<dyn Socket>> {
             };
             let msg = match receive_msg.await {
                 Ok(msg) => msg,
+                // The raw connection reports the peer being gone as a `BrokenPipe` error, however
+                // it was noticed. Nothing more can be received then, so the task ends, along with
+                // the message streams, after passing on the error if it can be queued.
+                Err(Error::Io(e)) if e.kind() == ErrorKind::BrokenPipe => {
+                    self.closed.store(true, SeqCst);
+                    log::debug!(target: logging::CONNECTION, "Connection closed: {}", e);
+                    let _ = self.error_sender.try_send(Error::Io(e));
+
+                    return;
+                }
                 Err(e) => {
+                    log::debug!(target: logging::CONNECTION, "Failed to receive a message: {}", e);
                     // Ignoring errors. See comment above.
                     let _ = self.error_sender.send(e).await;
 
@@ -194,13 +391,44 @@ impl MessageReceiverTask<Box<dyn Socket>> {
                 }
             };
 
-            let msg = Arc::new(msg);
-            // Ignoring errors. See comment above.
-            let _ = self.msg_sender.broadcast(msg.clone()).await;
+            *self.last_activity.lock().expect("lock poisoned") = Instant::now();
+            self.peer_stats.received(&msg);
+            self.credentials.received(&msg);
+            self.fanout.send(Arc::new(msg)).await;
         }
     }
 }
 
+impl<S> Drop for MessageReceiverTask<S> {
+    fn drop(&mut self) {
+        // No more messages are coming, so let the streams end.
+        self.fanout.close();
+    }
+}
+
+// Run one task of `executor`, catching the panic of the task if any.
+//
+// A panicking task only takes itself down, so the executor can keep running the others.
+async fn tick(executor: &Executor<'_>) {
+    if let Err(panic) = AssertUnwindSafe(executor.tick()).catch_unwind().await {
+        log::error!(
+            target: logging::CONNECTION,
+            "A task on the connection executor panicked: {}",
+            panic_message(&*panic)
+        );
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    match panic.downcast_ref::<&str>() {
+        Some(msg) => msg,
+        None => panic
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .unwrap_or("unknown panic"),
+    }
+}
+
 /// The asynchronous sibling of [`zbus::Connection`].
 ///
 /// Most of the API is very similar to [`zbus::Connection`], except it's asynchronous. However,
@@ -295,7 +523,7 @@ impl MessageReceiverTask<Box<dyn Socket>> {
 ///
 /// [Monitor]: https://dbus.freedesktop.org/doc/dbus-specification.html#bus-messages-become-monitor
 #[derive(Clone, Debug)]
-pub struct Connection(Arc<ConnectionInner<Box<dyn Socket>>>);
+pub struct Connection(Arc<ConnectionInner<AsyncSocket>>);
 
 assert_impl_all!(Connection: Send, Sync, Unpin);
 
@@ -311,7 +539,7 @@ impl Connection {
         // SASL Handshake
         let auth = Authenticated::client(Async::new(Box::new(stream) as Box<dyn Socket>)?).await?;
 
-        Self::new(auth, bus_connection).await
+        Self::new(auth, bus_connection, false, NATIVE_ENDIAN_SIG).await
     }
 
     /// Create a server `Connection` for the given `UnixStream` and the server `guid`.
@@ -322,52 +550,64 @@ impl Connection {
     /// Upon successful return, the connection is fully established and negotiated: D-Bus messages
     /// can be sent and received.
     pub async fn new_unix_server(stream: UnixStream, guid: &Guid) -> Result<Self> {
-        #[cfg(any(target_os = "android", target_os = "linux"))]
-        let client_uid = {
-            use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
-
-            let creds = getsockopt(stream.as_raw_fd(), PeerCredentials)
-                .map_err(|e| Error::Handshake(format!("Failed to get peer credentials: {}", e)))?;
-
-            creds.uid()
-        };
-        #[cfg(any(
-            target_os = "macos",
-            target_os = "ios",
-            target_os = "freebsd",
-            target_os = "dragonfly",
-            target_os = "openbsd",
-            target_os = "netbsd"
-        ))]
-        let client_uid = nix::unistd::getpeereid(stream.as_raw_fd())
-            .map_err(|e| Error::Handshake(format!("Failed to get peer credentials: {}", e)))?
-            .0
-            .into();
-
-        let auth = Authenticated::server(
-            Async::new(Box::new(stream) as Box<dyn Socket>)?,
-            guid.clone(),
-            client_uid,
-        )
-        .await?;
+        let auth = Authenticated::unix_server(stream, guid.clone(), vec![], None).await?;
 
-        Self::new(auth, false).await
+        Self::new(auth, false, false, NATIVE_ENDIAN_SIG).await
     }
 
     /// Get a stream to receive incoming messages.
+    ///
+    /// The stream gets all the messages received from now on, in a queue of its own. Its capacity
+    /// is that of [`max_queued`]. When the queue is full, the messages are dropped to create room,
+    /// starting from the oldest one. Use [`stream_with_policy`] for other settings.
+    ///
+    /// [`max_queued`]: #method.max_queued
+    /// [`stream_with_policy`]: #method.stream_with_policy
     pub async fn stream(&self) -> MessageStream {
-        let msg_receiver = self
-            .0
-            .msg_receiver
-            .read()
-            // SAFETY: Not much we can do about a poisoned mutex.
-            .expect("poisoned lock")
-            .activate_cloned()
-            .map(Ok);
+        self.new_stream(None, OverflowPolicy::DropOldest)
+    }
+
+    /// Get a stream to receive incoming messages, queueing up to `max_queued` of them.
+    ///
+    /// `policy` says what to do with the messages while the queue is full. As each stream has a
+    /// queue of its own, a stream lagging behind doesn't make the others lose messages (unless
+    /// its policy is [`OverflowPolicy::Block`]). [`MessageStream::dropped`] tells how many
+    /// messages the stream lost.
+    ///
+    /// ```
+    ///# use std::error::Error;
+    ///# use zbus::azync::{Connection, OverflowPolicy};
+    ///# use async_io::block_on;
+    ///#
+    ///# block_on(async {
+    /// let conn = Connection::new_session().await?;
+    /// // Only keep the first messages, while the logger is busy.
+    /// let log_stream = conn.stream_with_policy(16, OverflowPolicy::DropNewest).await;
+    /// assert_eq!(log_stream.dropped(), 0);
+    ///#     Ok::<(), zbus::Error>(())
+    ///# })?;
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`OverflowPolicy::Block`]: enum.OverflowPolicy.html#variant.Block
+    /// [`MessageStream::dropped`]: struct.MessageStream.html#method.dropped
+    pub async fn stream_with_policy(
+        &self,
+        max_queued: usize,
+        policy: OverflowPolicy,
+    ) -> MessageStream {
+        self.new_stream(Some(max_queued), policy)
+    }
+
+    fn new_stream(&self, max_queued: Option<usize>, policy: OverflowPolicy) -> MessageStream {
+        let queue = self.0.fanout.subscribe(max_queued, policy);
+        let msgs = futures_util::stream::unfold(queue.clone(), |queue| async move {
+            queue.pop().await.map(|msg| (Ok(msg), queue))
+        });
         let error_stream = self.0.error_receiver.clone().map(Err);
-        let stream = stream_select(error_stream, msg_receiver).boxed();
+        let stream = stream_select(error_stream, msgs).boxed();
 
-        MessageStream { stream }
+        MessageStream { stream, queue }
     }
 
     /// Get a sink to send out messages.
@@ -385,13 +625,270 @@ impl Connection {
     ///
     /// On successfully sending off `msg`, the assigned serial number is returned.
     pub async fn send_message(&self, mut msg: Message) -> Result<u32> {
+        self.check_failure()?;
+        let serial = self.assign_serial_num(&mut msg)?;
+        self.0.peer_stats.sent(&msg);
+
+        self.sink().await.send(msg).await?;
+
+        Ok(serial)
+    }
+
+    /// Send `msg` to the peer, with the given priority.
+    ///
+    /// This is the same as [`send_message`], except that a [`Priority::High`] message is queued
+    /// ahead of the normal-priority messages waiting to be written to the socket, e.g for a reply
+    /// that shouldn't wait for a bulk transfer to complete. See [`Priority`] for the ordering
+    /// guarantees. [`send_message`] uses [`Priority::Normal`].
+    ///
+    /// [`send_message`]: struct.Connection.html#method.send_message
+    /// [`Priority`]: ../enum.Priority.html
+    /// [`Priority::High`]: ../enum.Priority.html#variant.High
+    /// [`Priority::Normal`]: ../enum.Priority.html#variant.Normal
+    pub async fn send_message_with_priority(
+        &self,
+        mut msg: Message,
+        priority: Priority,
+    ) -> Result<u32> {
+        self.check_failure()?;
+        if !msg.fds().is_empty() && !self.0.cap_unix_fd {
+            return Err(Error::Unsupported);
+        }
+        let serial = self.assign_serial_num(&mut msg)?;
+        self.0.peer_stats.sent(&msg);
+
+        self.0
+            .raw_out_conn
+            .lock()
+            .expect("poisoned lock")
+            .enqueue_message_with_priority(msg, priority);
+        let mut sink = self.sink().await;
+        poll_fn(|cx| sink.flush(cx)).await?;
+
+        Ok(serial)
+    }
+
+    /// Send `msg` to the peer, without waiting.
+    ///
+    /// Unlike [`send_message`], this never waits for the socket to accept the message, so it can
+    /// be called from code that must not block, e.g a real-time audio thread. The message is
+    /// written to the socket right away if possible. Otherwise it's queued, and written out by a
+    /// task of the connection's executor, as the peer reads the previous ones.
+    ///
+    /// If [`out_queue_capacity`] messages are waiting to be written already, this fails with
+    /// [`Error::QueueFull`] and `msg` isn't sent at all. It's up to the caller to drop it or try
+    /// again later. [`out_queue_len`] tells how many messages are waiting.
+    ///
+    /// On success, the assigned serial number is returned.
+    ///
+    /// [`send_message`]: struct.Connection.html#method.send_message
+    /// [`out_queue_capacity`]: struct.Connection.html#method.out_queue_capacity
+    /// [`out_queue_len`]: struct.Connection.html#method.out_queue_len
+    /// [`Error::QueueFull`]: ../enum.Error.html#variant.QueueFull
+    pub fn try_send_message(&self, mut msg: Message) -> Result<u32> {
+        self.check_failure()?;
+        if !msg.fds().is_empty() && !self.0.cap_unix_fd {
+            return Err(Error::Unsupported);
+        }
+
+        let mut raw_conn = self.0.raw_out_conn.lock().expect("poisoned lock");
+        if raw_conn.queued_message_count() >= self.out_queue_capacity() {
+            return Err(Error::QueueFull);
+        }
         let serial = self.assign_serial_num(&mut msg)?;
+        self.0.peer_stats.sent(&msg);
+        raw_conn.enqueue_message(msg);
+        match raw_conn.try_flush() {
+            Ok(()) => Ok(serial),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                drop(raw_conn);
+                self.flush_in_background();
+
+                Ok(serial)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Write out the messages waiting in `raw_out_conn` from a task, unless one is at it already.
+    fn flush_in_background(&self) {
+        let flushing = self.0.background_flush.clone();
+        if flushing.swap(true, SeqCst) {
+            return;
+        }
+
+        let mut sink = MessageSink {
+            raw_conn: self.0.raw_out_conn.clone(),
+            cap_unix_fd: self.0.cap_unix_fd,
+        };
+        self.0
+            .executor
+            .spawn(async move {
+                loop {
+                    let res = poll_fn(|cx| sink.flush(cx)).await;
+                    flushing.store(false, SeqCst);
+                    if let Err(e) = res {
+                        log::warn!(
+                            target: logging::CONNECTION,
+                            "Failed to write out the queued messages: {}",
+                            e,
+                        );
+
+                        break;
+                    }
+
+                    // Messages queued before `flushing` was cleared are ours to write out.
+                    let needs_write = sink.raw_conn.lock().expect("poisoned lock").needs_write();
+                    if !needs_write || flushing.swap(true, SeqCst) {
+                        break;
+                    }
+                }
+            })
+            .detach();
+    }
+
+    /// The number of messages waiting to be written to the socket.
+    ///
+    /// See [`try_send_message`] for details.
+    ///
+    /// [`try_send_message`]: struct.Connection.html#method.try_send_message
+    pub fn out_queue_len(&self) -> usize {
+        self.0
+            .raw_out_conn
+            .lock()
+            .expect("poisoned lock")
+            .queued_message_count()
+    }
+
+    /// The max number of messages waiting to be written to the socket, for [`try_send_message`].
+    ///
+    /// The default is 64. The other methods sending messages wait for the socket instead, so they
+    /// aren't limited by it.
+    ///
+    /// [`try_send_message`]: struct.Connection.html#method.try_send_message
+    pub fn out_queue_capacity(&self) -> usize {
+        self.0.out_queue_capacity.load(SeqCst)
+    }
+
+    /// Set the max number of messages waiting to be written to the socket, for
+    /// [`try_send_message`].
+    ///
+    /// Like [`set_max_queued`], this method takes ownership of `self` and returns it, for setting
+    /// the value at instantiation time.
+    ///
+    /// [`try_send_message`]: struct.Connection.html#method.try_send_message
+    /// [`set_max_queued`]: struct.Connection.html#method.set_max_queued
+    pub fn set_out_queue_capacity(self, capacity: usize) -> Self {
+        self.0.out_queue_capacity.store(capacity, SeqCst);
+
+        self
+    }
+
+    /// Send the pre-built `msg` to the peer, as is.
+    ///
+    /// This is meant for messages built ahead of time, possibly on another thread or in another
+    /// process and brought in through [`Message::from_raw_parts`]. Unlike [`send_message`], which
+    /// keeps the serial number `msg` may already have, this always assigns it a new one, unique to
+    /// this connection, since a serial number set elsewhere could clash with those of the
+    /// connection. The serial number is written over that of the primary header, in place: the
+    /// rest of the header and the body bytes are sent verbatim, without any re-serialization.
+    ///
+    /// The connection takes over `msg`, along with the file descriptors it owns, e.g those passed
+    /// to [`Message::from_raw_parts`]. These are closed once the message is sent off, or when
+    /// sending fails, so they can be handed over with the message between threads without any
+    /// further care. File descriptors that `msg` refers to without owning them (e.g [`Fd`] values in the
+    /// body of a message from [`MessageBuilder`]) must stay open until this method returns.
+    ///
+    /// On successfully sending off `msg`, the assigned serial number is returned.
+    ///
+    /// [`Message::from_raw_parts`]: ../struct.Message.html#method.from_raw_parts
+    /// [`send_message`]: struct.Connection.html#method.send_message
+    /// [`Fd`]: https://docs.rs/zvariant/2.7.0/zvariant/struct.Fd.html
+    /// [`MessageBuilder`]: ../struct.MessageBuilder.html
+    pub async fn send_raw_message(&self, mut msg: Message) -> Result<u32> {
+        self.check_failure()?;
+        let serial = self.next_serial();
+        msg.modify_primary_header(|primary| {
+            primary.set_serial_num(serial);
+            Ok(())
+        })?;
+        self.0.peer_stats.sent(&msg);
 
         self.sink().await.send(msg).await?;
 
         Ok(serial)
     }
 
+    /// Send all the messages in `msgs` to the peer, at once.
+    ///
+    /// The messages are queued together and then flushed with [write coalescing], i.e. as many of
+    /// them as possible in each write to the socket. This is much cheaper than sending them one by
+    /// one when there are many small messages to send, e.g for a burst of signals.
+    ///
+    /// The connection sets a unique serial number on each message. On successfully sending off all
+    /// the messages, their assigned serial numbers are returned, in order.
+    ///
+    /// [write coalescing]: struct.Connection.html#method.set_write_coalescing
+    pub async fn send_batch<I>(&self, msgs: I) -> Result<Vec<u32>>
+    where
+        I: IntoIterator<Item = Message>,
+    {
+        self.check_failure()?;
+        let mut batch = vec![];
+        let mut serials = vec![];
+        for mut msg in msgs {
+            if !msg.fds().is_empty() && !self.0.cap_unix_fd {
+                return Err(Error::Unsupported);
+            }
+            serials.push(self.assign_serial_num(&mut msg)?);
+            self.0.peer_stats.sent(&msg);
+            batch.push(msg);
+        }
+
+        {
+            let mut raw_conn = self.0.raw_out_conn.lock().expect("poisoned lock");
+            for msg in batch {
+                raw_conn.enqueue_message(msg);
+            }
+        }
+        let mut sink = self.sink().await;
+        poll_fn(|cx| sink.flush_with(cx, true)).await?;
+
+        Ok(serials)
+    }
+
+    /// Set whether outgoing messages are coalesced into as few writes to the socket as possible.
+    ///
+    /// D-Bus being a byte stream, multiple complete messages can be written to the socket at once.
+    /// With write coalescing enabled, all the messages queued by the time the connection gets to
+    /// write to the socket are written together, up to 64 KiB per write. This saves syscalls when
+    /// many messages are sent concurrently, at the cost of copying them. Messages carrying file
+    /// descriptors always start a new write, so that the descriptors are sent along with them.
+    ///
+    /// This is disabled by default. [`send_batch`] always coalesces the messages it sends.
+    ///
+    /// [`send_batch`]: struct.Connection.html#method.send_batch
+    pub fn set_write_coalescing(&self, write_coalescing: bool) {
+        self.0
+            .raw_out_conn
+            .lock()
+            .expect("poisoned lock")
+            .set_write_coalescing(write_coalescing);
+    }
+
+    /// Whether outgoing messages are coalesced into as few writes to the socket as possible.
+    ///
+    /// See [`set_write_coalescing`] for details.
+    ///
+    /// [`set_write_coalescing`]: struct.Connection.html#method.set_write_coalescing
+    pub fn write_coalescing(&self) -> bool {
+        self.0
+            .raw_out_conn
+            .lock()
+            .expect("poisoned lock")
+            .write_coalescing()
+    }
+
     /// Send a method call.
     ///
     /// Create a method-call message, send it over the connection, then wait for the reply.
@@ -410,633 +907,3352 @@ impl Connection {
         B: serde::ser::Serialize + zvariant::Type,
         E: Into<MessageError>,
     {
-        let stream = self.stream().await;
-        let m = Message::method(
-            self.unique_name(),
-            destination,
-            path,
-            interface,
-            method_name,
-            body,
-        )?;
-        let serial = self.send_message(m).await?;
-        match stream
-            .filter(move |m| {
-                ready(
-                    m.as_ref()
-                        .map(|m| {
-                            matches!(
-                                m.primary_header().msg_type(),
-                                MessageType::Error | MessageType::MethodReturn
-                            ) && m.header().and_then(|h| h.reply_serial()) == Ok(Some(serial))
-                        })
-                        .unwrap_or(false),
-                )
-            })
-            .next()
-            .await
-        {
-            Some(msg) => match msg {
-                Ok(m) => {
-                    match m.header()?.message_type()? {
-                        MessageType::Error => Err(m.into()),
-                        MessageType::MethodReturn => Ok(m),
-                        // We already established the msg type in `filter` above.
-                        _ => unreachable!(),
-                    }
-                }
-                Err(e) => Err(e),
-            },
-            None => {
-                // If SocketStream gives us None, that means the socket was closed
-                Err(crate::Error::Io(io::Error::new(
-                    ErrorKind::BrokenPipe,
-                    "socket closed",
-                )))
-            }
-        }
+        let m = self
+            .builder(MessageBuilder::method_call(path, method_name)?)?
+            .optional_fields(None, destination, interface)
+            .build(body)?;
+
+        self.call_method_message(m).await
     }
 
-    /// Emit a signal.
+    /// Send a method call and return the reply body.
     ///
-    /// Create a signal message, and send it over the connection.
-    pub async fn emit_signal<B, E>(
+    /// This is the same as [`call_method`], except that the body of the reply is deserialized to
+    /// `R`. If it isn't of the type of `R`, the returned [`Error::ReplyBody`] tells which call the
+    /// reply is to, and the signatures that were expected and received.
+    ///
+    /// [`call_method`]: Connection::call_method
+    pub async fn call_method_typed<R, B, E>(
         &self,
         destination: Option<&str>,
         path: impl TryInto<ObjectPath<'_>, Error = E>,
-        interface: &str,
-        signal_name: &str,
+        interface: Option<&str>,
+        method_name: &str,
         body: &B,
-    ) -> Result<()>
+    ) -> Result<R>
     where
+        R: serde::de::DeserializeOwned + zvariant::Type,
         B: serde::ser::Serialize + zvariant::Type,
         E: Into<MessageError>,
     {
-        let m = Message::signal(
-            self.unique_name(),
-            destination,
-            path,
-            interface,
-            signal_name,
-            body,
-        )?;
+        let reply = self
+            .call_method(destination, path, interface, method_name, body)
+            .await?;
+        // The FDs in the body are owned by `R` from now on.
+        reply.disown_fds();
 
-        self.send_message(m).await.map(|_| ())
+        reply.reply_body(destination, interface, method_name, false)
     }
 
-    /// Reply to a message.
+    /// Send a method call, with a body serialized beforehand.
     ///
-    /// Given an existing message (likely a method call), send a reply back to the caller with the
-    /// given `body`.
+    /// This is the same as [`call_method`], except that `body` is not serialized again for each
+    /// call. This is useful for making the same call to many destinations.
     ///
-    /// Returns the message serial number.
-    pub async fn reply<B>(&self, call: &Message, body: &B) -> Result<u32>
+    /// [`call_method`]: Connection::call_method
+    pub async fn call_method_raw_body<E>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'_>, Error = E>,
+        interface: Option<&str>,
+        method_name: &str,
+        body: &RawBody,
+    ) -> Result<Arc<Message>>
     where
-        B: serde::ser::Serialize + zvariant::Type,
+        E: Into<MessageError>,
     {
-        let m = Message::method_reply(self.unique_name(), call, body)?;
-        self.send_message(m).await
+        let m = self
+            .builder(MessageBuilder::method_call(path, method_name)?)?
+            .optional_fields(None, destination, interface)
+            .build_raw_body(body)?;
+
+        self.call_method_message(m).await
     }
 
-    /// Reply an error to a message.
+    /// Send a method call, with arguments of types only known at runtime.
     ///
-    /// Given an existing message (likely a method call), send an error reply back to the caller
-    /// with the given `error_name` and `body`.
+    /// This is the same as [`call_method`], except that each of `args` is an argument of the call,
+    /// as serialized by [`RawBody::from_values`], rather than a variant. This is meant for generic
+    /// tools, such as a D-Bus REPL, calling methods with values built at runtime. The arguments of
+    /// the reply are available the same way, through [`Message::body_structure`].
     ///
-    /// Returns the message serial number.
-    pub async fn reply_error<B>(&self, call: &Message, error_name: &str, body: &B) -> Result<u32>
+    /// [`call_method`]: Connection::call_method
+    /// [`RawBody::from_values`]: crate::RawBody::from_values
+    /// [`Message::body_structure`]: crate::Message::body_structure
+    pub async fn call_method_with_values<E>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'_>, Error = E>,
+        interface: Option<&str>,
+        method_name: &str,
+        args: &[Value<'_>],
+    ) -> Result<Arc<Message>>
     where
-        B: serde::ser::Serialize + zvariant::Type,
+        E: Into<MessageError>,
     {
-        let m = Message::method_error(self.unique_name(), call, error_name, body)?;
-        self.send_message(m).await
-    }
+        let body = RawBody::from_values(args)?;
 
-    /// Checks if `self` is a connection to a message bus.
+        self.call_method_raw_body(destination, path, interface, method_name, &body)
+            .await
+    }
+
+    /// Send a method call, with arguments given as text.
     ///
-    /// This will return `false` for p2p connections.
-    pub fn is_bus(&self) -> bool {
-        self.0.bus_conn
+    /// This is the same as [`call_method_with_values`], except that the arguments are parsed
+    /// against `signature`, the signature of the whole body, with
+    /// [`Value::from_str_with_signature`]. It takes the same syntax as `busctl call`, for tools
+    /// passing on arguments given by their users:
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    ///# async_io::block_on(async {
+    /// let connection = zbus::azync::Connection::new_session().await?;
+    /// let reply = connection
+    ///     .call_method_dynamic(
+    ///         Some("org.freedesktop.Notifications"),
+    ///         "/org/freedesktop/Notifications",
+    ///         Some("org.freedesktop.Notifications"),
+    ///         "Notify",
+    ///         "susssasa{sv}i",
+    ///         &["zbus", "0", "", "Hello", "", "", "urgency=y:1", "-1"],
+    ///     )
+    ///     .await?;
+    /// let id: u32 = reply.body()?;
+    ///# Ok::<(), Box<dyn Error>>(())
+    ///# });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// [`Error::Argument`], with the index of the argument, if an argument is invalid, or if there
+    /// are too few or too many of them.
+    ///
+    /// [`call_method_with_values`]: Connection::call_method_with_values
+    /// [`Value::from_str_with_signature`]: zvariant::Value::from_str_with_signature
+    /// [`Error::Argument`]: crate::Error::Argument
+    pub async fn call_method_dynamic<E, S>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'_>, Error = E>,
+        interface: Option<&str>,
+        method_name: &str,
+        signature: &str,
+        args: &[S],
+    ) -> Result<Arc<Message>>
+    where
+        E: Into<MessageError>,
+        S: AsRef<str>,
+    {
+        let args = parse_args(signature, args)?;
+
+        self.call_method_with_values(destination, path, interface, method_name, &args)
+            .await
     }
 
-    /// Assigns a serial number to `msg` that is unique to this connection.
+    /// Send a method call, serializing `body` on a separate thread.
     ///
-    /// This method can fail if `msg` is corrupt.
-    pub fn assign_serial_num(&self, msg: &mut Message) -> Result<u32> {
-        let mut serial = 0;
-        msg.modify_primary_header(|primary| {
-            serial = *primary.serial_num_or_init(|| self.next_serial());
-            Ok(())
-        })?;
+    /// This is the same as [`call_method`], except that the method-call message is created on a
+    /// worker thread shared by all users of the connection, rather than on the calling task. This
+    /// avoids blocking the calling task (and everything else running on the same thread) for a
+    /// long time, when `body` is very large.
+    ///
+    /// The call is only sent out after the serialization is completed, so awaiting each call before
+    /// making the next one guarantees the messages are sent in order, just like with
+    /// [`call_method`]. As with [`call_method`], file descriptors in `body` are not duplicated and
+    /// hence must remain open until the call is completed.
+    ///
+    /// [`call_method`]: Connection::call_method
+    pub async fn call_method_offload<B, E>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'_>, Error = E>,
+        interface: Option<&str>,
+        method_name: &str,
+        body: B,
+    ) -> Result<Arc<Message>>
+    where
+        B: serde::ser::Serialize + zvariant::Type + Send + 'static,
+        E: Into<MessageError>,
+    {
+        let sender = self.sender()?.map(String::from);
+        let endian_sig = self.0.endian_sig;
+        let destination = destination.map(String::from);
+        let path = path
+            .try_into()
+            .map_err(|e| Error::Message(e.into()))?
+            .into_owned();
+        let interface = interface.map(String::from);
+        let method_name = String::from(method_name);
+        let m = self
+            .offload(move || {
+                MessageBuilder::method_call(path, &method_name)?
+                    .optional_fields(
+                        sender.as_deref(),
+                        destination.as_deref(),
+                        interface.as_deref(),
+                    )
+                    .endian_sig(endian_sig)
+                    .build(&body)
+            })
+            .await??;
 
-        Ok(serial)
+        self.call_method_message(m).await
     }
 
-    /// The unique name as assigned by the message bus or `None` if not a message bus connection.
-    pub fn unique_name(&self) -> Option<&str> {
-        self.0.unique_name.get().map(|s| s.as_str())
+    // Run `job` on the serialization worker thread, starting it if needed.
+    async fn offload<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = bounded(1);
+        let mut job: SerializationJob = Box::new(move || {
+            // The receiver is only gone if the caller is not interested anymore.
+            let _ = tx.try_send(f());
+        });
+
+        {
+            let mut worker = self.0.serialization_worker.lock().expect("lock poisoned");
+            loop {
+                if let Some(sender) = &*worker {
+                    match sender.send(job) {
+                        Ok(()) => break,
+                        // The worker thread is gone after a panic in a previous job.
+                        Err(mpsc::SendError(j)) => job = j,
+                    }
+                }
+
+                let (sender, receiver) = mpsc::channel::<SerializationJob>();
+                std::thread::Builder::new()
+                    .name("zbus::azync::Connection::serialize".into())
+                    .spawn(move || {
+                        // Run until the connection is dropped.
+                        for job in receiver {
+                            job();
+                        }
+                    })?;
+                *worker = Some(sender);
+            }
+        }
+
+        rx.recv().await.map_err(|_| {
+            Error::Io(io::Error::new(
+                ErrorKind::Other,
+                "serialization worker panicked",
+            ))
+        })
     }
 
-    /// Max number of messages to queue.
-    pub fn max_queued(&self) -> usize {
-        self.0
-            .msg_receiver
-            .read()
-            .expect("poisoned lock")
-            .capacity()
+    // Send the method-call message `m`, then wait for the reply.
+    pub(crate) async fn call_method_message(&self, m: Message) -> Result<Arc<Message>> {
+        self.call_method_message_with_priority(m, Priority::Normal)
+            .await
     }
 
-    /// Set the max number of messages to queue.
-    ///
-    /// Since typically you'd want to set this at instantiation time, this method takes ownership
-    /// of `self` and returns an owned `Connection` instance so you can use the builder pattern to
-    /// set the value.
+    // Like `call_method_message`, sending the call with `priority`.
+    pub(crate) async fn call_method_message_with_priority(
+        &self,
+        mut m: Message,
+        priority: Priority,
+    ) -> Result<Arc<Message>> {
+        let stream = self.stream().await;
+        let pending = self.reserve_reply_serial(&mut m)?;
+        let serial = pending.serial;
+        self.send_message_with_priority(m, priority).await?;
+        match stream
+            .filter(move |m| {
+                ready(
+                    m.as_ref()
+                        .map(|m| self.is_reply_to(m, serial))
+                        .unwrap_or(false),
+                )
+            })
+            .next()
+            .await
+        {
+            Some(msg) => match msg {
+                Ok(m) => {
+                    match m.header()?.message_type()? {
+                        MessageType::Error => Err(m.into()),
+                        MessageType::MethodReturn => Ok(m),
+                        // We already established the msg type in `filter` above.
+                        _ => unreachable!(),
+                    }
+                }
+                Err(e) => Err(e),
+            },
+            // If SocketStream gives us None, that means the socket was closed
+            None => Err(self.closed_error()),
+        }
+    }
+
+    // Fails with `Error::PeerUnresponsive` once the connection was given up on.
+    fn check_failure(&self) -> Result<()> {
+        match self.0.failure.get() {
+            Some(timeout) => Err(Error::PeerUnresponsive(*timeout)),
+            None => Ok(()),
+        }
+    }
+
+    // The error for the message streams having ended.
+    fn closed_error(&self) -> Error {
+        match self.check_failure() {
+            Ok(()) => Error::Io(io::Error::new(ErrorKind::BrokenPipe, "socket closed")),
+            Err(e) => e,
+        }
+    }
+
+    // Checks if `m` is the reply to our method call with the serial number `serial`.
+    fn is_reply_to(&self, m: &Message, serial: u32) -> bool {
+        is_reply_to(m, serial, self.unique_name())
+    }
+
+    /// Emit a signal.
     ///
-    /// # Example
+    /// Create a signal message, and send it over the connection.
+    pub async fn emit_signal<B, E>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'_>, Error = E>,
+        interface: &str,
+        signal_name: &str,
+        body: &B,
+    ) -> Result<()>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+        E: Into<MessageError>,
+    {
+        let m = self
+            .builder(MessageBuilder::signal(path, interface, signal_name)?)?
+            .optional_fields(None, destination, None)
+            .build(body)?;
+
+        self.send_message(m).await.map(|_| ())
+    }
+
+    /// Emit a signal, without waiting.
     ///
-    /// ```
-    ///# use std::error::Error;
-    ///# use zbus::azync::Connection;
-    ///# use async_io::block_on;
-    ///#
-    ///# block_on(async {
-    /// let conn = Connection::new_session()
-    ///     .await?
-    ///     .set_max_queued(30);
-    /// assert_eq!(conn.max_queued(), 30);
+    /// Like [`emit_signal`], but the signal is sent with [`try_send_message`], so this fails with
+    /// [`Error::QueueFull`] rather than waiting when the outgoing queue is full.
     ///
-    ///#     Ok::<(), zbus::Error>(())
-    ///# });
-    ///#
-    /// // Do something useful with `conn`..
-    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
-    /// ```
-    pub fn set_max_queued(self, max: usize) -> Self {
-        self.0
-            .msg_receiver
-            .write()
-            .expect("poisoned lock")
-            .set_capacity(max);
+    /// [`emit_signal`]: struct.Connection.html#method.emit_signal
+    /// [`try_send_message`]: struct.Connection.html#method.try_send_message
+    /// [`Error::QueueFull`]: ../enum.Error.html#variant.QueueFull
+    pub fn try_emit_signal<B, E>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'_>, Error = E>,
+        interface: &str,
+        signal_name: &str,
+        body: &B,
+    ) -> Result<()>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+        E: Into<MessageError>,
+    {
+        let m = self
+            .builder(MessageBuilder::signal(path, interface, signal_name)?)?
+            .optional_fields(None, destination, None)
+            .build(body)?;
 
-        self
+        self.try_send_message(m).map(|_| ())
     }
 
-    /// The server's GUID.
-    pub fn server_guid(&self) -> &str {
-        self.0.server_guid.as_str()
+    /// Reply to a message.
+    ///
+    /// Given an existing message (likely a method call), send a reply back to the caller with the
+    /// given `body`.
+    ///
+    /// Returns the message serial number.
+    pub async fn reply<B>(&self, call: &Message, body: &B) -> Result<u32>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        let m = self
+            .builder(MessageBuilder::method_return(call)?)?
+            .build(body)?;
+        self.send_message(m).await
     }
 
-    #[cfg(any(doc, not(feature = "internal-executor")))]
-    /// The underlying executor.
+    /// Reply an error to a message.
     ///
-    /// This method is available when built with the default `internal-executor` feature disabled.
-    /// Since zbus will not spawn thread internally to run the executor in this case, you're
-    /// responsible to continuously [tick the executor][tte]. Failure to do so will result in hangs.
+    /// Given an existing message (likely a method call), send an error reply back to the caller
+    /// with the given `error_name` and `body`.
     ///
-    /// # Examples
+    /// Returns the message serial number.
+    pub async fn reply_error<B>(&self, call: &Message, error_name: &str, body: &B) -> Result<u32>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        let m = self
+            .builder(MessageBuilder::error(call, error_name)?)?
+            .build(body)?;
+        self.send_message(m).await
+    }
+
+    /// Wait for the next emission of a signal.
     ///
-    /// Here is how one would typically run the zbus executor through tokio's single-threaded
-    /// scheduler:
+    /// This is a lighter alternative to [`Proxy::receive_signal`] for when you're only interested
+    /// in a single emission of a signal: the match rule for the signal is added on the bus, the
+    /// first matching signal message is returned and the match rule is removed again.
     ///
-    /// ```
-    /// use zbus::azync::Connection;
-    /// use tokio::runtime;
+    /// No match rule is added if `self` is a peer-to-peer connection, or if this connection has
+    /// already subscribed to the same signal on `path` or any of its ancestor paths.
     ///
-    ///# #[cfg(not(feature = "internal-executor"))]
-    /// runtime::Builder::new_current_thread()
-    ///        .build()
-    ///        .unwrap()
-    ///        .block_on(async {
-    ///     let conn = Connection::new_session().await.unwrap();
-    ///     {
-    ///        let conn = conn.clone();
-    ///        tokio::task::spawn(async move {
-    ///            loop {
-    ///                conn.executor().tick().await;
-    ///            }
-    ///        });
-    ///     }
+    /// # Errors
     ///
-    ///     // All your other async code goes here.
-    /// });
-    /// ```
+    /// Apart from general I/O errors, this method will also fail if `sender` is a well-known name
+    /// that currently has no owner on the bus.
     ///
-    /// [tte]: https://docs.rs/async-executor/1.4.1/async_executor/struct.Executor.html#method.tick
-    pub fn executor(&self) -> &Executor<'static> {
-        &self.0.executor
-    }
-
-    /// Get the raw file descriptor of this connection.
-    pub async fn as_raw_fd(&self) -> RawFd {
-        (self.0.raw_in_conn.lock().await.socket()).as_raw_fd()
-    }
-
-    pub(crate) async fn subscribe_signal<'s, E>(
+    /// [`Proxy::receive_signal`]: struct.Proxy.html#method.receive_signal
+    pub async fn wait_for_signal<'s, E>(
         &self,
         sender: &'s str,
         path: impl TryInto<ObjectPath<'s>, Error = E>,
         interface: &'s str,
         signal_name: &'s str,
-    ) -> Result<u64>
+    ) -> Result<Arc<Message>>
     where
         E: Into<Error>,
     {
-        let signal = SignalInfo::new(sender, path, interface, signal_name)?;
-        let hash = signal.calc_hash();
-        let mut subscriptions = self.0.signal_subscriptions.lock().await;
-        match subscriptions.get_mut(&hash) {
-            Some(subscription) => subscription.num_subscribers += 1,
-            None => {
-                let match_rule = signal.create_match_rule();
-                if let Some(match_rule) = &match_rule {
-                    fdo::AsyncDBusProxy::new(self)?
-                        .add_match(match_rule)
-                        .await?;
-                }
+        let signal = SignalInfo::new(Some(sender), path, interface, signal_name)?;
+        // Create the stream before subscribing so we don't miss the signal.
+        let mut stream = self.stream().await;
+        let mut subscription = SubscriptionGuard {
+            conn: self,
+            subscription_id: None,
+        };
+        let sender = if self.is_bus() {
+            subscription.subscription_id = Some(self.subscribe_signal_namespace(&signal).await?);
 
-                subscriptions.insert(
-                    hash,
-                    SignalSubscription {
-                        num_subscribers: 1,
-                        match_rule,
-                    },
-                );
+            Some(self.signal_sender_name(sender).await?)
+        } else {
+            None
+        };
+
+        let msg = loop {
+            match stream.next().await {
+                Some(Ok(msg)) if signal.matches(&msg, sender.as_deref()) => break msg,
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e),
+                None => return Err(self.closed_error()),
             }
+        };
+        if let Some(id) = subscription.subscription_id.take() {
+            self.unsubscribe_signal_by_id(id).await?;
         }
 
-        Ok(hash)
+        Ok(msg)
     }
 
-    pub(crate) async fn unsubscribe_signal<'s, E>(
-        &self,
-        sender: &'s str,
-        path: impl TryInto<ObjectPath<'s>, Error = E>,
-        interface: &'s str,
-        signal_name: &'s str,
-    ) -> Result<bool>
-    where
-        Error: From<E>,
-    {
-        let signal = SignalInfo::new(sender, path, interface, signal_name)?;
-        let hash = signal.calc_hash();
+    /// Create a stream that receives the bus' `NameAcquired` signals for this connection.
+    ///
+    /// The bus emits this signal when this connection becomes the primary owner of a name,
+    /// regardless of whether it was requested through zbus or otherwise. This includes names that
+    /// were queued for and later acquired because the previous owner released them.
+    ///
+    /// The message bus sends out the signal before the reply to the `RequestName` call that
+    /// resulted in it. Hence, if you create the stream before calling
+    /// [`fdo::AsyncDBusProxy::request_name`], the signal will already be available from the stream
+    /// once `request_name` returns with [`fdo::RequestNameReply::PrimaryOwner`].
+    pub async fn receive_name_acquired(&self) -> Result<fdo::NameAcquiredStream<'static>> {
+        fdo::AsyncDBusProxy::new(self)?
+            .receive_name_acquired()
+            .await
+    }
 
-        self.unsubscribe_signal_by_id(hash).await
+    /// Create a stream that receives the bus' `NameLost` signals for this connection.
+    ///
+    /// The bus emits this signal when this connection loses the primary ownership of a name, either
+    /// because it released the name or because another connection replaced it as the owner.
+    ///
+    /// Same as for [`Connection::receive_name_acquired`], if the stream is created before calling
+    /// [`fdo::AsyncDBusProxy::release_name`], the signal will already be available from the stream
+    /// once `release_name` returns.
+    pub async fn receive_name_lost(&self) -> Result<fdo::NameLostStream<'static>> {
+        fdo::AsyncDBusProxy::new(self)?.receive_name_lost().await
     }
 
-    pub(crate) async fn unsubscribe_signal_by_id(&self, subscription_id: u64) -> Result<bool> {
-        let mut subscriptions = self.0.signal_subscriptions.lock().await;
-        match subscriptions.get_mut(&subscription_id) {
-            Some(subscription) => {
-                subscription.num_subscribers -= 1;
+    /// Checks if this connection is currently the primary owner of the bus name `name`.
+    pub async fn is_name_owner(&self, name: &str) -> Result<bool> {
+        match fdo::AsyncDBusProxy::new(self)?.get_name_owner(name).await {
+            Ok(owner) => Ok(self.unique_name() == Some(owner.as_str())),
+            Err(fdo::Error::NameHasNoOwner(_)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
 
-                if subscription.num_subscribers == 0 {
-                    if let Some(match_rule) = &subscription.match_rule {
-                        fdo::AsyncDBusProxy::new(self)?
-                            .remove_match(match_rule.as_str())
-                            .await?;
-                    }
+    /// The well-known names registered through [`ConnectionBuilder::name`].
+    ///
+    /// [`ConnectionBuilder::name`]: ../struct.ConnectionBuilder.html#method.name
+    pub fn registered_names(&self) -> Vec<String> {
+        self.0
+            .registered_names
+            .lock()
+            .expect("lock poisoned")
+            .clone()
+    }
 
-                    subscriptions.remove(&subscription_id);
-                }
+    pub(crate) fn set_registered_names(&self, names: Vec<String>) {
+        *self.0.registered_names.lock().expect("lock poisoned") = names;
+    }
 
-                Ok(true)
+    /// Request the well-known names registered through [`ConnectionBuilder::name`] from the bus.
+    ///
+    /// The builder does that itself, unless told otherwise through
+    /// [`ConnectionBuilder::request_name_on_build`]. Calling this once the objects of the service
+    /// are served then ensures that no method call reaches the service before it's ready, and that
+    /// whoever waits for the name (e.g systemd, for services of `Type=dbus`) only sees it then.
+    ///
+    /// The names are requested in order, without queueing: this fails with [`Error::NameTaken`] for
+    /// the first one owned by another connection, leaving the names before it owned by this one.
+    /// Names already owned by this connection are fine. Fails with [`Error::Unsupported`] on
+    /// peer-to-peer connections.
+    ///
+    /// [`ConnectionBuilder::name`]: ../struct.ConnectionBuilder.html#method.name
+    /// [`ConnectionBuilder::request_name_on_build`]: ../struct.ConnectionBuilder.html#method.request_name_on_build
+    /// [`Error::NameTaken`]: ../enum.Error.html#variant.NameTaken
+    /// [`Error::Unsupported`]: ../enum.Error.html#variant.Unsupported
+    pub async fn request_registered_names(&self) -> Result<()> {
+        if !self.is_bus() {
+            return Err(Error::Unsupported);
+        }
+        let names = self.registered_names();
+        if names.is_empty() {
+            return Ok(());
+        }
+
+        let proxy = fdo::AsyncDBusProxy::new(self)?;
+        for name in names {
+            match proxy
+                .request_name(&name, fdo::RequestNameFlags::DoNotQueue.into())
+                .await?
+            {
+                fdo::RequestNameReply::PrimaryOwner | fdo::RequestNameReply::AlreadyOwner => {
+                    log::debug!(target: logging::CONNECTION, "Acquired name {}", name);
+                }
+                _ => return Err(Error::NameTaken(name)),
             }
-            None => Ok(false),
         }
+
+        Ok(())
     }
 
-    pub(crate) fn queue_unsubscribe_signal(&self, subscription_id: u64) {
+    /// Watch the ownership of the bus name `name`.
+    ///
+    /// The returned [`NameWatch`] first yields the current owner of `name`, then each change of
+    /// owner: the unique name of the new owner, or `None` when `name` has no owner. Since the
+    /// watch subscribes to the bus' `NameOwnerChanged` signals before asking for the current
+    /// owner, no change is missed in between, and the same owner is never yielded twice in a row.
+    /// The subscription is removed again when the watch is dropped.
+    ///
+    /// This works for unique names as well, which can only go away once.
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    ///# async_io::block_on(async {
+    /// use futures_util::stream::TryStreamExt;
+    /// use zbus::azync::Connection;
+    ///
+    /// let conn = Connection::new_session().await?;
+    /// let mut watch = conn.watch_name("org.freedesktop.Notifications").await?;
+    /// while let Some(owner) = watch.try_next().await? {
+    ///     match owner {
+    ///         Some(owner) => println!("Notifications available, from {}", owner),
+    ///         None => println!("Notifications unavailable"),
+    ///     }
+    /// }
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    ///# });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Unsupported`] if `self` isn't a bus connection.
+    ///
+    /// [`Error::Unsupported`]: ../enum.Error.html#variant.Unsupported
+    pub async fn watch_name(&self, name: &str) -> Result<NameWatch> {
+        if !self.is_bus() {
+            return Err(Error::Unsupported);
+        }
+
+        // Create the stream before subscribing so we don't miss any change.
+        let messages = self.stream().await;
+        let signal = SignalInfo {
+            arg0: Some(name),
+            ..SignalInfo::new(
+                Some(FDO_DBUS_SERVICE),
+                FDO_DBUS_PATH,
+                FDO_DBUS_INTERFACE,
+                "NameOwnerChanged",
+            )?
+        };
+        let mut subscription = SubscriptionGuard {
+            conn: self,
+            subscription_id: Some(self.subscribe_signal_info(&signal).await?),
+        };
+
+        // The reply isn't waited for here but picked from `messages`, which has the signals
+        // emitted before it in front of it: these are older than the owner it holds.
+        let m = self
+            .builder(MessageBuilder::method_call(FDO_DBUS_PATH, "GetNameOwner")?)?
+            .destination(FDO_DBUS_SERVICE)
+            .interface(FDO_DBUS_INTERFACE)
+            .build(&name)?;
+        let serial = self.send_message(m).await?;
+
         let conn = self.clone();
-        self.0
-            .executor
-            .spawn(async move {
-                // FIXME: Ignoring the errors here. We should at least log a message when we've
-                //        logging.
-                let _ = conn.unsubscribe_signal_by_id(subscription_id).await;
+        let name = name.to_string();
+        let mut current: Option<Option<String>> = None;
+        let stream = messages
+            .filter_map(move |msg| {
+                let owner = match msg {
+                    Ok(msg) if current.is_none() => {
+                        if conn.is_reply_to(&msg, serial) {
+                            Some(name_owner_reply(msg))
+                        } else {
+                            None
+                        }
+                    }
+                    Ok(msg) => name_owner_changed(&msg, &name).map(Ok),
+                    Err(e) => Some(Err(e)),
+                };
+                let item = match owner {
+                    Some(Ok(owner)) if current.as_ref() == Some(&owner) => None,
+                    Some(Ok(owner)) => {
+                        current = Some(owner.clone());
+
+                        Some(Ok(owner))
+                    }
+                    Some(Err(e)) => Some(Err(e)),
+                    None => None,
+                };
+
+                ready(item)
             })
-            .detach()
+            .boxed();
+
+        Ok(NameWatch {
+            stream,
+            conn: self.clone(),
+            subscription_id: subscription.subscription_id.take(),
+        })
+    }
+
+    /// The credentials of the peer owning the bus name `name`.
+    ///
+    /// The credentials of unique names are cached, since they can't change as long as the name
+    /// exists: only the first lookup of a unique name asks the bus, through its
+    /// `GetConnectionCredentials` method. The lookups made while it's ongoing wait for its outcome
+    /// rather than asking again. A name is evicted from the cache once the bus tells it went away.
+    ///
+    /// Well-known names can change owners at any time, so their lookups always ask the bus.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Unsupported`] if `self` isn't a bus connection, and with the error of
+    /// the bus if `name` has no owner.
+    ///
+    /// [`Error::Unsupported`]: ../enum.Error.html#variant.Unsupported
+    pub async fn credentials_of(&self, name: &str) -> Result<ConnectionCredentials> {
+        if !self.is_bus() {
+            return Err(Error::Unsupported);
+        }
+        if !name.starts_with(':') {
+            return self.get_connection_credentials(name).await;
+        }
+
+        let cache = &self.0.credentials;
+        // Asked before the first lookup, so the bus tells us about the name going away after it.
+        if cache.start_watching() {
+            if let Err(e) = self.watch_vanishing_names().await {
+                cache.unwatch();
+
+                return Err(e);
+            }
+        }
+
+        let slot = cache.slot(name);
+        let mut creds = slot.lock().await;
+        if let Some(creds) = &*creds {
+            return Ok(creds.clone());
+        }
+        match self.get_connection_credentials(name).await {
+            Ok(c) => {
+                *creds = Some(c.clone());
+
+                Ok(c)
+            }
+            Err(e) => {
+                cache.remove(name, &slot);
+
+                Err(e)
+            }
+        }
+    }
+
+    async fn get_connection_credentials(&self, name: &str) -> Result<ConnectionCredentials> {
+        let dict = fdo::AsyncDBusProxy::new(self)?
+            .get_connection_credentials(name)
+            .await?;
+
+        ConnectionCredentials::from_dict(dict)
     }
 
-    async fn hello_bus(&self) -> Result<()> {
+    /// Turns this connection into a monitor connection, receiving messages matching `match_rules`.
+    ///
+    /// An empty `match_rules` means all messages. This normally uses
+    /// [`fdo::AsyncMonitoringProxy::become_monitor`] but since the `org.freedesktop.DBus.Monitoring`
+    /// interface was only added in dbus-daemon 1.9.10, this falls back to adding each rule with the
+    /// `eavesdrop='true'` key, if the bus doesn't know `BecomeMonitor` or doesn't allow us to call
+    /// it. The main difference is that eavesdropping connections can still send messages, while
+    /// monitors can't.
+    ///
+    /// In both cases, the connection will receive replies to other connections' method calls.
+    /// These are never mistaken for replies to method calls made through this connection.
+    ///
+    /// Each rule in `match_rules` must not already contain the `eavesdrop` key.
+    pub async fn become_monitor(&self, match_rules: &[&str]) -> Result<()> {
+        match fdo::AsyncMonitoringProxy::new(self)?
+            .become_monitor(match_rules, 0)
+            .await
+        {
+            Ok(()) => return Ok(()),
+            Err(fdo::Error::UnknownMethod(_)) | Err(fdo::Error::AccessDenied(_)) => (),
+            Err(e) => return Err(e.into()),
+        }
+
+        // Old buses need a rule per message type to match all messages.
+        let all_types = [
+            "type='signal'",
+            "type='method_call'",
+            "type='method_return'",
+            "type='error'",
+        ];
+        let match_rules = if match_rules.is_empty() {
+            &all_types[..]
+        } else {
+            match_rules
+        };
         let dbus_proxy = fdo::AsyncDBusProxy::new(self)?;
-        let future = dbus_proxy.hello();
+        for rule in match_rules {
+            let rule = if rule.is_empty() {
+                String::from("eavesdrop='true'")
+            } else {
+                format!("{},eavesdrop='true'", rule)
+            };
+            dbus_proxy.add_match(&rule).await?;
+        }
 
-        #[cfg(feature = "internal-executor")]
-        let name = future.await?;
+        Ok(())
+    }
 
-        // With external executor, our executor is only run after the connection construction is
-        // completed and this method is (and must) run before that so we need to tick the executor
-        // ourselves in parallel to making the method call.
-        #[cfg(not(feature = "internal-executor"))]
-        let name = {
-            use futures_util::future::{select, Either};
+    /// Checks if `self` is a connection to a message bus.
+    ///
+    /// This will return `false` for p2p connections.
+    pub fn is_bus(&self) -> bool {
+        self.0.bus_conn
+    }
 
-            let executor = self.0.executor.clone();
-            let ticking_future = async move {
-                // Keep running as long as this task/future is not cancelled.
-                loop {
-                    executor.tick().await;
+    /// Assigns a serial number to `msg` that is unique to this connection.
+    ///
+    /// Nothing is done if `msg` already has a serial number, which is then returned.
+    ///
+    /// Serial numbers are assigned in increasing order and wrap around after `u32::MAX`. After the
+    /// wrap-around, 0 is skipped since it's not a valid serial number, and so are the serial
+    /// numbers of the method calls made through this connection that are still waiting for their
+    /// reply: a reply can't be mistaken for that of another call, however long it takes.
+    ///
+    /// This method can fail if `msg` is corrupt.
+    pub fn assign_serial_num(&self, msg: &mut Message) -> Result<u32> {
+        let mut serial = 0;
+        msg.modify_primary_header(|primary| {
+            serial = *primary.serial_num_or_init(|| self.next_serial());
+            Ok(())
+        })?;
+
+        Ok(serial)
+    }
+
+    /// The traffic exchanged with each peer, if enabled through [`ConnectionBuilder::peer_stats`].
+    ///
+    /// Returns an empty list otherwise. The peers are listed in no particular order.
+    ///
+    /// [`ConnectionBuilder::peer_stats`]: ../struct.ConnectionBuilder.html#method.peer_stats
+    pub fn peer_stats(&self) -> Vec<PeerStats> {
+        self.0.peer_stats.stats()
+    }
+
+    pub(crate) async fn enable_peer_stats(&self) -> Result<()> {
+        self.0.peer_stats.enable();
+        // Otherwise, it's done on `hello`.
+        if self.unique_name().is_some() {
+            self.watch_vanishing_names().await?;
+        }
+
+        Ok(())
+    }
+
+    // Ask the bus for the signals of the names going away. The reply isn't waited for, so this
+    // works even before the executor is run.
+    // Ping the peer whenever nothing was received from it for `interval`, giving up on the
+    // connection if it doesn't answer within `timeout`.
+    pub(crate) fn start_liveness_ping(&self, interval: Duration, timeout: Duration) {
+        // Only hold the connection while pinging, so that the task doesn't keep it alive.
+        let weak = Arc::downgrade(&self.0);
+        let task = self.0.executor.spawn(async move {
+            let mut pinged = Instant::now();
+            loop {
+                let last_activity = match weak.upgrade() {
+                    Some(inner) => *inner.last_activity.lock().expect("lock poisoned"),
+                    None => return,
+                };
+                let deadline = last_activity.max(pinged) + interval;
+                if Instant::now() < deadline {
+                    crate::sleep_until(deadline).await;
+
+                    continue;
                 }
-            };
 
-            futures_util::pin_mut!(future);
-            futures_util::pin_mut!(ticking_future);
+                let conn = match weak.upgrade() {
+                    Some(inner) => Self(inner),
+                    None => return,
+                };
+                pinged = Instant::now();
+                match crate::timeout(timeout, conn.ping()).await {
+                    // Any reply shows the peer is alive, even an error.
+                    Some(Ok(_)) | Some(Err(Error::MethodError(..))) => (),
+                    // The message streams get to see why.
+                    Some(Err(e)) if conn.0.closed.load(SeqCst) => {
+                        log::debug!(target: logging::CONNECTION, "Liveness ping failed: {}", e);
+
+                        return;
+                    }
+                    Some(Err(e)) => {
+                        log::warn!(target: logging::CONNECTION, "Liveness ping failed: {}", e)
+                    }
+                    None => {
+                        conn.fail(timeout);
 
-            match select(future, ticking_future).await {
-                Either::Left((res, _)) => res?,
-                Either::Right((_, _)) => unreachable!("ticking task future shouldn't finish"),
+                        return;
+                    }
+                }
             }
+        });
+        *self.0.liveness_task.lock().expect("lock poisoned") = Some(task);
+    }
+
+    async fn ping(&self) -> Result<Arc<Message>> {
+        let destination = if self.is_bus() {
+            Some(FDO_DBUS_SERVICE)
+        } else {
+            None
         };
 
+        self.call_method(
+            destination,
+            FDO_DBUS_PATH,
+            Some("org.freedesktop.DBus.Peer"),
+            "Ping",
+            &(),
+        )
+        .await
+    }
+
+    // Give up on the connection, after the peer didn't answer a liveness ping within `timeout`.
+    fn fail(&self, timeout: Duration) {
+        log::warn!(
+            target: logging::CONNECTION,
+            "Peer didn't answer a ping within {:?}, giving up on the connection",
+            timeout
+        );
+        let _ = self.0.failure.set(timeout);
+        self.0.closed.store(true, SeqCst);
+        // No more messages are received, so the message streams end, and the pending calls with
+        // them.
         self.0
-            .unique_name
-            .set(name)
-            // programmer (probably our) error if this fails.
-            .expect("Attempted to set unique_name twice");
+            .msg_receiver_task
+            .lock()
+            .expect("lock poisoned")
+            .take();
+        // Nor sent, through a `MessageSink` either.
+        let _ = self.0.raw_out_conn.lock().expect("poisoned lock").close();
+    }
+
+    async fn watch_vanishing_names(&self) -> Result<()> {
+        let m = self
+            .builder(MessageBuilder::method_call(FDO_DBUS_PATH, "AddMatch")?)?
+            .destination(FDO_DBUS_SERVICE)
+            .interface(FDO_DBUS_INTERFACE)
+            .flags(MessageFlags::NoReplyExpected.into())
+            .build(&VANISHING_NAMES_RULE)?;
+        self.send_message(m).await?;
 
         Ok(())
     }
 
-    async fn new(
-        auth: Authenticated<Async<Box<dyn Socket>>>,
-        bus_connection: bool,
-    ) -> Result<Self> {
-        let auth = auth.into_inner();
-        let out_socket = auth.conn.socket().get_ref().try_clone()?;
-        let out_conn = RawConnection::wrap(Async::new(out_socket)?);
-        let (mut msg_sender, msg_receiver) = broadcast(DEFAULT_MAX_QUEUED);
-        msg_sender.set_overflow(true);
-        let msg_receiver = msg_receiver.deactivate();
-        let (error_sender, error_receiver) = bounded(1);
-        let executor = Arc::new(Executor::new());
-        let raw_in_conn = Arc::new(Mutex::new(auth.conn));
+    /// The unique name as assigned by the message bus or `None` if not a message bus connection.
+    pub fn unique_name(&self) -> Option<&str> {
+        self.0.unique_name.get().map(|s| s.as_str())
+    }
 
-        // Start the message receiver task.
-        let msg_receiver_task =
-            MessageReceiverTask::new(raw_in_conn.clone(), msg_sender, error_sender)
-                .spawn(&executor);
+    /// The address this connection was established to.
+    ///
+    /// When connecting to a list of addresses, this is the one that worked. Returns `None` for
+    /// connections created from a socket.
+    pub fn address(&self) -> Option<&str> {
+        self.0.address.as_deref()
+    }
 
-        let connection = Self(Arc::new(ConnectionInner {
-            raw_in_conn,
-            raw_out_conn: Arc::new(sync::Mutex::new(out_conn)),
-            error_receiver,
-            server_guid: auth.server_guid,
-            cap_unix_fd: auth.cap_unix_fd,
-            bus_conn: bus_connection,
-            serial: AtomicU32::new(1),
-            unique_name: OnceCell::new(),
-            signal_subscriptions: Mutex::new(HashMap::new()),
-            msg_receiver: sync::RwLock::new(msg_receiver),
-            executor: executor.clone(),
-            msg_receiver_task: sync::Mutex::new(Some(msg_receiver_task)),
-        }));
+    /// Max number of messages to queue, for each [`stream`].
+    ///
+    /// [`stream`]: #method.stream
+    pub fn max_queued(&self) -> usize {
+        self.0.fanout.capacity()
+    }
+
+    /// Set the max number of messages to queue, for each [`stream`].
+    ///
+    /// This also applies to the existing streams, except those from [`stream_with_policy`].
+    ///
+    /// Since typically you'd want to set this at instantiation time, this method takes ownership
+    /// of `self` and returns an owned `Connection` instance so you can use the builder pattern to
+    /// set the value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    ///# use std::error::Error;
+    ///# use zbus::azync::Connection;
+    ///# use async_io::block_on;
+    ///#
+    ///# block_on(async {
+    /// let conn = Connection::new_session()
+    ///     .await?
+    ///     .set_max_queued(30);
+    /// assert_eq!(conn.max_queued(), 30);
+    ///
+    ///#     Ok::<(), zbus::Error>(())
+    ///# });
+    ///#
+    /// // Do something useful with `conn`..
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`stream`]: #method.stream
+    /// [`stream_with_policy`]: #method.stream_with_policy
+    pub fn set_max_queued(self, max: usize) -> Self {
+        self.0.fanout.set_capacity(max);
+
+        self
+    }
+
+    /// The server's GUID.
+    pub fn server_guid(&self) -> &str {
+        self.0.server_guid.as_str()
+    }
+
+    #[cfg(any(doc, not(feature = "internal-executor")))]
+    /// The underlying executor.
+    ///
+    /// This method is available when built with the default `internal-executor` feature disabled.
+    /// Since zbus will not spawn thread internally to run the executor in this case, you're
+    /// responsible to continuously [tick the executor][tte]. Failure to do so will result in hangs.
+    ///
+    /// If a task you spawned on the executor panics, the panic is propagated to the `tick` call
+    /// running it. The other tasks, including the internal ones of zbus, are not affected so you
+    /// can catch the panic (e.g with [`FutureExt::catch_unwind`]) and keep ticking. Should an
+    /// internal task of zbus panic, the connection is considered closed and the message streams
+    /// receive an error.
+    ///
+    /// # Examples
+    ///
+    /// Here is how one would typically run the zbus executor through tokio's single-threaded
+    /// scheduler:
+    ///
+    /// ```
+    /// use zbus::azync::Connection;
+    /// use tokio::runtime;
+    ///
+    ///# #[cfg(not(feature = "internal-executor"))]
+    /// runtime::Builder::new_current_thread()
+    ///        .build()
+    ///        .unwrap()
+    ///        .block_on(async {
+    ///     let conn = Connection::new_session().await.unwrap();
+    ///     {
+    ///        let conn = conn.clone();
+    ///        tokio::task::spawn(async move {
+    ///            loop {
+    ///                conn.executor().tick().await;
+    ///            }
+    ///        });
+    ///     }
+    ///
+    ///     // All your other async code goes here.
+    /// });
+    /// ```
+    ///
+    /// [tte]: https://docs.rs/async-executor/1.4.1/async_executor/struct.Executor.html#method.tick
+    /// [`FutureExt::catch_unwind`]: https://docs.rs/futures/0.3/futures/future/trait.FutureExt.html#method.catch_unwind
+    pub fn executor(&self) -> &Executor<'static> {
+        &self.0.executor
+    }
+
+    /// Get the raw file descriptor of this connection.
+    ///
+    /// The connections of an in-memory pair have none, so for them, this is -1.
+    pub async fn as_raw_fd(&self) -> RawFd {
+        (self.0.raw_in_conn.lock().await.socket()).as_raw_fd()
+    }
+
+    pub(crate) async fn subscribe_signal<'s, E>(
+        &self,
+        sender: &'s str,
+        path: impl TryInto<ObjectPath<'s>, Error = E>,
+        interface: &'s str,
+        signal_name: &'s str,
+    ) -> Result<u64>
+    where
+        E: Into<Error>,
+    {
+        let signal = SignalInfo::new(Some(sender), path, interface, signal_name)?;
+
+        self.subscribe_signal_info(&signal).await
+    }
+
+    async fn subscribe_signal_info(&self, signal: &SignalInfo<'_>) -> Result<u64> {
+        let hash = signal.calc_hash();
+        let mut subscriptions = self.0.signal_subscriptions.lock().await;
+        match subscriptions.get_mut(&hash) {
+            Some(subscription) => subscription.num_subscribers += 1,
+            None => {
+                let match_rule = signal.create_match_rule();
+                if let Some(match_rule) = &match_rule {
+                    fdo::AsyncDBusProxy::new(self)?
+                        .add_match(match_rule)
+                        .await?;
+                }
+
+                subscriptions.insert(
+                    hash,
+                    SignalSubscription {
+                        num_subscribers: 1,
+                        match_rule,
+                    },
+                );
+            }
+        }
+
+        Ok(hash)
+    }
+
+    // Same as `subscribe_signal` but if there is already a subscription for the same signal on an
+    // ancestor of `signal.path`, that subscription is used instead.
+    async fn subscribe_signal_namespace(&self, signal: &SignalInfo<'_>) -> Result<u64> {
+        {
+            let mut subscriptions = self.0.signal_subscriptions.lock().await;
+            for namespace in signal.namespaces() {
+                let hash = namespace.calc_hash();
+                if let Some(subscription) = subscriptions.get_mut(&hash) {
+                    subscription.num_subscribers += 1;
+
+                    return Ok(hash);
+                }
+            }
+        }
+
+        self.subscribe_signal_info(signal).await
+    }
+
+    // Signal messages only carry the unique name of the sender, so resolve well-known names.
+    async fn signal_sender_name(&self, sender: &str) -> Result<String> {
+        if sender.starts_with(':') || sender == FDO_DBUS_SERVICE {
+            Ok(sender.to_string())
+        } else {
+            Ok(fdo::AsyncDBusProxy::new(self)?
+                .get_name_owner(sender)
+                .await?)
+        }
+    }
+
+    pub(crate) async fn unsubscribe_signal_by_id(&self, subscription_id: u64) -> Result<bool> {
+        let mut subscriptions = self.0.signal_subscriptions.lock().await;
+        match subscriptions.get_mut(&subscription_id) {
+            Some(subscription) => {
+                subscription.num_subscribers -= 1;
+
+                if subscription.num_subscribers == 0 {
+                    if let Some(match_rule) = &subscription.match_rule {
+                        fdo::AsyncDBusProxy::new(self)?
+                            .remove_match(match_rule.as_str())
+                            .await?;
+                    }
+
+                    subscriptions.remove(&subscription_id);
+                }
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub(crate) fn queue_unsubscribe_signal(&self, subscription_id: u64) {
+        let conn = self.clone();
+        self.0
+            .executor
+            .spawn(async move {
+                // FIXME: Ignoring the errors here. We should at least log a message when we've
+                //        logging.
+                let _ = conn.unsubscribe_signal_by_id(subscription_id).await;
+            })
+            .detach()
+    }
+
+    /// Say `Hello` to the bus, to get our unique name.
+    ///
+    /// Bus connections do that as part of their creation, unless it was delayed through
+    /// [`ConnectionBuilder::delay_hello`]. Until then, only [`send_message`] and friends can be
+    /// used, which makes it possible to exchange messages with the bus before it gets to know us.
+    /// Everything needing a unique name, such as [`call_method`] or [`emit_signal`], fails with
+    /// [`Error::NoUniqueName`].
+    ///
+    /// Returns the unique name right away if we already have one, and [`Error::Unsupported`] for
+    /// peer-to-peer connections.
+    ///
+    /// [`ConnectionBuilder::delay_hello`]: ../struct.ConnectionBuilder.html#method.delay_hello
+    /// [`send_message`]: struct.Connection.html#method.send_message
+    /// [`call_method`]: struct.Connection.html#method.call_method
+    /// [`emit_signal`]: struct.Connection.html#method.emit_signal
+    /// [`Error::NoUniqueName`]: ../enum.Error.html#variant.NoUniqueName
+    /// [`Error::Unsupported`]: ../enum.Error.html#variant.Unsupported
+    pub async fn hello(&self) -> Result<&str> {
+        if !self.is_bus() {
+            return Err(Error::Unsupported);
+        }
+        // Saying hello twice gets us disconnected.
+        let _lock = self.0.hello_lock.lock().await;
+        if let Some(name) = self.unique_name() {
+            return Ok(name);
+        }
+
+        // Not going through `fdo::DBusProxy`, as `call_method` requires the unique name.
+        let m = MessageBuilder::method_call(FDO_DBUS_PATH, "Hello")?
+            .destination(FDO_DBUS_SERVICE)
+            .interface(FDO_DBUS_INTERFACE)
+            .endian_sig(self.0.endian_sig)
+            .build(&())?;
+        let future = self.call_method_message(m);
+
+        #[cfg(feature = "internal-executor")]
+        let reply = future.await?;
+
+        // With external executor, our executor is only run after the connection construction is
+        // completed and this method is (usually) run before that so we need to tick the executor
+        // ourselves in parallel to making the method call.
+        #[cfg(not(feature = "internal-executor"))]
+        let reply = {
+            use futures_util::future::{select, Either};
+
+            let executor = self.0.executor.clone();
+            let ticking_future = async move {
+                // Keep running as long as this task/future is not cancelled.
+                loop {
+                    tick(&executor).await;
+                }
+            };
+
+            futures_util::pin_mut!(future);
+            futures_util::pin_mut!(ticking_future);
+
+            match select(future, ticking_future).await {
+                Either::Left((res, _)) => res?,
+                Either::Right((_, _)) => unreachable!("ticking task future shouldn't finish"),
+            }
+        };
+
+        let name: String = reply.body()?;
+        log::debug!(target: logging::CONNECTION, "Unique name on the bus: {}", name);
+        self.0
+            .unique_name
+            .set(name)
+            // programmer (probably our) error if this fails.
+            .expect("Attempted to set unique_name twice");
+        self.0.unique_name_acquired.notify(usize::MAX);
+        if self.0.peer_stats.is_enabled() {
+            self.watch_vanishing_names().await?;
+        }
+
+        Ok(self.unique_name().expect("unique_name just set"))
+    }
+
+    /// Wait for the bus to give us a unique name.
+    ///
+    /// This is only useful if saying [`hello`] was delayed, e.g to wait for another task to be done
+    /// with its own exchange with the bus. Resolves as soon as we have a unique name.
+    ///
+    /// Returns [`Error::Unsupported`] for peer-to-peer connections, since they never get one.
+    ///
+    /// [`hello`]: struct.Connection.html#method.hello
+    /// [`Error::Unsupported`]: ../enum.Error.html#variant.Unsupported
+    pub async fn unique_name_acquired(&self) -> Result<&str> {
+        if !self.is_bus() {
+            return Err(Error::Unsupported);
+        }
+
+        loop {
+            // Listen before checking, not to miss a notification in between.
+            let listener = self.0.unique_name_acquired.listen();
+            if let Some(name) = self.unique_name() {
+                return Ok(name);
+            }
+            listener.await;
+        }
+    }
+
+    // The sender of our messages, failing for bus connections yet to say `Hello`.
+    fn sender(&self) -> Result<Option<&str>> {
+        match self.unique_name() {
+            None if self.is_bus() => Err(Error::NoUniqueName),
+            name => Ok(name),
+        }
+    }
+
+    // Set up `builder` for a message sent from this connection.
+    pub(crate) fn builder<'b>(&'b self, builder: MessageBuilder<'b>) -> Result<MessageBuilder<'b>> {
+        Ok(builder
+            .optional_fields(self.sender()?, None, None)
+            .endian_sig(self.0.endian_sig))
+    }
+
+    pub(crate) async fn new(
+        auth: Authenticated<Async<Box<dyn Socket>>>,
+        bus_connection: bool,
+        delay_hello: bool,
+        endian_sig: EndianSig,
+    ) -> Result<Self> {
+        let auth = auth.into_inner();
+        let auth = handshake::Authenticated {
+            conn: auth.conn.map_socket(AsyncSocket::from),
+            server_guid: auth.server_guid,
+            cap_unix_fd: auth.cap_unix_fd,
+            address: auth.address,
+        };
+
+        Self::with_socket(auth, bus_connection, delay_hello, endian_sig).await
+    }
+
+    // Set up a connection on the socket of `auth`, authenticated already.
+    async fn with_socket(
+        auth: handshake::Authenticated<AsyncSocket>,
+        bus_connection: bool,
+        delay_hello: bool,
+        endian_sig: EndianSig,
+    ) -> Result<Self> {
+        log::debug!(
+            target: logging::CONNECTION,
+            "Connection established to {} (address: {}, bus: {}, fd passing: {})",
+            auth.server_guid,
+            auth.address.as_deref().unwrap_or("none"),
+            bus_connection,
+            auth.cap_unix_fd,
+        );
+        let mut out_conn = RawConnection::wrap(auth.conn.socket().duplicate()?);
+        out_conn.set_body_compression(auth.conn.body_compression());
+        let fanout = Arc::new(Fanout::new(DEFAULT_MAX_QUEUED));
+        let (error_sender, error_receiver) = bounded(1);
+        let executor = Arc::new(Executor::new());
+        let raw_in_conn = Arc::new(Mutex::new(auth.conn));
+        let closed = Arc::new(AtomicBool::new(false));
+        let last_activity = Arc::new(sync::Mutex::new(Instant::now()));
+        let peer_stats = Arc::new(PeerStatsTracker::default());
+        let credentials = Arc::new(CredentialsCache::default());
+
+        // Start the message receiver task.
+        let msg_receiver_task = MessageReceiverTask::new(
+            raw_in_conn.clone(),
+            fanout.clone(),
+            error_sender,
+            closed.clone(),
+            last_activity.clone(),
+            peer_stats.clone(),
+            credentials.clone(),
+        )
+        .spawn(&executor);
+
+        let connection = Self(Arc::new(ConnectionInner {
+            raw_in_conn,
+            raw_out_conn: Arc::new(sync::Mutex::new(out_conn)),
+            out_queue_capacity: AtomicUsize::new(DEFAULT_OUT_QUEUE_CAPACITY),
+            background_flush: Arc::new(AtomicBool::new(false)),
+            error_receiver,
+            closed,
+            last_activity,
+            failure: OnceCell::new(),
+            liveness_task: sync::Mutex::new(None),
+            server_guid: auth.server_guid,
+            cap_unix_fd: auth.cap_unix_fd,
+            bus_conn: bus_connection,
+            endian_sig,
+            address: auth.address,
+            serial: AtomicU32::new(1),
+            pending_replies: sync::Mutex::new(HashSet::new()),
+            #[cfg(any(test, feature = "test-util"))]
+            serial_allocator: OnceCell::new(),
+            unique_name: OnceCell::new(),
+            hello_lock: Mutex::new(()),
+            unique_name_acquired: Event::new(),
+            signal_subscriptions: Mutex::new(HashMap::new()),
+            fanout,
+            executor: executor.clone(),
+            msg_receiver_task: sync::Mutex::new(Some(msg_receiver_task)),
+            serialization_worker: sync::Mutex::new(None),
+            peer_stats,
+            credentials,
+            registered_names: sync::Mutex::new(vec![]),
+        }));
+
+        #[cfg(feature = "internal-executor")]
+        std::thread::Builder::new()
+            .name("zbus::azync::Connection::receive_msg".into())
+            .spawn(move || {
+                block_on(async move {
+                    // Run as long as there is a task to run.
+                    while !executor.is_empty() {
+                        tick(&executor).await;
+                    }
+                })
+            })?;
+
+        if !bus_connection || delay_hello {
+            return Ok(connection);
+        }
+
+        // Now that the server has approved us, we must send the bus Hello, as per specs
+        connection.hello().await?;
+
+        Ok(connection)
+    }
+
+    // The counter wraps around after `u32::MAX`, skipping 0 (an invalid serial number) and the
+    // serial numbers of the calls still waiting for their reply, so their reply is never mistaken
+    // for that of a newer call.
+    fn next_serial(&self) -> u32 {
+        let pending = self.0.pending_replies.lock().expect("lock poisoned");
+        loop {
+            #[cfg(any(test, feature = "test-util"))]
+            let serial = match self.0.serial_allocator.get() {
+                Some(allocator) => allocator.next(),
+                None => self.0.serial.fetch_add(1, SeqCst),
+            };
+            #[cfg(not(any(test, feature = "test-util")))]
+            let serial = self.0.serial.fetch_add(1, SeqCst);
+            if serial != 0 && !pending.contains(&serial) {
+                return serial;
+            }
+        }
+    }
+
+    // Assign a serial number to the method call `msg`, reserved until the returned guard is
+    // dropped, once the reply is in.
+    fn reserve_reply_serial(&self, msg: &mut Message) -> Result<PendingReply<'_>> {
+        let serial = self.assign_serial_num(msg)?;
+        self.0
+            .pending_replies
+            .lock()
+            .expect("lock poisoned")
+            .insert(serial);
+
+        Ok(PendingReply { conn: self, serial })
+    }
+
+    #[cfg(any(test, feature = "test-util"))]
+    pub(crate) fn set_next_serial(&self, serial: u32) {
+        self.0.serial.store(serial, SeqCst);
+    }
+
+    #[cfg(any(test, feature = "test-util"))]
+    pub(crate) fn set_serial_allocator(&self, allocator: SerialAllocator) {
+        self.0
+            .serial_allocator
+            .set(allocator)
+            .expect("Attempted to set serial_allocator twice");
+    }
+
+    /// Create a `Connection` to the session/user message bus.
+    pub async fn new_session() -> Result<Self> {
+        Self::new(
+            Authenticated::session().await?,
+            true,
+            false,
+            NATIVE_ENDIAN_SIG,
+        )
+        .await
+    }
+
+    /// Create a `Connection` to the system-wide message bus.
+    pub async fn new_system() -> Result<Self> {
+        Self::new(
+            Authenticated::system().await?,
+            true,
+            false,
+            NATIVE_ENDIAN_SIG,
+        )
+        .await
+    }
+
+    /// Create a `Connection` to the message bus that started this process.
+    ///
+    /// Services started through D-Bus activation should connect to this bus, rather than assuming
+    /// it's the session or system bus. Its address is taken from the `DBUS_STARTER_ADDRESS`
+    /// environment variable, or else the session or system bus is used, depending on
+    /// `DBUS_STARTER_BUS_TYPE`. Fails with [`Error::Address`] if neither is set, i-e the process
+    /// wasn't activated.
+    ///
+    /// [`Error::Address`]: ../enum.Error.html#variant.Address
+    pub async fn new_starter() -> Result<Self> {
+        Self::new(
+            Authenticated::starter().await?,
+            true,
+            false,
+            NATIVE_ENDIAN_SIG,
+        )
+        .await
+    }
+
+    /// Create a `Connection` for the given [D-Bus address].
+    ///
+    /// The address can be a `;`-separated list of addresses, in which case they're tried in order
+    /// until one works. If none does, the returned error lists the error for each address. The
+    /// same goes for [`new_session`] and [`new_system`], whose addresses can be lists as well. Use
+    /// [`address`] to know which address the connection was established to.
+    ///
+    /// The `unix`, `tcp`, `unixexec` and `launchd` transports are supported, unless disabled
+    /// through their cargo feature. For `unixexec`, the executed process is killed, if need be,
+    /// and reaped when the connection is closed. File descriptors can't be passed over `tcp` and
+    /// `unixexec` connections. For `launchd`, the socket path is taken from the environment
+    /// variable of the address, if set, or else asked to `launchctl getenv` once per process. The
+    /// `ZBUS_LAUNCHD_SOCKET` environment variable overrides it.
+    ///
+    /// [`new_session`]: struct.Connection.html#method.new_session
+    /// [`new_system`]: struct.Connection.html#method.new_system
+    /// [`address`]: struct.Connection.html#method.address
+    /// [D-Bus address]: https://dbus.freedesktop.org/doc/dbus-specification.html#addresses
+    pub async fn new_for_address(address: &str, bus_connection: bool) -> Result<Self> {
+        Self::new(
+            Authenticated::for_address(address).await?,
+            bus_connection,
+            false,
+            NATIVE_ENDIAN_SIG,
+        )
+        .await
+    }
+
+    /// Get the connection to the session bus that is shared by the whole process.
+    ///
+    /// Each call of [`new_session`] creates a new connection, with its own socket, unique name,
+    /// message receiver task and match rules. Libraries that don't need a connection of their own
+    /// should use this method instead, so a process ends up with a single connection to the
+    /// session bus. The shared connection is created by the first call, and is kept for as long as
+    /// any clone of it is alive. Once all of them are dropped, or the connection is closed by the
+    /// bus, the next call creates a new connection.
+    ///
+    /// Use [`new_session`] if you need a connection of your own, e.g to become a monitor or not
+    /// to see the match rules and names of other parts of the process.
+    ///
+    /// [`new_session`]: struct.Connection.html#method.new_session
+    pub async fn shared_session() -> Result<Self> {
+        Self::shared(&SHARED_SESSION, Self::new_session).await
+    }
+
+    /// Get the connection to the system bus that is shared by the whole process.
+    ///
+    /// This is the system bus counterpart of [`shared_session`].
+    ///
+    /// [`shared_session`]: struct.Connection.html#method.shared_session
+    pub async fn shared_system() -> Result<Self> {
+        Self::shared(&SHARED_SYSTEM, Self::new_system).await
+    }
+
+    /// Make `conn` the connection returned by [`shared_session`].
+    ///
+    /// This is mainly useful for tests, to have all the code under test use a connection to a bus
+    /// of the test's own. As for any shared connection, `conn` is only kept while it is alive.
+    ///
+    /// [`shared_session`]: struct.Connection.html#method.shared_session
+    pub async fn set_shared_session(conn: &Self) {
+        *SHARED_SESSION.lock().await = Arc::downgrade(&conn.0);
+    }
+
+    /// Make `conn` the connection returned by [`shared_system`].
+    ///
+    /// See [`set_shared_session`] for details.
+    ///
+    /// [`shared_system`]: struct.Connection.html#method.shared_system
+    /// [`set_shared_session`]: struct.Connection.html#method.set_shared_session
+    pub async fn set_shared_system(conn: &Self) {
+        *SHARED_SYSTEM.lock().await = Arc::downgrade(&conn.0);
+    }
+
+    async fn shared<F, C>(shared: &SharedConnection, connect: C) -> Result<Self>
+    where
+        C: FnOnce() -> F,
+        F: Future<Output = Result<Self>>,
+    {
+        // Keep the lock while connecting, so concurrent callers don't connect twice.
+        let mut shared = shared.lock().await;
+        if let Some(inner) = shared.upgrade() {
+            if !inner.closed.load(SeqCst) {
+                return Ok(Self(inner));
+            }
+        }
+
+        let conn = connect().await?;
+        *shared = Arc::downgrade(&conn.0);
+
+        Ok(conn)
+    }
+}
+
+/// Create two peer-to-peer connections to each other, over an in-memory channel.
+///
+/// Nothing goes through the OS: the messages are handed from one connection to the other in
+/// memory, and the file descriptors sent along are duplicated. There's no handshake either, so
+/// both connections are ready to use right away. This is handy for tests, and for isolating
+/// components of the same process from each other.
+///
+/// Use [`in_memory_pair_with`] for a channel with latency or a limited byte rate.
+///
+/// # Example
+///
+/// ```
+///# use std::error::Error;
+///# async_io::block_on(async {
+/// use futures_util::stream::TryStreamExt;
+///
+/// let (service, client) = zbus::azync::in_memory_pair().await?;
+/// let mut stream = client.stream().await;
+/// service
+///     .emit_signal(None, "/org/zbus/Service", "org.zbus.Service", "Ready", &())
+///     .await?;
+/// let signal = stream.try_next().await?.unwrap();
+/// assert_eq!(signal.to_string(), "Signal Ready");
+///# Ok::<(), Box<dyn Error>>(())
+///# }).unwrap();
+/// ```
+///
+/// [`in_memory_pair_with`]: fn.in_memory_pair_with.html
+pub async fn in_memory_pair() -> Result<(Connection, Connection)> {
+    in_memory_pair_with(InMemoryOptions::default()).await
+}
+
+/// Create two peer-to-peer connections to each other, over an in-memory channel with the given
+/// `options`.
+///
+/// See [`in_memory_pair`] for details.
+///
+/// [`in_memory_pair`]: fn.in_memory_pair.html
+pub async fn in_memory_pair_with(options: InMemoryOptions) -> Result<(Connection, Connection)> {
+    let guid = Guid::generate();
+    let (socket0, socket1) = in_memory::pair(&options);
+    let connect = |socket| {
+        let auth = handshake::Authenticated {
+            conn: RawConnection::wrap(AsyncSocket::InMemory(socket)),
+            server_guid: guid.clone(),
+            cap_unix_fd: true,
+            address: None,
+        };
+
+        Connection::with_socket(auth, false, false, NATIVE_ENDIAN_SIG)
+    };
+
+    Ok((connect(socket0).await?, connect(socket1).await?))
+}
+
+/// A [`futures_sink::Sink`] implementation that consumes [`Message`] instances.
+///
+/// Use [`Connection::sink`] to create an instance of this type.
+///
+/// # Caveats
+///
+/// At the moment, a simultaneous [flush request] from multiple tasks/threads could
+/// potentially create a busy loop, thus wasting CPU time. This limitation may be removed in the
+/// future.
+///
+/// [flush request]: https://docs.rs/futures/0.3.15/futures/sink/trait.SinkExt.html#method.flush
+pub struct MessageSink {
+    raw_conn: Arc<sync::Mutex<DynSocketConnection>>,
+    cap_unix_fd: bool,
+}
+
+assert_impl_all!(MessageSink: Send, Sync, Unpin);
+
+impl MessageSink {
+    fn flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.flush_with(cx, false)
+    }
+
+    // Like `flush` but when `coalesce` is `true`, always write multiple messages at once.
+    fn flush_with(&mut self, cx: &mut Context<'_>, coalesce: bool) -> Poll<Result<()>> {
+        loop {
+            let mut raw_conn = self.raw_conn.lock().unwrap();
+            let res = if coalesce {
+                raw_conn.try_flush_coalesced()
+            } else {
+                raw_conn.try_flush()
+            };
+            match res {
+                Ok(()) => return Poll::Ready(Ok(())),
+                Err(e) => {
+                    if e.kind() == ErrorKind::WouldBlock {
+                        let poll = raw_conn.socket().poll_writable(cx);
+
+                        match poll {
+                            Poll::Pending => return Poll::Pending,
+                            // Guess socket became ready already so let's try it again.
+                            Poll::Ready(Ok(_)) => continue,
+                            Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                        }
+                    } else {
+                        return Poll::Ready(Err(Error::Io(e)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl futures_sink::Sink<Message> for MessageSink {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        // TODO: We should have a max queue length in raw::Socket for outgoing messages.
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: Message) -> Result<()> {
+        if !msg.fds().is_empty() && !self.cap_unix_fd {
+            return Err(Error::Unsupported);
+        }
+
+        self.raw_conn.lock().unwrap().enqueue_message(msg);
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let sink = self.get_mut();
+        match sink.flush(cx) {
+            Poll::Ready(Ok(_)) => (),
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        }
+
+        Poll::Ready((sink.raw_conn.lock().unwrap()).close())
+    }
+}
+
+/// A [`stream::Stream`] implementation that yields [`Message`] items.
+///
+/// Use [`Connection::stream`] to create an instance of this type.
+pub struct MessageStream {
+    stream: stream::BoxStream<'static, Result<Arc<Message>>>,
+    queue: Arc<Queue>,
+}
+
+assert_impl_all!(MessageStream: Send, Unpin);
+
+impl MessageStream {
+    /// The number of messages this stream dropped so far, because its queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped()
+    }
+}
+
+impl Drop for MessageStream {
+    fn drop(&mut self) {
+        // Don't keep the message receiver task waiting for room in the queue, if it is.
+        self.queue.close();
+    }
+}
+
+impl stream::Stream for MessageStream {
+    type Item = Result<Arc<Message>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        stream::Stream::poll_next(self.get_mut().stream.as_mut(), cx)
+    }
+}
+
+/// A [`stream::Stream`] implementation that yields the arguments of a signal.
+///
+/// Unlike [`SignalStream`], this is not tied to a [`Proxy`]: it receives the signal from any object
+/// path and, optionally, from any sender. Use [`TypedSignalStream::for_signal`] to create an
+/// instance of this type.
+///
+/// [`SignalStream`]: struct.SignalStream.html
+/// [`Proxy`]: struct.Proxy.html
+/// [`TypedSignalStream::for_signal`]: struct.TypedSignalStream.html#method.for_signal
+pub struct TypedSignalStream<T> {
+    stream: stream::BoxStream<'static, Result<(T, Arc<Message>)>>,
+    conn: Connection,
+    subscription_id: Option<u64>,
+    // The subscription to the `NameOwnerChanged` signal of the well-known sender.
+    owner_subscription_id: Option<u64>,
+}
+
+assert_impl_all!(TypedSignalStream<()>: Send, Unpin);
+
+impl<T> TypedSignalStream<T>
+where
+    T: DeserializeOwned + Type + Send + 'static,
+{
+    /// Create a stream for the `member` signal of `interface`.
+    ///
+    /// Each item holds the deserialized body of a signal message, alongside the message itself
+    /// for inspecting its header. If the body of a signal doesn't match `T`, the stream yields an
+    /// [`fdo::Error::InvalidSignature`] error naming both signatures, and goes on with the next
+    /// signals.
+    ///
+    /// If `sender` is given, only the signals it emits are yielded. Since signal messages only
+    /// carry the unique name of their sender, a well-known name is followed to the unique name of
+    /// its current owner: the stream yields nothing while the name has no owner, and the signals of
+    /// the new owner once it changes hands. The bus itself (`org.freedesktop.DBus`) is the sender
+    /// of its own signals. On a peer-to-peer connection, messages carry no sender and `sender` is
+    /// not checked.
+    ///
+    /// If `conn` is a bus connection, a match rule for the signal is added on the bus, and another
+    /// one for the `NameOwnerChanged` signal of a well-known `sender`. They are removed again when
+    /// the stream is dropped.
+    ///
+    /// [`fdo::Error::InvalidSignature`]: fdo/enum.Error.html#variant.InvalidSignature
+    pub async fn for_signal(
+        conn: &Connection,
+        interface: &str,
+        member: &str,
+        sender: Option<&str>,
+    ) -> Result<Self> {
+        let signal = SignalInfo::new(sender, "/", interface, member)?;
+        // Create the stream before subscribing so we don't miss any signal.
+        let messages = conn.stream().await;
+        let mut subscription = SubscriptionGuard {
+            conn,
+            subscription_id: None,
+        };
+        let mut owner_subscription = SubscriptionGuard {
+            conn,
+            subscription_id: None,
+        };
+        // The name to follow the owner of, and the serial of the `GetNameOwner` call.
+        let mut tracked = None;
+        // `None` while the owner isn't known yet, `Some(None)` while the name has no owner.
+        let mut owner: Option<Option<String>> = Some(None);
+        if conn.is_bus() {
+            subscription.subscription_id = Some(conn.subscribe_signal_info(&signal).await?);
+
+            match sender {
+                Some(sender) if sender.starts_with(':') || sender == FDO_DBUS_SERVICE => {
+                    owner = Some(Some(sender.to_string()));
+                }
+                Some(sender) => {
+                    let owner_changed = SignalInfo {
+                        arg0: Some(sender),
+                        ..SignalInfo::new(
+                            Some(FDO_DBUS_SERVICE),
+                            FDO_DBUS_PATH,
+                            FDO_DBUS_INTERFACE,
+                            "NameOwnerChanged",
+                        )?
+                    };
+                    owner_subscription.subscription_id =
+                        Some(conn.subscribe_signal_info(&owner_changed).await?);
+
+                    // Like in `watch_name`, the reply is picked from `messages`, so the changes
+                    // of owner emitted before it are not applied over the owner it holds. The
+                    // signals received before it can't be told apart and are skipped.
+                    let m = conn
+                        .builder(MessageBuilder::method_call(FDO_DBUS_PATH, "GetNameOwner")?)?
+                        .destination(FDO_DBUS_SERVICE)
+                        .interface(FDO_DBUS_INTERFACE)
+                        .build(&sender)?;
+                    let serial = conn.send_message(m).await?;
+                    tracked = Some((sender.to_string(), serial));
+                    owner = None;
+                }
+                None => (),
+            }
+        }
+        let check_sender = sender.is_some() && conn.is_bus();
+
+        let owner_conn = conn.clone();
+        let interface = interface.to_string();
+        let member = member.to_string();
+        let stream = messages
+            .filter_map(move |msg| {
+                let msg = match msg {
+                    Ok(msg) => msg,
+                    Err(e) => return ready(Some(Err(e))),
+                };
+                if let Some((name, serial)) = &tracked {
+                    if owner.is_none() {
+                        if !owner_conn.is_reply_to(&msg, *serial) {
+                            return ready(None);
+                        }
+
+                        return match name_owner_reply(msg) {
+                            Ok(o) => {
+                                owner = Some(o);
+
+                                ready(None)
+                            }
+                            Err(e) => {
+                                owner = Some(None);
+
+                                ready(Some(Err(e)))
+                            }
+                        };
+                    }
+
+                    if let Some(o) = name_owner_changed(&msg, name) {
+                        owner = Some(o);
+                    }
+                }
+
+                let signal = SignalInfo {
+                    sender: None,
+                    path: ObjectPath::from_str_unchecked("/"),
+                    interface: &interface,
+                    signal_name: &member,
+                    arg0: None,
+                };
+                let sender = match &owner {
+                    _ if !check_sender => None,
+                    Some(Some(owner)) => Some(owner.as_str()),
+                    // Nobody to receive the signal from.
+                    _ => return ready(None),
+                };
+                let item = if signal.matches(&msg, sender) {
+                    Some(signal_args(&msg, &signal).map(|args| (args, msg)))
+                } else {
+                    None
+                };
+
+                ready(item)
+            })
+            .boxed();
+
+        Ok(Self {
+            stream,
+            conn: conn.clone(),
+            subscription_id: subscription.subscription_id.take(),
+            owner_subscription_id: owner_subscription.subscription_id.take(),
+        })
+    }
+}
+
+// Deserializes the body of the `signal` message `msg`.
+fn signal_args<T>(msg: &Message, signal: &SignalInfo<'_>) -> Result<T>
+where
+    T: DeserializeOwned + Type,
+{
+    match msg.body() {
+        Ok(args) => Ok(args),
+        Err(MessageError::UnmatchedBodySignature) => {
+            let actual = msg
+                .body_signature()
+                .map(|sig| sig.to_string())
+                .unwrap_or_default();
+
+            Err(fdo::Error::InvalidSignature(format!(
+                "`{}.{}` signal has body signature '{}' instead of '{}'",
+                signal.interface,
+                signal.signal_name,
+                actual,
+                T::signature(),
+            ))
+            .into())
+        }
+        Err(e) => Err(Error::Message(e)),
+    }
+}
+
+impl<T> stream::Stream for TypedSignalStream<T> {
+    type Item = Result<(T, Arc<Message>)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        stream::Stream::poll_next(self.get_mut().stream.as_mut(), cx)
+    }
+}
+
+impl<T> Drop for TypedSignalStream<T> {
+    fn drop(&mut self) {
+        let ids = self.subscription_id.take().into_iter();
+        for id in ids.chain(self.owner_subscription_id.take()) {
+            self.conn.queue_unsubscribe_signal(id);
+        }
+    }
+}
+
+impl<T> AsyncDrop for TypedSignalStream<T> {
+    fn async_drop<'d>(mut self) -> BoxFuture<'d, Result<()>>
+    where
+        Self: 'd,
+    {
+        let conn = self.conn.clone();
+        let subscription_id = self.subscription_id.take();
+        let owner_subscription_id = self.owner_subscription_id.take();
+
+        async move {
+            for id in subscription_id.into_iter().chain(owner_subscription_id) {
+                conn.unsubscribe_signal_by_id(id).await?;
+            }
+
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// A [`stream::Stream`] implementation that yields the owner of a bus name, as it changes.
+///
+/// Each item is the unique name of the owner, or `None` when the name has no owner. Use
+/// [`Connection::watch_name`] to create an instance of this type.
+///
+/// [`Connection::watch_name`]: struct.Connection.html#method.watch_name
+pub struct NameWatch {
+    stream: stream::BoxStream<'static, Result<Option<String>>>,
+    conn: Connection,
+    subscription_id: Option<u64>,
+}
+
+assert_impl_all!(NameWatch: Send, Unpin);
+
+impl NameWatch {
+    /// Wait until the name is owned, returning the unique name of its owner.
+    ///
+    /// Returns right away if it's owned already.
+    pub async fn wait_until_owned(&mut self) -> Result<String> {
+        loop {
+            match self.stream.next().await {
+                Some(Ok(Some(owner))) => return Ok(owner),
+                Some(Ok(None)) => continue,
+                Some(Err(e)) => return Err(e),
+                None => {
+                    return Err(Error::Io(io::Error::new(
+                        ErrorKind::BrokenPipe,
+                        "socket closed",
+                    )))
+                }
+            }
+        }
+    }
+}
+
+// The owner of the name, from the reply to `GetNameOwner`.
+fn name_owner_reply(reply: Arc<Message>) -> Result<Option<String>> {
+    if reply.primary_header().msg_type() == MessageType::MethodReturn {
+        return reply.body().map(Some).map_err(Error::Message);
+    }
+
+    let no_owner =
+        reply.header()?.error_name()? == Some("org.freedesktop.DBus.Error.NameHasNoOwner");
+    if no_owner {
+        Ok(None)
+    } else {
+        Err(reply.into())
+    }
+}
+
+// The new owner of `name`, if `msg` is the bus' `NameOwnerChanged` signal about it.
+fn name_owner_changed(msg: &Message, name: &str) -> Option<Option<String>> {
+    let signal = SignalInfo::new(
+        Some(FDO_DBUS_SERVICE),
+        FDO_DBUS_PATH,
+        FDO_DBUS_INTERFACE,
+        "NameOwnerChanged",
+    )
+    .ok()?;
+    if !signal.matches(msg, Some(FDO_DBUS_SERVICE)) {
+        return None;
+    }
+
+    match msg.body::<(&str, &str, &str)>() {
+        Ok((changed, _, new_owner)) if changed == name => {
+            Some(Some(new_owner.to_string()).filter(|owner| !owner.is_empty()))
+        }
+        _ => None,
+    }
+}
+
+impl stream::Stream for NameWatch {
+    type Item = Result<Option<String>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        stream::Stream::poll_next(self.get_mut().stream.as_mut(), cx)
+    }
+}
+
+impl Drop for NameWatch {
+    fn drop(&mut self) {
+        if let Some(id) = self.subscription_id.take() {
+            self.conn.queue_unsubscribe_signal(id);
+        }
+    }
+}
+
+impl AsyncDrop for NameWatch {
+    fn async_drop<'d>(mut self) -> BoxFuture<'d, Result<()>>
+    where
+        Self: 'd,
+    {
+        let conn = self.conn.clone();
+        let subscription_id = self.subscription_id.take();
+
+        async move {
+            if let Some(id) = subscription_id {
+                conn.unsubscribe_signal_by_id(id).await?;
+            }
+
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+struct ReceiveMessage<'r, 's> {
+    raw_conn: &'r mut MutexGuard<'s, DynSocketConnection>,
+}
+
+impl<'r, 's> Future for ReceiveMessage<'r, 's> {
+    type Output = Result<Message>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let stream = self.get_mut();
+
+        loop {
+            match stream.raw_conn.try_receive_message() {
+                Err(Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => {
+                    let poll = stream.raw_conn.socket().poll_readable(cx);
+
+                    match poll {
+                        Poll::Pending => return Poll::Pending,
+                        // Guess socket became ready already so let's try it again.
+                        Poll::Ready(Ok(_)) => continue,
+                        Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                    }
+                }
+                m => return Poll::Ready(m),
+            }
+        }
+    }
+}
+
+// Checks if `m` is the reply to the method call with the serial number `serial`, made by the
+// connection with the unique name `unique_name`, if any.
+pub(super) fn is_reply_to(m: &Message, serial: u32, unique_name: Option<&str>) -> bool {
+    if !matches!(
+        m.primary_header().msg_type(),
+        MessageType::Error | MessageType::MethodReturn
+    ) {
+        return false;
+    }
+
+    let header = match m.header() {
+        Ok(header) => header,
+        Err(_) => return false,
+    };
+    if header.reply_serial() != Ok(Some(serial)) {
+        return false;
+    }
+
+    // When eavesdropping, we also receive replies to other connections' method calls, which
+    // can very well have the same serial number as ours.
+    match (unique_name, header.destination()) {
+        (Some(name), Ok(Some(dest))) => name == dest,
+        (_, Ok(_)) => true,
+        (_, Err(_)) => false,
+    }
+}
+
+impl From<crate::Connection> for Connection {
+    fn from(conn: crate::Connection) -> Self {
+        conn.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream::TryStreamExt;
+    use ntest::timeout;
+    use std::os::unix::net::UnixStream;
+    use test_env_log::test;
+
+    use super::*;
+
+    #[test]
+    #[timeout(1000)]
+    fn unix_p2p() {
+        async_io::block_on(test_unix_p2p()).unwrap();
+    }
+
+    async fn test_unix_p2p() -> Result<()> {
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let server = Connection::new_unix_server(p0, &guid);
+        let client = Connection::new_unix_client(p1, false);
+
+        let (client_conn, server_conn) = futures_util::try_join!(client, server)?;
+        let mut client_stream = client_conn.stream().await;
+        let mut server_stream = server_conn.stream().await;
+
+        let server_future = async {
+            let mut method: Option<Arc<Message>> = None;
+            while let Some(m) = server_stream.try_next().await? {
+                if m.to_string() == "Method call Test" {
+                    method.replace(m);
+
+                    break;
+                }
+            }
+            let method = method.unwrap();
+
+            // Send another message first to check the queueing function on client side.
+            server_conn
+                .emit_signal(None, "/", "org.zbus.p2p", "ASignalForYou", &())
+                .await?;
+            server_conn.reply(&method, &("yay")).await
+        };
+
+        let client_future = async {
+            let reply = client_conn
+                .call_method(None, "/", Some("org.zbus.p2p"), "Test", &())
+                .await?;
+            assert_eq!(reply.to_string(), "Method return");
+            // Check we didn't miss the signal that was sent during the call.
+            let m = client_stream.try_next().await?.unwrap();
+            assert_eq!(m.to_string(), "Signal ASignalForYou");
+            reply.body::<String>().map_err(|e| e.into())
+        };
+
+        let (val, _) = futures_util::try_join!(client_future, server_future)?;
+        assert_eq!(val, "yay");
+
+        Ok(())
+    }
+
+    #[test]
+    #[timeout(5000)]
+    fn peer_closed() {
+        async_io::block_on(test_peer_closed()).unwrap();
+    }
+
+    async fn test_peer_closed() -> Result<()> {
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let server = Connection::new_unix_server(p0, &guid);
+        let client = Connection::new_unix_client(p1, false);
+
+        let (client_conn, server_conn) = futures_util::try_join!(client, server)?;
+        let mut client_stream = client_conn.stream().await;
+        drop(server_conn);
+
+        // The stream ends once the peer is found gone, which closes the connection.
+        while let Some(res) = client_stream.next().await {
+            match res {
+                Err(Error::Io(e)) => assert_eq!(e.kind(), ErrorKind::BrokenPipe),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+        assert!(client_conn.0.closed.load(SeqCst));
+
+        // Whatever the size of the message, sending it fails the same way.
+        for &len in &[0, 8 * 1024 * 1024] {
+            let body = vec![0u8; len];
+            match client_conn
+                .emit_signal(None, "/", "org.zbus.p2p", "Big", &body)
+                .await
+            {
+                Err(Error::Io(e)) => assert_eq!(e.kind(), ErrorKind::BrokenPipe),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+        match client_conn
+            .call_method(None, "/", Some("org.zbus.p2p"), "Test", &())
+            .await
+        {
+            Err(Error::Io(e)) => assert_eq!(e.kind(), ErrorKind::BrokenPipe),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        Ok(())
+    }
+
+    // A peer-to-peer pair of connections over a unix socket pair.
+    async fn unix_pair() -> Result<(Connection, Connection)> {
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let server = Connection::new_unix_server(p0, &guid);
+        let client = Connection::new_unix_client(p1, false);
+
+        futures_util::try_join!(client, server)
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn send_batch() {
+        async_io::block_on(async {
+            let (client_conn, server_conn) = unix_pair().await?;
+
+            test_send_batch(client_conn, server_conn).await
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn in_memory_send_batch() {
+        async_io::block_on(async {
+            let (client_conn, server_conn) = in_memory_pair().await?;
+
+            test_send_batch(client_conn, server_conn).await
+        })
+        .unwrap();
+    }
+
+    async fn test_send_batch(client_conn: Connection, server_conn: Connection) -> Result<()> {
+        use nix::sys::stat::fstat;
+        use zvariant::Fd;
+
+        let mut server_stream = server_conn.stream().await;
+
+        // An fd sent in the middle of the batch, and the peer keeping it alive.
+        let (fd_sock, _fd_peer) = UnixStream::pair().unwrap();
+        let sent_ino = fstat(fd_sock.as_raw_fd())?.st_ino;
+        let mut msgs = (0..50u32)
+            .map(|i| Message::signal(None, None, "/", "org.zbus.p2p", "Burst", &i))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let with_fd = (50u32, Fd::from(fd_sock.as_raw_fd()));
+        msgs.insert(
+            25,
+            Message::signal(None, None, "/", "org.zbus.p2p", "Fd", &with_fd)?,
+        );
+        let serials = client_conn.send_batch(msgs).await?;
+        assert_eq!(serials.len(), 51);
+
+        let mut next = 0;
+        for serial in serials {
+            let m = server_stream.try_next().await?.unwrap();
+            assert_eq!(m.primary_header().serial_num(), Some(&serial));
+            if m.header()?.member()? == Some("Fd") {
+                assert_eq!(m.counted_fds(), 1);
+                let (i, fd) = m.body::<(u32, Fd)>()?;
+                assert_eq!(i, 50);
+                assert_eq!(fstat(fd.as_raw_fd())?.st_ino, sent_ino);
+            } else {
+                assert_eq!(m.counted_fds(), 0);
+                assert_eq!(m.body::<u32>()?, next);
+                next += 1;
+            }
+        }
+        assert_eq!(next, 50);
+
+        Ok(())
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn in_memory_slow_channel() {
+        async_io::block_on(test_in_memory_slow_channel()).unwrap();
+    }
+
+    async fn test_in_memory_slow_channel() -> Result<()> {
+        let options = InMemoryOptions::new()
+            .latency(Duration::from_millis(20))
+            .byte_rate(1_000_000);
+        let (client_conn, server_conn) = in_memory_pair_with(options).await?;
+        let mut server_stream = server_conn.stream().await;
+        let array: Vec<u8> = vec![42; 100_000];
+        let start = Instant::now();
+
+        let server_future = async {
+            let m = server_stream.try_next().await?.unwrap();
+            let len = m.body::<Vec<u8>>()?.len() as u64;
+            server_conn.reply(&m, &len).await?;
+
+            Ok::<_, Error>(())
+        };
+        let client_future = async {
+            let reply = client_conn
+                .call_method(None, "/", Some("org.zbus.p2p"), "Test", &array)
+                .await?;
+
+            reply.body::<u64>().map_err(Error::from)
+        };
+
+        let (_, len) = futures_util::try_join!(server_future, client_future)?;
+        assert_eq!(len, 100_000);
+        // 100ms for the call at 1MB/s, and the latency both ways.
+        assert!(start.elapsed() >= Duration::from_millis(140));
+
+        Ok(())
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn try_send_on_congested_connection() {
+        async_io::block_on(test_try_send_on_congested_connection()).unwrap();
+    }
+
+    async fn test_try_send_on_congested_connection() -> Result<()> {
+        // 10 bytes every 10ms, so the socket is congested after the first few bytes.
+        let options = InMemoryOptions::new().byte_rate(1000);
+        let (conn, _peer_conn) = in_memory_pair_with(options).await?;
+        let conn = conn.set_out_queue_capacity(4);
+        assert_eq!(conn.out_queue_capacity(), 4);
+        let array: Vec<u8> = vec![42; 10_000];
+        let start = Instant::now();
+
+        // The first signal is written partially, and the next 4 fill the queue.
+        for _ in 0..5 {
+            conn.try_emit_signal(None, "/", "org.zbus.p2p", "Congested", &array)?;
+        }
+        assert_eq!(conn.out_queue_len(), 4);
+        match conn.try_emit_signal(None, "/", "org.zbus.p2p", "Congested", &array) {
+            Err(Error::QueueFull) => (),
+            res => panic!("Expected Error::QueueFull, got {:?}", res),
+        }
+        assert_eq!(conn.out_queue_len(), 4);
+        // Writing all that would take almost a minute.
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        Ok(())
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn send_raw_message() {
+        async_io::block_on(test_send_raw_message()).unwrap();
+    }
+
+    async fn test_send_raw_message() -> Result<()> {
+        use std::{fs::File, io::Read, os::unix::io::FromRawFd};
+        use zvariant::Fd;
+
+        use crate::OwnedFd;
+
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let server = Connection::new_unix_server(p0, &guid);
+        let client = Connection::new_unix_client(p1, false);
+
+        let (client_conn, server_conn) = futures_util::try_join!(client, server)?;
+        let mut server_stream = server_conn.stream().await;
+
+        // A message built by a worker thread, with a serial number of its own and owning the
+        // write end of a pipe.
+        let (read, write) = nix::unistd::pipe().unwrap();
+        let msg = std::thread::spawn(move || -> Result<Message> {
+            let mut msg = Message::signal(
+                None,
+                None,
+                "/",
+                "org.zbus.p2p",
+                "Pipe",
+                &(Fd::from(write), "raw"),
+            )?;
+            msg.modify_primary_header(|primary| {
+                primary.set_serial_num(u32::max_value());
+                Ok(())
+            })?;
+            // SAFETY: nothing else owns the write end of the pipe.
+            let fds = vec![unsafe { OwnedFd::from_raw_fd(write) }];
+
+            Ok(Message::from_raw_parts(msg.as_bytes().to_vec(), fds)?)
+        })
+        .join()
+        .unwrap()?;
+        let body = msg.as_bytes()[msg.body_offset()?..].to_vec();
+
+        let serial = client_conn.send_raw_message(msg).await?;
+        assert_ne!(serial, u32::max_value());
+
+        let m = server_stream.try_next().await?.unwrap();
+        assert_eq!(m.primary_header().serial_num(), Some(&serial));
+        assert_eq!(&m.as_bytes()[m.body_offset()?..], &body[..]);
+        let (fd, s): (Fd, &str) = m.body()?;
+        assert_eq!(s, "raw");
+        nix::unistd::write(fd.as_raw_fd(), b"hello").unwrap();
+        drop(m);
+
+        // The pipe only reaches its end if the client closed its write end after sending it.
+        // SAFETY: nothing else owns the read end of the pipe.
+        let mut read = unsafe { File::from_raw_fd(read) };
+        let mut received = vec![];
+        read.read_to_end(&mut received).unwrap();
+        assert_eq!(received, b"hello");
+
+        Ok(())
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn slow_streams() {
+        async_io::block_on(test_slow_streams()).unwrap();
+    }
+
+    async fn test_slow_streams() -> Result<()> {
+        const COUNT: u32 = 50;
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let server = Connection::new_unix_server(p0, &guid);
+        let client = Connection::new_unix_client(p1, false);
+
+        let (client_conn, server_conn) = futures_util::try_join!(client, server)?;
+        // The streams created first get each message first, so the slow ones have all of them
+        // queued by the time the fast one is done.
+        let mut drop_oldest = server_conn
+            .stream_with_policy(4, OverflowPolicy::DropOldest)
+            .await;
+        let mut drop_newest = server_conn
+            .stream_with_policy(4, OverflowPolicy::DropNewest)
+            .await;
+        let mut fast = server_conn.stream().await;
+
+        for i in 0..COUNT {
+            client_conn
+                .emit_signal(None, "/", "org.zbus.p2p", "Tick", &i)
+                .await?;
+        }
+        for i in 0..COUNT {
+            let m = fast.try_next().await?.unwrap();
+            assert_eq!(m.body::<u32>()?, i);
+        }
+        assert_eq!(fast.dropped(), 0);
+
+        // The slow streams only get to their messages now.
+        for i in COUNT - 4..COUNT {
+            let m = drop_oldest.try_next().await?.unwrap();
+            assert_eq!(m.body::<u32>()?, i);
+        }
+        assert_eq!(drop_oldest.dropped(), u64::from(COUNT - 4));
+        for i in 0..4 {
+            let m = drop_newest.try_next().await?.unwrap();
+            assert_eq!(m.body::<u32>()?, i);
+        }
+        assert_eq!(drop_newest.dropped(), u64::from(COUNT - 4));
+        drop(drop_oldest);
+        drop(drop_newest);
+
+        // A full blocking stream holds back the others, until it's dropped.
+        let blocking = server_conn
+            .stream_with_policy(1, OverflowPolicy::Block)
+            .await;
+        for i in COUNT..COUNT * 2 {
+            client_conn
+                .emit_signal(None, "/", "org.zbus.p2p", "Tick", &i)
+                .await?;
+        }
+        for i in COUNT..COUNT + 2 {
+            let m = fast.try_next().await?.unwrap();
+            assert_eq!(m.body::<u32>()?, i);
+        }
+        drop(blocking);
+        for i in COUNT + 2..COUNT * 2 {
+            let m = fast.try_next().await?.unwrap();
+            assert_eq!(m.body::<u32>()?, i);
+        }
+        assert_eq!(fast.dropped(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    #[timeout(2000)]
+    #[cfg(feature = "internal-executor")]
+    fn task_panic() {
+        async_io::block_on(test_task_panic()).unwrap();
+    }
+
+    #[cfg(feature = "internal-executor")]
+    async fn test_task_panic() -> Result<()> {
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let server = Connection::new_unix_server(p0, &guid);
+        let client = Connection::new_unix_client(p1, false);
+
+        let (client_conn, server_conn) = futures_util::try_join!(client, server)?;
+        let mut server_stream = server_conn.stream().await;
+
+        // Panicking tasks must not bring the executor thread down with them.
+        for conn in &[&client_conn, &server_conn] {
+            conn.0
+                .executor
+                .spawn(async { panic!("user task panicked") })
+                .detach();
+        }
+
+        let server_future = async {
+            for _ in 0..2 {
+                let method = loop {
+                    let m = server_stream.try_next().await?.unwrap();
+                    if m.to_string() == "Method call Test" {
+                        break m;
+                    }
+                };
+                server_conn.reply(&method, &("yay")).await?;
+            }
+
+            Ok::<_, Error>(())
+        };
+
+        let client_future = async {
+            for _ in 0..2 {
+                let reply = client_conn
+                    .call_method(None, "/", Some("org.zbus.p2p"), "Test", &())
+                    .await?;
+                assert_eq!(reply.body::<String>()?, "yay");
+            }
+
+            Ok::<_, Error>(())
+        };
+
+        futures_util::try_join!(client_future, server_future)?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn call_method_offload() {
+        async_io::block_on(async {
+            let (client_conn, server_conn) = unix_pair().await?;
+
+            test_call_method_offload(client_conn, server_conn).await
+        })
+        .unwrap();
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn in_memory_call_method_offload() {
+        async_io::block_on(async {
+            let (client_conn, server_conn) = in_memory_pair().await?;
+
+            test_call_method_offload(client_conn, server_conn).await
+        })
+        .unwrap();
+    }
+
+    async fn test_call_method_offload(
+        client_conn: Connection,
+        server_conn: Connection,
+    ) -> Result<()> {
+        use nix::sys::stat::fstat;
+        use zvariant::Fd;
+
+        let mut server_stream = server_conn.stream().await;
+
+        // The fd to send along, and the peer keeping it alive.
+        let (fd_sock, _fd_peer) = UnixStream::pair().unwrap();
+        let sent_ino = fstat(fd_sock.as_raw_fd())?.st_ino;
+        let array: Vec<u32> = (0..100_000).collect();
+
+        let server_future = async {
+            let mut ret = vec![];
+            while ret.len() < 2 {
+                let m = server_stream.try_next().await?.unwrap();
+                if m.primary_header().msg_type() != MessageType::MethodCall {
+                    continue;
+                }
+
+                assert_eq!(m.counted_fds(), 1);
+                let (received, fd) = m.body::<(Vec<u32>, Fd)>()?;
+                assert_eq!(fstat(fd.as_raw_fd())?.st_ino, sent_ino);
+                server_conn.reply(&m, &()).await?;
+                ret.push((m.to_string(), m.body_signature()?.to_owned(), received));
+            }
+
+            Ok::<_, Error>(ret)
+        };
+
+        let client_future = async {
+            let body = (array.clone(), Fd::from(fd_sock.as_raw_fd()));
+            client_conn
+                .call_method(None, "/", Some("org.zbus.p2p"), "Test", &body)
+                .await?;
+            let reply = client_conn
+                .call_method_offload(None, "/", Some("org.zbus.p2p"), "Test", body)
+                .await?;
+            assert_eq!(reply.to_string(), "Method return");
+
+            Ok(())
+        };
+
+        let (calls, _) = futures_util::try_join!(server_future, client_future)?;
+        // Both calls look exactly the same.
+        assert_eq!(calls[0], calls[1]);
+        assert_eq!(calls[0].2, array);
+
+        Ok(())
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn eavesdropped_replies() {
+        async_io::block_on(test_eavesdropped_replies()).unwrap();
+    }
+
+    async fn test_eavesdropped_replies() -> Result<()> {
+        let conn = Connection::new_session().await?;
+        let unique_name = conn.unique_name().unwrap();
+
+        let mut ours = Message::method(Some(unique_name), None, "/", None, "Test", &())?;
+        let serial = conn.assign_serial_num(&mut ours)?;
+        let reply = Message::method_reply(None, &ours, &())?;
+        assert!(conn.is_reply_to(&reply, serial));
+        let error = Message::method_error(None, &ours, "org.zbus.Error", &())?;
+        assert!(conn.is_reply_to(&error, serial));
+
+        // Another connection's call with the same serial, as seen when eavesdropping.
+        let mut theirs = Message::method(Some(":1.eavesdropped"), None, "/", None, "Test", &())?;
+        theirs.modify_primary_header(|primary| {
+            primary.serial_num_or_init(|| serial);
+            Ok(())
+        })?;
+        let reply = Message::method_reply(None, &theirs, &())?;
+        assert_eq!(reply.header()?.reply_serial()?, Some(serial));
+        assert!(!conn.is_reply_to(&reply, serial));
+
+        // Neither a method call nor a signal is a reply.
+        assert!(!conn.is_reply_to(&ours, serial));
+
+        Ok(())
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn serial_monotonically_increases() {
+        async_io::block_on(test_serial_monotonically_increases());
+    }
+
+    async fn test_serial_monotonically_increases() {
+        let c = Connection::new_session().await.unwrap();
+        let serial = c.next_serial() + 1;
+
+        for next in serial..serial + 10 {
+            assert_eq!(next, c.next_serial());
+        }
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn wait_for_signal_p2p() {
+        async_io::block_on(test_wait_for_signal_p2p()).unwrap();
+    }
+
+    async fn test_wait_for_signal_p2p() -> Result<()> {
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let server = Connection::new_unix_server(p0, &guid);
+        let client = Connection::new_unix_client(p1, false);
+
+        let (client_conn, server_conn) = futures_util::try_join!(client, server)?;
+
+        let wait = client_conn.wait_for_signal(
+            "org.zbus.p2p",
+            "/org/zbus",
+            "org.zbus.p2p",
+            "ASignalForYou",
+        );
+        let emit = async {
+            server_conn
+                .emit_signal(None, "/org/zbus/child", "org.zbus.p2p", "NotForYou", &())
+                .await?;
+            server_conn
+                .emit_signal(
+                    None,
+                    "/org/zbus/child",
+                    "org.zbus.p2p",
+                    "ASignalForYou",
+                    &(),
+                )
+                .await
+        };
+
+        let (msg, _) = futures_util::try_join!(wait, emit)?;
+        assert_eq!(msg.to_string(), "Signal ASignalForYou");
+        assert_eq!(msg.header()?.path()?.unwrap().as_str(), "/org/zbus/child");
+
+        Ok(())
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn typed_signal_stream_p2p() {
+        async_io::block_on(test_typed_signal_stream_p2p()).unwrap();
+    }
+
+    async fn test_typed_signal_stream_p2p() -> Result<()> {
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
 
-        #[cfg(feature = "internal-executor")]
-        std::thread::Builder::new()
-            .name("zbus::azync::Connection::receive_msg".into())
-            .spawn(move || {
-                block_on(async move {
-                    // Run as long as there is a task to run.
-                    while !executor.is_empty() {
-                        executor.tick().await;
-                    }
-                })
-            })?;
+        let server = Connection::new_unix_server(p0, &guid);
+        let client = Connection::new_unix_client(p1, false);
 
-        if !bus_connection {
-            return Ok(connection);
+        let (client_conn, server_conn) = futures_util::try_join!(client, server)?;
+
+        let mut stream = TypedSignalStream::<(String, u32)>::for_signal(
+            &client_conn,
+            "org.zbus.p2p",
+            "Progress",
+            None,
+        )
+        .await?;
+        // The messages of a peer carry no sender, so it can't be checked.
+        let mut named = TypedSignalStream::<(String, u32)>::for_signal(
+            &client_conn,
+            "org.zbus.p2p",
+            "Progress",
+            Some("org.zbus.Peer"),
+        )
+        .await?;
+        let iface = "org.zbus.p2p";
+        server_conn
+            .emit_signal(None, "/org/zbus", iface, "NotForYou", &("ignored", 0u32))
+            .await?;
+        server_conn
+            .emit_signal(
+                None,
+                "/org/zbus/child",
+                iface,
+                "Progress",
+                &("download", 42u32),
+            )
+            .await?;
+        server_conn
+            .emit_signal(None, "/", iface, "Progress", &42u32)
+            .await?;
+        server_conn
+            .emit_signal(None, "/", iface, "Progress", &("upload", 7u32))
+            .await?;
+
+        let (args, msg) = stream.next().await.unwrap()?;
+        assert_eq!(args, (String::from("download"), 42));
+        assert_eq!(msg.header()?.path()?.unwrap().as_str(), "/org/zbus/child");
+
+        match stream.next().await.unwrap().unwrap_err() {
+            Error::FDO(e) => match *e {
+                fdo::Error::InvalidSignature(e) => assert_eq!(
+                    e,
+                    "`org.zbus.p2p.Progress` signal has body signature 'u' instead of '(su)'"
+                ),
+                e => panic!("unexpected error: {}", e),
+            },
+            e => panic!("unexpected error: {}", e),
         }
 
-        // Now that the server has approved us, we must send the bus Hello, as per specs
-        connection.hello_bus().await?;
+        // Mismatched bodies don't end the stream.
+        let (args, _) = stream.next().await.unwrap()?;
+        assert_eq!(args, (String::from("upload"), 7));
 
-        Ok(connection)
-    }
+        let (args, _) = named.next().await.unwrap()?;
+        assert_eq!(args, (String::from("download"), 42));
 
-    fn next_serial(&self) -> u32 {
-        self.0.serial.fetch_add(1, SeqCst)
+        Ok(())
     }
 
-    /// Create a `Connection` to the session/user message bus.
-    pub async fn new_session() -> Result<Self> {
-        Self::new(Authenticated::session().await?, true).await
+    #[test]
+    #[timeout(15000)]
+    fn typed_signal_stream_follows_owner() {
+        async_io::block_on(test_typed_signal_stream_follows_owner()).unwrap();
     }
 
-    /// Create a `Connection` to the system-wide message bus.
-    pub async fn new_system() -> Result<Self> {
-        Self::new(Authenticated::system().await?, true).await
-    }
+    async fn test_typed_signal_stream_follows_owner() -> Result<()> {
+        use enumflags2::BitFlags;
+
+        let name = "org.zbus.FollowOwnerTest";
+        let iface = "org.zbus.FollowOwner";
+        let conn = Connection::new_session().await?;
+        let first = Connection::new_session().await?;
+        let second = Connection::new_session().await?;
+
+        // Receives all the signals, so the ones of `first` reach `conn` even once the bus doesn't
+        // route them to the stream following `name`.
+        let mut all = TypedSignalStream::<String>::for_signal(&conn, iface, "Ping", None).await?;
+        // The name has no owner yet.
+        let mut stream =
+            TypedSignalStream::<String>::for_signal(&conn, iface, "Ping", Some(name)).await?;
+        let mut bus_signals = TypedSignalStream::<(String, String, String)>::for_signal(
+            &conn,
+            "org.freedesktop.DBus",
+            "NameOwnerChanged",
+            Some("org.freedesktop.DBus"),
+        )
+        .await?;
 
-    /// Create a `Connection` for the given [D-Bus address].
-    ///
-    /// [D-Bus address]: https://dbus.freedesktop.org/doc/dbus-specification.html#addresses
-    pub async fn new_for_address(address: &str, bus_connection: bool) -> Result<Self> {
-        Self::new(Authenticated::for_address(address).await?, bus_connection).await
-    }
-}
+        first
+            .emit_signal(None, "/", iface, "Ping", &"unowned")
+            .await?;
+        fdo::AsyncDBusProxy::new(&first)?
+            .request_name(name, BitFlags::empty())
+            .await?;
+        first
+            .emit_signal(None, "/", iface, "Ping", &"first")
+            .await?;
+        fdo::AsyncDBusProxy::new(&first)?.release_name(name).await?;
+        fdo::AsyncDBusProxy::new(&second)?
+            .request_name(name, BitFlags::empty())
+            .await?;
+        first
+            .emit_signal(None, "/", iface, "Ping", &"former owner")
+            .await?;
+        second
+            .emit_signal(None, "/", iface, "Ping", &"second")
+            .await?;
+
+        for expected in &["unowned", "first", "former owner", "second"] {
+            let (arg, _) = all.next().await.unwrap()?;
+            assert_eq!(arg, *expected);
+        }
+        for (expected, sender) in &[("first", &first), ("second", &second)] {
+            let (arg, msg) = stream.next().await.unwrap()?;
+            assert_eq!(arg, *expected);
+            assert_eq!(msg.header()?.sender()?, sender.unique_name());
+        }
 
-/// A [`futures_sink::Sink`] implementation that consumes [`Message`] instances.
-///
-/// Use [`Connection::sink`] to create an instance of this type.
-///
-/// # Caveats
-///
-/// At the moment, a simultaneous [flush request] from multiple tasks/threads could
-/// potentially create a busy loop, thus wasting CPU time. This limitation may be removed in the
-/// future.
-///
-/// [flush request]: https://docs.rs/futures/0.3.15/futures/sink/trait.SinkExt.html#method.flush
-pub struct MessageSink {
-    raw_conn: Arc<sync::Mutex<DynSocketConnection>>,
-    cap_unix_fd: bool,
-}
+        // The bus is the sender of its own signals.
+        let owner = loop {
+            let ((changed, _, new_owner), msg) = bus_signals.next().await.unwrap()?;
+            assert_eq!(msg.header()?.sender()?, Some("org.freedesktop.DBus"));
+            if changed == name {
+                break new_owner;
+            }
+        };
+        assert_eq!(Some(owner.as_str()), first.unique_name());
 
-assert_impl_all!(MessageSink: Send, Sync, Unpin);
+        Ok(())
+    }
 
-impl MessageSink {
-    fn flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        loop {
-            let mut raw_conn = self.raw_conn.lock().unwrap();
-            match raw_conn.try_flush() {
-                Ok(()) => return Poll::Ready(Ok(())),
-                Err(e) => {
-                    if e.kind() == ErrorKind::WouldBlock {
-                        let poll = raw_conn.socket().poll_writable(cx);
+    #[test]
+    #[timeout(1000)]
+    fn serial_wrap_around() {
+        async_io::block_on(test_serial_wrap_around()).unwrap();
+    }
 
-                        match poll {
-                            Poll::Pending => return Poll::Pending,
-                            // Guess socket became ready already so let's try it again.
-                            Poll::Ready(Ok(_)) => continue,
-                            Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+    async fn test_serial_wrap_around() -> Result<()> {
+        let guid = Guid::generate();
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let (client, server) = futures_util::try_join!(
+            Connection::new_unix_client(p1, false),
+            Connection::new_unix_server(p0, &guid),
+        )?;
+        let mut server_stream = server.stream().await;
+        let (held_tx, held_rx) = bounded(1);
+
+        // Replies to each of the 6 calls with its serial number, only replying to `Hold` after
+        // `Release`.
+        let server_thread = std::thread::spawn(move || {
+            async_io::block_on(async {
+                let mut held = None;
+                for _ in 0..6 {
+                    let m = server_stream.try_next().await?.unwrap();
+                    let serial = *m.primary_header().serial_num().unwrap();
+                    match m.to_string().as_str() {
+                        "Method call Hold" => {
+                            held = Some(m);
+                            held_tx.send(()).await.unwrap();
+                        }
+                        "Method call Release" => {
+                            server.reply(&m, &serial).await?;
+                            let held = held.take().unwrap();
+                            let serial = *held.primary_header().serial_num().unwrap();
+                            server.reply(&held, &serial).await?;
+                        }
+                        _ => {
+                            server.reply(&m, &serial).await?;
                         }
-                    } else {
-                        return Poll::Ready(Err(Error::Io(e)));
                     }
                 }
+
+                Ok::<_, Error>(())
+            })
+        });
+
+        let call = |method: &'static str| {
+            client
+                .call_method(None, "/", Some("org.zbus.Serials"), method, &())
+                .map(|r| r.and_then(|m| m.body::<u32>().map_err(Into::into)))
+        };
+        client.set_next_serial(1);
+        let hold = call("Hold");
+        let others = async {
+            held_rx.recv().await.unwrap();
+            // Going past `u32::MAX` skips 0, then 1 since `Hold` is still waiting for its reply.
+            client.set_next_serial(u32::MAX);
+            let mut serials = vec![];
+            for method in &["Echo", "Echo", "Echo", "Release"] {
+                serials.push(call(*method).await?);
             }
-        }
-    }
-}
 
-impl futures_sink::Sink<Message> for MessageSink {
-    type Error = Error;
+            Ok::<_, Error>(serials)
+        };
 
-    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
-        // TODO: We should have a max queue length in raw::Socket for outgoing messages.
-        Poll::Ready(Ok(()))
+        let (held, serials) = futures_util::try_join!(hold, others)?;
+        assert_eq!(held, 1);
+        assert_eq!(serials, [u32::MAX, 2, 3, 4]);
+
+        // Once replied to, the serial number is free again.
+        client.set_next_serial(1);
+        assert_eq!(call("Echo").await?, 1);
+
+        server_thread.join().unwrap()
     }
 
-    fn start_send(self: Pin<&mut Self>, msg: Message) -> Result<()> {
-        if !msg.fds().is_empty() && !self.cap_unix_fd {
-            return Err(Error::Unsupported);
-        }
+    #[test]
+    #[timeout(15000)]
+    fn credentials_of() {
+        async_io::block_on(test_credentials_of()).unwrap();
+    }
 
-        self.raw_conn.lock().unwrap().enqueue_message(msg);
+    async fn test_credentials_of() -> Result<()> {
+        let conn = Connection::new_session().await?;
+        let peer = Connection::new_session().await?;
+        let name = peer.unique_name().unwrap().to_string();
+        let mut stream = conn.stream().await;
+
+        let lookups = (0..10).map(|_| conn.credentials_of(&name));
+        let mut all = futures_util::future::try_join_all(lookups).await?;
+        all.push(conn.credentials_of(&name).await?);
+        assert!(all.iter().all(|c| *c == all[0]));
+        assert_eq!(all[0].process_id, Some(std::process::id()));
+
+        // All of these lookups made a single call to the bus.
+        let last = conn
+            .call_method(
+                Some(FDO_DBUS_SERVICE),
+                FDO_DBUS_PATH,
+                Some(FDO_DBUS_INTERFACE),
+                "GetId",
+                &(),
+            )
+            .await?;
+        let last_serial = last.header()?.reply_serial()?;
+        let mut replies = 0;
+        while let Some(msg) = stream.try_next().await? {
+            let header = msg.header()?;
+            if header.message_type()? != MessageType::MethodReturn {
+                continue;
+            }
+            if header.reply_serial()? == last_serial {
+                break;
+            }
+            replies += 1;
+        }
+        assert_eq!(replies, 1);
+
+        // Once the peer is gone, so are its credentials.
+        let mut watch = conn.watch_name(&name).await?;
+        assert_eq!(watch.try_next().await?, Some(Some(name.clone())));
+        drop(peer);
+        assert_eq!(watch.try_next().await?, Some(None));
+        match conn.credentials_of(&name).await.unwrap_err() {
+            Error::FDO(e) => match *e {
+                fdo::Error::NameHasNoOwner(_) => (),
+                e => panic!("unexpected error: {}", e),
+            },
+            e => panic!("unexpected error: {}", e),
+        }
 
         Ok(())
     }
 
-    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        self.get_mut().flush(cx)
+    #[test]
+    #[timeout(15000)]
+    fn shared_session() {
+        let conn = async_io::block_on(Connection::shared_session()).unwrap();
+        let name = conn.unique_name().unwrap().to_string();
+        let again = async_io::block_on(Connection::shared_session()).unwrap();
+        assert_eq!(again.unique_name(), Some(name.as_str()));
+        let blocking = crate::Connection::shared_session().unwrap();
+        assert_eq!(blocking.unique_name(), Some(name.as_str()));
+
+        // Once all the clones are gone, a new connection is created.
+        drop((conn, again, blocking));
+        let conn = async_io::block_on(Connection::shared_session()).unwrap();
+        assert_ne!(conn.unique_name(), Some(name.as_str()));
+
+        // An injected connection is used until it's closed.
+        let guid = Guid::generate();
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let (client, server) = async_io::block_on(async {
+            futures_util::try_join!(
+                Connection::new_unix_client(p1, false),
+                Connection::new_unix_server(p0, &guid),
+            )
+        })
+        .unwrap();
+        async_io::block_on(Connection::set_shared_session(&client));
+        assert!(!async_io::block_on(Connection::shared_session())
+            .unwrap()
+            .is_bus());
+
+        drop(server);
+        while !client.0.closed.load(SeqCst) {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(async_io::block_on(Connection::shared_session())
+            .unwrap()
+            .is_bus());
     }
 
-    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        let sink = self.get_mut();
-        match sink.flush(cx) {
-            Poll::Ready(Ok(_)) => (),
-            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
-            Poll::Pending => return Poll::Pending,
+    #[test]
+    #[timeout(15000)]
+    fn wait_for_signal() {
+        async_io::block_on(test_wait_for_signal()).unwrap();
+    }
+
+    async fn test_wait_for_signal() -> Result<()> {
+        let conn = Connection::new_session().await?;
+        let emitter = Connection::new_session().await?;
+        let sender = emitter.unique_name().unwrap();
+        let baseline = match_rule_count(&conn).await?;
+
+        let msg = wait_for_ping(&conn, &emitter, "/org/zbus").await?;
+        assert_eq!(msg.to_string(), "Signal Ping");
+        assert!(conn.0.signal_subscriptions.lock().await.is_empty());
+        assert_eq!(match_rule_count(&conn).await?, baseline);
+
+        // An existing subscription on an ancestor path should be reused.
+        let id = conn
+            .subscribe_signal(sender, "/org", "org.zbus.WaitForSignal", "Ping")
+            .await?;
+        wait_for_ping(&conn, &emitter, "/org/zbus").await?;
+        {
+            let subscriptions = conn.0.signal_subscriptions.lock().await;
+            assert_eq!(subscriptions.len(), 1);
+            assert_eq!(subscriptions[&id].num_subscribers, 1);
         }
+        assert!(conn.unsubscribe_signal_by_id(id).await?);
+        assert_eq!(match_rule_count(&conn).await?, baseline);
 
-        Poll::Ready((sink.raw_conn.lock().unwrap()).close())
+        Ok(())
     }
-}
-
-/// A [`stream::Stream`] implementation that yields [`Message`] items.
-///
-/// Use [`Connection::stream`] to create an instance of this type.
-pub struct MessageStream {
-    stream: stream::BoxStream<'static, Result<Arc<Message>>>,
-}
 
-assert_impl_all!(MessageStream: Send, Unpin);
+    async fn wait_for_ping(
+        conn: &Connection,
+        emitter: &Connection,
+        path: &str,
+    ) -> Result<Arc<Message>> {
+        use futures_util::future::{select, Either};
+        use std::time::Duration;
 
-impl stream::Stream for MessageStream {
-    type Item = Result<Arc<Message>>;
+        let wait = conn.wait_for_signal(
+            emitter.unique_name().unwrap(),
+            path,
+            "org.zbus.WaitForSignal",
+            "Ping",
+        );
+        // We can't know when exactly the match rule is in place so keep emitting until received.
+        let emit = async {
+            loop {
+                if let Err(e) = emitter
+                    .emit_signal(None, path, "org.zbus.WaitForSignal", "Ping", &())
+                    .await
+                {
+                    return e;
+                }
+                async_io::Timer::after(Duration::from_millis(10)).await;
+            }
+        };
+        futures_util::pin_mut!(wait);
+        futures_util::pin_mut!(emit);
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        stream::Stream::poll_next(self.get_mut().stream.as_mut(), cx)
+        match select(wait, emit).await {
+            Either::Left((msg, _)) => msg,
+            Either::Right((e, _)) => Err(e),
+        }
     }
-}
-
-struct ReceiveMessage<'r, 's> {
-    raw_conn: &'r mut MutexGuard<'s, RawConnection<Async<Box<dyn Socket>>>>,
-}
 
-impl<'r, 's> Future for ReceiveMessage<'r, 's> {
-    type Output = Result<Message>;
+    #[test]
+    #[timeout(15000)]
+    fn name_acquired_lost() {
+        async_io::block_on(test_name_acquired_lost()).unwrap();
+    }
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let stream = self.get_mut();
+    async fn test_name_acquired_lost() -> Result<()> {
+        use enumflags2::BitFlags;
+        use futures_util::future::FutureExt;
+
+        let name = "org.zbus.NameAcquiredLostTest";
+        let conn = Connection::new_session().await?;
+        let mut acquired = conn.receive_name_acquired().await?;
+        let mut lost = conn.receive_name_lost().await?;
+        let dbus = fdo::AsyncDBusProxy::new(&conn)?;
+
+        assert!(!conn.is_name_owner(name).await?);
+        let reply = dbus
+            .request_name(name, fdo::RequestNameFlags::AllowReplacement.into())
+            .await?;
+        assert_eq!(reply, fdo::RequestNameReply::PrimaryOwner);
+        // The signal must already be in the stream once `request_name` returns.
+        let signal = acquired.next().now_or_never().unwrap().unwrap();
+        assert_eq!(signal.args()?.name, name);
+        assert!(conn.is_name_owner(name).await?);
+
+        // Another connection waiting in the queue for the name.
+        let conn2 = Connection::new_session().await?;
+        let mut acquired2 = conn2.receive_name_acquired().await?;
+        let reply = fdo::AsyncDBusProxy::new(&conn2)?
+            .request_name(name, BitFlags::empty())
+            .await?;
+        assert_eq!(reply, fdo::RequestNameReply::InQueue);
+        assert!(!conn2.is_name_owner(name).await?);
+
+        let reply = dbus.release_name(name).await?;
+        assert_eq!(reply, fdo::ReleaseNameReply::Released);
+        let signal = lost.next().now_or_never().unwrap().unwrap();
+        assert_eq!(signal.args()?.name, name);
+        assert!(!conn.is_name_owner(name).await?);
+
+        let signal = acquired2.next().await.unwrap();
+        assert_eq!(signal.args()?.name, name);
+        assert!(conn2.is_name_owner(name).await?);
 
-        loop {
-            match stream.raw_conn.try_receive_message() {
-                Err(Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => {
-                    let poll = stream.raw_conn.socket().poll_readable(cx);
+        Ok(())
+    }
 
-                    match poll {
-                        Poll::Pending => return Poll::Pending,
-                        // Guess socket became ready already so let's try it again.
-                        Poll::Ready(Ok(_)) => continue,
-                        Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
-                    }
-                }
-                m => return Poll::Ready(m),
+    // The number of match rules `conn` has on the bus, if the bus provides statistics.
+    async fn match_rule_count(conn: &Connection) -> Result<Option<u32>> {
+        use std::{collections::HashMap, convert::TryFrom};
+        use zvariant::OwnedValue;
+
+        let reply = conn
+            .call_method(
+                Some("org.freedesktop.DBus"),
+                "/org/freedesktop/DBus",
+                Some("org.freedesktop.DBus.Debug.Stats"),
+                "GetConnectionStats",
+                &conn.unique_name().unwrap(),
+            )
+            .await;
+        match reply {
+            Ok(reply) => {
+                let stats: HashMap<String, OwnedValue> = reply.body()?;
+
+                Ok(stats
+                    .get("MatchRules")
+                    .and_then(|rules| u32::try_from(rules).ok()))
             }
+            // The statistics interface is optional.
+            Err(Error::MethodError(_, _, _)) => Ok(None),
+            Err(e) => Err(e),
         }
     }
-}
 
-impl From<crate::Connection> for Connection {
-    fn from(conn: crate::Connection) -> Self {
-        conn.into_inner()
+    #[test]
+    #[timeout(15000)]
+    fn drop_signal_subscriptions() {
+        async_io::block_on(test_drop_signal_subscriptions()).unwrap();
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use futures_util::stream::TryStreamExt;
-    use ntest::timeout;
-    use std::os::unix::net::UnixStream;
-    use test_env_log::test;
+    async fn test_drop_signal_subscriptions() -> Result<()> {
+        use crate::azync::Proxy;
+
+        let conn = Connection::new_session().await?;
+        let baseline = match_rule_count(&conn).await?;
+        let dbus = fdo::AsyncDBusProxy::new(&conn)?;
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let new_proxy = || {
+            Proxy::new(
+                &conn,
+                "org.freedesktop.DBus",
+                "/org/freedesktop/DBus",
+                "org.freedesktop.DBus",
+            )
+        };
+        let connect_handler = |proxy: Proxy<'static>| async move {
+            proxy
+                .connect_signal("NameOwnerChanged", |_| async { Ok(()) }.boxed())
+                .await?;
 
-    use super::*;
+            Ok::<_, Error>(proxy)
+        };
 
-    #[test]
-    #[timeout(1000)]
-    fn unix_p2p() {
-        async_io::block_on(test_unix_p2p()).unwrap();
-    }
+        // Dropping never blocks, be it from a tokio worker thread..
+        let stream = dbus.receive_signal("NameOwnerChanged").await?;
+        let typed =
+            TypedSignalStream::<()>::for_signal(&conn, "org.zbus.Drop", "Ping", None).await?;
+        let proxy = connect_handler(new_proxy().await?).await?;
+        runtime
+            .spawn(async move {
+                drop(stream);
+                drop(typed);
+                drop(proxy);
+            })
+            .await
+            .unwrap();
+        wait_for_no_subscriptions(&conn).await;
+        assert_eq!(match_rule_count(&conn).await?, baseline);
 
-    async fn test_unix_p2p() -> Result<()> {
-        let guid = Guid::generate();
+        // .. from within a tokio runtime context, outside of any task..
+        let stream = dbus.receive_signal("NameOwnerChanged").await?;
+        let proxy = connect_handler(new_proxy().await?).await?;
+        {
+            let _guard = runtime.enter();
+            drop(stream);
+            drop(proxy);
+        }
+        wait_for_no_subscriptions(&conn).await;
+
+        // .. or from async-io code.
+        let typed =
+            TypedSignalStream::<()>::for_signal(&conn, "org.zbus.Drop", "Ping", None).await?;
+        let proxy = connect_handler(new_proxy().await?).await?;
+        drop(typed);
+        drop(proxy);
+        wait_for_no_subscriptions(&conn).await;
+
+        // Dropping asynchronously is done once it returns.
+        dbus.receive_signal("NameOwnerChanged")
+            .await?
+            .async_drop()
+            .await?;
+        TypedSignalStream::<()>::for_signal(&conn, "org.zbus.Drop", "Ping", None)
+            .await?
+            .async_drop()
+            .await?;
+        connect_handler(new_proxy().await?)
+            .await?
+            .async_drop()
+            .await?;
+        assert!(conn.0.signal_subscriptions.lock().await.is_empty());
+        assert_eq!(match_rule_count(&conn).await?, baseline);
+
+        // The same goes for blocking proxies, dropped asynchronously from a tokio task.
+        let proxy = crate::Proxy::from(connect_handler(new_proxy().await?).await?);
+        runtime
+            .spawn(async move { proxy.async_drop().await })
+            .await
+            .unwrap()?;
+        assert!(conn.0.signal_subscriptions.lock().await.is_empty());
 
-        let (p0, p1) = UnixStream::pair().unwrap();
+        Ok(())
+    }
 
-        let server = Connection::new_unix_server(p0, &guid);
-        let client = Connection::new_unix_client(p1, false);
+    #[test]
+    #[timeout(15000)]
+    fn liveness_ping() {
+        async_io::block_on(test_liveness_ping()).unwrap();
+    }
 
-        let (client_conn, server_conn) = futures_util::try_join!(client, server)?;
-        let mut client_stream = client_conn.stream().await;
-        let mut server_stream = server_conn.stream().await;
+    async fn test_liveness_ping() -> Result<()> {
+        use futures_util::future::{select, Either};
 
-        let server_future = async {
-            let mut method: Option<Arc<Message>> = None;
-            while let Some(m) = server_stream.try_next().await? {
-                if m.to_string() == "Method call Test" {
-                    method.replace(m);
+        let interval = Duration::from_millis(200);
+        let timeout = Duration::from_millis(200);
+        let new_client = |stream| {
+            crate::ConnectionBuilder::unix_stream(stream)
+                .p2p()
+                .liveness_ping(interval, timeout)
+                .build_async()
+        };
 
-                    break;
+        // A responsive peer, only pinged while it's quiet.
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let server = Connection::new_unix_server(p0, &Guid::generate());
+        let (client, server) = futures_util::try_join!(new_client(p1), server)?;
+        let pings = AtomicU32::new(0);
+        let responder = async {
+            let mut stream = server.stream().await;
+            while let Some(msg) = stream.try_next().await? {
+                let header = msg.header()?;
+                if header.message_type()? != MessageType::MethodCall {
+                    continue;
+                }
+                if header.member()? == Some("Ping") {
+                    pings.fetch_add(1, SeqCst);
                 }
+                server.reply(&msg, &()).await?;
             }
-            let method = method.unwrap();
 
-            // Send another message first to check the queueing function on client side.
-            server_conn
-                .emit_signal(None, "/", "org.zbus.p2p", "ASignalForYou", &())
-                .await?;
-            server_conn.reply(&method, &("yay")).await
+            Ok::<(), Error>(())
         };
+        let checks = async {
+            // Keep a stream, for the signals to be received as they come.
+            let _stream = client.stream().await;
+            for _ in 0..25 {
+                server
+                    .emit_signal(None, "/", "org.zbus.Liveness", "Traffic", &())
+                    .await?;
+                crate::sleep(Duration::from_millis(20)).await;
+            }
+            assert_eq!(pings.load(SeqCst), 0);
 
-        let client_future = async {
-            let reply = client_conn
-                .call_method(None, "/", Some("org.zbus.p2p"), "Test", &())
+            crate::sleep(interval * 3).await;
+            assert!(pings.load(SeqCst) >= 2);
+            client
+                .call_method(None, "/", Some("org.zbus.Liveness"), "Test", &())
                 .await?;
-            assert_eq!(reply.to_string(), "Method return");
-            // Check we didn't miss the signal that was sent during the call.
-            let m = client_stream.try_next().await?.unwrap();
-            assert_eq!(m.to_string(), "Signal ASignalForYou");
-            reply.body::<String>().map_err(|e| e.into())
+
+            Ok::<(), Error>(())
         };
+        futures_util::pin_mut!(responder);
+        futures_util::pin_mut!(checks);
+        match select(responder, checks).await {
+            Either::Left((res, _)) => panic!("responder stopped: {:?}", res),
+            Either::Right((res, _)) => res?,
+        }
 
-        let (val, _) = futures_util::try_join!(client_future, server_future)?;
-        assert_eq!(val, "yay");
+        // A peer that stopped reading from the socket.
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let server = Authenticated::unix_server(p0, Guid::generate(), vec![], None);
+        let (client, _server) = futures_util::try_join!(new_client(p1), server)?;
+        let mut stream = client.stream().await;
+        let start = Instant::now();
+        let err = client
+            .call_method(None, "/", Some("org.zbus.Liveness"), "Test", &())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::PeerUnresponsive(t) if t == timeout),
+            "{}",
+            err
+        );
+        // Detected within a ping interval and timeout, give or take some scheduling delays.
+        assert!(start.elapsed() < interval + timeout + Duration::from_millis(500));
+        assert!(stream.next().await.is_none());
+        let err = client
+            .emit_signal(None, "/", "org.zbus.Liveness", "Traffic", &())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::PeerUnresponsive(_)), "{}", err);
 
         Ok(())
     }
 
-    #[test]
-    #[timeout(1000)]
-    fn serial_monotonically_increases() {
-        async_io::block_on(test_serial_monotonically_increases());
-    }
-
-    async fn test_serial_monotonically_increases() {
-        let c = Connection::new_session().await.unwrap();
-        let serial = c.next_serial() + 1;
-
-        for next in serial..serial + 10 {
-            assert_eq!(next, c.next_serial());
+    async fn wait_for_no_subscriptions(conn: &Connection) {
+        while !conn.0.signal_subscriptions.lock().await.is_empty() {
+            async_io::Timer::after(std::time::Duration::from_millis(10)).await;
         }
     }
 }
@@ -0,0 +1,219 @@
+use event_listener::Event;
+use std::{
+    collections::VecDeque,
+    sync::{
+        self,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering::SeqCst},
+        Arc, Weak,
+    },
+};
+
+use crate::Message;
+
+/// What a [`MessageStream`] does with incoming messages, once its queue is full.
+///
+/// Each stream has a queue of its own, so the policy of one stream never affects the others,
+/// except for `Block`.
+///
+/// [`MessageStream`]: struct.MessageStream.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drop the oldest message of the queue, to make room for the new one.
+    DropOldest,
+    /// Drop the new message.
+    DropNewest,
+    /// Stop reading from the socket until the stream makes room. This holds back the messages
+    /// of all the streams of the connection, so only use it for streams that keep up.
+    Block,
+}
+
+// The queue of a message stream.
+#[derive(Debug)]
+pub(crate) struct Queue {
+    messages: sync::Mutex<VecDeque<Arc<Message>>>,
+    capacity: AtomicUsize,
+    // Whether the capacity is that of the connection, following its changes.
+    default_capacity: bool,
+    policy: OverflowPolicy,
+    dropped: AtomicU64,
+    // Set once the connection is gone, or the stream is.
+    closed: AtomicBool,
+    pushed: Event,
+    popped: Event,
+}
+
+impl Queue {
+    /// Wait for the next message, or `None` once the connection is gone.
+    pub(crate) async fn pop(&self) -> Option<Arc<Message>> {
+        loop {
+            let listener = self.pushed.listen();
+            let msg = self.messages.lock().expect("poisoned lock").pop_front();
+            if let Some(msg) = msg {
+                self.popped.notify(1);
+
+                return Some(msg);
+            }
+            if self.closed.load(SeqCst) {
+                return None;
+            }
+
+            listener.await;
+        }
+    }
+
+    /// The number of messages dropped because the queue was full.
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(SeqCst)
+    }
+
+    /// Stop queueing messages, and wake up anyone waiting on the queue.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, SeqCst);
+        self.pushed.notify(usize::MAX);
+        self.popped.notify(usize::MAX);
+    }
+
+    async fn push(&self, msg: Arc<Message>) {
+        loop {
+            let listener = self.popped.listen();
+            if self.closed.load(SeqCst) {
+                return;
+            }
+            {
+                let mut messages = self.messages.lock().expect("poisoned lock");
+                if messages.len() < self.capacity.load(SeqCst) {
+                    messages.push_back(msg);
+                    self.pushed.notify(1);
+
+                    return;
+                }
+
+                match self.policy {
+                    OverflowPolicy::DropOldest => {
+                        messages.pop_front();
+                        messages.push_back(msg);
+                        self.dropped.fetch_add(1, SeqCst);
+                        self.pushed.notify(1);
+
+                        return;
+                    }
+                    OverflowPolicy::DropNewest => {
+                        self.dropped.fetch_add(1, SeqCst);
+
+                        return;
+                    }
+                    OverflowPolicy::Block => (),
+                }
+            }
+
+            listener.await;
+        }
+    }
+
+    fn set_capacity(&self, capacity: usize) {
+        // Room for at least a message, or nothing gets through.
+        self.capacity.store(capacity.max(1), SeqCst);
+        self.popped.notify(usize::MAX);
+    }
+}
+
+// Hands out the incoming messages of a connection to the queues of all its streams.
+#[derive(Debug)]
+pub(crate) struct Fanout {
+    queues: sync::Mutex<Vec<Weak<Queue>>>,
+    capacity: AtomicUsize,
+    queue_added: Event,
+    closed: AtomicBool,
+}
+
+impl Fanout {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            queues: sync::Mutex::new(vec![]),
+            capacity: AtomicUsize::new(capacity),
+            queue_added: Event::new(),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// The capacity of the queues created without one.
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity.load(SeqCst)
+    }
+
+    /// Set the default capacity, including that of the existing queues created without one.
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity, SeqCst);
+        for queue in self.queues() {
+            if queue.default_capacity {
+                queue.set_capacity(capacity);
+            }
+        }
+    }
+
+    /// Create a queue for a new stream, receiving all the messages from now on.
+    pub(crate) fn subscribe(&self, capacity: Option<usize>, policy: OverflowPolicy) -> Arc<Queue> {
+        let queue = Arc::new(Queue {
+            messages: sync::Mutex::new(VecDeque::new()),
+            capacity: AtomicUsize::new(0),
+            default_capacity: capacity.is_none(),
+            policy,
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+            pushed: Event::new(),
+            popped: Event::new(),
+        });
+        queue.set_capacity(capacity.unwrap_or_else(|| self.capacity()));
+
+        let mut queues = self.queues.lock().expect("poisoned lock");
+        if self.closed.load(SeqCst) {
+            queue.close();
+        } else {
+            queues.push(Arc::downgrade(&queue));
+            self.queue_added.notify(usize::MAX);
+        }
+        drop(queues);
+
+        queue
+    }
+
+    /// Queue `msg` for all the streams.
+    ///
+    /// As long as there is no stream, this waits for one to be created, so that the messages
+    /// received early on are not lost.
+    pub(crate) async fn send(&self, msg: Arc<Message>) {
+        let queues = loop {
+            let listener = self.queue_added.listen();
+            let queues = self.queues();
+            if !queues.is_empty() {
+                break queues;
+            }
+
+            listener.await;
+        };
+
+        for queue in queues {
+            queue.push(msg.clone()).await;
+        }
+    }
+
+    /// Close all the queues, for the streams to end.
+    pub(crate) fn close(&self) {
+        self.closed.store(true, SeqCst);
+        for queue in self.queues() {
+            queue.close();
+        }
+    }
+
+    // The queues of the live streams, forgetting about the others.
+    fn queues(&self) -> Vec<Arc<Queue>> {
+        let mut queues = self.queues.lock().expect("poisoned lock");
+        queues.retain(|q| q.strong_count() > 0);
+
+        queues
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|q| !q.closed.load(SeqCst))
+            .collect()
+    }
+}
@@ -4,18 +4,20 @@ use std::{
     fmt::Debug,
     future::Future,
     marker::PhantomData,
+    net::TcpStream,
     ops::Deref,
+    os::unix::{io::AsRawFd, net::UnixStream},
     pin::Pin,
     str::FromStr,
     task::{Context, Poll},
 };
 
 use crate::{
-    address::Address,
+    address::{AddressList, TcpOptions},
     guid::Guid,
     handshake::{self, Handshake as SyncHandshake, IoOperation},
     raw::Socket,
-    Error, Result,
+    AuthMechanism, Error, Result,
 };
 
 /// The asynchronous sibling of [`handshake::Handshake`].
@@ -32,6 +34,12 @@ where
     pub fn into_inner(self) -> handshake::Authenticated<S> {
         self.0
     }
+
+    /// Report `guid` as the server GUID, rather than the one of the handshake.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn set_server_guid(&mut self, guid: Guid) {
+        self.0.server_guid = guid;
+    }
 }
 
 impl<S> Deref for Authenticated<S> {
@@ -49,8 +57,21 @@ where
 {
     /// Create a client-side `Authenticated` for the given `socket`.
     pub async fn client(socket: Async<S>) -> Result<Self> {
+        Self::client_with_mechanisms(socket, vec![], None).await
+    }
+
+    /// Create a client-side `Authenticated` for the given `socket`, trying the `custom`
+    /// mechanisms first, and offering to compress the bodies longer than `compression` bytes.
+    pub async fn client_with_mechanisms(
+        socket: Async<S>,
+        custom: Vec<Box<dyn AuthMechanism>>,
+        compression: Option<usize>,
+    ) -> Result<Self> {
+        let mut handshake = handshake::ClientHandshake::with_mechanisms(socket, custom);
+        handshake.set_body_compression(compression);
+
         Handshake {
-            handshake: Some(handshake::ClientHandshake::new(socket)),
+            handshake: Some(handshake),
             phantom: PhantomData,
         }
         .await
@@ -58,8 +79,24 @@ where
 
     /// Create a server-side `Authenticated` for the given `socket`.
     pub async fn server(socket: Async<S>, guid: Guid, client_uid: u32) -> Result<Self> {
+        Self::server_with_mechanisms(socket, guid, client_uid, vec![], None).await
+    }
+
+    /// Create a server-side `Authenticated` for the given `socket`, also accepting the `custom`
+    /// mechanisms, and agreeing to compress the bodies longer than `compression` bytes.
+    pub async fn server_with_mechanisms(
+        socket: Async<S>,
+        guid: Guid,
+        client_uid: u32,
+        custom: Vec<Box<dyn AuthMechanism>>,
+        compression: Option<usize>,
+    ) -> Result<Self> {
+        let mut handshake =
+            handshake::ServerHandshake::with_mechanisms(socket, guid, client_uid, custom);
+        handshake.set_body_compression(compression);
+
         Handshake {
-            handshake: Some(handshake::ServerHandshake::new(socket, guid, client_uid)),
+            handshake: Some(handshake),
             phantom: PhantomData,
         }
         .await
@@ -69,19 +106,115 @@ where
 impl Authenticated<Async<Box<dyn Socket>>> {
     /// Create a `Authenticated` for the session/user message bus.
     pub async fn session() -> Result<Self> {
-        Self::client(Address::session()?.connect().await?.into_boxed()?).await
+        Self::for_address_list(
+            AddressList::session()?,
+            &TcpOptions::default(),
+            vec![],
+            None,
+        )
+        .await
     }
 
     /// Create a `Authenticated` for the system-wide message bus.
     pub async fn system() -> Result<Self> {
-        Self::client(Address::system()?.connect().await?.into_boxed()?).await
+        Self::for_address_list(AddressList::system()?, &TcpOptions::default(), vec![], None).await
+    }
+
+    /// Create a `Authenticated` for the bus that started us through D-Bus activation.
+    pub async fn starter() -> Result<Self> {
+        Self::for_address_list(
+            AddressList::starter()?,
+            &TcpOptions::default(),
+            vec![],
+            None,
+        )
+        .await
     }
 
     /// Create a `Authenticated` for the given [D-Bus address].
     ///
+    /// The address can be a `;`-separated list of addresses, in which case they're tried in order.
+    ///
     /// [D-Bus address]: https://dbus.freedesktop.org/doc/dbus-specification.html#addresses
     pub async fn for_address(address: &str) -> Result<Self> {
-        Self::client(Address::from_str(address)?.connect().await?.into_boxed()?).await
+        Self::for_address_list(
+            AddressList::from_str(address)?,
+            &TcpOptions::default(),
+            vec![],
+            None,
+        )
+        .await
+    }
+
+    /// Create a server-side `Authenticated` for the given `UnixStream`, accepting clients of the
+    /// same user as the peer, or through the `custom` mechanisms.
+    pub(crate) async fn unix_server(
+        stream: UnixStream,
+        guid: Guid,
+        custom: Vec<Box<dyn AuthMechanism>>,
+        compression: Option<usize>,
+    ) -> Result<Self> {
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        let client_uid = {
+            use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
+
+            let creds = getsockopt(stream.as_raw_fd(), PeerCredentials)
+                .map_err(|e| Error::Handshake(format!("Failed to get peer credentials: {}", e)))?;
+
+            creds.uid()
+        };
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        ))]
+        let client_uid = nix::unistd::getpeereid(stream.as_raw_fd())
+            .map_err(|e| Error::Handshake(format!("Failed to get peer credentials: {}", e)))?
+            .0
+            .into();
+
+        let socket = Async::new(Box::new(stream) as Box<dyn Socket>)?;
+
+        Self::server_with_mechanisms(socket, guid, client_uid, custom, compression).await
+    }
+
+    /// Create a server-side `Authenticated` for the given `TcpStream`, accepting clients of the
+    /// same user through `DBUS_COOKIE_SHA1`, or through the `custom` mechanisms.
+    pub(crate) async fn tcp_server(
+        stream: TcpStream,
+        guid: Guid,
+        custom: Vec<Box<dyn AuthMechanism>>,
+        compression: Option<usize>,
+    ) -> Result<Self> {
+        let socket = Async::new(Box::new(stream) as Box<dyn Socket>)?;
+        let mut handshake = handshake::ServerHandshake::without_credentials(socket, guid, custom);
+        handshake.set_body_compression(compression);
+
+        Handshake {
+            handshake: Some(handshake),
+            phantom: PhantomData,
+        }
+        .await
+    }
+
+    /// Create a `Authenticated` for the first address of `addresses` that works, connecting to
+    /// `tcp` addresses with the given options, trying the `custom` mechanisms first, and offering
+    /// to compress the bodies longer than `compression` bytes.
+    pub(crate) async fn for_address_list(
+        addresses: AddressList,
+        tcp: &TcpOptions,
+        custom: Vec<Box<dyn AuthMechanism>>,
+        compression: Option<usize>,
+    ) -> Result<Self> {
+        let (stream, address) = addresses.connect(tcp).await?;
+        let mut auth =
+            Self::client_with_mechanisms(stream.into_boxed()?, custom, compression).await?;
+        auth.0.address = Some(address);
+
+        Ok(auth)
     }
 }
 
@@ -0,0 +1,101 @@
+use async_io::Async;
+use static_assertions::assert_impl_all;
+use std::{fs, os::unix::net::UnixListener, path::PathBuf, str::FromStr};
+
+use crate::{address::Address, azync::Connection, Guid, Result};
+
+/// A socket listening for peer-to-peer D-Bus connections.
+///
+/// A `Listener` is bound to a [D-Bus address]. Besides the `unix:path=` and `unix:abstract=`
+/// addresses, it supports the listen-only `unix:dir=` and `unix:tmpdir=` ones: the socket is then
+/// created with a random `dbus-XXXXXXXXXX` name in the given directory, or under that name as an
+/// abstract socket for `tmpdir` on Linux. Clients connect to the concrete [`address`] of the
+/// listener.
+///
+/// The socket file, if any, is removed when the `Listener` is dropped.
+///
+/// # Example
+///
+/// ```no_run
+///# use std::error::Error;
+///# use zbus::azync::Listener;
+///#
+///# async_io::block_on(async {
+/// let listener = Listener::bind("unix:tmpdir=/tmp")?;
+/// println!("Listening on {}", listener.address());
+///
+/// loop {
+///     let conn = listener.accept().await?;
+///     // Serve `conn`..
+/// }
+///#     Ok::<(), Box<dyn Error + Send + Sync>>(())
+///# })?;
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+///
+/// [D-Bus address]: https://dbus.freedesktop.org/doc/dbus-specification.html#addresses
+/// [`address`]: #method.address
+#[derive(Debug)]
+pub struct Listener {
+    listener: Async<UnixListener>,
+    guid: Guid,
+    address: String,
+    socket_path: Option<PathBuf>,
+}
+
+assert_impl_all!(Listener: Send, Sync, Unpin);
+
+impl Listener {
+    /// Create a socket listening on the given D-Bus address.
+    pub fn bind(address: &str) -> Result<Self> {
+        let (listener, address, socket_path) = Address::from_str(address)?.listen()?;
+        let listener = match Async::new(listener) {
+            Ok(listener) => listener,
+            Err(e) => {
+                if let Some(path) = &socket_path {
+                    let _ = fs::remove_file(path);
+                }
+
+                return Err(e.into());
+            }
+        };
+        let guid = Guid::generate();
+        let address = format!("{},guid={}", address, guid);
+
+        Ok(Self {
+            listener,
+            guid,
+            address,
+            socket_path,
+        })
+    }
+
+    /// The address clients can connect to, including the GUID of the server.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// The server GUID of the accepted connections.
+    pub fn guid(&self) -> &Guid {
+        &self.guid
+    }
+
+    /// Wait for the next client and return the server connection to it.
+    ///
+    /// See [`Connection::new_unix_server`] for details.
+    ///
+    /// [`Connection::new_unix_server`]: struct.Connection.html#method.new_unix_server
+    pub async fn accept(&self) -> Result<Connection> {
+        let (stream, _) = self.listener.accept().await?;
+
+        Connection::new_unix_server(stream.into_inner()?, &self.guid).await
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Some(path) = &self.socket_path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,634 @@
+use async_io::Async;
+use futures_util::future::poll_fn;
+use once_cell::unsync::OnceCell;
+use static_assertions::assert_not_impl_any;
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashSet, VecDeque},
+    convert::TryInto,
+    io::ErrorKind,
+    os::unix::net::UnixStream,
+    rc::Rc,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+use zvariant::ObjectPath;
+
+use super::connection::is_reply_to;
+use crate::{
+    azync::Authenticated,
+    raw::{Connection as RawConnection, Socket},
+    Error, Guid, Message, MessageBuilder, MessageError, MessageType, Result, NATIVE_ENDIAN_SIG,
+};
+
+const FDO_DBUS_SERVICE: &str = "org.freedesktop.DBus";
+const FDO_DBUS_INTERFACE: &str = "org.freedesktop.DBus";
+const FDO_DBUS_PATH: &str = "/org/freedesktop/DBus";
+
+/// A D-Bus connection for a single thread.
+///
+/// Unlike [`Connection`], a `LocalConnection` is neither `Send` nor `Sync`: it's meant for
+/// thread-per-core programs, running many `!Send` futures on a local executor of each thread, that
+/// don't want to pay for the synchronization of a connection shared between threads. The state of
+/// the connection is kept in an [`Rc`], and there is no thread or task receiving messages in the
+/// background: messages are read off the socket by the futures waiting for them, i-e the method
+/// calls waiting for their reply and [`receive_message`]. Any future awaiting on a
+/// `LocalConnection` will do, so they can come from any executor, as long as they stay on the
+/// thread that created the connection.
+///
+/// The messages received while waiting for a reply are queued for [`receive_message`], or for
+/// the call they're the reply to, so several calls can be waited on at once.
+///
+/// This is available with the `local` feature. It covers the basic messaging of [`Connection`]:
+/// method calls, replies, signals and receiving messages. Interfaces are served with a
+/// [`LocalObjectServer`], which unlike [`ObjectServer`] takes `!Send` interfaces, and called with a
+/// [`LocalProxy`]. Signal subscriptions are specific to [`Connection`].
+///
+/// [`Connection`]: struct.Connection.html
+/// [`LocalObjectServer`]: struct.LocalObjectServer.html
+/// [`ObjectServer`]: ../struct.ObjectServer.html
+/// [`LocalProxy`]: struct.LocalProxy.html
+/// [`Rc`]: https://doc.rust-lang.org/std/rc/struct.Rc.html
+/// [`receive_message`]: struct.LocalConnection.html#method.receive_message
+#[derive(Clone, Debug)]
+pub struct LocalConnection(Rc<LocalConnectionInner>);
+
+assert_not_impl_any!(LocalConnection: Send, Sync);
+
+#[derive(Debug)]
+struct LocalConnectionInner {
+    server_guid: Guid,
+    cap_unix_fd: bool,
+    bus_conn: bool,
+    unique_name: OnceCell<String>,
+    raw_conn: RefCell<RawConnection<Async<Box<dyn Socket>>>>,
+    // Serial number for next outgoing message
+    serial: Cell<u32>,
+    // The serial numbers of the method calls waiting for their reply, not to be reused until then.
+    pending_replies: RefCell<HashSet<u32>>,
+    // The messages received but not taken yet, by `receive_message` or the call they reply to.
+    incoming: RefCell<VecDeque<Arc<Message>>>,
+    // The futures waiting for a message, woken up when another one queues a message in `incoming`.
+    waiters: RefCell<Vec<Waker>>,
+}
+
+impl LocalConnection {
+    /// Create and open a D-Bus connection from a `UnixStream`.
+    ///
+    /// The connection may either be set up for a *bus* connection, or not (for peer-to-peer
+    /// communications).
+    pub async fn new_unix_client(stream: UnixStream, bus_connection: bool) -> Result<Self> {
+        // SASL Handshake
+        let auth = Authenticated::client(Async::new(Box::new(stream) as Box<dyn Socket>)?).await?;
+
+        Self::new(auth, bus_connection).await
+    }
+
+    /// Create a server `LocalConnection` for the given `UnixStream` and the server `guid`.
+    ///
+    /// The connection will wait for incoming client authentication handshake & negotiation
+    /// messages, for peer-to-peer communications.
+    pub async fn new_unix_server(stream: UnixStream, guid: &Guid) -> Result<Self> {
+        let auth = Authenticated::unix_server(stream, guid.clone(), vec![], None).await?;
+
+        Self::new(auth, false).await
+    }
+
+    /// Create a `LocalConnection` to the session/user message bus.
+    pub async fn new_session() -> Result<Self> {
+        Self::new(Authenticated::session().await?, true).await
+    }
+
+    /// Create a `LocalConnection` to the system-wide message bus.
+    pub async fn new_system() -> Result<Self> {
+        Self::new(Authenticated::system().await?, true).await
+    }
+
+    /// Create a `LocalConnection` for the given [D-Bus address].
+    ///
+    /// [D-Bus address]: https://dbus.freedesktop.org/doc/dbus-specification.html#addresses
+    pub async fn new_for_address(address: &str, bus_connection: bool) -> Result<Self> {
+        Self::new(Authenticated::for_address(address).await?, bus_connection).await
+    }
+
+    async fn new(
+        auth: Authenticated<Async<Box<dyn Socket>>>,
+        bus_connection: bool,
+    ) -> Result<Self> {
+        let auth = auth.into_inner();
+        let connection = Self(Rc::new(LocalConnectionInner {
+            server_guid: auth.server_guid,
+            cap_unix_fd: auth.cap_unix_fd,
+            bus_conn: bus_connection,
+            unique_name: OnceCell::new(),
+            raw_conn: RefCell::new(auth.conn),
+            serial: Cell::new(1),
+            pending_replies: RefCell::new(HashSet::new()),
+            incoming: RefCell::new(VecDeque::new()),
+            waiters: RefCell::new(vec![]),
+        }));
+
+        if bus_connection {
+            // Not going through `call_method`, as it requires the unique name.
+            let m = MessageBuilder::method_call(FDO_DBUS_PATH, "Hello")?
+                .destination(FDO_DBUS_SERVICE)
+                .interface(FDO_DBUS_INTERFACE)
+                .endian_sig(NATIVE_ENDIAN_SIG)
+                .build(&())?;
+            let name: String = connection.call_method_message(m).await?.body()?;
+            connection
+                .0
+                .unique_name
+                .set(name)
+                // programmer (probably our) error if this fails.
+                .expect("Attempted to set unique_name twice");
+        }
+
+        Ok(connection)
+    }
+
+    /// The unique name as assigned by the message bus, or `None` if not a message bus connection.
+    pub fn unique_name(&self) -> Option<&str> {
+        self.0.unique_name.get().map(|s| s.as_str())
+    }
+
+    /// The server's GUID.
+    pub fn server_guid(&self) -> &str {
+        self.0.server_guid.as_str()
+    }
+
+    /// Checks if `self` is a connection to a message bus.
+    ///
+    /// This will return `false` for p2p connections.
+    pub fn is_bus(&self) -> bool {
+        self.0.bus_conn
+    }
+
+    /// Send `msg` to the peer.
+    ///
+    /// A serial number unique to this connection is set on the message, unless it already has one.
+    /// Like those of [`Connection`], serial numbers skip 0 and those of the calls still waiting for
+    /// their reply when wrapping around.
+    ///
+    /// On successfully sending off `msg`, the assigned serial number is returned.
+    ///
+    /// [`Connection`]: struct.Connection.html
+    pub async fn send_message(&self, mut msg: Message) -> Result<u32> {
+        if !msg.fds().is_empty() && !self.0.cap_unix_fd {
+            return Err(Error::Unsupported);
+        }
+        let serial = self.assign_serial_num(&mut msg)?;
+        self.0.raw_conn.borrow_mut().enqueue_message(msg);
+        poll_fn(|cx| self.poll_flush(cx)).await?;
+
+        Ok(serial)
+    }
+
+    /// Receive the next message, that isn't the reply to a method call made on this connection.
+    pub async fn receive_message(&self) -> Result<Arc<Message>> {
+        let _waiting = Waiting(self);
+        poll_fn(|cx| {
+            self.poll_message(cx, |m| {
+                !matches!(
+                    m.primary_header().msg_type(),
+                    MessageType::Error | MessageType::MethodReturn
+                ) || !self.is_pending_reply(m)
+            })
+        })
+        .await
+    }
+
+    /// Send a method call.
+    ///
+    /// Create a method-call message, send it over the connection, then wait for the reply.
+    ///
+    /// On successful reply, an `Ok(Message)` is returned. On error, an `Err` is returned. D-Bus
+    /// error replies are returned as [`Error::MethodError`].
+    ///
+    /// [`Error::MethodError`]: ../enum.Error.html#variant.MethodError
+    pub async fn call_method<B, E>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'_>, Error = E>,
+        interface: Option<&str>,
+        method_name: &str,
+        body: &B,
+    ) -> Result<Arc<Message>>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+        E: Into<MessageError>,
+    {
+        let m = self
+            .builder(MessageBuilder::method_call(path, method_name)?)?
+            .optional_fields(None, destination, interface)
+            .build(body)?;
+
+        self.call_method_message(m).await
+    }
+
+    /// Emit a signal.
+    ///
+    /// Create a signal message, and send it over the connection.
+    pub async fn emit_signal<B, E>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'_>, Error = E>,
+        interface: &str,
+        signal_name: &str,
+        body: &B,
+    ) -> Result<()>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+        E: Into<MessageError>,
+    {
+        let m = self.signal_message(destination, path, interface, signal_name, body)?;
+
+        self.send_message(m).await.map(|_| ())
+    }
+
+    /// Reply to a message.
+    ///
+    /// Given an existing message (likely a method call), send a reply back to the caller with the
+    /// given `body`.
+    ///
+    /// Returns the message serial number.
+    pub async fn reply<B>(&self, call: &Message, body: &B) -> Result<u32>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        let m = self.reply_message(call, body)?;
+        self.send_message(m).await
+    }
+
+    /// Reply an error to a message.
+    ///
+    /// Given an existing message (likely a method call), send an error reply back to the caller
+    /// with the given `error_name` and `body`.
+    ///
+    /// Returns the message serial number.
+    pub async fn reply_error<B>(&self, call: &Message, error_name: &str, body: &B) -> Result<u32>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        let m = self.error_message(call, error_name, body)?;
+        self.send_message(m).await
+    }
+
+    // The messages sent by `emit_signal`, `reply` and `reply_error`, built ahead of sending them.
+    pub(crate) fn signal_message<B, E>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'_>, Error = E>,
+        interface: &str,
+        signal_name: &str,
+        body: &B,
+    ) -> Result<Message>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+        E: Into<MessageError>,
+    {
+        let m = self
+            .builder(MessageBuilder::signal(path, interface, signal_name)?)?
+            .optional_fields(None, destination, None)
+            .build(body)?;
+
+        Ok(m)
+    }
+
+    pub(crate) fn reply_message<B>(&self, call: &Message, body: &B) -> Result<Message>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        let m = self
+            .builder(MessageBuilder::method_return(call)?)?
+            .build(body)?;
+
+        Ok(m)
+    }
+
+    pub(crate) fn error_message<B>(
+        &self,
+        call: &Message,
+        error_name: &str,
+        body: &B,
+    ) -> Result<Message>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        let m = self
+            .builder(MessageBuilder::error(call, error_name)?)?
+            .build(body)?;
+
+        Ok(m)
+    }
+
+    /// Assigns a serial number to `msg` that is unique to this connection.
+    ///
+    /// Nothing is done if `msg` already has a serial number, which is then returned.
+    ///
+    /// This method can fail if `msg` is corrupt.
+    pub fn assign_serial_num(&self, msg: &mut Message) -> Result<u32> {
+        let mut serial = 0;
+        msg.modify_primary_header(|primary| {
+            serial = *primary.serial_num_or_init(|| self.next_serial());
+            Ok(())
+        })?;
+
+        Ok(serial)
+    }
+
+    fn next_serial(&self) -> u32 {
+        let pending = self.0.pending_replies.borrow();
+        loop {
+            let serial = self.0.serial.get();
+            self.0.serial.set(serial.wrapping_add(1));
+            if serial != 0 && !pending.contains(&serial) {
+                return serial;
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_next_serial(&self, serial: u32) {
+        self.0.serial.set(serial);
+    }
+
+    fn sender(&self) -> Result<Option<&str>> {
+        match self.unique_name() {
+            None if self.is_bus() => Err(Error::NoUniqueName),
+            name => Ok(name),
+        }
+    }
+
+    // Set up `builder` for a message sent from this connection.
+    fn builder<'b>(&'b self, builder: MessageBuilder<'b>) -> Result<MessageBuilder<'b>> {
+        Ok(builder
+            .optional_fields(self.sender()?, None, None)
+            .endian_sig(NATIVE_ENDIAN_SIG))
+    }
+
+    // Send the method-call message `m`, then wait for the reply.
+    async fn call_method_message(&self, mut m: Message) -> Result<Arc<Message>> {
+        let serial = self.assign_serial_num(&mut m)?;
+        self.0.pending_replies.borrow_mut().insert(serial);
+        let _pending = PendingReply { conn: self, serial };
+        self.send_message(m).await?;
+
+        let _waiting = Waiting(self);
+
+        let unique_name = self.unique_name();
+        let reply =
+            poll_fn(|cx| self.poll_message(cx, |m| is_reply_to(m, serial, unique_name))).await?;
+        match reply.primary_header().msg_type() {
+            MessageType::Error => Err(reply.into()),
+            _ => Ok(reply),
+        }
+    }
+
+    fn is_pending_reply(&self, m: &Message) -> bool {
+        let pending = self.0.pending_replies.borrow();
+        match m.header().map(|h| h.reply_serial()) {
+            Ok(Ok(Some(serial))) => pending.contains(&serial),
+            _ => false,
+        }
+    }
+
+    // Take the first message `wanted` from the queue, or from the socket, queuing the others.
+    fn poll_message<F>(&self, cx: &mut Context<'_>, wanted: F) -> Poll<Result<Arc<Message>>>
+    where
+        F: Fn(&Message) -> bool,
+    {
+        {
+            let mut incoming = self.0.incoming.borrow_mut();
+            if let Some(i) = incoming.iter().position(|m| wanted(m)) {
+                return Poll::Ready(Ok(incoming.remove(i).expect("index just found")));
+            }
+        }
+
+        loop {
+            let mut raw_conn = self.0.raw_conn.borrow_mut();
+            let msg = match raw_conn.try_receive_message() {
+                Ok(msg) => Arc::new(msg),
+                Err(Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => {
+                    match raw_conn.socket().poll_readable(cx) {
+                        // Guess socket became ready already so let's try it again.
+                        Poll::Ready(Ok(_)) => continue,
+                        Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                        Poll::Pending => {
+                            // Only the last future polling the socket is woken up by it, and it
+                            // wakes the others when it gets a message for them, or goes away.
+                            let mut waiters = self.0.waiters.borrow_mut();
+                            if !waiters.iter().any(|w| w.will_wake(cx.waker())) {
+                                waiters.push(cx.waker().clone());
+                            }
+
+                            return Poll::Pending;
+                        }
+                    }
+                }
+                Err(e) => return Poll::Ready(Err(e)),
+            };
+            drop(raw_conn);
+            if wanted(&msg) {
+                return Poll::Ready(Ok(msg));
+            }
+
+            // The message is for another future, which may not be polling the socket.
+            self.0.incoming.borrow_mut().push_back(msg);
+            self.wake_waiters();
+        }
+    }
+
+    fn wake_waiters(&self) {
+        let waiters = std::mem::take(&mut *self.0.waiters.borrow_mut());
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+
+    fn poll_flush(&self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        loop {
+            let mut raw_conn = self.0.raw_conn.borrow_mut();
+            match raw_conn.try_flush() {
+                Ok(()) => return Poll::Ready(Ok(())),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    match raw_conn.socket().poll_writable(cx) {
+                        Poll::Pending => return Poll::Pending,
+                        // Guess socket became ready already so let's try it again.
+                        Poll::Ready(Ok(_)) => continue,
+                        Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                    }
+                }
+                Err(e) => return Poll::Ready(Err(Error::Io(e))),
+            }
+        }
+    }
+
+    /// Close the connection.
+    ///
+    /// After this call, all reading and writing operations will fail.
+    pub fn close(self) -> Result<()> {
+        self.0.raw_conn.borrow().close()
+    }
+}
+
+// Frees the serial number of a method call once its reply is in, or the call is cancelled.
+struct PendingReply<'c> {
+    conn: &'c LocalConnection,
+    serial: u32,
+}
+
+// Wakes up the other futures waiting for a message, when a future waiting for one is done or
+// cancelled: it may have been the one the socket wakes up.
+struct Waiting<'c>(&'c LocalConnection);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.wake_waiters();
+    }
+}
+
+impl Drop for PendingReply<'_> {
+    fn drop(&mut self) {
+        self.conn
+            .0
+            .pending_replies
+            .borrow_mut()
+            .remove(&self.serial);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::future::try_join;
+    use ntest::timeout;
+    use std::os::unix::net::UnixStream;
+    use test_env_log::test;
+
+    use super::*;
+
+    #[test]
+    #[timeout(1000)]
+    fn unix_p2p() {
+        async_io::block_on(test_unix_p2p()).unwrap();
+    }
+
+    async fn test_unix_p2p() -> Result<()> {
+        let guid = Guid::generate();
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let (client, server) = try_join(
+            LocalConnection::new_unix_client(p1, false),
+            LocalConnection::new_unix_server(p0, &guid),
+        )
+        .await?;
+        assert_eq!(client.server_guid(), guid.as_str());
+        assert!(!client.is_bus());
+        assert_eq!(client.unique_name(), None);
+
+        // Replies to the second call first.
+        let server_future = async {
+            let first = server.receive_message().await?;
+            let second = server.receive_message().await?;
+            assert_eq!(second.to_string(), "Method call Second");
+            server.reply(&second, &"second").await?;
+            assert_eq!(first.to_string(), "Method call First");
+            server
+                .reply_error(&first, "org.zbus.Error", &"first")
+                .await?;
+
+            let signal = server.receive_message().await?;
+            assert_eq!(signal.to_string(), "Signal Done");
+
+            Ok::<_, Error>(())
+        };
+
+        let call =
+            |method: &'static str| client.call_method(None, "/", Some("org.zbus.p2p"), method, &());
+        let client_future = async {
+            let (first, second) = futures_util::join!(call("First"), call("Second"));
+            match first.unwrap_err() {
+                Error::MethodError(name, detail, _) => {
+                    assert_eq!(name, "org.zbus.Error");
+                    assert_eq!(detail.as_deref(), Some("first"));
+                }
+                e => panic!("unexpected error: {}", e),
+            }
+            assert_eq!(second?.body::<&str>()?, "second");
+            client
+                .emit_signal(None, "/", "org.zbus.p2p", "Done", &())
+                .await
+        };
+
+        try_join(server_future, client_future).await?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn serial_wrap_around() {
+        async_io::block_on(test_serial_wrap_around()).unwrap();
+    }
+
+    async fn test_serial_wrap_around() -> Result<()> {
+        let guid = Guid::generate();
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let (client, server) = try_join(
+            LocalConnection::new_unix_client(p1, false),
+            LocalConnection::new_unix_server(p0, &guid),
+        )
+        .await?;
+
+        let server_future = async {
+            for _ in 0..3 {
+                let m = server.receive_message().await?;
+                let serial = *m.primary_header().serial_num().unwrap();
+                server.reply(&m, &serial).await?;
+            }
+
+            Ok::<_, Error>(())
+        };
+        let client_future = async {
+            client.set_next_serial(u32::MAX);
+            let mut serials = vec![];
+            for _ in 0..3 {
+                let reply = client
+                    .call_method(None, "/", Some("org.zbus.p2p"), "Echo", &())
+                    .await?;
+                serials.push(reply.body::<u32>()?);
+            }
+
+            Ok(serials)
+        };
+
+        let ((), serials) = try_join(server_future, client_future).await?;
+        assert_eq!(serials, [u32::MAX, 1, 2]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn session_bus() {
+        async_io::block_on(test_session_bus()).unwrap();
+    }
+
+    async fn test_session_bus() -> Result<()> {
+        let conn = LocalConnection::new_session().await?;
+        assert!(conn.is_bus());
+        assert!(conn.unique_name().unwrap().starts_with(':'));
+
+        let reply = conn
+            .call_method(
+                Some(FDO_DBUS_SERVICE),
+                FDO_DBUS_PATH,
+                Some(FDO_DBUS_INTERFACE),
+                "GetNameOwner",
+                &FDO_DBUS_SERVICE,
+            )
+            .await?;
+        assert_eq!(reply.body::<&str>()?, FDO_DBUS_SERVICE);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,562 @@
+use static_assertions::assert_not_impl_any;
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    convert::TryInto,
+    sync::Arc,
+};
+use zvariant::ObjectPath;
+
+use crate::{
+    azync::LocalConnection,
+    dispatch::{dispatch_local, MethodTable},
+    fdo::{self, Introspectable, Peer, Properties},
+    logging,
+    object_server::{Node, LOCAL_NODE, LOCAL_SIGNALS},
+    Error, Interface, Message, MessageHeader, MessageType, Result,
+};
+
+// Calls a method of an interface of a given type, see `dispatch_local`.
+type Dispatch = fn(&RefCell<dyn Interface>, &LocalReply, &Message, &str) -> Option<Result<u32>>;
+
+/// An object server for a [`LocalConnection`].
+///
+/// Unlike [`ObjectServer`], a `LocalObjectServer` serves interfaces that aren't `Send`, e.g
+/// sharing state with the rest of the thread through an [`Rc`]. Like the connection, it's meant to
+/// stay on the thread that created it, and messages are only dispatched when [`dispatch_message`]
+/// or [`try_handle_next`] is awaited on.
+///
+/// The interfaces are the same as for [`ObjectServer`], declared with the [`dbus_interface`]
+/// macro, and the standard `Peer`, `Introspectable` and `Properties` interfaces are served along
+/// with them. The signals an interface emits, `PropertiesChanged` included, are sent once the
+/// method (or the function given to [`with`]) returns, before the reply. Deferred methods and the
+/// credentials of callers are not supported, the calls using them fail with `NotSupported`.
+///
+/// This is available with the `local` feature.
+///
+/// # Example
+///
+/// ```no_run
+///# use std::error::Error;
+///# async_io::block_on(async {
+/// use std::{cell::Cell, rc::Rc};
+/// use zbus::{
+///     azync::{LocalConnection, LocalObjectServer},
+///     dbus_interface,
+/// };
+///
+/// struct Counter {
+///     // Shared with the rest of the thread, so `Counter` isn't `Send`.
+///     count: Rc<Cell<u32>>,
+/// }
+///
+/// #[dbus_interface(name = "org.zbus.Counter")]
+/// impl Counter {
+///     fn increment(&self) -> u32 {
+///         self.count.set(self.count.get() + 1);
+///
+///         self.count.get()
+///     }
+/// }
+///
+/// let count = Rc::new(Cell::new(0));
+/// let connection = LocalConnection::new_session().await?;
+/// let mut object_server = LocalObjectServer::new(&connection);
+/// object_server.at("/org/zbus/counter", Counter { count: count.clone() })?;
+///
+/// while count.get() < 10 {
+///     object_server.try_handle_next().await?;
+/// }
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+///# });
+/// ```
+///
+/// [`LocalConnection`]: struct.LocalConnection.html
+/// [`ObjectServer`]: ../struct.ObjectServer.html
+/// [`Rc`]: https://doc.rust-lang.org/std/rc/struct.Rc.html
+/// [`dispatch_message`]: struct.LocalObjectServer.html#method.dispatch_message
+/// [`try_handle_next`]: struct.LocalObjectServer.html#method.try_handle_next
+/// [`dbus_interface`]: ../attr.dbus_interface.html
+/// [`with`]: struct.LocalObjectServer.html#method.with
+#[derive(derivative::Derivative)]
+#[derivative(Debug)]
+pub struct LocalObjectServer {
+    conn: LocalConnection,
+    root: Node,
+    // The method dispatching of each type of interface registered.
+    #[derivative(Debug = "ignore")]
+    dispatchers: HashMap<TypeId, Dispatch>,
+}
+
+assert_not_impl_any!(LocalObjectServer: Send, Sync);
+
+impl LocalObjectServer {
+    /// Creates a new `LocalObjectServer` for a given connection.
+    pub fn new(connection: &LocalConnection) -> Self {
+        let mut dispatchers = HashMap::new();
+        dispatchers.insert(TypeId::of::<Peer>(), dispatch_local::<Peer> as Dispatch);
+        dispatchers.insert(
+            TypeId::of::<Introspectable>(),
+            dispatch_local::<Introspectable>,
+        );
+        dispatchers.insert(TypeId::of::<Properties>(), dispatch_local::<Properties>);
+
+        Self {
+            conn: connection.clone(),
+            root: Node::new("/".try_into().expect("zvariant bug")),
+            dispatchers,
+        }
+    }
+
+    /// Register a D-Bus [`Interface`] at a given path.
+    ///
+    /// If the interface already exists at this path, returns false.
+    ///
+    /// [`Interface`]: ../trait.Interface.html
+    pub fn at<'p, P, I, E>(&mut self, path: P, iface: I) -> Result<bool>
+    where
+        I: Interface + MethodTable,
+        P: TryInto<ObjectPath<'p>, Error = E>,
+        E: Into<Error>,
+    {
+        let path = path.try_into().map_err(Into::into)?;
+        self.dispatchers
+            .insert(TypeId::of::<I>(), dispatch_local::<I>);
+
+        Ok(self
+            .root
+            .child_mut(&path, true)
+            .unwrap()
+            .at(I::name().into(), iface))
+    }
+
+    /// Unregister a D-Bus [`Interface`] at a given path.
+    ///
+    /// If there are no more interfaces left at that path, destroys the object as well.
+    /// Returns whether the object was destroyed.
+    ///
+    /// [`Interface`]: ../trait.Interface.html
+    pub fn remove<'p, I, P, E>(&mut self, path: P) -> Result<bool>
+    where
+        I: Interface,
+        P: TryInto<ObjectPath<'p>, Error = E>,
+        E: Into<Error>,
+    {
+        let path = path.try_into().map_err(Into::into)?;
+
+        self.root
+            .remove_interface_at(&path, I::name(), Some(TypeId::of::<I>()))
+    }
+
+    /// Run `func` with the interface `I` at `path`, then send the signals it emitted.
+    ///
+    /// If the interface was not found, return `Error::InterfaceNotFound`. If `func` fails, the
+    /// signals it emitted are still sent, and its error is returned.
+    pub async fn with<'p, P, F, I>(&self, path: P, func: F) -> Result<()>
+    where
+        F: Fn(&I) -> Result<()>,
+        I: Interface,
+        P: TryInto<ObjectPath<'p>, Error = zvariant::Error>,
+    {
+        let path = path.try_into()?;
+        let node = self.root.child(&path).ok_or(Error::InterfaceNotFound)?;
+        let (res, signals) = self.run(node, || node.with_iface_func(func));
+        self.send_all(signals).await?;
+
+        res
+    }
+
+    /// Run `func` with a mutable reference to the interface `I` at `path`, then signal the changes
+    /// it made to the properties.
+    ///
+    /// See [`ObjectServer::with_mut_tracked`] for how the changes are tracked.
+    ///
+    /// [`ObjectServer::with_mut_tracked`]: ../struct.ObjectServer.html#method.with_mut_tracked
+    pub async fn with_mut_tracked<'p, P, F, I>(&self, path: P, func: F) -> Result<()>
+    where
+        F: FnOnce(&mut I) -> Result<()>,
+        I: Interface,
+        P: TryInto<ObjectPath<'p>, Error = zvariant::Error>,
+    {
+        let path = path.try_into()?;
+        let node = self.root.child(&path).ok_or(Error::InterfaceNotFound)?;
+        let (res, signals) = self.run(node, || node.with_iface_mut_tracked(func));
+        self.send_all(signals).await?;
+
+        res
+    }
+
+    // Run `f` on behalf of `node`, along with the signals it emitted.
+    fn run<F, R>(&self, node: &Node, f: F) -> (R, Vec<Message>)
+    where
+        F: FnOnce() -> R,
+    {
+        let signals = LocalSignals {
+            conn: self.conn.clone(),
+            queue: RefCell::new(vec![]),
+        };
+        let res = LOCAL_SIGNALS.set(&signals, || LOCAL_NODE.set(node, f));
+
+        (res, signals.queue.into_inner())
+    }
+
+    async fn send_all(&self, messages: Vec<Message>) -> Result<()> {
+        for m in messages {
+            self.conn.send_message(m).await?;
+        }
+
+        Ok(())
+    }
+
+    // Call the method of `msg`, keeping its reply in `reply`, along with the signals it emitted.
+    fn call_method(
+        &self,
+        msg_header: &MessageHeader<'_>,
+        msg: &Message,
+        reply: &LocalReply,
+    ) -> fdo::Result<(Result<u32>, Vec<Message>)> {
+        let path = msg_header
+            .path()
+            .ok()
+            .flatten()
+            .ok_or_else(|| fdo::Error::Failed("Missing object path".into()))?;
+        let iface = msg_header
+            .interface()
+            .ok()
+            .flatten()
+            .ok_or_else(|| fdo::Error::Failed("Missing interface".into()))?;
+        let member = msg_header
+            .member()
+            .ok()
+            .flatten()
+            .ok_or_else(|| fdo::Error::Failed("Missing member".into()))?;
+
+        let node = self
+            .root
+            .child(path)
+            .ok_or_else(|| fdo::Error::UnknownObject(format!("Unknown object '{}'", path)))?;
+        let iface = node.get_interface(iface).ok_or_else(|| {
+            fdo::Error::UnknownInterface(format!("Unknown interface '{}'", iface))
+        })?;
+        let unknown_method = || fdo::Error::UnknownMethod(format!("Unknown method '{}'", member));
+        let type_id = <dyn Interface as Any>::type_id(&*iface.borrow());
+        let dispatch = self.dispatchers.get(&type_id).ok_or_else(unknown_method)?;
+
+        let (res, signals) = self.run(node, || dispatch(&iface, reply, msg, member));
+
+        Ok((res.ok_or_else(unknown_method)?, signals))
+    }
+
+    async fn dispatch_method_call(
+        &self,
+        msg_header: &MessageHeader<'_>,
+        msg: &Message,
+    ) -> Result<u32> {
+        log::trace!(target: logging::OBJECT_SERVER, "Dispatching {}", msg);
+        let reply = LocalReply {
+            conn: self.conn.clone(),
+            message: RefCell::new(None),
+        };
+        let signals = match self.call_method(msg_header, msg, &reply) {
+            Ok((res, signals)) => {
+                res?;
+                signals
+            }
+            Err(e) => {
+                log::debug!(target: logging::OBJECT_SERVER, "{} failed: {}", msg, e);
+                reply.reply_error(msg, e)?;
+                vec![]
+            }
+        };
+
+        let res = match self.send_all(signals).await {
+            Ok(()) => match reply.message.into_inner() {
+                Some(m) => self.conn.send_message(m).await,
+                None => Ok(0),
+            },
+            Err(e) => Err(e),
+        };
+        if let Err(e) = &res {
+            log::warn!(target: logging::OBJECT_SERVER, "Failed to reply to {}: {}", msg, e);
+        }
+
+        res
+    }
+
+    /// Dispatch an incoming message to a registered interface.
+    ///
+    /// The object server will handle the message by:
+    ///
+    /// - looking up the called object path & interface,
+    ///
+    /// - calling the associated method if one exists,
+    ///
+    /// - sending the signals the method emitted, then the reply (either a return or error
+    ///   message) to the caller through the associated connection.
+    ///
+    /// Returns an error if the message is malformed, true if it's handled, false otherwise.
+    pub async fn dispatch_message(&mut self, msg: &Message) -> Result<bool> {
+        let msg_header = msg.header()?;
+
+        match msg_header.message_type()? {
+            MessageType::MethodCall => {
+                self.dispatch_method_call(&msg_header, msg).await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Receive and handle the next message from the associated connection.
+    ///
+    /// This function will read the incoming message from
+    /// [`receive_message()`](LocalConnection::receive_message) of the associated connection and
+    /// pass it to [`dispatch_message()`](Self::dispatch_message). If the message was handled by an
+    /// interface, it returns `Ok(None)`. If not, it returns the received message.
+    ///
+    /// Returns an error if the message is malformed or an error occurred.
+    pub async fn try_handle_next(&mut self) -> Result<Option<Arc<Message>>> {
+        let msg = self.conn.receive_message().await?;
+
+        if !self.dispatch_message(&msg).await? {
+            Ok(Some(msg))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+// The reply to a method call dispatched by a `LocalObjectServer`, built by the dispatching code
+// for the server to send.
+#[derive(Debug)]
+pub(crate) struct LocalReply {
+    conn: LocalConnection,
+    message: RefCell<Option<Message>>,
+}
+
+impl LocalReply {
+    // As the reply is only sent later, there's no serial number to return yet.
+    pub(crate) fn reply<B>(&self, call: &Message, body: &B) -> Result<u32>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        let m = self.conn.reply_message(call, body)?;
+        self.message.replace(Some(m));
+
+        Ok(0)
+    }
+
+    pub(crate) fn reply_error(&self, call: &Message, e: fdo::Error) -> Result<u32> {
+        // Unlike the others, `ZBus` errors have no D-Bus name to reply with.
+        let e = match e {
+            fdo::Error::ZBus(e) => fdo::Error::Failed(e.to_string()),
+            e => e,
+        };
+        let m = self.conn.error_message(call, e.name(), &e.description())?;
+        self.message.replace(Some(m));
+
+        Ok(0)
+    }
+}
+
+// The signals emitted while a `LocalObjectServer` runs the code of an interface, for the server
+// to send once it returns.
+#[derive(Debug)]
+pub(crate) struct LocalSignals {
+    conn: LocalConnection,
+    queue: RefCell<Vec<Message>>,
+}
+
+impl LocalSignals {
+    pub(crate) fn queue<B>(
+        &self,
+        destination: Option<&str>,
+        path: &ObjectPath<'_>,
+        interface: &str,
+        signal_name: &str,
+        body: &B,
+    ) -> Result<()>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        let m =
+            self.conn
+                .signal_message(destination, path.as_str(), interface, signal_name, body)?;
+        self.queue.borrow_mut().push(m);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::future::{select, try_join, Either};
+    use ntest::timeout;
+    use std::{cell::RefCell, os::unix::net::UnixStream, rc::Rc};
+    use test_env_log::test;
+    use zvariant::{OwnedValue, Value};
+
+    use super::*;
+    use crate::{azync::LocalProxy, dbus_interface, Guid};
+
+    // Shares its letters with the test, so it isn't `Send`.
+    struct Mailbox {
+        letters: Rc<RefCell<Vec<String>>>,
+        label: String,
+    }
+
+    #[dbus_interface(name = "org.zbus.Mailbox")]
+    impl Mailbox {
+        fn post(&mut self, letter: &str) -> fdo::Result<u32> {
+            if letter.is_empty() {
+                return Err(fdo::Error::InvalidArgs("empty letter".to_string()));
+            }
+            self.letters.borrow_mut().push(letter.to_string());
+            self.posted(letter)?;
+
+            Ok(self.letters.borrow().len() as u32)
+        }
+
+        #[dbus_interface(property)]
+        fn label(&self) -> &str {
+            &self.label
+        }
+
+        #[dbus_interface(property)]
+        fn set_label(&mut self, label: &str) {
+            self.label = label.to_string();
+        }
+
+        #[dbus_interface(signal)]
+        fn posted(&self, letter: &str) -> zbus::Result<()>;
+    }
+
+    // Serve the calls of the client, until it's done.
+    async fn serve(object_server: &mut LocalObjectServer) -> Result<()> {
+        loop {
+            if let Some(msg) = object_server.try_handle_next().await? {
+                panic!("unhandled message: {}", msg);
+            }
+        }
+    }
+
+    fn method_error(e: Error) -> (String, Option<String>) {
+        match e {
+            Error::MethodError(name, detail, _) => (name, detail),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn p2p() {
+        async_io::block_on(test_p2p()).unwrap();
+    }
+
+    async fn test_p2p() -> Result<()> {
+        let guid = Guid::generate();
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let (client, server) = try_join(
+            LocalConnection::new_unix_client(p1, false),
+            LocalConnection::new_unix_server(p0, &guid),
+        )
+        .await?;
+
+        let letters = Rc::new(RefCell::new(vec![]));
+        let mailbox = Mailbox {
+            letters: letters.clone(),
+            label: "home".to_string(),
+        };
+        let mut object_server = LocalObjectServer::new(&server);
+        assert!(object_server.at("/zbus/test/mailbox", mailbox)?);
+
+        let client_future = async {
+            let proxy = LocalProxy::new(&client, None, "/zbus/test/mailbox", "org.zbus.Mailbox")?;
+
+            // Method calls, with their signals sent before the reply.
+            assert_eq!(proxy.call::<_, u32>("Post", &"hello").await?, 1);
+            let signal = client.receive_message().await?;
+            assert_eq!(signal.to_string(), "Signal Posted");
+            assert_eq!(signal.body::<&str>()?, "hello");
+
+            // Properties, with their changes signalled.
+            assert_eq!(proxy.get_property::<String>("Label").await?, "home");
+            proxy.set_property("Label", "work").await?;
+            assert_eq!(proxy.get_property::<String>("Label").await?, "work");
+            let signal = client.receive_message().await?;
+            assert_eq!(signal.to_string(), "Signal PropertiesChanged");
+            let (interface, changed, _) =
+                signal.body::<(&str, HashMap<&str, Value<'_>>, Vec<&str>)>()?;
+            assert_eq!(interface, "org.zbus.Mailbox");
+            assert_eq!(changed["Label"], Value::from("work"));
+            let all: HashMap<String, OwnedValue> = client
+                .call_method(
+                    None,
+                    "/zbus/test/mailbox",
+                    Some("org.freedesktop.DBus.Properties"),
+                    "GetAll",
+                    &"org.zbus.Mailbox",
+                )
+                .await?
+                .body()?;
+            assert_eq!(*all["Label"], Value::from("work"));
+
+            // Errors, from the interface and from the object server.
+            let (name, detail) = method_error(proxy.call::<_, u32>("Post", &"").await.unwrap_err());
+            assert_eq!(name, "org.freedesktop.DBus.Error.InvalidArgs");
+            assert_eq!(detail.as_deref(), Some("empty letter"));
+            let (name, _) = method_error(proxy.call::<_, ()>("Burn", &()).await.unwrap_err());
+            assert_eq!(name, "org.freedesktop.DBus.Error.UnknownMethod");
+            let nowhere = LocalProxy::new(&client, None, "/zbus/test/nowhere", "org.zbus.Mailbox")?;
+            let (name, _) = method_error(nowhere.call::<_, u32>("Post", &"hi").await.unwrap_err());
+            assert_eq!(name, "org.freedesktop.DBus.Error.UnknownObject");
+
+            // The standard interfaces.
+            let peer = LocalProxy::new(
+                &client,
+                None,
+                "/zbus/test/mailbox",
+                "org.freedesktop.DBus.Peer",
+            )?;
+            peer.call::<_, ()>("Ping", &()).await?;
+            let xml = proxy.introspect().await?;
+            assert!(xml.contains("<interface name=\"org.zbus.Mailbox\">"));
+            assert!(xml.contains("<interface name=\"org.freedesktop.DBus.Peer\">"));
+
+            Ok::<_, Error>(())
+        };
+
+        match select(Box::pin(serve(&mut object_server)), Box::pin(client_future)).await {
+            Either::Left((res, _)) => res?,
+            Either::Right((res, _)) => res?,
+        }
+        assert_eq!(*letters.borrow(), ["hello"]);
+
+        // Signals emitted from outside of the method calls.
+        object_server
+            .with("/zbus/test/mailbox", |mailbox: &Mailbox| {
+                mailbox.posted("from the server")
+            })
+            .await?;
+        let signal = client.receive_message().await?;
+        assert_eq!(signal.body::<&str>()?, "from the server");
+        object_server
+            .with_mut_tracked("/zbus/test/mailbox", |mailbox: &mut Mailbox| {
+                mailbox.label = "away".to_string();
+                Ok(())
+            })
+            .await?;
+        let signal = client.receive_message().await?;
+        assert_eq!(signal.to_string(), "Signal PropertiesChanged");
+
+        assert!(object_server.remove::<Mailbox, _, _>("/zbus/test/mailbox")?);
+        let err = object_server
+            .with("/zbus/test/mailbox", |_: &Mailbox| Ok(()))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InterfaceNotFound));
+
+        Ok(())
+    }
+}
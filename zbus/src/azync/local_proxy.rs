@@ -0,0 +1,202 @@
+use static_assertions::assert_not_impl_any;
+use std::{
+    borrow::Cow,
+    convert::{TryFrom, TryInto},
+    sync::Arc,
+};
+use zvariant::{ObjectPath, OwnedValue, Value};
+
+use crate::{azync::LocalConnection, fdo, Error, Message, Result};
+
+const FDO_PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const FDO_INTROSPECTABLE_INTERFACE: &str = "org.freedesktop.DBus.Introspectable";
+
+/// A proxy for an interface of an object, over a [`LocalConnection`].
+///
+/// This is the counterpart of [`Proxy`] for a [`LocalConnection`], for calling methods and
+/// accessing properties. There are no signal subscriptions on a [`LocalConnection`], so signals
+/// are received with [`LocalConnection::receive_message`]. Like the connection, a `LocalProxy` is
+/// neither `Send` nor `Sync`.
+///
+/// This is available with the `local` feature.
+///
+/// # Example
+///
+/// ```no_run
+///# use std::error::Error;
+///# async_io::block_on(async {
+/// use zbus::azync::{LocalConnection, LocalProxy};
+///
+/// let connection = LocalConnection::new_session().await?;
+/// let p = LocalProxy::new(
+///     &connection,
+///     Some("org.freedesktop.DBus"),
+///     "/org/freedesktop/DBus",
+///     "org.freedesktop.DBus",
+/// )?;
+/// let _id: String = p.call("GetId", &()).await?;
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+///# });
+/// ```
+///
+/// [`LocalConnection`]: struct.LocalConnection.html
+/// [`Proxy`]: struct.Proxy.html
+/// [`LocalConnection::receive_message`]: struct.LocalConnection.html#method.receive_message
+#[derive(Clone, Debug)]
+pub struct LocalProxy<'a> {
+    conn: LocalConnection,
+    // Peer-to-peer connections have nothing to route on it.
+    destination: Option<Cow<'a, str>>,
+    path: ObjectPath<'a>,
+    interface: Cow<'a, str>,
+}
+
+assert_not_impl_any!(LocalProxy<'_>: Send, Sync);
+
+impl<'a> LocalProxy<'a> {
+    /// Create a new `LocalProxy` for the given destination/path/interface.
+    ///
+    /// The destination is needed on bus connections, where the calls without one go to the bus
+    /// itself. On peer-to-peer connections, messages go to the peer anyway.
+    pub fn new<E>(
+        conn: &LocalConnection,
+        destination: Option<&'a str>,
+        path: impl TryInto<ObjectPath<'a>, Error = E>,
+        interface: &'a str,
+    ) -> Result<Self>
+    where
+        E: Into<Error>,
+    {
+        Ok(Self {
+            conn: conn.clone(),
+            destination: destination.map(Cow::Borrowed),
+            path: path.try_into().map_err(Into::into)?,
+            interface: Cow::Borrowed(interface),
+        })
+    }
+
+    /// Get a reference to the associated connection.
+    pub fn connection(&self) -> &LocalConnection {
+        &self.conn
+    }
+
+    /// Get a reference to the destination service name, if any.
+    pub fn destination(&self) -> Option<&str> {
+        self.destination.as_deref()
+    }
+
+    /// Get a reference to the object path.
+    pub fn path(&self) -> &ObjectPath<'_> {
+        &self.path
+    }
+
+    /// Get a reference to the interface.
+    pub fn interface(&self) -> &str {
+        &self.interface
+    }
+
+    /// Introspect the associated object, and return the XML description.
+    pub async fn introspect(&self) -> fdo::Result<String> {
+        let reply = self
+            .conn
+            .call_method(
+                self.destination(),
+                self.path.as_str(),
+                Some(FDO_INTROSPECTABLE_INTERFACE),
+                "Introspect",
+                &(),
+            )
+            .await?;
+
+        reply.body().map_err(Into::into)
+    }
+
+    /// Get the property `property_name`.
+    ///
+    /// Effectively, call the `Get` method of the `org.freedesktop.DBus.Properties` interface.
+    pub async fn get_property<T>(&self, property_name: &str) -> fdo::Result<T>
+    where
+        T: TryFrom<OwnedValue>,
+    {
+        let reply = self
+            .conn
+            .call_method(
+                self.destination(),
+                self.path.as_str(),
+                Some(FDO_PROPERTIES_INTERFACE),
+                "Get",
+                &(self.interface(), property_name),
+            )
+            .await?;
+
+        reply
+            .body::<OwnedValue>()?
+            .try_into()
+            .map_err(|_| Error::InvalidReply.into())
+    }
+
+    /// Set the property `property_name`.
+    ///
+    /// Effectively, call the `Set` method of the `org.freedesktop.DBus.Properties` interface.
+    pub async fn set_property<'t, T: 't>(&self, property_name: &str, value: T) -> fdo::Result<()>
+    where
+        T: Into<Value<'t>>,
+    {
+        self.conn
+            .call_method(
+                self.destination(),
+                self.path.as_str(),
+                Some(FDO_PROPERTIES_INTERFACE),
+                "Set",
+                &(self.interface(), property_name, &value.into()),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Call a method and return the reply.
+    ///
+    /// Typically, you would want to use [`call`] method instead. Use this method if you need to
+    /// deserialize the reply message manually (this way, you can avoid the memory
+    /// allocation/copying, by deserializing the reply to an unowned type).
+    ///
+    /// [`call`]: struct.LocalProxy.html#method.call
+    pub async fn call_method<B>(&self, method_name: &str, body: &B) -> Result<Arc<Message>>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        self.conn
+            .call_method(
+                self.destination(),
+                self.path.as_str(),
+                Some(self.interface()),
+                method_name,
+                body,
+            )
+            .await
+    }
+
+    /// Call a method and return the reply body.
+    ///
+    /// Use [`call_method`] instead if you need to deserialize the reply manually/separately.
+    ///
+    /// [`call_method`]: struct.LocalProxy.html#method.call_method
+    pub async fn call<B, R>(&self, method_name: &str, body: &B) -> Result<R>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+        R: serde::de::DeserializeOwned + zvariant::Type,
+    {
+        let reply = self.call_method(method_name, body).await?;
+        // Since we don't keep the reply msg around and user still might use the FDs after this
+        // call returns, we must disown the FDs so we don't end up closing them after the call.
+        reply.disown_fds();
+
+        reply.reply_body(
+            self.destination(),
+            Some(self.interface()),
+            method_name,
+            false,
+        )
+    }
+}
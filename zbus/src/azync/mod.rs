@@ -6,5 +6,27 @@ mod handshake;
 pub(crate) use handshake::*;
 mod connection;
 pub use connection::*;
+mod fanout;
+pub use fanout::OverflowPolicy;
+mod listener;
+pub use listener::*;
 mod proxy;
 pub use proxy::*;
+mod property_cache;
+pub(crate) use property_cache::PropertyCache;
+mod socket;
+pub(crate) use socket::AsyncSocket;
+#[cfg(feature = "local")]
+mod local_connection;
+#[cfg(feature = "local")]
+pub use local_connection::*;
+#[cfg(feature = "local")]
+mod local_object_server;
+#[cfg(feature = "local")]
+pub use local_object_server::LocalObjectServer;
+#[cfg(feature = "local")]
+pub(crate) use local_object_server::{LocalReply, LocalSignals};
+#[cfg(feature = "local")]
+mod local_proxy;
+#[cfg(feature = "local")]
+pub use local_proxy::*;
@@ -0,0 +1,191 @@
+use async_task::Task;
+use futures_util::stream::StreamExt;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use zvariant::{ObjectPath, OwnedValue, Value};
+
+use crate::{azync::Connection, fdo::AsyncPropertiesProxy, logging, Result};
+
+// The values of the properties of a proxy, as last received.
+//
+// A task started along keeps them up to date with the `PropertiesChanged` signals of the object,
+// until the cache is dropped.
+#[derive(derivative::Derivative)]
+#[derivative(Debug)]
+pub(crate) struct PropertyCache {
+    values: Arc<Mutex<Values>>,
+    max_size: usize,
+    #[derivative(Debug = "ignore")]
+    _task: Task<()>,
+}
+
+impl PropertyCache {
+    // Start caching the properties of `interface` at `path`, up to `max_size` bytes per value.
+    //
+    // The signals are subscribed to before this returns, so no change is missed once a value is
+    // cached.
+    pub(crate) async fn new(
+        conn: &Connection,
+        destination: Option<&str>,
+        path: &ObjectPath<'_>,
+        interface: &str,
+        max_size: usize,
+    ) -> Result<Self> {
+        let mut builder = AsyncPropertiesProxy::builder(conn).path(path.to_owned())?;
+        if let Some(destination) = destination {
+            builder = builder.destination(destination.to_string());
+        }
+        let proxy = builder.build_async().await?;
+        let mut changes = proxy.receive_properties_changed().await?;
+
+        let values = Arc::new(Mutex::new(Values::default()));
+        let cached = values.clone();
+        let interface = interface.to_string();
+        let task = conn.executor().spawn(async move {
+            while let Some(signal) = changes.next().await {
+                let args = match signal.args() {
+                    Ok(args) => args,
+                    Err(e) => {
+                        log::warn!(target: logging::PROXY, "Invalid PropertiesChanged: {}", e);
+                        continue;
+                    }
+                };
+                if args.interface_name != interface {
+                    continue;
+                }
+
+                let mut values = cached.lock().expect("lock poisoned");
+                for (name, value) in &args.changed_properties {
+                    values.change(name);
+                    values.update(max_size, name, value);
+                }
+                for name in &args.invalidated_properties {
+                    values.change(name);
+                    values.values.remove(*name);
+                }
+            }
+        });
+
+        Ok(Self {
+            values,
+            max_size,
+            _task: task,
+        })
+    }
+
+    // The cached value of `name` if any, or the generation of the property to pass to `insert`
+    // along with the value read from the object otherwise.
+    pub(crate) fn get(&self, name: &str) -> std::result::Result<OwnedValue, u64> {
+        let values = self.values.lock().expect("lock poisoned");
+        match values.values.get(name) {
+            Some(value) => Ok(value.clone()),
+            None => Err(values.generation(name)),
+        }
+    }
+
+    // Cache `value`, read from the object at `generation`, unless the property changed since.
+    //
+    // This way, a value read before a `PropertiesChanged` signal doesn't replace the newer value
+    // of the signal, if the reply arrives after it.
+    pub(crate) fn insert(&self, name: &str, value: &Value<'_>, generation: u64) {
+        let mut values = self.values.lock().expect("lock poisoned");
+        if values.generation(name) == generation {
+            values.update(self.max_size, name, value);
+        }
+    }
+
+    pub(crate) fn remove(&self, name: &str) {
+        let mut values = self.values.lock().expect("lock poisoned");
+        values.change(name);
+        values.values.remove(name);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.values.lock().expect("lock poisoned").clear();
+    }
+
+    // The approximate size of the cached values, along with their names.
+    pub(crate) fn size(&self) -> usize {
+        self.values
+            .lock()
+            .expect("lock poisoned")
+            .values
+            .iter()
+            .map(|(name, value)| name.len() + value.serialized_size_hint())
+            .sum()
+    }
+}
+
+// The cached values, along with the generation of each property: the number of the last change
+// to it, to tell whether a value read from the object is still current once received.
+#[derive(Debug, Default)]
+struct Values {
+    values: HashMap<String, OwnedValue>,
+    // The number of changes so far.
+    changes: u64,
+    // The number of the last change to each property changed since the last clearing.
+    changed: HashMap<String, u64>,
+    // The number of the last clearing, which changes all properties.
+    cleared: u64,
+}
+
+impl Values {
+    fn generation(&self, name: &str) -> u64 {
+        self.changed.get(name).copied().unwrap_or(self.cleared)
+    }
+
+    fn change(&mut self, name: &str) {
+        self.changes += 1;
+        self.changed.insert(name.to_string(), self.changes);
+    }
+
+    fn clear(&mut self) {
+        self.changes += 1;
+        self.cleared = self.changes;
+        self.changed.clear();
+        self.values.clear();
+    }
+
+    // Cache `value`, unless it's bigger than `max_size`, in which case the previous value is
+    // dropped instead.
+    fn update(&mut self, max_size: usize, name: &str, value: &Value<'_>) {
+        if value.serialized_size_hint() > max_size {
+            self.values.remove(name);
+        } else {
+            self.values.insert(name.to_string(), value.into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_reads() {
+        let mut values = Values::default();
+        let value = Value::from(1u32);
+
+        // A read racing with a change, then with a clearing.
+        let generation = values.generation("Count");
+        values.change("Count");
+        values.update(64, "Count", &Value::from(2u32));
+        assert_ne!(values.generation("Count"), generation);
+        let generation = values.generation("Count");
+        values.clear();
+        assert_ne!(values.generation("Count"), generation);
+
+        // Changes to other properties don't matter.
+        let generation = values.generation("Count");
+        values.change("Name");
+        assert_eq!(values.generation("Count"), generation);
+        values.update(64, "Count", &value);
+        assert_eq!(values.values.get("Count"), Some(&OwnedValue::from(1u32)));
+
+        // Too big a value isn't cached, and drops the previous one.
+        values.update(2, "Count", &Value::from(3u64));
+        assert_eq!(values.values.get("Count"), None);
+    }
+}
@@ -1,25 +1,31 @@
 use async_lock::Mutex;
 use futures_core::{future::BoxFuture, stream};
-use futures_util::stream::StreamExt;
+use futures_util::{
+    future::{select, Either, FutureExt},
+    stream::StreamExt,
+};
 use once_cell::sync::OnceCell;
 use slotmap::{new_key_type, SlotMap};
 use static_assertions::assert_impl_all;
 use std::{
     borrow::Cow,
+    collections::VecDeque,
     convert::{TryFrom, TryInto},
-    future::ready,
+    future::{ready, Future},
     io::{self, ErrorKind},
     pin::Pin,
-    sync::Arc,
+    sync::{self, Arc},
     task::{Context, Poll},
+    time::Instant,
 };
 
 use zvariant::{ObjectPath, OwnedValue, Value};
 
 use crate::{
-    azync::{Connection, MessageStream},
+    azync::{Connection, MessageStream, PropertyCache},
     fdo::{self, AsyncIntrospectableProxy, AsyncPropertiesProxy},
-    Error, Message, MessageHeader, MessageType, Result,
+    logging, AsyncDrop, Error, InterfaceMetadata, Message, MessageBuilder, MessageHeader,
+    MessageType, Priority, Result, RetryPolicy,
 };
 
 type SignalHandler = Box<dyn for<'msg> FnMut(&'msg Message) -> BoxFuture<'msg, Result<()>> + Send>;
@@ -34,6 +40,7 @@ assert_impl_all!(SignalHandlerId: Send, Sync, Unpin);
 struct SignalHandlerInfo {
     signal_name: &'static str,
     handler: SignalHandler,
+    subscription_id: Option<u64>,
 }
 
 /// The asynchronous sibling of [`crate::Proxy`].
@@ -84,12 +91,13 @@ struct SignalHandlerInfo {
 ///
 /// At the moment, `Proxy` doesn't:
 ///
-/// * cache properties
+/// * cache properties, unless enabled with [`ProxyBuilder::cache_properties`]
 /// * track the current name owner
 /// * prevent auto-launching
 ///
 /// [`futures` crate]: https://crates.io/crates/futures
 /// [`dbus_proxy`]: attr.dbus_proxy.html
+/// [`ProxyBuilder::cache_properties`]: ../struct.ProxyBuilder.html#method.cache_properties
 #[derive(Debug)]
 pub struct Proxy<'a> {
     pub(crate) inner: Arc<ProxyInner<'a>>,
@@ -101,20 +109,28 @@ assert_impl_all!(Proxy<'_>: Send, Sync, Unpin);
 #[derivative(Debug)]
 pub(crate) struct ProxyInner<'a> {
     pub(crate) conn: Connection,
-    pub(crate) destination: Cow<'a, str>,
+    // Always set on bus connections. Peer-to-peer connections have nothing to route on it.
+    pub(crate) destination: Option<Cow<'a, str>>,
     pub(crate) path: ObjectPath<'a>,
     pub(crate) interface: Cow<'a, str>,
+    pub(crate) retry_policy: Option<RetryPolicy>,
+    pub(crate) metadata: Option<&'static InterfaceMetadata>,
+    // Set if the properties are cached.
+    pub(crate) property_cache: Option<PropertyCache>,
     dest_unique_name: OnceCell<String>,
     #[derivative(Debug = "ignore")]
     sig_handlers: Mutex<SlotMap<SignalHandlerId, SignalHandlerInfo>>,
     #[derivative(Debug = "ignore")]
     signal_msg_stream: OnceCell<Mutex<MessageStream>>,
+    // Messages received by `dispatch_signals_during`, but not handled, for `next_signal`.
+    #[derivative(Debug = "ignore")]
+    unhandled_msgs: sync::Mutex<VecDeque<Result<Arc<Message>>>>,
 }
 
 impl<'a> ProxyInner<'a> {
     pub(crate) fn new(
         conn: Connection,
-        destination: Cow<'a, str>,
+        destination: Option<Cow<'a, str>>,
         path: ObjectPath<'a>,
         interface: Cow<'a, str>,
     ) -> Self {
@@ -123,9 +139,13 @@ impl<'a> ProxyInner<'a> {
             destination,
             path,
             interface,
+            retry_policy: None,
+            metadata: None,
+            property_cache: None,
             dest_unique_name: OnceCell::new(),
             sig_handlers: Mutex::new(SlotMap::with_key()),
             signal_msg_stream: OnceCell::new(),
+            unhandled_msgs: sync::Mutex::new(VecDeque::new()),
         }
     }
 
@@ -135,8 +155,10 @@ impl<'a> ProxyInner<'a> {
             return None;
         }
         let uniq = self.dest_unique_name.get().unwrap();
+        // Without a bus, nothing sets the sender of the messages from the peer.
+        let from_destination = !self.conn.is_bus() || h.sender() == Ok(Some(uniq));
         if h.interface() == Ok(Some(&self.interface))
-            && h.sender() == Ok(Some(uniq))
+            && from_destination
             && h.path() == Ok(Some(&self.path))
         {
             h.member().ok().flatten()
@@ -184,14 +206,125 @@ impl<'a> Proxy<'a> {
             .await
     }
 
+    /// Create a proxy for the first of the `candidates` interfaces that the object has.
+    ///
+    /// This is for services serving several incompatible versions of an interface, under different
+    /// names (e.g with [`ObjectServer::at_versions`]). The object at `path` is introspected once,
+    /// and a proxy for the first interface of `candidates` it has, in the given order, is passed to
+    /// the factory of that interface. The factories typically wrap it into the proxy of the
+    /// version, in a variant of an enum. Fails with [`Error::InterfaceNotFound`] if the object has
+    /// none of the interfaces.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    ///# async_io::block_on(async {
+    /// use zbus::{azync::{Connection, Proxy}, dbus_proxy};
+    ///
+    /// #[dbus_proxy(interface = "org.example.Foo1")]
+    /// trait Foo1 {
+    ///     fn ping(&self) -> zbus::Result<String>;
+    /// }
+    ///
+    /// #[dbus_proxy(interface = "org.example.Foo2")]
+    /// trait Foo2 {
+    ///     fn ping(&self, count: u32) -> zbus::Result<Vec<String>>;
+    /// }
+    ///
+    /// enum Foo {
+    ///     V1(AsyncFoo1Proxy<'static>),
+    ///     V2(AsyncFoo2Proxy<'static>),
+    /// }
+    ///
+    /// let conn = Connection::new_session().await?;
+    /// let foo = Proxy::first_available(
+    ///     &conn,
+    ///     "org.example.Foo",
+    ///     "/org/example/foo",
+    ///     &[
+    ///         ("org.example.Foo2", |proxy| Foo::V2(proxy.into())),
+    ///         ("org.example.Foo1", |proxy| Foo::V1(proxy.into())),
+    ///     ],
+    /// )
+    /// .await?;
+    /// let pongs = match foo {
+    ///     Foo::V1(foo) => vec![foo.ping().await?],
+    ///     Foo::V2(foo) => foo.ping(2).await?,
+    /// };
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    ///# });
+    /// ```
+    ///
+    /// [`ObjectServer::at_versions`]: ../struct.ObjectServer.html#method.at_versions
+    /// [`Error::InterfaceNotFound`]: ../enum.Error.html#variant.InterfaceNotFound
+    pub async fn first_available<'p, T, E>(
+        conn: &Connection,
+        destination: &str,
+        path: impl TryInto<ObjectPath<'p>, Error = E>,
+        candidates: &[(&str, fn(Proxy<'static>) -> T)],
+    ) -> Result<T>
+    where
+        E: Into<Error>,
+    {
+        let interfaces: Vec<&str> = candidates.iter().map(|(interface, _)| *interface).collect();
+        let (i, proxy) = Self::first_of(conn, destination, path, &interfaces).await?;
+
+        Ok((candidates[i].1)(proxy))
+    }
+
+    // A proxy for the first of `interfaces` that the object has, along with its index.
+    pub(crate) async fn first_of<'p, E>(
+        conn: &Connection,
+        destination: &str,
+        path: impl TryInto<ObjectPath<'p>, Error = E>,
+        interfaces: &[&str],
+    ) -> Result<(usize, Proxy<'static>)>
+    where
+        E: Into<Error>,
+    {
+        let path = path.try_into().map_err(Into::into)?;
+        let xml = AsyncIntrospectableProxy::builder(conn)
+            .destination(destination)
+            .path(&path)?
+            .build()?
+            .introspect()
+            .await?;
+        let available = object_interfaces(&xml);
+        let i = interfaces
+            .iter()
+            .position(|interface| available.contains(interface))
+            .ok_or(Error::InterfaceNotFound)?;
+        let proxy = Proxy::new_owned(
+            conn.clone(),
+            destination.to_owned(),
+            path.into_owned(),
+            interfaces[i].to_owned(),
+        )
+        .await?;
+
+        Ok((i, proxy))
+    }
+
     /// Get a reference to the associated connection.
     pub fn connection(&self) -> &Connection {
         &self.inner.conn
     }
 
     /// Get a reference to the destination service name.
-    pub fn destination(&self) -> &str {
-        &self.inner.destination
+    ///
+    /// This is always set for proxies on a bus connection. On peer-to-peer connections, it's only
+    /// set if given to the [`ProxyBuilder`] explicitly, since messages go to the peer anyway.
+    ///
+    /// [`ProxyBuilder`]: ../struct.ProxyBuilder.html
+    pub fn destination(&self) -> Option<&str> {
+        self.inner.destination.as_deref()
+    }
+
+    // The destination of a proxy on a bus connection, where it's always set.
+    fn bus_destination(&self) -> &str {
+        self.destination()
+            .expect("proxies on bus connections have a destination")
     }
 
     /// Get a reference to the object path.
@@ -204,35 +337,77 @@ impl<'a> Proxy<'a> {
         &self.inner.interface
     }
 
+    // The body of `reply` to `method_name`, or its leading arguments only if `prefix` is set.
+    #[doc(hidden)]
+    pub fn reply_body<R>(&self, reply: &Message, method_name: &str, prefix: bool) -> Result<R>
+    where
+        R: serde::de::DeserializeOwned + zvariant::Type,
+    {
+        reply.reply_body(
+            self.destination(),
+            Some(self.interface()),
+            method_name,
+            prefix,
+        )
+    }
+
     /// Introspect the associated object, and return the XML description.
     ///
     /// See the [xml](xml/index.html) module for parsing the result.
     pub async fn introspect(&self) -> fdo::Result<String> {
         let proxy = AsyncIntrospectableProxy::builder(&self.inner.conn)
-            .destination(self.inner.destination.as_ref())
+            .optional_destination(self.destination())
             .path(&self.inner.path)?
             .build()?;
 
         proxy.introspect().await
     }
 
+    /// Check the proxy against the introspection data of the associated object.
+    ///
+    /// The object must implement the proxy interface. For proxies generated by the [`dbus_proxy`]
+    /// macro, all the methods, properties and signals of the trait must also be present in the
+    /// interface, with the same signatures, and properties must allow the access the proxy needs.
+    /// Otherwise, an [`Error::InterfaceMismatch`] listing all the differences is returned.
+    ///
+    /// See also [`ProxyBuilder::validate_on_build`].
+    ///
+    /// [`dbus_proxy`]: ../attr.dbus_proxy.html
+    /// [`Error::InterfaceMismatch`]: ../enum.Error.html#variant.InterfaceMismatch
+    /// [`ProxyBuilder::validate_on_build`]: ../struct.ProxyBuilder.html#method.validate_on_build
+    #[cfg(feature = "xml")]
+    pub async fn validate(&self) -> Result<()> {
+        let xml = self.introspect().await?;
+        let node = crate::xml::Node::from_reader(xml.as_bytes())?;
+
+        crate::proxy_metadata::validate(self.inner.metadata, &self.inner.interface, &node)
+    }
+
     /// Get the property `property_name`.
     ///
-    /// Effectively, call the `Get` method of the `org.freedesktop.DBus.Properties` interface.
+    /// Effectively, call the `Get` method of the `org.freedesktop.DBus.Properties` interface. If
+    /// the properties are cached, the cached value is returned if there is one, and the value
+    /// received is cached otherwise.
     pub async fn get_property<T>(&self, property_name: &str) -> fdo::Result<T>
     where
         T: TryFrom<OwnedValue>,
     {
+        let cache = self.inner.property_cache.as_ref();
+        let generation = match cache.map(|cache| cache.get(property_name)) {
+            Some(Ok(value)) => return value.try_into().map_err(|_| Error::InvalidReply.into()),
+            Some(Err(generation)) => Some(generation),
+            None => None,
+        };
         let proxy = AsyncPropertiesProxy::builder(&self.inner.conn)
-            .destination(self.inner.destination.as_ref())
+            .optional_destination(self.destination())
             .path(&self.inner.path)?
             .build()?;
+        let value = proxy.get(&self.inner.interface, property_name).await?;
+        if let (Some(cache), Some(generation)) = (cache, generation) {
+            cache.insert(property_name, &value, generation);
+        }
 
-        proxy
-            .get(&self.inner.interface, property_name)
-            .await?
-            .try_into()
-            .map_err(|_| Error::InvalidReply.into())
+        value.try_into().map_err(|_| Error::InvalidReply.into())
     }
 
     /// Set the property `property_name`.
@@ -243,13 +418,57 @@ impl<'a> Proxy<'a> {
         T: Into<Value<'t>>,
     {
         let proxy = AsyncPropertiesProxy::builder(&self.inner.conn)
-            .destination(self.inner.destination.as_ref())
+            .optional_destination(self.destination())
             .path(&self.inner.path)?
             .build()?;
-
-        proxy
+        let res = proxy
             .set(&self.inner.interface, property_name, &value.into())
-            .await
+            .await;
+        // The new value may not be signaled yet, so it's read on the next get.
+        self.uncache_property(property_name);
+
+        res
+    }
+
+    /// The approximate size in bytes of the property values cached, along with their names.
+    ///
+    /// The size of a value is its [`Value::serialized_size_hint`]. This is 0 if the properties are
+    /// not cached.
+    ///
+    /// [`Value::serialized_size_hint`]: https://docs.rs/zvariant/2.7.0/zvariant/enum.Value.html#method.serialized_size_hint
+    pub fn cached_properties_size(&self) -> usize {
+        self.inner
+            .property_cache
+            .as_ref()
+            .map_or(0, PropertyCache::size)
+    }
+
+    /// Drop the property values cached, if any.
+    ///
+    /// The properties are then read from the object again on their next get, and cached again.
+    pub fn clear_property_cache(&self) {
+        if let Some(cache) = &self.inner.property_cache {
+            cache.clear();
+        }
+    }
+
+    fn uncache_property(&self, property_name: &str) {
+        if let Some(cache) = &self.inner.property_cache {
+            cache.remove(property_name);
+        }
+    }
+
+    /// Prepare setting several properties, to send the changes together.
+    ///
+    /// See [`PropertiesTransaction`] for how they are sent.
+    ///
+    /// [`PropertiesTransaction`]: struct.PropertiesTransaction.html
+    pub fn properties_transaction(&self) -> PropertiesTransaction<'_> {
+        PropertiesTransaction {
+            proxy: self,
+            properties: vec![],
+            atomic: false,
+        }
     }
 
     /// Call a method and return the reply.
@@ -263,10 +482,18 @@ impl<'a> Proxy<'a> {
     where
         B: serde::ser::Serialize + zvariant::Type,
     {
+        log::trace!(
+            target: logging::PROXY,
+            "Calling {}.{} on {} at {}",
+            self.inner.interface,
+            method_name,
+            self.destination().unwrap_or("the peer"),
+            self.inner.path.as_str(),
+        );
         self.inner
             .conn
             .call_method(
-                Some(&self.inner.destination),
+                self.destination(),
                 self.inner.path.as_str(),
                 Some(&self.inner.interface),
                 method_name,
@@ -275,6 +502,77 @@ impl<'a> Proxy<'a> {
             .await
     }
 
+    /// Call a method with arguments of types only known at runtime, and return the reply.
+    ///
+    /// Each of `args` is an argument of the call, rather than a variant. The arguments of the reply
+    /// are available the same way, through [`Message::body_structure`]. See
+    /// [`Connection::call_method_with_values`] for details.
+    ///
+    /// [`Message::body_structure`]: ../struct.Message.html#method.body_structure
+    /// [`Connection::call_method_with_values`]: struct.Connection.html#method.call_method_with_values
+    pub async fn call_with_values(
+        &self,
+        method_name: &str,
+        args: &[Value<'_>],
+    ) -> Result<Arc<Message>> {
+        self.inner
+            .conn
+            .call_method_with_values(
+                self.destination(),
+                self.inner.path.as_str(),
+                Some(&self.inner.interface),
+                method_name,
+                args,
+            )
+            .await
+    }
+
+    /// Prepare a call of the method `method_name`, to customize its message before sending it.
+    ///
+    /// The returned [`MethodCallBuilder`] gives access to the [`MessageBuilder`] of the call, with
+    /// the destination, path, interface and sender already set, e.g to set flags or custom header
+    /// fields:
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    ///# async_io::block_on(async {
+    /// use zbus::{azync::{Connection, Proxy}, MessageField, MessageFlags};
+    ///
+    /// let conn = Connection::new_session().await?;
+    /// let proxy = Proxy::new(
+    ///     &conn,
+    ///     "org.freedesktop.DBus",
+    ///     "/org/freedesktop/DBus",
+    ///     "org.freedesktop.DBus",
+    /// )?;
+    /// let reply = proxy
+    ///     .call_builder("GetId")
+    ///     .modify(|b| {
+    ///         b.flags(MessageFlags::NoAutoStart.into())
+    ///             .field(MessageField::Unknown(100, "correlation-42".into()))
+    ///     })
+    ///     .send(&())
+    ///     .await?;
+    /// let id: &str = reply.body()?;
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    ///# });
+    /// ```
+    ///
+    /// [`MethodCallBuilder`]: struct.MethodCallBuilder.html
+    /// [`MessageBuilder`]: ../struct.MessageBuilder.html
+    pub fn call_builder<'p>(&'p self, method_name: &'p str) -> MethodCallBuilder<'p> {
+        let builder = MessageBuilder::method_call(self.inner.path.as_str(), method_name)
+            .map_err(Error::from)
+            .and_then(|b| self.inner.conn.builder(b))
+            .map(|b| b.optional_fields(None, self.destination(), Some(&self.inner.interface)));
+
+        MethodCallBuilder {
+            conn: &self.inner.conn,
+            builder,
+            priority: Priority::Normal,
+        }
+    }
+
     /// Call a method and return the reply body.
     ///
     /// Use [`call_method`] instead if you need to deserialize the reply manually/separately.
@@ -290,7 +588,82 @@ impl<'a> Proxy<'a> {
         // call returns, we must disown the FDs so we don't end up closing them after the call.
         reply.disown_fds();
 
-        Ok(reply.body()?)
+        self.reply_body(&reply, method_name, false)
+    }
+
+    /// Call an idempotent method and return the reply.
+    ///
+    /// Same as [`call_method`], except that the call is retried according to the proxy's
+    /// [`RetryPolicy`], if any. Only use this for methods that can safely be called more than once.
+    ///
+    /// [`call_method`]: struct.Proxy.html#method.call_method
+    /// [`RetryPolicy`]: ../struct.RetryPolicy.html
+    pub async fn call_method_idempotent<B>(
+        &self,
+        method_name: &str,
+        body: &B,
+    ) -> Result<Arc<Message>>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        let policy = match &self.inner.retry_policy {
+            Some(policy) => policy,
+            None => return self.call_method(method_name, body).await,
+        };
+        // A deadline too far away to be represented is as good as none.
+        let deadline = policy
+            .overall_deadline()
+            .and_then(|d| Instant::now().checked_add(d));
+
+        let mut attempt = 1;
+        loop {
+            // Each attempt is a new message, and therefore gets a new serial number.
+            let call = self.call_method(method_name, body);
+            let res = match deadline {
+                Some(deadline) => match crate::timeout_at(deadline, call).await {
+                    Some(res) => res,
+                    None => {
+                        return Err(Error::Io(io::Error::new(
+                            ErrorKind::TimedOut,
+                            "method call deadline reached",
+                        )))
+                    }
+                },
+                None => call.await,
+            };
+
+            let e = match res {
+                Err(e) if attempt < policy.max_attempts() && policy.should_retry(&e) => e,
+                res => return res,
+            };
+            // Don't wait for a retry past the deadline, or too far away to be represented.
+            let backoff = policy.backoff_for(attempt - 1);
+            let retry_at = match Instant::now().checked_add(backoff) {
+                Some(retry_at) if deadline.map_or(true, |deadline| retry_at < deadline) => retry_at,
+                _ => return Err(e),
+            };
+            crate::sleep_until(retry_at).await;
+            attempt += 1;
+        }
+    }
+
+    /// Call an idempotent method and return the reply body.
+    ///
+    /// Same as [`call`], except that the call is retried according to the proxy's
+    /// [`RetryPolicy`], if any.
+    ///
+    /// [`call`]: struct.Proxy.html#method.call
+    /// [`RetryPolicy`]: ../struct.RetryPolicy.html
+    pub async fn call_idempotent<B, R>(&self, method_name: &str, body: &B) -> Result<R>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+        R: serde::de::DeserializeOwned + zvariant::Type,
+    {
+        let reply = self.call_method_idempotent(method_name, body).await?;
+        // See `call` for why we do this.
+        reply.disown_fds();
+
+        self.reply_body(&reply, method_name, false)
     }
 
     /// Create a stream for signal named `signal_name`.
@@ -306,12 +679,20 @@ impl<'a> Proxy<'a> {
                 .inner
                 .conn
                 .subscribe_signal(
-                    self.destination(),
+                    self.bus_destination(),
                     self.path().clone(),
                     self.interface(),
                     signal_name,
                 )
                 .await?;
+            log::debug!(
+                target: logging::PROXY,
+                "Subscribed to {}.{} from {} at {}",
+                self.interface(),
+                signal_name,
+                self.bus_destination(),
+                self.path().as_str(),
+            );
 
             Some(id)
         } else {
@@ -370,6 +751,31 @@ impl<'a> Proxy<'a> {
         // Ensure the stream.
         self.msg_stream().await;
 
+        let subscription_id = if self.inner.conn.is_bus() {
+            let id = self
+                .inner
+                .conn
+                .subscribe_signal(
+                    self.bus_destination(),
+                    self.path().clone(),
+                    self.interface(),
+                    signal_name,
+                )
+                .await?;
+            log::debug!(
+                target: logging::PROXY,
+                "Subscribed to {}.{} from {} at {}",
+                self.interface(),
+                signal_name,
+                self.bus_destination(),
+                self.path().as_str(),
+            );
+
+            Some(id)
+        } else {
+            None
+        };
+
         let id = self
             .inner
             .sig_handlers
@@ -378,21 +784,9 @@ impl<'a> Proxy<'a> {
             .insert(SignalHandlerInfo {
                 signal_name,
                 handler: Box::new(handler),
+                subscription_id,
             });
 
-        if self.inner.conn.is_bus() {
-            let _ = self
-                .inner
-                .conn
-                .subscribe_signal(
-                    self.destination(),
-                    self.path().clone(),
-                    self.interface(),
-                    signal_name,
-                )
-                .await?;
-        }
-
         Ok(id)
     }
 
@@ -409,17 +803,8 @@ impl<'a> Proxy<'a> {
     pub async fn disconnect_signal(&self, handler_id: SignalHandlerId) -> fdo::Result<bool> {
         match self.inner.sig_handlers.lock().await.remove(handler_id) {
             Some(handler_info) => {
-                if self.inner.conn.is_bus() {
-                    let _ = self
-                        .inner
-                        .conn
-                        .unsubscribe_signal(
-                            self.destination(),
-                            self.path().clone(),
-                            self.interface(),
-                            handler_info.signal_name,
-                        )
-                        .await?;
+                if let Some(id) = handler_info.subscription_id {
+                    self.inner.conn.unsubscribe_signal_by_id(id).await?;
                 }
 
                 Ok(true)
@@ -440,6 +825,16 @@ impl<'a> Proxy<'a> {
     ///
     /// This method returns the same errors as [`Self::receive_signal`].
     pub async fn next_signal(&self) -> Result<Option<Arc<Message>>> {
+        let unhandled = self
+            .inner
+            .unhandled_msgs
+            .lock()
+            .expect("poisoned lock")
+            .pop_front();
+        if let Some(msg) = unhandled {
+            return msg.map(Some);
+        }
+
         let mut stream = self.msg_stream().await.lock().await;
         let msg = stream
             .next()
@@ -488,6 +883,65 @@ impl<'a> Proxy<'a> {
         Ok(handled)
     }
 
+    // Run `call` to completion, handling the signals received meanwhile, in order. All the signals
+    // received before `call` completes are handled before this returns.
+    //
+    // Nothing is dispatched if there are no handlers, or if the signals are already being
+    // dispatched, e.g by `next_signal` or when `call` is made from a signal handler.
+    pub(crate) async fn dispatch_signals_during<F, T>(&self, call: F) -> T
+    where
+        F: Future<Output = T>,
+    {
+        let no_handlers = self
+            .inner
+            .sig_handlers
+            .try_lock()
+            .map_or(true, |handlers| handlers.is_empty());
+        let stream = self
+            .inner
+            .signal_msg_stream
+            .get()
+            .and_then(|stream| stream.try_lock());
+        let mut stream = match stream {
+            Some(stream) if !no_handlers => stream,
+            _ => return call.await,
+        };
+
+        futures_util::pin_mut!(call);
+        let res = loop {
+            match select(call.as_mut(), stream.next()).await {
+                Either::Left((res, _)) => break res,
+                Either::Right((Some(msg), _)) => self.dispatch_signal(msg).await,
+                Either::Right((None, call)) => break call.await,
+            }
+        };
+        // The messages received before the reply are already queued in the stream.
+        while let Some(Some(msg)) = stream.next().now_or_never() {
+            self.dispatch_signal(msg).await;
+        }
+
+        res
+    }
+
+    // Handle `msg`, keeping it for `next_signal` if it's not handled.
+    async fn dispatch_signal(&self, msg: Result<Arc<Message>>) {
+        let unhandled = match msg {
+            Ok(msg) => match self.handle_signal(&msg).await {
+                Ok(true) => return,
+                Ok(false) => Ok(msg),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+
+        let mut msgs = self.inner.unhandled_msgs.lock().expect("poisoned lock");
+        // Like the connection, we drop the oldest messages when out of room.
+        if msgs.len() >= self.inner.conn.max_queued() {
+            msgs.pop_front();
+        }
+        msgs.push_back(unhandled);
+    }
+
     /// Resolves the destination name to the associated unique connection name.
     ///
     /// Typically you would want to create the [`Proxy`] with the well-known name of the destination
@@ -502,13 +956,18 @@ impl<'a> Proxy<'a> {
             return Ok(name);
         }
 
-        let destination = &self.inner.destination;
-        let unique_name = if destination.starts_with(':') || destination == "org.freedesktop.DBus" {
-            destination.to_string()
+        let unique_name = if !self.inner.conn.is_bus() {
+            // The peer has no name, and signals from it have no sender to check anyway.
+            self.destination().unwrap_or_default().to_string()
         } else {
-            fdo::AsyncDBusProxy::new(&self.inner.conn)?
-                .get_name_owner(destination)
-                .await?
+            let destination = self.bus_destination();
+            if destination.starts_with(':') || destination == "org.freedesktop.DBus" {
+                destination.to_string()
+            } else {
+                fdo::AsyncDBusProxy::new(&self.inner.conn)?
+                    .get_name_owner(destination)
+                    .await?
+            }
         };
         self.inner
             .dest_unique_name
@@ -569,6 +1028,279 @@ impl std::ops::Drop for SignalStream<'_> {
     }
 }
 
+impl AsyncDrop for SignalStream<'_> {
+    fn async_drop<'d>(mut self) -> BoxFuture<'d, Result<()>>
+    where
+        Self: 'd,
+    {
+        let conn = self.conn.clone();
+        let subscription_id = self.subscription_id.take();
+
+        async move {
+            if let Some(id) = subscription_id {
+                conn.unsubscribe_signal_by_id(id).await?;
+            }
+
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+// Dropping the last reference to a proxy drops its signal handlers, so their subscriptions are to
+// be dropped as well.
+impl Drop for ProxyInner<'_> {
+    fn drop(&mut self) {
+        for (_, handler_info) in self.sig_handlers.get_mut().drain() {
+            if let Some(id) = handler_info.subscription_id {
+                self.conn.queue_unsubscribe_signal(id);
+            }
+        }
+    }
+}
+
+/// Disconnects all the signal handlers of the proxy.
+impl<'a> AsyncDrop for Proxy<'a> {
+    fn async_drop<'d>(self) -> BoxFuture<'d, Result<()>>
+    where
+        Self: 'd,
+    {
+        async move {
+            let handlers: Vec<_> = self.inner.sig_handlers.lock().await.drain().collect();
+            let mut res = Ok(());
+            for id in handlers.into_iter().filter_map(|(_, h)| h.subscription_id) {
+                // Carry on with the other handlers on errors, so none is left behind.
+                res = res.and(
+                    self.inner
+                        .conn
+                        .unsubscribe_signal_by_id(id)
+                        .await
+                        .map(|_| ()),
+                );
+            }
+
+            res
+        }
+        .boxed()
+    }
+}
+
+/// A method call being prepared, created by [`Proxy::call_builder`].
+///
+/// [`Proxy::call_builder`]: struct.Proxy.html#method.call_builder
+#[derive(Debug)]
+pub struct MethodCallBuilder<'p> {
+    conn: &'p Connection,
+    // Errors are reported on `send`, to keep the chain of calls straightforward.
+    builder: Result<MessageBuilder<'p>>,
+    priority: Priority,
+}
+
+assert_impl_all!(MethodCallBuilder<'_>: Send, Sync, Unpin);
+
+impl<'p> MethodCallBuilder<'p> {
+    /// Modify the message of the call through its builder.
+    ///
+    /// The message is built on [`send`], before its serial number is assigned, so anything but the
+    /// serial number can be changed.
+    ///
+    /// Don't set the [`MessageFlags::NoReplyExpected`] flag, since a reply is waited for.
+    ///
+    /// [`send`]: #method.send
+    /// [`MessageFlags::NoReplyExpected`]: ../enum.MessageFlags.html#variant.NoReplyExpected
+    pub fn modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(MessageBuilder<'p>) -> MessageBuilder<'p>,
+    {
+        self.builder = self.builder.map(f);
+        self
+    }
+
+    /// Set the priority of the call, relative to the other messages sent on the connection.
+    ///
+    /// This is [`Priority::Normal`] by default. See
+    /// [`Connection::send_message_with_priority`] for details.
+    ///
+    /// [`Priority::Normal`]: ../enum.Priority.html#variant.Normal
+    /// [`Connection::send_message_with_priority`]: struct.Connection.html#method.send_message_with_priority
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Build the message with `body`, send it and return the reply.
+    pub async fn send<B>(self, body: &B) -> Result<Arc<Message>>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        let msg = self.builder?.build(body)?;
+
+        self.conn
+            .call_method_message_with_priority(msg, self.priority)
+            .await
+    }
+
+    /// Build the message with `body`, send it and return the reply body.
+    pub async fn call<B, R>(self, body: &B) -> Result<R>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+        R: serde::de::DeserializeOwned + zvariant::Type,
+    {
+        let reply = self.send(body).await?;
+        // See `Proxy::call` for why we do this.
+        reply.disown_fds();
+
+        Ok(reply.body()?)
+    }
+}
+
+/// Property changes being collected, created by [`Proxy::properties_transaction`].
+///
+/// The changes are only sent on [`commit`], in one of two ways:
+///
+/// * By default, as one `Set` call of the `org.freedesktop.DBus.Properties` interface per
+///   property, all sent before any reply is waited for. The service applies them one at a time,
+///   so it may go through the states in between, and stop at the first failing one.
+/// * With [`atomic`], as a single call of the zbus-specific `SetMultiple` method of the same
+///   interface, which the service applies all or none of. Only the interfaces declared with
+///   `#[dbus_interface(atomic_properties)]` support it, and proxies declared with
+///   `#[dbus_proxy(atomic_properties)]` use it.
+///
+/// [`Proxy::properties_transaction`]: struct.Proxy.html#method.properties_transaction
+/// [`commit`]: #method.commit
+/// [`atomic`]: #method.atomic
+#[derive(Debug)]
+pub struct PropertiesTransaction<'p> {
+    proxy: &'p Proxy<'p>,
+    properties: Vec<(String, OwnedValue)>,
+    atomic: bool,
+}
+
+assert_impl_all!(PropertiesTransaction<'_>: Send, Sync, Unpin);
+
+impl<'p> PropertiesTransaction<'p> {
+    /// Set the property `property_name` on commit. If it was set already, the value is replaced.
+    pub fn set<'t, T: 't>(mut self, property_name: &str, value: T) -> Self
+    where
+        T: Into<Value<'t>>,
+    {
+        let value = OwnedValue::from(value.into());
+        match self
+            .properties
+            .iter_mut()
+            .find(|(name, _)| name == property_name)
+        {
+            Some((_, v)) => *v = value,
+            None => self.properties.push((property_name.to_string(), value)),
+        }
+
+        self
+    }
+
+    /// Whether to set the properties with a single `SetMultiple` call, instead of a `Set` call per
+    /// property.
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+
+        self
+    }
+
+    /// Send the changes, returning the first error if any.
+    pub async fn commit(self) -> fdo::Result<()> {
+        if self.properties.is_empty() {
+            return Ok(());
+        }
+
+        if self.atomic {
+            let proxy = AsyncPropertiesProxy::builder(&self.proxy.inner.conn)
+                .optional_destination(self.proxy.destination())
+                .path(&self.proxy.inner.path)?
+                .build()?;
+            let properties = self
+                .properties
+                .iter()
+                .map(|(name, value)| (name.as_str(), &**value))
+                .collect();
+
+            let res = proxy
+                .set_multiple(&self.proxy.inner.interface, properties)
+                .await;
+            for (name, _) in &self.properties {
+                self.proxy.uncache_property(name);
+            }
+
+            res
+        } else {
+            let proxy = self.proxy;
+            let calls = self
+                .properties
+                .iter()
+                .map(|(name, value)| proxy.set_property(name, value.clone()));
+
+            futures_util::future::try_join_all(calls).await.map(|_| ())
+        }
+    }
+}
+
+// The names of the interfaces of the object introspected in `xml`. The interfaces of its children,
+// which some services introspect along, are left out.
+fn object_interfaces(xml: &str) -> Vec<&str> {
+    let mut interfaces = vec![];
+    let mut depth = 0usize;
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        // Comments may contain anything, including tags.
+        if let Some(comment) = rest.strip_prefix("!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+
+        let element = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("");
+        match element {
+            "" if tag.trim_start_matches('/').starts_with("node") => {
+                depth = depth.saturating_sub(1);
+            }
+            "node" if !tag.ends_with('/') => depth += 1,
+            "interface" if depth == 1 => interfaces.extend(attribute(tag, "name")),
+            _ => (),
+        }
+    }
+
+    interfaces
+}
+
+// The value of the attribute `name` of the element whose start `tag` is given.
+fn attribute<'t>(tag: &'t str, name: &str) -> Option<&'t str> {
+    let mut rest = tag;
+    while let Some(start) = rest.find(name) {
+        let preceded_by_space = rest[..start].ends_with(char::is_whitespace);
+        rest = &rest[start + name.len()..];
+        if !preceded_by_space {
+            continue;
+        }
+        let value = match rest.trim_start().strip_prefix('=') {
+            Some(value) => value.trim_start(),
+            None => continue,
+        };
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &value[1..];
+
+        return value.find(quote).map(|end| &value[..end]);
+    }
+
+    None
+}
+
 impl<'a> From<crate::Proxy<'a>> for Proxy<'a> {
     fn from(proxy: crate::Proxy<'a>) -> Self {
         proxy.into_inner()
@@ -579,10 +1311,13 @@ impl<'a> From<crate::Proxy<'a>> for Proxy<'a> {
 mod tests {
     use super::*;
     use async_io::block_on;
-    use futures_util::future::FutureExt;
+    use futures_util::{future::FutureExt, stream::TryStreamExt};
     use ntest::timeout;
-    use std::{future::ready, sync::Arc};
+    use std::{
+        collections::HashMap, future::ready, os::unix::net::UnixStream, sync::Arc, time::Duration,
+    };
     use test_env_log::test;
+    use zvariant::Guid;
 
     #[test]
     #[timeout(1000)]
@@ -741,4 +1476,105 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    #[timeout(1000)]
+    fn property_cache() {
+        block_on(test_property_cache()).unwrap();
+    }
+
+    async fn test_property_cache() -> Result<()> {
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let server = Connection::new_unix_server(p0, &guid);
+        let client = Connection::new_unix_client(p1, false);
+
+        let (client_conn, server_conn) = futures_util::try_join!(client, server)?;
+        let mut server_stream = server_conn.stream().await;
+
+        // Replies to each `Get` with the number of `Get` calls so far, and signals the changes of
+        // `Count` that `Notify` asks for.
+        let server_future = async {
+            let mut gets = 0u32;
+            while let Some(m) = server_stream.try_next().await? {
+                let header = m.header()?;
+                match header.member()? {
+                    Some("Get") => {
+                        gets += 1;
+                        server_conn.reply(&m, &Value::from(gets)).await?;
+                    }
+                    Some("Notify") => {
+                        let count = m.body::<u32>()?;
+                        let mut changed = HashMap::new();
+                        changed.insert("Count", Value::from(count));
+                        server_conn
+                            .emit_signal(
+                                None,
+                                "/org/zbus/Cache",
+                                "org.freedesktop.DBus.Properties",
+                                "PropertiesChanged",
+                                &("org.zbus.Cache", changed, Vec::<&str>::new()),
+                            )
+                            .await?;
+                        server_conn.reply(&m, &()).await?;
+                    }
+                    Some("Quit") => {
+                        server_conn.reply(&m, &()).await?;
+
+                        return Ok::<_, Error>(gets);
+                    }
+                    _ => (),
+                }
+            }
+
+            Err(Error::InvalidReply)
+        };
+
+        let client_future = async {
+            let proxy: Proxy<'_> = crate::ProxyBuilder::new_bare(&client_conn)
+                .path("/org/zbus/Cache")?
+                .interface("org.zbus.Cache")
+                .cache_properties(true)
+                .build_async()
+                .await?;
+            assert_eq!(proxy.cached_properties_size(), 0);
+
+            // The second get is served from the cache.
+            assert_eq!(proxy.get_property::<u32>("Count").await?, 1);
+            assert_eq!(proxy.get_property::<u32>("Count").await?, 1);
+            assert!(proxy.cached_properties_size() > 0);
+
+            // The cache is updated from the signal, in the background.
+            proxy.call::<_, ()>("Notify", &42u32).await?;
+            while proxy.get_property::<u32>("Count").await? != 42 {
+                async_io::Timer::after(Duration::from_millis(10)).await;
+            }
+
+            proxy.clear_property_cache();
+            assert_eq!(proxy.cached_properties_size(), 0);
+            assert_eq!(proxy.get_property::<u32>("Count").await?, 2);
+            assert_eq!(proxy.get_property::<u32>("Count").await?, 2);
+
+            // Values over the maximum size are never cached.
+            let uncached: Proxy<'_> = crate::ProxyBuilder::new_bare(&client_conn)
+                .path("/org/zbus/Cache")?
+                .interface("org.zbus.Cache")
+                .cache_properties(true)
+                .max_cached_property_size(1)
+                .build_async()
+                .await?;
+            assert_eq!(uncached.get_property::<u32>("Count").await?, 3);
+            assert_eq!(uncached.get_property::<u32>("Count").await?, 4);
+            assert_eq!(uncached.cached_properties_size(), 0);
+
+            proxy.call::<_, ()>("Quit", &()).await
+        };
+
+        let (_, gets) = futures_util::try_join!(client_future, server_future)?;
+        assert_eq!(gets, 4);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,93 @@
+use async_io::Async;
+use std::{
+    io,
+    os::unix::io::{AsRawFd, RawFd},
+    task::{Context, Poll},
+};
+
+use crate::{in_memory::InMemorySocket, raw::Socket, OwnedFd};
+
+/// The socket of a [`Connection`], polled for readiness.
+///
+/// That's a socket of the OS, watched by the `async-io` reactor, or one side of an in-memory pair,
+/// which has no file descriptor to watch and wakes up its tasks itself.
+///
+/// [`Connection`]: struct.Connection.html
+#[derive(Debug)]
+pub(crate) enum AsyncSocket {
+    Os(Async<Box<dyn Socket>>),
+    InMemory(InMemorySocket),
+}
+
+impl AsyncSocket {
+    pub(crate) fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self {
+            Self::Os(socket) => socket.poll_readable(cx),
+            Self::InMemory(socket) => socket.poll_readable(cx),
+        }
+    }
+
+    pub(crate) fn poll_writable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self {
+            Self::Os(socket) => socket.poll_writable(cx),
+            Self::InMemory(socket) => socket.poll_writable(cx),
+        }
+    }
+
+    /// A new handle to the same socket, e.g for writing while another one is used for reading.
+    pub(crate) fn duplicate(&self) -> io::Result<Self> {
+        match self {
+            Self::Os(socket) => Ok(Self::Os(Async::new(socket.get_ref().try_clone()?)?)),
+            Self::InMemory(socket) => Ok(Self::InMemory(socket.clone())),
+        }
+    }
+}
+
+impl From<Async<Box<dyn Socket>>> for AsyncSocket {
+    fn from(socket: Async<Box<dyn Socket>>) -> Self {
+        Self::Os(socket)
+    }
+}
+
+impl Socket for AsyncSocket {
+    fn recvmsg(&mut self, buffer: &mut [u8]) -> io::Result<(usize, Vec<OwnedFd>)> {
+        match self {
+            Self::Os(socket) => socket.recvmsg(buffer),
+            Self::InMemory(socket) => socket.recvmsg(buffer),
+        }
+    }
+
+    fn sendmsg(&mut self, buffer: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+        match self {
+            Self::Os(socket) => socket.sendmsg(buffer, fds),
+            Self::InMemory(socket) => socket.sendmsg(buffer, fds),
+        }
+    }
+
+    fn close(&self) -> io::Result<()> {
+        match self {
+            Self::Os(socket) => socket.close(),
+            Self::InMemory(socket) => socket.close(),
+        }
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Socket>> {
+        Ok(Box::new(self.duplicate()?))
+    }
+
+    fn can_pass_unix_fd(&self) -> bool {
+        match self {
+            Self::Os(socket) => socket.can_pass_unix_fd(),
+            Self::InMemory(socket) => socket.can_pass_unix_fd(),
+        }
+    }
+}
+
+impl AsRawFd for AsyncSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            Self::Os(socket) => socket.as_raw_fd(),
+            Self::InMemory(socket) => socket.as_raw_fd(),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Write,
+    rc::Rc,
+};
+
+use zvariant::{OwnedValue, Value};
+
+use crate::{fdo, Connection, Error, Interface, Message, Result};
+
+// The `Interface` registered for the parts of an interface, merged through `ObjectServer::merge_at`.
+//
+// Members are dispatched to the part having them. Since no two parts have a member of the same
+// kind and name, the order of the parts doesn't matter.
+pub(crate) struct Composite {
+    name: String,
+    parts: Vec<Rc<RefCell<dyn Interface>>>,
+}
+
+impl Composite {
+    /// Merge `part` into the interface `iface`, named `name`, which may be a composite already.
+    ///
+    /// Fails with [`Error::InvalidName`] if `part` has a member of the same kind and name as one
+    /// of the interface, or if it's of the same type as one of its parts.
+    pub(crate) fn merge(
+        name: &str,
+        iface: Rc<RefCell<dyn Interface>>,
+        part: Rc<RefCell<dyn Interface>>,
+    ) -> Result<Self> {
+        let parts = match iface.borrow().downcast_ref::<Composite>() {
+            Some(composite) => composite.parts.clone(),
+            None => vec![iface.clone()],
+        };
+
+        let part_type = part_type_id(&part);
+        let members = members(&*part.borrow());
+        for existing in &parts {
+            if part_type_id(existing) == part_type {
+                return Err(Error::InvalidName(format!(
+                    "a part of the same type is already registered for `{}`",
+                    name
+                )));
+            }
+            let existing = members(&*existing.borrow());
+            if let Some((kind, member)) = members.iter().find(|m| existing.contains(m)) {
+                return Err(Error::InvalidName(format!(
+                    "duplicate {} `{}` of `{}`",
+                    kind, member, name
+                )));
+            }
+        }
+
+        let mut parts = parts;
+        parts.push(part);
+
+        Ok(Self {
+            name: name.to_owned(),
+            parts,
+        })
+    }
+
+    /// The part of type `I`, if any.
+    pub(crate) fn part<I: Interface>(&self) -> Option<Rc<RefCell<dyn Interface>>> {
+        self.parts
+            .iter()
+            .find(|part| part_type_id(part) == TypeId::of::<I>())
+            .cloned()
+    }
+
+    /// The parts, in the order they were merged.
+    pub(crate) fn parts(&self) -> &[Rc<RefCell<dyn Interface>>] {
+        &self.parts
+    }
+
+    /// Remove the part of type `type_id`, returning whether there was one.
+    pub(crate) fn remove(&mut self, type_id: TypeId) -> bool {
+        let len = self.parts.len();
+        self.parts.retain(|part| part_type_id(part) != type_id);
+
+        self.parts.len() != len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+}
+
+impl Interface for Composite {
+    fn name() -> &'static str {
+        // Only called for the interfaces registered by type, which this type can't be.
+        unreachable!("composite interfaces are registered under the name of their parts")
+    }
+
+    fn get(&self, property_name: &str) -> Option<fdo::Result<OwnedValue>> {
+        self.parts
+            .iter()
+            .find_map(|part| part.borrow().get(property_name))
+    }
+
+    fn get_all(&self) -> HashMap<String, OwnedValue> {
+        self.parts
+            .iter()
+            .flat_map(|part| part.borrow().get_all())
+            .collect()
+    }
+
+    fn try_get_all(&self) -> fdo::Result<HashMap<String, OwnedValue>> {
+        let mut all = HashMap::new();
+        for part in &self.parts {
+            all.extend(part.borrow().try_get_all()?);
+        }
+
+        Ok(all)
+    }
+
+    fn tracked_properties(&self) -> HashMap<&'static str, OwnedValue> {
+        self.parts
+            .iter()
+            .flat_map(|part| part.borrow().tracked_properties())
+            .collect()
+    }
+
+    fn set(&mut self, property_name: &str, value: &Value<'_>) -> Option<fdo::Result<()>> {
+        self.parts
+            .iter()
+            .find_map(|part| part.borrow_mut().set(property_name, value))
+    }
+
+    // The parts having their own `RefCell`, this dispatches the `&mut self` methods as well,
+    // sparing the object server a mutable borrow of the whole interface.
+    fn call(&self, connection: &Connection, msg: &Message, name: &str) -> Option<Result<u32>> {
+        self.parts.iter().find_map(|part| {
+            let res = part.borrow().call(connection, msg, name);
+            res.or_else(|| part.borrow_mut().call_mut(connection, msg, name))
+        })
+    }
+
+    fn call_mut(&mut self, _: &Connection, _: &Message, _: &str) -> Option<Result<u32>> {
+        None
+    }
+
+    fn introspect_to_writer(&self, writer: &mut dyn Write, level: usize) {
+        writeln!(
+            writer,
+            r#"{:indent$}<interface name="{}">"#,
+            "",
+            self.name,
+            indent = level
+        )
+        .unwrap();
+        for part in &self.parts {
+            for line in members_xml(&*part.borrow(), level) {
+                writeln!(writer, "{}", line).unwrap();
+            }
+        }
+        writeln!(writer, "{:indent$}</interface>", "", indent = level).unwrap();
+    }
+}
+
+fn part_type_id(part: &Rc<RefCell<dyn Interface>>) -> TypeId {
+    <dyn Interface as Any>::type_id(&*part.borrow())
+}
+
+// The introspection of the members of `iface`, without the enclosing `interface` element.
+pub(crate) fn members_xml(iface: &dyn Interface, level: usize) -> Vec<String> {
+    let mut xml = String::new();
+    iface.introspect_to_writer(&mut xml, level);
+    let mut lines: Vec<String> = xml.lines().map(String::from).collect();
+    // The `interface` start and end tags.
+    if lines.len() >= 2 {
+        lines.pop();
+        lines.remove(0);
+    }
+
+    lines
+}
+
+// The kinds and names of the members of `iface`, from its introspection.
+fn members(iface: &dyn Interface) -> Vec<(&'static str, String)> {
+    members_xml(iface, 0)
+        .iter()
+        .filter_map(|line| {
+            let line = line.trim_start();
+            ["method", "signal", "property"].iter().find_map(|kind| {
+                let name = line.strip_prefix(&format!("<{} name=\"", kind))?;
+                let end = name.find('"')?;
+
+                Some((*kind, name[..end].to_owned()))
+            })
+        })
+        .collect()
+}
@@ -8,14 +8,16 @@ use std::{
         net::UnixStream,
     },
     sync::{Arc, Mutex},
+    time::Duration,
 };
-use zvariant::ObjectPath;
+use zvariant::{ObjectPath, Value};
 
 use async_io::block_on;
 
 use crate::{
     azync::{self, MessageStream},
-    Error, Guid, Message, MessageError, Result,
+    ConnectionCredentials, Error, Guid, InMemoryOptions, Message, MessageError, PeerStats,
+    Priority, RawBody, Result,
 };
 
 /// A D-Bus connection.
@@ -40,7 +42,39 @@ use crate::{
 ///
 /// `Connection` keeps an internal ringbuffer of incoming message. The maximum capacity of this
 /// ringbuffer is configurable through the [`set_max_queued`] method. The default size is 64. When
-/// the buffer is full, messages are dropped to create room, starting from the oldest one.
+/// the buffer is full, messages are dropped to create room, starting from the oldest one. The
+/// message streams of the underlying [`azync::Connection`] each have a queue of their own, so
+/// they're not affected by this one lagging behind.
+///
+/// # Conversion to and from [`azync::Connection`]
+///
+/// A `Connection` wraps an [`azync::Connection`], which [`inner`] and [`into_inner`] give access
+/// to, and `Connection::from` wraps back. Both are the same connection, so converting one into the
+/// other doesn't reconnect: the unique and well-known names, the match rules and signal
+/// subscriptions, and the [`ObjectServer`]s serving objects on it are all kept, in either
+/// direction and any number of times. So is the thread running the connection (with the default
+/// `internal-executor` feature), or the executor to tick otherwise.
+///
+/// The only state of the wrapper is the ringbuffer of [`receive_message`]. A `Connection` made
+/// from an [`azync::Connection`] starts with an empty one, and the messages not received yet go
+/// away with the last clone of a `Connection`. This lets a program open a connection in a
+/// synchronous initialization phase, and carry on with it in async code:
+///
+/// ```no_run
+///# use std::error::Error;
+/// use zbus::{azync, fdo, Connection};
+///
+/// let conn = Connection::new_session()?;
+/// fdo::DBusProxy::new(&conn)?.request_name("org.zbus.Example", Default::default())?;
+///
+/// let conn: azync::Connection = conn.into_inner();
+/// async_io::block_on(async {
+///     assert!(conn.is_name_owner("org.zbus.Example").await?);
+///     // The rest of the program..
+///#     Ok::<_, zbus::Error>(())
+/// })?;
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
 ///
 /// [method calls]: struct.Connection.html#method.call_method
 /// [signals]: struct.Connection.html#method.emit_signal
@@ -53,6 +87,10 @@ use crate::{
 /// [`Clone`]: https://doc.rust-lang.org/std/clone/trait.Clone.html
 /// [file an issue]: https://gitlab.freedesktop.org/dbus/zbus/-/issues/new
 /// [`set_max_queued`]: struct.Connection.html#method.set_max_queued
+/// [`azync::Connection`]: azync/struct.Connection.html
+/// [`inner`]: struct.Connection.html#method.inner
+/// [`into_inner`]: struct.Connection.html#method.into_inner
+/// [`receive_message`]: struct.Connection.html#method.receive_message
 #[derive(derivative::Derivative, Clone)]
 #[derivative(Debug)]
 pub struct Connection {
@@ -91,13 +129,66 @@ impl Connection {
         block_on(azync::Connection::new_system()).map(Self::from)
     }
 
+    /// Create a `Connection` to the message bus that started this process.
+    ///
+    /// See [`azync::Connection::new_starter`] for details.
+    ///
+    /// [`azync::Connection::new_starter`]: azync/struct.Connection.html#method.new_starter
+    pub fn new_starter() -> Result<Self> {
+        block_on(azync::Connection::new_starter()).map(Self::from)
+    }
+
     /// Create a `Connection` for the given [D-Bus address].
     ///
+    /// See [`azync::Connection::new_for_address`] for details on address lists.
+    ///
+    /// [`azync::Connection::new_for_address`]: azync/struct.Connection.html#method.new_for_address
     /// [D-Bus address]: https://dbus.freedesktop.org/doc/dbus-specification.html#addresses
     pub fn new_for_address(address: &str, bus_connection: bool) -> Result<Self> {
         block_on(azync::Connection::new_for_address(address, bus_connection)).map(Self::from)
     }
 
+    /// Get the connection to the session bus that is shared by the whole process.
+    ///
+    /// The underlying [`azync::Connection`] is the one returned by
+    /// [`azync::Connection::shared_session`], so blocking and asynchronous code share the same
+    /// connection. See its documentation for details.
+    ///
+    /// [`azync::Connection`]: azync/struct.Connection.html
+    /// [`azync::Connection::shared_session`]: azync/struct.Connection.html#method.shared_session
+    pub fn shared_session() -> Result<Self> {
+        block_on(azync::Connection::shared_session()).map(Self::from)
+    }
+
+    /// Get the connection to the system bus that is shared by the whole process.
+    ///
+    /// See [`azync::Connection::shared_system`] for details.
+    ///
+    /// [`azync::Connection::shared_system`]: azync/struct.Connection.html#method.shared_system
+    pub fn shared_system() -> Result<Self> {
+        block_on(azync::Connection::shared_system()).map(Self::from)
+    }
+
+    /// Make `conn` the connection returned by [`shared_session`].
+    ///
+    /// See [`azync::Connection::set_shared_session`] for details.
+    ///
+    /// [`shared_session`]: struct.Connection.html#method.shared_session
+    /// [`azync::Connection::set_shared_session`]: azync/struct.Connection.html#method.set_shared_session
+    pub fn set_shared_session(conn: &Self) {
+        block_on(azync::Connection::set_shared_session(&conn.inner))
+    }
+
+    /// Make `conn` the connection returned by [`shared_system`].
+    ///
+    /// See [`azync::Connection::set_shared_system`] for details.
+    ///
+    /// [`shared_system`]: struct.Connection.html#method.shared_system
+    /// [`azync::Connection::set_shared_system`]: azync/struct.Connection.html#method.set_shared_system
+    pub fn set_shared_system(conn: &Self) {
+        block_on(azync::Connection::set_shared_system(&conn.inner))
+    }
+
     /// Create a server `Connection` for the given `UnixStream` and the server `guid`.
     ///
     /// The connection will wait for incoming client authentication handshake & negotiation messages,
@@ -109,6 +200,15 @@ impl Connection {
         block_on(azync::Connection::new_unix_server(stream, guid)).map(Self::from)
     }
 
+    /// The address this connection was established to.
+    ///
+    /// See [`azync::Connection::address`] for details.
+    ///
+    /// [`azync::Connection::address`]: azync/struct.Connection.html#method.address
+    pub fn address(&self) -> Option<&str> {
+        self.inner.address()
+    }
+
     /// Max number of messages to queue.
     pub fn max_queued(&self) -> usize {
         self.inner.max_queued()
@@ -140,11 +240,39 @@ impl Connection {
         self.inner.server_guid()
     }
 
+    /// The traffic exchanged with each peer, if enabled through [`ConnectionBuilder::peer_stats`].
+    ///
+    /// See [`azync::Connection::peer_stats`] for details.
+    ///
+    /// [`ConnectionBuilder::peer_stats`]: struct.ConnectionBuilder.html#method.peer_stats
+    /// [`azync::Connection::peer_stats`]: azync/struct.Connection.html#method.peer_stats
+    pub fn peer_stats(&self) -> Vec<PeerStats> {
+        self.inner.peer_stats()
+    }
+
     /// The unique name as assigned by the message bus or `None` if not a message bus connection.
     pub fn unique_name(&self) -> Option<&str> {
         self.inner.unique_name()
     }
 
+    /// Say `Hello` to the bus, to get our unique name.
+    ///
+    /// See [`azync::Connection::hello`] for details.
+    ///
+    /// [`azync::Connection::hello`]: azync/struct.Connection.html#method.hello
+    pub fn hello(&self) -> Result<&str> {
+        block_on(self.inner.hello())
+    }
+
+    /// Wait for the bus to give us a unique name.
+    ///
+    /// See [`azync::Connection::unique_name_acquired`] for details.
+    ///
+    /// [`azync::Connection::unique_name_acquired`]: azync/struct.Connection.html#method.unique_name_acquired
+    pub fn unique_name_acquired(&self) -> Result<&str> {
+        block_on(self.inner.unique_name_acquired())
+    }
+
     /// Fetch the next message from the connection.
     ///
     /// Read from the connection until a message is received or an error is reached. Return the
@@ -164,6 +292,86 @@ impl Connection {
         block_on(self.inner.send_message(msg))
     }
 
+    /// Send `msg` to the peer, with the given priority.
+    ///
+    /// See [`azync::Connection::send_message_with_priority`] for details.
+    ///
+    /// [`azync::Connection::send_message_with_priority`]: azync/struct.Connection.html#method.send_message_with_priority
+    pub fn send_message_with_priority(&self, msg: Message, priority: Priority) -> Result<u32> {
+        block_on(self.inner.send_message_with_priority(msg, priority))
+    }
+
+    /// Send `msg` to the peer, without blocking.
+    ///
+    /// Fails with [`Error::QueueFull`] if [`out_queue_capacity`] messages are waiting to be
+    /// written already. See [`azync::Connection::try_send_message`] for details.
+    ///
+    /// [`Error::QueueFull`]: enum.Error.html#variant.QueueFull
+    /// [`out_queue_capacity`]: #method.out_queue_capacity
+    /// [`azync::Connection::try_send_message`]: azync/struct.Connection.html#method.try_send_message
+    pub fn try_send_message(&self, msg: Message) -> Result<u32> {
+        self.inner.try_send_message(msg)
+    }
+
+    /// The number of messages waiting to be written to the socket.
+    pub fn out_queue_len(&self) -> usize {
+        self.inner.out_queue_len()
+    }
+
+    /// The max number of messages waiting to be written to the socket, for [`try_send_message`].
+    ///
+    /// [`try_send_message`]: #method.try_send_message
+    pub fn out_queue_capacity(&self) -> usize {
+        self.inner.out_queue_capacity()
+    }
+
+    /// Set the max number of messages waiting to be written to the socket, for
+    /// [`try_send_message`].
+    ///
+    /// Like [`set_max_queued`], this method takes ownership of `self` and returns it.
+    ///
+    /// [`try_send_message`]: #method.try_send_message
+    /// [`set_max_queued`]: #method.set_max_queued
+    pub fn set_out_queue_capacity(self, capacity: usize) -> Self {
+        Self::from(self.inner.set_out_queue_capacity(capacity))
+    }
+
+    /// Send the pre-built `msg` to the peer, as is, with a new serial number.
+    ///
+    /// See [`azync::Connection::send_raw_message`] for details, including the ownership of the file
+    /// descriptors of `msg`.
+    ///
+    /// [`azync::Connection::send_raw_message`]: azync/struct.Connection.html#method.send_raw_message
+    pub fn send_raw_message(&self, msg: Message) -> Result<u32> {
+        block_on(self.inner.send_raw_message(msg))
+    }
+
+    /// Send all the messages in `msgs` to the peer, at once.
+    ///
+    /// See [`azync::Connection::send_batch`] for details.
+    ///
+    /// [`azync::Connection::send_batch`]: azync/struct.Connection.html#method.send_batch
+    pub fn send_batch<I>(&self, msgs: I) -> Result<Vec<u32>>
+    where
+        I: IntoIterator<Item = Message>,
+    {
+        block_on(self.inner.send_batch(msgs))
+    }
+
+    /// Set whether outgoing messages are coalesced into as few writes to the socket as possible.
+    ///
+    /// See [`azync::Connection::set_write_coalescing`] for details.
+    ///
+    /// [`azync::Connection::set_write_coalescing`]: azync/struct.Connection.html#method.set_write_coalescing
+    pub fn set_write_coalescing(&self, write_coalescing: bool) {
+        self.inner.set_write_coalescing(write_coalescing)
+    }
+
+    /// Whether outgoing messages are coalesced into as few writes to the socket as possible.
+    pub fn write_coalescing(&self) -> bool {
+        self.inner.write_coalescing()
+    }
+
     /// Send a method call.
     ///
     /// Create a method-call message, send it over the connection, then wait for the reply. Incoming
@@ -193,6 +401,105 @@ impl Connection {
         )
     }
 
+    /// Send a method call and return the reply body.
+    ///
+    /// This is the same as [`call_method`], except that the body of the reply is deserialized to
+    /// `R`. If it isn't of the type of `R`, the returned [`ReplyBody`] error tells which call the
+    /// reply is to, and the signatures that were expected and received.
+    ///
+    /// [`call_method`]: struct.Connection.html#method.call_method
+    /// [`ReplyBody`]: enum.Error.html#variant.ReplyBody
+    pub fn call_method_typed<'p, R, B, E>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'p>, Error = E>,
+        iface: Option<&str>,
+        method_name: &str,
+        body: &B,
+    ) -> Result<R>
+    where
+        R: serde::de::DeserializeOwned + zvariant::Type,
+        B: serde::ser::Serialize + zvariant::Type,
+        E: Into<MessageError>,
+    {
+        block_on(
+            self.inner
+                .call_method_typed(destination, path, iface, method_name, body),
+        )
+    }
+
+    /// Send a method call, with a body serialized beforehand.
+    ///
+    /// See [`azync::Connection::call_method_raw_body`] for details.
+    ///
+    /// [`azync::Connection::call_method_raw_body`]: azync/struct.Connection.html#method.call_method_raw_body
+    pub fn call_method_raw_body<'p, E>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'p>, Error = E>,
+        iface: Option<&str>,
+        method_name: &str,
+        body: &RawBody,
+    ) -> Result<Arc<Message>>
+    where
+        E: Into<MessageError>,
+    {
+        block_on(
+            self.inner
+                .call_method_raw_body(destination, path, iface, method_name, body),
+        )
+    }
+
+    /// Send a method call, with arguments of types only known at runtime.
+    ///
+    /// See [`azync::Connection::call_method_with_values`] for details.
+    ///
+    /// [`azync::Connection::call_method_with_values`]: azync/struct.Connection.html#method.call_method_with_values
+    pub fn call_method_with_values<'p, E>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'p>, Error = E>,
+        iface: Option<&str>,
+        method_name: &str,
+        args: &[Value<'_>],
+    ) -> Result<Arc<Message>>
+    where
+        E: Into<MessageError>,
+    {
+        block_on(
+            self.inner
+                .call_method_with_values(destination, path, iface, method_name, args),
+        )
+    }
+
+    /// Send a method call, with arguments given as text.
+    ///
+    /// See [`azync::Connection::call_method_dynamic`] for details.
+    ///
+    /// [`azync::Connection::call_method_dynamic`]: azync/struct.Connection.html#method.call_method_dynamic
+    pub fn call_method_dynamic<'p, E, S>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'p>, Error = E>,
+        iface: Option<&str>,
+        method_name: &str,
+        signature: &str,
+        args: &[S],
+    ) -> Result<Arc<Message>>
+    where
+        E: Into<MessageError>,
+        S: AsRef<str>,
+    {
+        block_on(self.inner.call_method_dynamic(
+            destination,
+            path,
+            iface,
+            method_name,
+            signature,
+            args,
+        ))
+    }
+
     /// Emit a signal.
     ///
     /// Create a signal message, and send it over the connection.
@@ -214,6 +521,29 @@ impl Connection {
         )
     }
 
+    /// Emit a signal, without blocking.
+    ///
+    /// Fails with [`Error::QueueFull`] rather than blocking when the outgoing queue is full. See
+    /// [`try_send_message`] for details.
+    ///
+    /// [`Error::QueueFull`]: enum.Error.html#variant.QueueFull
+    /// [`try_send_message`]: #method.try_send_message
+    pub fn try_emit_signal<'p, B, E>(
+        &self,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'p>, Error = E>,
+        iface: &str,
+        signal_name: &str,
+        body: &B,
+    ) -> Result<()>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+        E: Into<MessageError>,
+    {
+        self.inner
+            .try_emit_signal(destination, path, iface, signal_name, body)
+    }
+
     /// Reply to a message.
     ///
     /// Given an existing message (likely a method call), send a reply back to the caller with the
@@ -240,6 +570,125 @@ impl Connection {
         block_on(self.inner.reply_error(call, error_name, body))
     }
 
+    /// Wait for the next emission of a signal.
+    ///
+    /// See [`azync::Connection::wait_for_signal`] for details.
+    ///
+    /// [`azync::Connection::wait_for_signal`]: azync/struct.Connection.html#method.wait_for_signal
+    pub fn wait_for_signal<'s, E>(
+        &self,
+        sender: &'s str,
+        path: impl TryInto<ObjectPath<'s>, Error = E>,
+        interface: &'s str,
+        signal_name: &'s str,
+    ) -> Result<Arc<Message>>
+    where
+        E: Into<Error>,
+    {
+        block_on(
+            self.inner
+                .wait_for_signal(sender, path, interface, signal_name),
+        )
+    }
+
+    /// Wait for the next emission of a signal, for at most `timeout`.
+    ///
+    /// Same as [`wait_for_signal`], except that an [`Error::Io`] of kind [`ErrorKind::TimedOut`] is
+    /// returned if no matching signal is received in time.
+    ///
+    /// [`wait_for_signal`]: struct.Connection.html#method.wait_for_signal
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    /// [`ErrorKind::TimedOut`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.TimedOut
+    pub fn wait_for_signal_timeout<'s, E>(
+        &self,
+        sender: &'s str,
+        path: impl TryInto<ObjectPath<'s>, Error = E>,
+        interface: &'s str,
+        signal_name: &'s str,
+        timeout: Duration,
+    ) -> Result<Arc<Message>>
+    where
+        E: Into<Error>,
+    {
+        let wait = self
+            .inner
+            .wait_for_signal(sender, path, interface, signal_name);
+
+        block_on(async {
+            match crate::timeout(timeout, wait).await {
+                Some(msg) => msg,
+                None => Err(Error::Io(io::Error::new(
+                    ErrorKind::TimedOut,
+                    "timed out waiting for signal",
+                ))),
+            }
+        })
+    }
+
+    /// Checks if this connection is currently the primary owner of the bus name `name`.
+    pub fn is_name_owner(&self, name: &str) -> Result<bool> {
+        block_on(self.inner.is_name_owner(name))
+    }
+
+    /// The well-known names registered through [`ConnectionBuilder::name`].
+    ///
+    /// [`ConnectionBuilder::name`]: struct.ConnectionBuilder.html#method.name
+    pub fn registered_names(&self) -> Vec<String> {
+        self.inner.registered_names()
+    }
+
+    /// Request the well-known names registered through [`ConnectionBuilder::name`] from the bus.
+    ///
+    /// See [`azync::Connection::request_registered_names`] for details.
+    ///
+    /// [`ConnectionBuilder::name`]: struct.ConnectionBuilder.html#method.name
+    /// [`azync::Connection::request_registered_names`]: azync/struct.Connection.html#method.request_registered_names
+    pub fn request_registered_names(&self) -> Result<()> {
+        block_on(self.inner.request_registered_names())
+    }
+
+    /// Watch the ownership of the bus name `name`.
+    ///
+    /// See [`azync::Connection::watch_name`] for details.
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    /// use zbus::Connection;
+    ///
+    /// let conn = Connection::new_session()?;
+    /// let mut watch = conn.watch_name("org.freedesktop.Notifications")?;
+    /// let owner = watch.wait_until_owned()?;
+    /// println!("Notifications available, from {}", owner);
+    /// if let Some(None) = watch.next().transpose()? {
+    ///     println!("Notifications unavailable");
+    /// }
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`azync::Connection::watch_name`]: azync/struct.Connection.html#method.watch_name
+    pub fn watch_name(&self, name: &str) -> Result<NameWatch> {
+        block_on(self.inner.watch_name(name)).map(NameWatch)
+    }
+
+    /// The credentials of the peer owning the bus name `name`.
+    ///
+    /// See [`azync::Connection::credentials_of`] for details, including the caching of the
+    /// credentials of unique names.
+    ///
+    /// [`azync::Connection::credentials_of`]: azync/struct.Connection.html#method.credentials_of
+    pub fn credentials_of(&self, name: &str) -> Result<ConnectionCredentials> {
+        block_on(self.inner.credentials_of(name))
+    }
+
+    /// Turns this connection into a monitor connection, receiving messages matching `match_rules`.
+    ///
+    /// See [`azync::Connection::become_monitor`] for details.
+    ///
+    /// [`azync::Connection::become_monitor`]: azync/struct.Connection.html#method.become_monitor
+    pub fn become_monitor(&self, match_rules: &[&str]) -> Result<()> {
+        block_on(self.inner.become_monitor(match_rules))
+    }
+
     /// Checks if `self` is a connection to a message bus.
     ///
     /// This will return `false` for p2p connections.
@@ -253,6 +702,12 @@ impl Connection {
     }
 
     /// Get the underlying async Connection, consuming `self`.
+    ///
+    /// This keeps all the state of the connection, except for the messages queued for
+    /// [`receive_message`] (unless `self` has other clones). See the [conversion] details.
+    ///
+    /// [`receive_message`]: struct.Connection.html#method.receive_message
+    /// [conversion]: struct.Connection.html#conversion-to-and-from-azyncconnection
     pub fn into_inner(self) -> azync::Connection {
         self.inner
     }
@@ -269,13 +724,83 @@ impl From<azync::Connection> for Connection {
     }
 }
 
+/// Create two peer-to-peer connections to each other, over an in-memory channel.
+///
+/// This is the blocking sibling of [`azync::in_memory_pair`]. The connections are ready to use,
+/// with no handshake and without going through the OS:
+///
+/// ```
+/// let (service, client) = zbus::in_memory_pair()?;
+/// service.emit_signal(None, "/org/zbus/Service", "org.zbus.Service", "Ready", &())?;
+/// let signal = client.receive_message()?;
+/// assert_eq!(signal.to_string(), "Signal Ready");
+///# Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+/// ```
+///
+/// [`azync::in_memory_pair`]: azync/fn.in_memory_pair.html
+pub fn in_memory_pair() -> Result<(Connection, Connection)> {
+    in_memory_pair_with(InMemoryOptions::default())
+}
+
+/// Create two peer-to-peer connections to each other, over an in-memory channel with the given
+/// `options`.
+///
+/// This is the blocking sibling of [`azync::in_memory_pair_with`].
+///
+/// [`azync::in_memory_pair_with`]: azync/fn.in_memory_pair_with.html
+pub fn in_memory_pair_with(options: InMemoryOptions) -> Result<(Connection, Connection)> {
+    let (conn0, conn1) = block_on(azync::in_memory_pair_with(options))?;
+
+    Ok((conn0.into(), conn1.into()))
+}
+
+/// An [`Iterator`] over the owner of a bus name, as it changes.
+///
+/// Each item is the unique name of the owner, or `None` when the name has no owner. Use
+/// [`Connection::watch_name`] to create an instance of this type.
+///
+/// [`Iterator`]: https://doc.rust-lang.org/std/iter/trait.Iterator.html
+/// [`Connection::watch_name`]: struct.Connection.html#method.watch_name
+pub struct NameWatch(azync::NameWatch);
+
+assert_impl_all!(NameWatch: Send, Unpin);
+
+impl NameWatch {
+    /// Wait until the name is owned, returning the unique name of its owner.
+    ///
+    /// Returns right away if it's owned already.
+    pub fn wait_until_owned(&mut self) -> Result<String> {
+        block_on(self.0.wait_until_owned())
+    }
+}
+
+impl Iterator for NameWatch {
+    type Item = Result<Option<String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        block_on(self.0.next())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use ntest::timeout;
-    use std::{os::unix::net::UnixStream, thread};
+    use std::{
+        collections::HashMap,
+        convert::TryFrom,
+        fs::File,
+        io::ErrorKind,
+        os::unix::{io::AsRawFd, net::UnixStream},
+        thread,
+        time::Duration,
+    };
     use test_env_log::test;
+    use zvariant::{Fd, OwnedValue, Value};
 
-    use crate::{Connection, Error, Guid};
+    use crate::{
+        fdo, Connection, ConnectionBuilder, EndianSig, Error, Guid, Message, MessageBuilder,
+        PeerStats, NATIVE_ENDIAN_SIG,
+    };
     #[test]
     #[timeout(1000)]
     fn unix_p2p() {
@@ -303,4 +828,478 @@ mod tests {
         let val = server_thread.join().expect("failed to join server thread");
         assert_eq!(val, "yay");
     }
+
+    #[test]
+    #[timeout(1000)]
+    fn call_method_typed() {
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        // A peer replying to `Version` with a string, where clients expect a number.
+        let server_thread = thread::spawn(move || {
+            let c = Connection::new_unix_server(p0, &guid).unwrap();
+            for _ in 0..2 {
+                let m = c.receive_message().unwrap();
+                c.reply(&m, &"1.2").unwrap();
+            }
+        });
+
+        let c = Connection::new_unix_client(p1, false).unwrap();
+        let version: String = c
+            .call_method_typed(None, "/", Some("org.zbus.p2p"), "Version", &())
+            .unwrap();
+        assert_eq!(version, "1.2");
+
+        let err = c
+            .call_method_typed::<u32, _, _>(
+                Some("org.zbus.Peer"),
+                "/",
+                Some("org.zbus.p2p"),
+                "Version",
+                &(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid reply to `org.zbus.p2p.Version` from `org.zbus.Peer`: expected signature \
+             `u`, received `s` (unmatched body signature)"
+        );
+        match err {
+            Error::ReplyBody(e) => {
+                assert_eq!(e.destination(), Some("org.zbus.Peer"));
+                assert_eq!(e.interface(), Some("org.zbus.p2p"));
+                assert_eq!(e.member(), "Version");
+                assert_eq!(e.expected_signature(), "u");
+                assert_eq!(e.received_signature(), "s");
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn wait_for_signal_timeout() {
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let server_thread = thread::spawn(move || {
+            let c = Connection::new_unix_server(p0, &guid).unwrap();
+            let err = c
+                .wait_for_signal_timeout(
+                    "org.zbus.p2p",
+                    "/",
+                    "org.zbus.p2p",
+                    "NeverSent",
+                    Duration::from_millis(50),
+                )
+                .unwrap_err();
+            assert!(matches!(err, Error::Io(e) if e.kind() == ErrorKind::TimedOut));
+            c.wait_for_signal_timeout(
+                "org.zbus.p2p",
+                "/",
+                "org.zbus.p2p",
+                "Sent",
+                Duration::from_millis(500),
+            )
+            .unwrap()
+        });
+
+        let c = Connection::new_unix_client(p1, false).unwrap();
+        // Give the server enough time to time out on the first wait.
+        thread::sleep(Duration::from_millis(100));
+        c.emit_signal(None, "/", "org.zbus.p2p", "Sent", &())
+            .unwrap();
+
+        let msg = server_thread.join().expect("failed to join server thread");
+        assert_eq!(msg.to_string(), "Signal Sent");
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn watch_name() {
+        let name = "org.zbus.WatchNameTest";
+        let conn = Connection::new_session().unwrap();
+        let mut watch = conn.watch_name(name).unwrap();
+        assert_eq!(watch.next().unwrap().unwrap(), None);
+
+        // Another connection starting and stopping to own the name.
+        let service = Connection::new_session().unwrap();
+        let service_name = service.unique_name().unwrap().to_string();
+        let proxy = fdo::DBusProxy::new(&service).unwrap();
+        proxy.request_name(name, Default::default()).unwrap();
+        assert_eq!(watch.wait_until_owned().unwrap(), service_name);
+        proxy.release_name(name).unwrap();
+        assert_eq!(watch.next().unwrap().unwrap(), None);
+
+        // A watch created while the name is owned yields the owner right away.
+        proxy.request_name(name, Default::default()).unwrap();
+        let mut owned = conn.watch_name(name).unwrap();
+        assert_eq!(owned.wait_until_owned().unwrap(), service_name);
+        assert_eq!(watch.next().unwrap().unwrap(), Some(service_name));
+        proxy.release_name(name).unwrap();
+        assert_eq!(owned.next().unwrap().unwrap(), None);
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn request_registered_names() {
+        let names = ["org.zbus.DeferredNameTest1", "org.zbus.DeferredNameTest2"];
+        let conn = ConnectionBuilder::session()
+            .name(names[0])
+            .name(names[1])
+            .request_name_on_build(false)
+            .build()
+            .unwrap();
+        assert_eq!(conn.registered_names(), names);
+        for name in &names {
+            assert!(!conn.is_name_owner(name).unwrap());
+        }
+        conn.request_registered_names().unwrap();
+        for name in &names {
+            assert!(conn.is_name_owner(name).unwrap());
+        }
+        // Requesting them again is fine.
+        conn.request_registered_names().unwrap();
+
+        // Another connection can't take them, whether requested on build or later.
+        let err = ConnectionBuilder::session()
+            .name(names[1])
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Error::NameTaken(name) if name == names[1]));
+        let other = ConnectionBuilder::session()
+            .name(names[0])
+            .request_name_on_build(false)
+            .build()
+            .unwrap();
+        let err = other.request_registered_names().unwrap_err();
+        assert!(matches!(err, Error::NameTaken(name) if name == names[0]));
+
+        // Requested on build by default, once released by the previous owner.
+        fdo::DBusProxy::new(&conn)
+            .unwrap()
+            .release_name(names[1])
+            .unwrap();
+        let conn = ConnectionBuilder::session().name(names[1]).build().unwrap();
+        assert!(conn.is_name_owner(names[1]).unwrap());
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn delay_hello() {
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        // A peer playing the bus, expecting a registration before `Hello`.
+        let bus_thread = thread::spawn(move || {
+            let c = Connection::new_unix_server(p0, &guid).unwrap();
+            let m = c.receive_message().unwrap();
+            assert_eq!(m.to_string(), "Method call Register");
+            let token: String = m.body().unwrap();
+            c.reply(&m, &(token == "secret")).unwrap();
+
+            let m = c.receive_message().unwrap();
+            assert_eq!(m.to_string(), "Method call Hello");
+            c.reply(&m, &":1.42").unwrap();
+
+            c.receive_message().unwrap().to_string()
+        });
+
+        let c = ConnectionBuilder::unix_stream(p1)
+            .delay_hello(true)
+            .build()
+            .unwrap();
+        assert_eq!(c.unique_name(), None);
+        let err = c
+            .emit_signal(None, "/", "org.zbus.Broker", "Ready", &())
+            .unwrap_err();
+        assert!(matches!(err, Error::NoUniqueName));
+
+        let waiter = {
+            let c = c.clone();
+            thread::spawn(move || c.unique_name_acquired().map(String::from))
+        };
+
+        let register = Message::method(
+            None,
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            Some("org.zbus.Broker"),
+            "Register",
+            &"secret",
+        )
+        .unwrap();
+        let serial = c.send_message(register).unwrap();
+        let reply = c.receive_message().unwrap();
+        assert_eq!(
+            reply.header().unwrap().reply_serial().unwrap(),
+            Some(serial)
+        );
+        assert!(reply.body::<bool>().unwrap());
+
+        assert_eq!(c.hello().unwrap(), ":1.42");
+        assert_eq!(waiter.join().unwrap().unwrap(), ":1.42");
+        // Only said once.
+        assert_eq!(c.hello().unwrap(), ":1.42");
+        assert_eq!(c.unique_name(), Some(":1.42"));
+
+        c.emit_signal(None, "/", "org.zbus.Broker", "Ready", &())
+            .unwrap();
+        let msg = bus_thread.join().expect("failed to join bus thread");
+        assert_eq!(msg, "Signal Ready");
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn foreign_endian_p2p() {
+        let guid = Guid::generate();
+        let foreign = match NATIVE_ENDIAN_SIG {
+            EndianSig::Big => EndianSig::Little,
+            EndianSig::Little => EndianSig::Big,
+        };
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let server_thread = thread::spawn(move || {
+            let c = Connection::new_unix_server(p0, &guid).unwrap();
+
+            let m = c.receive_message().unwrap();
+            assert_eq!(m.to_string(), "Method call Echo");
+            assert_eq!(m.primary_header().endian_sig(), foreign);
+            let (fd, pairs, shorts, s): (Fd, Vec<(u8, u64)>, Vec<u16>, String) = m.body().unwrap();
+            assert!(fd.as_raw_fd() >= 0);
+            assert_eq!(pairs, vec![(1, 0x0102_0304_0506_0708), (2, 42)]);
+            assert_eq!(shorts, vec![0x0102, 3]);
+            c.reply(&m, &(pairs, shorts, s)).unwrap();
+
+            let m = c.receive_message().unwrap();
+            assert_eq!(m.to_string(), "Method call Fail");
+            let error = MessageBuilder::error(&m, "org.zbus.Endian.Error")
+                .unwrap()
+                .endian_sig(foreign)
+                .build(&"kaboom")
+                .unwrap();
+            c.send_message(error).unwrap();
+        });
+
+        let c = ConnectionBuilder::unix_stream(p1)
+            .p2p()
+            .endian_sig(foreign)
+            .build()
+            .unwrap();
+        let file = File::open("/dev/null").unwrap();
+        let pairs = vec![(1u8, 0x0102_0304_0506_0708u64), (2, 42)];
+        let shorts = vec![0x0102u16, 3];
+        let reply = c
+            .call_method(
+                None,
+                "/",
+                Some("org.zbus.Endian"),
+                "Echo",
+                &(Fd::from(file.as_raw_fd()), &pairs, &shorts, "hello"),
+            )
+            .unwrap();
+        assert_eq!(reply.primary_header().endian_sig(), NATIVE_ENDIAN_SIG);
+        let echoed: (Vec<(u8, u64)>, Vec<u16>, String) = reply.body().unwrap();
+        assert_eq!(echoed, (pairs, shorts, String::from("hello")));
+
+        let err = c
+            .call_method(None, "/", Some("org.zbus.Endian"), "Fail", &())
+            .unwrap_err();
+        match err {
+            Error::MethodError(name, detail, reply) => {
+                assert_eq!(name, "org.zbus.Endian.Error");
+                assert_eq!(detail.as_deref(), Some("kaboom"));
+                assert_eq!(reply.primary_header().endian_sig(), foreign);
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
+        server_thread.join().expect("failed to join server thread");
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn peer_stats_p2p() {
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let server_thread = thread::spawn(move || {
+            let c = ConnectionBuilder::unix_stream(p0)
+                .server(&guid)
+                .peer_stats(true)
+                .build()
+                .unwrap();
+
+            let m = c.receive_message().unwrap();
+            assert_eq!(m.to_string(), "Signal Ready");
+            let signal_bytes = m.as_bytes().len() as u64;
+
+            let m = c.receive_message().unwrap();
+            assert_eq!(m.to_string(), "Method call Echo");
+            let mut method_call_bytes = m.as_bytes().len() as u64;
+            c.reply(&m, &"hello").unwrap();
+
+            let m = c.receive_message().unwrap();
+            assert_eq!(m.to_string(), "Method call Fail");
+            method_call_bytes += m.as_bytes().len() as u64;
+            c.reply_error(&m, "org.zbus.Stats.Error", &"kaboom")
+                .unwrap();
+
+            (c.peer_stats(), signal_bytes, method_call_bytes)
+        });
+
+        let c = Connection::new_unix_client(p1, false).unwrap();
+        assert!(c.peer_stats().is_empty());
+        c.emit_signal(None, "/", "org.zbus.Stats", "Ready", &())
+            .unwrap();
+        let reply = c
+            .call_method(None, "/", Some("org.zbus.Stats"), "Echo", &"hello")
+            .unwrap();
+        let err = c
+            .call_method(None, "/", Some("org.zbus.Stats"), "Fail", &())
+            .unwrap_err();
+        let error_bytes = match err {
+            Error::MethodError(_, _, reply) => reply.as_bytes().len() as u64,
+            e => panic!("unexpected error: {}", e),
+        };
+        // Stats are disabled by default.
+        assert!(c.peer_stats().is_empty());
+
+        let (stats, signal_bytes, method_call_bytes) =
+            server_thread.join().expect("failed to join server thread");
+        assert_eq!(
+            stats,
+            vec![PeerStats {
+                name: None,
+                method_calls: 2,
+                method_call_bytes,
+                signals: 1,
+                signal_bytes,
+                replies_sent: 1,
+                reply_bytes_sent: reply.as_bytes().len() as u64,
+                errors_sent: 1,
+                error_bytes_sent: error_bytes,
+            }]
+        );
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn call_method_with_values() {
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let server_thread = thread::spawn(move || {
+            let c = Connection::new_unix_server(p0, &guid).unwrap();
+            for _ in 0..2 {
+                let m = c.receive_message().unwrap();
+                let sig = m.body_signature().map(|s| s.to_string());
+                c.reply(&m, &sig.unwrap_or_default()).unwrap();
+            }
+            let m = c.receive_message().unwrap();
+            let (name, flags, (n, b)): (&str, Vec<&str>, (u32, bool)) = m.body().unwrap();
+            c.reply(&m, &(format!("{} {:?} {} {}", name, flags, n, b), n * 2))
+                .unwrap();
+        });
+
+        let c = Connection::new_unix_client(p1, false).unwrap();
+        let iface = Some("org.zbus.p2p");
+        // A single structure argument stays one.
+        let args = [Value::from((1u32, true))];
+        let reply = c
+            .call_method_with_values(None, "/", iface, "Signature", &args)
+            .unwrap();
+        assert_eq!(reply.body::<&str>().unwrap(), "(ub)");
+        let reply = c
+            .call_method_with_values(None, "/", iface, "Signature", &[])
+            .unwrap();
+        assert_eq!(reply.body::<&str>().unwrap(), "");
+
+        let args = [
+            Value::from("zbus"),
+            Value::from(vec!["a", "b"]),
+            Value::from((21u32, true)),
+        ];
+        let reply = c
+            .call_method_with_values(None, "/", iface, "Test", &args)
+            .unwrap();
+        let body = reply.body_structure().unwrap();
+        assert_eq!(body.full_signature().as_str(), "(su)");
+        assert_eq!(
+            body.fields(),
+            [
+                Value::from(r#"zbus ["a", "b"] 21 true"#),
+                Value::from(42u32)
+            ]
+        );
+
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn call_method_dynamic() {
+        let guid = Guid::generate();
+
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let server_thread = thread::spawn(move || {
+            let c = Connection::new_unix_server(p0, &guid).unwrap();
+            let m = c.receive_message().unwrap();
+            let (name, props, (n, flags)): (&str, HashMap<&str, OwnedValue>, (i32, Vec<bool>)) =
+                m.body().unwrap();
+            let mut keys = props.keys().collect::<Vec<_>>();
+            keys.sort_unstable();
+            let count = u32::try_from(props["count"].clone()).unwrap();
+            c.reply(
+                &m,
+                &format!("{} {:?} {} {} {:?}", name, keys, count, n, flags),
+            )
+            .unwrap();
+        });
+
+        let c = Connection::new_unix_client(p1, false).unwrap();
+        let iface = Some("org.zbus.p2p");
+        let args = ["zbus", "name=s:zbus,count=u:3", "(-1,[yes,no])"];
+        let reply = c
+            .call_method_dynamic(None, "/", iface, "Test", "sa{sv}(iab)", &args)
+            .unwrap();
+        assert_eq!(
+            reply.body::<&str>().unwrap(),
+            r#"zbus ["count", "name"] 3 -1 [true, false]"#
+        );
+
+        // Nothing is sent for invalid arguments.
+        let err = c
+            .call_method_dynamic(None, "/", iface, "Test", "su", &["zbus", "-1"])
+            .unwrap_err();
+        match err {
+            Error::Argument(1, _) => (),
+            e => panic!("unexpected error: {}", e),
+        }
+        let err = c
+            .call_method_dynamic(None, "/", iface, "Test", "su", &["zbus"])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid argument 1: missing argument of type `u`"
+        );
+        let err = c
+            .call_method_dynamic(None, "/", iface, "Test", "", &["zbus"])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid argument 0: unexpected argument `zbus`"
+        );
+
+        server_thread.join().unwrap();
+    }
 }
@@ -0,0 +1,891 @@
+use std::{
+    net::{IpAddr, TcpStream},
+    os::unix::net::UnixStream,
+    str::FromStr,
+    time::Duration,
+};
+
+use async_io::{block_on, Async};
+use static_assertions::assert_impl_all;
+
+#[cfg(any(test, feature = "test-util"))]
+use crate::test_util;
+use crate::{
+    address::{AddressList, TcpOptions},
+    azync::{self, Authenticated},
+    raw::Socket,
+    AuthMechanism, Connection, EndianSig, Error, Guid, Result, NATIVE_ENDIAN_SIG,
+};
+
+#[derive(Debug)]
+enum Target {
+    UnixStream(UnixStream),
+    TcpStream(TcpStream),
+    Address(String),
+    Session,
+    System,
+    Starter,
+}
+
+/// Builder for client-side connections.
+///
+/// Use it for connections the `new_*` constructors of [`Connection`] can't make, e.g to delay
+/// saying `Hello` to the bus:
+///
+/// ```no_run
+///# use std::error::Error;
+///#
+/// use zbus::ConnectionBuilder;
+///
+/// let conn = ConnectionBuilder::session().delay_hello(true).build()?;
+/// assert_eq!(conn.unique_name(), None);
+///
+/// // Exchange messages with the bus through `send_message` and `receive_message`..
+///
+/// let name = conn.hello()?;
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+///
+/// The `tcp_*` methods set the socket options of connections to `tcp:` addresses, including
+/// the bus addresses from the environment. They have no effect on other transports:
+///
+/// ```no_run
+///# use std::error::Error;
+///#
+/// use std::time::Duration;
+/// use zbus::ConnectionBuilder;
+///
+/// let conn = ConnectionBuilder::address("tcp:host=bus.example.com,port=4242")
+///     .tcp_keepalive(Duration::from_secs(60))
+///     .tcp_connect_timeout(Duration::from_secs(5))
+///     .build()?;
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+///
+/// The builder also makes the server side of peer-to-peer connections over a `UnixStream` or a
+/// `TcpStream`, through [`server`]. On either side, [`auth_mechanism`] adds custom authentication
+/// mechanisms to the built-in ones.
+///
+/// [`Connection`]: struct.Connection.html
+/// [`server`]: #method.server
+/// [`auth_mechanism`]: #method.auth_mechanism
+#[derive(Debug)]
+pub struct ConnectionBuilder {
+    target: Target,
+    p2p: bool,
+    delay_hello: bool,
+    endian_sig: EndianSig,
+    tcp: TcpOptions,
+    server_guid: Option<Guid>,
+    auth_mechanisms: Vec<Box<dyn AuthMechanism>>,
+    peer_stats: bool,
+    names: Vec<String>,
+    request_name_on_build: bool,
+    liveness_ping: Option<(Duration, Duration)>,
+    body_compression: Option<usize>,
+    value_budget: Option<usize>,
+    #[cfg(any(test, feature = "test-util"))]
+    hooks: test_util::Hooks,
+}
+
+assert_impl_all!(ConnectionBuilder: Send, Sync, Unpin);
+
+impl ConnectionBuilder {
+    /// Create a builder for a connection to the session/user message bus.
+    pub fn session() -> Self {
+        Self::new(Target::Session)
+    }
+
+    /// Create a builder for a connection to the system-wide message bus.
+    pub fn system() -> Self {
+        Self::new(Target::System)
+    }
+
+    /// Create a builder for a connection to the message bus that started this process.
+    ///
+    /// See [`Connection::new_starter`] for details.
+    ///
+    /// [`Connection::new_starter`]: azync/struct.Connection.html#method.new_starter
+    pub fn starter() -> Self {
+        Self::new(Target::Starter)
+    }
+
+    /// Create a builder for a connection to the given [D-Bus address].
+    ///
+    /// See [`Connection::new_for_address`] for the details about address lists.
+    ///
+    /// [D-Bus address]: https://dbus.freedesktop.org/doc/dbus-specification.html#addresses
+    /// [`Connection::new_for_address`]: struct.Connection.html#method.new_for_address
+    pub fn address(address: &str) -> Self {
+        Self::new(Target::Address(address.to_owned()))
+    }
+
+    /// Create a builder for a connection over a `UnixStream`.
+    pub fn unix_stream(stream: UnixStream) -> Self {
+        Self::new(Target::UnixStream(stream))
+    }
+
+    /// Create a builder for a connection over a `TcpStream`.
+    ///
+    /// The `tcp_*` options don't apply to the stream, which is used as is.
+    pub fn tcp_stream(stream: TcpStream) -> Self {
+        Self::new(Target::TcpStream(stream))
+    }
+
+    fn new(target: Target) -> Self {
+        Self {
+            target,
+            p2p: false,
+            delay_hello: false,
+            endian_sig: NATIVE_ENDIAN_SIG,
+            tcp: TcpOptions::default(),
+            server_guid: None,
+            auth_mechanisms: vec![],
+            peer_stats: false,
+            names: vec![],
+            request_name_on_build: true,
+            liveness_ping: None,
+            body_compression: None,
+            value_budget: None,
+            #[cfg(any(test, feature = "test-util"))]
+            hooks: test_util::Hooks::default(),
+        }
+    }
+
+    /// Set the connection up for peer-to-peer communications, rather than with a bus.
+    pub fn p2p(mut self) -> Self {
+        self.p2p = true;
+        self
+    }
+
+    /// Set the connection up as the server side of a peer-to-peer connection, with the given `guid`.
+    ///
+    /// The client is authenticated, rather than authenticating to it. By default, only clients of
+    /// the same user as the server are accepted, through the `EXTERNAL` mechanism, or the
+    /// [`DBUS_COOKIE_SHA1`] one over a `TcpStream`. This is only supported on connections over a
+    /// `UnixStream` or a `TcpStream`: building others fails with [`Error::Unsupported`].
+    ///
+    /// [`DBUS_COOKIE_SHA1`]: struct.CookieSha1.html
+    /// [`Error::Unsupported`]: enum.Error.html#variant.Unsupported
+    pub fn server(mut self, guid: &Guid) -> Self {
+        self.server_guid = Some(guid.clone());
+        self.p2p = true;
+        self
+    }
+
+    /// Add a custom authentication mechanism.
+    ///
+    /// Clients try the mechanisms in the order they're added, before the built-in ones. Servers
+    /// accept clients authenticating with any of them, besides the built-in ones.
+    pub fn auth_mechanism<M>(mut self, mechanism: M) -> Self
+    where
+        M: AuthMechanism + 'static,
+    {
+        self.auth_mechanisms.push(Box::new(mechanism));
+        self
+    }
+
+    /// Don't say `Hello` to the bus when building the connection.
+    ///
+    /// The connection is then returned right after the authentication, without a unique name. This
+    /// gives a chance to exchange messages with the bus before anything else, until calling
+    /// [`Connection::hello`]. This has no effect on peer-to-peer connections.
+    ///
+    /// [`Connection::hello`]: struct.Connection.html#method.hello
+    pub fn delay_hello(mut self, delay: bool) -> Self {
+        self.delay_hello = delay;
+        self
+    }
+
+    /// Set the byte order of the messages created by the connection.
+    ///
+    /// The native byte order is used by default. Messages are received in whatever byte order
+    /// the peer chose, regardless of this setting. Note that [`RawBody`] is always in the native
+    /// byte order, so calls of [`Connection::call_method_raw_body`] fail with another one.
+    ///
+    /// [`RawBody`]: struct.RawBody.html
+    /// [`Connection::call_method_raw_body`]: azync/struct.Connection.html#method.call_method_raw_body
+    pub fn endian_sig(mut self, sig: EndianSig) -> Self {
+        self.endian_sig = sig;
+        self
+    }
+
+    /// Account for the traffic exchanged with each peer, to get it from [`Connection::peer_stats`].
+    ///
+    /// This is meant for debugging, e.g to find out which peer is flooding a service. The method
+    /// calls and signals received from each peer are counted, along with the replies and errors
+    /// sent to it, keyed by unique name. On bus connections, peers are forgotten when their unique
+    /// name goes away, for which the bus is asked for the `NameOwnerChanged` signals of vanishing
+    /// names: these then show up in the message streams of the connection as well.
+    ///
+    /// This is disabled by default, in which case it costs next to nothing.
+    ///
+    /// [`Connection::peer_stats`]: struct.Connection.html#method.peer_stats
+    pub fn peer_stats(mut self, enabled: bool) -> Self {
+        self.peer_stats = enabled;
+        self
+    }
+
+    /// Limit the number of values decoded from the body of each message received to `budget`.
+    ///
+    /// This is meant for services taking calls from untrusted peers: a small message can be
+    /// crafted to decode into a lot of values, and so allocations. See
+    /// [`Message::set_value_budget`] for what counts as a value. Decoding the body of a message
+    /// past the budget fails, as for any malformed message. There's no limit by default.
+    ///
+    /// [`Message::set_value_budget`]: struct.Message.html#method.set_value_budget
+    pub fn value_budget(mut self, budget: usize) -> Self {
+        self.value_budget = Some(budget);
+        self
+    }
+
+    /// Register a well-known name for the connection to own on the bus.
+    ///
+    /// The names are requested from the bus once the connection is built, without queueing, so
+    /// building fails with [`Error::NameTaken`] if another connection owns one of them already.
+    /// This can be deferred through [`request_name_on_build`]. It is also deferred if saying
+    /// `Hello` is, through [`delay_hello`]. This has no effect on peer-to-peer connections.
+    ///
+    /// [`Error::NameTaken`]: enum.Error.html#variant.NameTaken
+    /// [`request_name_on_build`]: #method.request_name_on_build
+    /// [`delay_hello`]: #method.delay_hello
+    pub fn name(mut self, name: &str) -> Self {
+        self.names.push(name.to_owned());
+        self
+    }
+
+    /// Whether to request the names registered through [`name`] when building the connection.
+    ///
+    /// This is the default. Otherwise, they're only requested on calling
+    /// [`Connection::request_registered_names`], typically once all the objects are served. This
+    /// matters for services started by the bus or by systemd (with `Type=dbus`), which are
+    /// considered ready as soon as they own their name:
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    ///#
+    /// use zbus::{ConnectionBuilder, ObjectServer};
+    ///
+    /// let conn = ConnectionBuilder::session()
+    ///     .name("org.zbus.MyService")
+    ///     .request_name_on_build(false)
+    ///     .build()?;
+    /// let mut object_server = ObjectServer::new(&conn);
+    ///
+    /// // Serve the objects with `object_server.at` here..
+    ///
+    /// conn.request_registered_names()?;
+    /// loop {
+    ///     object_server.try_handle_next()?;
+    /// }
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`name`]: #method.name
+    /// [`Connection::request_registered_names`]: struct.Connection.html#method.request_registered_names
+    pub fn request_name_on_build(mut self, request: bool) -> Self {
+        self.request_name_on_build = request;
+        self
+    }
+
+    /// Check that the peer is still alive, by pinging it whenever nothing was received from it for
+    /// `interval`.
+    ///
+    /// This is for peers that may hang without the connection being closed, e.g virtual machines
+    /// that freeze. The `Ping` method of the `org.freedesktop.DBus.Peer` interface is called, on
+    /// the bus itself for bus connections. Any reply will do, even an error. If none comes within
+    /// `timeout`, the connection is given up on:
+    ///
+    /// * The pending method calls fail with [`Error::PeerUnresponsive`], and so do the attempts to
+    ///   send messages.
+    /// * The socket is shut down, and the message streams end.
+    ///
+    /// The pings are sent by a task of the [connection executor], which must be running.
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    ///#
+    /// use std::{os::unix::net::UnixStream, time::Duration};
+    /// use zbus::ConnectionBuilder;
+    ///
+    /// let stream = UnixStream::connect("/run/vm/bus.sock")?;
+    /// let conn = ConnectionBuilder::unix_stream(stream)
+    ///     .p2p()
+    ///     .liveness_ping(Duration::from_secs(10), Duration::from_secs(5))
+    ///     .build()?;
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`Error::PeerUnresponsive`]: enum.Error.html#variant.PeerUnresponsive
+    /// [connection executor]: azync/struct.Connection.html#method.executor
+    pub fn liveness_ping(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.liveness_ping = Some((interval, timeout));
+        self
+    }
+
+    /// Compress the message bodies longer than `threshold` bytes, if the peer supports it.
+    ///
+    /// This is a zbus extension for peer-to-peer connections, to save bandwidth on the links
+    /// between hosts, e.g over tcp. The client offers it in the handshake, and the server agrees
+    /// to it if it's enabled on its side as well. Otherwise, e.g with other D-Bus implementations,
+    /// all messages are sent uncompressed. Once agreed, each side compresses the bodies longer
+    /// than its own threshold with deflate, unless that doesn't make them any smaller, and the
+    /// bodies are decompressed on receipt, before anything else sees them.
+    ///
+    /// This is disabled by default. Building a connection to a bus with it fails with
+    /// [`Error::Unsupported`]. This is only available with the `compression` feature.
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    ///#
+    /// use std::net::TcpStream;
+    /// use zbus::ConnectionBuilder;
+    ///
+    /// let stream = TcpStream::connect("telemetry.example.com:4242")?;
+    /// let conn = ConnectionBuilder::tcp_stream(stream)
+    ///     .p2p()
+    ///     .compress_bodies(4096)
+    ///     .build()?;
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`Error::Unsupported`]: enum.Error.html#variant.Unsupported
+    #[cfg(feature = "compression")]
+    pub fn compress_bodies(mut self, threshold: usize) -> Self {
+        self.body_compression = Some(threshold);
+        self
+    }
+
+    /// Start numbering the messages of the connection from `serial`.
+    ///
+    /// Together with the other `test-util` methods, this makes the bytes written by connections the
+    /// same from one run to the next, so tests can compare them with golden files. This is only
+    /// available with the `test-util` feature.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn initial_serial(mut self, serial: u32) -> Self {
+        self.hooks.initial_serial = Some(serial);
+        self
+    }
+
+    /// Take the serial numbers of the messages of the connection from `allocator`.
+    ///
+    /// This replaces the built-in counter, and [`initial_serial`] along with it. Like with the
+    /// counter, 0 and the numbers in use by the calls still waiting for their reply are skipped, so
+    /// `allocator` shouldn't return one of them over and over. This is only available with the
+    /// `test-util` feature.
+    ///
+    /// [`initial_serial`]: #method.initial_serial
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn serial_allocator<F>(mut self, allocator: F) -> Self
+    where
+        F: FnMut() -> u32 + Send + 'static,
+    {
+        self.hooks.serial_allocator = Some(test_util::SerialAllocator::new(allocator));
+        self
+    }
+
+    /// Report `guid` as the GUID of the server, rather than the one it sent in the handshake.
+    ///
+    /// This is for the client side, e.g of a connection to a bus started anew by each test run.
+    /// The GUID of the server side is given to [`server`]. This is only available with the
+    /// `test-util` feature.
+    ///
+    /// [`server`]: #method.server
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn reported_server_guid(mut self, guid: &Guid) -> Self {
+        self.hooks.server_guid = Some(guid.clone());
+        self
+    }
+
+    /// Draw the randomness of the handshake from a deterministic RNG, seeded with `seed`.
+    ///
+    /// This covers the challenges of the authentication mechanisms, and the cookies that
+    /// [`DBUS_COOKIE_SHA1`] adds to the keyrings. The algorithm of the RNG won't change in future
+    /// releases, so the same seed keeps giving the same bytes. This is obviously not secure, and
+    /// only available with the `test-util` feature.
+    ///
+    /// [`DBUS_COOKIE_SHA1`]: struct.CookieSha1.html
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn handshake_rng_seed(mut self, seed: u64) -> Self {
+        self.hooks.handshake_rng_seed = Some(seed);
+        self
+    }
+
+    /// Enable or disable Nagle's algorithm on tcp connections, through `TCP_NODELAY`.
+    pub fn tcp_nodelay(mut self, nodelay: bool) -> Self {
+        self.tcp.nodelay = Some(nodelay);
+        self
+    }
+
+    /// Enable TCP keepalive, starting the probes after the connection has been idle for `idle`.
+    ///
+    /// Without keepalive, a connection to a peer that went away without closing it (e.g after a
+    /// network outage) is only noticed on the next write. The times are rounded down to seconds.
+    /// Setting them is not supported on all platforms, in which case only the OS defaults apply.
+    pub fn tcp_keepalive(mut self, idle: Duration) -> Self {
+        self.tcp.keepalive = Some(idle);
+        self
+    }
+
+    /// Set the interval between the TCP keepalive probes.
+    ///
+    /// This only applies with [`tcp_keepalive`] enabled.
+    ///
+    /// [`tcp_keepalive`]: #method.tcp_keepalive
+    pub fn tcp_keepalive_interval(mut self, interval: Duration) -> Self {
+        self.tcp.keepalive_interval = Some(interval);
+        self
+    }
+
+    /// Give up on connecting to each tcp address after `timeout`.
+    ///
+    /// The connection fails with an `ErrorKind::TimedOut` I/O error then, unless other addresses
+    /// are left to try. The name resolution of the host isn't part of this timeout.
+    pub fn tcp_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.tcp.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Bind tcp connections to the given local address, before connecting.
+    ///
+    /// Only the resolved addresses of the same family as `address` are tried then.
+    pub fn tcp_source_address(mut self, address: IpAddr) -> Self {
+        self.tcp.source_address = Some(address);
+        self
+    }
+
+    /// Bind tcp connections to the given network interface, through `SO_BINDTODEVICE`.
+    ///
+    /// This is only supported on Linux, and typically requires the `CAP_NET_RAW` capability.
+    pub fn tcp_bind_device(mut self, device: &str) -> Self {
+        self.tcp.bind_device = Some(device.to_owned());
+        self
+    }
+
+    /// Call `map` on the `TcpStream` of tcp connections, once connected.
+    ///
+    /// This is for setting any socket options that the builder doesn't cover. It is called after
+    /// the other `tcp_*` options are applied, before the authentication.
+    pub fn map_tcp_stream(mut self, map: fn(TcpStream) -> TcpStream) -> Self {
+        self.tcp.map_stream = Some(map);
+        self
+    }
+
+    /// Build the connection.
+    pub fn build(self) -> Result<Connection> {
+        block_on(self.build_async()).map(Connection::from)
+    }
+
+    /// Build the connection, asynchronously.
+    pub async fn build_async(self) -> Result<azync::Connection> {
+        if self.body_compression.is_some() && !self.p2p {
+            return Err(Error::Unsupported);
+        }
+        let mechanisms = self.auth_mechanisms;
+        let (target, server_guid, tcp) = (self.target, self.server_guid, &self.tcp);
+        let compression = self.body_compression;
+        let auth = async move {
+            Ok::<_, Error>(match (target, server_guid) {
+                (Target::UnixStream(stream), Some(guid)) => {
+                    Authenticated::unix_server(stream, guid, mechanisms, compression).await?
+                }
+                (Target::TcpStream(stream), Some(guid)) => {
+                    Authenticated::tcp_server(stream, guid, mechanisms, compression).await?
+                }
+                (_, Some(_)) => return Err(Error::Unsupported),
+                (Target::UnixStream(stream), None) => {
+                    let socket = Async::new(Box::new(stream) as Box<dyn Socket>)?;
+                    Authenticated::client_with_mechanisms(socket, mechanisms, compression).await?
+                }
+                (Target::TcpStream(stream), None) => {
+                    let socket = Async::new(Box::new(stream) as Box<dyn Socket>)?;
+                    Authenticated::client_with_mechanisms(socket, mechanisms, compression).await?
+                }
+                (Target::Address(address), None) => {
+                    let addresses = AddressList::from_str(&address)?;
+                    Authenticated::for_address_list(addresses, tcp, mechanisms, compression).await?
+                }
+                (Target::Session, None) => {
+                    let addresses = AddressList::session()?;
+                    Authenticated::for_address_list(addresses, tcp, mechanisms, compression).await?
+                }
+                (Target::System, None) => {
+                    let addresses = AddressList::system()?;
+                    Authenticated::for_address_list(addresses, tcp, mechanisms, compression).await?
+                }
+                (Target::Starter, None) => {
+                    let addresses = AddressList::starter()?;
+                    Authenticated::for_address_list(addresses, tcp, mechanisms, compression).await?
+                }
+            })
+        };
+        #[cfg(any(test, feature = "test-util"))]
+        let auth = test_util::with_handshake_rng(
+            self.hooks
+                .handshake_rng_seed
+                .map(test_util::HandshakeRng::new),
+            auth,
+        );
+        #[cfg(any(test, feature = "test-util"))]
+        let mut auth = {
+            let mut auth = auth.await?;
+            if let Some(guid) = self.hooks.server_guid {
+                auth.set_server_guid(guid);
+            }
+
+            auth
+        };
+        #[cfg(not(any(test, feature = "test-util")))]
+        let mut auth = auth.await?;
+        auth.conn.set_value_budget(self.value_budget);
+
+        // `Hello` is said once the serial hooks are in place, so it's numbered by them as well.
+        let conn = azync::Connection::new(auth, !self.p2p, true, self.endian_sig).await?;
+        #[cfg(any(test, feature = "test-util"))]
+        {
+            if let Some(serial) = self.hooks.initial_serial {
+                conn.set_next_serial(serial);
+            }
+            if let Some(allocator) = self.hooks.serial_allocator {
+                conn.set_serial_allocator(allocator);
+            }
+        }
+        if conn.is_bus() && !self.delay_hello {
+            conn.hello().await?;
+        }
+        if self.peer_stats {
+            conn.enable_peer_stats().await?;
+        }
+        if let Some((interval, timeout)) = self.liveness_ping {
+            conn.start_liveness_ping(interval, timeout);
+        }
+        if conn.is_bus() {
+            conn.set_registered_names(self.names);
+            if self.request_name_on_build && conn.unique_name().is_some() {
+                conn.request_registered_names().await?;
+            }
+        }
+
+        Ok(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::TryFrom,
+        env, fs,
+        io::{Read, Write},
+        net::Shutdown,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+        thread::{self, JoinHandle},
+    };
+
+    use ntest::timeout;
+    use test_env_log::test;
+
+    use super::*;
+    use crate::{utils::random, AuthResponse, MessageError};
+
+    const MECHANISM: &str = "X_GOLDEN";
+
+    // Answers the nonce of the server, salted.
+    #[derive(Debug)]
+    struct GoldenClient;
+
+    impl AuthMechanism for GoldenClient {
+        fn name(&self) -> &str {
+            MECHANISM
+        }
+
+        fn challenge(&mut self, data: Option<&[u8]>) -> AuthResponse {
+            match data {
+                None => AuthResponse::Ok,
+                Some(nonce) => {
+                    let mut answer = nonce.to_vec();
+                    answer.extend_from_slice(&random::<[u8; 4]>());
+
+                    AuthResponse::Data(answer)
+                }
+            }
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct GoldenServer(Option<[u8; 8]>);
+
+    impl AuthMechanism for GoldenServer {
+        fn name(&self) -> &str {
+            MECHANISM
+        }
+
+        fn challenge(&mut self, data: Option<&[u8]>) -> AuthResponse {
+            match (data, self.0.take()) {
+                (None, _) => {
+                    let nonce = random::<[u8; 8]>();
+                    self.0 = Some(nonce);
+
+                    AuthResponse::Data(nonce.to_vec())
+                }
+                (Some(answer), Some(nonce)) if answer.starts_with(&nonce) => AuthResponse::Ok,
+                _ => AuthResponse::Reject,
+            }
+        }
+    }
+
+    // Copy the bytes of `from` to `to` until `from` is shut down, recording them into `log`.
+    fn relay(mut from: UnixStream, mut to: UnixStream, log: Arc<Mutex<Vec<u8>>>) -> JoinHandle<()> {
+        thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            loop {
+                let len = match from.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(len) => len,
+                };
+                log.lock().unwrap().extend_from_slice(&buffer[..len]);
+                if to.write_all(&buffer[..len]).is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
+    // Make a method call to a peer, through a relay, returning the bytes written by each side.
+    fn record_call() -> (Vec<u8>, Vec<u8>) {
+        let (client_socket, client_relay) = UnixStream::pair().unwrap();
+        let (server_socket, server_relay) = UnixStream::pair().unwrap();
+        let from_client = Arc::new(Mutex::new(vec![]));
+        let from_server = Arc::new(Mutex::new(vec![]));
+        let relays = vec![
+            relay(
+                client_relay.try_clone().unwrap(),
+                server_relay.try_clone().unwrap(),
+                from_client.clone(),
+            ),
+            relay(
+                server_relay.try_clone().unwrap(),
+                client_relay.try_clone().unwrap(),
+                from_server.clone(),
+            ),
+        ];
+
+        let server = thread::spawn(move || {
+            let guid = Guid::try_from("0123456789abcdef0123456789abcdef").unwrap();
+            let conn = ConnectionBuilder::unix_stream(server_socket)
+                .server(&guid)
+                .auth_mechanism(GoldenServer::default())
+                .endian_sig(EndianSig::Little)
+                .initial_serial(1000)
+                .handshake_rng_seed(1)
+                .build()?;
+            let call = conn.receive_message()?;
+            let text: String = call.body()?;
+            conn.reply(&call, &text)?;
+
+            Ok::<_, Error>(conn)
+        });
+
+        let reported = Guid::try_from("fedcba9876543210fedcba9876543210").unwrap();
+        let mut serial = 0;
+        let client = ConnectionBuilder::unix_stream(client_socket)
+            .p2p()
+            .auth_mechanism(GoldenClient)
+            .endian_sig(EndianSig::Little)
+            .serial_allocator(move || {
+                serial += 2;
+                serial
+            })
+            .handshake_rng_seed(2)
+            .reported_server_guid(&reported)
+            .build()
+            .unwrap();
+        assert_eq!(client.server_guid(), reported.as_str());
+        let reply = client
+            .call_method(
+                None,
+                "/org/zbus/Golden",
+                Some("org.zbus.Golden"),
+                "Echo",
+                &"golden",
+            )
+            .unwrap();
+        assert_eq!(reply.body::<String>().unwrap(), "golden");
+        let _server = server.join().unwrap().unwrap();
+
+        client_relay.shutdown(Shutdown::Both).unwrap();
+        server_relay.shutdown(Shutdown::Both).unwrap();
+        for relay in relays {
+            relay.join().unwrap();
+        }
+
+        let from_client = from_client.lock().unwrap().clone();
+        let from_server = from_server.lock().unwrap().clone();
+
+        (from_client, from_server)
+    }
+
+    // Echo `texts` through a relay between peers compressing the bodies as per their thresholds,
+    // returning the number of bytes written by the client.
+    #[cfg(feature = "compression")]
+    fn echo_compressed(
+        client_threshold: Option<usize>,
+        server_threshold: Option<usize>,
+        texts: &[String],
+    ) -> usize {
+        let (client_socket, client_relay) = UnixStream::pair().unwrap();
+        let (server_socket, server_relay) = UnixStream::pair().unwrap();
+        let from_client = Arc::new(Mutex::new(vec![]));
+        let relays = vec![
+            relay(
+                client_relay.try_clone().unwrap(),
+                server_relay.try_clone().unwrap(),
+                from_client.clone(),
+            ),
+            relay(
+                server_relay.try_clone().unwrap(),
+                client_relay.try_clone().unwrap(),
+                Arc::new(Mutex::new(vec![])),
+            ),
+        ];
+
+        let count = texts.len();
+        let server = thread::spawn(move || {
+            let mut builder =
+                ConnectionBuilder::unix_stream(server_socket).server(&Guid::generate());
+            if let Some(threshold) = server_threshold {
+                builder = builder.compress_bodies(threshold);
+            }
+            let conn = builder.build()?;
+            for _ in 0..count {
+                let call = conn.receive_message()?;
+                let text: String = call.body()?;
+                conn.reply(&call, &text)?;
+            }
+
+            Ok::<_, Error>(conn)
+        });
+
+        let mut builder = ConnectionBuilder::unix_stream(client_socket).p2p();
+        if let Some(threshold) = client_threshold {
+            builder = builder.compress_bodies(threshold);
+        }
+        let client = builder.build().unwrap();
+        for text in texts {
+            let reply = client
+                .call_method(None, "/org/zbus/Echo", Some("org.zbus.Echo"), "Echo", text)
+                .unwrap();
+            assert_eq!(&reply.body::<String>().unwrap(), text);
+        }
+        let _server = server.join().unwrap().unwrap();
+
+        client_relay.shutdown(Shutdown::Both).unwrap();
+        server_relay.shutdown(Shutdown::Both).unwrap();
+        for relay in relays {
+            relay.join().unwrap();
+        }
+
+        let from_client = Arc::try_unwrap(from_client).unwrap();
+
+        from_client.into_inner().unwrap().len()
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    #[timeout(15000)]
+    fn compressed_p2p_calls() {
+        // Small and big messages, around the threshold: a string body is its length, the string
+        // and a nul byte, so the first of these is 1024 bytes long.
+        let big = "telemetry ".repeat(100_000);
+        let texts: Vec<String> = vec![
+            "small".into(),
+            "x".repeat(1019),
+            big.clone(),
+            "x".repeat(1020),
+            "small".into(),
+            big.clone(),
+        ];
+
+        let compressed = echo_compressed(Some(1024), Some(1024), &texts);
+        assert!(compressed < big.len() / 10);
+
+        // The peer doesn't want it, or it isn't asked for: the messages go as they are.
+        for &(client, server) in &[(Some(1024), None), (None, Some(1024))] {
+            assert!(echo_compressed(client, server, &texts) > 2 * big.len());
+        }
+
+        // Either side compresses by its own threshold: the replies of the server are compressed,
+        // but the calls are not.
+        assert!(echo_compressed(Some(usize::MAX), Some(0), &texts) > 2 * big.len());
+
+        // Building a connection to a bus with it is an error.
+        let (socket, _) = UnixStream::pair().unwrap();
+        match ConnectionBuilder::unix_stream(socket)
+            .compress_bodies(1024)
+            .build()
+        {
+            Err(Error::Unsupported) => (),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("compression enabled on a bus connection"),
+        }
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn golden_p2p_call() {
+        let (from_client, from_server) = record_call();
+        assert!(record_call() == (from_client.clone(), from_server.clone()));
+
+        // Set `ZBUS_BLESS_GOLDEN` to update the files, after a deliberate change of the bytes.
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/golden");
+        for (name, bytes) in &[
+            ("p2p-call.client", from_client),
+            ("p2p-call.server", from_server),
+        ] {
+            let path = dir.join(name);
+            if env::var_os("ZBUS_BLESS_GOLDEN").is_some() {
+                fs::write(&path, bytes).unwrap();
+            } else {
+                let golden = fs::read(&path).unwrap();
+                assert!(golden == *bytes, "{} doesn't match", path.display());
+            }
+        }
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn value_budget() {
+        let (client_socket, server_socket) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            let conn = ConnectionBuilder::unix_stream(server_socket)
+                .server(&Guid::generate())
+                .value_budget(8)
+                .build()?;
+            let small = conn.receive_message()?;
+            let big = conn.receive_message()?;
+
+            Ok::<_, Error>((small, big))
+        });
+
+        let client = ConnectionBuilder::unix_stream(client_socket)
+            .p2p()
+            .build()
+            .unwrap();
+        // 2 arrays of 2 bytes, for 6 values, then 3 of them, for 9.
+        for n in 2..=3 {
+            let body = vec![vec![0u8; 2]; n];
+            client
+                .emit_signal(None, "/org/zbus/Budget", "org.zbus.Budget", "Spent", &body)
+                .unwrap();
+        }
+        let (small, big) = server.join().unwrap().unwrap();
+
+        assert_eq!(small.value_budget(), Some(8));
+        assert_eq!(small.body::<Vec<Vec<u8>>>().unwrap().len(), 2);
+        assert_eq!(big.value_budget(), Some(8));
+        assert_eq!(
+            big.body::<Vec<Vec<u8>>>().unwrap_err(),
+            MessageError::Variant(zvariant::Error::BudgetExceeded(8))
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use async_lock::Mutex;
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    sync::{
+        self,
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use static_assertions::assert_impl_all;
+use zvariant::OwnedValue;
+
+use crate::{peer_stats::vanished_name, Message, MessageType, Result};
+
+const LOCK_PANIC_MSG: &str = "lock poisoned";
+
+/// The credentials of a peer on the bus, as given by the bus' `GetConnectionCredentials` method.
+///
+/// See [`Connection::credentials_of`] for how to get these. Each credential is `None` if the bus
+/// doesn't know it, or doesn't support it.
+///
+/// [`Connection::credentials_of`]: struct.Connection.html#method.credentials_of
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConnectionCredentials {
+    /// The numeric Unix user ID of the peer.
+    pub unix_user_id: Option<u32>,
+    /// The numeric Unix group IDs of the peer, including its primary group.
+    pub unix_group_ids: Option<Vec<u32>>,
+    /// The numeric ID of the peer's process.
+    pub process_id: Option<u32>,
+    /// The Windows security identifier of the peer, in its string form.
+    pub windows_sid: Option<String>,
+    /// The security label of the peer, e.g. its SELinux context, without the trailing nul byte.
+    pub linux_security_label: Option<Vec<u8>>,
+}
+
+assert_impl_all!(ConnectionCredentials: Send, Sync, Unpin);
+
+impl ConnectionCredentials {
+    /// The credentials in the dictionary returned by `GetConnectionCredentials`.
+    ///
+    /// The keys this doesn't know about are ignored.
+    pub(crate) fn from_dict(dict: HashMap<String, OwnedValue>) -> Result<Self> {
+        let mut creds = Self::default();
+        for (key, value) in dict {
+            match key.as_str() {
+                "UnixUserID" => creds.unix_user_id = Some(u32::try_from(value)?),
+                "UnixGroupIDs" => creds.unix_group_ids = Some(Vec::try_from(value)?),
+                "ProcessID" => creds.process_id = Some(u32::try_from(value)?),
+                "WindowsSID" => creds.windows_sid = Some(String::try_from(value)?),
+                "LinuxSecurityLabel" => {
+                    let mut label = Vec::<u8>::try_from(value)?;
+                    if label.last() == Some(&0) {
+                        label.pop();
+                    }
+                    creds.linux_security_label = Some(label);
+                }
+                _ => (),
+            }
+        }
+
+        Ok(creds)
+    }
+}
+
+// The credentials of a peer, once looked up. Locked for the whole lookup, so concurrent lookups
+// of the same peer wait for the first one instead of asking the bus again.
+pub(crate) type CredentialsSlot = Arc<Mutex<Option<ConnectionCredentials>>>;
+
+/// The credentials of the peers of a connection, keyed by unique name.
+///
+/// Unique names are never reused, so the credentials behind a name can't change. They're only
+/// evicted when the name goes away, which the bus tells through `NameOwnerChanged`.
+#[derive(Debug, Default)]
+pub(crate) struct CredentialsCache {
+    // Set once the connection asked for the signals of the names going away.
+    watching: AtomicBool,
+    peers: sync::Mutex<HashMap<String, CredentialsSlot>>,
+}
+
+impl CredentialsCache {
+    /// Whether the signals of the names going away still need to be asked for.
+    ///
+    /// Only returns `true` once, unless `unwatch` is called.
+    pub(crate) fn start_watching(&self) -> bool {
+        !self.watching.swap(true, SeqCst)
+    }
+
+    /// Asking for the signals of the names going away failed.
+    pub(crate) fn unwatch(&self) {
+        self.watching.store(false, SeqCst);
+    }
+
+    /// The slot of the unique name `name`, added if missing.
+    pub(crate) fn slot(&self, name: &str) -> CredentialsSlot {
+        self.peers
+            .lock()
+            .expect(LOCK_PANIC_MSG)
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Remove `slot` of `name`, e.g. because the lookup failed.
+    ///
+    /// This leaves a newer slot of `name` alone.
+    pub(crate) fn remove(&self, name: &str, slot: &CredentialsSlot) {
+        let mut peers = self.peers.lock().expect(LOCK_PANIC_MSG);
+        if peers.get(name).map_or(false, |s| Arc::ptr_eq(s, slot)) {
+            peers.remove(name);
+        }
+    }
+
+    /// Evict the credentials of the name going away, if `msg` tells so.
+    pub(crate) fn received(&self, msg: &Message) {
+        if !self.watching.load(SeqCst) {
+            return;
+        }
+        let header = match msg.header() {
+            Ok(header) => header,
+            Err(_) => return,
+        };
+        if !matches!(header.message_type(), Ok(MessageType::Signal)) {
+            return;
+        }
+        if let Some(name) = vanished_name(msg, &header) {
+            self.peers.lock().expect(LOCK_PANIC_MSG).remove(&name);
+        }
+    }
+}
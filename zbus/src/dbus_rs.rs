@@ -0,0 +1,402 @@
+#![cfg(feature = "dbus")]
+
+// Conversions between zbus and dbus-rs messages, for programs using both libraries.
+//
+// The messages go through the wire format: one library marshals them, and the other one parses the
+// result, so the bodies are converted as is, whatever their signature. Only the file descriptors
+// take another route, as libdbus doesn't marshal them: they're read from or appended to the body
+// of the dbus-rs message.
+
+use std::{
+    convert::TryFrom,
+    os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+};
+
+use dbus::arg::messageitem::{MessageItem, MessageItemArray, MessageItemDict};
+use zvariant::{Signature, Value, Walker};
+
+use crate::{Error, Message, MessageError, OwnedFd, Result};
+
+/// Convert a dbus-rs message to a zbus one (`dbus` feature).
+///
+/// The message is marshalled by libdbus and parsed by zbus. The file descriptors of the message
+/// are duplicated, so the dbus-rs message still owns its own.
+impl TryFrom<&dbus::Message> for Message {
+    type Error = Error;
+
+    fn try_from(msg: &dbus::Message) -> Result<Self> {
+        let bytes = msg.marshal().map_err(|e| Error::DBusRs(e.to_string()))?;
+        // libdbus hands out duplicates of the file descriptors as the body is read, in the order
+        // of the arguments.
+        let mut fds = vec![];
+        for item in msg.get_items() {
+            collect_fds(item, &mut fds);
+        }
+        if fds.is_empty() {
+            return Message::from_raw_parts(bytes, vec![]).map_err(Into::into);
+        }
+
+        // Put each one at the index the body refers to it with.
+        let parsed = Message::from_bytes(&bytes)?;
+        let n_fds = parsed.header()?.unix_fds()?.unwrap_or(0) as usize;
+        let mut indices = FdIndices(vec![]);
+        let identity: Vec<RawFd> = (0..n_fds as RawFd).collect();
+        parsed.walk_body_with_fds(&identity, &mut indices)?;
+        let mut slots: Vec<Option<OwnedFd>> = (0..n_fds).map(|_| None).collect();
+        for (index, fd) in indices.0.into_iter().zip(fds) {
+            let slot = &mut slots[index as usize];
+            if slot.is_none() {
+                *slot = Some(fd);
+            }
+        }
+        let fds = slots
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or(MessageError::UnmatchedFdCount)?;
+
+        Message::from_raw_parts(bytes, fds).map_err(Into::into)
+    }
+}
+
+/// Convert a zbus message to a dbus-rs one (`dbus` feature).
+///
+/// The message is parsed by libdbus from its zbus encoding. Since libdbus can't parse messages
+/// carrying file descriptors, the arguments of those are appended one by one to the parsed header
+/// instead, with duplicates of the file descriptors.
+impl TryFrom<&Message> for dbus::Message {
+    type Error = Error;
+
+    fn try_from(msg: &Message) -> Result<Self> {
+        if msg.header()?.unix_fds()?.unwrap_or(0) == 0 {
+            return dbus::Message::demarshal(msg.as_bytes())
+                .map_err(|e| Error::DBusRs(e.to_string()));
+        }
+
+        let mut items = Items::default();
+        msg.walk_body(&mut items)?;
+        let mut dbus_msg = dbus::Message::demarshal(msg.without_body()?.as_bytes())
+            .map_err(|e| Error::DBusRs(e.to_string()))?;
+        dbus_msg.append_items(&items.body);
+
+        Ok(dbus_msg)
+    }
+}
+
+// Move the file descriptors of `item` to `fds`, in the order of the arguments.
+fn collect_fds(item: MessageItem, fds: &mut Vec<OwnedFd>) {
+    match item {
+        // SAFETY: The descriptor is owned by the item, and handed over.
+        MessageItem::UnixFd(fd) => fds.push(unsafe { OwnedFd::from_raw_fd(fd.into_raw_fd()) }),
+        MessageItem::Variant(item) => collect_fds(*item, fds),
+        MessageItem::Struct(items) => {
+            for item in items {
+                collect_fds(item, fds);
+            }
+        }
+        MessageItem::Array(array) => {
+            for item in array.into_vec() {
+                collect_fds(item, fds);
+            }
+        }
+        MessageItem::Dict(dict) => {
+            for (key, value) in dict.into_vec() {
+                collect_fds(key, fds);
+                collect_fds(value, fds);
+            }
+        }
+        _ => (),
+    }
+}
+
+// The indices of the file descriptors of a body, in the order of the arguments. The body is walked
+// with each index resolving to itself.
+struct FdIndices(Vec<u32>);
+
+impl<'de> Walker<'de> for FdIndices {
+    fn basic(&mut self, value: Value<'de>) -> zvariant::Result<()> {
+        if let Value::Fd(fd) = value {
+            self.0.push(fd.as_raw_fd() as u32);
+        }
+
+        Ok(())
+    }
+}
+
+// The dbus-rs items of the arguments of a body, built as it's walked.
+#[derive(Default)]
+struct Items {
+    body: Vec<MessageItem>,
+    // The containers being built, innermost last. The body is walked as a structure, so it's the
+    // outermost one.
+    containers: Vec<Container>,
+}
+
+enum Container {
+    Array {
+        signature: String,
+        items: Vec<MessageItem>,
+    },
+    Dict {
+        key_signature: String,
+        value_signature: String,
+        entries: Vec<(MessageItem, MessageItem)>,
+        key: Option<MessageItem>,
+    },
+    Struct(Vec<MessageItem>),
+    Variant(Option<MessageItem>),
+}
+
+impl Items {
+    fn push(&mut self, item: MessageItem) -> zvariant::Result<()> {
+        match self.containers.last_mut() {
+            Some(Container::Array { items, .. }) | Some(Container::Struct(items)) => {
+                items.push(item)
+            }
+            Some(Container::Dict { entries, key, .. }) => match key.take() {
+                Some(key) => entries.push((key, item)),
+                None => *key = Some(item),
+            },
+            Some(Container::Variant(value)) => *value = Some(item),
+            None => return Err(unexpected("value outside of the body")),
+        }
+
+        Ok(())
+    }
+
+    fn pop(&mut self) -> zvariant::Result<Container> {
+        self.containers
+            .pop()
+            .ok_or_else(|| unexpected("end of a container"))
+    }
+}
+
+impl<'de> Walker<'de> for Items {
+    fn basic(&mut self, value: Value<'de>) -> zvariant::Result<()> {
+        let item = match value {
+            Value::U8(v) => MessageItem::Byte(v),
+            Value::Bool(v) => MessageItem::Bool(v),
+            Value::I16(v) => MessageItem::Int16(v),
+            Value::U16(v) => MessageItem::UInt16(v),
+            Value::I32(v) => MessageItem::Int32(v),
+            Value::U32(v) => MessageItem::UInt32(v),
+            Value::I64(v) => MessageItem::Int64(v),
+            Value::U64(v) => MessageItem::UInt64(v),
+            Value::F64(v) => MessageItem::Double(v),
+            Value::Str(v) => MessageItem::Str(v.as_str().to_owned()),
+            Value::Signature(v) => MessageItem::Signature(dbus_signature(v.as_str())?),
+            Value::ObjectPath(v) => MessageItem::ObjectPath(
+                dbus::Path::new(v.as_str()).map_err(zvariant::Error::Message)?,
+            ),
+            Value::Fd(fd) => {
+                let fd = nix::unistd::dup(fd.as_raw_fd())
+                    .map_err(|e| zvariant::Error::Message(e.to_string()))?;
+                // SAFETY: The duplicate is ours, and handed over to the item.
+                MessageItem::UnixFd(unsafe { dbus::arg::OwnedFd::from_raw_fd(fd) })
+            }
+            _ => return Err(unexpected("container as a basic value")),
+        };
+
+        self.push(item)
+    }
+
+    fn array_start(&mut self, element_signature: &Signature<'_>) -> zvariant::Result<()> {
+        self.containers.push(Container::Array {
+            signature: format!("a{}", element_signature),
+            items: vec![],
+        });
+
+        Ok(())
+    }
+
+    fn array_end(&mut self) -> zvariant::Result<()> {
+        match self.pop()? {
+            Container::Array { signature, items } => {
+                let array = MessageItemArray::new(items, dbus_signature(&signature)?)
+                    .map_err(|e| zvariant::Error::Message(format!("{:?}", e)))?;
+
+                self.push(MessageItem::Array(array))
+            }
+            _ => Err(unexpected("end of an array")),
+        }
+    }
+
+    fn dict_start(
+        &mut self,
+        key_signature: &Signature<'_>,
+        value_signature: &Signature<'_>,
+    ) -> zvariant::Result<()> {
+        self.containers.push(Container::Dict {
+            key_signature: key_signature.to_string(),
+            value_signature: value_signature.to_string(),
+            entries: vec![],
+            key: None,
+        });
+
+        Ok(())
+    }
+
+    fn dict_end(&mut self) -> zvariant::Result<()> {
+        match self.pop()? {
+            Container::Dict {
+                key_signature,
+                value_signature,
+                entries,
+                key: None,
+            } => {
+                let dict = MessageItemDict::new(
+                    entries,
+                    dbus_signature(&key_signature)?,
+                    dbus_signature(&value_signature)?,
+                )
+                .map_err(|e| zvariant::Error::Message(format!("{:?}", e)))?;
+
+                self.push(MessageItem::Dict(dict))
+            }
+            _ => Err(unexpected("end of a dictionary")),
+        }
+    }
+
+    fn struct_start(&mut self, _signature: &Signature<'_>) -> zvariant::Result<()> {
+        self.containers.push(Container::Struct(vec![]));
+
+        Ok(())
+    }
+
+    fn struct_end(&mut self) -> zvariant::Result<()> {
+        let items = match self.pop()? {
+            Container::Struct(items) => items,
+            _ => return Err(unexpected("end of a structure")),
+        };
+        if self.containers.is_empty() {
+            // The arguments of the body.
+            self.body = items;
+
+            Ok(())
+        } else {
+            self.push(MessageItem::Struct(items))
+        }
+    }
+
+    fn variant_start(&mut self, _signature: &Signature<'_>) -> zvariant::Result<()> {
+        self.containers.push(Container::Variant(None));
+
+        Ok(())
+    }
+
+    fn variant_end(&mut self) -> zvariant::Result<()> {
+        match self.pop()? {
+            Container::Variant(Some(item)) => self.push(MessageItem::Variant(Box::new(item))),
+            _ => Err(unexpected("end of a variant")),
+        }
+    }
+}
+
+fn dbus_signature(signature: &str) -> zvariant::Result<dbus::Signature<'static>> {
+    dbus::Signature::new(signature).map_err(zvariant::Error::Message)
+}
+
+fn unexpected(what: &str) -> zvariant::Error {
+    zvariant::Error::Message(format!("Unexpected {} while walking the body", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::TryFrom,
+        fs::File,
+        os::unix::io::{AsRawFd, RawFd},
+    };
+
+    use nix::sys::stat::fstat;
+    use zvariant::Fd;
+
+    use crate::{Message, MessageType};
+
+    // Whether `a` and `b` refer to the same file.
+    fn same_file(a: RawFd, b: RawFd) -> bool {
+        let (a, b) = (fstat(a).unwrap(), fstat(b).unwrap());
+
+        (a.st_dev, a.st_ino) == (b.st_dev, b.st_ino)
+    }
+
+    #[test]
+    fn method_call() {
+        let call = dbus::Message::new_method_call(
+            "org.zbus.Peer",
+            "/org/zbus/Peer",
+            "org.zbus.Peer",
+            "Greet",
+        )
+        .unwrap()
+        .append2("world", vec![1u32, 2]);
+
+        let msg = Message::try_from(&call).unwrap();
+        assert_eq!(msg.primary_header().msg_type(), MessageType::MethodCall);
+        let header = msg.header().unwrap();
+        assert_eq!(header.destination().unwrap(), Some("org.zbus.Peer"));
+        assert_eq!(header.path().unwrap().unwrap().as_str(), "/org/zbus/Peer");
+        assert_eq!(header.member().unwrap(), Some("Greet"));
+        let (name, numbers): (String, Vec<u32>) = msg.body().unwrap();
+        assert_eq!((name.as_str(), numbers), ("world", vec![1, 2]));
+
+        let back = dbus::Message::try_from(&msg).unwrap();
+        assert_eq!(back.get_items(), call.get_items());
+        assert_eq!(&*back.member().unwrap(), "Greet");
+    }
+
+    #[test]
+    fn signal() {
+        let signal =
+            Message::signal(None, None, "/", "org.zbus.Peer", "Ping", &(42u8, "pong")).unwrap();
+
+        let dbus_signal = dbus::Message::try_from(&signal).unwrap();
+        assert_eq!(dbus_signal.msg_type(), dbus::MessageType::Signal);
+        assert_eq!(&*dbus_signal.interface().unwrap(), "org.zbus.Peer");
+        assert_eq!(dbus_signal.read2::<u8, &str>().unwrap(), (42, "pong"));
+
+        let back = Message::try_from(&dbus_signal).unwrap();
+        assert_eq!(back.to_string(), signal.to_string());
+        assert_eq!(back.body::<(u8, String)>().unwrap(), (42, "pong".into()));
+    }
+
+    #[test]
+    fn error() {
+        let call = Message::method(None, None, "/", None, "Fail", &()).unwrap();
+        let error = Message::method_error(None, &call, "org.zbus.Error", &"kaboom!").unwrap();
+
+        let dbus_error = dbus::Message::try_from(&error).unwrap();
+        assert_eq!(dbus_error.msg_type(), dbus::MessageType::Error);
+        assert_eq!(
+            dbus_error.get_reply_serial(),
+            call.primary_header().serial_num().cloned()
+        );
+        assert_eq!(dbus_error.read1::<&str>().unwrap(), "kaboom!");
+
+        let back = Message::try_from(&dbus_error).unwrap();
+        assert_eq!(back.to_string(), "Error org.zbus.Error: kaboom!");
+    }
+
+    #[test]
+    fn fds() {
+        let file = File::open("/dev/null").unwrap();
+        let body = (Fd::from(file.as_raw_fd()), "null", vec![Fd::from(0)]);
+        let msg = Message::method(None, None, "/", None, "Pass", &body).unwrap();
+
+        // Duplicates of the file descriptors go along.
+        let dbus_msg = dbus::Message::try_from(&msg).unwrap();
+        let (fd, name, fds): (dbus::arg::OwnedFd, &str, Vec<dbus::arg::OwnedFd>) =
+            dbus_msg.read3().unwrap();
+        assert_ne!(fd.as_raw_fd(), file.as_raw_fd());
+        assert!(same_file(fd.as_raw_fd(), file.as_raw_fd()));
+        assert_eq!(name, "null");
+        assert!(same_file(fds[0].as_raw_fd(), 0));
+
+        // And back, at the indices the body refers to them with.
+        let back = Message::try_from(&dbus_msg).unwrap();
+        assert_eq!(back.counted_fds(), 2);
+        let (fd, name, fds): (Fd, String, Vec<Fd>) = back.body().unwrap();
+        assert!(same_file(fd.as_raw_fd(), file.as_raw_fd()));
+        assert_eq!(name, "null");
+        assert!(same_file(fds[0].as_raw_fd(), 0));
+    }
+}
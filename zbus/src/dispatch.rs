@@ -0,0 +1,220 @@
+//! Support for the method dispatching of the `dbus_interface` macro.
+//!
+//! Rather than a `match` with all the code of each method inlined, the macro generates a table of
+//! small functions per interface, sorted by member name. All that is common to the methods (the
+//! lookup, the reading of the header and body, and sending of the reply or error) is done here,
+//! once for all the interfaces.
+//!
+//! The same tables serve the interfaces of a [`LocalObjectServer`], which can't reply on the spot:
+//! their replies are kept for the server to send.
+//!
+//! [`LocalObjectServer`]: ../azync/struct.LocalObjectServer.html
+
+use serde::{de::Deserialize, ser::Serialize};
+#[cfg(feature = "local")]
+use std::cell::RefCell;
+use zvariant::Type;
+
+#[cfg(feature = "local")]
+use crate::{azync::LocalReply, Interface};
+use crate::{
+    fdo, Connection, ConnectionCredentials, Error, Message, MessageHeader, Responder, Result,
+};
+
+/// A method in the dispatch table of an interface.
+#[derive(Debug)]
+pub struct Method<F> {
+    /// The member name of the method.
+    pub name: &'static str,
+    /// The function calling the method for a given call.
+    pub call: F,
+}
+
+/// A `&self` method.
+pub type MethodRef<T> = Method<fn(&T, &MethodCall<'_>) -> Result<u32>>;
+
+/// A `&mut self` method.
+pub type MethodMut<T> = Method<fn(&mut T, &MethodCall<'_>) -> Result<u32>>;
+
+/// The dispatch tables of an interface, implemented by the `dbus_interface` macro.
+///
+/// Both tables must be sorted by member name.
+pub trait MethodTable: Sized + 'static {
+    /// The `&self` methods.
+    const METHODS: &'static [MethodRef<Self>];
+
+    /// The `&mut self` methods.
+    const METHODS_MUT: &'static [MethodMut<Self>];
+}
+
+/// A method call being dispatched.
+#[derive(Debug)]
+pub struct MethodCall<'c> {
+    replier: Replier<'c>,
+    message: &'c Message,
+}
+
+// Where the reply to a call goes.
+#[derive(Debug)]
+enum Replier<'c> {
+    // Sent right away.
+    Connection(&'c Connection),
+    // Kept for the `LocalObjectServer` to send.
+    #[cfg(feature = "local")]
+    Local(&'c LocalReply),
+}
+
+impl<'c> MethodCall<'c> {
+    /// The header of the call.
+    pub fn header(&self) -> fdo::Result<MessageHeader<'c>> {
+        self.message.header().map_err(Into::into)
+    }
+
+    /// The arguments of the call, checked against their signature.
+    pub fn body<B>(&self) -> fdo::Result<B>
+    where
+        B: Deserialize<'c> + Type,
+    {
+        self.message.body().map_err(Into::into)
+    }
+
+    /// The credentials of the caller, through the cache of [`Connection::credentials_of`].
+    ///
+    /// Not supported by a [`LocalObjectServer`].
+    ///
+    /// [`Connection::credentials_of`]: ../struct.Connection.html#method.credentials_of
+    /// [`LocalObjectServer`]: ../azync/struct.LocalObjectServer.html
+    pub fn caller_credentials(&self) -> fdo::Result<ConnectionCredentials> {
+        let connection = self.connection("the credentials of callers")?;
+        let header = self.message.header()?;
+        let sender = header.sender()?.ok_or_else(|| {
+            fdo::Error::Failed("the credentials of callers are only known on a bus".into())
+        })?;
+
+        connection.credentials_of(sender).map_err(|e| match e {
+            Error::FDO(e) => *e,
+            e => fdo::Error::ZBus(e),
+        })
+    }
+
+    /// Call the method through `f`, and reply with its outcome.
+    pub fn reply_with<F, R>(&self, f: F) -> Result<u32>
+    where
+        F: FnOnce() -> fdo::Result<R>,
+        R: Serialize + Type,
+    {
+        match (f(), &self.replier) {
+            (Ok(r), Replier::Connection(connection)) => connection.reply(self.message, &r),
+            #[cfg(feature = "local")]
+            (Ok(r), Replier::Local(reply)) => reply.reply(self.message, &r),
+            (Err(e), _) => self.reply_error(e),
+        }
+    }
+
+    /// The responder of a `deferred` method, to reply to the call later.
+    ///
+    /// Not supported by a [`LocalObjectServer`].
+    ///
+    /// [`LocalObjectServer`]: ../azync/struct.LocalObjectServer.html
+    pub fn responder<T>(&self) -> fdo::Result<Responder<T>> {
+        let connection = self.connection("deferred methods")?;
+
+        Ok(Responder::new(connection.clone(), self.message.clone()))
+    }
+
+    /// Call the `deferred` method through `f`, only replying if it fails.
+    ///
+    /// Otherwise, the reply is up to the responder, so this returns a serial number of 0.
+    pub fn reply_deferred<F>(&self, f: F) -> Result<u32>
+    where
+        F: FnOnce() -> fdo::Result<()>,
+    {
+        match f() {
+            Ok(()) => Ok(0),
+            Err(e) => self.reply_error(e),
+        }
+    }
+
+    fn reply_error(&self, e: fdo::Error) -> Result<u32> {
+        match &self.replier {
+            Replier::Connection(connection) => e.reply(connection, self.message),
+            #[cfg(feature = "local")]
+            Replier::Local(reply) => reply.reply_error(self.message, e),
+        }
+    }
+
+    // The connection of the call, for `what` that needs one.
+    #[cfg_attr(not(feature = "local"), allow(unused_variables))]
+    fn connection(&self, what: &str) -> fdo::Result<&'c Connection> {
+        match self.replier {
+            Replier::Connection(connection) => Ok(connection),
+            #[cfg(feature = "local")]
+            Replier::Local(_) => Err(fdo::Error::NotSupported(format!(
+                "{} are not supported by LocalObjectServer",
+                what
+            ))),
+        }
+    }
+}
+
+fn lookup<'t, F>(methods: &'t [Method<F>], name: &str) -> Option<&'t F> {
+    methods
+        .binary_search_by(|m| m.name.cmp(name))
+        .ok()
+        .map(|i| &methods[i].call)
+}
+
+/// Call the `&self` method `name` of `iface`, if it has one.
+pub fn dispatch_call<T: MethodTable>(
+    iface: &T,
+    connection: &Connection,
+    message: &Message,
+    name: &str,
+) -> Option<Result<u32>> {
+    let call = MethodCall {
+        replier: Replier::Connection(connection),
+        message,
+    };
+
+    lookup(T::METHODS, name).map(|f| f(iface, &call))
+}
+
+/// Call the `&mut self` method `name` of `iface`, if it has one.
+pub fn dispatch_call_mut<T: MethodTable>(
+    iface: &mut T,
+    connection: &Connection,
+    message: &Message,
+    name: &str,
+) -> Option<Result<u32>> {
+    let call = MethodCall {
+        replier: Replier::Connection(connection),
+        message,
+    };
+
+    lookup(T::METHODS_MUT, name).map(|f| f(iface, &call))
+}
+
+/// Call the method `name` of `iface`, keeping its reply in `reply`.
+///
+/// `iface` must be of type `T`. The `&self` methods are looked up first.
+#[cfg(feature = "local")]
+pub(crate) fn dispatch_local<T: MethodTable>(
+    iface: &RefCell<dyn Interface>,
+    reply: &LocalReply,
+    message: &Message,
+    name: &str,
+) -> Option<Result<u32>> {
+    let call = MethodCall {
+        replier: Replier::Local(reply),
+        message,
+    };
+
+    if let Some(f) = lookup(T::METHODS, name) {
+        let iface = iface.borrow();
+        return iface.downcast_ref::<T>().map(|iface| f(iface, &call));
+    }
+    let f = lookup(T::METHODS_MUT, name)?;
+    let mut iface = iface.borrow_mut();
+
+    iface.downcast_mut::<T>().map(|iface| f(iface, &call))
+}
@@ -0,0 +1,473 @@
+use std::{collections::BTreeMap, collections::HashMap, convert::TryFrom, fmt::Write};
+
+use zvariant::{OwnedValue, Signature, Value};
+
+use crate::{
+    fdo, utils::complete_types, Connection, Error, Interface, Message, MessageBuilder,
+    MessageError, RawBody, Result,
+};
+
+/// An interface whose members are only known at runtime.
+///
+/// The [`dbus_interface`] macro needs all the members of an interface at compile-time. This is
+/// for the other cases, e.g bridging a plugin system to D-Bus: methods, properties and signals are
+/// registered by name and signature on a [`DynamicInterfaceBuilder`], with closures to handle
+/// them. Register the resulting interface with [`ObjectServer::at_dynamic`]. Its members are then
+/// dispatched to and introspected like those of any other interface.
+///
+/// Method calls and property writes are checked against the registered signatures before the
+/// handlers are called, and so are the replies and the property values the handlers return.
+/// Decoding and encoding the values according to these signatures is up to the handlers.
+///
+/// Like the other interfaces of the [`ObjectServer`], the handlers are called synchronously on its
+/// thread.
+///
+/// # Example
+///
+/// ```no_run
+///# use std::error::Error;
+/// use zbus::{Connection, DynamicInterfaceBuilder, ObjectServer, RawBody};
+///
+/// let iface = DynamicInterfaceBuilder::new("org.zbus.Plugin")
+///     .method("Greet", "s", "s", |call| {
+///         let name: &str = call.body()?;
+///
+///         Ok(RawBody::new(&format!("Hello {}!", name))?)
+///     })
+///     .property("Version", "u", || Ok(1u32.into()))
+///     .signal("Greeted", "s")
+///     .build()?;
+///
+/// let connection = Connection::new_session()?;
+/// let mut object_server = ObjectServer::new(&connection);
+/// object_server.at_dynamic("/org/zbus/Plugin", iface)?;
+///
+/// loop {
+///     object_server.try_handle_next()?;
+/// }
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+///
+/// [`dbus_interface`]: attr.dbus_interface.html
+/// [`DynamicInterfaceBuilder`]: struct.DynamicInterfaceBuilder.html
+/// [`ObjectServer::at_dynamic`]: struct.ObjectServer.html#method.at_dynamic
+/// [`ObjectServer`]: struct.ObjectServer.html
+#[derive(Debug)]
+pub struct DynamicInterface {
+    name: String,
+    methods: BTreeMap<String, Method>,
+    properties: BTreeMap<String, Property>,
+    signals: BTreeMap<String, Signal>,
+}
+
+impl DynamicInterface {
+    /// The name of the interface.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Builder for [`DynamicInterface`].
+///
+/// The names and signatures of the members are checked once [`build`] is called.
+///
+/// [`DynamicInterface`]: struct.DynamicInterface.html
+/// [`build`]: #method.build
+#[derive(Debug)]
+pub struct DynamicInterfaceBuilder {
+    name: String,
+    methods: Vec<Method>,
+    properties: Vec<Property>,
+    signals: Vec<Signal>,
+}
+
+impl DynamicInterfaceBuilder {
+    /// Create a builder for the interface `name`.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            methods: vec![],
+            properties: vec![],
+            signals: vec![],
+        }
+    }
+
+    /// Add a method, taking arguments of the `input` signature and replying with the `output` one.
+    ///
+    /// The `handler` is only called for calls matching `input`, and gets the whole call to read
+    /// its arguments from. The body it returns must match `output`. Note that a [`RawBody`] of a
+    /// structure stands for the list of its fields, as for messages.
+    ///
+    /// [`RawBody`]: struct.RawBody.html
+    pub fn method<F>(mut self, name: &str, input: &str, output: &str, handler: F) -> Self
+    where
+        F: Fn(&Message) -> fdo::Result<RawBody> + 'static,
+    {
+        self.methods.push(Method {
+            name: name.to_owned(),
+            input: input.to_owned(),
+            output: output.to_owned(),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// Add a read-only property of the given `signature`, read through `getter`.
+    pub fn property<G>(self, name: &str, signature: &str, getter: G) -> Self
+    where
+        G: Fn() -> fdo::Result<OwnedValue> + 'static,
+    {
+        self.add_property(name, signature, Box::new(getter), None)
+    }
+
+    /// Add a read-write property of the given `signature`, read through `getter` and written
+    /// through `setter`.
+    ///
+    /// The `setter` is only called with values of `signature`.
+    pub fn property_rw<G, S>(self, name: &str, signature: &str, getter: G, setter: S) -> Self
+    where
+        G: Fn() -> fdo::Result<OwnedValue> + 'static,
+        S: Fn(&Value<'_>) -> fdo::Result<()> + 'static,
+    {
+        self.add_property(name, signature, Box::new(getter), Some(Box::new(setter)))
+    }
+
+    fn add_property(
+        mut self,
+        name: &str,
+        signature: &str,
+        getter: Box<Getter>,
+        setter: Option<Box<Setter>>,
+    ) -> Self {
+        self.properties.push(Property {
+            name: name.to_owned(),
+            signature: signature.to_owned(),
+            getter,
+            setter,
+        });
+        self
+    }
+
+    /// Add a signal, with arguments of the given `signature`.
+    ///
+    /// Signals are only introspected. Emit them through [`Connection::emit_signal`].
+    ///
+    /// [`Connection::emit_signal`]: struct.Connection.html#method.emit_signal
+    pub fn signal(mut self, name: &str, signature: &str) -> Self {
+        self.signals.push(Signal {
+            name: name.to_owned(),
+            signature: signature.to_owned(),
+        });
+        self
+    }
+
+    /// Build the interface.
+    ///
+    /// Fails with [`Error::InvalidName`] if the name of the interface or of any of its members is
+    /// invalid, or if two members of the same kind have the same name. Invalid signatures are
+    /// reported as [`Error::Variant`], including property signatures of more than a single type.
+    ///
+    /// [`Error::InvalidName`]: enum.Error.html#variant.InvalidName
+    /// [`Error::Variant`]: enum.Error.html#variant.Variant
+    pub fn build(self) -> Result<DynamicInterface> {
+        if !is_interface_name(&self.name) {
+            return Err(Error::InvalidName(format!(
+                "`{}` is not a valid interface name",
+                self.name
+            )));
+        }
+
+        let mut methods = BTreeMap::new();
+        for method in self.methods {
+            check_signature(&method.input)?;
+            check_signature(&method.output)?;
+            insert_member(&mut methods, "method", method.name.clone(), method)?;
+        }
+        let mut properties = BTreeMap::new();
+        for property in self.properties {
+            check_signature(&property.signature)?;
+            if complete_types(&property.signature).len() != 1 {
+                return Err(zvariant::Error::Message(format!(
+                    "property `{}` of signature `{}` isn't of a single type",
+                    property.name, property.signature
+                ))
+                .into());
+            }
+            insert_member(&mut properties, "property", property.name.clone(), property)?;
+        }
+        let mut signals = BTreeMap::new();
+        for signal in self.signals {
+            check_signature(&signal.signature)?;
+            insert_member(&mut signals, "signal", signal.name.clone(), signal)?;
+        }
+
+        Ok(DynamicInterface {
+            name: self.name,
+            methods,
+            properties,
+            signals,
+        })
+    }
+}
+
+type Handler = dyn Fn(&Message) -> fdo::Result<RawBody>;
+type Getter = dyn Fn() -> fdo::Result<OwnedValue>;
+type Setter = dyn Fn(&Value<'_>) -> fdo::Result<()>;
+
+#[derive(derivative::Derivative)]
+#[derivative(Debug)]
+struct Method {
+    name: String,
+    input: String,
+    output: String,
+    #[derivative(Debug = "ignore")]
+    handler: Box<Handler>,
+}
+
+impl Method {
+    fn call(&self, connection: &Connection, msg: &Message) -> Result<u32> {
+        match self.reply(connection, msg) {
+            Ok(reply) => connection.send_message(reply),
+            Err(e) => e.reply(connection, msg),
+        }
+    }
+
+    fn reply(&self, connection: &Connection, msg: &Message) -> fdo::Result<Message> {
+        let signature = body_signature(msg)?;
+        if signature != self.input {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Method `{}` expects arguments of signature `{}`, not `{}`",
+                self.name, self.input, signature
+            )));
+        }
+
+        let body = (self.handler)(msg)?;
+        let mut builder = MessageBuilder::method_return(msg)?;
+        if let Some(sender) = connection.unique_name() {
+            builder = builder.sender(sender);
+        }
+        let reply = builder.build_raw_body(&body)?;
+        let signature = body_signature(&reply)?;
+        if signature != self.output {
+            return Err(fdo::Error::Failed(format!(
+                "Method `{}` replied with signature `{}` instead of `{}`",
+                self.name, signature, self.output
+            )));
+        }
+
+        Ok(reply)
+    }
+}
+
+#[derive(derivative::Derivative)]
+#[derivative(Debug)]
+struct Property {
+    name: String,
+    signature: String,
+    #[derivative(Debug = "ignore")]
+    getter: Box<Getter>,
+    #[derivative(Debug = "ignore")]
+    setter: Option<Box<Setter>>,
+}
+
+impl Property {
+    fn get(&self) -> fdo::Result<OwnedValue> {
+        let value = (self.getter)()?;
+        let signature = value.value_signature();
+        if signature != self.signature.as_str() {
+            return Err(fdo::Error::Failed(format!(
+                "Property `{}` read as signature `{}` instead of `{}`",
+                self.name, signature, self.signature
+            )));
+        }
+
+        Ok(value)
+    }
+
+    fn set(&self, value: &Value<'_>) -> fdo::Result<()> {
+        let setter = self.setter.as_ref().ok_or_else(|| {
+            fdo::Error::PropertyReadOnly(format!("Property `{}` is read-only", self.name))
+        })?;
+        let signature = value.value_signature();
+        if signature != self.signature.as_str() {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Property `{}` is of signature `{}`, not `{}`",
+                self.name, self.signature, signature
+            )));
+        }
+
+        setter(value)
+    }
+}
+
+#[derive(Debug)]
+struct Signal {
+    name: String,
+    signature: String,
+}
+
+// The `Interface` registered for a `DynamicInterface`, under its name rather than `name()`.
+#[derive(Debug)]
+pub(crate) struct Dynamic(pub(crate) DynamicInterface);
+
+impl Interface for Dynamic {
+    fn name() -> &'static str {
+        // Only called for the interfaces registered by type, which this type can't be.
+        unreachable!("dynamic interfaces are registered under the name of the instance")
+    }
+
+    fn get(&self, property_name: &str) -> Option<fdo::Result<OwnedValue>> {
+        self.0.properties.get(property_name).map(Property::get)
+    }
+
+    fn get_all(&self) -> HashMap<String, OwnedValue> {
+        self.0
+            .properties
+            .iter()
+            .filter_map(|(name, property)| property.get().ok().map(|value| (name.clone(), value)))
+            .collect()
+    }
+
+    fn try_get_all(&self) -> fdo::Result<HashMap<String, OwnedValue>> {
+        self.0
+            .properties
+            .iter()
+            .map(|(name, property)| property.get().map(|value| (name.clone(), value)))
+            .collect()
+    }
+
+    fn set(&mut self, property_name: &str, value: &Value<'_>) -> Option<fdo::Result<()>> {
+        self.0
+            .properties
+            .get(property_name)
+            .map(|property| property.set(value))
+    }
+
+    fn call(&self, connection: &Connection, msg: &Message, name: &str) -> Option<Result<u32>> {
+        self.0
+            .methods
+            .get(name)
+            .map(|method| method.call(connection, msg))
+    }
+
+    fn call_mut(&mut self, _: &Connection, _: &Message, _: &str) -> Option<Result<u32>> {
+        None
+    }
+
+    fn introspect_to_writer(&self, writer: &mut dyn Write, level: usize) {
+        writeln!(
+            writer,
+            r#"{:indent$}<interface name="{}">"#,
+            "",
+            self.0.name,
+            indent = level
+        )
+        .unwrap();
+        let level = level + 2;
+        for (name, method) in &self.0.methods {
+            writeln!(
+                writer,
+                "{:indent$}<method name=\"{}\">",
+                "",
+                name,
+                indent = level
+            )
+            .unwrap();
+            write_args(writer, &method.input, " direction=\"in\"", level + 2);
+            write_args(writer, &method.output, " direction=\"out\"", level + 2);
+            writeln!(writer, "{:indent$}</method>", "", indent = level).unwrap();
+        }
+        for (name, signal) in &self.0.signals {
+            writeln!(
+                writer,
+                "{:indent$}<signal name=\"{}\">",
+                "",
+                name,
+                indent = level
+            )
+            .unwrap();
+            write_args(writer, &signal.signature, "", level + 2);
+            writeln!(writer, "{:indent$}</signal>", "", indent = level).unwrap();
+        }
+        for (name, property) in &self.0.properties {
+            let access = if property.setter.is_some() {
+                "readwrite"
+            } else {
+                "read"
+            };
+            writeln!(
+                writer,
+                "{:indent$}<property name=\"{}\" type=\"{}\" access=\"{}\"/>",
+                "",
+                name,
+                property.signature,
+                access,
+                indent = level,
+            )
+            .unwrap();
+        }
+        writeln!(writer, "{:indent$}</interface>", "", indent = level - 2).unwrap();
+    }
+}
+
+fn write_args(writer: &mut dyn Write, signature: &str, direction: &str, level: usize) {
+    for ty in complete_types(signature) {
+        writeln!(
+            writer,
+            "{:indent$}<arg type=\"{}\"{}/>",
+            "",
+            ty,
+            direction,
+            indent = level
+        )
+        .unwrap();
+    }
+}
+
+// The signature of the body of `msg`, empty if it has none.
+fn body_signature(msg: &Message) -> fdo::Result<String> {
+    match msg.body_signature() {
+        Ok(signature) => Ok(signature.as_str().to_owned()),
+        Err(MessageError::NoBodySignature) => Ok(String::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn check_signature(signature: &str) -> Result<()> {
+    Signature::try_from(signature)?;
+
+    Ok(())
+}
+
+fn insert_member<M>(
+    members: &mut BTreeMap<String, M>,
+    kind: &str,
+    name: String,
+    member: M,
+) -> Result<()> {
+    if !is_member_name(&name) {
+        return Err(Error::InvalidName(format!(
+            "`{}` is not a valid {} name",
+            name, kind
+        )));
+    }
+    if members.contains_key(&name) {
+        return Err(Error::InvalidName(format!("duplicate {} `{}`", kind, name)));
+    }
+    members.insert(name, member);
+
+    Ok(())
+}
+
+// Whether `name` is a valid member name, as per the specification.
+fn is_member_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 255
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Whether `name` is a valid interface name, as per the specification.
+fn is_interface_name(name: &str) -> bool {
+    name.len() <= 255 && name.contains('.') && name.split('.').all(is_member_name)
+}
@@ -1,8 +1,8 @@
 use static_assertions::assert_impl_all;
-use std::{convert::Infallible, error, fmt, io, sync::Arc};
+use std::{convert::Infallible, error, fmt, io, sync::Arc, time::Duration};
 use zvariant::Error as VariantError;
 
-use crate::{fdo, Message, MessageError, MessageType};
+use crate::{fdo, CookieError, Discrepancy, Message, MessageError, MessageType};
 
 /// The error type for `zbus`.
 ///
@@ -22,6 +22,8 @@ pub enum Error {
     Variant(VariantError),
     /// Initial handshake error.
     Handshake(String),
+    /// A `DBUS_COOKIE_SHA1` authentication error.
+    Cookie(CookieError),
     /// Unexpected or incorrect reply.
     InvalidReply,
     /// A D-Bus method error reply.
@@ -34,9 +36,28 @@ pub enum Error {
     Unsupported,
     /// A [`fdo::Error`] transformed into [`Error`].
     FDO(Box<fdo::Error>),
+    /// A proxy doesn't match the introspected interface of its object.
+    InterfaceMismatch(Vec<Discrepancy>),
+    /// The bus connection has no unique name yet, as saying `Hello` was delayed.
+    NoUniqueName,
+    /// Invalid D-Bus name, or service file contents.
+    InvalidName(String),
+    /// A well-known name couldn't be acquired, as another connection owns it.
+    NameTaken(String),
+    /// The body of a method reply couldn't be deserialized to the expected type.
+    ReplyBody(Box<ReplyBodyError>),
+    /// The peer didn't answer a liveness ping in the given time, so the connection was given up on.
+    PeerUnresponsive(Duration),
+    /// The method-call argument at the given index, given as text, is invalid or missing.
+    Argument(usize, VariantError),
+    /// The outgoing message queue is full, so the message wasn't sent.
+    QueueFull,
     #[cfg(feature = "xml")]
     /// An XML error
     SerdeXml(serde_xml_rs::Error),
+    #[cfg(feature = "dbus")]
+    /// A message dbus-rs failed to marshal or parse, when converting it.
+    DBusRs(String),
     /// Only exists to allow `TryFrom<T> for T` conversions. You should never actually be getting
     /// this error from any API.
     Infallible,
@@ -60,6 +81,7 @@ impl error::Error for Error {
             Error::Address(_) => None,
             Error::Io(e) => Some(e),
             Error::Handshake(_) => None,
+            Error::Cookie(e) => Some(e),
             Error::Message(e) => Some(e),
             Error::Variant(e) => Some(e),
             Error::InvalidReply => None,
@@ -67,8 +89,18 @@ impl error::Error for Error {
             Error::InvalidGUID => None,
             Error::Unsupported => None,
             Error::FDO(e) => Some(e),
+            Error::InterfaceMismatch(_) => None,
+            Error::NoUniqueName => None,
+            Error::InvalidName(_) => None,
+            Error::NameTaken(_) => None,
+            Error::ReplyBody(e) => Some(e),
+            Error::PeerUnresponsive(_) => None,
+            Error::Argument(_, e) => Some(e),
+            Error::QueueFull => None,
             #[cfg(feature = "xml")]
             Error::SerdeXml(e) => Some(e),
+            #[cfg(feature = "dbus")]
+            Error::DBusRs(_) => None,
             Error::Infallible => None,
         }
     }
@@ -81,6 +113,7 @@ impl fmt::Display for Error {
             Error::Address(e) => write!(f, "address error: {}", e),
             Error::Io(e) => write!(f, "I/O error: {}", e),
             Error::Handshake(e) => write!(f, "D-Bus handshake failed: {}", e),
+            Error::Cookie(e) => write!(f, "D-Bus handshake failed: {}", e),
             Error::Message(e) => write!(f, "Message creation error: {}", e),
             Error::Variant(e) => write!(f, "{}", e),
             Error::InvalidReply => write!(f, "Invalid D-Bus method reply"),
@@ -93,8 +126,27 @@ impl fmt::Display for Error {
             Error::InvalidGUID => write!(f, "Invalid GUID"),
             Error::Unsupported => write!(f, "Connection support is lacking"),
             Error::FDO(e) => write!(f, "{}", e),
+            Error::InterfaceMismatch(discrepancies) => {
+                write!(f, "Proxy doesn't match the interface")?;
+                for (i, d) in discrepancies.iter().enumerate() {
+                    write!(f, "{} {}", if i == 0 { ":" } else { "," }, d)?;
+                }
+
+                Ok(())
+            }
+            Error::NoUniqueName => write!(f, "No unique name yet, Hello wasn't sent to the bus"),
+            Error::InvalidName(e) => write!(f, "{}", e),
+            Error::NameTaken(name) => write!(f, "Name `{}` is owned by another connection", name),
+            Error::ReplyBody(e) => write!(f, "{}", e),
+            Error::PeerUnresponsive(timeout) => {
+                write!(f, "Peer didn't answer a ping within {:?}", timeout)
+            }
+            Error::Argument(index, e) => write!(f, "Invalid argument {}: {}", index, e),
+            Error::QueueFull => write!(f, "The outgoing message queue is full"),
             #[cfg(feature = "xml")]
             Error::SerdeXml(e) => write!(f, "XML error: {}", e),
+            #[cfg(feature = "dbus")]
+            Error::DBusRs(e) => write!(f, "dbus-rs error: {}", e),
             Error::Infallible => write!(f, "Infallible conversion failed"),
         }
     }
@@ -181,5 +233,95 @@ impl From<Arc<Message>> for Error {
     }
 }
 
+/// The error of a method call whose reply body isn't of the expected type.
+///
+/// It identifies the call, along with the signature of the body that was expected and the one that
+/// was received.
+#[derive(Debug, PartialEq)]
+pub struct ReplyBodyError {
+    destination: Option<String>,
+    interface: Option<String>,
+    member: String,
+    expected_signature: String,
+    received_signature: String,
+    error: MessageError,
+}
+
+assert_impl_all!(ReplyBodyError: Send, Sync, Unpin);
+
+impl ReplyBodyError {
+    pub(crate) fn new(
+        destination: Option<&str>,
+        interface: Option<&str>,
+        member: &str,
+        expected_signature: &str,
+        received_signature: &str,
+        error: MessageError,
+    ) -> Self {
+        Self {
+            destination: destination.map(String::from),
+            interface: interface.map(String::from),
+            member: member.to_owned(),
+            expected_signature: expected_signature.to_owned(),
+            received_signature: received_signature.to_owned(),
+            error,
+        }
+    }
+
+    /// The destination of the call, if any.
+    pub fn destination(&self) -> Option<&str> {
+        self.destination.as_deref()
+    }
+
+    /// The interface of the called method, if any.
+    pub fn interface(&self) -> Option<&str> {
+        self.interface.as_deref()
+    }
+
+    /// The name of the called method.
+    pub fn member(&self) -> &str {
+        &self.member
+    }
+
+    /// The signature of the type the body was to be deserialized to.
+    pub fn expected_signature(&self) -> &str {
+        &self.expected_signature
+    }
+
+    /// The signature of the body of the reply, empty if it has none.
+    pub fn received_signature(&self) -> &str {
+        &self.received_signature
+    }
+
+    /// The error deserializing the body.
+    pub fn error(&self) -> &MessageError {
+        &self.error
+    }
+}
+
+impl error::Error for ReplyBodyError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl fmt::Display for ReplyBodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid reply to `")?;
+        if let Some(interface) = &self.interface {
+            write!(f, "{}.", interface)?;
+        }
+        write!(f, "{}`", self.member)?;
+        if let Some(destination) = &self.destination {
+            write!(f, " from `{}`", destination)?;
+        }
+        write!(
+            f,
+            ": expected signature `{}`, received `{}` ({})",
+            self.expected_signature, self.received_signature, self.error
+        )
+    }
+}
+
 /// Alias for a `Result` with the error type `zbus::Error`.
 pub type Result<T> = std::result::Result<T, Error>;
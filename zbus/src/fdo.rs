@@ -8,10 +8,16 @@ use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use static_assertions::assert_impl_all;
 use std::collections::HashMap;
-use zvariant::{derive::Type, ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Signature, Type, Value};
 
 use crate::{dbus_interface, dbus_proxy, object_server::LOCAL_NODE, DBusError};
 
+#[cfg(feature = "xml")]
+pub use crate::introspect_tree::{
+    introspect_tree, introspect_tree_async, AsyncIntrospectTree, IntrospectTree,
+    IntrospectTreeOptions,
+};
+
 /// Proxy for the `org.freedesktop.DBus.Introspectable` interface.
 #[dbus_proxy(interface = "org.freedesktop.DBus.Introspectable", default_path = "/")]
 trait Introspectable {
@@ -44,6 +50,18 @@ trait Properties {
     /// Set a property value.
     fn set(&self, interface_name: &str, property_name: &str, value: &Value<'_>) -> Result<()>;
 
+    /// Set several property values, all or none of them.
+    ///
+    /// This is a zbus extension, only supported by the interfaces declared with
+    /// `#[dbus_interface(atomic_properties)]`. Others fail with [`Error::NotSupported`].
+    ///
+    /// [`Error::NotSupported`]: enum.Error.html#variant.NotSupported
+    fn set_multiple(
+        &self,
+        interface_name: &str,
+        properties: HashMap<&str, &Value<'_>>,
+    ) -> Result<()>;
+
     /// Get all properties.
     fn get_all(&self, interface_name: &str) -> Result<HashMap<String, OwnedValue>>;
 
@@ -95,14 +113,33 @@ impl Properties {
         })
     }
 
+    fn set_multiple(
+        &mut self,
+        interface_name: &str,
+        properties: HashMap<String, OwnedValue>,
+    ) -> Result<()> {
+        LOCAL_NODE.with(|node| {
+            let iface = node.get_interface(interface_name).ok_or_else(|| {
+                Error::UnknownInterface(format!("Unknown interface '{}'", interface_name))
+            })?;
+
+            let res = iface.borrow_mut().set_multiple(&properties);
+            res.ok_or_else(|| {
+                Error::NotSupported(format!(
+                    "Interface '{}' can't set multiple properties at once",
+                    interface_name
+                ))
+            })?
+        })
+    }
+
     fn get_all(&self, interface_name: &str) -> Result<HashMap<String, OwnedValue>> {
         LOCAL_NODE.with(|node| {
             let iface = node.get_interface(interface_name).ok_or_else(|| {
                 Error::UnknownInterface(format!("Unknown interface '{}'", interface_name))
             })?;
 
-            let res = iface.borrow().get_all();
-            Ok(res)
+            iface.borrow().try_get_all()
         })
     }
 
@@ -234,7 +271,7 @@ assert_impl_all!(StatsProxy<'_>: Send, Sync, Unpin);
 ///
 /// [`request_name`]: struct.DBusProxy.html#method.request_name
 #[repr(u32)]
-#[derive(Type, BitFlags, Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[derive(BitFlags, Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum RequestNameFlags {
     /// If an application A specifies this flag and succeeds in becoming the owner of the name, and
     /// another application B later calls [`request_name`] with the [`ReplaceExisting`] flag, then
@@ -265,11 +302,17 @@ pub enum RequestNameFlags {
 
 assert_impl_all!(RequestNameFlags: Send, Sync, Unpin);
 
+impl Type for RequestNameFlags {
+    fn signature() -> Signature<'static> {
+        u32::signature()
+    }
+}
+
 /// The return code of the [`request_name`] method.
 ///
 /// [`request_name`]: struct.DBusProxy.html#method.request_name
 #[repr(u32)]
-#[derive(Deserialize_repr, Serialize_repr, Type, Debug, PartialEq)]
+#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq)]
 pub enum RequestNameReply {
     /// The caller is now the primary owner of the name, replacing any previous owner. Either the
     /// name had no owner before, or the caller specified [`ReplaceExisting`] and the current owner
@@ -300,11 +343,17 @@ pub enum RequestNameReply {
 
 assert_impl_all!(RequestNameReply: Send, Sync, Unpin);
 
+impl Type for RequestNameReply {
+    fn signature() -> Signature<'static> {
+        u32::signature()
+    }
+}
+
 /// The return code of the [`release_name`] method.
 ///
 /// [`release_name`]: struct.DBusProxy.html#method.release_name
 #[repr(u32)]
-#[derive(Deserialize_repr, Serialize_repr, Type, Debug, PartialEq)]
+#[derive(Deserialize_repr, Serialize_repr, Debug, PartialEq)]
 pub enum ReleaseNameReply {
     /// The caller has released their claim on the given name. Either the caller was the primary
     /// owner of the name, and the name is now unused or taken by somebody waiting in the queue for
@@ -320,6 +369,12 @@ pub enum ReleaseNameReply {
 
 assert_impl_all!(ReleaseNameReply: Send, Sync, Unpin);
 
+impl Type for ReleaseNameReply {
+    fn signature() -> Signature<'static> {
+        u32::signature()
+    }
+}
+
 /// Proxy for the `org.freedesktop.DBus` interface.
 #[dbus_proxy(interface = "org.freedesktop.DBus")]
 trait DBus {
@@ -624,6 +679,15 @@ impl From<zbus::MessageError> for Error {
             zbus::MessageError::MissingField => {
                 Self::InconsistentMessage("Required message field missing".to_string())
             }
+            zbus::MessageError::FdNotOwned => {
+                Self::InvalidArgs("file descriptor not owned by the message".to_string())
+            }
+            zbus::MessageError::UnknownFd => {
+                Self::InvalidArgs("file descriptor not attached to the message".to_string())
+            }
+            zbus::MessageError::UnmatchedFdCount => {
+                Self::InconsistentMessage("unmatched file descriptor count".to_string())
+            }
             zbus::MessageError::Infallible => Self::ZBus(zbus::Error::Infallible),
         }
     }
@@ -663,9 +727,10 @@ mod tests {
     #[test]
     #[timeout(1000)]
     fn signal_connect() {
-        // Register a well-known name with the session bus and ensure we get the appropriate
-        // signals called for that.
-        let conn = crate::Connection::new_session().unwrap();
+        // Register a well-known name with the bus and ensure we get the appropriate signals called
+        // for that.
+        let bus = crate::test_bus::TestBus::new().unwrap();
+        let conn = bus.connect().unwrap();
         let owner_change_signaled = Arc::new(AtomicBool::new(false));
         let name_acquired_signaled = Arc::new(AtomicBool::new(false));
 
@@ -0,0 +1,241 @@
+use byteorder::{ByteOrder, NativeEndian};
+use std::{fmt, marker::PhantomData};
+
+use crate::utils::padding_for_n_bytes;
+
+/// Message bodies that can be read directly from the encoded message, without going through serde.
+///
+/// This is implemented for the fixed-size basic types (except for file descriptors) and tuples of
+/// up to 4 of them. See [`Message::body_fixed`] for details.
+///
+/// [`Message::body_fixed`]: struct.Message.html#method.body_fixed
+pub trait FixedBody: Sized {
+    /// Read the value at `offset` in `bytes` and advance `offset` past it.
+    ///
+    /// `bytes` must be the body of a message in native endianness. Messages in the other byte
+    /// order are left to the generic deserialization path. Returns `None` if the value can not be
+    /// read this way, in which case the generic deserialization path will report the exact issue.
+    #[doc(hidden)]
+    fn read_at(bytes: &[u8], offset: &mut usize) -> Option<Self>;
+}
+
+// Advances `offset` to the next multiple of `alignment`, after validating the padding.
+fn align(bytes: &[u8], offset: &mut usize, alignment: usize) -> Option<()> {
+    let start = *offset + padding_for_n_bytes(*offset, alignment);
+    if start > bytes.len() || bytes[*offset..start].iter().any(|b| *b != 0) {
+        return None;
+    }
+    *offset = start;
+
+    Some(())
+}
+
+// Returns the `size` bytes at the next offset aligned to `size`.
+fn next_slice<'b>(bytes: &'b [u8], offset: &mut usize, size: usize) -> Option<&'b [u8]> {
+    align(bytes, offset, size)?;
+    let start = *offset;
+    let end = start + size;
+    if end > bytes.len() {
+        return None;
+    }
+    *offset = end;
+
+    Some(&bytes[start..end])
+}
+
+macro_rules! fixed_body_basic {
+    ($ty:ty, $size:expr, $read:expr) => {
+        impl FixedBody for $ty {
+            fn read_at(bytes: &[u8], offset: &mut usize) -> Option<Self> {
+                next_slice(bytes, offset, $size).and_then($read)
+            }
+        }
+    };
+}
+
+fixed_body_basic!(u8, 1, |s: &[u8]| Some(s[0]));
+fixed_body_basic!(bool, 4, |s: &[u8]| match NativeEndian::read_u32(s) {
+    0 => Some(false),
+    1 => Some(true),
+    // Invalid as per the D-Bus spec.
+    _ => None,
+});
+fixed_body_basic!(i16, 2, |s: &[u8]| Some(NativeEndian::read_i16(s)));
+fixed_body_basic!(u16, 2, |s: &[u8]| Some(NativeEndian::read_u16(s)));
+fixed_body_basic!(i32, 4, |s: &[u8]| Some(NativeEndian::read_i32(s)));
+fixed_body_basic!(u32, 4, |s: &[u8]| Some(NativeEndian::read_u32(s)));
+fixed_body_basic!(i64, 8, |s: &[u8]| Some(NativeEndian::read_i64(s)));
+fixed_body_basic!(u64, 8, |s: &[u8]| Some(NativeEndian::read_u64(s)));
+fixed_body_basic!(f64, 8, |s: &[u8]| Some(NativeEndian::read_f64(s)));
+
+macro_rules! fixed_body_tuple {
+    ($($name:ident)+) => {
+        impl<$($name),+> FixedBody for ($($name,)+)
+        where
+            $($name: FixedBody,)+
+        {
+            fn read_at(bytes: &[u8], offset: &mut usize) -> Option<Self> {
+                // Structures are 8-byte aligned.
+                align(bytes, offset, 8)?;
+                Some(($($name::read_at(bytes, offset)?,)+))
+            }
+        }
+    };
+}
+
+fixed_body_tuple! { A }
+fixed_body_tuple! { A B }
+fixed_body_tuple! { A B C }
+fixed_body_tuple! { A B C D }
+
+/// A message body consisting of a single array of fixed-size elements, read in place.
+///
+/// This is returned by [`Message::body_array`], for bodies like `ay`, `au` or `a(ii)`. The elements
+/// are read from the message bytes as they're iterated over, so even very large arrays take no
+/// memory besides that of the message itself. For byte arrays, [`as_slice`] gives the bytes as is.
+///
+/// The array is validated when the `FixedArray` is created, so iterating can't fail.
+///
+/// # Padding and alignment
+///
+/// In the D-Bus format, the elements of an array are aligned to the alignment of their type: 8
+/// bytes for structures, `u64`, `i64` and `f64`, and their size for the other basic types. Only
+/// the elements of structure types can hence be separated by padding (e.g the 4 bytes after each
+/// `(ui)` element), which [`as_bytes`] includes. The padding between the length of the array and
+/// its first element is never included.
+///
+/// [`Message::body_array`]: struct.Message.html#method.body_array
+/// [`as_slice`]: #method.as_slice
+/// [`as_bytes`]: #method.as_bytes
+pub struct FixedArray<'m, T> {
+    // The whole body, since the offsets of the elements are relative to its start.
+    body: &'m [u8],
+    start: usize,
+    end: usize,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<'m, T: FixedBody> FixedArray<'m, T> {
+    // Read the array making up the whole of `body`, with elements aligned to `alignment`.
+    pub(crate) fn new(body: &'m [u8], alignment: usize) -> Option<Self> {
+        let len = u32::read_at(body, &mut 0)? as usize;
+        let mut start = 4;
+        align(body, &mut start, alignment)?;
+        let end = start.checked_add(len)?;
+        if end != body.len() {
+            return None;
+        }
+
+        let array = Self {
+            body,
+            start,
+            end,
+            phantom: PhantomData,
+        };
+        // Validate the elements (and the padding between them) upfront.
+        let mut offset = start;
+        while offset < end {
+            T::read_at(&body[..end], &mut offset)?;
+        }
+
+        Some(array)
+    }
+
+    /// The encoded elements, including any padding between them.
+    pub fn as_bytes(&self) -> &'m [u8] {
+        &self.body[self.start..self.end]
+    }
+
+    /// Whether the array has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// An iterator over the elements.
+    pub fn iter(&self) -> FixedArrayIter<'m, T> {
+        FixedArrayIter {
+            body: &self.body[..self.end],
+            offset: self.start,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'m> FixedArray<'m, u8> {
+    /// The bytes of a byte array.
+    pub fn as_slice(&self) -> &'m [u8] {
+        self.as_bytes()
+    }
+}
+
+impl<T> Clone for FixedArray<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            body: self.body,
+            start: self.start,
+            end: self.end,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for FixedArray<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FixedArray")
+            .field("len", &(self.end - self.start))
+            .finish()
+    }
+}
+
+impl<'m, T: FixedBody> IntoIterator for FixedArray<'m, T> {
+    type Item = T;
+    type IntoIter = FixedArrayIter<'m, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, 'm, T: FixedBody> IntoIterator for &'a FixedArray<'m, T> {
+    type Item = T;
+    type IntoIter = FixedArrayIter<'m, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the elements of a [`FixedArray`].
+///
+/// [`FixedArray`]: struct.FixedArray.html
+#[derive(Debug)]
+pub struct FixedArrayIter<'m, T> {
+    // The body, up to the end of the array.
+    body: &'m [u8],
+    offset: usize,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T: FixedBody> Iterator for FixedArrayIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.offset >= self.body.len() {
+            return None;
+        }
+
+        // Can't fail, since the elements were validated.
+        T::read_at(self.body, &mut self.offset)
+    }
+}
+
+// The alignment of the elements of an array of `element`s, a fixed-size type.
+pub(crate) fn element_alignment(element: &str) -> usize {
+    match element.chars().next() {
+        Some('y') => 1,
+        Some('n') | Some('q') => 2,
+        Some('b') | Some('i') | Some('u') => 4,
+        // 64-bit types and structures.
+        _ => 8,
+    }
+}
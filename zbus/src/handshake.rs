@@ -1,21 +1,28 @@
-use std::{
-    collections::VecDeque,
-    fmt,
-    fs::File,
-    io::{BufRead, BufReader},
-    path::PathBuf,
-    str::FromStr,
-};
+use std::{collections::VecDeque, fmt, io::BufRead, str::FromStr};
 
-use nix::{poll::PollFlags, unistd::Uid};
+use nix::poll::PollFlags;
 
 use crate::{
+    auth::{self, AuthMechanism, AuthResponse},
     guid::Guid,
-    raw::{Connection, Socket},
+    logging,
+    raw::{socket::set_no_sigpipe, Connection, Socket},
     utils::wait_on,
     Error, Result,
 };
 
+// Have the writes to `socket` fail rather than raise `SIGPIPE` once the peer is gone, before
+// anything is written to it. Not every `Socket` is a socket of the OS, so failing is only logged.
+fn set_up_socket<S: Socket>(socket: &S) {
+    if let Err(e) = set_no_sigpipe(socket.as_raw_fd()) {
+        log::debug!(
+            target: logging::HANDSHAKE,
+            "Failed to keep the socket from raising SIGPIPE: {}",
+            e
+        );
+    }
+}
+
 /*
  * Client-side handshake logic
  */
@@ -26,8 +33,9 @@ enum ClientHandshakeStep {
     Init,
     MechanismInit,
     WaitingForData,
-    WaitingForOK,
+    WaitingForReject,
     WaitingForAgreeUnixFD,
+    WaitingForAgreeCompression,
     Done,
 }
 
@@ -37,14 +45,6 @@ pub enum IoOperation {
     Write,
 }
 
-// See <https://dbus.freedesktop.org/doc/dbus-specification.html#auth-mechanisms>
-#[derive(Clone, Copy, Debug)]
-enum Mechanism {
-    External,
-    Cookie,
-    Anonymous,
-}
-
 // The plain-text SASL profile authentication protocol described here:
 // <https://dbus.freedesktop.org/doc/dbus-specification.html#auth-protocol>
 //
@@ -52,17 +52,23 @@ enum Mechanism {
 #[derive(Debug)]
 #[allow(clippy::upper_case_acronyms)]
 enum Command {
-    Auth(Option<Mechanism>, Option<String>),
+    Auth(Option<String>, Option<Vec<u8>>),
     Cancel,
     Begin,
     Data(Vec<u8>),
     Error(String),
     NegotiateUnixFD,
-    Rejected(Vec<Mechanism>),
+    Rejected(Vec<String>),
     Ok(Guid),
     AgreeUnixFD,
+    // The zbus extension compressing the message bodies, which other peers reply `ERROR` to.
+    NegotiateCompression,
+    AgreeCompression,
 }
 
+const NEGOTIATE_COMPRESSION: &str = "EXTENSION_ZBUS_NEGOTIATE_DEFLATE";
+const AGREE_COMPRESSION: &str = "EXTENSION_ZBUS_AGREE_DEFLATE";
+
 /// A representation of an in-progress handshake, client-side
 ///
 /// This struct is an async-compatible representation of the initial handshake that must be performed before
@@ -87,8 +93,13 @@ pub struct ClientHandshake<S> {
     step: ClientHandshakeStep,
     server_guid: Option<Guid>,
     cap_unix_fd: bool,
+    // the body size threshold of the compression to offer, if any
+    compression: Option<usize>,
+    cap_compression: bool,
     // the current AUTH mechanism is front, ordered by priority
-    mechanisms: VecDeque<Mechanism>,
+    mechanisms: VecDeque<Box<dyn AuthMechanism>>,
+    // the first error of the rejected mechanisms
+    error: Option<Error>,
 }
 
 /// The result of a finalized handshake
@@ -106,6 +117,8 @@ pub struct Authenticated<S> {
     pub(crate) server_guid: Guid,
     /// Whether file descriptor passing has been accepted by both sides
     pub(crate) cap_unix_fd: bool,
+    /// The address the connection was established to, if any
+    pub(crate) address: Option<String>,
 }
 
 pub trait Handshake<S> {
@@ -149,9 +162,13 @@ pub trait Handshake<S> {
 impl<S: Socket> ClientHandshake<S> {
     /// Start a handshake on this client socket
     pub fn new(socket: S) -> ClientHandshake<S> {
-        let mut mechanisms = VecDeque::new();
-        mechanisms.push_back(Mechanism::External);
-        mechanisms.push_back(Mechanism::Cookie);
+        Self::with_mechanisms(socket, vec![])
+    }
+
+    /// Start a handshake on this client socket, trying the `custom` mechanisms before the
+    /// built-in ones
+    pub fn with_mechanisms(socket: S, custom: Vec<Box<dyn AuthMechanism>>) -> ClientHandshake<S> {
+        set_up_socket(&socket);
         ClientHandshake {
             socket,
             recv_buffer: Vec::new(),
@@ -159,7 +176,29 @@ impl<S: Socket> ClientHandshake<S> {
             step: ClientHandshakeStep::Init,
             server_guid: None,
             cap_unix_fd: false,
-            mechanisms,
+            compression: None,
+            cap_compression: false,
+            mechanisms: auth::client_mechanisms(custom).into(),
+            error: None,
+        }
+    }
+
+    /// Offer the server to compress the bodies longer than `threshold` bytes, in both directions
+    ///
+    /// This is a zbus extension, so messages are sent uncompressed if the server doesn't agree.
+    pub(crate) fn set_body_compression(&mut self, threshold: Option<usize>) {
+        self.compression = threshold;
+    }
+
+    // Offer the compression if enabled, before beginning the session.
+    fn negotiate_compression(&self) -> (ClientHandshakeStep, Command) {
+        if self.compression.is_some() {
+            (
+                ClientHandshakeStep::WaitingForAgreeCompression,
+                Command::NegotiateCompression,
+            )
+        } else {
+            (ClientHandshakeStep::Done, Command::Begin)
         }
     }
 
@@ -186,156 +225,53 @@ impl<S: Socket> ClientHandshake<S> {
         line.parse()
     }
 
-    fn mechanism(&self) -> Result<&Mechanism> {
-        self.mechanisms
-            .front()
-            .ok_or_else(|| Error::Handshake("Exhausted available AUTH mechanisms".into()))
+    fn mechanism(&mut self) -> Result<&mut Box<dyn AuthMechanism>> {
+        let error = &mut self.error;
+        self.mechanisms.front_mut().ok_or_else(|| {
+            error
+                .take()
+                .unwrap_or_else(|| Error::Handshake("Exhausted available AUTH mechanisms".into()))
+        })
     }
 
-    fn mechanism_init(&mut self) -> Result<(ClientHandshakeStep, Command)> {
-        use ClientHandshakeStep::*;
-        let mech = self.mechanism()?;
-        match mech {
-            Mechanism::External => Ok((
-                WaitingForOK,
-                Command::Auth(Some(*mech), Some(sasl_auth_id())),
-            )),
-            Mechanism::Cookie => Ok((
-                WaitingForData,
-                Command::Auth(Some(*mech), Some(sasl_auth_id())),
-            )),
-            _ => Err(Error::Handshake("Unimplemented AUTH mechanisms".into())),
+    // Go on with the next mechanism, keeping track of why the current one failed.
+    fn next_mechanism(&mut self) {
+        if let Some(mut mech) = self.mechanisms.pop_front() {
+            log::debug!(
+                target: logging::HANDSHAKE,
+                "Authentication mechanism `{}` rejected",
+                mech.name(),
+            );
+            if let Some(e) = mech.take_error() {
+                self.error.get_or_insert(e);
+            }
         }
     }
 
-    fn mechanism_data(&mut self, data: Vec<u8>) -> Result<(ClientHandshakeStep, Command)> {
+    fn mechanism_init(&mut self) -> Result<(ClientHandshakeStep, Command)> {
         use ClientHandshakeStep::*;
-        let mech = self.mechanism()?;
-        match mech {
-            Mechanism::Cookie => {
-                let context = String::from_utf8_lossy(&data);
-                let mut split = context.split_ascii_whitespace();
-                let name = split
-                    .next()
-                    .ok_or_else(|| Error::Handshake("Missing cookie context name".into()))?;
-                let id = split
-                    .next()
-                    .ok_or_else(|| Error::Handshake("Missing cookie ID".into()))?;
-                let server_chall = split
-                    .next()
-                    .ok_or_else(|| Error::Handshake("Missing cookie challenge".into()))?;
-
-                let cookie = Cookie::lookup(name, id)?;
-                let client_chall = random_ascii(16);
-                let sec = format!("{}:{}:{}", server_chall, client_chall, cookie);
-                let sha1 = sha1::Sha1::from(sec).hexdigest();
-                let data = format!("{} {}", client_chall, sha1);
-                Ok((WaitingForOK, Command::Data(data.into())))
+        loop {
+            let mech = self.mechanism()?;
+            let name = mech.name().to_string();
+            match mech.challenge(None) {
+                AuthResponse::Data(data) => {
+                    return Ok((WaitingForData, Command::Auth(Some(name), Some(data))))
+                }
+                AuthResponse::Ok => return Ok((WaitingForData, Command::Auth(Some(name), None))),
+                // Nothing was sent yet, so there's nothing to cancel.
+                AuthResponse::Reject => self.next_mechanism(),
             }
-            _ => Err(Error::Handshake("Unexpected mechanism DATA".into())),
         }
     }
-}
-
-fn random_ascii(len: usize) -> String {
-    use rand::{distributions::Alphanumeric, thread_rng, Rng};
-    use std::iter;
-
-    let mut rng = thread_rng();
-    iter::repeat(())
-        .map(|()| rng.sample(Alphanumeric))
-        .map(char::from)
-        .take(len)
-        .collect()
-}
 
-fn sasl_auth_id() -> String {
-    Uid::current()
-        .to_string()
-        .chars()
-        .map(|c| format!("{:x}", c as u32))
-        .collect::<String>()
-}
-
-#[derive(Debug)]
-struct Cookie {
-    id: String,
-    creation_time: String,
-    cookie: String,
-}
-
-impl Cookie {
-    fn keyring_path() -> Result<PathBuf> {
-        let home = std::env::var("HOME")
-            .map_err(|e| Error::Handshake(format!("Failed to read $HOME: {}", e)))?;
-        let mut path = PathBuf::new();
-        path.push(home);
-        path.push(".dbus-keyrings");
-        Ok(path)
-    }
-
-    fn read_keyring(name: &str) -> Result<Vec<Cookie>> {
-        use std::os::unix::fs::PermissionsExt;
-
-        let mut path = Cookie::keyring_path()?;
-        let perms = std::fs::metadata(&path)?.permissions().mode();
-        if perms & 0o066 != 0 {
-            return Err(Error::Handshake(
-                "DBus keyring has invalid permissions".into(),
-            ));
-        }
-        path.push(name);
-        let file = File::open(&path)?;
-        let mut cookies = vec![];
-        for (n, line) in BufReader::new(file).lines().enumerate() {
-            let line = line?;
-            let mut split = line.split_whitespace();
-            let id = split
-                .next()
-                .ok_or_else(|| {
-                    Error::Handshake(format!(
-                        "DBus cookie `{}` missing ID at line {}",
-                        path.to_str().unwrap(),
-                        n
-                    ))
-                })?
-                .to_string();
-            let creation_time = split
-                .next()
-                .ok_or_else(|| {
-                    Error::Handshake(format!(
-                        "DBus cookie `{}` missing creation time at line {}",
-                        path.to_str().unwrap(),
-                        n
-                    ))
-                })?
-                .to_string();
-            let cookie = split
-                .next()
-                .ok_or_else(|| {
-                    Error::Handshake(format!(
-                        "DBus cookie `{}` missing cookie data at line {}",
-                        path.to_str().unwrap(),
-                        n
-                    ))
-                })?
-                .to_string();
-            cookies.push(Cookie {
-                id,
-                creation_time,
-                cookie,
-            })
+    fn mechanism_data(&mut self, data: Vec<u8>) -> Result<(ClientHandshakeStep, Command)> {
+        use ClientHandshakeStep::*;
+        let mech = self.mechanism()?;
+        match mech.challenge(Some(&data)) {
+            AuthResponse::Data(data) => Ok((WaitingForData, Command::Data(data))),
+            AuthResponse::Ok => Ok((WaitingForData, Command::Data(vec![]))),
+            AuthResponse::Reject => Ok((WaitingForReject, Command::Cancel)),
         }
-        Ok(cookies)
-    }
-
-    fn lookup(name: &str, id: &str) -> Result<String> {
-        let keyring = Self::read_keyring(name)?;
-        let c = keyring
-            .iter()
-            .find(|c| c.id == id)
-            .ok_or_else(|| Error::Handshake(format!("DBus cookie ID {} not found", id)))?;
-        Ok(c.cookie.to_string())
     }
 }
 
@@ -363,7 +299,10 @@ impl<S: Socket> Handshake<S> for ClientHandshake<S> {
         use ClientHandshakeStep::*;
         if self.send_buffer.is_empty() {
             match self.step {
-                WaitingForOK | WaitingForData | WaitingForAgreeUnixFD => IoOperation::Read,
+                WaitingForData
+                | WaitingForReject
+                | WaitingForAgreeUnixFD
+                | WaitingForAgreeCompression => IoOperation::Read,
                 Init | MechanismInit | Done => IoOperation::None,
             }
         } else {
@@ -377,22 +316,44 @@ impl<S: Socket> Handshake<S> for ClientHandshake<S> {
             self.flush_buffer()?;
             let (next_step, cmd) = match self.step {
                 Init | MechanismInit => self.mechanism_init()?,
-                WaitingForData | WaitingForOK => {
+                WaitingForData => {
                     let reply = self.read_command()?;
-                    match (self.step, reply) {
-                        (_, Command::Data(data)) => self.mechanism_data(data)?,
-                        (_, Command::Rejected(_)) => {
-                            self.mechanisms.pop_front();
+                    match reply {
+                        Command::Data(data) => self.mechanism_data(data)?,
+                        Command::Rejected(_) => {
+                            self.next_mechanism();
                             self.step = MechanismInit;
                             continue;
                         }
-                        (WaitingForOK, Command::Ok(guid)) => {
+                        Command::Ok(guid) => {
+                            log::debug!(target: logging::HANDSHAKE, "Authenticated to {}", guid);
                             self.server_guid = Some(guid);
-                            (WaitingForAgreeUnixFD, Command::NegotiateUnixFD)
+                            if self.socket.can_pass_unix_fd() {
+                                (WaitingForAgreeUnixFD, Command::NegotiateUnixFD)
+                            } else {
+                                self.negotiate_compression()
+                            }
+                        }
+                        Command::Error(_) => (WaitingForReject, Command::Cancel),
+                        reply => {
+                            return Err(Error::Handshake(format!(
+                                "Unexpected server AUTH reply: {}",
+                                reply
+                            )))
+                        }
+                    }
+                }
+                WaitingForReject => {
+                    let reply = self.read_command()?;
+                    match reply {
+                        Command::Rejected(_) => {
+                            self.next_mechanism();
+                            self.step = MechanismInit;
+                            continue;
                         }
-                        (_, reply) => {
+                        reply => {
                             return Err(Error::Handshake(format!(
-                                "Unexpected server AUTH OK reply: {}",
+                                "Unexpected server CANCEL reply: {}",
                                 reply
                             )))
                         }
@@ -410,6 +371,20 @@ impl<S: Socket> Handshake<S> for ClientHandshake<S> {
                             )));
                         }
                     }
+                    self.negotiate_compression()
+                }
+                WaitingForAgreeCompression => {
+                    let reply = self.read_command()?;
+                    match reply {
+                        Command::AgreeCompression => self.cap_compression = true,
+                        Command::Error(_) => self.cap_compression = false,
+                        _ => {
+                            return Err(Error::Handshake(format!(
+                                "Unexpected server compression reply: {}",
+                                reply
+                            )));
+                        }
+                    }
                     (Done, Command::Begin)
                 }
                 Done => return Ok(()),
@@ -448,10 +423,16 @@ impl<S: Socket> Handshake<S> for ClientHandshake<S> {
 
     fn try_finish(self) -> std::result::Result<Authenticated<S>, Self> {
         if let ClientHandshakeStep::Done = self.step {
+            let mut conn = Connection::wrap(self.socket);
+            if self.cap_compression {
+                conn.set_body_compression(self.compression);
+            }
+
             Ok(Authenticated {
-                conn: Connection::wrap(self.socket),
+                conn,
                 server_guid: self.server_guid.unwrap(),
                 cap_unix_fd: self.cap_unix_fd,
+                address: None,
             })
         } else {
             Err(self)
@@ -472,6 +453,8 @@ impl<S: Socket> Handshake<S> for ClientHandshake<S> {
 enum ServerHandshakeStep {
     WaitingForNull,
     WaitingForAuth,
+    SendingAuthData,
+    WaitingForData,
     SendingAuthOK,
     SendingAuthError,
     WaitingForBegin,
@@ -504,21 +487,67 @@ pub struct ServerHandshake<S> {
     step: ServerHandshakeStep,
     server_guid: Guid,
     cap_unix_fd: bool,
-    client_uid: u32,
+    // the body size threshold of the compression to agree to, if any
+    compression: Option<usize>,
+    cap_compression: bool,
+    mechanisms: Vec<Box<dyn AuthMechanism>>,
+    // the index of the mechanism of the ongoing AUTH
+    mechanism: usize,
 }
 
 impl<S: Socket> ServerHandshake<S> {
     pub fn new(socket: S, guid: Guid, client_uid: u32) -> ServerHandshake<S> {
+        Self::with_mechanisms(socket, guid, client_uid, vec![])
+    }
+
+    /// Start a handshake on this server socket, accepting the `custom` mechanisms besides the
+    /// built-in ones
+    pub fn with_mechanisms(
+        socket: S,
+        guid: Guid,
+        client_uid: u32,
+        custom: Vec<Box<dyn AuthMechanism>>,
+    ) -> ServerHandshake<S> {
+        Self::start(
+            socket,
+            guid,
+            auth::server_mechanisms(custom, Some(client_uid)),
+        )
+    }
+
+    /// Start a handshake on this server socket, not knowing the credentials of the client (e.g
+    /// over TCP)
+    ///
+    /// Besides the `custom` mechanisms, clients of the same user are accepted through
+    /// `DBUS_COOKIE_SHA1`.
+    pub fn without_credentials(
+        socket: S,
+        guid: Guid,
+        custom: Vec<Box<dyn AuthMechanism>>,
+    ) -> ServerHandshake<S> {
+        Self::start(socket, guid, auth::server_mechanisms(custom, None))
+    }
+
+    fn start(socket: S, guid: Guid, mechanisms: Vec<Box<dyn AuthMechanism>>) -> ServerHandshake<S> {
+        set_up_socket(&socket);
         ServerHandshake {
             socket,
             buffer: Vec::new(),
             step: ServerHandshakeStep::WaitingForNull,
             server_guid: guid,
             cap_unix_fd: false,
-            client_uid,
+            compression: None,
+            cap_compression: false,
+            mechanisms,
+            mechanism: 0,
         }
     }
 
+    /// Agree to compress the bodies longer than `threshold` bytes, if the client offers it
+    pub(crate) fn set_body_compression(&mut self, threshold: Option<usize>) {
+        self.compression = threshold;
+    }
+
     fn flush_buffer(&mut self) -> Result<()> {
         while !self.buffer.is_empty() {
             let written = self.socket.sendmsg(&self.buffer, &[])?;
@@ -535,6 +564,41 @@ impl<S: Socket> ServerHandshake<S> {
         }
         Ok(())
     }
+
+    // Read the next command of the client, `None` if it's not one we know of.
+    fn read_auth_command(&mut self) -> Result<Option<Command>> {
+        self.read_command()?;
+        let mut line = String::new();
+        (&self.buffer[..]).read_line(&mut line)?;
+        self.buffer.clear();
+
+        Ok(line.parse().ok())
+    }
+
+    fn challenge(&mut self, data: Option<&[u8]>) {
+        match self.mechanisms[self.mechanism].challenge(data) {
+            AuthResponse::Data(data) => {
+                self.buffer = Command::Data(data).into();
+                self.step = ServerHandshakeStep::SendingAuthData;
+            }
+            AuthResponse::Ok => {
+                self.buffer = Command::Ok(self.server_guid.clone()).into();
+                self.step = ServerHandshakeStep::SendingAuthOK;
+            }
+            AuthResponse::Reject => self.reject(),
+        }
+    }
+
+    fn reject(&mut self) {
+        let mechanisms: Vec<String> = self.mechanisms.iter().map(|m| m.name().into()).collect();
+        log::debug!(
+            target: logging::HANDSHAKE,
+            "Client authentication rejected, offering `{}`",
+            mechanisms.join(" "),
+        );
+        self.buffer = Command::Rejected(mechanisms).into();
+        self.step = ServerHandshakeStep::SendingAuthError;
+    }
 }
 
 impl<S: Socket> Handshake<S> for ServerHandshake<S> {
@@ -547,11 +611,13 @@ impl<S: Socket> Handshake<S> for ServerHandshake<S> {
                     // we use poll to wait until the action we need is available
                     let flags = match self.step {
                         ServerHandshakeStep::SendingAuthError
+                        | ServerHandshakeStep::SendingAuthData
                         | ServerHandshakeStep::SendingAuthOK
                         | ServerHandshakeStep::SendingBeginMessage => PollFlags::POLLOUT,
                         ServerHandshakeStep::WaitingForNull
                         | ServerHandshakeStep::WaitingForBegin
-                        | ServerHandshakeStep::WaitingForAuth => PollFlags::POLLIN,
+                        | ServerHandshakeStep::WaitingForAuth
+                        | ServerHandshakeStep::WaitingForData => PollFlags::POLLIN,
                         ServerHandshakeStep::Done => unreachable!(),
                     };
                     wait_on(self.socket.as_raw_fd(), flags)?;
@@ -566,8 +632,10 @@ impl<S: Socket> Handshake<S> for ServerHandshake<S> {
             ServerHandshakeStep::Done => IoOperation::None,
             ServerHandshakeStep::WaitingForNull
             | ServerHandshakeStep::WaitingForAuth
+            | ServerHandshakeStep::WaitingForData
             | ServerHandshakeStep::WaitingForBegin => IoOperation::Read,
             ServerHandshakeStep::SendingAuthOK
+            | ServerHandshakeStep::SendingAuthData
             | ServerHandshakeStep::SendingAuthError
             | ServerHandshakeStep::SendingBeginMessage => IoOperation::Write,
         }
@@ -588,38 +656,44 @@ impl<S: Socket> Handshake<S> for ServerHandshake<S> {
                     }
                     self.step = ServerHandshakeStep::WaitingForAuth;
                 }
-                ServerHandshakeStep::WaitingForAuth => {
-                    self.read_command()?;
-                    let mut reply = String::new();
-                    (&self.buffer[..]).read_line(&mut reply)?;
-                    let mut words = reply.split_whitespace();
-                    match (words.next(), words.next(), words.next(), words.next()) {
-                        (Some("AUTH"), Some("EXTERNAL"), Some(uid), None) => {
-                            let uid = id_from_str(uid)
-                                .map_err(|e| Error::Handshake(format!("Invalid UID: {}", e)))?;
-                            if uid == self.client_uid {
-                                self.buffer = format!("OK {}\r\n", self.server_guid).into();
-                                self.step = ServerHandshakeStep::SendingAuthOK;
-                            } else {
-                                self.buffer = Vec::from(&b"REJECTED EXTERNAL\r\n"[..]);
-                                self.step = ServerHandshakeStep::SendingAuthError;
+                ServerHandshakeStep::WaitingForAuth => match self.read_auth_command()? {
+                    Some(Command::Auth(Some(name), resp)) => {
+                        match self.mechanisms.iter().position(|m| m.name() == name) {
+                            Some(i) => {
+                                self.mechanism = i;
+                                self.challenge(resp.as_deref());
                             }
-                        }
-                        (Some("AUTH"), _, _, _) | (Some("ERROR"), _, _, _) => {
-                            self.buffer = Vec::from(&b"REJECTED EXTERNAL\r\n"[..]);
-                            self.step = ServerHandshakeStep::SendingAuthError;
-                        }
-                        (Some("BEGIN"), None, None, None) => {
-                            return Err(Error::Handshake(
-                                "Received BEGIN while not authenticated".to_string(),
-                            ));
-                        }
-                        _ => {
-                            self.buffer = Vec::from(&b"ERROR Unsupported command\r\n"[..]);
-                            self.step = ServerHandshakeStep::SendingAuthError;
+                            None => self.reject(),
                         }
                     }
+                    Some(Command::Auth(None, _)) | Some(Command::Error(_)) => self.reject(),
+                    Some(Command::Begin) => {
+                        return Err(Error::Handshake(
+                            "Received BEGIN while not authenticated".to_string(),
+                        ));
+                    }
+                    _ => {
+                        self.buffer = Vec::from(&b"ERROR Unsupported command\r\n"[..]);
+                        self.step = ServerHandshakeStep::SendingAuthError;
+                    }
+                },
+                ServerHandshakeStep::SendingAuthData => {
+                    self.flush_buffer()?;
+                    self.step = ServerHandshakeStep::WaitingForData;
                 }
+                ServerHandshakeStep::WaitingForData => match self.read_auth_command()? {
+                    Some(Command::Data(data)) => self.challenge(Some(&data)),
+                    Some(Command::Cancel) | Some(Command::Error(_)) => self.reject(),
+                    Some(Command::Begin) => {
+                        return Err(Error::Handshake(
+                            "Received BEGIN while not authenticated".to_string(),
+                        ));
+                    }
+                    _ => {
+                        self.buffer = Vec::from(&b"ERROR Unsupported command\r\n"[..]);
+                        self.step = ServerHandshakeStep::SendingAuthData;
+                    }
+                },
                 ServerHandshakeStep::SendingAuthError => {
                     self.flush_buffer()?;
                     self.step = ServerHandshakeStep::WaitingForAuth;
@@ -635,21 +709,20 @@ impl<S: Socket> Handshake<S> for ServerHandshake<S> {
                     let mut words = reply.split_whitespace();
                     match (words.next(), words.next()) {
                         (Some("BEGIN"), None) => {
+                            log::debug!(target: logging::HANDSHAKE, "Client authenticated");
                             self.step = ServerHandshakeStep::Done;
                         }
-                        (Some("CANCEL"), None) => {
-                            self.buffer = Vec::from(&b"REJECTED EXTERNAL\r\n"[..]);
-                            self.step = ServerHandshakeStep::SendingAuthError;
-                        }
-                        (Some("ERROR"), _) => {
-                            self.buffer = Vec::from(&b"REJECTED EXTERNAL\r\n"[..]);
-                            self.step = ServerHandshakeStep::SendingAuthError;
-                        }
-                        (Some("NEGOTIATE_UNIX_FD"), None) => {
+                        (Some("CANCEL"), None) | (Some("ERROR"), _) => self.reject(),
+                        (Some("NEGOTIATE_UNIX_FD"), None) if self.socket.can_pass_unix_fd() => {
                             self.cap_unix_fd = true;
                             self.buffer = Vec::from(&b"AGREE_UNIX_FD\r\n"[..]);
                             self.step = ServerHandshakeStep::SendingBeginMessage;
                         }
+                        (Some(NEGOTIATE_COMPRESSION), None) if self.compression.is_some() => {
+                            self.cap_compression = true;
+                            self.buffer = Command::AgreeCompression.into();
+                            self.step = ServerHandshakeStep::SendingBeginMessage;
+                        }
                         _ => {
                             self.buffer = Vec::from(&b"ERROR Unsupported command\r\n"[..]);
                             self.step = ServerHandshakeStep::SendingBeginMessage;
@@ -667,10 +740,16 @@ impl<S: Socket> Handshake<S> for ServerHandshake<S> {
 
     fn try_finish(self) -> std::result::Result<Authenticated<S>, Self> {
         if let ServerHandshakeStep::Done = self.step {
+            let mut conn = Connection::wrap(self.socket);
+            if self.cap_compression {
+                conn.set_body_compression(self.compression);
+            }
+
             Ok(Authenticated {
-                conn: Connection::wrap(self.socket),
+                conn,
                 server_guid: self.server_guid,
                 cap_unix_fd: self.cap_unix_fd,
+                address: None,
             })
         } else {
             Err(self)
@@ -682,39 +761,6 @@ impl<S: Socket> Handshake<S> for ServerHandshake<S> {
     }
 }
 
-fn id_from_str(s: &str) -> std::result::Result<u32, Box<dyn std::error::Error>> {
-    let mut id = String::new();
-    for s in s.as_bytes().chunks(2) {
-        let c = char::from(u8::from_str_radix(std::str::from_utf8(s)?, 16)?);
-        id.push(c);
-    }
-    Ok(id.parse::<u32>()?)
-}
-
-impl fmt::Display for Mechanism {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mech = match self {
-            Mechanism::External => "EXTERNAL",
-            Mechanism::Cookie => "DBUS_COOKIE_SHA1",
-            Mechanism::Anonymous => "ANONYMOUS",
-        };
-        write!(f, "{}", mech)
-    }
-}
-
-impl FromStr for Mechanism {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<Self> {
-        match s {
-            "EXTERNAL" => Ok(Mechanism::External),
-            "DBUS_COOKIE_SHA1" => Ok(Mechanism::Cookie),
-            "ANONYMOUS" => Ok(Mechanism::Anonymous),
-            _ => Err(Error::Handshake(format!("Unknown mechanism: {}", s))),
-        }
-    }
-}
-
 impl From<Command> for Vec<u8> {
     fn from(c: Command) -> Self {
         c.to_string().into()
@@ -725,12 +771,13 @@ impl fmt::Display for Command {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let cmd = match self {
             Command::Auth(mech, resp) => match (mech, resp) {
-                (Some(mech), Some(resp)) => format!("AUTH {} {}", mech, resp),
+                (Some(mech), Some(resp)) => format!("AUTH {} {}", mech, hex::encode(resp)),
                 (Some(mech), None) => format!("AUTH {}", mech),
                 _ => "AUTH".into(),
             },
             Command::Cancel => "CANCEL".into(),
             Command::Begin => "BEGIN".into(),
+            Command::Data(data) if data.is_empty() => "DATA".into(),
             Command::Data(data) => {
                 format!("DATA {}", hex::encode(data))
             }
@@ -752,6 +799,8 @@ impl fmt::Display for Command {
                 format!("OK {}", guid)
             }
             Command::AgreeUnixFD => "AGREE_UNIX_FD".into(),
+            Command::NegotiateCompression => NEGOTIATE_COMPRESSION.into(),
+            Command::AgreeCompression => AGREE_COMPRESSION.into(),
         };
         write!(f, "{}\r\n", cmd)
     }
@@ -770,26 +819,21 @@ impl FromStr for Command {
         let mut words = s.split_ascii_whitespace();
         let cmd = match words.next() {
             Some("AUTH") => {
-                let mech = if let Some(m) = words.next() {
-                    Some(m.parse()?)
-                } else {
-                    None
-                };
-                let resp = words.next().map(|s| s.into());
+                let mech = words.next().map(|m| m.into());
+                let resp = words.next().map(hex::decode).transpose()?;
                 Command::Auth(mech, resp)
             }
             Some("CANCEL") => Command::Cancel,
             Some("BEGIN") => Command::Begin,
             Some("DATA") => {
-                let data = words
-                    .next()
-                    .ok_or_else(|| Error::Handshake("Missing DATA data".into()))?;
+                // An empty response is sent as a bare `DATA`.
+                let data = words.next().unwrap_or("");
                 Command::Data(hex::decode(data)?)
             }
             Some("ERROR") => Command::Error(s.into()),
             Some("NEGOTIATE_UNIX_FD") => Command::NegotiateUnixFD,
             Some("REJECTED") => {
-                let mechs = words.map(|m| m.parse()).collect::<Result<_>>()?;
+                let mechs = words.map(|m| m.into()).collect();
                 Command::Rejected(mechs)
             }
             Some("OK") => {
@@ -799,6 +843,8 @@ impl FromStr for Command {
                 Command::Ok(guid.parse()?)
             }
             Some("AGREE_UNIX_FD") => Command::AgreeUnixFD,
+            Some(NEGOTIATE_COMPRESSION) => Command::NegotiateCompression,
+            Some(AGREE_COMPRESSION) => Command::AgreeCompression,
             _ => return Err(Error::Handshake(format!("Unknown command: {}", s))),
         };
         Ok(cmd)
@@ -807,12 +853,13 @@ impl FromStr for Command {
 
 #[cfg(test)]
 mod tests {
+    use nix::unistd::Uid;
     use std::os::unix::net::UnixStream;
     use test_env_log::test;
 
     use super::*;
 
-    use crate::Guid;
+    use crate::{CookieError, Guid};
 
     #[test]
     fn handshake() {
@@ -848,4 +895,100 @@ mod tests {
         assert_eq!(client.server_guid, server.server_guid);
         assert_eq!(client.cap_unix_fd, server.cap_unix_fd);
     }
+
+    #[test]
+    fn compression() {
+        // Only enabled if both sides want it, each with its own threshold.
+        for &(client_threshold, server_threshold, agreed) in &[
+            (Some(64), Some(128), true),
+            (Some(64), None, false),
+            (None, Some(128), false),
+        ] {
+            let (p0, p1) = UnixStream::pair().unwrap();
+            p0.set_nonblocking(true).unwrap();
+            p1.set_nonblocking(true).unwrap();
+
+            let mut client = ClientHandshake::new(p0);
+            client.set_body_compression(client_threshold);
+            let mut server = ServerHandshake::new(p1, Guid::generate(), Uid::current().into());
+            server.set_body_compression(server_threshold);
+
+            let mut client_done = false;
+            let mut server_done = false;
+            while !(client_done && server_done) {
+                match client.advance_handshake() {
+                    Ok(()) => client_done = true,
+                    Err(Error::Io(e)) => assert!(e.kind() == std::io::ErrorKind::WouldBlock),
+                    Err(e) => panic!("Unexpected error: {:?}", e),
+                }
+
+                match server.advance_handshake() {
+                    Ok(()) => server_done = true,
+                    Err(Error::Io(e)) => assert!(e.kind() == std::io::ErrorKind::WouldBlock),
+                    Err(e) => panic!("Unexpected error: {:?}", e),
+                }
+            }
+
+            let client = client.try_finish().unwrap();
+            let server = server.try_finish().unwrap();
+            // File descriptors are still negotiated before.
+            assert!(client.cap_unix_fd && server.cap_unix_fd);
+            if agreed {
+                assert_eq!(client.conn.body_compression(), client_threshold);
+                assert_eq!(server.conn.body_compression(), server_threshold);
+            } else {
+                assert_eq!(client.conn.body_compression(), None);
+                assert_eq!(server.conn.body_compression(), None);
+            }
+        }
+    }
+
+    // Gives up right away, knowing why.
+    #[derive(Debug)]
+    struct Failing;
+
+    impl AuthMechanism for Failing {
+        fn name(&self) -> &str {
+            "X_ZBUS_FAILING"
+        }
+
+        fn challenge(&mut self, _: Option<&[u8]>) -> AuthResponse {
+            AuthResponse::Reject
+        }
+
+        fn take_error(&mut self) -> Option<Error> {
+            Some(CookieError::Locked("/nowhere".into()).into())
+        }
+    }
+
+    #[test]
+    fn mechanism_error() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        p0.set_nonblocking(true).unwrap();
+        p1.set_nonblocking(true).unwrap();
+
+        // The server rejects all the other mechanisms of the client.
+        let mut client = ClientHandshake::with_mechanisms(p0, vec![Box::new(Failing)]);
+        let mut server = ServerHandshake::new(p1, Guid::generate(), Uid::current().as_raw() + 1);
+
+        let err = loop {
+            match client.advance_handshake() {
+                Ok(()) => panic!("client unexpectedly authenticated"),
+                Err(Error::Io(e)) => assert!(e.kind() == std::io::ErrorKind::WouldBlock),
+                Err(e) => break e,
+            }
+
+            match server.advance_handshake() {
+                Ok(()) => panic!("server unexpectedly authenticated the client"),
+                Err(Error::Io(e)) => assert!(e.kind() == std::io::ErrorKind::WouldBlock),
+                Err(e) => panic!("Unexpected error: {:?}", e),
+            }
+        };
+
+        // Rather than a generic error, the client fails with the error of the failing mechanism.
+        match err {
+            Error::Cookie(CookieError::Locked(path)) => assert_eq!(path.to_str(), Some("/nowhere")),
+            e => panic!("Unexpected error: {:?}", e),
+        }
+    }
 }
@@ -0,0 +1,408 @@
+use async_io::Timer;
+use nix::unistd::dup;
+use static_assertions::assert_impl_all;
+use std::{
+    cmp::min,
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    io::{self, ErrorKind},
+    mem,
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use crate::{raw::Socket, OwnedFd};
+
+// The number of bytes written on one side and not read yet on the other, after which the writes
+// block, like with the buffers of a socket.
+const CHANNEL_CAPACITY: usize = 64 * 1024;
+
+/// The conditions of the channel between the connections of an in-memory pair.
+///
+/// By default, the bytes written on one side can be read on the other right away and as fast as
+/// they're written, up to 64 KiB waiting to be read. A latency and a cap on the byte rate can be
+/// set, e.g to test how a service copes with slow or busy peers.
+///
+/// # Example
+///
+/// ```
+/// # use std::time::Duration;
+/// use zbus::{in_memory_pair_with, InMemoryOptions};
+///
+/// let (service, client) = in_memory_pair_with(
+///     InMemoryOptions::new()
+///         .latency(Duration::from_millis(20))
+///         .byte_rate(1024 * 1024),
+/// )?;
+/// # Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InMemoryOptions {
+    latency: Duration,
+    byte_rate: Option<u64>,
+}
+
+assert_impl_all!(InMemoryOptions: Send, Sync, Unpin);
+
+impl InMemoryOptions {
+    /// Options for a channel without latency nor byte rate cap.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delay the bytes written on either side by `latency` before they can be read on the other.
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Cap the bytes written by either side to `bytes_per_sec` bytes per second.
+    ///
+    /// The writes beyond the cap block until the rate allows them, so the messages to send queue
+    /// up in the connection, as with a peer not keeping up.
+    ///
+    /// # Panics
+    ///
+    /// If `bytes_per_sec` is 0.
+    pub fn byte_rate(mut self, bytes_per_sec: u64) -> Self {
+        assert!(bytes_per_sec > 0, "the byte rate can't be 0");
+        self.byte_rate = Some(bytes_per_sec);
+        self
+    }
+}
+
+/// Create the two sides of an in-memory channel.
+pub(crate) fn pair(options: &InMemoryOptions) -> (InMemorySocket, InMemorySocket) {
+    let a_to_b = Arc::new(Mutex::new(Channel::default()));
+    let b_to_a = Arc::new(Mutex::new(Channel::default()));
+    let a = End {
+        incoming: b_to_a.clone(),
+        outgoing: a_to_b.clone(),
+        options: options.clone(),
+    };
+    let b = End {
+        incoming: a_to_b,
+        outgoing: b_to_a,
+        options: options.clone(),
+    };
+
+    (InMemorySocket(Arc::new(a)), InMemorySocket(Arc::new(b)))
+}
+
+/// One side of an in-memory channel, see [`pair`].
+///
+/// It has no file descriptor, so the asynchronous connections poll it for readiness through
+/// [`InMemorySocket::poll_readable`] and [`InMemorySocket::poll_writable`] instead. The file
+/// descriptors sent through it are duplicated, as the kernel does for unix sockets, and received
+/// along with the first bytes they were sent with.
+#[derive(Clone)]
+pub(crate) struct InMemorySocket(Arc<End>);
+
+assert_impl_all!(InMemorySocket: Send, Sync, Unpin);
+
+impl InMemorySocket {
+    pub(crate) fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut channel = self.0.incoming.lock().expect("lock poisoned");
+        let channel = &mut *channel;
+        if channel.closed {
+            return Poll::Ready(Ok(()));
+        }
+        if let Some(chunk) = channel.chunks.front() {
+            if chunk.delivered_at <= Instant::now() {
+                return Poll::Ready(Ok(()));
+            }
+
+            // Sent already, but delayed by the latency.
+            let timer = channel
+                .read_timer
+                .get_or_insert_with(|| Timer::at(chunk.delivered_at));
+            timer.set_at(chunk.delivered_at);
+            if Pin::new(timer).poll(cx).is_ready() {
+                return Poll::Ready(Ok(()));
+            }
+        }
+        channel.reader = Some(cx.waker().clone());
+
+        Poll::Pending
+    }
+
+    pub(crate) fn poll_writable(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut channel = self.0.outgoing.lock().expect("lock poisoned");
+        let channel = &mut *channel;
+        if channel.closed {
+            return Poll::Ready(Ok(()));
+        }
+        match channel.next_write {
+            // Held back by the byte rate.
+            Some(next_write) if next_write > Instant::now() => {
+                let timer = channel
+                    .write_timer
+                    .get_or_insert_with(|| Timer::at(next_write));
+                timer.set_at(next_write);
+                if Pin::new(timer).poll(cx).is_ready() {
+                    return Poll::Ready(Ok(()));
+                }
+            }
+            _ if channel.len < CHANNEL_CAPACITY => return Poll::Ready(Ok(())),
+            _ => (),
+        }
+        channel.writer = Some(cx.waker().clone());
+
+        Poll::Pending
+    }
+}
+
+impl Socket for InMemorySocket {
+    fn recvmsg(&mut self, buffer: &mut [u8]) -> io::Result<(usize, Vec<OwnedFd>)> {
+        let mut channel = self.0.incoming.lock().expect("lock poisoned");
+        let now = Instant::now();
+        let mut read = 0;
+        let mut fds = vec![];
+        while read < buffer.len() {
+            let chunk = match channel.chunks.front_mut() {
+                Some(chunk) if chunk.delivered_at <= now => chunk,
+                _ => break,
+            };
+            // Like with unix sockets, bytes sent with file descriptors are never received along
+            // with bytes sent before them.
+            if !chunk.fds.is_empty() {
+                if read > 0 {
+                    break;
+                }
+                fds = mem::take(&mut chunk.fds);
+            }
+
+            let len = min(buffer.len() - read, chunk.bytes.len() - chunk.read);
+            buffer[read..read + len].copy_from_slice(&chunk.bytes[chunk.read..chunk.read + len]);
+            chunk.read += len;
+            read += len;
+            if chunk.read == chunk.bytes.len() {
+                channel.chunks.pop_front();
+            }
+        }
+
+        if read == 0 && !buffer.is_empty() {
+            if channel.closed && channel.chunks.is_empty() {
+                return Ok((0, vec![]));
+            }
+
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        channel.len -= read;
+        if let Some(writer) = channel.writer.take() {
+            writer.wake();
+        }
+
+        Ok((read, fds))
+    }
+
+    fn sendmsg(&mut self, buffer: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+        let mut channel = self.0.outgoing.lock().expect("lock poisoned");
+        if channel.closed {
+            return Err(io::Error::new(
+                ErrorKind::BrokenPipe,
+                "in-memory socket closed",
+            ));
+        }
+        let now = Instant::now();
+        if matches!(channel.next_write, Some(next_write) if next_write > now) {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+
+        let mut len = min(buffer.len(), CHANNEL_CAPACITY - channel.len);
+        if len == 0 && !buffer.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        if let Some(byte_rate) = self.0.options.byte_rate {
+            // At most 10ms worth of bytes at once, for the rate to be smooth.
+            len = min(len, (byte_rate / 100).max(1) as usize);
+            let secs = len as f64 / byte_rate as f64;
+            channel.next_write = Some(now + Duration::from_secs_f64(secs));
+        }
+
+        let fds = fds
+            .iter()
+            .map(|&fd| match dup(fd) {
+                Ok(fd) => Ok(unsafe { OwnedFd::from_raw_fd(fd) }),
+                Err(nix::Error::Sys(e)) => Err(io::Error::from(e)),
+                Err(_) => Err(io::Error::new(ErrorKind::Other, "unhandled nix error")),
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        channel.chunks.push_back(Chunk {
+            bytes: buffer[..len].to_vec(),
+            read: 0,
+            fds,
+            delivered_at: now + self.0.options.latency,
+        });
+        channel.len += len;
+        if let Some(reader) = channel.reader.take() {
+            reader.wake();
+        }
+
+        Ok(len)
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.0.close();
+
+        Ok(())
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Socket>> {
+        Ok(Box::new(self.clone()))
+    }
+}
+
+impl AsRawFd for InMemorySocket {
+    /// There's no file descriptor behind an in-memory channel, so this is always -1.
+    fn as_raw_fd(&self) -> RawFd {
+        -1
+    }
+}
+
+impl fmt::Debug for InMemorySocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InMemorySocket")
+            .field("options", &self.0.options)
+            .finish()
+    }
+}
+
+// The handles of one side of an in-memory channel. The channel is closed once they're all dropped.
+struct End {
+    incoming: Arc<Mutex<Channel>>,
+    outgoing: Arc<Mutex<Channel>>,
+    options: InMemoryOptions,
+}
+
+impl End {
+    fn close(&self) {
+        for channel in [&self.incoming, &self.outgoing].iter() {
+            channel.lock().expect("lock poisoned").close();
+        }
+    }
+}
+
+impl Drop for End {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+// One direction of an in-memory channel.
+#[derive(Default)]
+struct Channel {
+    chunks: VecDeque<Chunk>,
+    // The number of bytes in `chunks` not read yet.
+    len: usize,
+    closed: bool,
+    // The tasks waiting for bytes to read, and for room to write them.
+    reader: Option<Waker>,
+    writer: Option<Waker>,
+    // When the byte rate allows writing again.
+    next_write: Option<Instant>,
+    // Fire when the first chunk is delivered, and when the byte rate allows writing again.
+    read_timer: Option<Timer>,
+    write_timer: Option<Timer>,
+}
+
+impl Channel {
+    fn close(&mut self) {
+        self.closed = true;
+        self.read_timer = None;
+        self.write_timer = None;
+        for waker in self.reader.take().into_iter().chain(self.writer.take()) {
+            waker.wake();
+        }
+    }
+}
+
+// The bytes of one write, and the file descriptors sent along.
+struct Chunk {
+    bytes: Vec<u8>,
+    // The number of `bytes` read already.
+    read: usize,
+    fds: Vec<OwnedFd>,
+    delivered_at: Instant,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::unix::io::AsRawFd,
+        time::{Duration, Instant},
+    };
+
+    use futures_util::future::poll_fn;
+    use nix::sys::stat::fstat;
+    use ntest::timeout;
+    use test_env_log::test;
+
+    use super::{pair, InMemoryOptions};
+    use crate::raw::Socket;
+
+    #[test]
+    #[timeout(1000)]
+    fn fds_come_with_their_bytes() {
+        let (mut a, mut b) = pair(&InMemoryOptions::new());
+        let stdout = std::io::stdout();
+        assert_eq!(a.sendmsg(b"one", &[]).unwrap(), 3);
+        assert_eq!(a.sendmsg(b"two", &[stdout.as_raw_fd()]).unwrap(), 3);
+
+        let mut buffer = [0; 16];
+        let (len, fds) = b.recvmsg(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"one");
+        assert!(fds.is_empty());
+        let (len, fds) = b.recvmsg(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"two");
+        assert_eq!(fds.len(), 1);
+        assert_ne!(fds[0].as_raw_fd(), stdout.as_raw_fd());
+        assert_eq!(
+            fstat(fds[0].as_raw_fd()).unwrap().st_ino,
+            fstat(stdout.as_raw_fd()).unwrap().st_ino
+        );
+
+        let err = b.recvmsg(&mut buffer).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+        drop(a);
+        assert_eq!(b.recvmsg(&mut buffer).unwrap().0, 0);
+        assert!(b.sendmsg(b"three", &[]).is_err());
+    }
+
+    #[test]
+    #[timeout(1000)]
+    fn latency_and_byte_rate() {
+        let options = InMemoryOptions::new()
+            .latency(Duration::from_millis(50))
+            .byte_rate(10_000);
+        let (mut a, mut b) = pair(&options);
+        let start = Instant::now();
+
+        async_io::block_on(async {
+            let mut written = 0;
+            while written < 1000 {
+                poll_fn(|cx| a.poll_writable(cx)).await.unwrap();
+                match a.sendmsg(&[0; 1000][written..], &[]) {
+                    Ok(len) => written += len,
+                    Err(e) => assert_eq!(e.kind(), std::io::ErrorKind::WouldBlock),
+                }
+            }
+            let mut buffer = [0; 1000];
+            let mut read = 0;
+            while read < 1000 {
+                poll_fn(|cx| b.poll_readable(cx)).await.unwrap();
+                match b.recvmsg(&mut buffer) {
+                    Ok((len, _)) => read += len,
+                    Err(e) => assert_eq!(e.kind(), std::io::ErrorKind::WouldBlock),
+                }
+            }
+        });
+
+        // 1000 bytes at 10000 bytes per second, the last 100 of them delivered 50ms later.
+        assert!(start.elapsed() >= Duration::from_millis(140));
+    }
+}
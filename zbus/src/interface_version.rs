@@ -0,0 +1,99 @@
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Write,
+    rc::Rc,
+};
+
+use zvariant::{OwnedValue, Value};
+
+use crate::{
+    composite_interface::members_xml, fdo, Connection, Interface, InterfaceMetadata, Message,
+    Result,
+};
+
+// The `Interface` registered for another version of an interface, through
+// `ObjectServer::at_versions`.
+//
+// Everything is forwarded to the implementation registered under the name of the interface, which
+// is shared with it. Only the introspection is different, as it's under the name of the version.
+pub(crate) struct Version {
+    name: &'static str,
+    iface: Rc<RefCell<dyn Interface>>,
+}
+
+impl Version {
+    pub(crate) fn new(name: &'static str, iface: Rc<RefCell<dyn Interface>>) -> Self {
+        Self { name, iface }
+    }
+
+    /// Whether this is a version of the interface `I`.
+    pub(crate) fn is_of<I: Interface>(&self) -> bool {
+        <dyn Interface as Any>::type_id(&*self.iface.borrow()) == TypeId::of::<I>()
+    }
+}
+
+impl Interface for Version {
+    fn name() -> &'static str {
+        // Only called for the interfaces registered by type, which this type can't be.
+        unreachable!("interface versions are registered under their own name")
+    }
+
+    fn metadata(&self) -> Option<&'static InterfaceMetadata> {
+        self.iface.borrow().metadata()
+    }
+
+    fn get(&self, property_name: &str) -> Option<fdo::Result<OwnedValue>> {
+        self.iface.borrow().get(property_name)
+    }
+
+    fn get_all(&self) -> HashMap<String, OwnedValue> {
+        self.iface.borrow().get_all()
+    }
+
+    fn try_get_all(&self) -> fdo::Result<HashMap<String, OwnedValue>> {
+        self.iface.borrow().try_get_all()
+    }
+
+    fn tracked_properties(&self) -> HashMap<&'static str, OwnedValue> {
+        self.iface.borrow().tracked_properties()
+    }
+
+    fn set(&mut self, property_name: &str, value: &Value<'_>) -> Option<fdo::Result<()>> {
+        self.iface.borrow_mut().set(property_name, value)
+    }
+
+    fn set_multiple(
+        &mut self,
+        properties: &HashMap<String, OwnedValue>,
+    ) -> Option<fdo::Result<()>> {
+        self.iface.borrow_mut().set_multiple(properties)
+    }
+
+    // Like for composite interfaces, the `&mut self` methods are dispatched here as well, as the
+    // implementation has its own `RefCell`.
+    fn call(&self, connection: &Connection, msg: &Message, name: &str) -> Option<Result<u32>> {
+        let res = self.iface.borrow().call(connection, msg, name);
+        res.or_else(|| self.iface.borrow_mut().call_mut(connection, msg, name))
+    }
+
+    fn call_mut(&mut self, _: &Connection, _: &Message, _: &str) -> Option<Result<u32>> {
+        None
+    }
+
+    fn introspect_to_writer(&self, writer: &mut dyn Write, level: usize) {
+        writeln!(
+            writer,
+            r#"{:indent$}<interface name="{}">"#,
+            "",
+            self.name,
+            indent = level
+        )
+        .unwrap();
+        for line in members_xml(&*self.iface.borrow(), level) {
+            writeln!(writer, "{}", line).unwrap();
+        }
+        writeln!(writer, "{:indent$}</interface>", "", indent = level).unwrap();
+    }
+}
@@ -0,0 +1,393 @@
+#![cfg(feature = "xml")]
+
+use async_io::block_on;
+use futures_core::{future::BoxFuture, stream::Stream};
+use futures_util::{
+    future::FutureExt,
+    stream::{FuturesUnordered, StreamExt},
+};
+use static_assertions::assert_impl_all;
+use std::{
+    collections::VecDeque,
+    convert::{TryFrom, TryInto},
+    fmt,
+    pin::Pin,
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use zvariant::{ObjectPath, OwnedObjectPath};
+
+use crate::{azync, logging, xml::Node, Connection, Error, Result};
+
+type Filter = Arc<dyn Fn(&ObjectPath<'_>) -> bool + Send + Sync>;
+
+/// The settings of a walk through a remote object tree, see [`introspect_tree`].
+///
+/// By default, the whole tree is walked, introspecting up to 16 objects at once.
+///
+/// [`introspect_tree`]: fn.introspect_tree.html
+#[derive(Clone)]
+pub struct IntrospectTreeOptions {
+    max_depth: Option<usize>,
+    concurrency: usize,
+    filter: Option<Filter>,
+}
+
+assert_impl_all!(IntrospectTreeOptions: Send, Sync, Unpin);
+
+impl IntrospectTreeOptions {
+    /// Options for walking the whole tree, introspecting up to 16 objects at once.
+    pub fn new() -> Self {
+        Self {
+            max_depth: None,
+            concurrency: 16,
+            filter: None,
+        }
+    }
+
+    /// Only walk the objects up to `max_depth` levels below the root, which is at depth 0.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Introspect up to `concurrency` objects at once.
+    ///
+    /// # Panics
+    ///
+    /// If `concurrency` is 0.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        assert!(concurrency > 0, "the concurrency can't be 0");
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Only walk the objects for which `filter` returns `true`, skipping the subtrees of the
+    /// others.
+    ///
+    /// The root is always walked.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&ObjectPath<'_>) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+}
+
+impl Default for IntrospectTreeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for IntrospectTreeOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntrospectTreeOptions")
+            .field("max_depth", &self.max_depth)
+            .field("concurrency", &self.concurrency)
+            .field("filter", &self.filter.is_some())
+            .finish()
+    }
+}
+
+/// Walk the object tree of `destination` from `root`, introspecting each object.
+///
+/// The children of each object are found in its introspection data and introspected in turn, with
+/// several calls in flight at once, as set through `options`. This is much faster than one call at
+/// a time for services with many objects.
+///
+/// Each object comes with its introspection data, or the error introspecting it, which doesn't
+/// stop the walk. The objects come in the order their introspection completes, with parents always
+/// before their children.
+///
+/// ```no_run
+/// use zbus::{
+///     fdo::{introspect_tree, IntrospectTreeOptions},
+///     Connection,
+/// };
+///
+/// let connection = Connection::new_system()?;
+/// let options = IntrospectTreeOptions::new()
+///     .concurrency(32)
+///     .filter(|path| !path.as_str().starts_with("/org/freedesktop/systemd1/job"));
+/// let objects = introspect_tree(
+///     &connection,
+///     "org.freedesktop.systemd1",
+///     "/org/freedesktop/systemd1",
+///     options,
+/// )?;
+/// for (path, node) in objects {
+///     match node {
+///         Ok(node) => println!("{}: {} interfaces", path.as_str(), node.interfaces().len()),
+///         Err(e) => println!("{}: {}", path.as_str(), e),
+///     }
+/// }
+/// # Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+/// ```
+pub fn introspect_tree<'p, P, E>(
+    conn: &Connection,
+    destination: &str,
+    root: P,
+    options: IntrospectTreeOptions,
+) -> Result<IntrospectTree>
+where
+    P: TryInto<ObjectPath<'p>, Error = E>,
+    E: Into<Error>,
+{
+    introspect_tree_async(conn.inner(), destination, root, options).map(IntrospectTree)
+}
+
+/// The asynchronous sibling of [`introspect_tree`].
+///
+/// [`introspect_tree`]: fn.introspect_tree.html
+pub fn introspect_tree_async<'p, P, E>(
+    conn: &azync::Connection,
+    destination: &str,
+    root: P,
+    options: IntrospectTreeOptions,
+) -> Result<AsyncIntrospectTree>
+where
+    P: TryInto<ObjectPath<'p>, Error = E>,
+    E: Into<Error>,
+{
+    let root = root.try_into().map_err(Into::into)?;
+    let mut queue = VecDeque::new();
+    queue.push_back((root.into(), 0));
+
+    Ok(AsyncIntrospectTree {
+        conn: conn.clone(),
+        destination: destination.to_string(),
+        options,
+        queue,
+        in_flight: FuturesUnordered::new(),
+    })
+}
+
+/// An [`Iterator`] over the objects of a remote object tree, with their introspection data.
+///
+/// Use [`introspect_tree`] to create an instance of this type.
+///
+/// [`Iterator`]: https://doc.rust-lang.org/std/iter/trait.Iterator.html
+/// [`introspect_tree`]: fn.introspect_tree.html
+pub struct IntrospectTree(AsyncIntrospectTree);
+
+assert_impl_all!(IntrospectTree: Send, Unpin);
+
+impl Iterator for IntrospectTree {
+    type Item = (OwnedObjectPath, Result<Node>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        block_on(self.0.next())
+    }
+}
+
+/// A [`Stream`] over the objects of a remote object tree, with their introspection data.
+///
+/// Use [`introspect_tree_async`] to create an instance of this type.
+///
+/// [`Stream`]: https://docs.rs/futures/0.3/futures/stream/trait.Stream.html
+/// [`introspect_tree_async`]: fn.introspect_tree_async.html
+pub struct AsyncIntrospectTree {
+    conn: azync::Connection,
+    destination: String,
+    options: IntrospectTreeOptions,
+    // The objects to introspect once there's room in `in_flight`, with their depth.
+    queue: VecDeque<(OwnedObjectPath, usize)>,
+    in_flight: FuturesUnordered<BoxFuture<'static, Introspected>>,
+}
+
+assert_impl_all!(AsyncIntrospectTree: Send, Unpin);
+
+type Introspected = (OwnedObjectPath, usize, Result<Node>);
+
+impl AsyncIntrospectTree {
+    fn enqueue_children(&mut self, path: &ObjectPath<'_>, depth: usize, node: &Node) {
+        if self
+            .options
+            .max_depth
+            .map_or(false, |max_depth| depth >= max_depth)
+        {
+            return;
+        }
+
+        for name in node.nodes().into_iter().filter_map(|child| child.name()) {
+            let child = match path.as_str() {
+                "/" => format!("/{}", name),
+                parent => format!("{}/{}", parent, name),
+            };
+            let child = match OwnedObjectPath::try_from(child) {
+                Ok(child) => child,
+                Err(e) => {
+                    log::warn!(
+                        target: logging::PROXY,
+                        "Invalid child node `{}` of `{}`: {}",
+                        name,
+                        path.as_str(),
+                        e,
+                    );
+
+                    continue;
+                }
+            };
+            if self.options.filter.as_ref().map_or(true, |f| f(&child)) {
+                self.queue.push_back((child, depth + 1));
+            }
+        }
+    }
+}
+
+impl Stream for AsyncIntrospectTree {
+    type Item = (OwnedObjectPath, Result<Node>);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        while this.in_flight.len() < this.options.concurrency {
+            let (path, depth) = match this.queue.pop_front() {
+                Some(next) => next,
+                None => break,
+            };
+            let introspection = introspect(this.conn.clone(), this.destination.clone(), path);
+            this.in_flight.push(
+                introspection
+                    .map(move |(path, node)| (path, depth, node))
+                    .boxed(),
+            );
+        }
+
+        let (path, depth, node) = match this.in_flight.poll_next_unpin(cx) {
+            Poll::Ready(Some(introspected)) => introspected,
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+        if let Ok(node) = &node {
+            this.enqueue_children(&path, depth, node);
+        }
+
+        Poll::Ready(Some((path, node)))
+    }
+}
+
+async fn introspect(
+    conn: azync::Connection,
+    destination: String,
+    path: OwnedObjectPath,
+) -> (OwnedObjectPath, Result<Node>) {
+    let node = async {
+        let reply = conn
+            .call_method(
+                Some(&destination),
+                path.as_str(),
+                Some("org.freedesktop.DBus.Introspectable"),
+                "Introspect",
+                &(),
+            )
+            .await?;
+
+        Node::from_str(reply.body::<&str>()?)
+    }
+    .await;
+
+    (path, node)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, collections::HashMap, convert::TryFrom, thread};
+
+    use async_io::block_on;
+    use futures_util::StreamExt;
+    use ntest::timeout;
+    use test_env_log::test;
+    use zvariant::OwnedObjectPath;
+
+    use crate::{
+        dbus_interface,
+        fdo::{introspect_tree, introspect_tree_async, IntrospectTreeOptions},
+        in_memory_pair, ObjectServer,
+    };
+
+    struct Leaf {
+        quit: bool,
+    }
+
+    #[dbus_interface(name = "org.zbus.Leaf")]
+    impl Leaf {
+        fn quit(&mut self) {
+            self.quit = true;
+        }
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn walk() {
+        let (service, conn) = in_memory_pair().unwrap();
+
+        let server_thread = thread::spawn(move || {
+            let mut object_server = ObjectServer::new(&service);
+            for i in 0..20 {
+                for j in 0..15 {
+                    let path = format!("/zbus/tree/{}/{}", i, j);
+                    object_server.at(path, Leaf { quit: false }).unwrap();
+                }
+            }
+
+            let quit = Cell::new(false);
+            while !quit.get() {
+                object_server.try_handle_next().unwrap();
+                object_server
+                    .with("/zbus/tree/0/0", |leaf: &Leaf| {
+                        quit.set(leaf.quit);
+
+                        Ok(())
+                    })
+                    .unwrap();
+            }
+        });
+
+        // `/`, `/zbus`, `/zbus/tree`, the 20 branches and their 15 leaves each.
+        let options = IntrospectTreeOptions::new().concurrency(8);
+        let tree: HashMap<_, _> = introspect_tree(&conn, "org.zbus.Tree", "/", options)
+            .unwrap()
+            .collect();
+        assert_eq!(tree.len(), 323);
+        for (path, node) in &tree {
+            let node = node.as_ref().unwrap();
+            let is_leaf = node
+                .interfaces()
+                .iter()
+                .any(|iface| iface.name() == "org.zbus.Leaf");
+            assert_eq!(is_leaf, path.as_str().matches('/').count() == 4);
+        }
+        let branch = OwnedObjectPath::try_from("/zbus/tree/7").unwrap();
+        assert_eq!(tree[&branch].as_ref().unwrap().nodes().len(), 15);
+
+        let options = IntrospectTreeOptions::new().max_depth(3);
+        let walk = introspect_tree_async(conn.inner(), "org.zbus.Tree", "/", options).unwrap();
+        let paths: Vec<_> = block_on(walk.map(|(path, _)| path).collect());
+        assert_eq!(paths.len(), 23);
+        assert_eq!(paths[0].as_str(), "/");
+        assert!(paths
+            .iter()
+            .all(|path| path.as_str().matches('/').count() <= 3));
+
+        // The branches 1 and 10 to 19 are skipped.
+        let options =
+            IntrospectTreeOptions::new().filter(|path| !path.as_str().starts_with("/zbus/tree/1"));
+        let walk = introspect_tree(&conn, "org.zbus.Tree", "/zbus", options).unwrap();
+        assert_eq!(walk.count(), 2 + 9 + 9 * 15);
+
+        // The errors are reported along with the path.
+        let options = IntrospectTreeOptions::new();
+        let mut walk = introspect_tree(&conn, "org.zbus.Tree", "/zbus/nope", options).unwrap();
+        let (path, node) = walk.next().unwrap();
+        assert_eq!(path.as_str(), "/zbus/nope");
+        assert!(node.is_err());
+        assert!(walk.next().is_none());
+
+        conn.call_method(None, "/zbus/tree/0/0", Some("org.zbus.Leaf"), "Quit", &())
+            .unwrap();
+        server_thread.join().unwrap();
+    }
+}
@@ -0,0 +1,469 @@
+use std::{
+    error, fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use nix::unistd::Uid;
+use static_assertions::assert_impl_all;
+
+use crate::{utils::random, Error, Result};
+
+/// The context of the cookies of the `DBUS_COOKIE_SHA1` mechanism, unless the server says
+/// otherwise.
+pub(crate) const DEFAULT_CONTEXT: &str = "org_freedesktop_general";
+
+// The timings and limits of the reference implementation, so that we play well with it on a shared
+// keyring.
+//
+// A new cookie is added once the newest one is this old, in seconds.
+const NEW_COOKIE_AGE: u64 = 5 * 60;
+// Cookies are dropped once this old, leaving the clients some time to answer with the cookie they
+// were asked for.
+const EXPIRED_COOKIE_AGE: u64 = NEW_COOKIE_AGE + 2 * 60;
+// Cookies from further in the future are dropped, in case the clock went backwards.
+const MAX_TIME_TRAVEL: u64 = 5 * 60;
+const MAX_COOKIES: usize = 256;
+const COOKIE_LEN: usize = 24;
+// The reference implementation breaks locks it failed to take for this long, assuming their holder
+// died.
+const STALE_LOCK_AGE: Duration = Duration::from_secs(8);
+
+/// Errors of the `DBUS_COOKIE_SHA1` authentication.
+///
+/// Clients failing to authenticate get the first of these errors, through [`Error::Cookie`], if
+/// no other mechanism worked.
+///
+/// [`Error::Cookie`]: enum.Error.html#variant.Cookie
+#[derive(Debug, PartialEq)]
+pub enum CookieError {
+    /// The keyring directory is accessible to other users, or owned by another one.
+    ///
+    /// The keyrings in it are ignored, as anyone could read or forge their cookies.
+    PermissionsTooOpen(PathBuf),
+    /// The cookie the server asked for isn't in the keyring of the given context.
+    NotFound {
+        /// The context of the keyring.
+        context: String,
+        /// The ID of the cookie.
+        id: u32,
+    },
+    /// The cookie the server asked for is in the keyring, but too old to be used.
+    ///
+    /// Cookies from the future, by more than a few minutes, are considered expired as well.
+    Expired {
+        /// The context of the keyring.
+        context: String,
+        /// The ID of the cookie.
+        id: u32,
+    },
+    /// The keyring is locked by another process, so a cookie can't be added to it.
+    Locked(PathBuf),
+}
+
+assert_impl_all!(CookieError: Send, Sync, Unpin);
+
+impl error::Error for CookieError {}
+
+impl fmt::Display for CookieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CookieError::PermissionsTooOpen(path) => write!(
+                f,
+                "keyring directory `{}` is accessible to other users",
+                path.display()
+            ),
+            CookieError::NotFound { context, id } => {
+                write!(f, "cookie {} not found in keyring `{}`", id, context)
+            }
+            CookieError::Expired { context, id } => {
+                write!(f, "cookie {} of keyring `{}` expired", id, context)
+            }
+            CookieError::Locked(path) => write!(f, "keyring `{}` is locked", path.display()),
+        }
+    }
+}
+
+impl From<CookieError> for Error {
+    fn from(e: CookieError) -> Self {
+        Error::Cookie(e)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Cookie {
+    pub(crate) id: u32,
+    // The creation time, in seconds since the epoch.
+    created: u64,
+    pub(crate) secret: String,
+}
+
+impl Cookie {
+    fn is_expired(&self, now: u64) -> bool {
+        self.created > now.saturating_add(MAX_TIME_TRAVEL)
+            || now.saturating_sub(self.created) > EXPIRED_COOKIE_AGE
+    }
+}
+
+/// A keyring of cookies: a file named after its context, in the keyring directory.
+///
+/// The file holds one cookie per line: its ID, creation time and secret, separated by spaces. It
+/// is only written while holding a lock file next to it, and replaced atomically so that readers
+/// don't need the lock.
+#[derive(Debug)]
+pub(crate) struct Keyring {
+    dir: PathBuf,
+    context: String,
+}
+
+impl Keyring {
+    /// The keyring of the given `context`, in `dir`, or `~/.dbus-keyrings` by default.
+    pub(crate) fn new(dir: Option<&Path>, context: &str) -> Result<Self> {
+        // The context names a file in the keyring directory.
+        if context.is_empty()
+            || !context
+                .chars()
+                .all(|c| c.is_ascii_graphic() && c != '/' && c != '\\' && c != '.')
+        {
+            return Err(Error::Handshake(format!(
+                "Invalid cookie context `{}`",
+                context
+            )));
+        }
+        let dir = match dir {
+            Some(dir) => dir.to_owned(),
+            None => {
+                let home = std::env::var_os("HOME")
+                    .ok_or_else(|| Error::Handshake("Failed to read $HOME".into()))?;
+
+                Path::new(&home).join(".dbus-keyrings")
+            }
+        };
+
+        Ok(Self {
+            dir,
+            context: context.to_owned(),
+        })
+    }
+
+    /// The secret of the cookie `id`, if it's not expired.
+    pub(crate) fn lookup(&self, id: u32) -> Result<String> {
+        self.check_dir()?;
+        let cookie = self
+            .load()?
+            .into_iter()
+            .find(|c| c.id == id)
+            .ok_or_else(|| CookieError::NotFound {
+                context: self.context.clone(),
+                id,
+            })?;
+        if cookie.is_expired(now()?) {
+            return Err(CookieError::Expired {
+                context: self.context.clone(),
+                id,
+            }
+            .into());
+        }
+
+        Ok(cookie.secret)
+    }
+
+    /// A cookie to challenge clients with, added to the keyring if there's none recent enough.
+    ///
+    /// The keyring directory is created if missing.
+    pub(crate) fn current_cookie(&self) -> Result<Cookie> {
+        if let Err(e) = fs::DirBuilder::new().mode(0o700).create(&self.dir) {
+            if e.kind() != io::ErrorKind::AlreadyExists {
+                return Err(e.into());
+            }
+        }
+        self.check_dir()?;
+
+        let now = now()?;
+        if let Some(cookie) = newest(self.load()?, now) {
+            return Ok(cookie);
+        }
+
+        let _lock = self.lock()?;
+        // Someone else may have added one in the meantime.
+        let mut cookies = self.load()?;
+        if let Some(cookie) = newest(cookies.clone(), now) {
+            return Ok(cookie);
+        }
+
+        cookies.retain(|c| !c.is_expired(now));
+        let mut id = random::<u32>();
+        while cookies.iter().any(|c| c.id == id) {
+            id = random();
+        }
+        let cookie = Cookie {
+            id,
+            created: now,
+            secret: hex::encode(random::<[u8; COOKIE_LEN]>()),
+        };
+        cookies.push(cookie.clone());
+        if cookies.len() > MAX_COOKIES {
+            cookies.drain(..cookies.len() - MAX_COOKIES);
+        }
+        self.save(&cookies)?;
+
+        Ok(cookie)
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.join(&self.context)
+    }
+
+    // Like the reference implementation, only care about others reading or writing the keyrings.
+    fn check_dir(&self) -> Result<()> {
+        let metadata = fs::metadata(&self.dir)?;
+        if metadata.uid() != Uid::effective().as_raw() || metadata.mode() & 0o066 != 0 {
+            return Err(CookieError::PermissionsTooOpen(self.dir.clone()).into());
+        }
+
+        Ok(())
+    }
+
+    // The cookies of the keyring, skipping the invalid lines.
+    fn load(&self) -> Result<Vec<Cookie>> {
+        let file = match File::open(self.path()) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut cookies = vec![];
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let mut fields = line.split_ascii_whitespace();
+            let id = fields.next().and_then(|id| id.parse().ok());
+            let created = fields.next().and_then(|created| created.parse().ok());
+            let secret = fields.next();
+            if let (Some(id), Some(created), Some(secret)) = (id, created, secret) {
+                // The first of duplicate IDs wins.
+                if !cookies.iter().any(|c: &Cookie| c.id == id) {
+                    cookies.push(Cookie {
+                        id,
+                        created,
+                        secret: secret.to_owned(),
+                    });
+                }
+            }
+        }
+
+        Ok(cookies)
+    }
+
+    fn save(&self, cookies: &[Cookie]) -> Result<()> {
+        let path = self.path();
+        let tmp_path = self.dir.join(format!(
+            "{}.{}.tmp",
+            self.context,
+            hex::encode(random::<[u8; 4]>())
+        ));
+        let write = || -> io::Result<()> {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&tmp_path)?;
+            for c in cookies {
+                writeln!(file, "{} {} {}", c.id, c.created, c.secret)?;
+            }
+            file.sync_all()?;
+
+            fs::rename(&tmp_path, &path)
+        };
+
+        write().map_err(|e| {
+            let _ = fs::remove_file(&tmp_path);
+
+            e.into()
+        })
+    }
+
+    // Take the lock of the keyring, breaking it if it looks stale.
+    //
+    // This runs as part of the handshake, possibly on an executor thread, so rather than waiting
+    // for the lock like the reference implementation, it fails right away if someone holds it.
+    fn lock(&self) -> Result<KeyringLock> {
+        let path = self.dir.join(format!("{}.lock", self.context));
+        let create = || {
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&path)
+        };
+
+        match create() {
+            Ok(_) => return Ok(KeyringLock(path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+            Err(e) => return Err(e.into()),
+        }
+
+        let stale = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .map_or(false, |age| age > STALE_LOCK_AGE);
+        if stale {
+            let _ = fs::remove_file(&path);
+            if create().is_ok() {
+                return Ok(KeyringLock(path));
+            }
+        }
+
+        Err(CookieError::Locked(self.path()).into())
+    }
+}
+
+// Removes the lock file when dropped.
+#[derive(Debug)]
+struct KeyringLock(PathBuf);
+
+impl Drop for KeyringLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+// The newest of `cookies`, if it's recent enough to challenge clients with.
+fn newest(cookies: Vec<Cookie>, now: u64) -> Option<Cookie> {
+    cookies
+        .into_iter()
+        .filter(|c| !c.is_expired(now))
+        .max_by_key(|c| c.created)
+        .filter(|c| now.saturating_sub(c.created) < NEW_COOKIE_AGE)
+}
+
+fn now() -> Result<u64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .map_err(|e| Error::Handshake(format!("Invalid system time: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        os::unix::fs::{DirBuilderExt, PermissionsExt},
+        path::PathBuf,
+    };
+
+    use super::*;
+
+    fn keyring_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("zbus-keyring-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        dir
+    }
+
+    #[test]
+    fn current_cookie() {
+        let dir = keyring_dir("current");
+        let keyring = Keyring::new(Some(&dir), DEFAULT_CONTEXT).unwrap();
+
+        // The directory and keyring are created on demand, private to the user.
+        let cookie = keyring.current_cookie().unwrap();
+        assert_eq!(
+            fs::metadata(&dir).unwrap().permissions().mode() & 0o777,
+            0o700
+        );
+        let path = dir.join(DEFAULT_CONTEXT);
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(cookie.secret.len(), COOKIE_LEN * 2);
+        assert_eq!(keyring.lookup(cookie.id).unwrap(), cookie.secret);
+        assert!(!dir.join(format!("{}.lock", DEFAULT_CONTEXT)).exists());
+
+        // A recent cookie is reused.
+        assert_eq!(keyring.current_cookie().unwrap(), cookie);
+
+        // Expired ones are dropped, and old ones replaced.
+        let now = now().unwrap();
+        let old = format!(
+            "1 {} {}\n2 {} 0123\n",
+            now - EXPIRED_COOKIE_AGE - 1,
+            "ab".repeat(COOKIE_LEN),
+            now - NEW_COOKIE_AGE - 1,
+        );
+        fs::write(&path, old).unwrap();
+        match keyring.lookup(1).unwrap_err() {
+            Error::Cookie(CookieError::Expired { context, id }) => {
+                assert_eq!((context.as_str(), id), (DEFAULT_CONTEXT, 1))
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+        assert_eq!(keyring.lookup(2).unwrap(), "0123");
+        let cookie = keyring.current_cookie().unwrap();
+        assert!(cookie.id != 1 && cookie.id != 2);
+        let ids: Vec<_> = keyring.load().unwrap().iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![2, cookie.id]);
+        match keyring.lookup(1).unwrap_err() {
+            Error::Cookie(CookieError::NotFound { id: 1, .. }) => (),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn locked() {
+        let dir = keyring_dir("locked");
+        fs::DirBuilder::new().mode(0o700).create(&dir).unwrap();
+        let keyring = Keyring::new(Some(&dir), DEFAULT_CONTEXT).unwrap();
+
+        // Someone else is adding a cookie, so there's none to challenge clients with yet.
+        let lock = dir.join(format!("{}.lock", DEFAULT_CONTEXT));
+        fs::write(&lock, "").unwrap();
+        match keyring.current_cookie().unwrap_err() {
+            Error::Cookie(CookieError::Locked(path)) => assert_eq!(path, keyring.path()),
+            e => panic!("unexpected error: {}", e),
+        }
+        assert!(lock.exists());
+
+        fs::remove_file(&lock).unwrap();
+        keyring.current_cookie().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn permissions_too_open() {
+        let dir = keyring_dir("open");
+        fs::DirBuilder::new().mode(0o700).create(&dir).unwrap();
+        let keyring = Keyring::new(Some(&dir), DEFAULT_CONTEXT).unwrap();
+        let cookie = keyring.current_cookie().unwrap();
+
+        // Others may go through the directory, as long as they can't list or write it.
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o711)).unwrap();
+        assert_eq!(keyring.lookup(cookie.id).unwrap(), cookie.secret);
+
+        for mode in &[0o740, 0o702, 0o777] {
+            fs::set_permissions(&dir, fs::Permissions::from_mode(*mode)).unwrap();
+            match keyring.lookup(cookie.id).unwrap_err() {
+                Error::Cookie(CookieError::PermissionsTooOpen(path)) => assert_eq!(path, dir),
+                e => panic!("unexpected error: {}", e),
+            }
+            assert!(keyring.current_cookie().is_err());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_context() {
+        for context in &["", "../escape", "a/b", "with space", "dot.ted"] {
+            assert!(Keyring::new(None, context).is_err(), "{}", context);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+#![cfg(feature = "launchd")]
+
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    env,
+    ffi::{OsStr, OsString},
+    io,
+    os::unix::ffi::OsStringExt,
+    process::{Command, Output},
+    sync::Mutex,
+    thread,
+};
+
+use crate::{Error, Result};
+
+/// The environment variable overriding the socket path of all the `launchd:` addresses.
+///
+/// The path isn't cached then. This is meant for testing.
+pub(crate) const SOCKET_OVERRIDE_ENV: &str = "ZBUS_LAUNCHD_SOCKET";
+
+// The socket paths resolved so far, by the name of their launchd environment variable. launchd
+// keeps them for the lifetime of the user session, so we don't need to ask it again.
+static SOCKET_PATHS: Lazy<Mutex<HashMap<OsString, OsString>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Runs `launchctl`, so the tests can fake it.
+pub(crate) trait Launchctl {
+    /// Run `launchctl getenv <name>`.
+    fn getenv(&self, name: &OsStr) -> io::Result<Output>;
+}
+
+#[derive(Debug)]
+struct SystemLaunchctl;
+
+impl Launchctl for SystemLaunchctl {
+    fn getenv(&self, name: &OsStr) -> io::Result<Output> {
+        Command::new("launchctl").arg("getenv").arg(name).output()
+    }
+}
+
+/// The path of the socket of a `launchd:env=<env>` address.
+///
+/// `launchctl` is run on a separate thread, if needed, so this doesn't block the executor.
+pub(crate) async fn socket_path(env: &OsStr) -> Result<OsString> {
+    if let Some(path) = cached_socket_path(env, &SOCKET_PATHS) {
+        return Ok(path);
+    }
+
+    let (sender, receiver) = async_channel::bounded(1);
+    let env = env.to_owned();
+    thread::Builder::new()
+        .name("zbus::launchd::socket_path".into())
+        .spawn(move || {
+            let _ = sender.try_send(resolve_socket_path(&env, &SystemLaunchctl, &SOCKET_PATHS));
+        })?;
+
+    receiver.recv().await.unwrap_or_else(|_| {
+        Err(Error::Address(
+            "failed to resolve the launchd socket path".into(),
+        ))
+    })
+}
+
+// The override, or the cached path, if any.
+fn cached_socket_path(env: &OsStr, cache: &Mutex<HashMap<OsString, OsString>>) -> Option<OsString> {
+    if let Some(path) = env::var_os(SOCKET_OVERRIDE_ENV) {
+        return Some(path);
+    }
+
+    cache.lock().expect("lock poisoned").get(env).cloned()
+}
+
+// Resolve the path from our own environment, falling back to asking launchd.
+fn resolve_socket_path(
+    env: &OsStr,
+    launchctl: &dyn Launchctl,
+    cache: &Mutex<HashMap<OsString, OsString>>,
+) -> Result<OsString> {
+    if let Some(path) = cached_socket_path(env, cache) {
+        return Ok(path);
+    }
+
+    let path = match env::var_os(env) {
+        Some(path) if !path.is_empty() => path,
+        _ => launchctl_getenv(env, launchctl)?,
+    };
+    cache
+        .lock()
+        .expect("lock poisoned")
+        .insert(env.to_owned(), path.clone());
+
+    Ok(path)
+}
+
+fn launchctl_getenv(env: &OsStr, launchctl: &dyn Launchctl) -> Result<OsString> {
+    let command = format!("launchctl getenv {}", env.to_string_lossy());
+    let output = launchctl
+        .getenv(env)
+        .map_err(|e| Error::Address(format!("failed to run `{}`: {}", command, e)))?;
+    if !output.status.success() {
+        return Err(Error::Address(format!(
+            "`{}` failed ({}): {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let mut path = output.stdout;
+    while path.last().map_or(false, u8::is_ascii_whitespace) {
+        path.pop();
+    }
+    if path.is_empty() {
+        return Err(Error::Address(format!(
+            "`{}` is not set in the launchd environment",
+            env.to_string_lossy()
+        )));
+    }
+
+    Ok(OsString::from_vec(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        collections::HashMap,
+        env,
+        ffi::{OsStr, OsString},
+        io,
+        os::unix::process::ExitStatusExt,
+        process::{ExitStatus, Output},
+        sync::Mutex,
+    };
+
+    use super::{resolve_socket_path, Launchctl, SOCKET_OVERRIDE_ENV};
+    use crate::Error;
+
+    // Replies to `getenv` with the given result, counting the calls.
+    struct FakeLaunchctl {
+        output: fn() -> io::Result<Output>,
+        calls: Cell<usize>,
+    }
+
+    impl FakeLaunchctl {
+        fn new(output: fn() -> io::Result<Output>) -> Self {
+            Self {
+                output,
+                calls: Cell::new(0),
+            }
+        }
+    }
+
+    impl Launchctl for FakeLaunchctl {
+        fn getenv(&self, _name: &OsStr) -> io::Result<Output> {
+            self.calls.set(self.calls.get() + 1);
+
+            (self.output)()
+        }
+    }
+
+    fn output(status: i32, stdout: &[u8], stderr: &[u8]) -> Output {
+        Output {
+            status: ExitStatus::from_raw(status << 8),
+            stdout: stdout.to_vec(),
+            stderr: stderr.to_vec(),
+        }
+    }
+
+    // All in one test, as the override is shared by the threads of the tests.
+    #[test]
+    fn socket_path() {
+        let env_name = OsStr::new("ZBUS_TEST_LAUNCHD_SOCKET");
+        env::remove_var(SOCKET_OVERRIDE_ENV);
+        env::remove_var(env_name);
+        let cache = Mutex::new(HashMap::new());
+
+        // Missing `launchctl`, e.g in a sandbox.
+        let launchctl = FakeLaunchctl::new(|| Err(io::ErrorKind::NotFound.into()));
+        match resolve_socket_path(env_name, &launchctl, &cache).unwrap_err() {
+            Error::Address(e) => assert!(
+                e.starts_with("failed to run `launchctl getenv ZBUS_TEST_LAUNCHD_SOCKET`: "),
+                "{}",
+                e
+            ),
+            e => panic!("unexpected error: {}", e),
+        }
+        let launchctl = FakeLaunchctl::new(|| Ok(output(1, b"", b"permission denied\n")));
+        match resolve_socket_path(env_name, &launchctl, &cache).unwrap_err() {
+            Error::Address(e) => assert_eq!(
+                e,
+                "`launchctl getenv ZBUS_TEST_LAUNCHD_SOCKET` failed (exit status: 1): permission denied"
+            ),
+            e => panic!("unexpected error: {}", e),
+        }
+        let launchctl = FakeLaunchctl::new(|| Ok(output(0, b"\n", b"")));
+        match resolve_socket_path(env_name, &launchctl, &cache).unwrap_err() {
+            Error::Address(e) => assert_eq!(
+                e,
+                "`ZBUS_TEST_LAUNCHD_SOCKET` is not set in the launchd environment"
+            ),
+            e => panic!("unexpected error: {}", e),
+        }
+        assert!(cache.lock().unwrap().is_empty());
+
+        // launchd is only asked once.
+        let launchctl = FakeLaunchctl::new(|| Ok(output(0, b"/tmp/launchd-1/bus\n", b"")));
+        for _ in 0..2 {
+            assert_eq!(
+                resolve_socket_path(env_name, &launchctl, &cache).unwrap(),
+                "/tmp/launchd-1/bus"
+            );
+        }
+        assert_eq!(launchctl.calls.get(), 1);
+
+        // Our own environment takes precedence over launchd, and the override over anything.
+        let cache = Mutex::new(HashMap::new());
+        env::set_var(env_name, "/tmp/launchd-2/bus");
+        assert_eq!(
+            resolve_socket_path(env_name, &launchctl, &cache).unwrap(),
+            "/tmp/launchd-2/bus"
+        );
+        env::set_var(SOCKET_OVERRIDE_ENV, "/tmp/launchd-3/bus");
+        assert_eq!(
+            resolve_socket_path(env_name, &launchctl, &cache).unwrap(),
+            "/tmp/launchd-3/bus"
+        );
+        env::remove_var(SOCKET_OVERRIDE_ENV);
+        env::remove_var(env_name);
+        assert_eq!(
+            resolve_socket_path(env_name, &launchctl, &cache).unwrap(),
+            OsString::from("/tmp/launchd-2/bus")
+        );
+        assert_eq!(launchctl.calls.get(), 1);
+    }
+}
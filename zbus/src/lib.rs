@@ -125,10 +125,36 @@
 //!   * disable the `internal-executor` feature (which is a default feature).
 //!   * Ensure the [internal executor keeps ticking continuously][iektc].
 //!
+//! Programs running a single-threaded executor per thread can also enable the `local` feature, for
+//! [`azync::LocalConnection`][lc]. It's a `!Send` connection, that spawns no thread and reads the
+//! messages from the futures waiting for them. Its [`azync::LocalObjectServer`][los] serves
+//! interfaces that aren't `Send` either.
+//!
+//! Finally, event loops that are not Rust async runtimes, e.g GLib's used through its C API, can
+//! drive a [`raw::Connection`][rc] themselves, when its socket is readable or writable.
+//!
+//! The timeouts and delays of zbus go through [`sleep`][sl] and [`timeout`][to], which use the
+//! timers of tokio instead of async-io's within a tokio runtime, if the `tokio` feature is enabled.
+//!
+//! ### Transports
+//!
+//! Each transport of the [D-Bus addresses][addr] is behind a default feature: `unix-transport`,
+//! `tcp-transport`, `unixexec-transport` and `launchd`. Programs only ever connecting to some of
+//! them can disable the others to get a smaller binary. Addresses of a disabled transport fail to
+//! parse, with an [`Error::Address`][ea] telling it was disabled at compile time. Connections
+//! over a `UnixStream` or a `TcpStream` set up by the program itself are always supported.
+//!
 //! [book]: https://dbus.pages.freedesktop.org/zbus/
 //! [(not so) low-level]: azync::Connection
 //! [high-level client-side proxy]: https://dbus.pages.freedesktop.org/zbus/async.html#client
 //! [iektc]: `azync::Connection::executor`
+//! [lc]: azync/struct.LocalConnection.html
+//! [los]: azync/struct.LocalObjectServer.html
+//! [rc]: raw/struct.Connection.html
+//! [addr]: https://dbus.freedesktop.org/doc/dbus-specification.html#addresses
+//! [ea]: enum.Error.html#variant.Address
+//! [sl]: fn.sleep.html
+//! [to]: fn.timeout.html
 //!
 //! [^otheros]: Support for other OS exist, but it is not supported to the same extent. D-Bus
 //!   clients in javascript (running from any browser) do exist though. And zbus may also be
@@ -151,6 +177,12 @@ mod error;
 pub use error::*;
 
 mod address;
+mod launchd;
+mod tcp;
+mod unixexec;
+
+mod in_memory;
+pub use in_memory::InMemoryOptions;
 
 mod guid;
 pub use guid::*;
@@ -158,6 +190,9 @@ pub use guid::*;
 mod message;
 pub use message::*;
 
+mod priority;
+pub use priority::*;
+
 mod message_header;
 pub use message_header::*;
 
@@ -167,14 +202,44 @@ pub use message_field::*;
 mod message_fields;
 pub use message_fields::*;
 
+mod dbus_rs;
+
+mod fixed_body;
+pub use fixed_body::*;
+
+mod peer_stats;
+pub use peer_stats::*;
+
+mod credentials;
+pub use credentials::*;
+
+mod raw_body;
+pub use raw_body::*;
+
+mod async_drop;
+pub use async_drop::*;
+
 mod connection;
 pub use connection::*;
+mod connection_builder;
+pub use connection_builder::*;
+
+mod listener;
+pub use listener::*;
 
 mod proxy;
 pub use proxy::*;
 
 mod proxy_builder;
 pub use proxy_builder::*;
+mod proxy_metadata;
+pub use proxy_metadata::*;
+
+mod retry_policy;
+pub use retry_policy::*;
+
+mod service_file;
+pub use service_file::*;
 
 mod signal_receiver;
 pub use signal_receiver::*;
@@ -184,19 +249,42 @@ pub use owned_fd::*;
 
 mod utils;
 
+mod test_util;
+
+mod time;
+pub use time::*;
+
 mod object_server;
 pub use object_server::*;
+mod dynamic_interface;
+pub use dynamic_interface::*;
+mod composite_interface;
+mod dispatch;
+mod interface_version;
 
 pub mod fdo;
+mod introspect_tree;
 
-mod raw;
+pub mod raw;
 
 pub mod azync;
 pub use azync::SignalHandlerId;
+mod auth;
 mod handshake;
+pub use auth::*;
+mod keyring;
+pub use keyring::*;
 
 pub mod xml;
 
+pub mod notifications;
+
+pub mod logging;
+
+pub mod sd_notify;
+
+pub mod test_bus;
+
 pub use zbus_macros::{dbus_interface, dbus_proxy, DBusError};
 
 // Required for the macros to function within this crate.
@@ -205,7 +293,11 @@ extern crate self as zbus;
 // Macro support module, not part of the public API.
 #[doc(hidden)]
 pub mod export {
+    pub use crate::dispatch::{
+        dispatch_call, dispatch_call_mut, Method, MethodCall, MethodMut, MethodRef, MethodTable,
+    };
     pub use futures_core;
+    pub use log;
     pub use serde;
     pub use static_assertions;
     pub use zvariant;
@@ -543,10 +635,11 @@ mod tests {
         // While this is not an exact reproduction of the issue 68, the underlying problem it
         // produces is exactly the same: `Connection::call_method` dropping all incoming messages
         // while waiting for the reply to the method call.
-        let conn = Connection::new_session().unwrap();
+        let bus = crate::test_bus::TestBus::new().unwrap();
+        let conn = bus.connect().unwrap();
 
         // Send a message as client before service starts to process messages
-        let client_conn = Connection::new_session().unwrap();
+        let client_conn = bus.connect().unwrap();
         let msg = Message::method(
             None,
             conn.unique_name(),
@@ -580,7 +673,9 @@ mod tests {
         // signature we receive on the reply message.
         use std::{cell::RefCell, convert::TryFrom, rc::Rc};
         use zvariant::{ObjectPath, Value};
-        let conn = Connection::new_session().unwrap();
+        let bus = crate::test_bus::TestBus::new().unwrap();
+        let address = bus.address().to_string();
+        let conn = bus.connect().unwrap();
         let service_name = conn.unique_name().unwrap().to_string();
         let mut object_server = super::ObjectServer::new(&conn);
 
@@ -609,7 +704,7 @@ mod tests {
             .unwrap();
 
         let child = std::thread::spawn(move || {
-            let conn = Connection::new_session().unwrap();
+            let conn = Connection::new_for_address(&address, true).unwrap();
             #[super::dbus_proxy(interface = "org.freedesktop.Secret.Service")]
             trait Secret {
                 fn open_session(
@@ -0,0 +1,125 @@
+use async_io::block_on;
+use static_assertions::assert_impl_all;
+
+use crate::{azync, Connection, Guid, Result};
+
+/// A socket listening for peer-to-peer D-Bus connections.
+///
+/// This is the blocking wrapper of [`azync::Listener`]. See its documentation for the supported
+/// addresses.
+///
+/// # Example
+///
+/// ```no_run
+///# use std::error::Error;
+/// use zbus::{Connection, Listener};
+///
+/// let listener = Listener::bind("unix:dir=/run/myservice")?;
+/// // Tell the clients about `listener.address()`..
+/// let conn = listener.accept()?;
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+///
+/// [`azync::Listener`]: azync/struct.Listener.html
+#[derive(Debug)]
+pub struct Listener {
+    azync: azync::Listener,
+}
+
+assert_impl_all!(Listener: Send, Sync, Unpin);
+
+impl Listener {
+    /// Create a socket listening on the given D-Bus address.
+    pub fn bind(address: &str) -> Result<Self> {
+        azync::Listener::bind(address).map(|azync| Self { azync })
+    }
+
+    /// The address clients can connect to, including the GUID of the server.
+    pub fn address(&self) -> &str {
+        self.azync.address()
+    }
+
+    /// The server GUID of the accepted connections.
+    pub fn guid(&self) -> &Guid {
+        self.azync.guid()
+    }
+
+    /// Wait for the next client and return the server connection to it.
+    pub fn accept(&self) -> Result<Connection> {
+        block_on(self.azync.accept()).map(Connection::from)
+    }
+
+    /// The reference to the underlying [`azync::Listener`].
+    ///
+    /// [`azync::Listener`]: azync/struct.Listener.html
+    pub fn inner(&self) -> &azync::Listener {
+        &self.azync
+    }
+}
+
+#[cfg(all(test, feature = "unix-transport"))]
+mod tests {
+    use std::{fs, thread};
+
+    use ntest::timeout;
+    use test_env_log::test;
+
+    use crate::{Connection, Error, Listener};
+
+    fn check_listener(listener: Listener) {
+        let address = listener.address().to_string();
+        assert!(address.ends_with(&format!(",guid={}", listener.guid())));
+
+        let server_thread = thread::spawn(move || {
+            let conn = listener.accept().unwrap();
+            let m = conn.receive_message().unwrap();
+            let header = m.header().unwrap();
+            assert_eq!(header.member().unwrap(), Some("Hello"));
+        });
+
+        let conn = Connection::new_for_address(&address, false).unwrap();
+        conn.emit_signal(None, "/", "org.zbus.Listener", "Hello", &())
+            .unwrap();
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn dir() {
+        let dir = std::env::temp_dir().join(format!("zbus-listener-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let listener = Listener::bind(&format!("unix:dir={}", dir.display())).unwrap();
+        let prefix = format!("unix:path={}/dbus-", dir.display());
+        assert!(listener.address().starts_with(&prefix));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // The socket is removed along with the listener.
+        check_listener(listener);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    #[timeout(15000)]
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    fn tmpdir() {
+        let listener = Listener::bind("unix:tmpdir=/tmp").unwrap();
+        assert!(listener.address().starts_with("unix:abstract=/tmp/dbus-"));
+
+        check_listener(listener);
+    }
+
+    #[test]
+    fn listen_only_addresses() {
+        for address in &["unix:dir=/tmp", "unix:tmpdir=/tmp"] {
+            match Connection::new_for_address(address, false).unwrap_err() {
+                Error::Address(e) => assert_eq!(
+                    e,
+                    "`dir` and `tmpdir` addresses can only be listened on, not connected to"
+                ),
+                e => panic!("unexpected error: {}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! The logging of zbus, and its control at runtime (`logging` feature).
+//!
+//! zbus logs through the [`log`] crate, with the following targets. They are stable, so they can
+//! be used in filters, e.g `RUST_LOG=zbus::handshake=debug` with [`env_logger`]:
+//!
+//! | Target               | Logs                                                              |
+//! |----------------------|-------------------------------------------------------------------|
+//! | [`CONNECTION`]       | Connections being established and closed, and receiving errors.   |
+//! | [`HANDSHAKE`]        | The authentication handshake, including rejected mechanisms.      |
+//! | [`OBJECT_SERVER`]    | Method calls dispatched by the object server, and failed replies. |
+//! | [`PROXY`]            | Method calls and signal subscriptions of the proxies.             |
+//! | [`SOCKET`]           | Messages and bytes read from and written to the sockets.          |
+//!
+//! With the `logging` feature, [`Logging`] is an implementation of the `org.zbus.Logging1`
+//! interface, to change the filter of the application's logger over D-Bus, without restarting it.
+//! See the `runtime-logging` example for its use with [`tracing_subscriber::reload`].
+//!
+//! [`log`]: https://docs.rs/log
+//! [`env_logger`]: https://docs.rs/env_logger
+//! [`CONNECTION`]: constant.CONNECTION.html
+//! [`HANDSHAKE`]: constant.HANDSHAKE.html
+//! [`OBJECT_SERVER`]: constant.OBJECT_SERVER.html
+//! [`PROXY`]: constant.PROXY.html
+//! [`SOCKET`]: constant.SOCKET.html
+//! [`Logging`]: struct.Logging.html
+//! [`tracing_subscriber::reload`]: https://docs.rs/tracing-subscriber/0.2/tracing_subscriber/reload/index.html
+
+#[cfg(feature = "logging")]
+use std::fmt::Display;
+
+#[cfg(feature = "logging")]
+use crate::{dbus_interface, fdo};
+
+/// The target of the connection logs.
+pub const CONNECTION: &str = "zbus::connection";
+
+/// The target of the authentication handshake logs.
+pub const HANDSHAKE: &str = "zbus::handshake";
+
+/// The target of the object server logs.
+pub const OBJECT_SERVER: &str = "zbus::object_server";
+
+/// The target of the proxy logs.
+pub const PROXY: &str = "zbus::proxy";
+
+/// The target of the socket logs. They are all at the `trace` level.
+pub const SOCKET: &str = "zbus::socket";
+
+/// An implementation of the `org.zbus.Logging1` interface, to change the logging filter.
+///
+/// The interface has two methods:
+///
+/// * `SetFilter(s)` applies a new filter, through the function given to [`Logging::new`]. If it
+///   fails, the error is returned to the caller as `org.freedesktop.DBus.Error.InvalidArgs`, and
+///   the current filter is kept.
+/// * `GetFilter() -> s` returns the current filter.
+///
+/// The syntax of the filter is the one of the logger used by the application. Any peer able to call
+/// the methods can change it, so on the system bus, the bus policy should only allow trusted ones.
+///
+/// # Example
+///
+/// ```no_run
+///# use std::error::Error;
+/// use zbus::{logging::Logging, Connection, ObjectServer};
+///
+/// let connection = Connection::new_session()?;
+/// let mut object_server = ObjectServer::new(&connection);
+/// let logging = Logging::new("info", |filter: &str| -> Result<(), String> {
+///     // Apply `filter` to the logger here.
+///     Ok(())
+/// });
+/// object_server.at("/org/zbus/Logging", logging)?;
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+///
+/// [`Logging::new`]: struct.Logging.html#method.new
+#[cfg(feature = "logging")]
+#[derive(derivative::Derivative)]
+#[derivative(Debug)]
+pub struct Logging {
+    filter: String,
+    #[derivative(Debug = "ignore")]
+    apply: Box<dyn Fn(&str) -> Result<(), String>>,
+}
+
+#[cfg(feature = "logging")]
+impl Logging {
+    /// Create the interface, with the `filter` currently in use and the function applying a new
+    /// one.
+    pub fn new<F, E>(filter: impl Into<String>, apply: F) -> Self
+    where
+        F: Fn(&str) -> Result<(), E> + 'static,
+        E: Display,
+    {
+        Self {
+            filter: filter.into(),
+            apply: Box::new(move |filter| apply(filter).map_err(|e| e.to_string())),
+        }
+    }
+}
+
+#[cfg(feature = "logging")]
+#[dbus_interface(name = "org.zbus.Logging1")]
+impl Logging {
+    /// Apply a new logging filter.
+    fn set_filter(&mut self, filter: &str) -> fdo::Result<()> {
+        (self.apply)(filter).map_err(fdo::Error::InvalidArgs)?;
+        self.filter = filter.to_owned();
+
+        Ok(())
+    }
+
+    /// The current logging filter.
+    fn get_filter(&self) -> String {
+        self.filter.clone()
+    }
+}
+
+#[cfg(all(test, feature = "logging"))]
+mod tests {
+    use std::{cell::RefCell, os::unix::net::UnixStream, rc::Rc, thread};
+
+    use ntest::timeout;
+    use test_env_log::test;
+
+    use super::Logging;
+    use crate::{dbus_proxy, fdo, Connection, Guid, ObjectServer};
+
+    #[dbus_proxy(interface = "org.zbus.Logging1", default_path = "/org/zbus/Logging")]
+    trait LoggingControl {
+        fn set_filter(&self, filter: &str) -> fdo::Result<()>;
+
+        fn get_filter(&self) -> fdo::Result<String>;
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn set_filter() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let applied = Rc::new(RefCell::new(vec![]));
+            let logging = {
+                let applied = applied.clone();
+                Logging::new("info", move |filter: &str| {
+                    if filter.contains('!') {
+                        return Err(format!("invalid filter `{}`", filter));
+                    }
+                    applied.borrow_mut().push(filter.to_owned());
+
+                    Ok(())
+                })
+            };
+            object_server.at("/org/zbus/Logging", logging).unwrap();
+            // GetFilter, SetFilter, GetFilter, SetFilter, GetFilter.
+            for _ in 0..5 {
+                object_server.try_handle_next().unwrap();
+            }
+
+            drop(object_server);
+
+            Rc::try_unwrap(applied).unwrap().into_inner()
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        let proxy = LoggingControlProxy::builder(&conn)
+            .destination("org.zbus.Logging")
+            .build()
+            .unwrap();
+        assert_eq!(proxy.get_filter().unwrap(), "info");
+        proxy.set_filter("zbus::handshake=debug").unwrap();
+        assert_eq!(proxy.get_filter().unwrap(), "zbus::handshake=debug");
+        match proxy.set_filter("zbus=!").unwrap_err() {
+            fdo::Error::InvalidArgs(e) => assert_eq!(e, "invalid filter `zbus=!`"),
+            e => panic!("unexpected error: {}", e),
+        }
+        assert_eq!(proxy.get_filter().unwrap(), "zbus::handshake=debug");
+
+        assert_eq!(server_thread.join().unwrap(), vec!["zbus::handshake=debug"]);
+    }
+}
@@ -1,26 +1,53 @@
 use std::{
     convert::{Infallible, TryFrom, TryInto},
     error, fmt,
-    io::{Cursor, Error as IOError},
+    io::{Cursor, Error as IOError, Write},
     os::unix::io::{AsRawFd, IntoRawFd, RawFd},
     sync::{Arc, RwLock},
 };
 
+use enumflags2::BitFlags;
+use serde::de::DeserializeSeed;
 use static_assertions::assert_impl_all;
-use zvariant::{EncodingContext, Error as VariantError, ObjectPath, Signature, Type};
+use zvariant::{
+    walk_slice_fds, EncodingContext, Error as VariantError, Fd, ObjectPath, PrettyOptions,
+    PrettyPrinter, Signature, StaticType, Structure, StructureSeed, Type, Walker,
+};
 
 use crate::{
-    owned_fd::OwnedFd, utils::padding_for_8_bytes, EndianSig, MessageField, MessageFieldCode,
-    MessageFields, MessageHeader, MessagePrimaryHeader, MessageType, MIN_MESSAGE_SIZE,
-    NATIVE_ENDIAN_SIG, PRIMARY_HEADER_SIZE,
+    owned_fd::OwnedFd,
+    utils::{padding_for_8_bytes, padding_for_n_bytes},
+    EndianSig, FixedArray, FixedBody, MessageField, MessageFieldCode, MessageFields, MessageFlags,
+    MessageHeader, MessagePrimaryHeader, MessageType, RawBody, MIN_MESSAGE_SIZE, NATIVE_ENDIAN_SIG,
+    PRIMARY_HEADER_SIZE,
 };
 
 const FIELDS_LEN_START_OFFSET: usize = 12;
 const LOCK_PANIC_MSG: &str = "lock poisoned";
 
-macro_rules! dbus_context {
-    ($n_bytes_before: expr) => {
-        EncodingContext::<byteorder::NativeEndian>::new_dbus($n_bytes_before)
+// Evaluates `$body` with `$ctxt` bound to the D-Bus encoding context for the byte order `$endian`.
+macro_rules! with_dbus_context {
+    ($endian: expr, $n_bytes_before: expr, |$ctxt: ident| $body: expr) => {
+        match $endian {
+            EndianSig::Big => {
+                let $ctxt = EncodingContext::<byteorder::BE>::new_dbus($n_bytes_before);
+                $body
+            }
+            EndianSig::Little => {
+                let $ctxt = EncodingContext::<byteorder::LE>::new_dbus($n_bytes_before);
+                $body
+            }
+        }
+    };
+    // Same, limiting the values decoded to `$budget`, if any.
+    ($endian: expr, $n_bytes_before: expr, $budget: expr, |$ctxt: ident| $body: expr) => {
+        with_dbus_context!($endian, $n_bytes_before, |$ctxt| {
+            let $ctxt = match $budget {
+                Some(budget) => $ctxt.with_value_budget(budget),
+                None => $ctxt,
+            };
+            $body
+        })
     };
 }
 
@@ -47,6 +74,12 @@ pub enum MessageError {
     Variant(VariantError),
     /// A required field is missing in the headers.
     MissingField,
+    /// The file descriptor is not owned by the message, or its ownership was already taken.
+    FdNotOwned,
+    /// The file descriptor is not attached to the message.
+    UnknownFd,
+    /// The number of file descriptors attached doesn't match the `UnixFDs` header field.
+    UnmatchedFdCount,
     /// Only exists to allow `TryFrom<T> for T` conversions. You should never actually be getting
     /// this error from any API.
     Infallible,
@@ -65,6 +98,9 @@ impl PartialEq for MessageError {
             (Self::UnmatchedBodySignature, Self::UnmatchedBodySignature) => true,
             (Self::InvalidField, Self::InvalidField) => true,
             (Self::Variant(s), Self::Variant(o)) => s == o,
+            (Self::FdNotOwned, Self::FdNotOwned) => true,
+            (Self::UnknownFd, Self::UnknownFd) => true,
+            (Self::UnmatchedFdCount, Self::UnmatchedFdCount) => true,
             (Self::Infallible, Self::Infallible) => true,
             (_, _) => false,
         }
@@ -93,6 +129,9 @@ impl fmt::Display for MessageError {
             MessageError::UnmatchedBodySignature => write!(f, "unmatched body signature"),
             MessageError::Variant(e) => write!(f, "{}", e),
             MessageError::MissingField => write!(f, "A required field is missing"),
+            MessageError::FdNotOwned => write!(f, "file descriptor not owned by the message"),
+            MessageError::UnknownFd => write!(f, "file descriptor not attached to the message"),
+            MessageError::UnmatchedFdCount => write!(f, "unmatched file descriptor count"),
             MessageError::Infallible => write!(f, "Infallible conversion failed"),
         }
     }
@@ -117,156 +156,315 @@ impl From<Infallible> for MessageError {
 }
 
 #[derive(Debug)]
-struct MessageBuilder<'a, B> {
+enum MessageBody<'a, B> {
+    Value(&'a B),
+    Raw(&'a RawBody),
+}
+
+/// A builder for [`Message`].
+///
+/// The [`Message`] constructors cover the common cases. The builder gives more control over the
+/// message, e.g to encode it in a byte order other than the native one:
+///
+/// ```
+///# use std::error::Error;
+///#
+/// use zbus::{EndianSig, MessageBuilder};
+///
+/// let msg = MessageBuilder::method_call("/org/zbus/Test", "Ping")?
+///     .destination("org.zbus.Test")
+///     .interface("org.zbus.Test")
+///     .endian_sig(EndianSig::Big)
+///     .build(&("hello", 42u32))?;
+/// assert_eq!(msg.primary_header().endian_sig(), EndianSig::Big);
+/// assert_eq!(msg.body::<(&str, u32)>()?, ("hello", 42));
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+///
+/// The header and the body of the message are always encoded in the same byte order.
+///
+/// [`Message`]: struct.Message.html
+#[derive(Debug)]
+pub struct MessageBuilder<'a> {
     ty: MessageType,
-    body: &'a B,
-    body_len: u32,
-    reply_to: Option<MessageHeader<'a>>,
     fields: MessageFields<'a>,
+    reply_to: Option<MessageHeader<'a>>,
+    endian_sig: EndianSig,
+    flags: BitFlags<MessageFlags>,
 }
 
-impl<'a, B> MessageBuilder<'a, B>
-where
-    B: serde::ser::Serialize + Type,
-{
-    fn new(ty: MessageType, sender: Option<&'a str>, body: &'a B) -> Result<Self, MessageError> {
-        let ctxt = dbus_context!(0);
-        let (body_len, fds_len) = zvariant::serialized_size_fds(ctxt, body)?;
-        let body_len = u32::try_from(body_len).map_err(|_| MessageError::ExcessData)?;
+assert_impl_all!(MessageBuilder<'_>: Send, Sync, Unpin);
 
-        let mut fields = MessageFields::new();
-
-        let mut signature = B::signature();
-        if !signature.is_empty() {
-            if signature.starts_with(zvariant::STRUCT_SIG_START_STR) {
-                // Remove leading and trailing STRUCT delimiters
-                signature = signature.slice(1..signature.len() - 1);
-            }
-            fields.add(MessageField::Signature(signature));
+impl<'a> MessageBuilder<'a> {
+    fn new(ty: MessageType) -> Self {
+        Self {
+            ty,
+            fields: MessageFields::new(),
+            reply_to: None,
+            endian_sig: NATIVE_ENDIAN_SIG,
+            flags: BitFlags::empty(),
         }
+    }
+
+    /// Create a builder for a message of type [`MessageType::MethodCall`].
+    ///
+    /// [`MessageType::MethodCall`]: enum.MessageType.html#variant.MethodCall
+    pub fn method_call<'p: 'a, E>(
+        path: impl TryInto<ObjectPath<'p>, Error = E>,
+        method_name: &'a str,
+    ) -> Result<Self, MessageError>
+    where
+        E: Into<MessageError>,
+    {
+        let mut b = Self::new(MessageType::MethodCall);
+        b.fields
+            .add(MessageField::Path(path.try_into().map_err(Into::into)?));
+        b.fields.add(MessageField::Member(method_name.into()));
+
+        Ok(b)
+    }
+
+    /// Create a builder for a message of type [`MessageType::Signal`].
+    ///
+    /// [`MessageType::Signal`]: enum.MessageType.html#variant.Signal
+    pub fn signal<'p: 'a, E>(
+        path: impl TryInto<ObjectPath<'p>, Error = E>,
+        iface: &'a str,
+        signal_name: &'a str,
+    ) -> Result<Self, MessageError>
+    where
+        E: Into<MessageError>,
+    {
+        let mut b = Self::new(MessageType::Signal);
+        b.fields
+            .add(MessageField::Path(path.try_into().map_err(Into::into)?));
+        b.fields.add(MessageField::Interface(iface.into()));
+        b.fields.add(MessageField::Member(signal_name.into()));
+
+        Ok(b)
+    }
+
+    /// Create a builder for a message of type [`MessageType::MethodReturn`], replying to `call`.
+    ///
+    /// [`MessageType::MethodReturn`]: enum.MessageType.html#variant.MethodReturn
+    pub fn method_return(call: &'a Message) -> Result<Self, MessageError> {
+        let mut b = Self::new(MessageType::MethodReturn);
+        b.reply_to = Some(call.header()?);
+
+        Ok(b)
+    }
+
+    /// Create a builder for a message of type [`MessageType::Error`], replying to `call`.
+    ///
+    /// [`MessageType::Error`]: enum.MessageType.html#variant.Error
+    pub fn error(call: &'a Message, name: &'a str) -> Result<Self, MessageError> {
+        let mut b = Self::new(MessageType::Error);
+        b.reply_to = Some(call.header()?);
+        b.fields.add(MessageField::ErrorName(name.into()));
+
+        Ok(b)
+    }
+
+    /// Set the sender of the message.
+    pub fn sender(mut self, sender: &'a str) -> Self {
+        self.fields.replace(MessageField::Sender(sender.into()));
+        self
+    }
+
+    /// Set the destination of the message.
+    ///
+    /// Replies are sent to the sender of the call by default.
+    pub fn destination(mut self, destination: &'a str) -> Self {
+        self.fields
+            .replace(MessageField::Destination(destination.into()));
+        self
+    }
+
+    /// Set the interface of the message.
+    pub fn interface(mut self, iface: &'a str) -> Self {
+        self.fields.replace(MessageField::Interface(iface.into()));
+        self
+    }
+
+    /// Set the flags of the message.
+    ///
+    /// No flags are set by default.
+    pub fn flags(mut self, flags: BitFlags<MessageFlags>) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Set a header field of the message, replacing the field with the same code, if any.
+    ///
+    /// This is mostly useful for fields zbus doesn't know about, i-e [`MessageField::Unknown`].
+    /// The `Signature`, `UnixFDs` and `ReplySerial` fields are set from the body and the call
+    /// being replied to, on [`build`].
+    ///
+    /// [`MessageField::Unknown`]: enum.MessageField.html#variant.Unknown
+    /// [`build`]: #method.build
+    pub fn field(mut self, field: MessageField<'a>) -> Self {
+        self.fields.replace(field);
+        self
+    }
+
+    /// Set the byte order to encode the message in.
+    ///
+    /// The native byte order is used by default.
+    pub fn endian_sig(mut self, sig: EndianSig) -> Self {
+        self.endian_sig = sig;
+        self
+    }
+
+    // Set the fields the `Message` constructors take optionally.
+    pub(crate) fn optional_fields(
+        mut self,
+        sender: Option<&'a str>,
+        destination: Option<&'a str>,
+        iface: Option<&'a str>,
+    ) -> Self {
         if let Some(sender) = sender {
-            fields.add(MessageField::Sender(sender.into()));
+            self = self.sender(sender);
         }
-
-        if fds_len > 0 {
-            fields.add(MessageField::UnixFDs(fds_len as u32));
+        if let Some(destination) = destination {
+            self = self.destination(destination);
+        }
+        if let Some(iface) = iface {
+            self = self.interface(iface);
         }
 
-        Ok(Self {
-            ty,
-            body,
-            body_len,
-            fields,
-            reply_to: None,
-        })
+        self
     }
 
-    fn build(self) -> Result<Message, MessageError> {
+    /// Build the message with `body`.
+    pub fn build<B>(self, body: &B) -> Result<Message, MessageError>
+    where
+        B: serde::ser::Serialize + Type,
+    {
+        self.build_body(MessageBody::Value(body))
+    }
+
+    /// Build the message with a body serialized beforehand.
+    ///
+    /// The bytes of `body` are copied into the message as is. Since [`RawBody`] is always encoded
+    /// in the native byte order, [`MessageError::IncorrectEndian`] is returned if the message is
+    /// to be built in another one.
+    ///
+    /// [`RawBody`]: struct.RawBody.html
+    /// [`MessageError::IncorrectEndian`]: enum.MessageError.html#variant.IncorrectEndian
+    pub fn build_raw_body(self, body: &RawBody) -> Result<Message, MessageError> {
+        self.build_body::<()>(MessageBody::Raw(body))
+    }
+
+    fn build_body<B>(self, body: MessageBody<'_, B>) -> Result<Message, MessageError>
+    where
+        B: serde::ser::Serialize + Type,
+    {
         let MessageBuilder {
             ty,
-            body,
-            body_len,
             mut fields,
             reply_to,
+            endian_sig,
+            flags,
         } = self;
 
+        let (body_len, fds_len, mut signature) = match &body {
+            MessageBody::Value(body) => {
+                let (body_len, fds_len) = with_dbus_context!(endian_sig, 0, |ctxt| {
+                    zvariant::serialized_size_fds(ctxt, *body)?
+                });
+
+                (body_len, fds_len, B::signature())
+            }
+            MessageBody::Raw(body) => {
+                if endian_sig != NATIVE_ENDIAN_SIG {
+                    return Err(MessageError::IncorrectEndian);
+                }
+
+                (
+                    body.bytes().len(),
+                    body.fds().len(),
+                    body.signature().clone(),
+                )
+            }
+        };
+        let body_len = u32::try_from(body_len).map_err(|_| MessageError::ExcessData)?;
+
+        if !signature.is_empty() {
+            if signature.starts_with(zvariant::STRUCT_SIG_START_STR) {
+                // Remove leading and trailing STRUCT delimiters
+                signature = signature.slice(1..signature.len() - 1);
+            }
+            fields.replace(MessageField::Signature(signature));
+        }
+        if fds_len > 0 {
+            fields.replace(MessageField::UnixFDs(fds_len as u32));
+        }
         if let Some(reply_to) = reply_to.as_ref() {
             let serial = reply_to
                 .primary()
                 .serial_num()
                 .ok_or(MessageError::MissingField)?;
-            fields.add(MessageField::ReplySerial(*serial));
+            fields.replace(MessageField::ReplySerial(*serial));
 
-            if let Some(sender) = reply_to.sender()? {
+            let has_destination = fields.get_field(MessageFieldCode::Destination).is_some();
+            if let (false, Some(sender)) = (has_destination, reply_to.sender()?) {
                 fields.add(MessageField::Destination(sender.into()));
             }
         }
 
-        let primary = MessagePrimaryHeader::new(ty, body_len);
+        let mut primary = MessagePrimaryHeader::new(ty, body_len);
+        primary.set_endian_sig(endian_sig);
+        primary.set_flags(flags);
         let header = MessageHeader::new(primary, fields);
 
-        let ctxt = dbus_context!(0);
         // 1K for all the fields should be enough for most messages?
         let mut bytes: Vec<u8> =
             Vec::with_capacity(PRIMARY_HEADER_SIZE + 1024 + (body_len as usize));
         let mut cursor = Cursor::new(&mut bytes);
 
-        zvariant::to_writer(&mut cursor, ctxt, &header)?;
-        let (_, fds) = zvariant::to_writer_fds(&mut cursor, ctxt, body)?;
+        let header_len = with_dbus_context!(endian_sig, 0, |ctxt| {
+            zvariant::to_writer(&mut cursor, ctxt, &header)?
+        });
+        let fds = match body {
+            MessageBody::Value(body) => with_dbus_context!(endian_sig, header_len, |ctxt| {
+                zvariant::to_writer_fds(&mut cursor, ctxt, body)?.1
+            }),
+            MessageBody::Raw(body) => {
+                cursor.write_all(body.bytes())?;
+
+                body.fds().to_vec()
+            }
+        };
 
         Ok(Message {
             primary_header: header.into_primary(),
             bytes,
             fds: Arc::new(RwLock::new(Fds::Raw(fds))),
+            value_budget: None,
         })
     }
-
-    fn set_reply_to(mut self, reply_to: &'a Message) -> Result<Self, MessageError> {
-        self.reply_to = Some(reply_to.header()?);
-        Ok(self)
-    }
-
-    fn set_field(mut self, field: MessageField<'a>) -> Self {
-        self.fields.add(field);
-        self
-    }
-
-    fn reply(
-        sender: Option<&'a str>,
-        reply_to: &'a Message,
-        body: &'a B,
-    ) -> Result<Self, MessageError> {
-        Self::new(MessageType::MethodReturn, sender, body)?.set_reply_to(reply_to)
-    }
-
-    fn error(
-        sender: Option<&'a str>,
-        reply_to: &'a Message,
-        error_name: &'a str,
-        body: &'a B,
-    ) -> Result<Self, MessageError> {
-        Ok(Self::new(MessageType::Error, sender, body)?
-            .set_reply_to(reply_to)?
-            .set_field(MessageField::ErrorName(error_name.into())))
-    }
-
-    fn method(
-        sender: Option<&'a str>,
-        path: ObjectPath<'a>,
-        method_name: &'a str,
-        body: &'a B,
-    ) -> Result<Self, MessageError> {
-        Ok(Self::new(MessageType::MethodCall, sender, body)?
-            .set_field(MessageField::Path(path))
-            .set_field(MessageField::Member(method_name.into())))
-    }
-
-    fn signal(
-        sender: Option<&'a str>,
-        path: ObjectPath<'a>,
-        iface: &'a str,
-        signal_name: &'a str,
-        body: &'a B,
-    ) -> Result<Self, MessageError> {
-        Ok(Self::new(MessageType::Signal, sender, body)?
-            .set_field(MessageField::Path(path))
-            .set_field(MessageField::Interface(iface.into()))
-            .set_field(MessageField::Member(signal_name.into())))
-    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
 enum Fds {
-    Owned(Vec<OwnedFd>),
+    // The raw FDs are kept around even after the ownership of the FD was taken, since they're still
+    // needed for deserializing the body.
+    Owned(Vec<(RawFd, Option<OwnedFd>)>),
     Raw(Vec<RawFd>),
 }
 
+impl Fds {
+    fn raw(&self) -> Vec<RawFd> {
+        match self {
+            Fds::Raw(v) => v.clone(),
+            Fds::Owned(v) => v.iter().map(|(fd, _)| *fd).collect(),
+        }
+    }
+}
+
 impl Clone for Fds {
     fn clone(&self) -> Self {
-        Fds::Raw(match self {
-            Fds::Raw(v) => v.clone(),
-            Fds::Owned(v) => v.iter().map(|fd| fd.as_raw_fd()).collect(),
-        })
+        Fds::Raw(self.raw())
     }
 }
 
@@ -281,22 +479,32 @@ impl Clone for Fds {
 /// and hence use the API provided by [`Connection`], even when using the low-level API.
 ///
 /// **Note**: The message owns the received FDs and will close them when dropped. You can call
-/// [`disown_fds`] after deserializing to `RawFD` using [`body`] if you want to take the ownership.
-/// Moreover, a clone of a message with owned FDs will only receive unowned copies of the FDs.
+/// [`take_fd`] or [`disown_fds`] after deserializing to [`Fd`] using [`body`] if you want to take
+/// the ownership. Moreover, a clone of a message with owned FDs will only receive unowned copies of
+/// the FDs.
+///
+/// Neither the message nor [`Connection`] ever duplicate (`dup`) file descriptors: the FDs in the
+/// body of a message you create are referred to by their raw value only, so they must be kept open
+/// until the message is sent, unless you give their ownership to the message with [`adopt_fd`].
+/// The only duplication happens in the kernel, when the FDs are passed to the peer.
 ///
 /// [`body`]: #method.body
+/// [`take_fd`]: #method.take_fd
+/// [`adopt_fd`]: #method.adopt_fd
 /// [`disown_fds`]: #method.disown_fds
+/// [`Fd`]: https://docs.rs/zvariant/2.7.0/zvariant/struct.Fd.html
 /// [`Connection`]: struct.Connection#method.call_method
 #[derive(Clone)]
 pub struct Message {
     primary_header: MessagePrimaryHeader,
     bytes: Vec<u8>,
     fds: Arc<RwLock<Fds>>,
+    // The maximum number of values decoded from the body, if limited.
+    value_budget: Option<usize>,
 }
 
 assert_impl_all!(Message: Send, Sync, Unpin);
 
-// TODO: Handle non-native byte order: https://gitlab.freedesktop.org/dbus/zbus/-/issues/19
 impl Message {
     /// Create a message of type [`MessageType::MethodCall`].
     ///
@@ -313,19 +521,30 @@ impl Message {
         B: serde::ser::Serialize + Type,
         E: Into<MessageError>,
     {
-        let mut b = MessageBuilder::method(
-            sender,
-            path.try_into().map_err(Into::into)?,
-            method_name,
-            body,
-        )?;
-        if let Some(destination) = destination {
-            b = b.set_field(MessageField::Destination(destination.into()));
-        }
-        if let Some(iface) = iface {
-            b = b.set_field(MessageField::Interface(iface.into()));
-        }
-        b.build()
+        MessageBuilder::method_call(path, method_name)?
+            .optional_fields(sender, destination, iface)
+            .build(body)
+    }
+
+    /// Create a message of type [`MessageType::MethodCall`], with a body serialized beforehand.
+    ///
+    /// The bytes of `body` are copied into the message as is.
+    ///
+    /// [`MessageType::MethodCall`]: enum.MessageType.html#variant.MethodCall
+    pub fn method_raw_body<'p, E>(
+        sender: Option<&str>,
+        destination: Option<&str>,
+        path: impl TryInto<ObjectPath<'p>, Error = E>,
+        iface: Option<&str>,
+        method_name: &str,
+        body: &RawBody,
+    ) -> Result<Self, MessageError>
+    where
+        E: Into<MessageError>,
+    {
+        MessageBuilder::method_call(path, method_name)?
+            .optional_fields(sender, destination, iface)
+            .build_raw_body(body)
     }
 
     /// Create a message of type [`MessageType::Signal`].
@@ -343,17 +562,9 @@ impl Message {
         B: serde::ser::Serialize + Type,
         E: Into<MessageError>,
     {
-        let mut b = MessageBuilder::signal(
-            sender,
-            path.try_into().map_err(Into::into)?,
-            iface,
-            signal_name,
-            body,
-        )?;
-        if let Some(destination) = destination {
-            b = b.set_field(MessageField::Destination(destination.into()));
-        }
-        b.build()
+        MessageBuilder::signal(path, iface, signal_name)?
+            .optional_fields(sender, destination, None)
+            .build(body)
     }
 
     /// Create a message of type [`MessageType::MethodReturn`].
@@ -367,7 +578,9 @@ impl Message {
     where
         B: serde::ser::Serialize + Type,
     {
-        MessageBuilder::reply(sender, call, body)?.build()
+        MessageBuilder::method_return(call)?
+            .optional_fields(sender, None, None)
+            .build(body)
     }
 
     /// Create a message of type [`MessageType::MethodError`].
@@ -382,7 +595,9 @@ impl Message {
     where
         B: serde::ser::Serialize + Type,
     {
-        MessageBuilder::error(sender, call, name, body)?.build()
+        MessageBuilder::error(call, name)?
+            .optional_fields(sender, None, None)
+            .build(body)
     }
 
     pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self, MessageError> {
@@ -390,21 +605,60 @@ impl Message {
             return Err(MessageError::InsufficientData);
         }
 
-        if EndianSig::try_from(bytes[0])? != NATIVE_ENDIAN_SIG {
-            return Err(MessageError::IncorrectEndian);
-        }
-
-        let primary_header =
-            zvariant::from_slice(bytes, dbus_context!(0)).map_err(MessageError::from)?;
+        let endian = EndianSig::try_from(bytes[0])?;
+        let primary_header = with_dbus_context!(endian, 0, |ctxt| {
+            zvariant::from_slice(bytes, ctxt).map_err(MessageError::from)?
+        });
         let bytes = bytes.to_vec();
         let fds = Arc::new(RwLock::new(Fds::Raw(vec![])));
         Ok(Self {
             primary_header,
             bytes,
             fds,
+            value_budget: None,
         })
     }
 
+    /// Create a message from its complete wire encoding and the file descriptors passed with it.
+    ///
+    /// This is the way to bring in messages serialized by another D-Bus library, e.g dbus-rs or
+    /// rustbus: marshal the message with that library and pass the resulting bytes here. The other
+    /// way around, [`as_bytes`] and [`fds`] give you what the other library needs to parse a zbus
+    /// message. Messages in either byte order are supported. With the `dbus` feature, the
+    /// `TryFrom` conversions between `Message` and `dbus::Message` do all this for dbus-rs.
+    ///
+    /// The message takes ownership of `fds` and the number of FDs must match the `UNIX_FDS` header
+    /// field of the message, or [`MessageError::UnmatchedFdCount`] is returned.
+    ///
+    /// [`as_bytes`]: #method.as_bytes
+    /// [`fds`]: #method.fds
+    /// [`MessageError::UnmatchedFdCount`]: enum.MessageError.html#variant.UnmatchedFdCount
+    pub fn from_raw_parts(bytes: Vec<u8>, fds: Vec<OwnedFd>) -> Result<Self, MessageError> {
+        let mut msg = Self::from_bytes(&bytes[..bytes.len().min(MIN_MESSAGE_SIZE)])?;
+        let len = msg.encoded_len()?;
+        if bytes.len() < len {
+            return Err(MessageError::InsufficientData);
+        } else if bytes.len() > len {
+            return Err(MessageError::ExcessData);
+        }
+        msg.bytes = bytes;
+
+        let n_fds = msg.header()?.unix_fds()?.unwrap_or(0);
+        if n_fds as usize != fds.len() {
+            return Err(MessageError::UnmatchedFdCount);
+        }
+        msg.set_owned_fds(fds);
+
+        Ok(msg)
+    }
+
+    // The same message, with other bytes on the wire. These don't have to match the primary
+    // header, so the message can only be written out then.
+    #[cfg(feature = "compression")]
+    pub(crate) fn with_wire_bytes(self, bytes: Vec<u8>) -> Self {
+        Self { bytes, ..self }
+    }
+
     pub(crate) fn add_bytes(&mut self, bytes: &[u8]) -> Result<(), MessageError> {
         if bytes.len() > self.bytes_to_completion()? {
             return Err(MessageError::ExcessData);
@@ -416,7 +670,11 @@ impl Message {
     }
 
     pub(crate) fn set_owned_fds(&self, fds: Vec<OwnedFd>) {
-        *self.fds.write().expect(LOCK_PANIC_MSG) = Fds::Owned(fds);
+        *self.fds.write().expect(LOCK_PANIC_MSG) = Fds::Owned(
+            fds.into_iter()
+                .map(|fd| (fd.as_raw_fd(), Some(fd)))
+                .collect(),
+        );
     }
 
     /// Disown the associated file descriptors.
@@ -429,17 +687,88 @@ impl Message {
         let mut fds_lock = self.fds.write().expect(LOCK_PANIC_MSG);
         if let Fds::Owned(ref mut fds) = *fds_lock {
             // From now on, it's the caller responsibility to close the fds
-            *fds_lock = Fds::Raw(fds.drain(..).map(|fd| fd.into_raw_fd()).collect());
+            *fds_lock = Fds::Raw(
+                fds.drain(..)
+                    .map(|(fd, owned)| owned.map(|owned| owned.into_raw_fd()).unwrap_or(fd))
+                    .collect(),
+            );
+        }
+    }
+
+    /// Take the ownership of a file descriptor associated with the message.
+    ///
+    /// This is a more fine-grained alternative to [`disown_fds`]: `fd` would typically be the
+    /// [`Fd`] deserialized from the body through [`body`] and the returned [`OwnedFd`] will close
+    /// it when dropped, while the message will no longer do so. No new file descriptor is created
+    /// in the process, so the ownership of each file descriptor can only be taken once. Any
+    /// subsequent call for the same `fd` will return [`MessageError::FdNotOwned`], as will calls
+    /// for FDs that the message doesn't own in the first place (e.g the message was not received
+    /// from a socket or [`disown_fds`] was called).
+    ///
+    /// [`disown_fds`]: #method.disown_fds
+    /// [`body`]: #method.body
+    /// [`Fd`]: https://docs.rs/zvariant/2.7.0/zvariant/struct.Fd.html
+    /// [`OwnedFd`]: struct.OwnedFd.html
+    /// [`MessageError::FdNotOwned`]: enum.MessageError.html#variant.FdNotOwned
+    pub fn take_fd(&self, fd: Fd) -> Result<OwnedFd, MessageError> {
+        let mut fds_lock = self.fds.write().expect(LOCK_PANIC_MSG);
+        match &mut *fds_lock {
+            Fds::Owned(fds) => fds
+                .iter_mut()
+                .find(|(raw, _)| *raw == fd.as_raw_fd())
+                .and_then(|(_, owned)| owned.take())
+                .ok_or(MessageError::FdNotOwned),
+            Fds::Raw(_) => Err(MessageError::FdNotOwned),
+        }
+    }
+
+    /// Give the ownership of a file descriptor to the message.
+    ///
+    /// This is the counterpart of [`take_fd`], for the messages you create: `fd` would typically
+    /// be the owner of an FD you put in the body, through an [`Fd`] created from a reference to
+    /// it. The message then closes the FD when dropped, i.e once it's sent, so you don't have to
+    /// keep the FD open yourself until then. No new file descriptor is created in the process.
+    ///
+    /// Returns [`MessageError::UnknownFd`] if `fd` is not attached to the message, in which case
+    /// `fd` is closed.
+    ///
+    /// [`take_fd`]: #method.take_fd
+    /// [`Fd`]: https://docs.rs/zvariant/2.7.0/zvariant/struct.Fd.html
+    /// [`MessageError::UnknownFd`]: enum.MessageError.html#variant.UnknownFd
+    pub fn adopt_fd(&self, fd: OwnedFd) -> Result<(), MessageError> {
+        let mut fds_lock = self.fds.write().expect(LOCK_PANIC_MSG);
+        if let Fds::Raw(fds) = &*fds_lock {
+            *fds_lock = Fds::Owned(fds.iter().map(|fd| (*fd, None)).collect());
+        }
+        if let Fds::Owned(fds) = &mut *fds_lock {
+            let owned = fds
+                .iter_mut()
+                .find(|(raw, _)| *raw == fd.as_raw_fd())
+                .map(|(_, owned)| owned)
+                .ok_or(MessageError::UnknownFd)?;
+            match owned {
+                // Already owned by the message. Only one of the two owners must close the FD.
+                Some(_) => {
+                    let _ = fd.into_raw_fd();
+                }
+                None => *owned = Some(fd),
+            }
         }
+
+        Ok(())
     }
 
     pub(crate) fn bytes_to_completion(&self) -> Result<usize, MessageError> {
+        Ok(self.encoded_len()? - self.bytes.len())
+    }
+
+    // The length of the complete message, as given by its primary header.
+    fn encoded_len(&self) -> Result<usize, MessageError> {
         let header_len = MIN_MESSAGE_SIZE + self.fields_len()?;
         let body_padding = padding_for_8_bytes(header_len);
         let body_len = self.primary_header().body_len();
-        let required = header_len + body_padding + body_len as usize;
 
-        Ok(required - self.bytes.len())
+        Ok(header_len + body_padding + body_len as usize)
     }
 
     /// The signature of the body.
@@ -464,28 +793,70 @@ impl Message {
         &self.primary_header
     }
 
+    /// Limit the number of values decoded from the body to `budget`, or lift the limit with `None`.
+    ///
+    /// The body of a message from an untrusted peer can be crafted to decode into many more values
+    /// than its size suggests, e.g. an array of empty arrays, each of which the decoded type may
+    /// allocate for. With a budget, each element of an array (or entry of a dictionary) and the
+    /// value of each variant counts as one value, and the body methods (e.g. [`body`] and
+    /// [`body_structure`]) fail with a `BudgetExceeded` error of zvariant once it's spent. The
+    /// messages received by a connection built with [`ConnectionBuilder::value_budget`] have it
+    /// set already. There's no limit by default.
+    ///
+    /// [`body`]: #method.body
+    /// [`body_structure`]: #method.body_structure
+    /// [`ConnectionBuilder::value_budget`]: struct.ConnectionBuilder.html#method.value_budget
+    pub fn set_value_budget(&mut self, budget: Option<usize>) {
+        self.value_budget = budget;
+    }
+
+    /// The maximum number of values decoded from the body, if limited.
+    ///
+    /// See [`set_value_budget`] for details.
+    ///
+    /// [`set_value_budget`]: #method.set_value_budget
+    pub fn value_budget(&self) -> Option<usize> {
+        self.value_budget
+    }
+
     pub(crate) fn modify_primary_header<F>(&mut self, mut modifier: F) -> Result<(), MessageError>
     where
         F: FnMut(&mut MessagePrimaryHeader) -> Result<(), MessageError>,
     {
+        let endian = self.endian_sig();
         modifier(&mut self.primary_header)?;
+        // The rest of the message is still in the original byte order.
+        if self.endian_sig() != endian {
+            self.primary_header.set_endian_sig(endian);
+
+            return Err(MessageError::IncorrectEndian);
+        }
 
         let mut cursor = Cursor::new(&mut self.bytes);
-        zvariant::to_writer(&mut cursor, dbus_context!(0), &self.primary_header)
-            .map(|_| ())
-            .map_err(MessageError::from)
+        with_dbus_context!(endian, 0, |ctxt| {
+            zvariant::to_writer(&mut cursor, ctxt, &self.primary_header)
+                .map(|_| ())
+                .map_err(MessageError::from)
+        })
+    }
+
+    fn endian_sig(&self) -> EndianSig {
+        self.primary_header.endian_sig()
     }
 
     /// Deserialize the header.
     pub fn header(&self) -> Result<MessageHeader<'_>, MessageError> {
-        zvariant::from_slice(&self.bytes, dbus_context!(0)).map_err(MessageError::from)
+        with_dbus_context!(self.endian_sig(), 0, |ctxt| {
+            zvariant::from_slice(&self.bytes, ctxt).map_err(MessageError::from)
+        })
     }
 
     /// Deserialize the fields.
     pub fn fields(&self) -> Result<MessageFields<'_>, MessageError> {
-        let ctxt = dbus_context!(crate::PRIMARY_HEADER_SIZE);
-        zvariant::from_slice(&self.bytes[crate::PRIMARY_HEADER_SIZE..], ctxt)
-            .map_err(MessageError::from)
+        let bytes = &self.bytes[crate::PRIMARY_HEADER_SIZE..];
+        with_dbus_context!(self.endian_sig(), crate::PRIMARY_HEADER_SIZE, |ctxt| {
+            zvariant::from_slice(bytes, ctxt).map_err(MessageError::from)
+        })
     }
 
     /// Deserialize the body (without checking signature matching).
@@ -497,15 +868,13 @@ impl Message {
             return Err(MessageError::InsufficientData);
         }
 
-        let mut header_len = MIN_MESSAGE_SIZE + self.fields_len()?;
-        header_len = header_len + padding_for_8_bytes(header_len);
+        let header_len = self.body_offset()?;
+        let fds = self.fds();
 
-        zvariant::from_slice_fds(
-            &self.bytes[header_len..],
-            Some(&self.fds()),
-            dbus_context!(0),
-        )
-        .map_err(MessageError::from)
+        with_dbus_context!(self.endian_sig(), header_len, self.value_budget, |ctxt| {
+            zvariant::from_slice_fds(&self.bytes[header_len..], Some(&fds), ctxt)
+                .map_err(MessageError::from)
+        })
     }
 
     /// Check the signature and deserialize the body.
@@ -535,22 +904,438 @@ impl Message {
         self.body_unchecked()
     }
 
-    pub(crate) fn fds(&self) -> Vec<RawFd> {
+    /// Deserialize the leading arguments of the body, ignoring any that follow.
+    ///
+    /// Same as [`body`], except that the body may have more arguments than `B`, as long as it
+    /// starts with those of `B`. This allows decoding the messages of a service that has since
+    /// appended arguments to its signals or method replies, as services commonly do. Arguments are
+    /// compared as whole types, so a change to an existing argument is still an error.
+    ///
+    /// [`body`]: #method.body
+    pub fn body_prefix<'d, 'm: 'd, B>(&'m self) -> Result<B, MessageError>
+    where
+        B: serde::de::Deserialize<'d> + Type,
+    {
+        let expected_sig = B::signature();
+        let actual_sig = match self.body_signature() {
+            Ok(sig) => sig,
+            Err(MessageError::NoBodySignature) => Signature::from_str_unchecked(""),
+            Err(e) => return Err(e),
+        };
+
+        // As in `body`, multiple arguments are the fields of `B`. A single structure argument can't
+        // be told apart from these, but since its encoding is the same, either way works.
+        let c = zvariant::STRUCT_SIG_START_CHAR;
+        let is_single_arg = expected_sig.is_prefix_of(&actual_sig);
+        let expected_args = if expected_sig.len() >= 2 && expected_sig.starts_with(c) {
+            expected_sig.slice(1..expected_sig.len() - 1)
+        } else {
+            expected_sig.clone()
+        };
+        if !is_single_arg && !expected_args.is_prefix_of(&actual_sig) {
+            return Err(MessageError::UnmatchedBodySignature);
+        }
+
+        self.body_unchecked()
+    }
+
+    // `body`, or `body_prefix` if `prefix` is set, of the reply to a call of `member`. On failure,
+    // the call and both signatures are in the error.
+    pub(crate) fn reply_body<'d, 'm: 'd, B>(
+        &'m self,
+        destination: Option<&str>,
+        interface: Option<&str>,
+        member: &str,
+        prefix: bool,
+    ) -> crate::Result<B>
+    where
+        B: serde::de::Deserialize<'d> + Type,
+    {
+        let body = if prefix {
+            self.body_prefix()
+        } else {
+            self.body()
+        };
+
+        body.map_err(|e| {
+            let received = match self.body_signature() {
+                Ok(sig) => sig.to_string(),
+                Err(_) => String::new(),
+            };
+            // Multiple arguments are expected as the fields of `B`, so show them as they're sent.
+            let expected = B::signature();
+            let c = zvariant::STRUCT_SIG_START_CHAR;
+            let expected =
+                if expected.len() >= 2 && expected.starts_with(c) && !received.starts_with(c) {
+                    &expected[1..expected.len() - 1]
+                } else {
+                    expected.as_str()
+                };
+
+            crate::Error::ReplyBody(Box::new(crate::ReplyBodyError::new(
+                destination,
+                interface,
+                member,
+                expected,
+                &received,
+                e,
+            )))
+        })
+    }
+
+    /// Deserialize the body as a [`Structure`], of which each argument is a field.
+    ///
+    /// This is for bodies of a signature only known at runtime, e.g to show the reply of a method
+    /// call made from the arguments typed in by a user. A message without a body gives a structure
+    /// without any fields.
+    ///
+    /// [`Structure`]: https://docs.rs/zvariant/2/zvariant/struct.Structure.html
+    pub fn body_structure(&self) -> Result<Structure<'_>, MessageError> {
+        let signature = match self.body_signature() {
+            Ok(signature) if !signature.is_empty() => signature,
+            Ok(_) | Err(MessageError::NoBodySignature) => return Ok(Structure::default()),
+            Err(e) => return Err(e),
+        };
+        if self.bytes_to_completion()? != 0 {
+            return Err(MessageError::InsufficientData);
+        }
+        let signature = Signature::from_string_unchecked(format!("({})", signature));
+        let seed = StructureSeed::try_from(signature.clone())?;
+        let body_offset = self.body_offset()?;
+        let fds = self.fds();
+
+        with_dbus_context!(self.endian_sig(), body_offset, self.value_budget, |ctxt| {
+            let mut deserializer = zvariant::Deserializer::new(
+                &self.bytes[body_offset..],
+                Some(&fds),
+                &signature,
+                ctxt,
+            );
+            seed.deserialize(&mut deserializer)
+                .map_err(MessageError::from)
+        })
+    }
+
+    /// Check the signature and deserialize a fixed-size body, such as `u32` or `(i32, u64)`.
+    ///
+    /// The result is exactly the same as that of [`body`], but the value is read directly from the
+    /// message bytes instead of going through serde, which saves the (comparatively) costly setup
+    /// of the deserializer. This makes a difference for hot paths, e.g method calls with small
+    /// primitive replies. If the value cannot be read directly (e.g the signature doesn't match or
+    /// the message is malformed), this falls back to [`body`] so errors are also exactly the same.
+    /// Messages that are not in the native byte order always take the [`body`] path.
+    ///
+    /// [`body`]: #method.body
+    pub fn body_fixed<B>(&self) -> Result<B, MessageError>
+    where
+        B: FixedBody + StaticType + serde::de::DeserializeOwned,
+    {
+        self.try_body_fixed().map(Ok).unwrap_or_else(|| self.body())
+    }
+
+    fn try_body_fixed<B>(&self) -> Option<B>
+    where
+        B: FixedBody + StaticType,
+    {
+        // The values are read in the native byte order, and so is the header.
+        if self.endian_sig() != NATIVE_ENDIAN_SIG {
+            return None;
+        }
+        let fields_len = self.bytes.get(FIELDS_LEN_START_OFFSET..MIN_MESSAGE_SIZE)?;
+        let fields_len = u32::from_ne_bytes(fields_len.try_into().ok()?) as usize;
+        let fields_end = MIN_MESSAGE_SIZE.checked_add(fields_len)?;
+        let body_offset = fields_end + padding_for_8_bytes(fields_end);
+        let body_len = self.primary_header().body_len() as usize;
+        if body_offset.checked_add(body_len)? != self.bytes.len() {
+            return None;
+        }
+
+        let expected_sig = B::SIGNATURE_STR.as_bytes();
+        let actual_sig = native_body_signature(&self.bytes[..fields_end])?;
+        let c = zvariant::STRUCT_SIG_START_CHAR as u8;
+        let signature = if expected_sig.starts_with(&[c]) && !actual_sig.starts_with(&[c]) {
+            &expected_sig[1..expected_sig.len() - 1]
+        } else {
+            expected_sig
+        };
+        if signature != actual_sig {
+            return None;
+        }
+
+        B::read_at(&self.bytes[body_offset..], &mut 0)
+    }
+
+    /// Check the signature and read a body consisting of a single array of fixed-size elements,
+    /// without copying it.
+    ///
+    /// This is meant for very large arrays, e.g a multi-megabyte `ay`, for which [`body`] would
+    /// make a copy of the data: the returned [`FixedArray`] reads the elements from the message
+    /// bytes as they're iterated over, and gives access to the bytes of byte arrays as is. The
+    /// element type `T` can be any of the [`FixedBody`] types, so `a(ii)` bodies are supported as
+    /// well. See [`FixedArray`] for the details on alignment and padding.
+    ///
+    /// Only whole-body arrays are supported, so `T` must be the element type of the only argument.
+    /// Messages in a byte order other than the native one can't be read this way, and
+    /// [`MessageError::IncorrectEndian`] is returned for them.
+    ///
+    /// ```
+    ///# use std::error::Error;
+    ///#
+    /// use zbus::Message;
+    ///
+    /// let data = vec![42u8; 4 * 1024 * 1024];
+    /// let msg = Message::method(None, None, "/", None, "Put", &data)?;
+    /// let array = msg.body_array::<u8>()?;
+    /// assert_eq!(array.as_slice(), &data[..]);
+    ///
+    /// let msg = Message::method(None, None, "/", None, "Put", &vec![(1u32, -1i32); 3])?;
+    /// assert_eq!(msg.body_array::<(u32, i32)>()?.iter().count(), 3);
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`body`]: #method.body
+    /// [`FixedArray`]: struct.FixedArray.html
+    /// [`FixedBody`]: trait.FixedBody.html
+    /// [`MessageError::IncorrectEndian`]: enum.MessageError.html#variant.IncorrectEndian
+    pub fn body_array<T>(&self) -> Result<FixedArray<'_, T>, MessageError>
+    where
+        T: FixedBody + Type,
+    {
+        let element_sig = T::signature();
+        let actual_sig = self.body_signature()?;
+        if !actual_sig.starts_with(zvariant::ARRAY_SIGNATURE_CHAR)
+            || &actual_sig[1..] != element_sig.as_str()
+        {
+            return Err(MessageError::UnmatchedBodySignature);
+        }
+        if self.endian_sig() != NATIVE_ENDIAN_SIG {
+            return Err(MessageError::IncorrectEndian);
+        }
+        if self.bytes_to_completion()? != 0 {
+            return Err(MessageError::InsufficientData);
+        }
+
+        let body = &self.bytes[self.body_offset()?..];
+        let alignment = crate::fixed_body::element_alignment(&element_sig);
+        FixedArray::new(body, alignment).ok_or_else(|| {
+            MessageError::Variant(VariantError::Message(
+                "malformed array of fixed-size elements".into(),
+            ))
+        })
+    }
+
+    /// The number of file descriptors attached to the message.
+    ///
+    /// For a valid message, this is the same as the value of the `UnixFDs` header field (or 0 if
+    /// the field is absent). It's not possible to receive messages for which that doesn't hold;
+    /// this is mostly useful for diagnostics.
+    pub fn counted_fds(&self) -> usize {
         match &*self.fds.read().expect(LOCK_PANIC_MSG) {
-            Fds::Raw(fds) => fds.clone(),
-            Fds::Owned(fds) => fds.iter().map(|f| f.as_raw_fd()).collect(),
+            Fds::Raw(fds) => fds.len(),
+            Fds::Owned(fds) => fds.len(),
         }
     }
 
+    /// The raw file descriptors associated with the message.
+    ///
+    /// The message may still own these FDs and close them when dropped, see [`disown_fds`].
+    ///
+    /// [`disown_fds`]: #method.disown_fds
+    pub fn fds(&self) -> Vec<RawFd> {
+        self.fds.read().expect(LOCK_PANIC_MSG).raw()
+    }
+
     /// Get a reference to the byte encoding of the message.
     pub fn as_bytes(&self) -> &[u8] {
         &self.bytes
     }
 
+    /// Render the message in a human-readable form, as `dbus-monitor` does.
+    ///
+    /// The first line gives the type of the message and its header fields. It's followed by the
+    /// arguments in the body, in the format of [`PrettyPrinter`]. The alternate form of `Display`
+    /// (`{:#}`) gives the same.
+    ///
+    /// [`PrettyPrinter`]: https://docs.rs/zvariant/2/zvariant/struct.PrettyPrinter.html
+    pub fn to_pretty_string(&self) -> String {
+        self.to_pretty_string_with(PrettyOptions::default())
+    }
+
+    /// Render the message in a human-readable form, truncating the body as per `options`.
+    ///
+    /// See [`to_pretty_string`] for details.
+    ///
+    /// [`to_pretty_string`]: #method.to_pretty_string
+    pub fn to_pretty_string_with(&self, options: PrettyOptions) -> String {
+        let mut s = String::new();
+        self.write_pretty(&mut s, options)
+            .expect("rendering a message into a string can't fail");
+
+        s
+    }
+
     fn fields_len(&self) -> Result<usize, MessageError> {
-        zvariant::from_slice(&self.bytes[FIELDS_LEN_START_OFFSET..], dbus_context!(0))
-            .map(|v: u32| v as usize)
-            .map_err(MessageError::from)
+        let bytes = &self.bytes[FIELDS_LEN_START_OFFSET..];
+        with_dbus_context!(self.endian_sig(), 0, |ctxt| {
+            zvariant::from_slice(bytes, ctxt)
+                .map(|v: u32| v as usize)
+                .map_err(MessageError::from)
+        })
+    }
+
+    // The offset of the body in the encoded message, i-e the length of the padded header.
+    pub(crate) fn body_offset(&self) -> Result<usize, MessageError> {
+        let header_len = MIN_MESSAGE_SIZE + self.fields_len()?;
+
+        Ok(header_len + padding_for_8_bytes(header_len))
+    }
+
+    fn write_pretty<W: fmt::Write>(&self, out: &mut W, options: PrettyOptions) -> fmt::Result {
+        let header = match self.header() {
+            Ok(header) => header,
+            Err(e) => return writeln!(out, "malformed message: {}", e),
+        };
+        let ty = match header.message_type() {
+            Ok(MessageType::MethodCall) => "method call",
+            Ok(MessageType::MethodReturn) => "method return",
+            Ok(MessageType::Error) => "error",
+            Ok(MessageType::Signal) => "signal",
+            _ => "unknown message",
+        };
+        write!(out, "{}", ty)?;
+        if let Ok(Some(sender)) = header.sender() {
+            write!(out, " sender={}", sender)?;
+        }
+        if let Ok(Some(destination)) = header.destination() {
+            write!(out, " -> destination={}", destination)?;
+        }
+        if let Some(serial) = self.primary_header().serial_num() {
+            write!(out, " serial={}", serial)?;
+        }
+        if let Ok(Some(reply_serial)) = header.reply_serial() {
+            write!(out, " reply_serial={}", reply_serial)?;
+        }
+        if let Ok(Some(error_name)) = header.error_name() {
+            write!(out, " error_name={}", error_name)?;
+        }
+        if let Ok(Some(path)) = header.path() {
+            write!(out, " path={};", path)?;
+        }
+        if let Ok(Some(interface)) = header.interface() {
+            write!(out, " interface={};", interface)?;
+        }
+        if let Ok(Some(member)) = header.member() {
+            write!(out, " member={}", member)?;
+        }
+        writeln!(out)?;
+
+        // The arguments are walked as a structure, which the printer leaves out.
+        match self.walk_body(&mut PrettyPrinter::for_body(out, options)) {
+            Ok(()) => Ok(()),
+            Err(e) => writeln!(out, "   malformed body: {}", e),
+        }
+    }
+
+    // Walk the arguments in the body, as the fields of a structure.
+    pub(crate) fn walk_body<'m, W>(&'m self, walker: &mut W) -> Result<(), MessageError>
+    where
+        W: Walker<'m>,
+    {
+        self.walk_body_with_fds(&self.fds(), walker)
+    }
+
+    // Same as `walk_body`, resolving the file descriptor indices of the body with `fds` rather
+    // than the descriptors of the message.
+    pub(crate) fn walk_body_with_fds<'m, W>(
+        &'m self,
+        fds: &[RawFd],
+        walker: &mut W,
+    ) -> Result<(), MessageError>
+    where
+        W: Walker<'m>,
+    {
+        let signature = match self.body_signature() {
+            Ok(signature) if !signature.is_empty() => signature,
+            Ok(_) | Err(MessageError::NoBodySignature) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let body_offset = self.body_offset()?;
+        if body_offset > self.bytes.len() {
+            return Err(MessageError::InsufficientData);
+        }
+        let signature = Signature::from_string_unchecked(format!("({})", signature));
+
+        with_dbus_context!(self.endian_sig(), body_offset, self.value_budget, |ctxt| {
+            walk_slice_fds(
+                &self.bytes[body_offset..],
+                Some(fds),
+                ctxt,
+                &signature,
+                walker,
+            )?
+        });
+
+        Ok(())
+    }
+
+    // A copy of this message without its body, nor the `Signature` and `UnixFDs` fields describing
+    // it.
+    #[cfg(feature = "dbus")]
+    pub(crate) fn without_body(&self) -> Result<Self, MessageError> {
+        let header = self.header()?;
+        let mut fields = MessageFields::new();
+        for field in header.fields().iter() {
+            let code = field.code();
+            if code != MessageFieldCode::Signature && code != MessageFieldCode::UnixFDs {
+                fields.add(field.clone());
+            }
+        }
+        let mut primary = header.primary().clone();
+        primary.set_body_len(0);
+        let header = MessageHeader::new(primary, fields);
+
+        let mut bytes = vec![];
+        let mut cursor = Cursor::new(&mut bytes);
+        with_dbus_context!(self.endian_sig(), 0, |ctxt| {
+            zvariant::to_writer(&mut cursor, ctxt, &header)?
+        });
+        // The header is padded to 8 bytes, even without a body.
+        bytes.resize(bytes.len() + padding_for_8_bytes(bytes.len()), 0);
+
+        Ok(Self {
+            primary_header: header.into_primary(),
+            bytes,
+            fds: Arc::new(RwLock::new(Fds::Raw(vec![]))),
+            value_budget: self.value_budget,
+        })
+    }
+
+    // A copy of this message with its `Sender` field set to `sender`, as a bus does when routing
+    // it. The serial number is kept, since replies refer to it.
+    pub(crate) fn with_sender(&self, sender: &str) -> Result<Self, MessageError> {
+        let header = self.header()?;
+        let mut fields = MessageFields::new();
+        for field in header.fields().iter() {
+            if field.code() != MessageFieldCode::Sender {
+                fields.add(field.clone());
+            }
+        }
+        fields.add(MessageField::Sender(sender.into()));
+        let header = MessageHeader::new(header.primary().clone(), fields);
+
+        let mut bytes = Vec::with_capacity(self.bytes.len() + sender.len() + 16);
+        let mut cursor = Cursor::new(&mut bytes);
+        with_dbus_context!(self.endian_sig(), 0, |ctxt| {
+            zvariant::to_writer(&mut cursor, ctxt, &header)?
+        });
+        bytes.extend_from_slice(&self.bytes[self.body_offset()?..]);
+
+        Ok(Self {
+            primary_header: header.into_primary(),
+            bytes,
+            fds: Arc::new(RwLock::new(Fds::Raw(self.fds()))),
+            value_budget: self.value_budget,
+        })
     }
 }
 
@@ -590,6 +1375,10 @@ impl fmt::Debug for Message {
 
 impl fmt::Display for Message {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return self.write_pretty(f, PrettyOptions::default());
+        }
+
         let header = self.header();
         let (ty, error_name, sender, member) = if let Ok(h) = header.as_ref() {
             (
@@ -642,12 +1431,57 @@ impl fmt::Display for Message {
     }
 }
 
+// The body signature in `header`, the bytes of a message in native byte order up to the end of its
+// fields, read in place rather than deserializing the fields.
+//
+// Returns `None` if there's no signature field, or any field isn't of the type the specification
+// gives it, leaving it to the generic deserialization path to report the exact issue.
+fn native_body_signature(header: &[u8]) -> Option<&[u8]> {
+    let mut offset = MIN_MESSAGE_SIZE;
+    while offset < header.len() {
+        // Each field is a `(yv)` structure, so 8-byte aligned.
+        offset += padding_for_8_bytes(offset);
+        let code = *header.get(offset)?;
+        let sig_len = *header.get(offset + 1)? as usize;
+        let value_sig = header.get(offset + 2..offset + 2 + sig_len)?;
+        offset += 2 + sig_len + 1;
+
+        match value_sig {
+            b"g" => {
+                let len = *header.get(offset)? as usize;
+                let signature = header.get(offset + 1..offset + 1 + len)?;
+                if code == MessageFieldCode::Signature as u8 {
+                    return Some(signature);
+                }
+                offset += 1 + len + 1;
+            }
+            b"s" | b"o" => {
+                offset += padding_for_n_bytes(offset, 4);
+                let len = header.get(offset..offset + 4)?;
+                let len = u32::from_ne_bytes(len.try_into().ok()?) as usize;
+                offset = offset.checked_add(4 + len + 1)?;
+            }
+            b"u" => offset += padding_for_n_bytes(offset, 4) + 4,
+            _ => return None,
+        }
+    }
+
+    None
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Fds, Message, MessageError};
-    use std::os::unix::io::AsRawFd;
+    use super::{Fds, Message, MessageBuilder, MessageError};
+    use crate::{
+        utils::padding_for_8_bytes, EndianSig, MessageField, OwnedFd, RawBody, NATIVE_ENDIAN_SIG,
+    };
+    use byteorder::ByteOrder;
+    use std::{
+        collections::HashMap,
+        os::unix::io::{AsRawFd, FromRawFd},
+    };
     use test_env_log::test;
-    use zvariant::Fd;
+    use zvariant::{EncodingContext, Fd, PrettyOptions, Signature, Value};
 
     #[test]
     fn test() {
@@ -674,4 +1508,416 @@ mod tests {
             .unwrap();
         assert_eq!(e.to_string(), "Error org.freedesktop.zbus.Error: kaboom!");
     }
+
+    #[test]
+    fn pretty() {
+        let stdout = std::io::stdout();
+        let mut dict = HashMap::new();
+        dict.insert("k", Value::from(1u32));
+        let m = Message::method(
+            Some(":1.72"),
+            Some("org.zbus.Test"),
+            "/org/zbus",
+            Some("org.zbus.Test"),
+            "Do",
+            &("foo", vec![0u8; 20], dict, Fd::from(&stdout)),
+        )
+        .unwrap();
+
+        let pretty = m.to_pretty_string_with(PrettyOptions::new().max_bytes(4));
+        let expected = format!(
+            r#"method call sender=:1.72 -> destination=org.zbus.Test path=/org/zbus; interface=org.zbus.Test; member=Do
+   string "foo"
+   array of bytes [
+      00 00 00 00
+      ... 16 more bytes
+   ]
+   array [
+      dict entry(
+         string "k"
+         variant uint32 1
+      )
+   ]
+   file descriptor {}
+"#,
+            stdout.as_raw_fd(),
+        );
+        assert_eq!(pretty, expected);
+        assert_eq!(format!("{:#}", m), m.to_pretty_string());
+        assert!(m.to_pretty_string().contains(&"00 ".repeat(16)));
+
+        let m = Message::signal(None, None, "/", "org.zbus.Test", "Ping", &()).unwrap();
+        assert_eq!(
+            m.to_pretty_string(),
+            "signal path=/; interface=org.zbus.Test; member=Ping\n"
+        );
+    }
+
+    #[test]
+    fn body_fixed() {
+        let m = Message::method(None, None, "/", None, "do", &42u32).unwrap();
+        assert_eq!(m.body_fixed::<u32>().unwrap(), 42);
+        assert_eq!(m.body_fixed::<(u32,)>(), m.body::<(u32,)>());
+
+        let body = (7u8, -2i16, true, 3.5f64);
+        let m = Message::method(None, None, "/", None, "do", &body).unwrap();
+        assert_eq!(m.body_fixed::<(u8, i16, bool, f64)>().unwrap(), body);
+
+        let body = (-1i32, u64::MAX);
+        let m = Message::method(None, None, "/", None, "do", &body).unwrap();
+        assert_eq!(m.body_fixed::<(i32, u64)>().unwrap(), body);
+        assert_eq!(
+            m.body_fixed::<(u64, i32)>().unwrap_err(),
+            MessageError::UnmatchedBodySignature
+        );
+
+        // Non-fixed bodies go through the generic path.
+        let m = Message::method(None, None, "/", None, "do", &"foo").unwrap();
+        assert_eq!(
+            m.body_fixed::<u32>().unwrap_err(),
+            MessageError::UnmatchedBodySignature
+        );
+        let m = Message::method(None, None, "/", None, "do", &()).unwrap();
+        assert_eq!(m.body_fixed::<u8>(), m.body::<u8>());
+    }
+
+    #[test]
+    fn native_body_signature() {
+        let call = Message::method(
+            Some(":1.42"),
+            Some("org.zbus.Test"),
+            "/org/zbus/Test",
+            Some("org.zbus.Test"),
+            "Do",
+            &(1u8, 2u32),
+        )
+        .unwrap();
+        let reply = Message::method_reply(Some(":1.43"), &call, &-1i64).unwrap();
+        let error = Message::method_error(None, &call, "org.zbus.Error", &42u32).unwrap();
+        let signal = Message::signal(None, None, "/", "org.zbus.Test", "Ping", &()).unwrap();
+        for m in &[call, reply, error, signal] {
+            let fields_end = MIN_MESSAGE_SIZE + m.fields_len().unwrap();
+            let signature = super::native_body_signature(&m.bytes[..fields_end]);
+            let expected = m.body_signature().ok();
+            assert_eq!(signature, expected.as_ref().map(|s| s.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn body_prefix() {
+        // Appended basic arguments.
+        let m = Message::method(None, None, "/", None, "do", &("foo", 42u32, true)).unwrap();
+        assert_eq!(m.body_prefix::<(&str, u32)>().unwrap(), ("foo", 42));
+        assert_eq!(m.body_prefix::<&str>().unwrap(), "foo");
+        assert_eq!(m.body_prefix::<()>().unwrap(), ());
+        assert_eq!(
+            m.body::<(&str, u32)>().unwrap_err(),
+            MessageError::UnmatchedBodySignature
+        );
+
+        // Appended container arguments, after a structure.
+        let mut extra = HashMap::new();
+        extra.insert("bar", Value::from(7u8));
+        let body = (("foo", 42u32), vec![1u8, 2], extra);
+        let m = Message::method(None, None, "/", None, "do", &body).unwrap();
+        assert_eq!(m.body_prefix::<((&str, u32),)>().unwrap(), (("foo", 42),));
+        assert_eq!(m.body_prefix::<(&str, u32)>().unwrap(), ("foo", 42));
+        assert_eq!(
+            m.body_prefix::<((&str, u32), Vec<u8>)>().unwrap(),
+            (("foo", 42), vec![1, 2])
+        );
+
+        // Changed arguments are still an error, even at the end of the expected ones.
+        for m in &[
+            Message::method(None, None, "/", None, "do", &(("foo", 42u32, 7u8),)).unwrap(),
+            Message::method(None, None, "/", None, "do", &(vec![("foo", 42u32)],)).unwrap(),
+            Message::method(None, None, "/", None, "do", &(42u32, "foo")).unwrap(),
+            Message::method(None, None, "/", None, "do", &"foo").unwrap(),
+        ] {
+            assert_eq!(
+                m.body_prefix::<((&str, u32), u8)>().unwrap_err(),
+                MessageError::UnmatchedBodySignature
+            );
+            assert_eq!(
+                m.body_prefix::<(&str, u32)>().unwrap_err(),
+                MessageError::UnmatchedBodySignature
+            );
+        }
+    }
+
+    #[test]
+    fn raw_body() {
+        let stdout = std::io::stdout();
+        let body = (Fd::from(&stdout), "foo", 42u32);
+        let expected =
+            Message::method(Some(":1.72"), Some("org.zbus"), "/", None, "do", &body).unwrap();
+
+        let raw_body = RawBody::new(&body).unwrap();
+        let m =
+            Message::method_raw_body(Some(":1.72"), Some("org.zbus"), "/", None, "do", &raw_body)
+                .unwrap();
+        assert_eq!(m.as_bytes(), expected.as_bytes());
+        assert_eq!(m.fds(), vec![stdout.as_raw_fd()]);
+        assert_eq!(m.body_signature().unwrap().as_str(), "hsu");
+
+        // Cloning doesn't copy the encoded bytes.
+        let clone = raw_body.clone();
+        assert_eq!(clone.bytes().as_ptr(), raw_body.bytes().as_ptr());
+
+        let ctxt = EncodingContext::<byteorder::NativeEndian>::new_dbus(4);
+        let encoded = zvariant::to_bytes(ctxt, &"foo").unwrap();
+        RawBody::from_bytes(ctxt, Signature::from_str_unchecked("s"), encoded, vec![]).unwrap_err();
+    }
+
+    fn foreign(endian: EndianSig) -> EndianSig {
+        match endian {
+            EndianSig::Big => EndianSig::Little,
+            EndianSig::Little => EndianSig::Big,
+        }
+    }
+
+    #[test]
+    fn endianness() {
+        let stdout = std::io::stdout();
+        // The `u64` of each element needs padding after the `u8`.
+        let body = (Fd::from(&stdout), vec![(1u8, 2u64), (3, 4)], 42u32);
+        for endian in [EndianSig::Big, EndianSig::Little].iter().copied() {
+            let m = MessageBuilder::method_call("/org/zbus", "Do")
+                .unwrap()
+                .sender(":1.72")
+                .interface("org.zbus.Test")
+                .endian_sig(endian)
+                .build(&body)
+                .unwrap();
+            assert_eq!(m.primary_header().endian_sig(), endian);
+            assert_eq!(m.as_bytes()[0], endian as u8);
+            let body_len = match endian {
+                EndianSig::Big => byteorder::BE::read_u32(&m.as_bytes()[4..]),
+                EndianSig::Little => byteorder::LE::read_u32(&m.as_bytes()[4..]),
+            };
+            assert_eq!(body_len, 4 + 4 + 2 * 16 + 4);
+            assert_eq!(m.primary_header().body_len(), body_len);
+            assert_eq!(
+                m.as_bytes().len(),
+                m.body_offset().unwrap() + body_len as usize
+            );
+
+            let header = m.header().unwrap();
+            assert_eq!(header.sender().unwrap(), Some(":1.72"));
+            assert_eq!(header.member().unwrap(), Some("Do"));
+            assert_eq!(header.unix_fds().unwrap(), Some(1));
+            assert_eq!(m.body_signature().unwrap().as_str(), "ha(yt)u");
+            let decoded: (Fd, Vec<(u8, u64)>, u32) = m.body().unwrap();
+            assert_eq!(decoded, body);
+            assert_eq!(
+                m.body_fixed::<u32>().unwrap_err(),
+                MessageError::UnmatchedBodySignature
+            );
+            let fixed = MessageBuilder::method_call("/org/zbus", "Do")
+                .unwrap()
+                .endian_sig(endian)
+                .build(&(-1i32, u64::MAX))
+                .unwrap();
+            assert_eq!(fixed.body_fixed::<(i32, u64)>().unwrap(), (-1, u64::MAX));
+
+            // The encoding is understood as is by the receiving end.
+            let mut received = Message::from_bytes(m.as_bytes()).unwrap();
+            assert_eq!(received.primary_header().endian_sig(), endian);
+            assert_eq!(received.bytes_to_completion().unwrap(), 0);
+            *received.fds.write().unwrap() = Fds::Raw(m.fds());
+            assert_eq!(received.body::<(Fd, Vec<(u8, u64)>, u32)>().unwrap(), body);
+            received
+                .modify_primary_header(|primary| {
+                    primary.serial_num_or_init(|| 7);
+                    Ok(())
+                })
+                .unwrap();
+            assert_eq!(received.header().unwrap().primary().serial_num(), Some(&7));
+            assert_eq!(
+                received.modify_primary_header(|primary| {
+                    primary.set_endian_sig(foreign(endian));
+                    Ok(())
+                }),
+                Err(MessageError::IncorrectEndian)
+            );
+            assert_eq!(received.primary_header().endian_sig(), endian);
+            let routed = received.with_sender(":1.73").unwrap();
+            assert_eq!(routed.header().unwrap().sender().unwrap(), Some(":1.73"));
+            assert_eq!(routed.body::<(Fd, Vec<(u8, u64)>, u32)>().unwrap(), body);
+
+            let e = MessageBuilder::error(&received, "org.zbus.Error")
+                .unwrap()
+                .endian_sig(endian)
+                .build(&"kaboom!")
+                .unwrap();
+            let header = e.header().unwrap();
+            assert_eq!(header.destination().unwrap(), Some(":1.72"));
+            assert_eq!(header.reply_serial().unwrap(), Some(7));
+            assert_eq!(e.body::<&str>().unwrap(), "kaboom!");
+            assert_eq!(e.to_string(), "Error org.zbus.Error: kaboom!");
+        }
+
+        // A raw body is always in the native byte order.
+        let raw_body = RawBody::new(&42u32).unwrap();
+        let res = MessageBuilder::method_call("/", "Do")
+            .unwrap()
+            .endian_sig(foreign(NATIVE_ENDIAN_SIG))
+            .build_raw_body(&raw_body);
+        assert_eq!(res.unwrap_err(), MessageError::IncorrectEndian);
+    }
+
+    #[test]
+    fn take_fd() {
+        let stdout = std::io::stdout();
+        let m = Message::method(None, None, "/", None, "do", &(Fd::from(&stdout))).unwrap();
+        let fd = nix::unistd::dup(stdout.as_raw_fd()).unwrap();
+        // Not owned by the message yet.
+        assert_eq!(
+            m.take_fd(Fd::from(fd)).unwrap_err(),
+            MessageError::FdNotOwned
+        );
+
+        m.set_owned_fds(vec![unsafe { OwnedFd::from_raw_fd(fd) }]);
+        let taken = m.take_fd(m.body::<Fd>().unwrap()).unwrap();
+        assert_eq!(taken.as_raw_fd(), fd);
+        assert_eq!(
+            m.take_fd(Fd::from(fd)).unwrap_err(),
+            MessageError::FdNotOwned
+        );
+
+        // The message must not close the FD anymore.
+        drop(m);
+        nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_GETFD).unwrap();
+    }
+
+    #[test]
+    fn adopt_fd() {
+        let stdout = std::io::stdout();
+        let fd = unsafe { OwnedFd::from_raw_fd(nix::unistd::dup(stdout.as_raw_fd()).unwrap()) };
+        let raw_fd = fd.as_raw_fd();
+        let m = Message::method(None, None, "/", None, "do", &(Fd::from(&fd))).unwrap();
+        assert_eq!(
+            m.take_fd(Fd::from(raw_fd)).unwrap_err(),
+            MessageError::FdNotOwned
+        );
+
+        m.adopt_fd(fd).unwrap();
+        assert_eq!(m.fds(), vec![raw_fd]);
+        let other = unsafe { OwnedFd::from_raw_fd(nix::unistd::dup(stdout.as_raw_fd()).unwrap()) };
+        assert_eq!(m.adopt_fd(other).unwrap_err(), MessageError::UnknownFd);
+
+        // The same FD, not a duplicate, is handed back.
+        let taken = m.take_fd(m.body::<Fd>().unwrap()).unwrap();
+        assert_eq!(taken.as_raw_fd(), raw_fd);
+    }
+
+    #[test]
+    fn unknown_field() {
+        let m = Message::method(Some(":1.72"), None, "/", None, "do", &()).unwrap();
+        let mut header = m.header().unwrap();
+        let n_fields = header.fields().len();
+        let credentials = MessageField::Unknown(42, Value::from("some credentials"));
+        header.fields_mut().add(credentials.clone());
+
+        // Create the message, as it would be received from a newer peer.
+        let mut bytes = zvariant::to_bytes(
+            EncodingContext::<byteorder::NativeEndian>::new_dbus(0),
+            &header,
+        )
+        .unwrap();
+        bytes.resize(bytes.len() + padding_for_8_bytes(bytes.len()), 0);
+        let received = Message::from_bytes(&bytes).unwrap();
+
+        let header = received.header().unwrap();
+        assert_eq!(header.member().unwrap(), Some("do"));
+        let fields = header.fields();
+        assert_eq!(fields.iter().count(), n_fields + 1);
+        assert_eq!(fields.get_field_by_code(42), Some(&credentials));
+        assert_eq!(fields.get_field_by_code(43), None);
+        assert_eq!(credentials.raw_code(), 42);
+
+        // Nothing is lost when writing the header back.
+        let header_bytes = zvariant::to_bytes(
+            EncodingContext::<byteorder::NativeEndian>::new_dbus(0),
+            &header,
+        )
+        .unwrap();
+        assert_eq!(header_bytes, bytes[..header_bytes.len()]);
+    }
+
+    #[test]
+    fn from_raw_parts() {
+        let stdout = std::io::stdout();
+        let m = Message::method(
+            Some(":1.72"),
+            Some("org.zbus.Peer"),
+            "/org/zbus/Peer",
+            Some("org.zbus.Peer"),
+            "Pass",
+            &(Fd::from(&stdout), "foo"),
+        )
+        .unwrap();
+        let bytes = m.as_bytes().to_vec();
+        let dup = || unsafe { OwnedFd::from_raw_fd(nix::unistd::dup(stdout.as_raw_fd()).unwrap()) };
+
+        let fd = dup();
+        let raw_fd = fd.as_raw_fd();
+        let received = Message::from_raw_parts(bytes.clone(), vec![fd]).unwrap();
+        assert_eq!(received.as_bytes(), m.as_bytes());
+        assert_eq!(received.to_string(), m.to_string());
+        assert_eq!(received.fds(), vec![raw_fd]);
+        let (fd, s): (Fd, String) = received.body().unwrap();
+        assert_eq!(fd.as_raw_fd(), raw_fd);
+        assert_eq!(s, "foo");
+
+        assert_eq!(
+            Message::from_raw_parts(bytes.clone(), vec![]).unwrap_err(),
+            MessageError::UnmatchedFdCount
+        );
+        assert_eq!(
+            Message::from_raw_parts(bytes[..bytes.len() - 1].to_vec(), vec![dup()]).unwrap_err(),
+            MessageError::InsufficientData
+        );
+        let mut longer = bytes;
+        longer.push(0);
+        assert_eq!(
+            Message::from_raw_parts(longer, vec![dup()]).unwrap_err(),
+            MessageError::ExcessData
+        );
+
+        let e = Message::method_error(None, &m, "org.zbus.Error", &"kaboom!").unwrap();
+        let received = Message::from_raw_parts(e.as_bytes().to_vec(), vec![]).unwrap();
+        assert_eq!(received.to_string(), "Error org.zbus.Error: kaboom!");
+        assert_eq!(
+            received.header().unwrap().reply_serial().unwrap(),
+            m.primary_header().serial_num().cloned()
+        );
+
+        let s = Message::signal(None, None, "/", "org.zbus.Peer", "Passed", &42u32).unwrap();
+        let received = Message::from_raw_parts(s.as_bytes().to_vec(), vec![]).unwrap();
+        assert_eq!(received.as_bytes(), s.as_bytes());
+        assert_eq!(received.header().unwrap().member().unwrap(), Some("Passed"));
+        assert_eq!(received.body::<u32>().unwrap(), 42);
+    }
+
+    #[test]
+    fn value_budget() {
+        let body = vec![vec![0u8; 2]; 3];
+        let m = Message::signal(None, None, "/", "org.zbus.Peer", "Passed", &body).unwrap();
+        let mut received = Message::from_raw_parts(m.as_bytes().to_vec(), vec![]).unwrap();
+        assert_eq!(received.value_budget(), None);
+
+        // 3 arrays of 2 bytes, for 9 values.
+        received.set_value_budget(Some(9));
+        assert_eq!(received.body::<Vec<Vec<u8>>>().unwrap(), body);
+        assert_eq!(received.body_structure().unwrap().fields().len(), 1);
+
+        received.set_value_budget(Some(8));
+        let exceeded = MessageError::Variant(zvariant::Error::BudgetExceeded(8));
+        assert_eq!(received.body::<Vec<Vec<u8>>>().unwrap_err(), exceeded);
+        assert_eq!(received.body_structure().unwrap_err(), exceeded);
+
+        received.set_value_budget(None);
+        assert_eq!(received.body::<Vec<Vec<u8>>>().unwrap(), body);
+    }
 }
@@ -7,7 +7,7 @@ use serde::{
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
 use static_assertions::assert_impl_all;
-use zvariant::{derive::Type, ObjectPath, Signature, Str, Type, Value};
+use zvariant::{ObjectPath, Signature, StaticType, Str, Type, Value};
 
 /// The message field code.
 ///
@@ -19,7 +19,7 @@ use zvariant::{derive::Type, ObjectPath, Signature, Str, Type, Value};
 /// [retrieve a specific field]: struct.MessageFields.html#method.get_field
 /// [`MessageFields`]: struct.MessageFields.html
 #[repr(u8)]
-#[derive(Copy, Clone, Debug, Deserialize_repr, PartialEq, Serialize_repr, Type)]
+#[derive(Copy, Clone, Debug, Deserialize_repr, PartialEq, Serialize_repr)]
 pub enum MessageFieldCode {
     /// Code for [`MessageField::Invalid`](enum.MessageField.html#variant.Invalid)
     Invalid = 0,
@@ -45,6 +45,16 @@ pub enum MessageFieldCode {
 
 assert_impl_all!(MessageFieldCode: Send, Sync, Unpin);
 
+impl Type for MessageFieldCode {
+    fn signature() -> Signature<'static> {
+        Signature::from_str_unchecked(<Self as StaticType>::SIGNATURE_STR)
+    }
+}
+
+impl StaticType for MessageFieldCode {
+    const SIGNATURE_STR: &'static str = u8::SIGNATURE_STR;
+}
+
 impl From<u8> for MessageFieldCode {
     fn from(val: u8) -> MessageFieldCode {
         match val {
@@ -64,6 +74,13 @@ impl From<u8> for MessageFieldCode {
 
 impl<'f> MessageField<'f> {
     /// Get the associated code for this field.
+    ///
+    /// This is [`MessageFieldCode::Invalid`] for [`MessageField::Unknown`] fields. Use [`raw_code`]
+    /// to get the actual code.
+    ///
+    /// [`MessageFieldCode::Invalid`]: enum.MessageFieldCode.html#variant.Invalid
+    /// [`MessageField::Unknown`]: enum.MessageField.html#variant.Unknown
+    /// [`raw_code`]: #method.raw_code
     pub fn code(&self) -> MessageFieldCode {
         match self {
             MessageField::Path(_) => MessageFieldCode::Path,
@@ -75,7 +92,15 @@ impl<'f> MessageField<'f> {
             MessageField::Sender(_) => MessageFieldCode::Sender,
             MessageField::Signature(_) => MessageFieldCode::Signature,
             MessageField::UnixFDs(_) => MessageFieldCode::UnixFDs,
-            MessageField::Invalid => MessageFieldCode::Invalid,
+            MessageField::Unknown(_, _) | MessageField::Invalid => MessageFieldCode::Invalid,
+        }
+    }
+
+    /// Get the numeric code for this field, as encoded in the message.
+    pub fn raw_code(&self) -> u8 {
+        match self {
+            MessageField::Unknown(code, _) => *code,
+            _ => self.code() as u8,
         }
     }
 }
@@ -113,38 +138,46 @@ pub enum MessageField<'f> {
     Signature(Signature<'f>),
     /// The number of Unix file descriptors that accompany the message.
     UnixFDs(u32),
+    /// A field zbus doesn't know about, with its code and value.
+    ///
+    /// As per the specification, these must be ignored but they're kept around so that they're
+    /// not lost when a message is forwarded.
+    Unknown(u8, Value<'f>),
 }
 
 assert_impl_all!(MessageField<'_>: Send, Sync, Unpin);
 
 impl<'f> Type for MessageField<'f> {
     fn signature() -> Signature<'static> {
-        Signature::from_str_unchecked("(yv)")
+        Signature::from_str_unchecked(<Self as StaticType>::SIGNATURE_STR)
     }
 }
 
+impl<'f> StaticType for MessageField<'f> {
+    const SIGNATURE_STR: &'static str = "(yv)";
+}
+
 impl<'f> Serialize for MessageField<'f> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let tuple: (MessageFieldCode, Value<'_>) = match self {
-            MessageField::Path(value) => (MessageFieldCode::Path, value.clone().into()),
-            MessageField::Interface(value) => (MessageFieldCode::Interface, value.as_str().into()),
-            MessageField::Member(value) => (MessageFieldCode::Member, value.as_str().into()),
-            MessageField::ErrorName(value) => (MessageFieldCode::ErrorName, value.as_str().into()),
-            MessageField::ReplySerial(value) => (MessageFieldCode::ReplySerial, (*value).into()),
-            MessageField::Destination(value) => {
-                (MessageFieldCode::Destination, value.as_str().into())
-            }
-            MessageField::Sender(value) => (MessageFieldCode::Sender, value.as_str().into()),
-            MessageField::Signature(value) => (MessageFieldCode::Signature, value.clone().into()),
-            MessageField::UnixFDs(value) => (MessageFieldCode::UnixFDs, (*value).into()),
+        let value: Value<'_> = match self {
+            MessageField::Path(value) => value.clone().into(),
+            MessageField::Interface(value) => value.as_str().into(),
+            MessageField::Member(value) => value.as_str().into(),
+            MessageField::ErrorName(value) => value.as_str().into(),
+            MessageField::ReplySerial(value) => (*value).into(),
+            MessageField::Destination(value) => value.as_str().into(),
+            MessageField::Sender(value) => value.as_str().into(),
+            MessageField::Signature(value) => value.clone().into(),
+            MessageField::UnixFDs(value) => (*value).into(),
+            MessageField::Unknown(_, value) => value.clone(),
             // This is a programmer error
             MessageField::Invalid => panic!("Attempt to serialize invalid MessageField"),
         };
 
-        tuple.serialize(serializer)
+        (self.raw_code(), value).serialize(serializer)
     }
 }
 
@@ -153,7 +186,8 @@ impl<'de: 'f, 'f> Deserialize<'de> for MessageField<'f> {
     where
         D: Deserializer<'de>,
     {
-        let (code, value) = <(MessageFieldCode, Value<'_>)>::deserialize(deserializer)?;
+        let (raw_code, value) = <(u8, Value<'_>)>::deserialize(deserializer)?;
+        let code = MessageFieldCode::from(raw_code);
         Ok(match code {
             MessageFieldCode::Path => {
                 MessageField::Path(ObjectPath::try_from(value).map_err(D::Error::custom)?)
@@ -188,12 +222,13 @@ impl<'de: 'f, 'f> Deserialize<'de> for MessageField<'f> {
             MessageFieldCode::UnixFDs => {
                 MessageField::UnixFDs(u32::try_from(value).map_err(D::Error::custom)?)
             }
-            MessageFieldCode::Invalid => {
+            MessageFieldCode::Invalid if raw_code == 0 => {
                 return Err(Error::invalid_value(
                     serde::de::Unexpected::Unsigned(code as u64),
                     &"A valid D-Bus message field code",
                 ));
             }
+            MessageFieldCode::Invalid => MessageField::Unknown(raw_code, value),
         })
     }
 }
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use static_assertions::assert_impl_all;
-use zvariant::derive::Type;
+use zvariant::{Signature, StaticType, Type};
 
 use crate::{MessageField, MessageFieldCode};
 
@@ -10,11 +10,21 @@ const MAX_FIELDS_IN_MESSAGE: usize = 16;
 /// A collection of [`MessageField`] instances.
 ///
 /// [`MessageField`]: enum.MessageField.html
-#[derive(Debug, Serialize, Deserialize, Type)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct MessageFields<'m>(#[serde(borrow)] Vec<MessageField<'m>>);
 
 assert_impl_all!(MessageFields<'_>: Send, Sync, Unpin);
 
+impl Type for MessageFields<'_> {
+    fn signature() -> Signature<'static> {
+        Signature::from_str_unchecked(<Self as StaticType>::SIGNATURE_STR)
+    }
+}
+
+impl StaticType for MessageFields<'_> {
+    const SIGNATURE_STR: &'static str = Vec::<MessageField<'_>>::SIGNATURE_STR;
+}
+
 impl<'m> MessageFields<'m> {
     /// Creates an empty collection of fields.
     pub fn new() -> Self {
@@ -28,6 +38,15 @@ impl<'m> MessageFields<'m> {
         self.0.push(field);
     }
 
+    // Adds `field`, replacing the field with the same code, if any.
+    pub(crate) fn replace<'f: 'm>(&mut self, field: MessageField<'f>) {
+        let code = field.raw_code();
+        match self.0.iter_mut().find(|f| f.raw_code() == code) {
+            Some(f) => *f = field,
+            None => self.0.push(field),
+        }
+    }
+
     /// Returns a slice with all the [`MessageField`] in the message.
     ///
     /// [`MessageField`]: enum.MessageField.html
@@ -35,6 +54,16 @@ impl<'m> MessageFields<'m> {
         &self.0
     }
 
+    /// Returns an iterator over all the [`MessageField`] in the message.
+    ///
+    /// This includes the fields unknown to zbus, as [`MessageField::Unknown`].
+    ///
+    /// [`MessageField`]: enum.MessageField.html
+    /// [`MessageField::Unknown`]: enum.MessageField.html#variant.Unknown
+    pub fn iter(&self) -> std::slice::Iter<'_, MessageField<'m>> {
+        self.0.iter()
+    }
+
     /// Gets a reference to a specific [`MessageField`] by its code.
     ///
     /// Returns `None` if the message has no such field.
@@ -44,6 +73,17 @@ impl<'m> MessageFields<'m> {
         self.0.iter().find(|f| f.code() == code)
     }
 
+    /// Gets a reference to a specific [`MessageField`] by its numeric code.
+    ///
+    /// Unlike [`get_field`], this also works for the fields unknown to zbus. Returns `None` if the
+    /// message has no such field.
+    ///
+    /// [`MessageField`]: enum.MessageField.html
+    /// [`get_field`]: #method.get_field
+    pub fn get_field_by_code(&self, code: u8) -> Option<&MessageField<'m>> {
+        self.0.iter().find(|f| f.raw_code() == code)
+    }
+
     /// Consumes the `MessageFields` and returns a specific [`MessageField`] by its code.
     ///
     /// Returns `None` if the message has no such field.
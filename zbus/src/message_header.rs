@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
 use static_assertions::assert_impl_all;
-use zvariant::{derive::Type, ObjectPath, Signature};
+use zvariant::{ConstSignature, ObjectPath, Signature, StaticType, Type};
 
 use crate::{MessageError, MessageField, MessageFieldCode, MessageFields};
 
@@ -15,7 +15,7 @@ pub(crate) const MIN_MESSAGE_SIZE: usize = PRIMARY_HEADER_SIZE + 4;
 
 /// D-Bus code for endianness.
 #[repr(u8)]
-#[derive(Debug, Copy, Clone, Deserialize_repr, PartialEq, Serialize_repr, Type)]
+#[derive(Debug, Copy, Clone, Deserialize_repr, PartialEq, Serialize_repr)]
 pub enum EndianSig {
     /// The D-Bus message is in big-endian (network) byte order.
     Big = b'B',
@@ -26,6 +26,16 @@ pub enum EndianSig {
 
 assert_impl_all!(EndianSig: Send, Sync, Unpin);
 
+impl Type for EndianSig {
+    fn signature() -> Signature<'static> {
+        Signature::from_str_unchecked(<Self as StaticType>::SIGNATURE_STR)
+    }
+}
+
+impl StaticType for EndianSig {
+    const SIGNATURE_STR: &'static str = u8::SIGNATURE_STR;
+}
+
 // Such a shame I've to do this manually
 impl TryFrom<u8> for EndianSig {
     type Error = MessageError;
@@ -48,7 +58,7 @@ pub const NATIVE_ENDIAN_SIG: EndianSig = EndianSig::Little;
 
 /// Message header representing the D-Bus type of the message.
 #[repr(u8)]
-#[derive(Debug, Copy, Clone, Deserialize_repr, PartialEq, Serialize_repr, Type)]
+#[derive(Debug, Copy, Clone, Deserialize_repr, PartialEq, Serialize_repr)]
 pub enum MessageType {
     /// Invalid message type. All unknown types on received messages are treated as invalid.
     Invalid = 0,
@@ -64,6 +74,16 @@ pub enum MessageType {
 
 assert_impl_all!(MessageType: Send, Sync, Unpin);
 
+impl Type for MessageType {
+    fn signature() -> Signature<'static> {
+        Signature::from_str_unchecked(<Self as StaticType>::SIGNATURE_STR)
+    }
+}
+
+impl StaticType for MessageType {
+    const SIGNATURE_STR: &'static str = u8::SIGNATURE_STR;
+}
+
 // Such a shame I've to do this manually
 impl From<u8> for MessageType {
     fn from(val: u8) -> MessageType {
@@ -79,7 +99,7 @@ impl From<u8> for MessageType {
 
 /// Pre-defined flags that can be passed in Message header.
 #[repr(u8)]
-#[derive(Debug, Copy, Clone, PartialEq, BitFlags, Type)]
+#[derive(Debug, Copy, Clone, PartialEq, BitFlags)]
 pub enum MessageFlags {
     /// This message does not expect method return replies or error replies, even if it is of a type
     /// that can have a reply; the reply should be omitted.
@@ -100,17 +120,29 @@ pub enum MessageFlags {
 
 assert_impl_all!(MessageFlags: Send, Sync, Unpin);
 
+impl Type for MessageFlags {
+    fn signature() -> Signature<'static> {
+        Signature::from_str_unchecked(<Self as StaticType>::SIGNATURE_STR)
+    }
+}
+
+impl StaticType for MessageFlags {
+    const SIGNATURE_STR: &'static str = u8::SIGNATURE_STR;
+}
+
 #[derive(Clone, Debug)]
 struct SerialNum(OnceCell<u32>);
 
-// FIXME: Can use `zvariant::derive::Type` after `zvariant` provides a blanket implementation for
-// `OnceCell<T>`.
-impl zvariant::Type for SerialNum {
+impl Type for SerialNum {
     fn signature() -> Signature<'static> {
-        u32::signature()
+        Signature::from_str_unchecked(<Self as StaticType>::SIGNATURE_STR)
     }
 }
 
+impl StaticType for SerialNum {
+    const SIGNATURE_STR: &'static str = u32::SIGNATURE_STR;
+}
+
 // Unfortunately Serde doesn't provide a blanket impl. for `Cell<T>` so we have to implement manually.
 //
 // https://github.com/serde-rs/serde/issues/1952
@@ -142,7 +174,7 @@ impl<'de> Deserialize<'de> for SerialNum {
 /// The primary message header, which is present in all D-Bus messages.
 ///
 /// This header contains all the essential information about a message, regardless of its type.
-#[derive(Clone, Debug, Serialize, Deserialize, Type)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MessagePrimaryHeader {
     endian_sig: EndianSig,
     msg_type: MessageType,
@@ -154,6 +186,25 @@ pub struct MessagePrimaryHeader {
 
 assert_impl_all!(MessagePrimaryHeader: Send, Sync, Unpin);
 
+impl Type for MessagePrimaryHeader {
+    fn signature() -> Signature<'static> {
+        Signature::from_str_unchecked(<Self as StaticType>::SIGNATURE_STR)
+    }
+}
+
+impl StaticType for MessagePrimaryHeader {
+    const SIGNATURE_STR: &'static str = (&ConstSignature::new()
+        .push("(")
+        .push(EndianSig::SIGNATURE_STR)
+        .push(MessageType::SIGNATURE_STR)
+        .push(BitFlags::<MessageFlags>::SIGNATURE_STR)
+        .push(u8::SIGNATURE_STR)
+        .push(u32::SIGNATURE_STR)
+        .push(SerialNum::SIGNATURE_STR)
+        .push(")"))
+        .as_str();
+}
+
 impl MessagePrimaryHeader {
     /// Create a new `MessagePrimaryHeader` instance.
     pub fn new(msg_type: MessageType, body_len: u32) -> Self {
@@ -237,6 +288,12 @@ impl MessagePrimaryHeader {
     {
         self.serial_num.0.get_or_init(f)
     }
+
+    // Unlike `serial_num_or_init`, this replaces any serial number the header already has.
+    pub(crate) fn set_serial_num(&mut self, serial: u32) {
+        self.serial_num = SerialNum(OnceCell::new());
+        let _ = self.serial_num.0.set(serial);
+    }
 }
 
 /// The message header, containing all the metadata about the message.
@@ -245,7 +302,7 @@ impl MessagePrimaryHeader {
 ///
 /// [`MessagePrimaryHeader`]: struct.MessagePrimaryHeader.html
 /// [`MessageFields`]: struct.MessageFields.html
-#[derive(Debug, Serialize, Deserialize, Type)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct MessageHeader<'m> {
     primary: MessagePrimaryHeader,
     #[serde(borrow)]
@@ -255,6 +312,22 @@ pub struct MessageHeader<'m> {
 
 assert_impl_all!(MessageHeader<'_>: Send, Sync, Unpin);
 
+impl Type for MessageHeader<'_> {
+    fn signature() -> Signature<'static> {
+        Signature::from_str_unchecked(<Self as StaticType>::SIGNATURE_STR)
+    }
+}
+
+impl StaticType for MessageHeader<'_> {
+    const SIGNATURE_STR: &'static str = (&ConstSignature::new()
+        .push("(")
+        .push(MessagePrimaryHeader::SIGNATURE_STR)
+        .push(MessageFields::SIGNATURE_STR)
+        .push(<((),)>::SIGNATURE_STR)
+        .push(")"))
+        .as_str();
+}
+
 macro_rules! get_field {
     ($self:ident, $kind:ident) => {
         get_field!($self, $kind, (|v| v))
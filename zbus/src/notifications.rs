@@ -0,0 +1,579 @@
+#![cfg(feature = "notifications")]
+
+//! Desktop notifications (`notifications` feature)
+//!
+//! This module provides a proxy for the [`org.freedesktop.Notifications`] interface, together with
+//! the types needed to make use of it without having to deal with its loosely-typed `a{sv}` hints:
+//!
+//! * [`Hints`] builds the hints dictionary of a notification, including the `(iiibiiay)`
+//!   structure of [`ImageData`].
+//! * [`CloseReason`] types the reason argument of the `NotificationClosed` signal.
+//! * [`Actions`] keeps track of the callbacks to invoke when the user activates an action of a
+//!   notification.
+//!
+//! # Example
+//!
+//! ```no_run
+//!# use std::error::Error;
+//! use zbus::{
+//!     notifications::{Actions, Hints, NotificationsProxy, Urgency},
+//!     Connection,
+//! };
+//!
+//! let connection = Connection::new_session()?;
+//! let proxy = NotificationsProxy::new(&connection)?;
+//!
+//! let hints = Hints::new()
+//!     .urgency(Urgency::Critical)
+//!     .category("device.removed")
+//!     .transient(true);
+//! let id = proxy.notify(
+//!     "my-app",
+//!     0,
+//!     "drive-removable-media",
+//!     "Drive removed",
+//!     "The USB stick was pulled out before being unmounted.",
+//!     &["help", "Get help"],
+//!     &hints,
+//!     -1,
+//! )?;
+//!
+//! let actions = Actions::new();
+//! actions.register(id, |action| println!("Action `{}` invoked", action));
+//!
+//! let handler = actions.clone();
+//! proxy.connect_action_invoked(move |id, action| {
+//!     handler.invoke(id, action);
+//!
+//!     Ok(())
+//! })?;
+//! let handler = actions.clone();
+//! proxy.connect_notification_closed(move |id, _reason| {
+//!     handler.unregister(id);
+//!
+//!     Ok(())
+//! })?;
+//!
+//! while !actions.is_empty() {
+//!     proxy.next_signal()?;
+//! }
+//!# Ok::<_, Box<dyn Error + Send + Sync>>(())
+//! ```
+//!
+//! [`org.freedesktop.Notifications`]: https://specifications.freedesktop.org/notification-spec/latest/
+//! [`Hints`]: struct.Hints.html
+//! [`ImageData`]: struct.ImageData.html
+//! [`CloseReason`]: enum.CloseReason.html
+//! [`Actions`]: struct.Actions.html
+
+// `Notify` has more arguments than clippy likes, and that's not for us to change.
+#![allow(clippy::too_many_arguments)]
+
+use serde::{Deserialize, Serialize, Serializer};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use static_assertions::assert_impl_all;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+use zvariant::{Signature, Type, Value};
+
+use crate::{dbus_proxy, Result};
+
+/// Proxy for the `org.freedesktop.Notifications` interface.
+#[dbus_proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    /// Returns the optional capabilities implemented by the server, such as `actions`,
+    /// `body-markup` or `persistence`.
+    fn get_capabilities(&self) -> Result<Vec<String>>;
+
+    /// Sends a notification to the server and returns its ID.
+    ///
+    /// If `replaces_id` is not 0, the notification with that ID is replaced, keeping the same ID.
+    /// `actions` is a list of action identifiers and their localized labels, one after the other.
+    /// An `expire_timeout` of -1 leaves the timeout to the server and 0 means the notification
+    /// never expires. Otherwise, it's the timeout in milliseconds.
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: &Hints<'_>,
+        expire_timeout: i32,
+    ) -> Result<u32>;
+
+    /// Closes the notification with the given ID and removes it from the screen.
+    fn close_notification(&self, id: u32) -> Result<()>;
+
+    /// Returns the name, vendor, version and implemented specification version of the server.
+    fn get_server_information(&self) -> Result<(String, String, String, String)>;
+
+    /// Emitted when a notification is closed.
+    #[dbus_proxy(signal)]
+    fn notification_closed(&self, id: u32, reason: CloseReason) -> Result<()>;
+
+    /// Emitted when the user invokes an action of a notification.
+    #[dbus_proxy(signal)]
+    fn action_invoked(&self, id: u32, action_key: &str) -> Result<()>;
+}
+
+assert_impl_all!(AsyncNotificationsProxy<'_>: Send, Sync, Unpin);
+assert_impl_all!(NotificationsProxy<'_>: Send, Sync, Unpin);
+
+/// The reason a notification was closed, as reported by the `NotificationClosed` signal.
+#[repr(u32)]
+#[derive(Deserialize_repr, Serialize_repr, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The notification expired.
+    Expired = 1,
+    /// The notification was dismissed by the user.
+    Dismissed = 2,
+    /// The notification was closed by a call to [`close_notification`].
+    ///
+    /// [`close_notification`]: struct.NotificationsProxy.html#method.close_notification
+    Closed = 3,
+    /// The reason is undefined or reserved.
+    Undefined = 4,
+}
+
+assert_impl_all!(CloseReason: Send, Sync, Unpin);
+
+impl Type for CloseReason {
+    fn signature() -> Signature<'static> {
+        u32::signature()
+    }
+}
+
+/// The urgency level of a notification.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    /// Low urgency, e.g. a contact coming online.
+    Low = 0,
+    /// Normal urgency, the default.
+    Normal = 1,
+    /// Critical urgency, e.g. a low battery. Critical notifications don't normally expire.
+    Critical = 2,
+}
+
+assert_impl_all!(Urgency: Send, Sync, Unpin);
+
+/// Raw image data, for the `image-data` hint.
+///
+/// This is serialized as the `(iiibiiay)` structure the specification requires.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ImageData {
+    /// The width of the image, in pixels.
+    pub width: i32,
+    /// The height of the image, in pixels.
+    pub height: i32,
+    /// The distance in bytes between row starts.
+    pub rowstride: i32,
+    /// Whether the image has an alpha channel.
+    pub has_alpha: bool,
+    /// The number of bits per sample, must be 8.
+    pub bits_per_sample: i32,
+    /// The number of channels, 4 with an alpha channel and 3 without.
+    pub channels: i32,
+    /// The image data, in RGB or RGBA byte order.
+    pub data: Vec<u8>,
+}
+
+assert_impl_all!(ImageData: Send, Sync, Unpin);
+
+impl Type for ImageData {
+    fn signature() -> Signature<'static> {
+        Signature::from_str_unchecked("(iiibiiay)")
+    }
+}
+
+impl ImageData {
+    /// Image data from tightly packed 8-bit RGBA pixels.
+    pub fn rgba(width: i32, height: i32, data: Vec<u8>) -> Self {
+        Self::packed(width, height, true, data)
+    }
+
+    /// Image data from tightly packed 8-bit RGB pixels.
+    pub fn rgb(width: i32, height: i32, data: Vec<u8>) -> Self {
+        Self::packed(width, height, false, data)
+    }
+
+    fn packed(width: i32, height: i32, has_alpha: bool, data: Vec<u8>) -> Self {
+        let channels = if has_alpha { 4 } else { 3 };
+
+        Self {
+            width,
+            height,
+            rowstride: width * channels,
+            has_alpha,
+            bits_per_sample: 8,
+            channels,
+            data,
+        }
+    }
+}
+
+impl From<ImageData> for Value<'static> {
+    fn from(image: ImageData) -> Self {
+        Value::from((
+            image.width,
+            image.height,
+            image.rowstride,
+            image.has_alpha,
+            image.bits_per_sample,
+            image.channels,
+            image.data,
+        ))
+    }
+}
+
+/// The hints of a notification.
+///
+/// A builder for the `a{sv}` dictionary passed to [`notify`], with a method for each hint defined
+/// by the specification. Server-specific hints can be added with [`hint`].
+///
+/// [`notify`]: struct.NotificationsProxy.html#method.notify
+/// [`hint`]: #method.hint
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hints<'a>(HashMap<&'a str, Value<'a>>);
+
+assert_impl_all!(Hints<'_>: Send, Sync, Unpin);
+
+impl<'a> Hints<'a> {
+    /// Create an empty set of hints.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the hint `key` to `value`, replacing any previous value.
+    pub fn hint<V>(mut self, key: &'a str, value: V) -> Self
+    where
+        V: Into<Value<'a>>,
+    {
+        self.0.insert(key, value.into());
+        self
+    }
+
+    /// The value of the hint `key`, if set.
+    pub fn get(&self, key: &str) -> Option<&Value<'a>> {
+        self.0.get(key)
+    }
+
+    /// Set the urgency level.
+    pub fn urgency(self, urgency: Urgency) -> Self {
+        self.hint("urgency", urgency as u8)
+    }
+
+    /// Set the type of notification, e.g. `email.arrived`.
+    pub fn category(self, category: &'a str) -> Self {
+        self.hint("category", category)
+    }
+
+    /// Set the name of the desktop file of the application, without the `.desktop` suffix.
+    pub fn desktop_entry(self, desktop_entry: &'a str) -> Self {
+        self.hint("desktop-entry", desktop_entry)
+    }
+
+    /// Set whether the server should bypass its persistence capability.
+    pub fn transient(self, transient: bool) -> Self {
+        self.hint("transient", transient)
+    }
+
+    /// Set whether the notification is kept when one of its actions is invoked.
+    pub fn resident(self, resident: bool) -> Self {
+        self.hint("resident", resident)
+    }
+
+    /// Set whether the action identifiers should be interpreted as icon names.
+    pub fn action_icons(self, action_icons: bool) -> Self {
+        self.hint("action-icons", action_icons)
+    }
+
+    /// Set the image of the notification from raw data.
+    pub fn image_data(self, image: ImageData) -> Self {
+        self.hint("image-data", image)
+    }
+
+    /// Set the image of the notification from an icon name or a `file://` URI.
+    pub fn image_path(self, image_path: &'a str) -> Self {
+        self.hint("image-path", image_path)
+    }
+
+    /// Set the path of a sound file to play along with the notification.
+    pub fn sound_file(self, sound_file: &'a str) -> Self {
+        self.hint("sound-file", sound_file)
+    }
+
+    /// Set the themeable name of a sound to play along with the notification.
+    pub fn sound_name(self, sound_name: &'a str) -> Self {
+        self.hint("sound-name", sound_name)
+    }
+
+    /// Set whether the server should not play any sound.
+    pub fn suppress_sound(self, suppress_sound: bool) -> Self {
+        self.hint("suppress-sound", suppress_sound)
+    }
+
+    /// Set the screen position the notification should point to.
+    pub fn position(self, x: i32, y: i32) -> Self {
+        self.hint("x", x).hint("y", y)
+    }
+}
+
+impl Serialize for Hints<'_> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl Type for Hints<'_> {
+    fn signature() -> Signature<'static> {
+        <HashMap<&str, Value<'_>>>::signature()
+    }
+}
+
+type ActionCallback = Box<dyn FnMut(&str) + Send>;
+
+/// The action callbacks of the notifications sent by an application.
+///
+/// The `ActionInvoked` and `NotificationClosed` signals are broadcast to all applications, so each
+/// needs to keep track of the IDs of its own notifications. `Actions` maps these IDs to the
+/// callbacks to run when an action is invoked. Clones share the same callbacks, so that one can be
+/// moved into each signal handler.
+#[derive(Clone, Default)]
+pub struct Actions {
+    callbacks: Arc<Mutex<HashMap<u32, ActionCallback>>>,
+}
+
+assert_impl_all!(Actions: Send, Sync, Unpin);
+
+impl Actions {
+    /// Create an empty set of callbacks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the callback for the actions of the notification `id`.
+    ///
+    /// The callback receives the key of the invoked action. It replaces any callback previously
+    /// registered for the same notification.
+    pub fn register<F>(&self, id: u32, callback: F)
+    where
+        F: FnMut(&str) + Send + 'static,
+    {
+        self.callbacks
+            .lock()
+            .expect("lock poisoned")
+            .insert(id, Box::new(callback));
+    }
+
+    /// Unregister the callback of the notification `id`, typically once it's closed.
+    ///
+    /// Returns `false` if no callback was registered for it.
+    pub fn unregister(&self, id: u32) -> bool {
+        self.callbacks
+            .lock()
+            .expect("lock poisoned")
+            .remove(&id)
+            .is_some()
+    }
+
+    /// Run the callback of the notification `id` with `action_key`.
+    ///
+    /// Returns `false` if no callback is registered for it, i.e. the notification belongs to
+    /// another application.
+    pub fn invoke(&self, id: u32, action_key: &str) -> bool {
+        match self.callbacks.lock().expect("lock poisoned").get_mut(&id) {
+            Some(callback) => {
+                callback(action_key);
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Run the callback for an `ActionInvoked` signal.
+    ///
+    /// See [`invoke`] for details.
+    ///
+    /// [`invoke`]: #method.invoke
+    pub fn handle_action_invoked(&self, signal: &ActionInvoked) -> Result<bool> {
+        let args = signal.args()?;
+
+        Ok(self.invoke(args.id, args.action_key))
+    }
+
+    /// Unregister the callback of the notification a `NotificationClosed` signal is about.
+    ///
+    /// See [`unregister`] for details.
+    ///
+    /// [`unregister`]: #method.unregister
+    pub fn handle_notification_closed(&self, signal: &NotificationClosed) -> Result<bool> {
+        let args = signal.args()?;
+
+        Ok(self.unregister(args.id))
+    }
+
+    /// The number of notifications with a registered callback.
+    pub fn len(&self) -> usize {
+        self.callbacks.lock().expect("lock poisoned").len()
+    }
+
+    /// Whether no callback is registered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for Actions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let callbacks = self.callbacks.lock().expect("lock poisoned");
+        let mut ids: Vec<_> = callbacks.keys().collect();
+        ids.sort_unstable();
+
+        f.debug_struct("Actions").field("ids", &ids).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        convert::TryFrom,
+        os::unix::net::UnixStream,
+        sync::{
+            mpsc::{channel, Sender},
+            Arc, Mutex,
+        },
+        thread,
+    };
+
+    use async_io::block_on;
+    use futures_util::StreamExt;
+    use ntest::timeout;
+    use test_env_log::test;
+    use zvariant::{OwnedValue, Value};
+
+    use super::*;
+    use crate::{dbus_interface, Connection, Guid, ObjectServer};
+
+    struct Server {
+        hints: Sender<HashMap<String, OwnedValue>>,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.Notifications")]
+    impl Server {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            _app_name: &str,
+            _replaces_id: u32,
+            _app_icon: &str,
+            _summary: &str,
+            _body: &str,
+            actions: Vec<&str>,
+            hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            self.hints.send(hints).unwrap();
+            self.action_invoked(7, actions[0])
+                .expect("Failed to emit signal");
+
+            7
+        }
+
+        #[dbus_interface(signal)]
+        fn action_invoked(&self, id: u32, action_key: &str) -> zbus::Result<()>;
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn notify() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = channel::<()>();
+        let (hints_tx, hints_rx) = channel();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let server = Server { hints: hints_tx };
+            object_server
+                .at("/org/freedesktop/Notifications", server)
+                .unwrap();
+            tx.send(()).unwrap();
+
+            assert!(object_server.try_handle_next().unwrap().is_none());
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+
+        let invoked = Arc::new(Mutex::new(vec![]));
+        let actions = Actions::new();
+        {
+            let invoked = invoked.clone();
+            actions.register(7, move |key| invoked.lock().unwrap().push(key.to_string()));
+        }
+
+        block_on(async {
+            let proxy = AsyncNotificationsProxy::new(conn.inner()).unwrap();
+            let mut action_invoked = proxy.receive_action_invoked().await.unwrap();
+
+            let hints = Hints::new()
+                .urgency(Urgency::Critical)
+                .transient(true)
+                .image_data(ImageData::rgb(1, 1, vec![0xff, 0, 0]));
+            let id = proxy
+                .notify("zbus", 0, "", "Hi", "", &["default", "Open"], &hints, -1)
+                .await
+                .unwrap();
+            assert_eq!(id, 7);
+
+            let signal = action_invoked.next().await.unwrap();
+            assert!(actions.handle_action_invoked(&signal).unwrap());
+        });
+        assert_eq!(*invoked.lock().unwrap(), vec!["default"]);
+
+        let hints = hints_rx.recv().unwrap();
+        assert_eq!(u8::try_from(hints["urgency"].clone()).unwrap(), 2);
+        assert!(bool::try_from(hints["transient"].clone()).unwrap());
+        assert_eq!(hints["image-data"].value_signature(), "(iiibiiay)");
+        match &*hints["image-data"] {
+            Value::Structure(s) => {
+                assert_eq!(s.fields()[2], Value::I32(3));
+                assert_eq!(s.fields()[6], Value::from(vec![0xffu8, 0, 0]));
+            }
+            v => panic!("unexpected image data: {:?}", v),
+        }
+
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    fn actions() {
+        let actions = Actions::new();
+        assert!(!actions.invoke(1, "default"));
+
+        actions.register(1, |key| assert_eq!(key, "default"));
+        assert_eq!(actions.len(), 1);
+        assert!(actions.clone().invoke(1, "default"));
+        assert!(!actions.invoke(2, "default"));
+
+        assert!(actions.unregister(1));
+        assert!(!actions.unregister(1));
+        assert!(actions.is_empty());
+    }
+}
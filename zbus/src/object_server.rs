@@ -1,11 +1,15 @@
 use std::{
     any::{Any, TypeId},
+    borrow::Cow,
     cell::RefCell,
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, HashMap, VecDeque},
     convert::TryInto,
     fmt::Write,
     io::{self, ErrorKind},
-    rc::Rc,
+    iter,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    rc::{Rc, Weak},
     sync::Arc,
 };
 
@@ -17,13 +21,20 @@ use zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
 
 use crate::{
     azync::MessageStream,
+    composite_interface::Composite,
+    dynamic_interface::Dynamic,
     fdo,
     fdo::{Introspectable, Peer, Properties},
-    Connection, Error, Message, MessageHeader, MessageType, Result,
+    interface_version::Version,
+    logging, Connection, DynamicInterface, Error, InterfaceMetadata, Message, MessageHeader,
+    MessageType, Result,
 };
 
 scoped_thread_local!(pub(crate) static LOCAL_NODE: Node);
 scoped_thread_local!(static LOCAL_CONNECTION: Connection);
+// The signals emitted from the interfaces of a `LocalObjectServer`, for it to send.
+#[cfg(feature = "local")]
+scoped_thread_local!(pub(crate) static LOCAL_SIGNALS: crate::azync::LocalSignals);
 
 /// The trait used to dispatch messages to an interface instance.
 ///
@@ -37,15 +48,75 @@ pub trait Interface: Any {
     where
         Self: Sized;
 
+    /// Return the names of the other versions of the interface, served by the same implementation
+    /// through [`ObjectServer::at_versions`].
+    ///
+    /// Set with `#[dbus_interface(versions("org.foo.MyInterface1"))]`. Empty by default.
+    ///
+    /// [`ObjectServer::at_versions`]: struct.ObjectServer.html#method.at_versions
+    fn versions() -> &'static [&'static str]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+
+    /// Return the description of the members of the interface, if known.
+    ///
+    /// Generated by the [`dbus_interface`] macro. `None` by default, and for the interfaces built
+    /// at runtime.
+    ///
+    /// [`dbus_interface`]: attr.dbus_interface.html
+    fn metadata(&self) -> Option<&'static InterfaceMetadata> {
+        None
+    }
+
     /// Get a property value. Returns `None` if the property doesn't exist.
     fn get(&self, property_name: &str) -> Option<fdo::Result<OwnedValue>>;
 
     /// Return all the properties.
+    ///
+    /// The properties failing to be read are left out.
     fn get_all(&self) -> HashMap<String, OwnedValue>;
 
+    /// Return all the properties, for the `GetAll` method of [`fdo::Properties`].
+    ///
+    /// Fails if any of the property getters fails, unless that property is marked to be skipped on
+    /// errors. Same as [`get_all`] by default, which never fails.
+    ///
+    /// [`fdo::Properties`]: fdo/struct.Properties.html
+    /// [`get_all`]: #tymethod.get_all
+    fn try_get_all(&self) -> fdo::Result<HashMap<String, OwnedValue>> {
+        Ok(self.get_all())
+    }
+
+    /// Return the properties whose changes are signaled, for tracking these changes.
+    ///
+    /// Properties annotated not to emit `PropertiesChanged` are left out, and so are the ones
+    /// failing to be read. Used by [`ObjectServer::with_mut_tracked`].
+    ///
+    /// [`ObjectServer::with_mut_tracked`]: struct.ObjectServer.html#method.with_mut_tracked
+    fn tracked_properties(&self) -> HashMap<&'static str, OwnedValue> {
+        HashMap::new()
+    }
+
     /// Set a property value. Returns `None` if the property doesn't exist.
     fn set(&mut self, property_name: &str, value: &Value<'_>) -> Option<fdo::Result<()>>;
 
+    /// Set several property values at once, all or none of them. Returns `None` if the interface
+    /// doesn't support it.
+    ///
+    /// Implemented for the interfaces declared with `#[dbus_interface(atomic_properties)]`, for
+    /// the `SetMultiple` method of [`fdo::Properties`].
+    ///
+    /// [`fdo::Properties`]: fdo/struct.Properties.html
+    fn set_multiple(
+        &mut self,
+        _properties: &HashMap<String, OwnedValue>,
+    ) -> Option<fdo::Result<()>> {
+        None
+    }
+
     /// Call a `&self` method. Returns `None` if the method doesn't exist.
     fn call(&self, connection: &Connection, msg: &Message, name: &str) -> Option<Result<u32>>;
 
@@ -63,7 +134,7 @@ pub trait Interface: Any {
 
 impl dyn Interface {
     /// Return Any of self
-    fn downcast_ref<T: Any>(&self) -> Option<&T> {
+    pub(crate) fn downcast_ref<T: Any>(&self) -> Option<&T> {
         if <dyn Interface as Any>::type_id(self) == TypeId::of::<T>() {
             // SAFETY: If type ID matches, it means object is of type T
             Some(unsafe { &*(self as *const dyn Interface as *const T) })
@@ -71,6 +142,16 @@ impl dyn Interface {
             None
         }
     }
+
+    /// Return Any of self, mutably
+    pub(crate) fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+        if <dyn Interface as Any>::type_id(self) == TypeId::of::<T>() {
+            // SAFETY: If type ID matches, it means object is of type T
+            Some(unsafe { &mut *(self as *mut dyn Interface as *mut T) })
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Default, derivative::Derivative)]
@@ -79,7 +160,7 @@ pub(crate) struct Node {
     path: OwnedObjectPath,
     children: HashMap<String, Node>,
     #[derivative(Debug = "ignore")]
-    interfaces: HashMap<&'static str, Rc<RefCell<dyn Interface>>>,
+    interfaces: HashMap<Cow<'static, str>, Rc<RefCell<dyn Interface>>>,
 }
 
 impl Node {
@@ -88,9 +169,9 @@ impl Node {
             path,
             ..Default::default()
         };
-        node.at(Peer::name(), Peer);
-        node.at(Introspectable::name(), Introspectable);
-        node.at(Properties::name(), Properties);
+        node.at(Peer::name().into(), Peer);
+        node.at(Introspectable::name().into(), Introspectable);
+        node.at(Properties::name().into(), Properties);
 
         node
     }
@@ -99,22 +180,152 @@ impl Node {
         self.interfaces.get(iface).cloned()
     }
 
-    fn remove_interface(&mut self, iface: &str) -> bool {
+    // The interface `I`, registered on its own or as a part of a composite interface.
+    fn interface_of<I: Interface>(&self) -> Option<Rc<RefCell<dyn Interface>>> {
+        self.interface_named::<I>(I::name())
+    }
+
+    // The interface `I` registered under `name`, on its own or as a part of a composite interface.
+    fn interface_named<I: Interface>(&self, name: &str) -> Option<Rc<RefCell<dyn Interface>>> {
+        let iface = self.interfaces.get(name)?;
+        let part = iface
+            .borrow()
+            .downcast_ref::<Composite>()
+            .map(|composite| composite.part::<I>());
+
+        part.unwrap_or_else(|| Some(iface.clone()))
+    }
+
+    // Collect this node and its descendants on which the interface `I` is registered.
+    fn collect_nodes_of<'n, I: Interface>(&'n self, nodes: &mut Vec<&'n Node>) {
+        // A `DynamicInterface` may have the same name as `I`.
+        let found = self
+            .interface_of::<I>()
+            .map_or(false, |iface| iface.borrow().downcast_ref::<I>().is_some());
+        if found {
+            nodes.push(self);
+        }
+        for child in self.children.values() {
+            child.collect_nodes_of::<I>(nodes);
+        }
+    }
+
+    // Collect this node and its descendants on which any non-standard interface is registered.
+    fn collect_nodes<'n>(&'n self, nodes: &mut Vec<&'n Node>) {
+        if !self.is_empty() {
+            nodes.push(self);
+        }
+        for child in self.children.values() {
+            child.collect_nodes(nodes);
+        }
+    }
+
+    // Remove the interface `iface`, or only its part of type `part` if it's a composite.
+    fn remove_interface(&mut self, iface: &str, part: Option<TypeId>) -> bool {
+        let existing = match (part, self.interfaces.get(iface)) {
+            (Some(part), Some(existing)) => Some((part, existing.clone())),
+            _ => None,
+        };
+        if let Some((part, existing)) = existing {
+            let mut existing = existing.borrow_mut();
+            if let Some(composite) = existing.downcast_mut::<Composite>() {
+                let removed = composite.remove(part);
+                if composite.is_empty() {
+                    self.interfaces.remove(iface);
+                }
+
+                return removed;
+            }
+        }
+
         self.interfaces.remove(iface).is_some()
     }
 
+    // Get the Node at path, from the root node.
+    pub(crate) fn child(&self, path: &ObjectPath<'_>) -> Option<&Node> {
+        let mut node = self;
+        let mut node_path = String::new();
+
+        for i in path.split('/').skip(1) {
+            if i.is_empty() {
+                continue;
+            }
+            write!(&mut node_path, "/{}", i).unwrap();
+            match node.children.get(i) {
+                Some(n) => node = n,
+                None => return None,
+            }
+        }
+
+        Some(node)
+    }
+
+    // Get the Node at path, from the root node. Optionally create one if it doesn't exist.
+    pub(crate) fn child_mut(&mut self, path: &ObjectPath<'_>, create: bool) -> Option<&mut Node> {
+        let mut node = self;
+        let mut node_path = String::new();
+
+        for i in path.split('/').skip(1) {
+            if i.is_empty() {
+                continue;
+            }
+            write!(&mut node_path, "/{}", i).unwrap();
+            match node.children.entry(i.into()) {
+                Entry::Vacant(e) => {
+                    if create {
+                        let path = node_path.as_str().try_into().expect("Invalid Object Path");
+                        node = e.insert(Node::new(path));
+                    } else {
+                        return None;
+                    }
+                }
+                Entry::Occupied(e) => node = e.into_mut(),
+            }
+        }
+
+        Some(node)
+    }
+
+    // Remove the interface `name` of the node at `path`, from the root node, and the node along
+    // with its last interface.
+    pub(crate) fn remove_interface_at(
+        &mut self,
+        path: &ObjectPath<'_>,
+        name: &str,
+        part: Option<TypeId>,
+    ) -> Result<bool> {
+        let node = self
+            .child_mut(path, false)
+            .ok_or(Error::InterfaceNotFound)?;
+        if !node.remove_interface(name, part) {
+            return Err(Error::InterfaceNotFound);
+        }
+        if node.is_empty() {
+            let mut path_parts = path.rsplit('/').filter(|i| !i.is_empty());
+            let last_part = path_parts.next().unwrap();
+            let ppath = ObjectPath::from_string_unchecked(
+                path_parts.fold(String::new(), |a, p| format!("/{}{}", p, a)),
+            );
+            self.child_mut(&ppath, false)
+                .unwrap()
+                .remove_node(last_part);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
     fn is_empty(&self) -> bool {
         !self
             .interfaces
             .keys()
-            .any(|&k| k != Peer::name() && k != Introspectable::name() && k != Properties::name())
+            .any(|k| *k != Peer::name() && *k != Introspectable::name() && *k != Properties::name())
     }
 
     fn remove_node(&mut self, node: &str) -> bool {
         self.children.remove(node).is_some()
     }
 
-    fn at<I>(&mut self, name: &'static str, iface: I) -> bool
+    pub(crate) fn at<I>(&mut self, name: Cow<'static, str>, iface: I) -> bool
     where
         I: Interface,
     {
@@ -126,20 +337,97 @@ impl Node {
         true
     }
 
-    fn with_iface_func<F, I>(&self, func: F) -> Result<()>
+    fn at_versions<I>(&mut self, iface: I) -> bool
+    where
+        I: Interface,
+    {
+        let mut names = iter::once(I::name()).chain(I::versions().iter().copied());
+        if names.any(|name| self.interfaces.contains_key(name)) {
+            return false;
+        }
+
+        let iface: Rc<RefCell<dyn Interface>> = Rc::new(RefCell::new(iface));
+        for name in I::versions() {
+            let version = Version::new(name, iface.clone());
+            self.interfaces
+                .insert((*name).into(), Rc::new(RefCell::new(version)));
+        }
+        self.interfaces.insert(I::name().into(), iface);
+
+        true
+    }
+
+    // Remove the other versions of `I` registered along with it, if any.
+    fn remove_versions<I: Interface>(&mut self) {
+        for name in I::versions() {
+            let is_version = self.interfaces.get(*name).map_or(false, |iface| {
+                iface
+                    .borrow()
+                    .downcast_ref::<Version>()
+                    .map_or(false, Version::is_of::<I>)
+            });
+            if is_version {
+                self.interfaces.remove(*name);
+            }
+        }
+    }
+
+    fn merge<I>(&mut self, name: &'static str, iface: I) -> Result<()>
+    where
+        I: Interface,
+    {
+        let part: Rc<RefCell<dyn Interface>> = Rc::new(RefCell::new(iface));
+        match self.interfaces.entry(name.into()) {
+            Entry::Vacant(e) => {
+                e.insert(part);
+            }
+            Entry::Occupied(mut e) => {
+                let composite = Composite::merge(name, e.get().clone(), part)?;
+                e.insert(Rc::new(RefCell::new(composite)));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn with_iface_func<F, I>(&self, func: F) -> Result<()>
     where
         F: Fn(&I) -> Result<()>,
         I: Interface,
     {
-        let iface = self
-            .interfaces
-            .get(I::name())
-            .ok_or(Error::InterfaceNotFound)?
-            .borrow();
+        let iface = self.interface_of::<I>().ok_or(Error::InterfaceNotFound)?;
+        let iface = iface.borrow();
         let iface = iface.downcast_ref::<I>().ok_or(Error::InterfaceNotFound)?;
         func(iface)
     }
 
+    pub(crate) fn with_iface_mut_tracked<F, I>(&self, func: F) -> Result<()>
+    where
+        F: FnOnce(&mut I) -> Result<()>,
+        I: Interface,
+    {
+        let iface = self.interface_of::<I>().ok_or(Error::InterfaceNotFound)?;
+        let mut iface = iface.borrow_mut();
+        let before = iface.tracked_properties();
+        let res = func(iface.downcast_mut::<I>().ok_or(Error::InterfaceNotFound)?);
+        let after = iface.tracked_properties();
+        drop(iface);
+
+        // The interface may well have been changed before `func` failed.
+        let changed: HashMap<&str, &Value<'_>> = after
+            .iter()
+            .filter(|(name, value)| before.get(*name) != Some(value))
+            .map(|(name, value)| (*name, &**value))
+            .collect();
+        let emitted = if changed.is_empty() {
+            Ok(())
+        } else {
+            Properties.properties_changed(I::name(), &changed, &[])
+        };
+
+        res.and(emitted)
+    }
+
     fn introspect_to_writer<W: Write>(&self, writer: &mut W, level: usize) {
         if level == 0 {
             writeln!(
@@ -193,6 +481,11 @@ impl Node {
     where
         B: serde::ser::Serialize + zvariant::Type,
     {
+        #[cfg(feature = "local")]
+        if !LOCAL_CONNECTION.is_set() && LOCAL_SIGNALS.is_set() {
+            return LOCAL_SIGNALS
+                .with(|signals| signals.queue(dest, &self.path, iface, signal_name, body));
+        }
         if !LOCAL_CONNECTION.is_set() {
             panic!("emit_signal: Connection TLS not set");
         }
@@ -201,6 +494,244 @@ impl Node {
     }
 }
 
+/// A handle for emitting the signals of an object, from anywhere.
+///
+/// The signal methods generated by [`dbus_interface`] can only be called from a method call of
+/// the interface or from [`ObjectServer::with`], as they need the object server to tell the
+/// connection and object path to emit the signal on. A `SignalEmitter` holds these itself, so it
+/// can be cloned and moved to other threads or tasks. Pass its [`connection`] and [`path`] to the
+/// generated `<signal>_on` functions, or use [`emit_signal`] directly.
+///
+/// Use [`ObjectServer::signal_emitter`] to create an instance of this type.
+///
+/// [`dbus_interface`]: attr.dbus_interface.html
+/// [`ObjectServer::with`]: struct.ObjectServer.html#method.with
+/// [`connection`]: #method.connection
+/// [`path`]: #method.path
+/// [`emit_signal`]: #method.emit_signal
+/// [`ObjectServer::signal_emitter`]: struct.ObjectServer.html#method.signal_emitter
+#[derive(Debug, Clone)]
+pub struct SignalEmitter {
+    conn: Connection,
+    path: OwnedObjectPath,
+}
+
+assert_impl_all!(SignalEmitter: Send, Sync, Unpin);
+
+impl SignalEmitter {
+    /// The connection to emit the signals on.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// The path of the object emitting the signals.
+    pub fn path(&self) -> &ObjectPath<'_> {
+        &self.path
+    }
+
+    /// Emit the signal `signal_name` of the interface `iface`.
+    pub fn emit_signal<B>(
+        &self,
+        destination: Option<&str>,
+        iface: &str,
+        signal_name: &str,
+        body: &B,
+    ) -> Result<()>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        self.conn
+            .emit_signal(destination, &self.path, iface, signal_name, body)
+    }
+
+    /// Emit the signal `signal_name` of the interface `iface`, without blocking.
+    ///
+    /// Fails with [`Error::QueueFull`] rather than blocking when the outgoing queue of the
+    /// connection is full, e.g when emitting from a real-time thread. See
+    /// [`Connection::try_send_message`] for details.
+    ///
+    /// [`Error::QueueFull`]: enum.Error.html#variant.QueueFull
+    /// [`Connection::try_send_message`]: struct.Connection.html#method.try_send_message
+    pub fn try_emit_signal<B>(
+        &self,
+        destination: Option<&str>,
+        iface: &str,
+        signal_name: &str,
+        body: &B,
+    ) -> Result<()>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        self.conn
+            .try_emit_signal(destination, &self.path, iface, signal_name, body)
+    }
+}
+
+/// The pending reply of a method call, for replying once the method has returned.
+///
+/// A method of a [`dbus_interface`] taking a `#[zbus(deferred)] responder: Responder<T>` argument
+/// doesn't reply when it returns. Instead, the `Responder` can be moved to another thread or task
+/// and the reply (of type `T`) sent through [`ok`], or an error through [`error`], whenever it's
+/// ready. The object server meanwhile goes on dispatching the other calls.
+///
+/// If the `Responder` is dropped without an answer, a `org.freedesktop.DBus.Error.NoReply` error
+/// is sent to the caller, so that it isn't left waiting.
+///
+/// [`dbus_interface`]: attr.dbus_interface.html
+/// [`ok`]: #method.ok
+/// [`error`]: #method.error
+#[derive(Debug)]
+pub struct Responder<T> {
+    conn: Connection,
+    // The call, until it's answered.
+    call: Option<Message>,
+    phantom: PhantomData<fn(T)>,
+}
+
+assert_impl_all!(Responder<()>: Send, Sync, Unpin);
+
+impl<T> Responder<T> {
+    /// Create a responder for `call`, received on `conn`.
+    ///
+    /// Methods of a [`dbus_interface`] are given theirs. This is for the calls a [`Fallback`]
+    /// handler forwards, to reply to them once the answer is known.
+    ///
+    /// [`dbus_interface`]: attr.dbus_interface.html
+    /// [`Fallback`]: trait.Fallback.html
+    pub fn new(conn: Connection, call: Message) -> Self {
+        Self {
+            conn,
+            call: Some(call),
+            phantom: PhantomData,
+        }
+    }
+
+    /// The connection the call was received on.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// The call to reply to.
+    pub fn call(&self) -> &Message {
+        self.call.as_ref().expect("answered responder")
+    }
+
+    /// Send `reply`, a reply to the call built beforehand.
+    ///
+    /// This is for replies of a body only known at runtime, e.g that of the reply to a forwarded
+    /// call. `reply` is sent as is, so it should be built with [`MessageBuilder::method_return`] or
+    /// [`MessageBuilder::error`] on the [`call`].
+    ///
+    /// Returns the serial number of `reply`.
+    ///
+    /// [`MessageBuilder::method_return`]: struct.MessageBuilder.html#method.method_return
+    /// [`MessageBuilder::error`]: struct.MessageBuilder.html#method.error
+    /// [`call`]: #method.call
+    pub fn reply(mut self, reply: Message) -> Result<u32> {
+        self.call.take();
+
+        self.conn.send_message(reply)
+    }
+
+    /// Reply an error to the call.
+    ///
+    /// Returns the serial number of the error message.
+    pub fn error<E>(mut self, error: E) -> Result<u32>
+    where
+        E: Into<fdo::Error>,
+    {
+        let call = self.call.take().expect("answered responder");
+
+        error.into().reply(&self.conn, &call)
+    }
+}
+
+impl<T> Responder<T>
+where
+    T: serde::ser::Serialize + zvariant::Type,
+{
+    /// Reply `value` to the call.
+    ///
+    /// Returns the serial number of the reply message.
+    pub fn ok(mut self, value: T) -> Result<u32> {
+        let call = self.call.take().expect("answered responder");
+
+        self.conn.reply(&call, &value)
+    }
+}
+
+impl<T> Drop for Responder<T> {
+    fn drop(&mut self) {
+        if let Some(call) = self.call.take() {
+            // Nothing more can be done if the connection is gone.
+            let _ = fdo::Error::NoReply("The method call was never answered".to_string())
+                .reply(&self.conn, &call);
+        }
+    }
+}
+
+/// What a [`Fallback`] handler did with a method call.
+///
+/// [`Fallback`]: trait.Fallback.html
+#[derive(Debug)]
+pub enum FallbackReply {
+    /// Send this reply to the call.
+    Reply(Message),
+    /// Reply this error to the call.
+    Error(fdo::Error),
+    /// The handler will reply later, e.g through a [`Responder`], once the forwarded call is
+    /// answered.
+    ///
+    /// [`Responder`]: struct.Responder.html
+    Forwarded,
+}
+
+/// A handler of the method calls no registered interface handles, under an object path.
+///
+/// Registered with [`ObjectServer::at_fallback`], it's given the calls to the objects under its
+/// path prefix that no interface of the object server handles: calls to unknown objects, and to
+/// unknown interfaces or methods of known objects. This is typically used to forward any call to
+/// another connection, without knowing the interfaces of the objects beforehand.
+///
+/// It's implemented for closures taking the same arguments as [`call`].
+///
+/// [`ObjectServer::at_fallback`]: struct.ObjectServer.html#method.at_fallback
+/// [`call`]: #tymethod.call
+pub trait Fallback {
+    /// Handle `msg`, a method call received on `conn`, of which `header` is the header.
+    fn call(
+        &mut self,
+        conn: &Connection,
+        msg: &Message,
+        header: &MessageHeader<'_>,
+    ) -> FallbackReply;
+
+    /// The introspection XML of the object at `path`, if the handler provides it.
+    ///
+    /// If this returns `Some`, `org.freedesktop.DBus.Introspectable.Introspect` calls to objects
+    /// that have no interface registered are replied to with it. Otherwise, the default, they are
+    /// handled by [`call`] like any other.
+    ///
+    /// [`call`]: #tymethod.call
+    fn introspect(&mut self, _path: &ObjectPath<'_>) -> Option<String> {
+        None
+    }
+}
+
+impl<F> Fallback for F
+where
+    F: FnMut(&Connection, &Message, &MessageHeader<'_>) -> FallbackReply,
+{
+    fn call(
+        &mut self,
+        conn: &Connection,
+        msg: &Message,
+        header: &MessageHeader<'_>,
+    ) -> FallbackReply {
+        self(conn, msg, header)
+    }
+}
+
 /// An object server, holding server-side D-Bus objects & interfaces.
 ///
 /// Object servers hold interfaces on various object paths, and expose them over D-Bus.
@@ -208,6 +739,25 @@ impl Node {
 /// All object paths will have the standard interfaces implemented on your behalf, such as
 /// `org.freedesktop.DBus.Introspectable` or `org.freedesktop.DBus.Properties`.
 ///
+/// # Threading and runtime requirements
+///
+/// `ObjectServer` is entirely synchronous and doesn't need an async runtime of any kind: messages
+/// are dispatched to the interfaces from [`try_handle_next`], on the calling thread. Since
+/// interfaces are not required to be [`Send`], the `ObjectServer` must stay on the thread it was
+/// created on. The only other thread involved is the one zbus spawns for each [`Connection`] to
+/// receive messages (when the default `internal-executor` feature is enabled). It's owned by the
+/// connection and goes away with it.
+///
+/// A panic in an interface method is not caught, and unwinds through [`try_handle_next`] (or
+/// [`dispatch_message`]) without any reply being sent to the caller.
+///
+/// See the `sync-server` example in the zbus repository for a complete service, serving and
+/// answering a method call without any async code.
+///
+/// [`try_handle_next`]: struct.ObjectServer.html#method.try_handle_next
+/// [`dispatch_message`]: struct.ObjectServer.html#method.dispatch_message
+/// [`Connection`]: struct.Connection.html
+///
 /// # Example
 ///
 /// This example exposes the `org.myiface.Example.Quit` method on the `/org/zbus/path`
@@ -269,6 +819,11 @@ pub struct ObjectServer {
     root: Node,
     #[derivative(Debug = "ignore")]
     msg_stream: MessageStream,
+    // Method calls for unknown objects or interfaces, held while frozen.
+    held_calls: Option<VecDeque<Message>>,
+    max_held_calls: usize,
+    #[derivative(Debug = "ignore")]
+    fallbacks: Vec<(OwnedObjectPath, Box<dyn Fallback>)>,
 }
 
 assert_impl_all!(ObjectServer: Unpin);
@@ -280,52 +835,57 @@ impl ObjectServer {
             conn: connection.clone(),
             msg_stream: block_on(connection.inner().stream()),
             root: Node::new("/".try_into().expect("zvariant bug")),
+            held_calls: None,
+            max_held_calls: 0,
+            fallbacks: vec![],
         }
     }
 
-    // Get the Node at path.
-    fn get_node(&self, path: &ObjectPath<'_>) -> Option<&Node> {
-        let mut node = &self.root;
-        let mut node_path = String::new();
-
-        for i in path.split('/').skip(1) {
-            if i.is_empty() {
-                continue;
-            }
-            write!(&mut node_path, "/{}", i).unwrap();
-            match node.children.get(i) {
-                Some(n) => node = n,
-                None => return None,
+    /// Hold method calls for unknown objects and interfaces, until [`thaw`] is called.
+    ///
+    /// Normally, a method call for an object path or interface that is not registered is
+    /// immediately replied to with an `UnknownObject` or `UnknownInterface` error. This is a
+    /// problem if clients start calling methods as soon as the service's name appears on the bus,
+    /// while the service is still registering its interfaces. Freezing the object server before
+    /// requesting the name and thawing it once all interfaces are registered, avoids this race.
+    ///
+    /// At most `max_held` method calls are held. Any further calls to unknown objects or
+    /// interfaces are replied to with the usual error.
+    ///
+    /// [`thaw`]: struct.ObjectServer.html#method.thaw
+    pub fn freeze(&mut self, max_held: usize) {
+        self.held_calls.get_or_insert_with(VecDeque::new);
+        self.max_held_calls = max_held;
+    }
+
+    /// Stop holding method calls and dispatch the ones held since [`freeze`] was called.
+    ///
+    /// Held calls for objects or interfaces that are still unknown are replied to with the usual
+    /// error. If dispatching any of the calls fails, the remaining calls are still dispatched and
+    /// the first error is returned.
+    ///
+    /// [`freeze`]: struct.ObjectServer.html#method.freeze
+    pub fn thaw(&mut self) -> Result<()> {
+        let mut res = Ok(());
+        for msg in self.held_calls.take().unwrap_or_default() {
+            if let Err(e) = self.dispatch_message(&msg) {
+                if res.is_ok() {
+                    res = Err(e);
+                }
             }
         }
 
-        Some(node)
+        res
+    }
+
+    // Get the Node at path.
+    fn get_node(&self, path: &ObjectPath<'_>) -> Option<&Node> {
+        self.root.child(path)
     }
 
     // Get the Node at path. Optionally create one if it doesn't exist.
     fn get_node_mut(&mut self, path: &ObjectPath<'_>, create: bool) -> Option<&mut Node> {
-        let mut node = &mut self.root;
-        let mut node_path = String::new();
-
-        for i in path.split('/').skip(1) {
-            if i.is_empty() {
-                continue;
-            }
-            write!(&mut node_path, "/{}", i).unwrap();
-            match node.children.entry(i.into()) {
-                Entry::Vacant(e) => {
-                    if create {
-                        let path = node_path.as_str().try_into().expect("Invalid Object Path");
-                        node = e.insert(Node::new(path));
-                    } else {
-                        return None;
-                    }
-                }
-                Entry::Occupied(e) => node = e.into_mut(),
-            }
-        }
-
-        Some(node)
+        self.root.child_mut(path, create)
     }
 
     /// Register a D-Bus [`Interface`] at a given path. (see the example above)
@@ -340,7 +900,163 @@ impl ObjectServer {
         E: Into<Error>,
     {
         let path = path.try_into().map_err(Into::into)?;
-        Ok(self.get_node_mut(&path, true).unwrap().at(I::name(), iface))
+        Ok(self
+            .get_node_mut(&path, true)
+            .unwrap()
+            .at(I::name().into(), iface))
+    }
+
+    /// Register a D-Bus [`Interface`] at a given path, under its name and the names of its other
+    /// versions.
+    ///
+    /// This serves incompatible versions of an interface with a single implementation, handling the
+    /// members of all of them, e.g to keep serving the clients of `org.example.Foo1` while the
+    /// newer ones use `org.example.Foo2`. The other versions are declared on the implementation,
+    /// with `#[dbus_interface(versions(...))]`. Each version is introspected under its own name,
+    /// with all the members of the implementation. Its members are dispatched to the same instance.
+    ///
+    /// [`with`], [`with_mut_tracked`] and [`signal_emitter`] take the implementation, under the
+    /// name of the interface. The signals are only emitted under that name, `PropertiesChanged`
+    /// included. [`remove`] removes all the versions.
+    ///
+    /// If any of the versions already exists at this path, nothing is registered and this returns
+    /// false. See [`Proxy::first_available`] for the client side.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    /// use zbus::{dbus_interface, Connection, ObjectServer};
+    ///
+    /// struct Foo;
+    ///
+    /// #[dbus_interface(name = "org.example.Foo2", versions("org.example.Foo1"))]
+    /// impl Foo {
+    ///     fn ping(&self) -> &str {
+    ///         "pong"
+    ///     }
+    /// }
+    ///
+    /// let connection = Connection::new_session()?;
+    /// let mut object_server = ObjectServer::new(&connection);
+    /// object_server.at_versions("/org/example/foo", Foo)?;
+    ///
+    /// loop {
+    ///     object_server.try_handle_next()?;
+    /// }
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`Interface`]: trait.Interface.html
+    /// [`with`]: struct.ObjectServer.html#method.with
+    /// [`with_mut_tracked`]: struct.ObjectServer.html#method.with_mut_tracked
+    /// [`signal_emitter`]: struct.ObjectServer.html#method.signal_emitter
+    /// [`remove`]: struct.ObjectServer.html#method.remove
+    /// [`Proxy::first_available`]: struct.Proxy.html#method.first_available
+    pub fn at_versions<'p, P, I, E>(&mut self, path: P, iface: I) -> Result<bool>
+    where
+        I: Interface,
+        P: TryInto<ObjectPath<'p>, Error = E>,
+        E: Into<Error>,
+    {
+        let path = path.try_into().map_err(Into::into)?;
+        Ok(self.get_node_mut(&path, true).unwrap().at_versions(iface))
+    }
+
+    /// Register a [`DynamicInterface`] at a given path.
+    ///
+    /// If an interface of the same name already exists at this path, returns false.
+    ///
+    /// [`DynamicInterface`]: struct.DynamicInterface.html
+    pub fn at_dynamic<'p, P, E>(&mut self, path: P, iface: DynamicInterface) -> Result<bool>
+    where
+        P: TryInto<ObjectPath<'p>, Error = E>,
+        E: Into<Error>,
+    {
+        let path = path.try_into().map_err(Into::into)?;
+        let name = iface.name().to_owned();
+        Ok(self
+            .get_node_mut(&path, true)
+            .unwrap()
+            .at(name.into(), Dynamic(iface)))
+    }
+
+    /// Register a D-Bus [`Interface`] at a given path, merging it with the interface of the same
+    /// name, if any.
+    ///
+    /// This splits the implementation of an interface in parts, of different types, e.g to share
+    /// a part implementing common members between objects. The parts are exposed as a single
+    /// interface: their members are dispatched to the part having them, introspected together,
+    /// and `Properties.GetAll` returns the properties of all of them. As their signals are emitted
+    /// with the name of their interface, the parts must be [`dbus_interface`]s of the same `name`.
+    ///
+    /// Fails with [`Error::InvalidName`] if a member of `iface` has the same kind and name as one
+    /// of the interface already registered, or if a part of the same type is already registered.
+    ///
+    /// [`with`], [`with_mut_tracked`], [`signal_emitter`] and [`remove`] take the type of a part,
+    /// and apply to that part only.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    /// use zbus::{dbus_interface, Connection, ObjectServer};
+    ///
+    /// // The members all the devices have.
+    /// struct Device {
+    ///     vendor: String,
+    /// }
+    ///
+    /// #[dbus_interface(name = "org.zbus.Device")]
+    /// impl Device {
+    ///     #[dbus_interface(property)]
+    ///     fn vendor(&self) -> &str {
+    ///         &self.vendor
+    ///     }
+    /// }
+    ///
+    /// // The members specific to disks.
+    /// struct Disk {
+    ///     size: u64,
+    /// }
+    ///
+    /// #[dbus_interface(name = "org.zbus.Device")]
+    /// impl Disk {
+    ///     #[dbus_interface(property)]
+    ///     fn size(&self) -> u64 {
+    ///         self.size
+    ///     }
+    /// }
+    ///
+    /// let connection = Connection::new_session()?;
+    /// let mut object_server = ObjectServer::new(&connection);
+    /// let path = "/org/zbus/disk0";
+    /// object_server.merge_at(path, Device { vendor: "zbus".into() })?;
+    /// object_server.merge_at(path, Disk { size: 1 << 40 })?;
+    ///
+    /// loop {
+    ///     object_server.try_handle_next()?;
+    /// }
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`Interface`]: trait.Interface.html
+    /// [`dbus_interface`]: attr.dbus_interface.html
+    /// [`Error::InvalidName`]: enum.Error.html#variant.InvalidName
+    /// [`with`]: struct.ObjectServer.html#method.with
+    /// [`with_mut_tracked`]: struct.ObjectServer.html#method.with_mut_tracked
+    /// [`signal_emitter`]: struct.ObjectServer.html#method.signal_emitter
+    /// [`remove`]: struct.ObjectServer.html#method.remove
+    pub fn merge_at<'p, P, I, E>(&mut self, path: P, iface: I) -> Result<()>
+    where
+        I: Interface,
+        P: TryInto<ObjectPath<'p>, Error = E>,
+        E: Into<Error>,
+    {
+        let path = path.try_into().map_err(Into::into)?;
+        self.get_node_mut(&path, true)
+            .unwrap()
+            .merge(I::name(), iface)
     }
 
     /// Unregister a D-Bus [`Interface`] at a given path.
@@ -348,7 +1064,13 @@ impl ObjectServer {
     /// If there are no more interfaces left at that path, destroys the object as well.
     /// Returns whether the object was destroyed.
     ///
+    /// Only the part of type `I` is removed from an interface registered in parts through
+    /// [`merge_at`], unless it's the last one. The other versions of an interface registered
+    /// through [`at_versions`] are removed along with it.
+    ///
     /// [`Interface`]: trait.Interface.html
+    /// [`merge_at`]: struct.ObjectServer.html#method.merge_at
+    /// [`at_versions`]: struct.ObjectServer.html#method.at_versions
     pub fn remove<'p, I, P, E>(&mut self, path: P) -> Result<bool>
     where
         I: Interface,
@@ -356,24 +1078,261 @@ impl ObjectServer {
         E: Into<Error>,
     {
         let path = path.try_into().map_err(Into::into)?;
-        let node = self
-            .get_node_mut(&path, false)
-            .ok_or(Error::InterfaceNotFound)?;
-        if !node.remove_interface(I::name()) {
-            return Err(Error::InterfaceNotFound);
+        if let Some(node) = self.get_node_mut(&path, false) {
+            node.remove_versions::<I>();
         }
-        if node.is_empty() {
-            let mut path_parts = path.rsplit('/').filter(|i| !i.is_empty());
-            let last_part = path_parts.next().unwrap();
-            let ppath = ObjectPath::from_string_unchecked(
-                path_parts.fold(String::new(), |a, p| format!("/{}{}", p, a)),
-            );
-            self.get_node_mut(&ppath, false)
-                .unwrap()
-                .remove_node(last_part);
-            return Ok(true);
+        self.remove_interface(&path, I::name(), Some(TypeId::of::<I>()))
+    }
+
+    /// Unregister the [`DynamicInterface`] named `name` at a given path.
+    ///
+    /// As with [`remove`], the object is destroyed along with its last interface, and the return
+    /// value says whether it was.
+    ///
+    /// [`DynamicInterface`]: struct.DynamicInterface.html
+    /// [`remove`]: struct.ObjectServer.html#method.remove
+    pub fn remove_dynamic<'p, P, E>(&mut self, path: P, name: &str) -> Result<bool>
+    where
+        P: TryInto<ObjectPath<'p>, Error = E>,
+        E: Into<Error>,
+    {
+        let path = path.try_into().map_err(Into::into)?;
+        self.remove_interface(&path, name, None)
+    }
+
+    /// Register a [`Fallback`] handler for the calls under `path_prefix` no interface handles.
+    ///
+    /// The handler gets the calls to `path_prefix` itself and to the objects below it, e.g
+    /// `/org/zbus/Gateway/1` for a prefix of `/org/zbus/Gateway`. If several prefixes match, the
+    /// handler of the longest one gets the call. Calls no handler gets are replied to with the
+    /// usual `UnknownObject`, `UnknownInterface` or `UnknownMethod` error, unless the object server
+    /// is [frozen].
+    ///
+    /// If a handler is already registered at `path_prefix`, returns false.
+    ///
+    /// # Example
+    ///
+    /// Forwarding the calls to the objects under `/org/zbus/Gateway` to a system service:
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    /// use zbus::{
+    ///     fdo, Connection, FallbackReply, Message, MessageBuilder, MessageHeader, ObjectServer,
+    ///     RawBody,
+    /// };
+    ///
+    /// fn forward(backend: &Connection, call: &Message) -> zbus::Result<Message> {
+    ///     let header = call.header()?;
+    ///     let args = call.body_structure()?;
+    ///     // Method calls always have a path and a member.
+    ///     let reply = backend.call_method_with_values(
+    ///         Some("org.zbus.Backend"),
+    ///         header.path()?.unwrap().as_str(),
+    ///         header.interface()?,
+    ///         header.member()?.unwrap(),
+    ///         args.fields(),
+    ///     )?;
+    ///     let body = RawBody::from_values(reply.body_structure()?.fields())?;
+    ///
+    ///     Ok(MessageBuilder::method_return(call)?.build_raw_body(&body)?)
+    /// }
+    ///
+    /// let connection = Connection::new_session()?;
+    /// let backend = Connection::new_system()?;
+    /// let mut object_server = ObjectServer::new(&connection);
+    /// let gateway = move |_: &Connection, call: &Message, _: &MessageHeader<'_>| {
+    ///     match forward(&backend, call) {
+    ///         Ok(reply) => FallbackReply::Reply(reply),
+    ///         Err(e) => FallbackReply::Error(fdo::Error::Failed(e.to_string())),
+    ///     }
+    /// };
+    /// object_server.at_fallback("/org/zbus/Gateway", gateway)?;
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`Fallback`]: trait.Fallback.html
+    /// [frozen]: struct.ObjectServer.html#method.freeze
+    pub fn at_fallback<'p, P, F, E>(&mut self, path_prefix: P, handler: F) -> Result<bool>
+    where
+        F: Fallback + 'static,
+        P: TryInto<ObjectPath<'p>, Error = E>,
+        E: Into<Error>,
+    {
+        let path_prefix = path_prefix.try_into().map_err(Into::into)?;
+        if self.fallbacks.iter().any(|(p, _)| **p == *path_prefix) {
+            return Ok(false);
+        }
+        self.fallbacks.push((path_prefix.into(), Box::new(handler)));
+
+        Ok(true)
+    }
+
+    /// Unregister the [`Fallback`] handler registered at `path_prefix`.
+    ///
+    /// Returns whether there was one.
+    ///
+    /// [`Fallback`]: trait.Fallback.html
+    pub fn remove_fallback<'p, P, E>(&mut self, path_prefix: P) -> Result<bool>
+    where
+        P: TryInto<ObjectPath<'p>, Error = E>,
+        E: Into<Error>,
+    {
+        let path_prefix = path_prefix.try_into().map_err(Into::into)?;
+        let len = self.fallbacks.len();
+        self.fallbacks.retain(|(p, _)| **p != *path_prefix);
+
+        Ok(self.fallbacks.len() != len)
+    }
+
+    fn remove_interface(
+        &mut self,
+        path: &ObjectPath<'_>,
+        name: &str,
+        part: Option<TypeId>,
+    ) -> Result<bool> {
+        self.root.remove_interface_at(path, name, part)
+    }
+
+    /// Register a D-Bus [`Interface`] at a given path, until the returned guard is dropped.
+    ///
+    /// The object server is only accessible through the returned [`ScopedInterface`] (it
+    /// dereferences to the `ObjectServer`), so messages can still be dispatched while the interface
+    /// is registered. Since methods are only ever called from [`dispatch_message`] on the same
+    /// thread, no method of the interface runs after the guard is dropped.
+    ///
+    /// As with [`at`], the interface must be `'static`. See [`serve_dynamic_scoped`] to serve
+    /// a [`DynamicInterface`] for the duration of a closure.
+    ///
+    /// If the interface already exists at this path, returns `None`.
+    ///
+    /// [`Interface`]: trait.Interface.html
+    /// [`ScopedInterface`]: struct.ScopedInterface.html
+    /// [`dispatch_message`]: struct.ObjectServer.html#method.dispatch_message
+    /// [`at`]: struct.ObjectServer.html#method.at
+    /// [`serve_dynamic_scoped`]: struct.ObjectServer.html#method.serve_dynamic_scoped
+    /// [`DynamicInterface`]: struct.DynamicInterface.html
+    pub fn serve_scoped<'p, P, I, E>(
+        &mut self,
+        path: P,
+        iface: I,
+    ) -> Result<Option<ScopedInterface<'_, I>>>
+    where
+        I: Interface,
+        P: TryInto<ObjectPath<'p>, Error = E>,
+        E: Into<Error>,
+    {
+        let path = path.try_into().map_err(Into::into)?;
+        if !self.at(path.clone(), iface)? {
+            return Ok(None);
+        }
+        let iface = self
+            .get_node(&path)
+            .and_then(Node::interface_of::<I>)
+            .expect("interface just registered");
+
+        Ok(Some(ScopedInterface {
+            server: self,
+            path: path.into(),
+            iface: Rc::downgrade(&iface),
+            phantom: PhantomData,
+        }))
+    }
+
+    /// Serve a [`DynamicInterface`] at a given path while `func` runs.
+    ///
+    /// `func` gets the object server, to dispatch messages through [`try_handle_next`] or
+    /// [`dispatch_message`]. The interface is unregistered and dropped as soon as `func` returns
+    /// or panics, so none of the handlers runs after that. Only this interface is unregistered,
+    /// not one of the same name `func` would register at the same path after removing this one.
+    ///
+    /// The handlers must be `'static`, so state shared with the caller, e.g that of a plugin, goes
+    /// through an `Rc`. Since the handlers are dropped along with the interface, the caller gets
+    /// back sole ownership of the state once this returns.
+    ///
+    /// If an interface of the same name already exists at this path, `func` isn't called and this
+    /// returns `None`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    /// use std::{cell::Cell, rc::Rc};
+    /// use zbus::{Connection, DynamicInterfaceBuilder, ObjectServer, RawBody};
+    ///
+    /// let connection = Connection::new_session()?;
+    /// let mut object_server = ObjectServer::new(&connection);
+    ///
+    /// let pings = Rc::new(Cell::new(0));
+    /// let handler_pings = pings.clone();
+    /// let iface = DynamicInterfaceBuilder::new("org.zbus.Plugin")
+    ///     .method("Ping", "", "", move |_| {
+    ///         handler_pings.set(handler_pings.get() + 1);
+    ///
+    ///         Ok(RawBody::new(&())?)
+    ///     })
+    ///     .build()?;
+    /// object_server
+    ///     .serve_dynamic_scoped("/org/zbus/Plugin", iface, |object_server| {
+    ///         while pings.get() < 3 {
+    ///             object_server.try_handle_next()?;
+    ///         }
+    ///
+    ///         Ok::<_, zbus::Error>(())
+    ///     })?
+    ///     .expect("plugin already served")?;
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`DynamicInterface`]: struct.DynamicInterface.html
+    /// [`try_handle_next`]: struct.ObjectServer.html#method.try_handle_next
+    /// [`dispatch_message`]: struct.ObjectServer.html#method.dispatch_message
+    pub fn serve_dynamic_scoped<'p, P, E, F, R>(
+        &mut self,
+        path: P,
+        iface: DynamicInterface,
+        func: F,
+    ) -> Result<Option<R>>
+    where
+        P: TryInto<ObjectPath<'p>, Error = E>,
+        E: Into<Error>,
+        F: FnOnce(&mut ObjectServer) -> R,
+    {
+        let path = path.try_into().map_err(Into::into)?;
+        let name = iface.name().to_owned();
+        let node = self.get_node_mut(&path, true).unwrap();
+        if !node.at(name.clone().into(), Dynamic(iface)) {
+            return Ok(None);
+        }
+        let iface = Rc::downgrade(
+            &node
+                .get_interface(&name)
+                .expect("interface just registered"),
+        );
+
+        let scoped = ScopedDynamic {
+            server: self,
+            path: path.into(),
+            name,
+            iface,
+        };
+
+        Ok(Some(func(&mut *scoped.server)))
+    }
+
+    // Whether `iface` is still registered at `path`, as the interface of type `I` named `name`.
+    fn is_registered<I: Interface>(
+        &self,
+        path: &ObjectPath<'_>,
+        name: &str,
+        iface: &Weak<RefCell<dyn Interface>>,
+    ) -> bool {
+        let registered = self
+            .get_node(path)
+            .and_then(|node| node.interface_named::<I>(name));
+        match (registered, iface.upgrade()) {
+            (Some(registered), Some(iface)) => Rc::ptr_eq(&registered, &iface),
+            _ => false,
         }
-        Ok(false)
     }
 
     /// Run `func` with the given path & interface.
@@ -418,47 +1377,307 @@ impl ObjectServer {
         })
     }
 
-    /// Emit a signal on the currently dispatched node.
+    /// Run `func` with a mutable reference to the interface `I` at `path`, then signal the changes
+    /// it made to the properties.
     ///
-    /// This is an internal helper function to emit a signal on on the current node. You shouldn't
-    /// call this method directly, rather with the derived signal implementation from
-    /// [`dbus_interface`].
+    /// The properties are read before and after running `func`, and a single `PropertiesChanged`
+    /// signal is emitted for all the ones whose value is different, sparing the calls of each
+    /// `<property>_changed` method. Properties annotated with `emits_changed_signal = "false"` or
+    /// `"const"` are not tracked. As with [`with`], the signals of the interface can be emitted
+    /// from `func`.
     ///
-    /// # Panics
+    /// If `func` fails, the changes it made are still signaled, and its error is returned.
     ///
-    /// This method will panic if called from outside of a node context. Use [`ObjectServer::with`]
-    /// to bring a node into the current context.
+    /// # Example
     ///
-    /// [`dbus_interface`]: attr.dbus_interface.html
-    pub fn local_node_emit_signal<B>(
-        destination: Option<&str>,
-        iface: &str,
-        signal_name: &str,
-        body: &B,
-    ) -> Result<()>
+    /// ```no_run
+    ///# use std::error::Error;
+    ///# use zbus::{Connection, ObjectServer, dbus_interface};
+    ///#
+    /// struct Player {
+    ///     volume: f64,
+    ///     title: String,
+    /// }
+    ///
+    /// #[dbus_interface(name = "org.myiface.Player")]
+    /// impl Player {
+    ///     #[dbus_interface(property)]
+    ///     fn volume(&self) -> f64 {
+    ///         self.volume
+    ///     }
+    ///
+    ///     #[dbus_interface(property)]
+    ///     fn title(&self) -> &str {
+    ///         &self.title
+    ///     }
+    /// }
+    ///
+    /// let connection = Connection::new_session()?;
+    /// let mut object_server = ObjectServer::new(&connection);
+    /// let player = Player { volume: 0.5, title: String::new() };
+    /// object_server.at("/org/zbus/player", player)?;
+    ///
+    /// // Emits a single `PropertiesChanged` for both properties.
+    /// object_server.with_mut_tracked("/org/zbus/player", |player: &mut Player| {
+    ///     player.volume = 1.0;
+    ///     player.title = "Sonata".into();
+    ///     Ok(())
+    /// })?;
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`with`]: struct.ObjectServer.html#method.with
+    pub fn with_mut_tracked<'p, P, F, I>(&self, path: P, func: F) -> Result<()>
     where
-        B: serde::ser::Serialize + zvariant::Type,
+        F: FnOnce(&mut I) -> Result<()>,
+        I: Interface,
+        P: TryInto<ObjectPath<'p>, Error = zvariant::Error>,
     {
-        if !LOCAL_NODE.is_set() {
-            panic!("emit_signal: Node TLS not set");
-        }
-
-        LOCAL_NODE.with(|n| n.emit_signal(destination, iface, signal_name, body))
+        let path = path.try_into()?;
+        let node = self.get_node(&path).ok_or(Error::InterfaceNotFound)?;
+        LOCAL_CONNECTION.set(&self.conn, || {
+            LOCAL_NODE.set(node, || node.with_iface_mut_tracked(func))
+        })
     }
 
-    fn dispatch_method_call_try(
-        &mut self,
-        msg_header: &MessageHeader<'_>,
-        msg: &Message,
-    ) -> fdo::Result<Result<u32>> {
-        let conn = self.conn.clone();
-        let path = msg_header
-            .path()
-            .ok()
-            .flatten()
-            .ok_or_else(|| fdo::Error::Failed("Missing object path".into()))?;
-        let iface = msg_header
-            .interface()
+    /// Get a [`SignalEmitter`] for the interface `I` at `path`.
+    ///
+    /// The emitter is independent of the object server, so it can be moved to another thread or
+    /// task to emit the signals of the interface from there. Returns
+    /// [`Error::InterfaceNotFound`] if the interface is not registered at `path`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    ///# use std::{error::Error, thread};
+    ///# use zbus::{Connection, ObjectServer, dbus_interface};
+    ///#
+    /// struct Clock;
+    ///
+    /// #[dbus_interface(name = "org.myiface.Clock")]
+    /// impl Clock {
+    ///     #[dbus_interface(signal)]
+    ///     fn tick(&self, count: u32) -> zbus::Result<()>;
+    /// }
+    ///
+    /// let connection = Connection::new_session()?;
+    /// let mut object_server = ObjectServer::new(&connection);
+    /// object_server.at("/org/zbus/clock", Clock)?;
+    ///
+    /// let emitter = object_server.signal_emitter::<_, Clock>("/org/zbus/clock")?;
+    /// thread::spawn(move || {
+    ///     for count in 0.. {
+    ///         Clock::tick_on(emitter.connection(), emitter.path(), count).unwrap();
+    ///         thread::sleep(std::time::Duration::from_secs(1));
+    ///     }
+    /// });
+    ///
+    /// loop {
+    ///     object_server.try_handle_next()?;
+    /// }
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`SignalEmitter`]: struct.SignalEmitter.html
+    /// [`Error::InterfaceNotFound`]: enum.Error.html#variant.InterfaceNotFound
+    pub fn signal_emitter<'p, P, I>(&self, path: P) -> Result<SignalEmitter>
+    where
+        I: Interface,
+        P: TryInto<ObjectPath<'p>, Error = zvariant::Error>,
+    {
+        let path = path.try_into()?;
+        self.get_node(&path)
+            .and_then(|node| node.interface_of::<I>())
+            .ok_or(Error::InterfaceNotFound)?;
+
+        Ok(SignalEmitter {
+            conn: self.conn.clone(),
+            path: path.into(),
+        })
+    }
+
+    // The nodes on which the interface `I` is registered, sorted by path.
+    fn nodes_of<I: Interface>(&self) -> Vec<&Node> {
+        let mut nodes = vec![];
+        self.root.collect_nodes_of::<I>(&mut nodes);
+        nodes.sort_unstable_by(|a, b| a.path.as_str().cmp(b.path.as_str()));
+
+        nodes
+    }
+
+    /// The paths of all the objects on which the interface `I` is registered, in order.
+    ///
+    /// Interfaces registered in parts through [`merge_at`] are included if `I` is one of them.
+    ///
+    /// [`merge_at`]: struct.ObjectServer.html#method.merge_at
+    pub fn paths_of<I: Interface>(&self) -> Vec<OwnedObjectPath> {
+        self.nodes_of::<I>()
+            .into_iter()
+            .map(|node| node.path.clone())
+            .collect()
+    }
+
+    /// The paths of all the objects on which interfaces are registered, in order.
+    ///
+    /// Objects only having the standard `Peer`, `Introspectable` and `Properties` interfaces, such
+    /// as the parents of the registered ones, are left out.
+    pub fn paths(&self) -> Vec<OwnedObjectPath> {
+        let mut nodes = vec![];
+        self.root.collect_nodes(&mut nodes);
+        let mut paths: Vec<_> = nodes.into_iter().map(|node| node.path.clone()).collect();
+        paths.sort_unstable_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        paths
+    }
+
+    /// The names of the interfaces registered at `path`, in order.
+    ///
+    /// The standard interfaces are included. Fails with [`Error::InterfaceNotFound`] if there's no
+    /// object at `path`.
+    ///
+    /// [`Error::InterfaceNotFound`]: enum.Error.html#variant.InterfaceNotFound
+    pub fn interfaces_at<'p, P>(&self, path: P) -> Result<Vec<String>>
+    where
+        P: TryInto<ObjectPath<'p>, Error = zvariant::Error>,
+    {
+        let path = path.try_into()?;
+        let node = self.get_node(&path).ok_or(Error::InterfaceNotFound)?;
+        let mut names: Vec<_> = node.interfaces.keys().map(|k| k.to_string()).collect();
+        names.sort_unstable();
+
+        Ok(names)
+    }
+
+    /// The description of the members of the interface `iface` at `path`.
+    ///
+    /// That's the [`InterfaceMetadata`] generated by the [`dbus_interface`] macro, or one per part
+    /// for the interfaces registered in parts through [`merge_at`]. Interfaces served as another
+    /// version through [`at_versions`] are described under the name of their implementation, and
+    /// the ones built at runtime, such as [`DynamicInterface`], aren't described at all.
+    ///
+    /// Fails with [`Error::InterfaceNotFound`] if `iface` isn't registered at `path`.
+    ///
+    /// [`InterfaceMetadata`]: struct.InterfaceMetadata.html
+    /// [`dbus_interface`]: attr.dbus_interface.html
+    /// [`merge_at`]: struct.ObjectServer.html#method.merge_at
+    /// [`at_versions`]: struct.ObjectServer.html#method.at_versions
+    /// [`DynamicInterface`]: struct.DynamicInterface.html
+    /// [`Error::InterfaceNotFound`]: enum.Error.html#variant.InterfaceNotFound
+    pub fn interface_metadata<'p, P>(
+        &self,
+        path: P,
+        iface: &str,
+    ) -> Result<Vec<&'static InterfaceMetadata>>
+    where
+        P: TryInto<ObjectPath<'p>, Error = zvariant::Error>,
+    {
+        let path = path.try_into()?;
+        let iface = self
+            .get_node(&path)
+            .and_then(|node| node.get_interface(iface))
+            .ok_or(Error::InterfaceNotFound)?;
+        let iface = iface.borrow();
+        let metadata = match iface.downcast_ref::<Composite>() {
+            Some(composite) => composite
+                .parts()
+                .iter()
+                .filter_map(|part| part.borrow().metadata())
+                .collect(),
+            None => iface.metadata().into_iter().collect(),
+        };
+
+        Ok(metadata)
+    }
+
+    /// Run `func` with each instance of the interface `I`, in the order of [`paths_of`].
+    ///
+    /// This is the same as calling [`with`] for each path, e.g. to emit a signal from all the
+    /// objects implementing an interface. If `func` fails for an object, it is still run for the
+    /// remaining ones and the first error is returned.
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    ///# use zbus::{Connection, ObjectServer, dbus_interface};
+    ///#
+    /// struct Device;
+    ///
+    /// #[dbus_interface(name = "org.myiface.Device")]
+    /// impl Device {
+    ///     #[dbus_interface(signal)]
+    ///     fn rescan_done(&self) -> zbus::Result<()>;
+    /// }
+    ///
+    /// let connection = Connection::new_session()?;
+    /// let mut object_server = ObjectServer::new(&connection);
+    /// object_server.at("/org/zbus/device/0", Device)?;
+    /// object_server.at("/org/zbus/device/1", Device)?;
+    ///
+    /// object_server.emit_signal_to_all(|device: &Device| device.rescan_done())?;
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`paths_of`]: struct.ObjectServer.html#method.paths_of
+    /// [`with`]: struct.ObjectServer.html#method.with
+    pub fn emit_signal_to_all<I, F>(&self, func: F) -> Result<()>
+    where
+        F: Fn(&I) -> Result<()>,
+        I: Interface,
+    {
+        let mut res = Ok(());
+        LOCAL_CONNECTION.set(&self.conn, || {
+            for node in self.nodes_of::<I>() {
+                if let Err(e) = LOCAL_NODE.set(node, || node.with_iface_func(&func)) {
+                    if res.is_ok() {
+                        res = Err(e);
+                    }
+                }
+            }
+        });
+
+        res
+    }
+
+    /// Emit a signal on the currently dispatched node.
+    ///
+    /// This is an internal helper function to emit a signal on on the current node. You shouldn't
+    /// call this method directly, rather with the derived signal implementation from
+    /// [`dbus_interface`].
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if called from outside of a node context. Use [`ObjectServer::with`]
+    /// to bring a node into the current context.
+    ///
+    /// [`dbus_interface`]: attr.dbus_interface.html
+    pub fn local_node_emit_signal<B>(
+        destination: Option<&str>,
+        iface: &str,
+        signal_name: &str,
+        body: &B,
+    ) -> Result<()>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        if !LOCAL_NODE.is_set() {
+            panic!("emit_signal: Node TLS not set");
+        }
+
+        LOCAL_NODE.with(|n| n.emit_signal(destination, iface, signal_name, body))
+    }
+
+    fn dispatch_method_call_try(
+        &mut self,
+        msg_header: &MessageHeader<'_>,
+        msg: &Message,
+    ) -> fdo::Result<Result<u32>> {
+        let conn = self.conn.clone();
+        let path = msg_header
+            .path()
+            .ok()
+            .flatten()
+            .ok_or_else(|| fdo::Error::Failed("Missing object path".into()))?;
+        let iface = msg_header
+            .interface()
             .ok()
             .flatten()
             // TODO: In the absence of an INTERFACE field, if two or more interfaces on the same object
@@ -490,14 +1709,95 @@ impl ObjectServer {
         })
     }
 
+    // The fallback handler of the longest prefix of `path`, if any.
+    fn fallback_of(&mut self, path: &ObjectPath<'_>) -> Option<&mut dyn Fallback> {
+        self.fallbacks
+            .iter_mut()
+            .filter(|(prefix, _)| {
+                let prefix = prefix.as_str().trim_end_matches('/');
+                match path.strip_prefix(prefix) {
+                    Some(rest) => rest.is_empty() || rest.starts_with('/'),
+                    None => false,
+                }
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, handler)| handler.as_mut())
+    }
+
+    // Give the call no interface handled to the fallback handler of its path, if any.
+    fn dispatch_fallback(
+        &mut self,
+        msg_header: &MessageHeader<'_>,
+        msg: &Message,
+        unknown_object: bool,
+    ) -> Option<Result<u32>> {
+        let path = msg_header.path().ok().flatten()?;
+        let conn = self.conn.clone();
+        let handler = self.fallback_of(path)?;
+        log::trace!(target: logging::OBJECT_SERVER, "Dispatching {} to fallback", msg);
+
+        let is_introspect = msg_header.interface().ok().flatten()
+            == Some("org.freedesktop.DBus.Introspectable")
+            && msg_header.member().ok().flatten() == Some("Introspect");
+        if unknown_object && is_introspect {
+            if let Some(xml) = handler.introspect(path) {
+                return Some(conn.reply(msg, &xml));
+            }
+        }
+
+        Some(match handler.call(&conn, msg, msg_header) {
+            FallbackReply::Reply(reply) => conn.send_message(reply),
+            FallbackReply::Error(e) => e.reply(&conn, msg),
+            FallbackReply::Forwarded => Ok(0),
+        })
+    }
+
     fn dispatch_method_call(
         &mut self,
         msg_header: &MessageHeader<'_>,
         msg: &Message,
     ) -> Result<u32> {
-        match self.dispatch_method_call_try(msg_header, msg) {
-            Err(e) => e.reply(&self.conn, msg),
+        log::trace!(target: logging::OBJECT_SERVER, "Dispatching {}", msg);
+        let mut res = self.dispatch_method_call_try(msg_header, msg);
+        let unknown = match &res {
+            Err(fdo::Error::UnknownObject(_)) => Some(true),
+            Err(fdo::Error::UnknownInterface(_)) | Err(fdo::Error::UnknownMethod(_)) => Some(false),
+            _ => None,
+        };
+        if let Some(unknown_object) = unknown {
+            if let Some(r) = self.dispatch_fallback(msg_header, msg, unknown_object) {
+                res = Ok(r);
+            }
+        }
+        let res = match res {
+            Err(fdo::Error::UnknownObject(_)) | Err(fdo::Error::UnknownInterface(_))
+                if self.hold_call(msg) =>
+            {
+                log::debug!(target: logging::OBJECT_SERVER, "Holding {}", msg);
+                return Ok(0);
+            }
+            Err(e) => {
+                log::debug!(target: logging::OBJECT_SERVER, "{} failed: {}", msg, e);
+                e.reply(&self.conn, msg)
+            }
             Ok(r) => r,
+        };
+        if let Err(e) = &res {
+            log::warn!(target: logging::OBJECT_SERVER, "Failed to reply to {}: {}", msg, e);
+        }
+
+        res
+    }
+
+    // Returns `false` if the call can't be held.
+    fn hold_call(&mut self, msg: &Message) -> bool {
+        match &mut self.held_calls {
+            Some(held) if held.len() < self.max_held_calls => {
+                held.push_back(msg.clone());
+
+                true
+            }
+            _ => false,
         }
     }
 
@@ -555,25 +1855,108 @@ impl ObjectServer {
     }
 }
 
+/// An [`Interface`] registered by [`ObjectServer::serve_scoped`].
+///
+/// The interface is unregistered when this is dropped. Only the instance [`serve_scoped`]
+/// registered is, so if it was removed and another instance registered at the same path in the
+/// meantime, the latter is left alone.
+///
+/// [`Interface`]: trait.Interface.html
+/// [`ObjectServer::serve_scoped`]: struct.ObjectServer.html#method.serve_scoped
+/// [`serve_scoped`]: struct.ObjectServer.html#method.serve_scoped
+#[derive(derivative::Derivative)]
+#[derivative(Debug)]
+pub struct ScopedInterface<'o, I: Interface> {
+    server: &'o mut ObjectServer,
+    path: OwnedObjectPath,
+    // The registered instance.
+    #[derivative(Debug = "ignore")]
+    iface: Weak<RefCell<dyn Interface>>,
+    phantom: PhantomData<fn() -> I>,
+}
+
+impl<I: Interface> ScopedInterface<'_, I> {
+    /// The path the interface is registered at.
+    pub fn path(&self) -> &ObjectPath<'_> {
+        &self.path
+    }
+}
+
+impl<I: Interface> Deref for ScopedInterface<'_, I> {
+    type Target = ObjectServer;
+
+    fn deref(&self) -> &ObjectServer {
+        self.server
+    }
+}
+
+impl<I: Interface> DerefMut for ScopedInterface<'_, I> {
+    fn deref_mut(&mut self) -> &mut ObjectServer {
+        self.server
+    }
+}
+
+impl<I: Interface> Drop for ScopedInterface<'_, I> {
+    fn drop(&mut self) {
+        if self
+            .server
+            .is_registered::<I>(&self.path, I::name(), &self.iface)
+        {
+            let _ = self.server.remove::<I, _, _>(self.path.as_str());
+        }
+    }
+}
+
+// Unregisters the interface served by `ObjectServer::serve_dynamic_scoped`, when dropped.
+struct ScopedDynamic<'o> {
+    server: &'o mut ObjectServer,
+    path: OwnedObjectPath,
+    name: String,
+    iface: Weak<RefCell<dyn Interface>>,
+}
+
+impl Drop for ScopedDynamic<'_> {
+    fn drop(&mut self) {
+        if self
+            .server
+            .is_registered::<Dynamic>(&self.path, &self.name, &self.iface)
+        {
+            let part = Some(TypeId::of::<Dynamic>());
+            let _ = self.server.remove_interface(&self.path, &self.name, part);
+        }
+    }
+}
+
 #[cfg(test)]
 #[allow(clippy::blacklisted_name)]
 mod tests {
     use std::{
         cell::Cell,
         collections::HashMap,
+        convert::TryFrom,
         error::Error,
+        io::{self, Read},
+        os::unix::{io::AsRawFd, net::UnixStream},
         rc::Rc,
         sync::mpsc::{channel, Sender},
         thread,
     };
 
+    use async_io::block_on;
+    use enumflags2::BitFlags;
+    use futures_util::StreamExt;
     use ntest::timeout;
     use serde::{Deserialize, Serialize};
     use test_env_log::test;
-    use zvariant::derive::Type;
+    use zvariant::{
+        derive::{DeserializeNewtype, OwnedValue, Type, Value},
+        Fd, ObjectPath, OwnedObjectPath, TruncatedBitFlags, Value,
+    };
 
     use crate::{
-        dbus_interface, dbus_proxy, fdo, Connection, MessageHeader, MessageType, ObjectServer,
+        dbus_interface, dbus_proxy, fdo, Connection, ConnectionCredentials,
+        DynamicInterfaceBuilder, Fallback, FallbackReply, Guid, Interface, Message, MessageBuilder,
+        MessageHeader, MessageType, ObjectServer, RawBody, Responder,
     };
 
     #[derive(Deserialize, Serialize, Type)]
@@ -871,4 +2254,2045 @@ mod tests {
         let val = child.join().expect("failed to join");
         assert_eq!(val, 2);
     }
+
+    struct Counter(Rc<Cell<u32>>);
+
+    #[dbus_interface(name = "org.zbus.Counter")]
+    impl Counter {
+        fn count(&self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn serve_scoped() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = channel::<()>();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let calls = Rc::new(Cell::new(0));
+            tx.send(()).unwrap();
+
+            {
+                let iface = Counter(calls.clone());
+                let mut scoped = object_server
+                    .serve_scoped("/zbus/test/scoped", iface)
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(scoped.path().as_str(), "/zbus/test/scoped");
+                // Only one interface of a kind per path.
+                assert!(scoped
+                    .serve_scoped("/zbus/test/scoped", Counter(calls.clone()))
+                    .unwrap()
+                    .is_none());
+
+                assert!(scoped.try_handle_next().unwrap().is_none());
+                assert_eq!(calls.get(), 1);
+            }
+
+            // The interface is gone with the guard.
+            assert!(object_server.try_handle_next().unwrap().is_none());
+            assert_eq!(calls.get(), 1);
+            assert_eq!(Rc::strong_count(&calls), 1);
+
+            {
+                let mut scoped = object_server
+                    .serve_scoped("/zbus/test/scoped", Counter(calls.clone()))
+                    .unwrap()
+                    .unwrap();
+                scoped.remove::<Counter, _, _>("/zbus/test/scoped").unwrap();
+                assert!(scoped
+                    .at("/zbus/test/scoped", Counter(calls.clone()))
+                    .unwrap());
+            }
+            // The guard only removes the instance it registered.
+            assert_eq!(Rc::strong_count(&calls), 2);
+            assert!(object_server
+                .remove::<Counter, _, _>("/zbus/test/scoped")
+                .unwrap());
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+        let call = || {
+            conn.call_method(
+                None,
+                "/zbus/test/scoped",
+                Some("org.zbus.Counter"),
+                "Count",
+                &(),
+            )
+        };
+        call().unwrap();
+        match call().unwrap_err() {
+            zbus::Error::MethodError(name, _, _) => {
+                assert_eq!(name, "org.freedesktop.DBus.Error.UnknownObject")
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn serve_dynamic_scoped() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = channel::<()>();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            tx.send(()).unwrap();
+
+            let calls = Rc::new(Cell::new(0));
+            let handler_calls = calls.clone();
+            let iface = DynamicInterfaceBuilder::new("org.zbus.Counter")
+                .method("Count", "", "", move |_| {
+                    handler_calls.set(handler_calls.get() + 1);
+
+                    Ok(RawBody::new(&())?)
+                })
+                .build()
+                .unwrap();
+            let handled = object_server
+                .serve_dynamic_scoped("/zbus/test/scoped", iface, |object_server| {
+                    object_server.try_handle_next().unwrap().is_none()
+                })
+                .unwrap();
+            assert_eq!(handled, Some(true));
+
+            // The interface is gone with the scope, along with its handlers.
+            assert_eq!(Rc::strong_count(&calls), 1);
+            assert!(object_server.try_handle_next().unwrap().is_none());
+            assert_eq!(calls.get(), 1);
+
+            // Only one interface of a name per path.
+            let iface = DynamicInterfaceBuilder::new("org.zbus.Counter")
+                .build()
+                .unwrap();
+            object_server
+                .at_dynamic("/zbus/test/scoped", iface)
+                .unwrap();
+            let iface = DynamicInterfaceBuilder::new("org.zbus.Counter")
+                .build()
+                .unwrap();
+            let served = object_server
+                .serve_dynamic_scoped("/zbus/test/scoped", iface, |_| unreachable!())
+                .unwrap();
+            assert!(served.is_none());
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+        let call = || {
+            conn.call_method(
+                None,
+                "/zbus/test/scoped",
+                Some("org.zbus.Counter"),
+                "Count",
+                &(),
+            )
+        };
+        call().unwrap();
+        match call().unwrap_err() {
+            zbus::Error::MethodError(name, _, _) => {
+                assert_eq!(name, "org.freedesktop.DBus.Error.UnknownObject")
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
+        server_thread.join().unwrap();
+    }
+
+    struct Guard(Rc<Cell<u32>>);
+
+    #[dbus_interface(
+        name = "org.zbus.Guard",
+        proxy(default_path = "/zbus/test/guard", default_service = "org.zbus.Guard")
+    )]
+    impl Guard {
+        fn whoami(&self, #[zbus(caller_credentials)] creds: ConnectionCredentials) -> u32 {
+            self.0.set(self.0.get() + 1);
+
+            creds.process_id.unwrap()
+        }
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn caller_credentials() {
+        let conn = Connection::new_session().unwrap();
+        let mut object_server = ObjectServer::new(&conn);
+        let calls = Rc::new(Cell::new(0));
+        let path = "/zbus/test/guard";
+        object_server.at(path, Guard(calls.clone())).unwrap();
+        let xml = object_server
+            .get_node(&ObjectPath::try_from(path).unwrap())
+            .unwrap()
+            .introspect();
+        assert!(!xml.contains("creds"));
+        fdo::DBusProxy::new(&conn)
+            .unwrap()
+            .request_name(
+                "org.zbus.Guard",
+                fdo::RequestNameFlags::ReplaceExisting.into(),
+            )
+            .unwrap();
+        let mut stream = block_on(conn.inner().stream());
+
+        let client = thread::spawn(move || {
+            let conn = Connection::new_session().unwrap();
+            let proxy = GuardProxy::new(&conn).unwrap();
+            for _ in 0..5 {
+                assert_eq!(proxy.whoami().unwrap(), std::process::id());
+            }
+        });
+        while calls.get() < 5 {
+            object_server.try_handle_next().unwrap();
+        }
+        client.join().unwrap();
+
+        // Only the first call asked the bus for the credentials of the client.
+        let last = conn
+            .call_method(
+                Some("org.freedesktop.DBus"),
+                "/org/freedesktop/DBus",
+                Some("org.freedesktop.DBus"),
+                "GetId",
+                &(),
+            )
+            .unwrap();
+        let last_serial = last.header().unwrap().reply_serial().unwrap();
+        let mut replies = 0;
+        block_on(async {
+            while let Some(msg) = stream.next().await {
+                let msg = msg.unwrap();
+                let header = msg.header().unwrap();
+                if header.message_type().unwrap() != MessageType::MethodReturn {
+                    continue;
+                }
+                if header.reply_serial().unwrap() == last_serial {
+                    break;
+                }
+                replies += 1;
+            }
+        });
+        assert_eq!(replies, 1);
+    }
+
+    struct Sensors;
+
+    #[dbus_interface(name = "org.zbus.Sensors")]
+    impl Sensors {
+        #[dbus_interface(property)]
+        fn name(&self) -> &str {
+            "probe"
+        }
+
+        #[dbus_interface(property(skip_on_error))]
+        fn temperature(&self) -> fdo::Result<f64> {
+            Err(fdo::Error::IOError("sensor unplugged".to_string()))
+        }
+
+        #[dbus_interface(property(skip_on_error))]
+        fn voltage(&self) -> fdo::Result<u32> {
+            Ok(5)
+        }
+    }
+
+    struct StrictSensors;
+
+    #[dbus_interface(name = "org.zbus.StrictSensors")]
+    impl StrictSensors {
+        #[dbus_interface(property)]
+        fn name(&self) -> &str {
+            "probe"
+        }
+
+        #[dbus_interface(property)]
+        fn temperature(&self) -> fdo::Result<f64> {
+            Err(fdo::Error::IOError("sensor unplugged".to_string()))
+        }
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn get_all_errors() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = channel::<()>();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            object_server.at("/zbus/test/sensors", Sensors).unwrap();
+            object_server
+                .at("/zbus/test/sensors", StrictSensors)
+                .unwrap();
+            tx.send(()).unwrap();
+
+            for _ in 0..4 {
+                assert!(object_server.try_handle_next().unwrap().is_none());
+            }
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+        let path = "/zbus/test/sensors";
+        let props_iface = Some("org.freedesktop.DBus.Properties");
+        let get_all = |iface: &str| conn.call_method(None, path, props_iface, "GetAll", &(iface));
+        let get = |iface: &str, property: &str| {
+            conn.call_method(None, path, props_iface, "Get", &(iface, property))
+        };
+        let assert_io_error = |e: zbus::Error| match e {
+            zbus::Error::MethodError(name, Some(detail), _) => {
+                assert_eq!(name, "org.freedesktop.DBus.Error.IOError");
+                assert_eq!(detail, "sensor unplugged");
+            }
+            e => panic!("unexpected error: {}", e),
+        };
+
+        // The failing property is omitted, the others are still returned.
+        let reply = get_all("org.zbus.Sensors").unwrap();
+        let props: HashMap<String, zvariant::OwnedValue> = reply.body().unwrap();
+        assert_eq!(props.len(), 2);
+        assert_eq!(String::try_from(props["Name"].clone()).unwrap(), "probe");
+        assert_eq!(u32::try_from(props["Voltage"].clone()).unwrap(), 5);
+
+        // Getting the property on its own still fails.
+        assert_io_error(get("org.zbus.Sensors", "Temperature").unwrap_err());
+        let reply = get("org.zbus.Sensors", "Voltage").unwrap();
+        let voltage: zvariant::OwnedValue = reply.body().unwrap();
+        assert_eq!(u32::try_from(voltage).unwrap(), 5);
+
+        // Without `skip_on_error`, the whole call fails.
+        assert_io_error(get_all("org.zbus.StrictSensors").unwrap_err());
+
+        // The infallible `get_all` leaves the failing properties out either way.
+        assert_eq!(Sensors.get_all().len(), 2);
+        assert_eq!(StrictSensors.get_all().len(), 1);
+        assert!(StrictSensors.try_get_all().is_err());
+
+        server_thread.join().unwrap();
+    }
+
+    struct Doorbell {
+        rings: u32,
+        volume: u8,
+    }
+
+    #[dbus_interface(
+        name = "org.zbus.Doorbell",
+        proxy(default_path = "/zbus/test/doorbell", vis = "pub(crate)")
+    )]
+    impl Doorbell {
+        fn ring(&mut self, visitor: &str, #[zbus(header)] _hdr: MessageHeader<'_>) -> String {
+            self.rings += 1;
+            self.rang(visitor, self.rings)
+                .expect("Failed to emit signal");
+
+            format!("Welcome {}!", visitor)
+        }
+
+        #[dbus_interface(property)]
+        fn volume(&self) -> u8 {
+            self.volume
+        }
+
+        #[dbus_interface(property)]
+        fn set_volume(&mut self, volume: u8) {
+            self.volume = volume;
+        }
+
+        #[dbus_interface(property, name = "Rings")]
+        fn ring_count(&self) -> u32 {
+            self.rings
+        }
+
+        #[dbus_interface(signal)]
+        fn rang(&self, visitor: &str, rings: u32) -> zbus::Result<()>;
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn interface_proxy() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = channel::<()>();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let doorbell = Doorbell {
+                rings: 0,
+                volume: 3,
+            };
+            object_server.at("/zbus/test/doorbell", doorbell).unwrap();
+            tx.send(()).unwrap();
+
+            for _ in 0..5 {
+                assert!(object_server.try_handle_next().unwrap().is_none());
+            }
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+
+        let proxy = DoorbellProxy::new(&conn).unwrap();
+        assert_eq!(proxy.volume().unwrap(), 3);
+        proxy.set_volume(7).unwrap();
+        assert_eq!(proxy.volume().unwrap(), 7);
+
+        block_on(async {
+            let proxy = AsyncDoorbellProxy::new(conn.inner()).unwrap();
+            let mut rang = proxy.receive_rang().await.unwrap();
+            assert_eq!(proxy.ring("Alice").await.unwrap(), "Welcome Alice!");
+
+            let signal = rang.next().await.unwrap();
+            let args = signal.args().unwrap();
+            assert_eq!(args.visitor, "Alice");
+            assert_eq!(args.rings, 1);
+
+            assert_eq!(proxy.ring_count().await.unwrap(), 1);
+        });
+
+        server_thread.join().unwrap();
+    }
+
+    struct Mailbox {
+        letters: Vec<String>,
+        label: String,
+    }
+
+    #[dbus_interface(
+        name = "org.zbus.Mailbox",
+        proxy(default_path = "/zbus/test/mailbox", vis = "pub(crate)")
+    )]
+    impl Mailbox {
+        fn post(
+            &mut self,
+            letter: &str,
+            #[zbus(header)] hdr: MessageHeader<'_>,
+        ) -> fdo::Result<u32> {
+            // Nothing routes the messages of a peer-to-peer connection, nor sets their sender.
+            if hdr.destination()?.is_some() || hdr.sender()?.is_some() {
+                return Err(fdo::Error::Failed(format!("unexpected header: {:?}", hdr)));
+            }
+            if letter.is_empty() {
+                return Err(fdo::Error::InvalidArgs("empty letter".to_string()));
+            }
+            self.letters.push(letter.to_string());
+            self.posted(letter).expect("Failed to emit signal");
+
+            Ok(self.letters.len() as u32)
+        }
+
+        // Write the letters to `stream`, one per line.
+        fn read_out(&self, stream: Fd) -> fdo::Result<()> {
+            for letter in &self.letters {
+                let line = format!("{}\n", letter);
+                nix::unistd::write(stream.as_raw_fd(), line.as_bytes())
+                    .map_err(|e| fdo::Error::IOError(e.to_string()))?;
+            }
+
+            Ok(())
+        }
+
+        #[dbus_interface(property)]
+        fn label(&self) -> &str {
+            &self.label
+        }
+
+        #[dbus_interface(property)]
+        fn set_label(&mut self, label: &str) {
+            self.label = label.to_string();
+        }
+
+        #[dbus_interface(signal)]
+        fn posted(&self, letter: &str) -> zbus::Result<()>;
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn p2p() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = channel::<()>();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let mailbox = Mailbox {
+                letters: vec![],
+                label: "home".to_string(),
+            };
+            object_server.at("/zbus/test/mailbox", mailbox).unwrap();
+            tx.send(()).unwrap();
+
+            // Serve until the client goes away.
+            loop {
+                match object_server.try_handle_next() {
+                    Ok(None) => (),
+                    Ok(Some(msg)) => panic!("unhandled message: {}", msg),
+                    Err(zbus::Error::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => break,
+                    Err(e) => panic!("unexpected error: {}", e),
+                }
+            }
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+
+        // Proxies don't need a destination, and generated ones leave out their default.
+        let proxy = MailboxProxy::new(&conn).unwrap();
+        assert_eq!(proxy.destination(), None);
+        assert_eq!(proxy.post("hello").unwrap(), 1);
+
+        block_on(async {
+            let conn = conn.inner();
+            let proxy = AsyncMailboxProxy::new(conn).unwrap();
+            let props = fdo::AsyncPropertiesProxy::builder(conn)
+                .path("/zbus/test/mailbox")
+                .unwrap()
+                .build()
+                .unwrap();
+            let mut posted = proxy.receive_posted().await.unwrap();
+            let mut changed = props.receive_properties_changed().await.unwrap();
+
+            // Method calls and signals.
+            assert_eq!(proxy.post("world").await.unwrap(), 2);
+            let signal = posted.next().await.unwrap();
+            assert_eq!(signal.args().unwrap().letter, "world");
+
+            // Properties, with their changes signalled.
+            assert_eq!(proxy.label().await.unwrap(), "home");
+            proxy.set_label("work").await.unwrap();
+            assert_eq!(proxy.label().await.unwrap(), "work");
+            let signal = changed.next().await.unwrap();
+            let args = signal.args().unwrap();
+            assert_eq!(args.interface_name, "org.zbus.Mailbox");
+            assert_eq!(args.changed_properties["Label"], Value::from("work"));
+            let all = props.get_all("org.zbus.Mailbox").await.unwrap();
+            assert_eq!(String::try_from(all["Label"].clone()).unwrap(), "work");
+
+            // File descriptors.
+            let (mut ours, theirs) = UnixStream::pair().unwrap();
+            proxy.read_out(Fd::from(&theirs)).await.unwrap();
+            let mut letters = [0; 12];
+            ours.read_exact(&mut letters).unwrap();
+            assert_eq!(&letters, b"hello\nworld\n");
+
+            // Errors, from the interface and from the object server.
+            match proxy.post("").await.unwrap_err() {
+                zbus::Error::MethodError(name, Some(detail), _) => {
+                    assert_eq!(name, "org.freedesktop.DBus.Error.InvalidArgs");
+                    assert_eq!(detail, "empty letter");
+                }
+                e => panic!("unexpected error: {}", e),
+            }
+            let err = conn
+                .call_method(
+                    None,
+                    "/zbus/test/nowhere",
+                    Some("org.zbus.Mailbox"),
+                    "Post",
+                    &"hi",
+                )
+                .await
+                .unwrap_err();
+            match err {
+                zbus::Error::MethodError(name, _, _) => {
+                    assert_eq!(name, "org.freedesktop.DBus.Error.UnknownObject")
+                }
+                e => panic!("unexpected error: {}", e),
+            }
+
+            // The standard interfaces.
+            let peer = fdo::AsyncPeerProxy::builder(conn)
+                .path("/zbus/test/mailbox")
+                .unwrap()
+                .build()
+                .unwrap();
+            peer.ping().await.unwrap();
+            let introspectable = fdo::AsyncIntrospectableProxy::builder(conn)
+                .path("/zbus/test/mailbox")
+                .unwrap()
+                .build()
+                .unwrap();
+            let xml = introspectable.introspect().await.unwrap();
+            assert!(xml.contains("<interface name=\"org.zbus.Mailbox\">"));
+        });
+
+        drop(proxy);
+        drop(conn);
+        server_thread.join().unwrap();
+    }
+
+    // A range whose bounds must stay in order.
+    struct Range {
+        min: u32,
+        max: u32,
+        label: String,
+    }
+
+    impl Range {
+        fn validate(&self, properties: &HashMap<String, zvariant::OwnedValue>) -> fdo::Result<()> {
+            let bound = |name: &str, current: u32| match properties.get(name) {
+                Some(value) => u32::try_from(value.clone())
+                    .map_err(|_| fdo::Error::InvalidArgs(format!("invalid `{}`", name))),
+                None => Ok(current),
+            };
+            if bound("Min", self.min)? > bound("Max", self.max)? {
+                return Err(fdo::Error::InvalidArgs("`Min` is above `Max`".to_string()));
+            }
+
+            Ok(())
+        }
+    }
+
+    #[dbus_interface(
+        name = "org.zbus.Range",
+        atomic_properties = "validate",
+        proxy(default_path = "/zbus/test/range", vis = "pub(crate)")
+    )]
+    impl Range {
+        #[dbus_interface(property)]
+        fn min(&self) -> u32 {
+            self.min
+        }
+
+        #[dbus_interface(property)]
+        fn set_min(&mut self, min: u32) {
+            self.min = min;
+        }
+
+        #[dbus_interface(property)]
+        fn max(&self) -> u32 {
+            self.max
+        }
+
+        #[dbus_interface(property)]
+        fn set_max(&mut self, max: u32) {
+            self.max = max;
+        }
+
+        #[dbus_interface(property)]
+        fn label(&self) -> &str {
+            &self.label
+        }
+
+        #[dbus_interface(property)]
+        fn set_label(&mut self, label: &str) -> fdo::Result<()> {
+            if label.is_empty() {
+                return Err(fdo::Error::InvalidArgs("empty label".to_string()));
+            }
+            self.label = label.to_string();
+
+            Ok(())
+        }
+
+        #[dbus_interface(property)]
+        fn unit(&self) -> &str {
+            "mm"
+        }
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn properties_transaction() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = channel::<()>();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let range = Range {
+                min: 0,
+                max: 5,
+                label: "depth".to_string(),
+            };
+            object_server.at("/zbus/test/range", range).unwrap();
+            let doorbell = Doorbell {
+                rings: 0,
+                volume: 3,
+            };
+            object_server.at("/zbus/test/doorbell", doorbell).unwrap();
+            tx.send(()).unwrap();
+
+            for _ in 0..15 {
+                assert!(object_server.try_handle_next().unwrap().is_none());
+            }
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+        let proxy = RangeProxy::new(&conn).unwrap();
+        let assert_invalid_args = |e: fdo::Error, expected: &str| match e {
+            fdo::Error::InvalidArgs(e) => assert_eq!(e, expected),
+            e => panic!("unexpected error: {}", e),
+        };
+
+        // Each bound on its own would be out of order with the current other one.
+        proxy
+            .properties_transaction()
+            .set_min(10)
+            .set_max(20)
+            .commit()
+            .unwrap();
+        assert_eq!(proxy.min().unwrap(), 10);
+        assert_eq!(proxy.max().unwrap(), 20);
+
+        // Nothing is set if the validation fails..
+        let e = proxy
+            .properties_transaction()
+            .set_min(30)
+            .commit()
+            .unwrap_err();
+        assert_invalid_args(e, "`Min` is above `Max`");
+        assert_eq!(proxy.min().unwrap(), 10);
+
+        // .. and what was set is rolled back if a setter fails.
+        let e = proxy
+            .properties_transaction()
+            .set_min(1)
+            .set_max(2)
+            .set_label("")
+            .commit()
+            .unwrap_err();
+        assert_invalid_args(e, "empty label");
+        assert_eq!(proxy.min().unwrap(), 10);
+        assert_eq!(proxy.max().unwrap(), 20);
+        assert_eq!(proxy.label().unwrap(), "depth");
+
+        match proxy
+            .inner()
+            .properties_transaction()
+            .atomic(true)
+            .set("Unit", "cm")
+            .commit()
+            .unwrap_err()
+        {
+            fdo::Error::PropertyReadOnly(e) => assert_eq!(e, "Property 'Unit' is read-only"),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        // One `Set` call per property.
+        proxy
+            .properties_transaction()
+            .atomic(false)
+            .set_min(0)
+            .set_label("height")
+            .commit()
+            .unwrap();
+        assert_eq!(proxy.min().unwrap(), 0);
+        assert_eq!(proxy.label().unwrap(), "height");
+
+        // Other interfaces don't support `SetMultiple`.
+        let doorbell = DoorbellProxy::new(&conn).unwrap();
+        match doorbell
+            .properties_transaction()
+            .atomic(true)
+            .set_volume(1)
+            .commit()
+            .unwrap_err()
+        {
+            fdo::Error::NotSupported(_) => (),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        server_thread.join().unwrap();
+    }
+
+    #[derive(Debug, PartialEq, Serialize, DeserializeNewtype, Type, Value, OwnedValue)]
+    #[zvariant(validate = "Percent::validate")]
+    pub(crate) struct Percent(u8);
+
+    impl Percent {
+        fn validate(value: &u8) -> std::result::Result<(), String> {
+            match value {
+                0..=100 => Ok(()),
+                _ => Err(format!("{}% is out of range", value)),
+            }
+        }
+    }
+
+    struct Dimmer {
+        level: Percent,
+    }
+
+    #[dbus_interface(
+        name = "org.zbus.Dimmer",
+        proxy(default_path = "/zbus/test/dimmer", vis = "pub(crate)")
+    )]
+    impl Dimmer {
+        #[dbus_interface(property)]
+        fn level(&self) -> Percent {
+            Percent(self.level.0)
+        }
+
+        #[dbus_interface(property)]
+        fn set_level(&mut self, level: Percent) {
+            self.level = level;
+        }
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn validated_property() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = channel::<()>();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let dimmer = Dimmer { level: Percent(0) };
+            object_server.at("/zbus/test/dimmer", dimmer).unwrap();
+            tx.send(()).unwrap();
+
+            for _ in 0..4 {
+                assert!(object_server.try_handle_next().unwrap().is_none());
+            }
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+
+        let proxy = DimmerProxy::new(&conn).unwrap();
+        proxy.set_level(Percent(42)).unwrap();
+        assert_eq!(proxy.level().unwrap(), Percent(42));
+
+        // The newtype is checked on the service side, whatever the client sends.
+        match proxy.inner().set_property("Level", 101u8).unwrap_err() {
+            fdo::Error::InvalidArgs(e) => assert_eq!(e, "101% is out of range"),
+            e => panic!("unexpected error: {}", e),
+        }
+        assert_eq!(proxy.level().unwrap(), Percent(42));
+
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn signal_emitter() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = channel();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let doorbell = Doorbell {
+                rings: 0,
+                volume: 3,
+            };
+            object_server.at("/zbus/test/doorbell", doorbell).unwrap();
+            assert_eq!(
+                object_server
+                    .signal_emitter::<_, Doorbell>("/zbus/test/nothing")
+                    .unwrap_err(),
+                crate::Error::InterfaceNotFound
+            );
+            assert_eq!(
+                object_server
+                    .signal_emitter::<_, MyIfaceImpl>("/zbus/test/doorbell")
+                    .unwrap_err(),
+                crate::Error::InterfaceNotFound
+            );
+
+            // The emitter outlives the object server.
+            let emitter = object_server
+                .signal_emitter::<_, Doorbell>("/zbus/test/doorbell")
+                .unwrap();
+            tx.send(emitter).unwrap();
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        let emitter = rx.recv().unwrap();
+        server_thread.join().unwrap();
+
+        block_on(async {
+            let proxy = AsyncDoorbellProxy::new(conn.inner()).unwrap();
+            let mut rang = proxy.receive_rang().await.unwrap();
+
+            Doorbell::rang_on(emitter.connection(), emitter.path(), "Bob", 3).unwrap();
+            emitter
+                .emit_signal(None, "org.zbus.Doorbell", "Rang", &("Carol", 4u32))
+                .unwrap();
+
+            for (visitor, rings) in &[("Bob", 3), ("Carol", 4)] {
+                let signal = rang.next().await.unwrap();
+                let args = signal.args().unwrap();
+                assert_eq!(args.visitor, *visitor);
+                assert_eq!(args.rings, *rings);
+            }
+        });
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn emit_signal_to_all() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            for path in &[
+                "/zbus/test/doorbell/2",
+                "/zbus/test/doorbell/1",
+                "/zbus/test",
+            ] {
+                let doorbell = Doorbell {
+                    rings: 0,
+                    volume: 3,
+                };
+                object_server.at(*path, doorbell).unwrap();
+            }
+            object_server
+                .at_dynamic(
+                    "/zbus/test/dynamic",
+                    DynamicInterfaceBuilder::new("org.zbus.Doorbell")
+                        .build()
+                        .unwrap(),
+                )
+                .unwrap();
+            assert!(object_server.paths_of::<MyIfaceImpl>().is_empty());
+            assert_eq!(
+                object_server.paths_of::<Doorbell>(),
+                vec![
+                    OwnedObjectPath::try_from("/zbus/test").unwrap(),
+                    OwnedObjectPath::try_from("/zbus/test/doorbell/1").unwrap(),
+                    OwnedObjectPath::try_from("/zbus/test/doorbell/2").unwrap(),
+                ]
+            );
+
+            object_server
+                .emit_signal_to_all(|doorbell: &Doorbell| doorbell.rang("Bob", doorbell.rings))
+                .unwrap();
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        server_thread.join().unwrap();
+
+        for path in &[
+            "/zbus/test",
+            "/zbus/test/doorbell/1",
+            "/zbus/test/doorbell/2",
+        ] {
+            let msg = conn.receive_message().unwrap();
+            let hdr = msg.header().unwrap();
+            assert_eq!(hdr.member().unwrap(), Some("Rang"));
+            assert_eq!(hdr.path().unwrap().unwrap().as_str(), *path);
+        }
+    }
+
+    struct Worker {
+        pending: Sender<(u32, Responder<u32>)>,
+    }
+
+    #[dbus_interface(name = "org.zbus.Worker", proxy(default_path = "/zbus/test/worker"))]
+    impl Worker {
+        fn double(&self, input: u32, #[zbus(deferred)] responder: Responder<u32>) {
+            self.pending.send((input, responder)).unwrap();
+        }
+
+        fn forget(&self, #[zbus(deferred)] responder: Responder<()>) {
+            drop(responder);
+        }
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn deferred_reply() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = channel::<()>();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let (pending_tx, pending_rx) = channel();
+            let path = "/zbus/test/worker";
+            object_server
+                .at(
+                    path,
+                    Worker {
+                        pending: pending_tx,
+                    },
+                )
+                .unwrap();
+
+            // The responder is neither an input nor the output of the method.
+            let xml = object_server
+                .get_node(&ObjectPath::try_from(path).unwrap())
+                .unwrap()
+                .introspect();
+            assert!(xml.contains(
+                "<method name=\"Double\">\n      \
+                 <arg name=\"input\" type=\"u\" direction=\"in\"/>\n      \
+                 <arg type=\"u\" direction=\"out\"/>\n    \
+                 </method>"
+            ));
+            assert!(!xml.contains("responder"));
+            tx.send(()).unwrap();
+
+            // The method returns without replying, and the reply comes from another thread.
+            assert!(object_server.try_handle_next().unwrap().is_none());
+            let worker = thread::spawn(move || {
+                let (input, responder) = pending_rx.recv().unwrap();
+                responder.ok(input * 2).unwrap();
+            });
+            assert!(object_server.try_handle_next().unwrap().is_none());
+            worker.join().unwrap();
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+
+        let proxy = WorkerProxy::new(&conn).unwrap();
+        assert_eq!(proxy.double(21).unwrap(), 42);
+        match proxy.forget().unwrap_err() {
+            zbus::Error::MethodError(name, _, _) => {
+                assert_eq!(name, "org.freedesktop.DBus.Error.NoReply")
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
+        server_thread.join().unwrap();
+    }
+
+    struct Calculator {
+        done: Rc<Cell<bool>>,
+    }
+
+    #[dbus_interface(name = "org.zbus.Calculator", proxy(default_path = "/zbus/calc"))]
+    impl Calculator {
+        fn add(&self, a: u32, b: u32) -> u32 {
+            a + b
+        }
+
+        fn negate(&self, a: i32) -> i32 {
+            -a
+        }
+
+        fn fail(&self) -> fdo::Result<()> {
+            Err(fdo::Error::AccessDenied("Not allowed".into()))
+        }
+
+        fn quit(&self) {
+            self.done.set(true);
+        }
+    }
+
+    // Forwards the calls it gets to the backend, replying to `Negate` from another thread.
+    struct Gateway {
+        backend: Connection,
+        done: Rc<Cell<bool>>,
+        introspected: Rc<Cell<usize>>,
+    }
+
+    impl Gateway {
+        // The reply to `call`, built from that of the backend.
+        fn forward(backend: &Connection, call: &Message) -> zbus::Result<Message> {
+            let header = call.header()?;
+            let args = call.body_structure()?;
+            let res = backend.call_method_with_values(
+                None,
+                header.path()?.unwrap().as_str(),
+                header.interface()?,
+                header.member()?.unwrap(),
+                args.fields(),
+            );
+
+            match res {
+                Ok(reply) => {
+                    let body = RawBody::from_values(reply.body_structure()?.fields())?;
+
+                    Ok(MessageBuilder::method_return(call)?.build_raw_body(&body)?)
+                }
+                Err(zbus::Error::MethodError(name, detail, _)) => {
+                    Ok(MessageBuilder::error(call, &name)?.build(&detail.unwrap_or_default())?)
+                }
+                Err(e) => Err(e),
+            }
+        }
+    }
+
+    impl Fallback for Gateway {
+        fn call(
+            &mut self,
+            conn: &Connection,
+            msg: &Message,
+            header: &MessageHeader<'_>,
+        ) -> FallbackReply {
+            let member = header.member().unwrap().unwrap();
+            if member == "Negate" {
+                let backend = self.backend.clone();
+                let responder = Responder::<()>::new(conn.clone(), msg.clone());
+                thread::spawn(move || {
+                    let reply = Self::forward(&backend, responder.call()).unwrap();
+                    responder.reply(reply).unwrap();
+                });
+
+                return FallbackReply::Forwarded;
+            }
+            if member == "Quit" {
+                self.done.set(true);
+            }
+
+            match Self::forward(&self.backend, msg) {
+                Ok(reply) => FallbackReply::Reply(reply),
+                Err(e) => FallbackReply::Error(fdo::Error::Failed(e.to_string())),
+            }
+        }
+
+        fn introspect(&mut self, path: &ObjectPath<'_>) -> Option<String> {
+            self.introspected.set(self.introspected.get() + 1);
+            self.backend
+                .call_method_typed(
+                    None,
+                    path.as_str(),
+                    Some("org.freedesktop.DBus.Introspectable"),
+                    "Introspect",
+                    &(),
+                )
+                .ok()
+        }
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn fallback() {
+        let (b0, b1) = UnixStream::pair().unwrap();
+        let (f0, f1) = UnixStream::pair().unwrap();
+        let (tx, rx) = channel::<()>();
+
+        let backend_guid = Guid::generate();
+        let backend_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(b0, &backend_guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let done = Rc::new(Cell::new(false));
+            let calculator = Calculator { done: done.clone() };
+            object_server.at("/zbus/calc", calculator).unwrap();
+
+            while !done.get() {
+                object_server.try_handle_next().unwrap();
+            }
+        });
+
+        let gateway_guid = Guid::generate();
+        let gateway_thread = thread::spawn(move || {
+            let backend = Connection::new_unix_client(b1, false).unwrap();
+            let conn = Connection::new_unix_server(f0, &gateway_guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let done = Rc::new(Cell::new(false));
+            let introspected = Rc::new(Cell::new(0));
+            let gateway = Gateway {
+                backend,
+                done: done.clone(),
+                introspected: introspected.clone(),
+            };
+            assert!(object_server.at_fallback("/zbus/calc", gateway).unwrap());
+            let reject = |_: &Connection, _: &Message, _: &MessageHeader<'_>| {
+                FallbackReply::Error(fdo::Error::AccessDenied("Gateway closed".into()))
+            };
+            assert!(object_server.at_fallback("/zbus/closed", reject).unwrap());
+            assert!(!object_server.at_fallback("/zbus/closed", reject).unwrap());
+            assert!(object_server.remove_fallback("/zbus/closed").unwrap());
+            assert!(!object_server.remove_fallback("/zbus/closed").unwrap());
+            tx.send(()).unwrap();
+
+            while !done.get() {
+                object_server.try_handle_next().unwrap();
+            }
+
+            introspected.get()
+        });
+
+        let conn = Connection::new_unix_client(f1, false).unwrap();
+        rx.recv().unwrap();
+
+        // Replies and errors go through, whether replied to right away or later.
+        let proxy = CalculatorProxy::new(&conn).unwrap();
+        assert_eq!(proxy.add(40, 2).unwrap(), 42);
+        assert_eq!(proxy.negate(42).unwrap(), -42);
+        let is_error = |e: zbus::Error, error_name: &str| match e {
+            zbus::Error::MethodError(name, _, _) => name == error_name,
+            e => panic!("unexpected error: {}", e),
+        };
+        assert!(is_error(
+            proxy.fail().unwrap_err(),
+            "org.freedesktop.DBus.Error.AccessDenied"
+        ));
+        let err = conn
+            .call_method(None, "/zbus/calc", Some("org.zbus.Calculator"), "Nope", &())
+            .unwrap_err();
+        assert!(is_error(err, "org.freedesktop.DBus.Error.UnknownMethod"));
+
+        // The introspection is delegated to the gateway.
+        let introspectable = fdo::IntrospectableProxy::builder(&conn)
+            .path("/zbus/calc")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(introspectable
+            .introspect()
+            .unwrap()
+            .contains("<interface name=\"org.zbus.Calculator\">"));
+
+        // Only the calls under the prefix are forwarded.
+        for path in &["/zbus/calculator", "/zbus/closed"] {
+            let err = conn
+                .call_method(
+                    None,
+                    *path,
+                    Some("org.zbus.Calculator"),
+                    "Add",
+                    &(1u32, 2u32),
+                )
+                .unwrap_err();
+            assert!(is_error(err, "org.freedesktop.DBus.Error.UnknownObject"));
+        }
+
+        proxy.quit().unwrap();
+        backend_thread.join().unwrap();
+        assert_eq!(gateway_thread.join().unwrap(), 1);
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn freeze_thaw() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = channel::<()>();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            object_server.freeze(1);
+            tx.send(()).unwrap();
+
+            // The first call is held, the second one is over the limit and gets an error reply.
+            assert!(object_server.try_handle_next().unwrap().is_none());
+            assert!(object_server.try_handle_next().unwrap().is_none());
+
+            let action = Rc::new(Cell::new(NextAction::Nothing));
+            object_server
+                .at("/zbus/test/frozen", MyIfaceImpl::new(action))
+                .unwrap();
+            object_server.thaw().unwrap();
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+        let mut serials = vec![];
+        for _ in 0..2 {
+            let msg = Message::method(
+                None,
+                None,
+                "/zbus/test/frozen",
+                Some("org.freedesktop.MyIface"),
+                "Ping",
+                &(),
+            )
+            .unwrap();
+            serials.push(conn.send_message(msg).unwrap());
+        }
+
+        let reply = conn.receive_message().unwrap();
+        let header = reply.header().unwrap();
+        assert_eq!(header.reply_serial().unwrap(), Some(serials[1]));
+        assert_eq!(
+            header.error_name().unwrap(),
+            Some("org.freedesktop.DBus.Error.UnknownObject")
+        );
+
+        let reply = conn.receive_message().unwrap();
+        let header = reply.header().unwrap();
+        assert_eq!(header.reply_serial().unwrap(), Some(serials[0]));
+        assert_eq!(header.message_type().unwrap(), MessageType::MethodReturn);
+        assert_eq!(reply.body::<u32>().unwrap(), 1);
+
+        server_thread.join().unwrap();
+    }
+
+    #[repr(u32)]
+    #[derive(enumflags2::BitFlags, Copy, Clone, Debug, PartialEq)]
+    pub enum ChimeOptions {
+        Loud = 0x1,
+        Long = 0x2,
+    }
+
+    struct Chime {
+        options: BitFlags<ChimeOptions>,
+    }
+
+    #[dbus_interface(name = "org.zbus.Chime", proxy(default_path = "/zbus/test/chime"))]
+    impl Chime {
+        fn configure(&mut self, options: BitFlags<ChimeOptions>) -> BitFlags<ChimeOptions> {
+            std::mem::replace(&mut self.options, options)
+        }
+
+        fn configure_known(&mut self, options: TruncatedBitFlags<ChimeOptions>) {
+            self.options = options.into();
+        }
+
+        #[dbus_interface(property)]
+        fn options(&self) -> BitFlags<ChimeOptions> {
+            self.options
+        }
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn bitflags() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = channel::<()>();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let chime = Chime {
+                options: BitFlags::empty(),
+            };
+            object_server.at("/zbus/test/chime", chime).unwrap();
+            tx.send(()).unwrap();
+
+            for _ in 0..5 {
+                assert!(object_server.try_handle_next().unwrap().is_none());
+            }
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+
+        let proxy = ChimeProxy::new(&conn).unwrap();
+        let loud_and_long = ChimeOptions::Loud | ChimeOptions::Long;
+        assert_eq!(proxy.configure(loud_and_long).unwrap(), BitFlags::empty());
+        assert_eq!(proxy.options().unwrap(), loud_and_long);
+
+        // Unknown bits are rejected, unless the method truncates them.
+        let call = |method, bits: u32| {
+            conn.call_method(
+                None,
+                "/zbus/test/chime",
+                Some("org.zbus.Chime"),
+                method,
+                &bits,
+            )
+        };
+        assert!(call("Configure", 0x5).is_err());
+        call("ConfigureKnown", 0x5).unwrap();
+        assert_eq!(proxy.options().unwrap(), ChimeOptions::Loud);
+
+        server_thread.join().unwrap();
+    }
+
+    struct Player {
+        volume: u32,
+        title: String,
+        position: u64,
+        id: u32,
+    }
+
+    #[dbus_interface(name = "org.zbus.Player")]
+    impl Player {
+        #[dbus_interface(property)]
+        fn volume(&self) -> u32 {
+            self.volume
+        }
+
+        #[dbus_interface(property)]
+        fn title(&self) -> &str {
+            &self.title
+        }
+
+        #[dbus_interface(property, emits_changed_signal = "false")]
+        fn position(&self) -> u64 {
+            self.position
+        }
+
+        #[dbus_interface(property, emits_changed_signal = "const")]
+        fn id(&self) -> u32 {
+            self.id
+        }
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn with_mut_tracked() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let player = Player {
+                volume: 5,
+                title: "Prelude".into(),
+                position: 0,
+                id: 1,
+            };
+            let path = "/zbus/test/player";
+            object_server.at(path, player).unwrap();
+
+            let xml = object_server
+                .get_node(&ObjectPath::try_from(path).unwrap())
+                .unwrap()
+                .introspect();
+            assert!(xml.contains(
+                r#"<annotation name="org.freedesktop.DBus.Property.EmitsChangedSignal" value="const"/>"#
+            ));
+
+            object_server
+                .with_mut_tracked(path, |player: &mut Player| {
+                    player.volume = 7;
+                    player.title = "Sonata".into();
+                    player.position = 10;
+                    player.id = 2;
+                    Ok(())
+                })
+                .unwrap();
+            // Nothing tracked changes, so nothing is emitted.
+            object_server
+                .with_mut_tracked(path, |player: &mut Player| {
+                    player.position = 20;
+                    Ok(())
+                })
+                .unwrap();
+            let err = object_server
+                .with_mut_tracked(path, |player: &mut Player| {
+                    player.volume = 8;
+                    Err(zbus::Error::Unsupported)
+                })
+                .unwrap_err();
+            assert!(matches!(err, zbus::Error::Unsupported));
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+
+        let msg = conn.receive_message().unwrap();
+        assert_eq!(msg.to_string(), "Signal PropertiesChanged");
+        let (iface, changed, invalidated): (&str, HashMap<&str, Value<'_>>, Vec<&str>) =
+            msg.body().unwrap();
+        assert_eq!(iface, "org.zbus.Player");
+        assert_eq!(changed.len(), 2);
+        assert_eq!(changed["Volume"], Value::U32(7));
+        assert_eq!(changed["Title"], Value::from("Sonata"));
+        assert!(invalidated.is_empty());
+
+        let msg = conn.receive_message().unwrap();
+        let (_, changed, _): (&str, HashMap<&str, Value<'_>>, Vec<&str>) = msg.body().unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed["Volume"], Value::U32(8));
+
+        server_thread.join().unwrap();
+    }
+
+    fn error_name(err: zbus::Error) -> String {
+        match err {
+            zbus::Error::MethodError(name, _, _) => name,
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn dynamic_interface_builder() {
+        let err = DynamicInterfaceBuilder::new("zbus").build().unwrap_err();
+        assert!(matches!(err, zbus::Error::InvalidName(_)));
+        let err = DynamicInterfaceBuilder::new("org.zbus.Dynamic")
+            .signal("Changed", "s")
+            .signal("Changed", "u")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, zbus::Error::InvalidName(_)));
+        let err = DynamicInterfaceBuilder::new("org.zbus.Dynamic")
+            .property("Pair", "uu", || Ok(0u32.into()))
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, zbus::Error::Variant(_)));
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn dynamic_interface() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let path = "/zbus/test/dynamic";
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let done = Rc::new(Cell::new(false));
+            let level = Rc::new(Cell::new(1u32));
+            let (quit, get_level, set_level) = (done.clone(), level.clone(), level.clone());
+            let iface = DynamicInterfaceBuilder::new("org.zbus.Dynamic")
+                .method("Double", "u", "u", |call| {
+                    let n: u32 = call.body()?;
+
+                    Ok(RawBody::new(&(n * 2))?)
+                })
+                .method("Quit", "", "", move |_| {
+                    quit.set(true);
+
+                    Ok(RawBody::new(&())?)
+                })
+                .property("Name", "s", || Ok(Value::from("dynamic").into()))
+                .property_rw(
+                    "Level",
+                    "u",
+                    move || Ok(get_level.get().into()),
+                    move |value| {
+                        set_level.set(u32::try_from(value).map_err(zbus::Error::from)?);
+
+                        Ok(())
+                    },
+                )
+                .signal("LevelChanged", "u")
+                .build()
+                .unwrap();
+            assert!(object_server.at_dynamic(path, iface).unwrap());
+
+            while !done.get() {
+                object_server.try_handle_next().unwrap();
+            }
+
+            assert!(object_server
+                .remove_dynamic(path, "org.zbus.Dynamic")
+                .unwrap());
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        let iface = Some("org.zbus.Dynamic");
+
+        let reply = conn
+            .call_method(None, path, iface, "Double", &21u32)
+            .unwrap();
+        assert_eq!(reply.body::<u32>().unwrap(), 42);
+        let err = conn
+            .call_method(None, path, iface, "Double", &"21")
+            .unwrap_err();
+        assert_eq!(error_name(err), "org.freedesktop.DBus.Error.InvalidArgs");
+
+        let properties = fdo::PropertiesProxy::builder(&conn)
+            .path(path)
+            .unwrap()
+            .build()
+            .unwrap();
+        let name = properties.get("org.zbus.Dynamic", "Name").unwrap();
+        assert_eq!(*name, Value::from("dynamic"));
+        properties
+            .set("org.zbus.Dynamic", "Level", &Value::from(3u32))
+            .unwrap();
+        let level = properties.get("org.zbus.Dynamic", "Level").unwrap();
+        assert_eq!(*level, Value::U32(3));
+        let err = properties
+            .set("org.zbus.Dynamic", "Name", &Value::from("static"))
+            .unwrap_err();
+        assert!(matches!(err, fdo::Error::PropertyReadOnly(_)));
+        let err = properties
+            .set("org.zbus.Dynamic", "Level", &Value::from("high"))
+            .unwrap_err();
+        assert!(matches!(err, fdo::Error::InvalidArgs(_)));
+        let all = properties.get_all("org.zbus.Dynamic").unwrap();
+        assert_eq!(all.len(), 2);
+
+        let introspectable = fdo::IntrospectableProxy::builder(&conn)
+            .path(path)
+            .unwrap()
+            .build()
+            .unwrap();
+        let xml = introspectable.introspect().unwrap();
+        assert!(xml.contains(r#"<interface name="org.zbus.Dynamic">"#));
+        assert!(xml.contains(r#"<method name="Double">"#));
+        assert!(xml.contains(r#"<signal name="LevelChanged">"#));
+        assert!(xml.contains(r#"<property name="Level" type="u" access="readwrite"/>"#));
+
+        conn.call_method(None, path, iface, "Quit", &()).unwrap();
+        server_thread.join().unwrap();
+    }
+
+    struct Device {
+        vendor: String,
+    }
+
+    #[dbus_interface(name = "org.zbus.Device")]
+    impl Device {
+        fn identify(&self) -> String {
+            format!("{} device", self.vendor)
+        }
+
+        #[dbus_interface(property)]
+        fn vendor(&self) -> &str {
+            &self.vendor
+        }
+    }
+
+    struct Disk {
+        size: u64,
+        quit: bool,
+    }
+
+    #[dbus_interface(name = "org.zbus.Device")]
+    impl Disk {
+        fn grow(&mut self, by: u64) -> u64 {
+            self.size += by;
+
+            self.size
+        }
+
+        fn quit(&mut self) {
+            self.quit = true;
+        }
+
+        #[dbus_interface(property)]
+        fn size(&self) -> u64 {
+            self.size
+        }
+    }
+
+    struct Clash;
+
+    #[dbus_interface(name = "org.zbus.Device")]
+    impl Clash {
+        #[dbus_interface(property)]
+        fn size(&self) -> u64 {
+            0
+        }
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn composite_interface() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let path = "/zbus/test/disk0";
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let vendor = String::from("zbus");
+            object_server.merge_at(path, Device { vendor }).unwrap();
+            let disk = Disk {
+                size: 1,
+                quit: false,
+            };
+            object_server.merge_at(path, disk).unwrap();
+            let err = object_server.merge_at(path, Clash).unwrap_err();
+            assert!(matches!(err, zbus::Error::InvalidName(_)));
+            let disk = Disk {
+                size: 0,
+                quit: false,
+            };
+            let err = object_server.merge_at(path, disk).unwrap_err();
+            assert!(matches!(err, zbus::Error::InvalidName(_)));
+
+            let quit = Cell::new(false);
+            while !quit.get() {
+                object_server.try_handle_next().unwrap();
+                object_server
+                    .with(path, |disk: &Disk| {
+                        quit.set(disk.quit);
+
+                        Ok(())
+                    })
+                    .unwrap();
+            }
+            object_server
+                .with(path, |device: &Device| {
+                    assert_eq!(device.vendor, "zbus");
+
+                    Ok(())
+                })
+                .unwrap();
+
+            // Only the `Disk` part goes away.
+            assert!(!object_server.remove::<Disk, _, _>(path).unwrap());
+            object_server.with(path, |_: &Device| Ok(())).unwrap();
+            let err = object_server.with(path, |_: &Disk| Ok(())).unwrap_err();
+            assert!(matches!(err, zbus::Error::InterfaceNotFound));
+            assert!(object_server.remove::<Device, _, _>(path).unwrap());
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        let iface = Some("org.zbus.Device");
+
+        let reply = conn
+            .call_method(None, path, iface, "Identify", &())
+            .unwrap();
+        assert_eq!(reply.body::<&str>().unwrap(), "zbus device");
+        let reply = conn.call_method(None, path, iface, "Grow", &2u64).unwrap();
+        assert_eq!(reply.body::<u64>().unwrap(), 3);
+
+        let properties = fdo::PropertiesProxy::builder(&conn)
+            .path(path)
+            .unwrap()
+            .build()
+            .unwrap();
+        let all = properties.get_all("org.zbus.Device").unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(*all["Vendor"], Value::from("zbus"));
+        assert_eq!(*all["Size"], Value::U64(3));
+
+        let introspectable = fdo::IntrospectableProxy::builder(&conn)
+            .path(path)
+            .unwrap()
+            .build()
+            .unwrap();
+        let xml = introspectable.introspect().unwrap();
+        assert_eq!(
+            xml.matches(r#"<interface name="org.zbus.Device">"#).count(),
+            1
+        );
+        let device = xml
+            .split(r#"<interface name="org.zbus.Device">"#)
+            .nth(1)
+            .unwrap();
+        let device = device.split("</interface>").next().unwrap();
+        assert!(device.contains(r#"<method name="Identify">"#));
+        assert!(device.contains(r#"<method name="Grow">"#));
+        assert!(device.contains(r#"<property name="Vendor" type="s" access="read"/>"#));
+        assert!(device.contains(r#"<property name="Size" type="t" access="read"/>"#));
+
+        conn.call_method(None, path, iface, "Quit", &()).unwrap();
+        server_thread.join().unwrap();
+    }
+
+    struct FooV1;
+
+    #[dbus_interface(name = "org.zbus.Foo1")]
+    impl FooV1 {
+        fn ping(&self) -> &str {
+            "v1"
+        }
+    }
+
+    struct Foo {
+        quit: bool,
+    }
+
+    #[dbus_interface(name = "org.zbus.Foo2", versions("org.zbus.Foo1"))]
+    impl Foo {
+        fn ping(&self) -> &str {
+            "v2"
+        }
+
+        fn quit(&mut self) {
+            self.quit = true;
+        }
+    }
+
+    #[dbus_proxy(interface = "org.zbus.Foo1")]
+    trait Foo1 {
+        fn ping(&self) -> zbus::Result<String>;
+    }
+
+    #[dbus_proxy(interface = "org.zbus.Foo2")]
+    trait Foo2 {
+        fn ping(&self) -> zbus::Result<String>;
+
+        fn quit(&self) -> zbus::Result<()>;
+    }
+
+    enum AnyFoo {
+        V1(Foo1Proxy<'static>),
+        V2(Foo2Proxy<'static>),
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn interface_versions() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            object_server.at("/zbus/test/v1", FooV1).unwrap();
+            object_server
+                .at("/zbus/test/v2", Foo { quit: false })
+                .unwrap();
+            assert!(object_server
+                .at_versions("/zbus/test/both", Foo { quit: false })
+                .unwrap());
+            // All the names are taken already.
+            assert!(!object_server
+                .at_versions("/zbus/test/both", Foo { quit: false })
+                .unwrap());
+            assert!(!object_server
+                .at_versions("/zbus/test/v1", Foo { quit: false })
+                .unwrap());
+
+            let quit = Cell::new(false);
+            while !quit.get() {
+                object_server.try_handle_next().unwrap();
+                object_server
+                    .with("/zbus/test/both", |foo: &Foo| {
+                        quit.set(foo.quit);
+
+                        Ok(())
+                    })
+                    .unwrap();
+            }
+
+            // The other version goes away along with the interface.
+            assert!(object_server
+                .remove::<Foo, _, _>("/zbus/test/both")
+                .unwrap());
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        let first_available = |path, candidates: &[(&str, fn(zbus::Proxy<'static>) -> AnyFoo)]| {
+            zbus::Proxy::first_available(&conn, "org.zbus.Foo", path, candidates)
+        };
+        let v2_first: &[(&str, fn(zbus::Proxy<'static>) -> AnyFoo)] = &[
+            ("org.zbus.Foo2", |proxy| AnyFoo::V2(proxy.into())),
+            ("org.zbus.Foo1", |proxy| AnyFoo::V1(proxy.into())),
+        ];
+        let v1_first: &[(&str, fn(zbus::Proxy<'static>) -> AnyFoo)] = &[
+            ("org.zbus.Foo1", |proxy| AnyFoo::V1(proxy.into())),
+            ("org.zbus.Foo2", |proxy| AnyFoo::V2(proxy.into())),
+        ];
+
+        match first_available("/zbus/test/v1", v2_first).unwrap() {
+            AnyFoo::V1(foo) => assert_eq!(foo.ping().unwrap(), "v1"),
+            AnyFoo::V2(_) => panic!("only version 1 is served at /zbus/test/v1"),
+        }
+        match first_available("/zbus/test/v2", v1_first).unwrap() {
+            AnyFoo::V2(foo) => assert_eq!(foo.ping().unwrap(), "v2"),
+            AnyFoo::V1(_) => panic!("only version 2 is served at /zbus/test/v2"),
+        }
+        match first_available("/zbus/test/both", v1_first).unwrap() {
+            // Served by the same implementation.
+            AnyFoo::V1(foo) => assert_eq!(foo.ping().unwrap(), "v2"),
+            AnyFoo::V2(_) => panic!("version 1 was preferred"),
+        }
+        let err = first_available(
+            "/zbus/test/both",
+            &[("org.zbus.Foo3", |proxy| AnyFoo::V1(proxy.into()))],
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(err, zbus::Error::InterfaceNotFound));
+
+        let introspectable = fdo::IntrospectableProxy::builder(&conn)
+            .path("/zbus/test/both")
+            .unwrap()
+            .build()
+            .unwrap();
+        let xml = introspectable.introspect().unwrap();
+        assert!(xml.contains(r#"<interface name="org.zbus.Foo1">"#));
+        assert!(xml.contains(r#"<interface name="org.zbus.Foo2">"#));
+
+        match first_available("/zbus/test/both", v2_first).unwrap() {
+            AnyFoo::V2(foo) => foo.quit().unwrap(),
+            AnyFoo::V1(_) => panic!("version 2 was preferred"),
+        }
+        server_thread.join().unwrap();
+    }
+
+    struct UnitManager {
+        quit: bool,
+    }
+
+    #[dbus_interface(name = "org.zbus.Units")]
+    impl UnitManager {
+        #[dbus_interface(out_args("name", "description", "load_state", "pid", "job"))]
+        fn get_unit(&self, name: &str) -> (String, String, String, u32, OwnedObjectPath) {
+            (
+                name.to_string(),
+                format!("The {} unit", name),
+                "loaded".to_string(),
+                42,
+                OwnedObjectPath::try_from("/zbus/test/job/1").unwrap(),
+            )
+        }
+
+        fn quit(&mut self) {
+            self.quit = true;
+        }
+    }
+
+    #[dbus_proxy(interface = "org.zbus.Units")]
+    trait Units {
+        #[dbus_proxy(
+            out_struct = "UnitReply",
+            out_args("name", "Description", "LoadState", "pid", "job")
+        )]
+        fn get_unit(
+            &self,
+            name: &str,
+        ) -> zbus::Result<(String, String, String, u32, OwnedObjectPath)>;
+
+        #[dbus_proxy(name = "GetUnit", out_struct = "UnnamedUnitReply")]
+        fn get_unit_unnamed(
+            &self,
+            name: &str,
+        ) -> zbus::Result<(String, String, String, u32, OwnedObjectPath)>;
+
+        fn quit(&self) -> zbus::Result<()>;
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn out_struct() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            object_server
+                .at("/zbus/test/units", UnitManager { quit: false })
+                .unwrap();
+
+            let quit = Cell::new(false);
+            while !quit.get() {
+                object_server.try_handle_next().unwrap();
+                object_server
+                    .with("/zbus/test/units", |units: &UnitManager| {
+                        quit.set(units.quit);
+
+                        Ok(())
+                    })
+                    .unwrap();
+            }
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        let proxy = UnitsProxy::builder(&conn)
+            .path("/zbus/test/units")
+            .unwrap()
+            .build()
+            .unwrap();
+        let unit: UnitReply = proxy.get_unit("dbus.service").unwrap();
+        assert_eq!(unit.name, "dbus.service");
+        assert_eq!(unit.description, "The dbus.service unit");
+        assert_eq!(unit.load_state, "loaded");
+        assert_eq!(unit.pid, 42);
+        assert_eq!(unit.job.as_str(), "/zbus/test/job/1");
+
+        // Same encoding as the tuple.
+        assert_eq!(<UnitReply as zvariant::Type>::signature(), "(sssuo)",);
+        let unit = block_on(
+            AsyncUnitsProxy::builder(&conn.clone().into())
+                .path("/zbus/test/units")
+                .unwrap()
+                .build()
+                .unwrap()
+                .get_unit_unnamed("zbus.service"),
+        )
+        .unwrap();
+        assert_eq!(unit.arg1, "zbus.service");
+        assert_eq!(unit.arg4, 42);
+
+        proxy.quit().unwrap();
+        server_thread.join().unwrap();
+    }
+
+    #[cfg(feature = "xml")]
+    #[test]
+    fn interface_metadata() {
+        use std::str::FromStr;
+
+        let (conn, _peer) = crate::in_memory_pair().unwrap();
+        let mut object_server = ObjectServer::new(&conn);
+        let action = Rc::new(Cell::new(NextAction::Nothing));
+        object_server
+            .at("/zbus/test/my", MyIfaceImpl::new(action))
+            .unwrap();
+        object_server
+            .at("/zbus/test/my/counter", Counter(Rc::new(Cell::new(0))))
+            .unwrap();
+        let dynamic = DynamicInterfaceBuilder::new("org.zbus.Dynamic")
+            .signal("Changed", "s")
+            .build()
+            .unwrap();
+        object_server
+            .at_dynamic("/zbus/test/dynamic", dynamic)
+            .unwrap();
+
+        // The parents of the objects only have the standard interfaces.
+        let paths = object_server.paths();
+        assert_eq!(
+            paths.iter().map(|p| p.as_str()).collect::<Vec<_>>(),
+            [
+                "/zbus/test/dynamic",
+                "/zbus/test/my",
+                "/zbus/test/my/counter"
+            ],
+        );
+        assert_eq!(
+            object_server.interfaces_at("/zbus/test/my").unwrap(),
+            [
+                "org.freedesktop.DBus.Introspectable",
+                "org.freedesktop.DBus.Peer",
+                "org.freedesktop.DBus.Properties",
+                "org.freedesktop.MyIface",
+            ],
+        );
+        assert!(object_server.interfaces_at("/zbus/test/none").is_err());
+        assert!(object_server
+            .interface_metadata("/zbus/test/my", "org.zbus.Counter")
+            .is_err());
+        assert!(object_server
+            .interface_metadata("/zbus/test/dynamic", "org.zbus.Dynamic")
+            .unwrap()
+            .is_empty());
+
+        let metadata = object_server
+            .interface_metadata("/zbus/test/my", "org.freedesktop.MyIface")
+            .unwrap();
+        assert_eq!(metadata.len(), 1);
+        let metadata = metadata[0];
+        assert_eq!(metadata.name, "org.freedesktop.MyIface");
+
+        // The metadata describes exactly the members of the introspected interface.
+        let xml = object_server
+            .get_node(&ObjectPath::try_from("/zbus/test/my").unwrap())
+            .unwrap()
+            .introspect();
+        let node = crate::xml::Node::from_str(&xml).unwrap();
+        crate::proxy_metadata::validate(Some(metadata), metadata.name, &node).unwrap();
+        let iface = node
+            .interfaces()
+            .into_iter()
+            .find(|i| i.name() == metadata.name)
+            .unwrap();
+        assert_eq!(metadata.methods.len(), iface.methods().len());
+        assert_eq!(metadata.properties.len(), iface.properties().len());
+        assert_eq!(metadata.signals.len(), iface.signals().len());
+        for method in metadata.methods {
+            assert!(method.input.is_some() && method.output.is_some());
+        }
+        for property in metadata.properties {
+            let access = match (property.read, property.write) {
+                (true, true) => "readwrite",
+                (true, false) => "read",
+                _ => "write",
+            };
+            let p = iface.lookup_property(property.name).unwrap();
+            assert_eq!(p.access(), access);
+            assert_eq!(property.signature.unwrap()(), p.signature().unwrap());
+        }
+        let alert_count = &metadata.signals[0];
+        assert_eq!(alert_count.name, "AlertCount");
+        assert_eq!(alert_count.signature.unwrap()(), "(u)");
+        // The header isn't given by the caller.
+        let test_header = metadata
+            .methods
+            .iter()
+            .find(|m| m.name == "TestHeader")
+            .unwrap();
+        assert_eq!(test_header.input.unwrap()(), "");
+    }
 }
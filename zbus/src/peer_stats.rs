@@ -0,0 +1,181 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering::Relaxed},
+        Arc, RwLock,
+    },
+};
+
+use static_assertions::assert_impl_all;
+
+use crate::{Message, MessageHeader, MessageType};
+
+const LOCK_PANIC_MSG: &str = "lock poisoned";
+
+/// The traffic exchanged with a peer of a connection.
+///
+/// See [`ConnectionBuilder::peer_stats`] for how to get these. Only the messages received from
+/// the peer, and the replies and errors sent to it, are accounted for. The byte counts are those
+/// of the whole messages, header included.
+///
+/// [`ConnectionBuilder::peer_stats`]: struct.ConnectionBuilder.html#method.peer_stats
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeerStats {
+    /// The unique name of the peer, or `None` for the messages without a sender, i-e those of
+    /// peer-to-peer connections.
+    pub name: Option<String>,
+    /// The number of method calls received from the peer.
+    pub method_calls: u64,
+    /// The size of the method calls received from the peer.
+    pub method_call_bytes: u64,
+    /// The number of signals received from the peer.
+    pub signals: u64,
+    /// The size of the signals received from the peer.
+    pub signal_bytes: u64,
+    /// The number of method replies sent to the peer.
+    pub replies_sent: u64,
+    /// The size of the method replies sent to the peer.
+    pub reply_bytes_sent: u64,
+    /// The number of errors sent to the peer.
+    pub errors_sent: u64,
+    /// The size of the errors sent to the peer.
+    pub error_bytes_sent: u64,
+}
+
+assert_impl_all!(PeerStats: Send, Sync, Unpin);
+
+// Gives one of the counters.
+type CounterFn = fn(&Counters) -> &AtomicU64;
+
+#[derive(Debug, Default)]
+struct Counters {
+    method_calls: AtomicU64,
+    method_call_bytes: AtomicU64,
+    signals: AtomicU64,
+    signal_bytes: AtomicU64,
+    replies_sent: AtomicU64,
+    reply_bytes_sent: AtomicU64,
+    errors_sent: AtomicU64,
+    error_bytes_sent: AtomicU64,
+}
+
+/// The per-peer accounting of a connection.
+///
+/// Disabled by default, in which case accounting a message is a single atomic load. Once a peer is
+/// known, accounting its messages only takes a read lock of the map, so the receiving and sending
+/// paths don't contend with each other.
+#[derive(Debug, Default)]
+pub(crate) struct PeerStatsTracker {
+    enabled: AtomicBool,
+    // Keyed by unique name, or the empty string for the messages without a sender/destination.
+    peers: RwLock<HashMap<String, Arc<Counters>>>,
+}
+
+impl PeerStatsTracker {
+    pub(crate) fn enable(&self) {
+        self.enabled.store(true, Relaxed);
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.load(Relaxed)
+    }
+
+    /// Account for `msg`, received from its sender.
+    pub(crate) fn received(&self, msg: &Message) {
+        if !self.is_enabled() {
+            return;
+        }
+        let header = match msg.header() {
+            Ok(header) => header,
+            Err(_) => return,
+        };
+        let size = msg.as_bytes().len() as u64;
+        let (count, bytes): (CounterFn, CounterFn) = match header.message_type() {
+            Ok(MessageType::MethodCall) => (|c| &c.method_calls, |c| &c.method_call_bytes),
+            Ok(MessageType::Signal) => {
+                if let Some(name) = vanished_name(msg, &header) {
+                    self.peers.write().expect(LOCK_PANIC_MSG).remove(&name);
+                }
+
+                (|c| &c.signals, |c| &c.signal_bytes)
+            }
+            _ => return,
+        };
+        let sender = header.sender().ok().flatten();
+        self.with_counters(sender, |c| {
+            count(c).fetch_add(1, Relaxed);
+            bytes(c).fetch_add(size, Relaxed);
+        });
+    }
+
+    /// Account for `msg`, sent to its destination.
+    pub(crate) fn sent(&self, msg: &Message) {
+        if !self.is_enabled() {
+            return;
+        }
+        let header = match msg.header() {
+            Ok(header) => header,
+            Err(_) => return,
+        };
+        let size = msg.as_bytes().len() as u64;
+        let (count, bytes): (CounterFn, CounterFn) = match header.message_type() {
+            Ok(MessageType::MethodReturn) => (|c| &c.replies_sent, |c| &c.reply_bytes_sent),
+            Ok(MessageType::Error) => (|c| &c.errors_sent, |c| &c.error_bytes_sent),
+            _ => return,
+        };
+        let destination = header.destination().ok().flatten();
+        self.with_counters(destination, |c| {
+            count(c).fetch_add(1, Relaxed);
+            bytes(c).fetch_add(size, Relaxed);
+        });
+    }
+
+    pub(crate) fn stats(&self) -> Vec<PeerStats> {
+        self.peers
+            .read()
+            .expect(LOCK_PANIC_MSG)
+            .iter()
+            .map(|(name, c)| PeerStats {
+                name: Some(name.clone()).filter(|n| !n.is_empty()),
+                method_calls: c.method_calls.load(Relaxed),
+                method_call_bytes: c.method_call_bytes.load(Relaxed),
+                signals: c.signals.load(Relaxed),
+                signal_bytes: c.signal_bytes.load(Relaxed),
+                replies_sent: c.replies_sent.load(Relaxed),
+                reply_bytes_sent: c.reply_bytes_sent.load(Relaxed),
+                errors_sent: c.errors_sent.load(Relaxed),
+                error_bytes_sent: c.error_bytes_sent.load(Relaxed),
+            })
+            .collect()
+    }
+
+    fn with_counters<F>(&self, name: Option<&str>, f: F)
+    where
+        F: FnOnce(&Counters),
+    {
+        let name = name.unwrap_or("");
+        if let Some(counters) = self.peers.read().expect(LOCK_PANIC_MSG).get(name) {
+            return f(counters);
+        }
+
+        let mut peers = self.peers.write().expect(LOCK_PANIC_MSG);
+        f(peers.entry(name.to_string()).or_default())
+    }
+}
+
+// The unique name that went away, if `msg` is a `NameOwnerChanged` signal of the bus telling so.
+pub(crate) fn vanished_name(msg: &Message, header: &MessageHeader<'_>) -> Option<String> {
+    if header.sender().ok()? != Some("org.freedesktop.DBus")
+        || header.interface().ok()? != Some("org.freedesktop.DBus")
+        || header.member().ok()? != Some("NameOwnerChanged")
+    {
+        return None;
+    }
+
+    let (name, _, new_owner) = msg.body::<(&str, &str, &str)>().ok()?;
+    if name.starts_with(':') && new_owner.is_empty() {
+        Some(name.to_string())
+    } else {
+        None
+    }
+}
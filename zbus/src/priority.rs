@@ -0,0 +1,26 @@
+/// The priority of an outgoing message, relative to the others queued on the same connection.
+///
+/// D-Bus has no notion of priority on the wire, but a connection queues the messages it sends
+/// until the socket accepts them. When that queue is backed up, e.g by bulk transfers, a
+/// high-priority message skips ahead of the normal-priority ones still queued:
+///
+/// * messages of the same priority are always written in the order they were queued in.
+/// * a high-priority message is written after the message being written already, if any, since a
+///   message can't be interrupted once part of it is written, and after the high-priority
+///   messages queued before it.
+///
+/// The order in which the messages are written is the order in which the peer, or the bus, gets
+/// them, but not necessarily the order in which they are processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// Queued after all the messages queued before. This is the default.
+    Normal,
+    /// Queued ahead of all the normal-priority messages not written yet.
+    High,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::Normal
+    }
+}
@@ -1,15 +1,16 @@
 use async_io::block_on;
+use futures_core::future::BoxFuture;
 use static_assertions::assert_impl_all;
 use std::{
     convert::{TryFrom, TryInto},
-    future::ready,
+    future::{ready, Future},
     sync::Arc,
 };
 use zvariant::{ObjectPath, OwnedValue, Value};
 
 use crate::{
     azync::{self, SignalHandlerId},
-    Connection, Error, Message, Result,
+    AsyncDrop, Connection, Error, Message, MessageBuilder, Priority, Result,
 };
 
 use crate::fdo;
@@ -46,15 +47,42 @@ use crate::fdo;
 /// It is recommended to use the [`dbus_proxy`] macro, which provides a more convenient and
 /// type-safe *façade* `Proxy` derived from a Rust trait.
 ///
+/// # Signals during method calls
+///
+/// While a method call or a property access waits for its reply, the signals received for the
+/// handlers registered with [`connect_signal`] are dispatched to them on the calling thread, in the
+/// order of reception. All the signals received before the reply are handled before the call
+/// returns, so there is no need for another thread to keep handling signals in the meantime. The
+/// other messages received meanwhile are returned by the following calls to [`next_signal`].
+///
+/// Signals are not dispatched this way for the calls made from a signal handler, or while another
+/// thread is in [`next_signal`].
+///
+/// # Conversion to and from [`azync::Proxy`]
+///
+/// A `Proxy` wraps an [`azync::Proxy`], which [`inner`] and [`into_inner`] give access to, and
+/// `Proxy::from` wraps back. Both are the same proxy, on the same connection: the handlers
+/// registered with [`connect_signal`] and their signal subscriptions, the signals received but not
+/// handled yet, and the resolved owner of the destination are all kept, in either direction. The
+/// handlers connected on one side are called by [`next_signal`] on the other. See the
+/// [conversion of connections] as well.
+///
 /// ## Current limitations:
 ///
 /// At the moment, `Proxy` doesn't:
 ///
-/// * cache properties
+/// * cache properties, unless enabled with [`ProxyBuilder::cache_properties`]
 /// * track the current name owner
 /// * prevent auto-launching
 ///
 /// [`dbus_proxy`]: attr.dbus_proxy.html
+/// [`ProxyBuilder::cache_properties`]: struct.ProxyBuilder.html#method.cache_properties
+/// [`connect_signal`]: struct.Proxy.html#method.connect_signal
+/// [`next_signal`]: struct.Proxy.html#method.next_signal
+/// [`azync::Proxy`]: azync/struct.Proxy.html
+/// [`inner`]: struct.Proxy.html#method.inner
+/// [`into_inner`]: struct.Proxy.html#method.into_inner
+/// [conversion of connections]: struct.Connection.html#conversion-to-and-from-azyncconnection
 #[derive(Debug)]
 pub struct Proxy<'a> {
     conn: Connection,
@@ -108,13 +136,76 @@ impl<'a> Proxy<'a> {
         Ok(Self { conn, azync: proxy })
     }
 
+    /// Create a proxy for the first of the `candidates` interfaces that the object has.
+    ///
+    /// See [`azync::Proxy::first_available`] for details. The blocking proxies generated by
+    /// [`dbus_proxy`] convert from a `Proxy`:
+    ///
+    /// ```no_run
+    ///# use std::error::Error;
+    /// use zbus::{dbus_proxy, Connection, Proxy};
+    ///
+    /// #[dbus_proxy(interface = "org.example.Foo1")]
+    /// trait Foo1 {
+    ///     fn ping(&self) -> zbus::Result<String>;
+    /// }
+    ///
+    /// #[dbus_proxy(interface = "org.example.Foo2")]
+    /// trait Foo2 {
+    ///     fn ping(&self, count: u32) -> zbus::Result<Vec<String>>;
+    /// }
+    ///
+    /// enum Foo {
+    ///     V1(Foo1Proxy<'static>),
+    ///     V2(Foo2Proxy<'static>),
+    /// }
+    ///
+    /// let conn = Connection::new_session()?;
+    /// let foo = Proxy::first_available(
+    ///     &conn,
+    ///     "org.example.Foo",
+    ///     "/org/example/foo",
+    ///     &[
+    ///         ("org.example.Foo2", |proxy| Foo::V2(proxy.into())),
+    ///         ("org.example.Foo1", |proxy| Foo::V1(proxy.into())),
+    ///     ],
+    /// )?;
+    ///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+    /// ```
+    ///
+    /// [`azync::Proxy::first_available`]: azync/struct.Proxy.html#method.first_available
+    /// [`dbus_proxy`]: attr.dbus_proxy.html
+    pub fn first_available<'p, T, E>(
+        conn: &Connection,
+        destination: &str,
+        path: impl TryInto<ObjectPath<'p>, Error = E>,
+        candidates: &[(&str, fn(Proxy<'static>) -> T)],
+    ) -> Result<T>
+    where
+        E: Into<Error>,
+    {
+        let interfaces: Vec<&str> = candidates.iter().map(|(interface, _)| *interface).collect();
+        let (i, proxy) = block_on(azync::Proxy::first_of(
+            conn.inner(),
+            destination,
+            path,
+            &interfaces,
+        ))?;
+
+        Ok((candidates[i].1)(proxy.into()))
+    }
+
     /// Get a reference to the associated connection.
     pub fn connection(&self) -> &Connection {
         &self.conn
     }
 
     /// Get a reference to the destination service name.
-    pub fn destination(&self) -> &str {
+    ///
+    /// See [`azync::Proxy::destination`] for when it's set.
+    ///
+    /// [`azync::Proxy::destination`]: azync/struct.Proxy.html#method.destination
+    pub fn destination(&self) -> Option<&str> {
         self.azync.destination()
     }
 
@@ -135,6 +226,16 @@ impl<'a> Proxy<'a> {
         block_on(self.azync.introspect())
     }
 
+    /// Check the proxy against the introspection data of the associated object.
+    ///
+    /// See [`azync::Proxy::validate`] for details.
+    ///
+    /// [`azync::Proxy::validate`]: azync/struct.Proxy.html#method.validate
+    #[cfg(feature = "xml")]
+    pub fn validate(&self) -> Result<()> {
+        block_on(self.azync.validate())
+    }
+
     /// Get the property `property_name`.
     ///
     /// Effectively, call the `Get` method of the `org.freedesktop.DBus.Properties` interface.
@@ -142,7 +243,7 @@ impl<'a> Proxy<'a> {
     where
         T: TryFrom<OwnedValue>,
     {
-        block_on(self.azync.get_property(property_name))
+        self.block_on_call(self.azync.get_property(property_name))
     }
 
     /// Set the property `property_name`.
@@ -152,7 +253,37 @@ impl<'a> Proxy<'a> {
     where
         T: Into<Value<'t>>,
     {
-        block_on(self.azync.set_property(property_name, value))
+        self.block_on_call(self.azync.set_property(property_name, value))
+    }
+
+    /// The approximate size in bytes of the property values cached, along with their names.
+    ///
+    /// See [`azync::Proxy::cached_properties_size`] for details.
+    ///
+    /// [`azync::Proxy::cached_properties_size`]: azync/struct.Proxy.html#method.cached_properties_size
+    pub fn cached_properties_size(&self) -> usize {
+        self.azync.cached_properties_size()
+    }
+
+    /// Drop the property values cached, if any.
+    ///
+    /// See [`azync::Proxy::clear_property_cache`] for details.
+    ///
+    /// [`azync::Proxy::clear_property_cache`]: azync/struct.Proxy.html#method.clear_property_cache
+    pub fn clear_property_cache(&self) {
+        self.azync.clear_property_cache()
+    }
+
+    /// Prepare setting several properties, to send the changes together.
+    ///
+    /// See [`azync::PropertiesTransaction`] for how they are sent.
+    ///
+    /// [`azync::PropertiesTransaction`]: azync/struct.PropertiesTransaction.html
+    pub fn properties_transaction(&self) -> PropertiesTransaction<'_> {
+        PropertiesTransaction {
+            proxy: &self.azync,
+            azync: self.azync.properties_transaction(),
+        }
     }
 
     /// Call a method and return the reply.
@@ -166,7 +297,28 @@ impl<'a> Proxy<'a> {
     where
         B: serde::ser::Serialize + zvariant::Type,
     {
-        block_on(self.azync.call_method(method_name, body))
+        self.block_on_call(self.azync.call_method(method_name, body))
+    }
+
+    /// Prepare a call of the method `method_name`, to customize its message before sending it.
+    ///
+    /// See [`azync::Proxy::call_builder`] for details.
+    ///
+    /// [`azync::Proxy::call_builder`]: azync/struct.Proxy.html#method.call_builder
+    pub fn call_builder<'p>(&'p self, method_name: &'p str) -> MethodCallBuilder<'p> {
+        MethodCallBuilder {
+            proxy: &self.azync,
+            azync: self.azync.call_builder(method_name),
+        }
+    }
+
+    /// Call a method with arguments of types only known at runtime, and return the reply.
+    ///
+    /// See [`azync::Proxy::call_with_values`] for details.
+    ///
+    /// [`azync::Proxy::call_with_values`]: azync/struct.Proxy.html#method.call_with_values
+    pub fn call_with_values(&self, method_name: &str, args: &[Value<'_>]) -> Result<Arc<Message>> {
+        self.block_on_call(self.azync.call_with_values(method_name, args))
     }
 
     /// Call a method and return the reply body.
@@ -179,7 +331,36 @@ impl<'a> Proxy<'a> {
         B: serde::ser::Serialize + zvariant::Type,
         R: serde::de::DeserializeOwned + zvariant::Type,
     {
-        block_on(self.azync.call(method_name, body))
+        self.block_on_call(self.azync.call(method_name, body))
+    }
+
+    /// Call an idempotent method and return the reply.
+    ///
+    /// Same as [`call_method`], except that the call is retried according to the proxy's
+    /// [`RetryPolicy`], if any. Only use this for methods that can safely be called more than once.
+    ///
+    /// [`call_method`]: struct.Proxy.html#method.call_method
+    /// [`RetryPolicy`]: struct.RetryPolicy.html
+    pub fn call_method_idempotent<B>(&self, method_name: &str, body: &B) -> Result<Arc<Message>>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        self.block_on_call(self.azync.call_method_idempotent(method_name, body))
+    }
+
+    /// Call an idempotent method and return the reply body.
+    ///
+    /// Same as [`call`], except that the call is retried according to the proxy's
+    /// [`RetryPolicy`], if any.
+    ///
+    /// [`call`]: struct.Proxy.html#method.call
+    /// [`RetryPolicy`]: struct.RetryPolicy.html
+    pub fn call_idempotent<B, R>(&self, method_name: &str, body: &B) -> Result<R>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+        R: serde::de::DeserializeOwned + zvariant::Type,
+    {
+        self.block_on_call(self.azync.call_idempotent(method_name, body))
     }
 
     /// Register a handler for signal named `signal_name`.
@@ -251,6 +432,11 @@ impl<'a> Proxy<'a> {
         block_on(self.azync.handle_signal(msg))
     }
 
+    // Wait for a call, handling the signals received meanwhile.
+    fn block_on_call<F: Future>(&self, call: F) -> F::Output {
+        block_on(self.azync.dispatch_signals_during(call))
+    }
+
     /// Get a reference to the underlying async Proxy.
     pub fn inner(&self) -> &azync::Proxy<'a> {
         &self.azync
@@ -281,11 +467,129 @@ impl<'a> From<azync::Proxy<'a>> for Proxy<'a> {
     }
 }
 
+/// A method call being prepared, created by [`Proxy::call_builder`].
+///
+/// This is the blocking sibling of [`azync::MethodCallBuilder`].
+///
+/// [`Proxy::call_builder`]: struct.Proxy.html#method.call_builder
+/// [`azync::MethodCallBuilder`]: azync/struct.MethodCallBuilder.html
+#[derive(Debug)]
+pub struct MethodCallBuilder<'p> {
+    proxy: &'p azync::Proxy<'p>,
+    azync: azync::MethodCallBuilder<'p>,
+}
+
+assert_impl_all!(MethodCallBuilder<'_>: Send, Sync, Unpin);
+
+impl<'p> MethodCallBuilder<'p> {
+    /// Modify the message of the call through its builder.
+    ///
+    /// See [`azync::MethodCallBuilder::modify`] for details.
+    ///
+    /// [`azync::MethodCallBuilder::modify`]: azync/struct.MethodCallBuilder.html#method.modify
+    pub fn modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(MessageBuilder<'p>) -> MessageBuilder<'p>,
+    {
+        self.azync = self.azync.modify(f);
+        self
+    }
+
+    /// Set the priority of the call, relative to the other messages sent on the connection.
+    ///
+    /// See [`azync::MethodCallBuilder::priority`] for details.
+    ///
+    /// [`azync::MethodCallBuilder::priority`]: azync/struct.MethodCallBuilder.html#method.priority
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.azync = self.azync.priority(priority);
+        self
+    }
+
+    /// Build the message with `body`, send it and return the reply.
+    pub fn send<B>(self, body: &B) -> Result<Arc<Message>>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+    {
+        block_on(self.proxy.dispatch_signals_during(self.azync.send(body)))
+    }
+
+    /// Build the message with `body`, send it and return the reply body.
+    pub fn call<B, R>(self, body: &B) -> Result<R>
+    where
+        B: serde::ser::Serialize + zvariant::Type,
+        R: serde::de::DeserializeOwned + zvariant::Type,
+    {
+        block_on(self.proxy.dispatch_signals_during(self.azync.call(body)))
+    }
+}
+
+/// Property changes being collected, created by [`Proxy::properties_transaction`].
+///
+/// This is the blocking sibling of [`azync::PropertiesTransaction`].
+///
+/// [`Proxy::properties_transaction`]: struct.Proxy.html#method.properties_transaction
+/// [`azync::PropertiesTransaction`]: azync/struct.PropertiesTransaction.html
+#[derive(Debug)]
+pub struct PropertiesTransaction<'p> {
+    proxy: &'p azync::Proxy<'p>,
+    azync: azync::PropertiesTransaction<'p>,
+}
+
+assert_impl_all!(PropertiesTransaction<'_>: Send, Sync, Unpin);
+
+impl<'p> PropertiesTransaction<'p> {
+    /// Set the property `property_name` on commit. If it was set already, the value is replaced.
+    pub fn set<'t, T: 't>(mut self, property_name: &str, value: T) -> Self
+    where
+        T: Into<Value<'t>>,
+    {
+        self.azync = self.azync.set(property_name, value);
+        self
+    }
+
+    /// Whether to set the properties with a single `SetMultiple` call, instead of a `Set` call per
+    /// property.
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.azync = self.azync.atomic(atomic);
+        self
+    }
+
+    /// Send the changes, returning the first error if any.
+    pub fn commit(self) -> fdo::Result<()> {
+        block_on(self.proxy.dispatch_signals_during(self.azync.commit()))
+    }
+}
+
+/// Disconnects all the signal handlers of the proxy, for dropping it from async code without
+/// blocking.
+impl<'a> AsyncDrop for Proxy<'a> {
+    fn async_drop<'d>(self) -> BoxFuture<'d, Result<()>>
+    where
+        Self: 'd,
+    {
+        self.azync.async_drop()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        dbus_interface, dbus_proxy, EndianSig, Guid, MessageError, MessageField, MessageFlags,
+        ObjectServer, RetryPolicy,
+    };
     use ntest::timeout;
-    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::{
+        cell::Cell,
+        os::unix::net::UnixStream,
+        rc::Rc,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Mutex,
+        },
+        thread,
+        time::Duration,
+    };
     use test_env_log::test;
 
     #[test]
@@ -346,4 +650,350 @@ mod tests {
             }
         }
     }
+
+    struct StartingService {
+        calls: Rc<Cell<u32>>,
+        done: Rc<Cell<bool>>,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.zbus.Starting")]
+    impl StartingService {
+        // Fails until the 4th call.
+        fn version(&self) -> fdo::Result<String> {
+            self.calls.set(self.calls.get() + 1);
+            if self.calls.get() < 4 {
+                return Err(fdo::Error::NoReply("still starting".into()));
+            }
+
+            Ok("1.0".into())
+        }
+
+        fn denied(&self) -> fdo::Result<()> {
+            self.calls.set(self.calls.get() + 1);
+
+            Err(fdo::Error::AccessDenied("nope".into()))
+        }
+
+        fn quit(&self) {
+            self.done.set(true);
+        }
+    }
+
+    #[dbus_proxy(
+        interface = "org.freedesktop.zbus.Starting",
+        default_service = "org.freedesktop.zbus.Starting",
+        default_path = "/org/freedesktop/zbus/Starting"
+    )]
+    trait Starting {
+        #[dbus_proxy(idempotent)]
+        fn version(&self) -> crate::Result<String>;
+
+        #[dbus_proxy(name = "Version")]
+        fn version_once(&self) -> crate::Result<String>;
+
+        #[dbus_proxy(idempotent)]
+        fn denied(&self) -> crate::Result<()>;
+
+        fn quit(&self) -> crate::Result<()>;
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn retry_policy() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let calls = Rc::new(Cell::new(0));
+            let done = Rc::new(Cell::new(false));
+            let iface = StartingService {
+                calls: calls.clone(),
+                done: done.clone(),
+            };
+            object_server
+                .at("/org/freedesktop/zbus/Starting", iface)
+                .unwrap();
+            tx.send(()).unwrap();
+
+            while !done.get() {
+                object_server.try_handle_next().unwrap();
+            }
+
+            calls.get()
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+        let proxy = StartingProxy::builder(&conn)
+            .retry_policy(RetryPolicy::new(3).backoff(Duration::from_millis(1)))
+            .build()
+            .unwrap();
+        let is_error = |e: Error, name: &str| matches!(e, Error::MethodError(n, _, _) if n == name);
+
+        // Not idempotent, so not retried.
+        let e = proxy.version_once().unwrap_err();
+        assert!(is_error(e, "org.freedesktop.DBus.Error.NoReply"));
+        // Fails twice more before succeeding on the last attempt.
+        assert_eq!(proxy.version().unwrap(), "1.0");
+        // Not an error to retry on.
+        let e = proxy.denied().unwrap_err();
+        assert!(is_error(e, "org.freedesktop.DBus.Error.AccessDenied"));
+
+        // The whole call gives up once the next retry would be after the deadline.
+        let proxy = StartingProxy::builder(&conn)
+            .retry_policy(
+                RetryPolicy::new(10)
+                    .backoff(Duration::from_secs(10))
+                    .retry_on(["org.freedesktop.DBus.Error.AccessDenied"])
+                    .deadline(Duration::from_millis(500)),
+            )
+            .build()
+            .unwrap();
+        let e = proxy.denied().unwrap_err();
+        assert!(is_error(e, "org.freedesktop.DBus.Error.AccessDenied"));
+
+        proxy.quit().unwrap();
+        assert_eq!(server_thread.join().unwrap(), 6);
+    }
+
+    struct WorkerService {
+        done: Rc<Cell<bool>>,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.zbus.Worker")]
+    impl WorkerService {
+        fn work(&self, steps: u32) -> fdo::Result<u32> {
+            for step in 1..=steps {
+                self.progress(step)?;
+            }
+
+            Ok(steps)
+        }
+
+        fn quit(&self) {
+            self.done.set(true);
+        }
+
+        #[dbus_interface(signal)]
+        fn progress(&self, step: u32) -> crate::Result<()>;
+    }
+
+    #[dbus_proxy(
+        interface = "org.freedesktop.zbus.Worker",
+        default_service = "org.freedesktop.zbus.Worker",
+        default_path = "/org/freedesktop/zbus/Worker"
+    )]
+    trait Worker {
+        fn work(&self, steps: u32) -> crate::Result<u32>;
+
+        fn quit(&self) -> crate::Result<()>;
+
+        #[dbus_proxy(signal)]
+        fn progress(&self, step: u32) -> crate::Result<()>;
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn signals_during_call() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let done = Rc::new(Cell::new(false));
+            let iface = WorkerService { done: done.clone() };
+            object_server
+                .at("/org/freedesktop/zbus/Worker", iface)
+                .unwrap();
+            tx.send(()).unwrap();
+
+            while !done.get() {
+                object_server.try_handle_next().unwrap();
+            }
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+        let proxy = WorkerProxy::new(&conn).unwrap();
+        let steps = Arc::new(Mutex::new(vec![]));
+        {
+            let steps = steps.clone();
+            proxy
+                .connect_progress(move |step| {
+                    steps.lock().unwrap().push(step);
+
+                    Ok(())
+                })
+                .unwrap();
+        }
+
+        // The signals are emitted while the call is handled, and so before the reply. All of them
+        // must be handled by the time the call returns, without having to call `next_signal`.
+        assert_eq!(proxy.work(3).unwrap(), 3);
+        assert_eq!(*steps.lock().unwrap(), [1, 2, 3]);
+
+        // The messages no handler was found for, like the reply, are left for `next_signal`.
+        let reply = proxy.next_signal().unwrap().unwrap();
+        assert_eq!(
+            reply.primary_header().msg_type(),
+            crate::MessageType::MethodReturn
+        );
+
+        proxy.quit().unwrap();
+        server_thread.join().unwrap();
+    }
+    struct NewerService {
+        done: Rc<Cell<bool>>,
+    }
+
+    // A newer version of the service, with more arguments than `Older` knows of.
+    #[dbus_interface(name = "org.freedesktop.zbus.Versioned")]
+    impl NewerService {
+        fn status(&self) -> fdo::Result<(String, u32, Vec<String>)> {
+            self.changed("up", 2, &["eth0"])?;
+
+            Ok(("up".into(), 2, vec!["eth0".into()]))
+        }
+
+        fn quit(&self) {
+            self.done.set(true);
+        }
+
+        #[dbus_interface(signal)]
+        fn changed(&self, state: &str, level: u32, devices: &[&str]) -> crate::Result<()>;
+    }
+
+    #[dbus_proxy(
+        interface = "org.freedesktop.zbus.Versioned",
+        default_service = "org.freedesktop.zbus.Versioned",
+        default_path = "/org/freedesktop/zbus/Versioned"
+    )]
+    trait Older {
+        fn status(&self) -> crate::Result<(String, u32)>;
+
+        #[dbus_proxy(name = "Status", strict_signature)]
+        fn strict_status(&self) -> crate::Result<(String, u32)>;
+
+        #[dbus_proxy(name = "Status")]
+        fn changed_status(&self) -> crate::Result<(String, String)>;
+
+        fn quit(&self) -> crate::Result<()>;
+
+        #[dbus_proxy(signal)]
+        fn changed(&self, state: &str) -> crate::Result<()>;
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn appended_args() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let mut object_server = ObjectServer::new(&conn);
+            let done = Rc::new(Cell::new(false));
+            let iface = NewerService { done: done.clone() };
+            object_server
+                .at("/org/freedesktop/zbus/Versioned", iface)
+                .unwrap();
+            tx.send(()).unwrap();
+
+            while !done.get() {
+                object_server.try_handle_next().unwrap();
+            }
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        rx.recv().unwrap();
+        let proxy = OlderProxy::new(&conn).unwrap();
+        let states = Arc::new(Mutex::new(vec![]));
+        {
+            let states = states.clone();
+            proxy
+                .connect_changed(move |state| {
+                    states.lock().unwrap().push(state.to_string());
+
+                    Ok(())
+                })
+                .unwrap();
+        }
+
+        // The appended arguments of the reply and of the signal are ignored.
+        assert_eq!(proxy.status().unwrap(), ("up".to_string(), 2));
+        assert_eq!(*states.lock().unwrap(), ["up"]);
+
+        // Unless the exact signature is required, and changed arguments are always an error. The
+        // error tells which call it's about, and what was received.
+        assert_eq!(
+            proxy.strict_status().unwrap_err().to_string(),
+            "Invalid reply to `org.freedesktop.zbus.Versioned.Status` from \
+             `org.freedesktop.zbus.Versioned`: expected signature `su`, received `suas` \
+             (unmatched body signature)"
+        );
+        match proxy.changed_status().unwrap_err() {
+            Error::ReplyBody(e) => {
+                assert_eq!(e.member(), "Status");
+                assert_eq!(e.expected_signature(), "ss");
+                assert_eq!(e.received_signature(), "suas");
+                assert_eq!(e.error(), &MessageError::UnmatchedBodySignature);
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
+        proxy.quit().unwrap();
+        server_thread.join().unwrap();
+    }
+    #[test]
+    #[timeout(2000)]
+    fn call_builder() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+
+        let server_thread = thread::spawn(move || {
+            let conn = Connection::new_unix_server(p0, &guid).unwrap();
+            let msg = conn.receive_message().unwrap();
+            conn.reply(&msg, &"pong").unwrap();
+
+            msg
+        });
+
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        let proxy = Proxy::new(&conn, "org.zbus.Peer", "/org/zbus/Peer", "org.zbus.Peer").unwrap();
+        let reply: String = proxy
+            .call_builder("Ping")
+            .modify(|b| {
+                b.flags(MessageFlags::NoAutoStart.into())
+                    .field(MessageField::Unknown(100, "correlation-42".into()))
+                    .endian_sig(EndianSig::Big)
+            })
+            .modify(|b| b.interface("org.zbus.Monitored"))
+            .call(&("ping", 1u32))
+            .unwrap();
+        assert_eq!(reply, "pong");
+
+        // The modifications made it to the other side.
+        let msg = server_thread.join().unwrap();
+        let primary = msg.primary_header();
+        assert_eq!(primary.endian_sig(), EndianSig::Big);
+        assert!(primary.flags() == MessageFlags::NoAutoStart);
+        let header = msg.header().unwrap();
+        assert_eq!(header.destination().unwrap(), Some("org.zbus.Peer"));
+        assert_eq!(header.interface().unwrap(), Some("org.zbus.Monitored"));
+        assert_eq!(header.member().unwrap(), Some("Ping"));
+        let fields = msg.fields().unwrap();
+        assert_eq!(
+            fields.get_field_by_code(100),
+            Some(&MessageField::Unknown(100, "correlation-42".into()))
+        );
+        // The interface was replaced, not added a second time.
+        assert_eq!(fields.iter().filter(|f| f.raw_code() == 2).count(), 1);
+        assert_eq!(msg.body::<(&str, u32)>().unwrap(), ("ping", 1));
+    }
 }
@@ -4,7 +4,7 @@ use async_io::block_on;
 use static_assertions::assert_impl_all;
 use zvariant::ObjectPath;
 
-use crate::{azync, Error, Result};
+use crate::{azync, Error, InterfaceMetadata, Result, RetryPolicy};
 
 /// Builder for proxies.
 #[derive(Debug)]
@@ -13,6 +13,12 @@ pub struct ProxyBuilder<'a, T = ()> {
     destination: Option<Cow<'a, str>>,
     path: Option<ObjectPath<'a>>,
     interface: Option<Cow<'a, str>>,
+    retry_policy: Option<RetryPolicy>,
+    metadata: Option<&'static InterfaceMetadata>,
+    cache_properties: bool,
+    max_cached_property_size: usize,
+    #[cfg(feature = "xml")]
+    validate_on_build: bool,
     proxy_type: PhantomData<T>,
 }
 
@@ -23,6 +29,12 @@ impl<'a, T> Clone for ProxyBuilder<'a, T> {
             destination: self.destination.clone(),
             path: self.path.clone(),
             interface: self.interface.clone(),
+            retry_policy: self.retry_policy.clone(),
+            metadata: self.metadata,
+            cache_properties: self.cache_properties,
+            max_cached_property_size: self.max_cached_property_size,
+            #[cfg(feature = "xml")]
+            validate_on_build: self.validate_on_build,
             proxy_type: PhantomData,
         }
     }
@@ -41,6 +53,12 @@ impl<'a, T> ProxyBuilder<'a, T> {
             destination: None,
             path: None,
             interface: None,
+            retry_policy: None,
+            metadata: None,
+            cache_properties: false,
+            max_cached_property_size: usize::MAX,
+            #[cfg(feature = "xml")]
+            validate_on_build: false,
             proxy_type: PhantomData,
         }
     }
@@ -48,11 +66,20 @@ impl<'a, T> ProxyBuilder<'a, T> {
 
 impl<'a, T> ProxyBuilder<'a, T> {
     /// Set the proxy destination address.
+    ///
+    /// This is required on bus connections. On peer-to-peer connections, messages go to the peer
+    /// whatever their destination, so the proxy has none unless set explicitly.
     pub fn destination<D: Into<Cow<'a, str>>>(mut self, destination: D) -> Self {
         self.destination = Some(destination.into());
         self
     }
 
+    // Set the destination to that of another proxy, e.g for a proxy of a standard interface.
+    pub(crate) fn optional_destination(mut self, destination: Option<&'a str>) -> Self {
+        self.destination = destination.map(Cow::from);
+        self
+    }
+
     /// Set the proxy path.
     pub fn path<E, P: TryInto<ObjectPath<'a>, Error = E>>(mut self, path: P) -> Result<Self>
     where
@@ -68,11 +95,67 @@ impl<'a, T> ProxyBuilder<'a, T> {
         self
     }
 
+    /// Set the policy for retrying failed calls to idempotent methods.
+    ///
+    /// See [`RetryPolicy`] for details. By default, calls are never retried.
+    ///
+    /// [`RetryPolicy`]: struct.RetryPolicy.html
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Cache the values of the properties of the interface.
+    ///
+    /// The value of a property is cached on its first get, and returned by the next ones without
+    /// calling the object. The cached values are kept up to date with the `PropertiesChanged`
+    /// signals of the object, from a task run by the [executor of the connection], so this is only
+    /// for interfaces signaling all the changes of their properties. A property set through the
+    /// proxy is read from the object again on its next get.
+    ///
+    /// See [`max_cached_property_size`] to leave out the biggest values, and
+    /// [`Proxy::cached_properties_size`] and [`Proxy::clear_property_cache`] to keep the memory
+    /// they take in check. This is disabled by default.
+    ///
+    /// [executor of the connection]: azync/struct.Connection.html#method.executor
+    /// [`max_cached_property_size`]: struct.ProxyBuilder.html#method.max_cached_property_size
+    /// [`Proxy::cached_properties_size`]: struct.Proxy.html#method.cached_properties_size
+    /// [`Proxy::clear_property_cache`]: struct.Proxy.html#method.clear_property_cache
+    pub fn cache_properties(mut self, cache: bool) -> Self {
+        self.cache_properties = cache;
+        self
+    }
+
+    /// Don't cache the property values bigger than `max_size` bytes.
+    ///
+    /// The size of a value is its [`Value::serialized_size_hint`]. The bigger values are read from
+    /// the object on every get, and a value growing bigger is dropped from the cache. Only relevant
+    /// if the properties are cached, see [`cache_properties`]. There is no limit by default.
+    ///
+    /// [`Value::serialized_size_hint`]: https://docs.rs/zvariant/2.7.0/zvariant/enum.Value.html#method.serialized_size_hint
+    /// [`cache_properties`]: struct.ProxyBuilder.html#method.cache_properties
+    pub fn max_cached_property_size(mut self, max_size: usize) -> Self {
+        self.max_cached_property_size = max_size;
+        self
+    }
+
+    /// Validate the proxy against the introspection data of its object when building it.
+    ///
+    /// The proxy is built only if [`Proxy::validate`] succeeds. This is disabled by default.
+    ///
+    /// [`Proxy::validate`]: struct.Proxy.html#method.validate
+    #[cfg(feature = "xml")]
+    pub fn validate_on_build(mut self, validate: bool) -> Self {
+        self.validate_on_build = validate;
+        self
+    }
+
     /// Build a proxy from the builder.
     ///
     /// # Panics
     ///
-    /// Panics if the builder is lacking the necessary details to build a proxy.
+    /// Panics if the builder is lacking the necessary details to build a proxy, i-e the path, the
+    /// interface, and the destination on bus connections.
     pub fn build(self) -> Result<T>
     where
         T: From<azync::Proxy<'a>>,
@@ -84,20 +167,46 @@ impl<'a, T> ProxyBuilder<'a, T> {
     ///
     /// # Panics
     ///
-    /// Panics if the builder is lacking the necessary details to build a proxy.
+    /// Panics if the builder is lacking the necessary details to build a proxy, i-e the path, the
+    /// interface, and the destination on bus connections.
     pub async fn build_async(self) -> Result<T>
     where
         T: From<azync::Proxy<'a>>,
     {
         let conn = self.conn;
-        let destination = self.destination.expect("missing `destination`");
+        let destination = match self.destination {
+            None if conn.is_bus() => panic!("missing `destination`"),
+            destination => destination,
+        };
         let path = self.path.expect("missing `path`");
         let interface = self.interface.expect("missing `interface`");
+        let property_cache = if self.cache_properties {
+            let cache = azync::PropertyCache::new(
+                &conn,
+                destination.as_deref(),
+                &path,
+                &interface,
+                self.max_cached_property_size,
+            )
+            .await?;
 
-        Ok(azync::Proxy {
-            inner: Arc::new(azync::ProxyInner::new(conn, destination, path, interface)),
+            Some(cache)
+        } else {
+            None
+        };
+        let mut inner = azync::ProxyInner::new(conn, destination, path, interface);
+        inner.retry_policy = self.retry_policy;
+        inner.metadata = self.metadata;
+        inner.property_cache = property_cache;
+        let proxy = azync::Proxy {
+            inner: Arc::new(inner),
+        };
+        #[cfg(feature = "xml")]
+        if self.validate_on_build {
+            proxy.validate().await?;
         }
-        .into())
+
+        Ok(proxy.into())
     }
 }
 
@@ -106,15 +215,30 @@ where
     T: ProxyDefault,
 {
     /// Create a new [`ProxyBuilder`] for the given connection.
+    ///
+    /// The default destination is only set on bus connections.
     pub fn new<C>(conn: &C) -> Self
     where
         C: Clone + Into<azync::Connection>,
     {
+        let conn: azync::Connection = conn.clone().into();
+        let destination = if conn.is_bus() {
+            Some(T::DESTINATION.into())
+        } else {
+            None
+        };
+
         Self {
-            conn: conn.clone().into(),
-            destination: Some(T::DESTINATION.into()),
+            conn,
+            destination,
             path: Some(T::PATH.try_into().expect("invalid default path")),
             interface: Some(T::INTERFACE.into()),
+            retry_policy: None,
+            metadata: T::METADATA,
+            cache_properties: false,
+            max_cached_property_size: usize::MAX,
+            #[cfg(feature = "xml")]
+            validate_on_build: false,
             proxy_type: PhantomData,
         }
     }
@@ -130,6 +254,8 @@ pub trait ProxyDefault {
     const INTERFACE: &'static str;
     const DESTINATION: &'static str;
     const PATH: &'static str;
+    /// The description of the interface, used to validate the proxy.
+    const METADATA: Option<&'static InterfaceMetadata> = None;
 }
 
 #[cfg(test)]
@@ -152,7 +278,7 @@ mod tests {
             Cow::Borrowed(_)
         ));
         let proxy = builder.build().unwrap();
-        assert!(matches!(proxy.inner.destination, Cow::Borrowed(_)));
+        assert!(matches!(proxy.inner.destination, Some(Cow::Borrowed(_))));
         assert!(matches!(proxy.inner.interface, Cow::Borrowed(_)));
     }
 }
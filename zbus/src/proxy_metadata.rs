@@ -0,0 +1,340 @@
+use static_assertions::assert_impl_all;
+use std::fmt;
+use zvariant::Signature;
+
+#[cfg(feature = "xml")]
+use crate::{xml, Error, Result};
+
+/// The description of a D-Bus interface, as expected by a proxy or as served.
+///
+/// The [`dbus_proxy`] macro generates it from the trait, and makes it available through
+/// [`ProxyDefault::METADATA`]. It is used to validate proxies against the introspection data of
+/// their object (see [`Proxy::validate`]).
+///
+/// The [`dbus_interface`] macro generates it from the implementation, and makes it available
+/// through [`Interface::metadata`] and [`ObjectServer::interface_metadata`].
+///
+/// The signatures are only known for members with no generic types.
+///
+/// [`dbus_proxy`]: attr.dbus_proxy.html
+/// [`ProxyDefault::METADATA`]: trait.ProxyDefault.html#associatedconstant.METADATA
+/// [`Proxy::validate`]: struct.Proxy.html#method.validate
+/// [`dbus_interface`]: attr.dbus_interface.html
+/// [`Interface::metadata`]: trait.Interface.html#method.metadata
+/// [`ObjectServer::interface_metadata`]: struct.ObjectServer.html#method.interface_metadata
+#[derive(Debug)]
+pub struct InterfaceMetadata {
+    /// The interface name.
+    pub name: &'static str,
+    /// The methods called by the proxy, or served.
+    pub methods: &'static [MethodMetadata],
+    /// The properties got or set by the proxy, or served.
+    pub properties: &'static [PropertyMetadata],
+    /// The signals received by the proxy, or emitted.
+    pub signals: &'static [SignalMetadata],
+}
+
+assert_impl_all!(InterfaceMetadata: Send, Sync, Unpin);
+
+/// A method of an [`InterfaceMetadata`].
+///
+/// [`InterfaceMetadata`]: struct.InterfaceMetadata.html
+#[derive(Debug)]
+pub struct MethodMetadata {
+    /// The method name.
+    pub name: &'static str,
+    /// The signature of a tuple of the input arguments.
+    pub input: Option<fn() -> Signature<'static>>,
+    /// The signature of the reply body.
+    pub output: Option<fn() -> Signature<'static>>,
+}
+
+assert_impl_all!(MethodMetadata: Send, Sync, Unpin);
+
+/// A property of an [`InterfaceMetadata`].
+///
+/// [`InterfaceMetadata`]: struct.InterfaceMetadata.html
+#[derive(Debug)]
+pub struct PropertyMetadata {
+    /// The property name.
+    pub name: &'static str,
+    /// The property type.
+    pub signature: Option<fn() -> Signature<'static>>,
+    /// Whether the proxy gets the property, or whether it's readable.
+    pub read: bool,
+    /// Whether the proxy sets the property, or whether it's writable.
+    pub write: bool,
+}
+
+assert_impl_all!(PropertyMetadata: Send, Sync, Unpin);
+
+/// A signal of an [`InterfaceMetadata`].
+///
+/// [`InterfaceMetadata`]: struct.InterfaceMetadata.html
+#[derive(Debug)]
+pub struct SignalMetadata {
+    /// The signal name.
+    pub name: &'static str,
+    /// The signature of a tuple of the signal arguments.
+    pub signature: Option<fn() -> Signature<'static>>,
+}
+
+assert_impl_all!(SignalMetadata: Send, Sync, Unpin);
+
+/// A difference between a proxy and the introspected interface of its object.
+///
+/// These are reported by [`Error::InterfaceMismatch`].
+///
+/// [`Error::InterfaceMismatch`]: enum.Error.html#variant.InterfaceMismatch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// The object doesn't implement the interface.
+    InterfaceNotFound(String),
+    /// The interface has no such method.
+    MethodNotFound(String),
+    /// The interface has no such property.
+    PropertyNotFound(String),
+    /// The interface has no such signal.
+    SignalNotFound(String),
+    /// A method, property or signal has a different signature.
+    Signature {
+        /// The member name.
+        member: String,
+        /// The signature expected by the proxy.
+        expected: String,
+        /// The signature given by the interface.
+        found: String,
+    },
+    /// A property can't be accessed the way the proxy does.
+    PropertyAccess {
+        /// The property name.
+        property: String,
+        /// The access flags given by the interface.
+        found: String,
+    },
+}
+
+assert_impl_all!(Discrepancy: Send, Sync, Unpin);
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Discrepancy::InterfaceNotFound(name) => write!(f, "no interface `{}`", name),
+            Discrepancy::MethodNotFound(name) => write!(f, "no method `{}`", name),
+            Discrepancy::PropertyNotFound(name) => write!(f, "no property `{}`", name),
+            Discrepancy::SignalNotFound(name) => write!(f, "no signal `{}`", name),
+            Discrepancy::Signature {
+                member,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{}` has signature `{}`, expected `{}`",
+                member, found, expected
+            ),
+            Discrepancy::PropertyAccess { property, found } => {
+                write!(f, "property `{}` has access `{}`", property, found)
+            }
+        }
+    }
+}
+
+// Check the `interface` of the introspected `node` against `metadata`. Without metadata, only the
+// presence of the interface is checked.
+#[cfg(feature = "xml")]
+pub(crate) fn validate(
+    metadata: Option<&InterfaceMetadata>,
+    interface: &str,
+    node: &xml::Node,
+) -> Result<()> {
+    let iface = match node
+        .interfaces()
+        .into_iter()
+        .find(|i| i.name() == interface)
+    {
+        Some(iface) => iface,
+        None => {
+            return Err(Error::InterfaceMismatch(vec![
+                Discrepancy::InterfaceNotFound(interface.to_owned()),
+            ]))
+        }
+    };
+    let metadata = match metadata {
+        Some(metadata) => metadata,
+        None => return Ok(()),
+    };
+
+    let mut discrepancies = vec![];
+    for method in metadata.methods {
+        match iface.lookup_method(method.name) {
+            Some(m) => {
+                discrepancies.extend(check_signature(
+                    method.name,
+                    method.input,
+                    &m.input_signature()?,
+                ));
+                discrepancies.extend(check_signature(
+                    method.name,
+                    method.output,
+                    &m.output_signature()?,
+                ));
+            }
+            None => discrepancies.push(Discrepancy::MethodNotFound(method.name.to_owned())),
+        }
+    }
+    for property in metadata.properties {
+        match iface.lookup_property(property.name) {
+            Some(p) => {
+                discrepancies.extend(check_signature(
+                    property.name,
+                    property.signature,
+                    &p.signature()?,
+                ));
+                let allowed = p
+                    .access_mode()
+                    .map(|mode| {
+                        (!property.read || mode.read()) && (!property.write || mode.write())
+                    })
+                    .unwrap_or(false);
+                if !allowed {
+                    discrepancies.push(Discrepancy::PropertyAccess {
+                        property: property.name.to_owned(),
+                        found: p.access().to_owned(),
+                    });
+                }
+            }
+            None => discrepancies.push(Discrepancy::PropertyNotFound(property.name.to_owned())),
+        }
+    }
+    for signal in metadata.signals {
+        match iface.lookup_signal(signal.name) {
+            Some(s) => {
+                discrepancies.extend(check_signature(
+                    signal.name,
+                    signal.signature,
+                    &s.signature()?,
+                ));
+            }
+            None => discrepancies.push(Discrepancy::SignalNotFound(signal.name.to_owned())),
+        }
+    }
+
+    if discrepancies.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InterfaceMismatch(discrepancies))
+    }
+}
+
+// Tuples of arguments and multiple out arguments are both given as a structure by the proxy, while
+// the introspection data lists the arguments.
+#[cfg(feature = "xml")]
+fn check_signature(
+    member: &str,
+    expected: Option<fn() -> Signature<'static>>,
+    found: &Signature<'_>,
+) -> Option<Discrepancy> {
+    let expected = expected?();
+    let (e, f) = (expected.as_str(), found.as_str());
+    let fields = e.strip_prefix('(').and_then(|e| e.strip_suffix(')'));
+    if e == f || fields == Some(f) {
+        return None;
+    }
+
+    Some(Discrepancy::Signature {
+        member: member.to_owned(),
+        expected: expected.to_string(),
+        found: found.to_string(),
+    })
+}
+
+#[cfg(all(test, feature = "xml"))]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{dbus_proxy, xml::Node, Discrepancy, Error, ProxyDefault};
+
+    const XML: &str = r#"
+<node>
+  <interface name="org.zbus.Test">
+    <method name="Add">
+      <arg name="a" type="i" direction="in"/>
+      <arg name="b" type="i" direction="in"/>
+      <arg name="sum" type="i" direction="out"/>
+    </method>
+    <method name="Split">
+      <arg name="s" type="s" direction="in"/>
+      <arg name="head" type="s" direction="out"/>
+      <arg name="tail" type="s" direction="out"/>
+    </method>
+    <method name="Rename">
+      <arg name="name" type="s" direction="in"/>
+    </method>
+    <signal name="Changed">
+      <arg name="what" type="s"/>
+    </signal>
+    <property name="Count" type="u" access="read"/>
+  </interface>
+</node>
+"#;
+
+    #[dbus_proxy(interface = "org.zbus.Test")]
+    trait Test {
+        fn add(&self, a: i32, b: i32) -> zbus::Result<i32>;
+
+        fn split(&self, s: &str) -> zbus::Result<(String, String)>;
+
+        fn rename(&self, name: &str) -> zbus::Result<()>;
+
+        #[dbus_proxy(signal)]
+        fn changed(&self, what: &str) -> zbus::Result<()>;
+
+        #[dbus_proxy(property)]
+        fn count(&self) -> zbus::Result<u32>;
+    }
+
+    #[dbus_proxy(interface = "org.zbus.Test")]
+    trait Outdated {
+        fn add(&self, a: i32, b: u32) -> zbus::Result<i32>;
+
+        fn remove(&self) -> zbus::Result<()>;
+
+        #[dbus_proxy(property)]
+        fn set_count(&self, count: u32) -> zbus::Result<()>;
+    }
+
+    #[test]
+    fn validate() {
+        let node = Node::from_str(XML).unwrap();
+
+        super::validate(TestProxy::METADATA, "org.zbus.Test", &node).unwrap();
+        super::validate(None, "org.zbus.Test", &node).unwrap();
+
+        match super::validate(OutdatedProxy::METADATA, "org.zbus.Test", &node) {
+            Err(Error::InterfaceMismatch(discrepancies)) => assert_eq!(
+                discrepancies,
+                vec![
+                    Discrepancy::Signature {
+                        member: "Add".into(),
+                        expected: "(iu)".into(),
+                        found: "ii".into(),
+                    },
+                    Discrepancy::MethodNotFound("Remove".into()),
+                    Discrepancy::PropertyAccess {
+                        property: "Count".into(),
+                        found: "read".into(),
+                    },
+                ]
+            ),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        match super::validate(TestProxy::METADATA, "org.zbus.Other", &node) {
+            Err(Error::InterfaceMismatch(discrepancies)) => assert_eq!(
+                discrepancies,
+                vec![Discrepancy::InterfaceNotFound("org.zbus.Other".into())]
+            ),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// The compression of the message bodies between two zbus peers, as agreed on in the handshake.
+//
+// The bodies are compressed with deflate, which is marked by a flag of the primary header that
+// the specification doesn't define. The flag and the compressed body only exist on the wire: the
+// messages are compressed right before being written, and decompressed right after being read.
+use std::{
+    convert::TryFrom,
+    io::{Read, Write},
+};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+use crate::{EndianSig, Message, MessageError};
+
+// The flag marking a compressed body, at `FLAGS_OFFSET` in the message.
+pub(crate) const COMPRESSED_FLAG: u8 = 0x80;
+const FLAGS_OFFSET: usize = 2;
+const BODY_LEN_OFFSET: usize = 4;
+// The maximum length of a message, as per the specification, so of a decompressed body as well.
+const MAX_BODY_LEN: usize = 1 << 27;
+
+// Compress the body of `msg` if it's longer than `threshold` bytes, and compressing saves bytes.
+//
+// The returned message can only be written out, its bytes don't match its primary header anymore.
+pub(crate) fn compress(msg: Message, threshold: usize) -> Message {
+    let body_len = msg.primary_header().body_len() as usize;
+    if body_len <= threshold {
+        return msg;
+    }
+    let offset = match msg.body_offset() {
+        Ok(offset) => offset,
+        Err(_) => return msg,
+    };
+
+    let (header, body) = msg.as_bytes().split_at(offset);
+    let mut encoder = DeflateEncoder::new(Vec::with_capacity(body.len() / 2), Compression::fast());
+    let compressed = match encoder.write_all(body).and_then(|_| encoder.finish()) {
+        Ok(compressed) if compressed.len() < body.len() => compressed,
+        _ => return msg,
+    };
+
+    let mut bytes = Vec::with_capacity(header.len() + compressed.len());
+    bytes.extend_from_slice(header);
+    bytes.extend_from_slice(&compressed);
+    bytes[FLAGS_OFFSET] |= COMPRESSED_FLAG;
+    write_body_len(&mut bytes, compressed.len());
+
+    msg.with_wire_bytes(bytes)
+}
+
+// Clear the compression flag from the start of a message received, returning whether it was set.
+pub(crate) fn take_flag(bytes: &mut [u8]) -> bool {
+    let compressed = bytes[FLAGS_OFFSET] & COMPRESSED_FLAG != 0;
+    bytes[FLAGS_OFFSET] &= !COMPRESSED_FLAG;
+
+    compressed
+}
+
+// Decompress the body of `msg`, received with the compression flag.
+pub(crate) fn decompress(msg: &Message) -> Result<Message, MessageError> {
+    let offset = msg.body_offset()?;
+    let (header, body) = msg.as_bytes().split_at(offset);
+
+    let mut bytes = header.to_vec();
+    DeflateDecoder::new(body)
+        .take(MAX_BODY_LEN as u64 + 1)
+        .read_to_end(&mut bytes)?;
+    let body_len = bytes.len() - offset;
+    if body_len > MAX_BODY_LEN {
+        return Err(MessageError::ExcessData);
+    }
+    write_body_len(&mut bytes, body_len);
+
+    Message::from_bytes(&bytes)
+}
+
+fn write_body_len(bytes: &mut [u8], len: usize) {
+    // The byte order was checked on parsing the message.
+    let endian = EndianSig::try_from(bytes[0]);
+    let buf = &mut bytes[BODY_LEN_OFFSET..BODY_LEN_OFFSET + 4];
+    match endian {
+        Ok(EndianSig::Big) => BigEndian::write_u32(buf, len as u32),
+        _ => LittleEndian::write_u32(buf, len as u32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_env_log::test;
+
+    use super::*;
+
+    // A method call with a body of `len` bytes, all zeros after the array length.
+    fn call(len: usize) -> Message {
+        Message::method(None, None, "/", None, "Test", &vec![0u8; len - 4]).unwrap()
+    }
+
+    // Receive `msg` the way the raw connection does.
+    fn receive(msg: &Message) -> Message {
+        let mut bytes = msg.as_bytes().to_vec();
+        if take_flag(&mut bytes) {
+            decompress(&Message::from_bytes(&bytes).unwrap()).unwrap()
+        } else {
+            Message::from_bytes(&bytes).unwrap()
+        }
+    }
+
+    #[test]
+    fn threshold() {
+        let msg = call(1024);
+        assert_eq!(msg.primary_header().body_len(), 1024);
+        let bytes = msg.as_bytes().to_vec();
+        let sent = compress(msg, 1024);
+        assert_eq!(sent.as_bytes(), &bytes[..]);
+        assert_eq!(receive(&sent).as_bytes(), &bytes[..]);
+
+        let msg = call(1025);
+        let bytes = msg.as_bytes().to_vec();
+        let sent = compress(msg, 1024);
+        assert_ne!(sent.as_bytes()[FLAGS_OFFSET] & COMPRESSED_FLAG, 0);
+        assert!(sent.as_bytes().len() < bytes.len());
+        assert_eq!(receive(&sent).as_bytes(), &bytes[..]);
+        assert_eq!(receive(&sent).body::<Vec<u8>>().unwrap(), vec![0u8; 1021]);
+    }
+
+    #[test]
+    fn incompressible() {
+        let body: Vec<u8> = (0..4096).map(|_| rand::random()).collect();
+        let msg = Message::method(None, None, "/", None, "Test", &body).unwrap();
+        let bytes = msg.as_bytes().to_vec();
+        assert_eq!(compress(msg, 0).as_bytes(), &bytes[..]);
+    }
+
+    #[test]
+    fn big_endian() {
+        let msg = crate::MessageBuilder::method_call("/", "Test")
+            .unwrap()
+            .endian_sig(EndianSig::Big)
+            .build(&vec![0u8; 4096])
+            .unwrap();
+        let bytes = msg.as_bytes().to_vec();
+        let received = receive(&compress(msg, 0));
+        assert_eq!(received.as_bytes(), &bytes[..]);
+        assert_eq!(received.primary_header().body_len(), 4100);
+    }
+}
@@ -1,6 +1,20 @@
-use std::{collections::VecDeque, io};
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind},
+    os::unix::io::{AsRawFd, RawFd},
+};
 
-use crate::{message::Message, message_header::MIN_MESSAGE_SIZE, raw::Socket, OwnedFd};
+use crate::{
+    handshake::{ClientHandshake, Handshake},
+    logging,
+    message::Message,
+    message_header::MIN_MESSAGE_SIZE,
+    raw::{
+        socket::{map_peer_closed, peer_closed_error},
+        Socket,
+    },
+    Error, MessageError, OwnedFd, Priority, Result,
+};
 
 /// A low-level representation of a D-Bus connection
 ///
@@ -10,6 +24,29 @@ use crate::{message::Message, message_header::MIN_MESSAGE_SIZE, raw::Socket, Own
 ///
 /// This wrapper abstracts away the serialization & buffering considerations of the
 /// protocol, and allows interaction based on messages, rather than bytes.
+///
+/// # Integration with a foreign event loop
+///
+/// With a socket in non-blocking mode, the connection can be driven by any event loop able to
+/// watch a file descriptor, without the internal executor of [`azync::Connection`]:
+///
+/// * watch [`as_raw_fd`] for readability, and for writability as long as [`needs_write`] is
+///   `true`.
+/// * call [`process_readable`] when the socket is readable, to get the messages received.
+/// * call [`process_writable`] when it's writable, to send the messages enqueued with
+///   [`enqueue_message`]. Their serial number must have been assigned with
+///   [`assign_serial_num`] first.
+///
+/// None of these methods block, so they can be called from the callbacks of the event loop. See
+/// the `poll-loop` example for a complete client.
+///
+/// [`azync::Connection`]: ../azync/struct.Connection.html
+/// [`as_raw_fd`]: #method.as_raw_fd
+/// [`needs_write`]: #method.needs_write
+/// [`process_readable`]: #method.process_readable
+/// [`process_writable`]: #method.process_writable
+/// [`enqueue_message`]: #method.enqueue_message
+/// [`assign_serial_num`]: #method.assign_serial_num
 #[derive(derivative::Derivative)]
 #[derivative(Debug)]
 pub struct Connection<S> {
@@ -20,8 +57,27 @@ pub struct Connection<S> {
     msg_in_buffer: Option<Message>,
     raw_out_buffer: VecDeque<u8>,
     msg_out_buffer: VecDeque<Message>,
+    // The number of high-priority messages at the front of `msg_out_buffer`.
+    high_priority_count: usize,
+    write_coalescing: bool,
+    // The serial number of the next message, for `assign_serial_num`.
+    next_serial: u32,
+    // An error met by `process_readable` after receiving messages, returned on its next call.
+    in_error: Option<Error>,
+    // Compress the bodies longer than this, and decompress the ones received compressed, if the
+    // peer agreed to it in the handshake.
+    body_compression: Option<usize>,
+    // Whether the body of `msg_in_buffer` is compressed.
+    #[cfg(feature = "compression")]
+    msg_in_compressed: bool,
+    // The value budget of the messages received.
+    value_budget: Option<usize>,
 }
 
+// The maximum number of bytes written in one go when coalescing messages. A single message bigger
+// than this is still written as a whole.
+const MAX_COALESCED_BYTES: usize = 64 * 1024;
+
 impl<S: Socket> Connection<S> {
     pub(crate) fn wrap(socket: S) -> Connection<S> {
         Connection {
@@ -31,16 +87,89 @@ impl<S: Socket> Connection<S> {
             msg_in_buffer: None,
             raw_out_buffer: VecDeque::new(),
             msg_out_buffer: VecDeque::new(),
+            high_priority_count: 0,
+            write_coalescing: false,
+            next_serial: 1,
+            in_error: None,
+            body_compression: None,
+            #[cfg(feature = "compression")]
+            msg_in_compressed: false,
+            value_budget: None,
         }
     }
 
+    /// Create a client connection, doing the authentication handshake on `socket`.
+    ///
+    /// This blocks until the handshake is done, even if the socket is in non-blocking mode. If
+    /// the peer is a bus, the `Hello` method still has to be called before any other.
+    pub fn new_client(socket: S) -> Result<Self> {
+        ClientHandshake::new(socket)
+            .blocking_finish()
+            .map(|auth| auth.conn)
+    }
+
+    /// Set whether `try_flush` writes multiple messages to the socket at once.
+    ///
+    /// D-Bus is a byte stream so several complete messages can be sent with a single `sendmsg`
+    /// call, which saves a syscall per message when sending many small ones. Messages carrying
+    /// file descriptors always start a new write, so that the descriptors are sent along with the
+    /// message they belong to.
+    pub fn set_write_coalescing(&mut self, write_coalescing: bool) {
+        self.write_coalescing = write_coalescing;
+    }
+
+    /// Whether `try_flush` writes multiple messages to the socket at once.
+    pub fn write_coalescing(&self) -> bool {
+        self.write_coalescing
+    }
+
+    /// Set the value budget of the messages received, `None` (the default) for no limit.
+    ///
+    /// See [`Message::set_value_budget`] for details.
+    ///
+    /// [`Message::set_value_budget`]: ../struct.Message.html#method.set_value_budget
+    pub fn set_value_budget(&mut self, budget: Option<usize>) {
+        self.value_budget = budget;
+    }
+
+    /// The value budget of the messages received, if any.
+    pub fn value_budget(&self) -> Option<usize> {
+        self.value_budget
+    }
+
+    // Compress the bodies longer than `threshold` bytes, for a peer that agreed to it.
+    pub(crate) fn set_body_compression(&mut self, threshold: Option<usize>) {
+        self.body_compression = threshold;
+    }
+
+    pub(crate) fn body_compression(&self) -> Option<usize> {
+        self.body_compression
+    }
+
     /// Attempt to flush the outgoing buffer
     ///
     /// This will try to write as many messages as possible from the
     /// outgoing buffer into the socket, until an error is encountered.
     ///
-    /// This method will thus only block if the socket is in blocking mode.
+    /// This method will thus only block if the socket is in blocking mode. Once the peer is gone,
+    /// it fails with an I/O error of kind `BrokenPipe`.
     pub fn try_flush(&mut self) -> io::Result<()> {
+        self.flush(self.write_coalescing)
+    }
+
+    /// Attempt to flush the outgoing buffer, writing multiple messages at once.
+    ///
+    /// This is the same as `try_flush` with write coalescing enabled. See `set_write_coalescing`
+    /// for details.
+    pub fn try_flush_coalesced(&mut self) -> io::Result<()> {
+        self.flush(true)
+    }
+
+    fn flush(&mut self, coalesce: bool) -> io::Result<()> {
+        self.write_out(coalesce).map_err(map_peer_closed)
+    }
+
+    fn write_out(&mut self, coalesce: bool) -> io::Result<()> {
         // first, empty the raw_out_buffer of any partially-sent message
         while !self.raw_out_buffer.is_empty() {
             let (front, _) = self.raw_out_buffer.as_slices();
@@ -53,14 +182,42 @@ impl<S: Socket> Connection<S> {
 
         // now, try to drain the msg_out_buffer
         while let Some(msg) = self.msg_out_buffer.front() {
-            let mut data = msg.as_bytes();
+            let count = if coalesce {
+                self.coalescable_count()
+            } else {
+                1
+            };
             let fds = msg.fds();
-            let written = self.socket.sendmsg(data, &fds)?;
-            // at least some part of the message has been sent, see if we can/need to send more
-            // now the message must be removed from msg_out_buffer and any leftover bytes
+            let written = if count == 1 {
+                self.socket.sendmsg(msg.as_bytes(), &fds)?
+            } else {
+                let mut data = vec![];
+                for msg in self.msg_out_buffer.range(..count) {
+                    data.extend_from_slice(msg.as_bytes());
+                }
+
+                self.socket.sendmsg(&data, &fds)?
+            };
+            log::trace!(
+                target: logging::SOCKET,
+                "Wrote {} bytes of {} message(s)",
+                written,
+                count,
+            );
+            // at least some part of the messages has been sent, see if we can/need to send more
+            // now the messages must be removed from msg_out_buffer and any leftover bytes
             // must be stored into raw_out_buffer
-            let msg = self.msg_out_buffer.pop_front().unwrap();
-            data = &msg.as_bytes()[written..];
+            let mut skip = written;
+            let mut leftover = vec![];
+            for msg in self.msg_out_buffer.drain(..count) {
+                let bytes = msg.as_bytes();
+                if skip < bytes.len() {
+                    leftover.extend_from_slice(&bytes[skip..]);
+                }
+                skip = skip.saturating_sub(bytes.len());
+            }
+            self.high_priority_count = self.high_priority_count.saturating_sub(count);
+            let mut data = &leftover[..];
             while !data.is_empty() {
                 match self.socket.sendmsg(data, &[]) {
                     Ok(n) => data = &data[n..],
@@ -76,12 +233,121 @@ impl<S: Socket> Connection<S> {
         Ok(())
     }
 
+    // The number of messages at the front of `msg_out_buffer` that can be written at once.
+    fn coalescable_count(&self) -> usize {
+        let mut len = 0;
+
+        self.msg_out_buffer
+            .iter()
+            .enumerate()
+            .take_while(|(i, msg)| {
+                len += msg.as_bytes().len();
+
+                *i == 0 || (len <= MAX_COALESCED_BYTES && msg.fds().is_empty())
+            })
+            .count()
+    }
+
+    /// The number of messages waiting to be written to the socket.
+    ///
+    /// A message written partially already isn't counted.
+    pub fn queued_message_count(&self) -> usize {
+        self.msg_out_buffer.len()
+    }
+
+    /// Whether there are messages, or part of a message, waiting to be written to the socket.
+    ///
+    /// The socket should be watched for writability as long as this is `true`.
+    pub fn needs_write(&self) -> bool {
+        !self.raw_out_buffer.is_empty() || !self.msg_out_buffer.is_empty()
+    }
+
+    /// Write as much of the outgoing buffer as the socket accepts, without blocking.
+    ///
+    /// This is the same as `try_flush`, except that the socket being full isn't an error: the
+    /// rest is written on the next call, once the socket is writable again.
+    pub fn process_writable(&mut self) -> io::Result<()> {
+        match self.try_flush() {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+            res => res,
+        }
+    }
+
+    /// Receive all the messages available on the socket, without blocking.
+    ///
+    /// The socket must be in non-blocking mode. Messages are read until the socket has no more
+    /// data, a message received partially being completed on a later call. An empty list is
+    /// returned if no complete message was available.
+    ///
+    /// If an error occurs after some messages were received, these are returned and the error is
+    /// returned on the next call.
+    pub fn process_readable(&mut self) -> Result<Vec<Message>> {
+        if let Some(e) = self.in_error.take() {
+            return Err(e);
+        }
+
+        let mut msgs = vec![];
+        loop {
+            match self.try_receive_message() {
+                Ok(msg) => msgs.push(msg),
+                Err(Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => return Ok(msgs),
+                Err(e) if msgs.is_empty() => return Err(e),
+                Err(e) => {
+                    self.in_error = Some(e);
+
+                    return Ok(msgs);
+                }
+            }
+        }
+    }
+
+    /// Assign the next serial number to `msg`, unless it already has one.
+    ///
+    /// Returns the serial number of the message. Serial numbers are never 0, so the count starts
+    /// over at 1 once `u32::MAX` is reached.
+    pub fn assign_serial_num(&mut self, msg: &mut Message) -> Result<u32> {
+        let next_serial = &mut self.next_serial;
+        let mut serial = 0;
+        msg.modify_primary_header(|primary| {
+            serial = *primary.serial_num_or_init(|| {
+                let serial = *next_serial;
+                *next_serial = serial.checked_add(1).unwrap_or(1);
+
+                serial
+            });
+            Ok(())
+        })?;
+
+        Ok(serial)
+    }
+
     /// Enqueue a message to be sent out to the socket
     ///
     /// This method will *not* write anything to the socket, you need to call
     /// `try_flush()` afterwards so that your message is actually sent out.
     pub fn enqueue_message(&mut self, msg: Message) {
-        self.msg_out_buffer.push_back(msg);
+        self.enqueue_message_with_priority(msg, Priority::Normal);
+    }
+
+    /// Enqueue a message to be sent out to the socket, with the given priority.
+    ///
+    /// A high-priority message is queued ahead of the normal-priority ones, but never ahead of a
+    /// message partially written already. See [`Priority`] for the ordering guarantees.
+    ///
+    /// [`Priority`]: ../enum.Priority.html
+    pub fn enqueue_message_with_priority(&mut self, msg: Message, priority: Priority) {
+        #[cfg(feature = "compression")]
+        let msg = match self.body_compression {
+            Some(threshold) => super::compression::compress(msg, threshold),
+            None => msg,
+        };
+        match priority {
+            Priority::Normal => self.msg_out_buffer.push_back(msg),
+            Priority::High => {
+                self.msg_out_buffer.insert(self.high_priority_count, msg);
+                self.high_priority_count += 1;
+            }
+        }
     }
 
     /// Attempt to read a message from the socket
@@ -91,7 +357,10 @@ impl<S: Socket> Connection<S> {
     ///
     /// If the socket is in non-blocking mode, it may read a partial message. In such case it
     /// will buffer it internally and try to complete it the next time you call `try_receive_message`.
-    pub fn try_receive_message(&mut self) -> crate::Result<Message> {
+    ///
+    /// Once the peer is gone, this fails with an I/O error of kind `BrokenPipe`, whether the
+    /// socket was closed or the connection reset.
+    pub fn try_receive_message(&mut self) -> Result<Message> {
         if self.msg_in_buffer.is_none() {
             // We don't have enough data to make a proper message header yet.
             // Some partial read may be in raw_in_buffer, so we try to complete it
@@ -102,16 +371,17 @@ impl<S: Socket> Connection<S> {
             while self.raw_in_buffer.len() < MIN_MESSAGE_SIZE {
                 let current_bytes = self.raw_in_buffer.len();
                 let mut buf = vec![0; MIN_MESSAGE_SIZE - current_bytes];
-                let (read, fds) = self.socket.recvmsg(&mut buf)?;
+                let (read, fds) = self.socket.recvmsg(&mut buf).map_err(map_peer_closed)?;
                 if read == 0 {
-                    return Err(crate::Error::Io(std::io::Error::new(
-                        std::io::ErrorKind::UnexpectedEof,
-                        "failed to receive message",
-                    )));
+                    return Err(peer_closed_error().into());
                 }
                 self.raw_in_buffer.extend(&buf[..read]);
                 self.raw_in_fds.extend(fds);
             }
+            #[cfg(feature = "compression")]
+            if self.body_compression.is_some() {
+                self.msg_in_compressed = super::compression::take_flag(&mut self.raw_in_buffer);
+            }
 
             // We now have a full message header, so let us construct the Message
             self.msg_in_buffer = Some(Message::from_bytes(&self.raw_in_buffer)?);
@@ -131,7 +401,10 @@ impl<S: Socket> Connection<S> {
                     Ok(needed) => {
                         // we need to read more data
                         let mut buf = vec![0; needed];
-                        let (read, fds) = self.socket.recvmsg(&mut buf)?;
+                        let (read, fds) = self.socket.recvmsg(&mut buf).map_err(map_peer_closed)?;
+                        if read == 0 {
+                            return Err(peer_closed_error().into());
+                        }
                         msg.add_bytes(&buf[..read])?;
                         self.raw_in_fds.extend(fds);
                     }
@@ -144,15 +417,27 @@ impl<S: Socket> Connection<S> {
         }
 
         // If we reach here, the message is complete, return it
-        let msg = self.msg_in_buffer.take().unwrap();
-        msg.set_owned_fds(std::mem::take(&mut self.raw_in_fds));
+        let mut msg = self.msg_in_buffer.take().unwrap();
+        #[cfg(feature = "compression")]
+        if std::mem::take(&mut self.msg_in_compressed) {
+            msg = super::compression::decompress(&msg)?;
+        }
+        let fds = std::mem::take(&mut self.raw_in_fds);
+        // Otherwise the FDs would be assigned to the wrong indices in the body. The FDs get closed
+        // on return.
+        if msg.header()?.unix_fds()?.unwrap_or(0) as usize != fds.len() {
+            return Err(MessageError::UnmatchedFdCount.into());
+        }
+        msg.set_owned_fds(fds);
+        msg.set_value_budget(self.value_budget);
+        log::trace!(target: logging::SOCKET, "Received {}", msg);
         Ok(msg)
     }
 
     /// Close the connection.
     ///
     /// After this call, all reading and writing operations will fail.
-    pub fn close(&self) -> crate::Result<()> {
+    pub fn close(&self) -> Result<()> {
         self.socket().close().map_err(|e| e.into())
     }
 
@@ -166,14 +451,50 @@ impl<S: Socket> Connection<S> {
     pub fn socket(&self) -> &S {
         &self.socket
     }
+
+    // Wrap the socket into another type of socket, keeping the buffered data.
+    pub(crate) fn map_socket<T, F>(self, f: F) -> Connection<T>
+    where
+        F: FnOnce(S) -> T,
+    {
+        Connection {
+            socket: f(self.socket),
+            raw_in_buffer: self.raw_in_buffer,
+            raw_in_fds: self.raw_in_fds,
+            msg_in_buffer: self.msg_in_buffer,
+            raw_out_buffer: self.raw_out_buffer,
+            msg_out_buffer: self.msg_out_buffer,
+            high_priority_count: self.high_priority_count,
+            write_coalescing: self.write_coalescing,
+            next_serial: self.next_serial,
+            in_error: self.in_error,
+            body_compression: self.body_compression,
+            #[cfg(feature = "compression")]
+            msg_in_compressed: self.msg_in_compressed,
+            value_budget: self.value_budget,
+        }
+    }
+}
+
+impl<S: Socket> AsRawFd for Connection<S> {
+    /// The file descriptor of the socket, to watch for readiness.
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Connection;
-    use crate::message::Message;
-    use std::os::unix::net::UnixStream;
+    use crate::{message::Message, Error, MessageError, Priority};
+    use nix::sys::signal::{SigSet, Signal};
+    use std::{
+        io::{ErrorKind, Read, Write},
+        os::unix::net::UnixStream,
+        thread,
+    };
     use test_env_log::test;
+    use zvariant::Fd;
 
     #[test]
     fn raw_send_receive() {
@@ -191,4 +512,223 @@ mod tests {
 
         assert_eq!(ret.to_string(), "Method call Test");
     }
+
+    #[test]
+    fn unmatched_fd_count() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let mut conn0 = Connection::wrap(p0);
+        let mut conn1 = Connection::wrap(p1);
+
+        let stdout = std::io::stdout();
+        let msg = Message::method(None, None, "/", None, "Test", &Fd::from(&stdout)).unwrap();
+        assert_eq!(msg.counted_fds(), 1);
+        // Same message but without the FD attached.
+        let forged = Message::from_bytes(msg.as_bytes()).unwrap();
+        assert_eq!(forged.header().unwrap().unix_fds().unwrap(), Some(1));
+        assert_eq!(forged.counted_fds(), 0);
+
+        conn0.enqueue_message(forged);
+        conn0.enqueue_message(msg);
+        conn0.try_flush().unwrap();
+
+        match conn1.try_receive_message().unwrap_err() {
+            Error::Message(e) => assert_eq!(e, MessageError::UnmatchedFdCount),
+            e => panic!("unexpected error: {}", e),
+        }
+        let ret = conn1.try_receive_message().unwrap();
+        assert_eq!(ret.counted_fds(), 1);
+    }
+
+    #[test]
+    fn poll_based() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        p0.set_nonblocking(true).unwrap();
+        p1.set_nonblocking(true).unwrap();
+
+        let mut conn0 = Connection::wrap(p0);
+        let mut conn1 = Connection::wrap(p1);
+        assert!(!conn0.needs_write());
+        assert!(conn1.process_readable().unwrap().is_empty());
+
+        for i in 1..4u32 {
+            let mut msg = Message::method(None, None, "/", None, "Test", &i).unwrap();
+            assert_eq!(conn0.assign_serial_num(&mut msg).unwrap(), i);
+            assert_eq!(conn0.assign_serial_num(&mut msg).unwrap(), i);
+            conn0.enqueue_message(msg);
+        }
+        // Same message but without the FD attached.
+        let stdout = std::io::stdout();
+        let msg = Message::method(None, None, "/", None, "Test", &Fd::from(&stdout)).unwrap();
+        conn0.enqueue_message(Message::from_bytes(msg.as_bytes()).unwrap());
+        assert!(conn0.needs_write());
+        conn0.process_writable().unwrap();
+        assert!(!conn0.needs_write());
+
+        // The error comes after the messages received before it.
+        let msgs = conn1.process_readable().unwrap();
+        let serials: Vec<_> = msgs
+            .iter()
+            .map(|msg| msg.primary_header().serial_num().copied())
+            .collect();
+        assert_eq!(serials, vec![Some(1), Some(2), Some(3)]);
+        match conn1.process_readable().unwrap_err() {
+            Error::Message(e) => assert_eq!(e, MessageError::UnmatchedFdCount),
+            e => panic!("unexpected error: {}", e),
+        }
+        assert!(conn1.process_readable().unwrap().is_empty());
+
+        conn0.next_serial = u32::MAX;
+        let mut msg = Message::method(None, None, "/", None, "Test", &()).unwrap();
+        assert_eq!(conn0.assign_serial_num(&mut msg).unwrap(), u32::MAX);
+        let mut msg = Message::method(None, None, "/", None, "Test", &()).unwrap();
+        assert_eq!(conn0.assign_serial_num(&mut msg).unwrap(), 1);
+
+        drop(conn0);
+        match conn1.process_readable().unwrap_err() {
+            Error::Io(e) => assert_eq!(e.kind(), ErrorKind::BrokenPipe),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    // Whether a `SIGPIPE` is pending for the current thread, which must block it.
+    fn sigpipe_pending() -> bool {
+        use nix::libc::{sigemptyset, sigismember, sigpending, sigset_t, SIGPIPE};
+
+        // SAFETY: The set is initialized before it's used.
+        unsafe {
+            let mut pending: sigset_t = std::mem::zeroed();
+            sigemptyset(&mut pending);
+            assert_eq!(sigpending(&mut pending), 0);
+
+            sigismember(&pending, SIGPIPE) == 1
+        }
+    }
+
+    #[test]
+    fn peer_closed_mid_write() {
+        // `SIGPIPE` is ignored by the test harness, but stays pending while it's blocked, so we
+        // can check that none is raised.
+        let mut sigpipe = SigSet::empty();
+        sigpipe.add(Signal::SIGPIPE);
+        sigpipe.thread_block().unwrap();
+
+        // Way more than the socket buffers can hold, so the peer goes away in the middle of it.
+        let body = vec![0u8; 8 * 1024 * 1024];
+        for &read in &[0, 16, 64 * 1024] {
+            let (p0, mut p1) = UnixStream::pair().unwrap();
+            let peer = thread::spawn(move || {
+                let mut buffer = vec![0; read];
+                p1.read_exact(&mut buffer).unwrap();
+            });
+
+            let mut conn0 = Connection::wrap(p0);
+            let msg = Message::method(None, None, "/", None, "Big", &body).unwrap();
+            conn0.enqueue_message(msg);
+            assert_eq!(conn0.try_flush().unwrap_err().kind(), ErrorKind::BrokenPipe);
+            peer.join().unwrap();
+            // The rest of the message can't be sent either.
+            assert!(conn0.needs_write());
+            assert_eq!(conn0.try_flush().unwrap_err().kind(), ErrorKind::BrokenPipe);
+        }
+        assert!(!sigpipe_pending());
+    }
+
+    #[test]
+    fn peer_closed_mid_message() {
+        let msg = Message::method(None, None, "/", None, "Big", &vec![0u8; 1024]).unwrap();
+
+        // In the middle of the primary header, and of the body.
+        for &len in &[8, 512] {
+            let (mut p0, p1) = UnixStream::pair().unwrap();
+            p0.write_all(&msg.as_bytes()[..len]).unwrap();
+            drop(p0);
+
+            let mut conn1 = Connection::wrap(p1);
+            match conn1.try_receive_message().unwrap_err() {
+                Error::Io(e) => assert_eq!(e.kind(), ErrorKind::BrokenPipe),
+                e => panic!("unexpected error: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn coalesced_flush() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+
+        let mut conn0 = Connection::wrap(p0);
+        let mut conn1 = Connection::wrap(p1);
+        conn0.set_write_coalescing(true);
+
+        // The FDs must arrive with the message carrying them, whichever write it's part of.
+        let stdout = std::io::stdout();
+        for i in 0..6u32 {
+            let msg = if i % 3 == 1 {
+                Message::method(None, None, "/", None, "WithFd", &(i, Fd::from(&stdout))).unwrap()
+            } else {
+                Message::method(None, None, "/", None, "Plain", &i).unwrap()
+            };
+            conn0.enqueue_message(msg);
+        }
+        assert_eq!(conn0.coalescable_count(), 1);
+        conn0.msg_out_buffer.pop_front();
+        assert_eq!(conn0.coalescable_count(), 3);
+        conn0.try_flush().unwrap();
+
+        for i in 1..6u32 {
+            let ret = conn1.try_receive_message().unwrap();
+            if i % 3 == 1 {
+                assert_eq!(ret.to_string(), "Method call WithFd");
+                assert_eq!(ret.counted_fds(), 1);
+                let (n, _): (u32, Fd) = ret.body().unwrap();
+                assert_eq!(n, i);
+            } else {
+                assert_eq!(ret.to_string(), "Method call Plain");
+                assert_eq!(ret.counted_fds(), 0);
+                assert_eq!(ret.body::<u32>().unwrap(), i);
+            }
+        }
+    }
+
+    #[test]
+    fn high_priority() {
+        let (p0, p1) = UnixStream::pair().unwrap();
+        p0.set_nonblocking(true).unwrap();
+        let p0_handle = p0.try_clone().unwrap();
+
+        let mut conn0 = Connection::wrap(p0);
+        let mut conn1 = Connection::wrap(p1);
+
+        // The first message is too big for the socket buffer, so it's still being written when the
+        // urgent ones are queued.
+        for i in 0..100u32 {
+            let data = vec![0u8; if i == 0 { 4 * 1024 * 1024 } else { 16 }];
+            let msg = Message::method(None, None, "/", None, "Bulk", &(i, data)).unwrap();
+            conn0.enqueue_message(msg);
+        }
+        let e = conn0.try_flush().unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::WouldBlock);
+        for i in 0..2u32 {
+            let msg = Message::method(None, None, "/", None, "Urgent", &i).unwrap();
+            conn0.enqueue_message_with_priority(msg, Priority::High);
+        }
+
+        let receiver = thread::spawn(move || {
+            (0..102)
+                .map(|_| conn1.try_receive_message().unwrap())
+                .collect::<Vec<_>>()
+        });
+        p0_handle.set_nonblocking(false).unwrap();
+        conn0.try_flush().unwrap();
+        let msgs = receiver.join().unwrap();
+
+        let urgent = msgs[1..3].iter().map(|m| m.body::<u32>().unwrap());
+        assert!(urgent.eq(0..2));
+        let bulk = msgs[..1]
+            .iter()
+            .chain(&msgs[3..])
+            .map(|m| m.body::<(u32, Vec<u8>)>().unwrap().0);
+        assert!(bulk.eq(0..100));
+        assert_eq!(conn0.high_priority_count, 0);
+    }
 }
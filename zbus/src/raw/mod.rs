@@ -1,5 +1,14 @@
+//! The low-level, poll-based API.
+//!
+//! [`Connection`] is a D-Bus connection that doesn't spawn any thread or task, nor depend on an
+//! async runtime. It's driven by the caller, based on the readiness of the socket, which makes it
+//! suitable for integration in a foreign event loop, e.g GLib's main loop through its C API.
+//!
+//! [`Connection`]: struct.Connection.html
+#[cfg(feature = "compression")]
+mod compression;
 mod connection;
-mod socket;
+pub(crate) mod socket;
 
 pub use connection::Connection;
 pub use socket::Socket;
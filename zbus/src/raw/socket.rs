@@ -1,6 +1,7 @@
 use async_io::Async;
 use std::{
-    io,
+    io::{self, Read, Write},
+    net::TcpStream,
     os::unix::{
         io::{AsRawFd, FromRawFd, RawFd},
         net::UnixStream,
@@ -9,6 +10,7 @@ use std::{
 
 use nix::{
     cmsg_space,
+    errno::Errno,
     sys::{
         socket::{recvmsg, sendmsg, ControlMessage, ControlMessageOwned, MsgFlags},
         uio::IoVec,
@@ -19,9 +21,14 @@ use crate::{utils::FDS_MAX, OwnedFd};
 
 /// Trait representing some transport layer over which the DBus protocol can be used
 ///
-/// The crate provides an implementation of it for std's `UnixStream` on unix platforms.
+/// The crate provides an implementation of it for std's `UnixStream` and `TcpStream` on unix
+/// platforms.
 /// You will want to implement this trait to integrate zbus with a async-runtime-aware
-/// implementation of the socket, for example.
+/// implementation of the socket, for example. Implementations needing to wait, e.g before retrying,
+/// can use [`zbus::sleep`] or [`zbus::timeout`], so they work under any runtime.
+///
+/// [`zbus::sleep`]: ../fn.sleep.html
+/// [`zbus::timeout`]: ../fn.timeout.html
 pub trait Socket: std::fmt::Debug + AsRawFd + Send + Sync {
     /// Attempt to receive a message from the socket
     ///
@@ -63,6 +70,102 @@ pub trait Socket: std::fmt::Debug + AsRawFd + Send + Sync {
     /// This is useful for having two independent handles to the socket, one for writing only and
     /// the other for reading only.
     fn try_clone(&self) -> io::Result<Box<dyn Socket>>;
+
+    /// Whether file descriptors can be passed over the socket.
+    ///
+    /// If not, the client side of the handshake doesn't negotiate their passing with the server.
+    /// The default implementation returns `true`.
+    fn can_pass_unix_fd(&self) -> bool {
+        true
+    }
+}
+
+/// The error of the operations on a socket whose peer is gone.
+pub(crate) fn peer_closed_error() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "socket closed")
+}
+
+/// Turn `e` into a [`peer_closed_error`] if it means the peer closed the socket or reset the
+/// connection, so the closing is reported the same way however it's noticed.
+pub(crate) fn map_peer_closed(e: io::Error) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::BrokenPipe
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::UnexpectedEof => peer_closed_error(),
+        _ => e,
+    }
+}
+
+/// Run `f` again for as long as it's interrupted by a signal (`EINTR`).
+pub(crate) fn retry_interrupted<T, F>(mut f: F) -> io::Result<T>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    loop {
+        match f() {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            res => return res,
+        }
+    }
+}
+
+/// Have writes to the socket `fd` fail with `EPIPE` once the peer is gone, rather than raise
+/// `SIGPIPE`, which kills the process unless it's handled.
+///
+/// That's only needed on the platforms without `MSG_NOSIGNAL`, which is passed to every
+/// `sendmsg` call elsewhere.
+pub(crate) fn set_no_sigpipe(fd: RawFd) -> io::Result<()> {
+    #[cfg(any(target_os = "ios", target_os = "macos"))]
+    {
+        use nix::libc::{c_int, c_void, setsockopt, socklen_t, SOL_SOCKET, SO_NOSIGPIPE};
+
+        let on: c_int = 1;
+        // SAFETY: The option value is a valid `c_int`, of the given size.
+        let res = unsafe {
+            setsockopt(
+                fd,
+                SOL_SOCKET,
+                SO_NOSIGPIPE,
+                &on as *const c_int as *const c_void,
+                std::mem::size_of::<c_int>() as socklen_t,
+            )
+        };
+        if res == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    #[cfg(not(any(target_os = "ios", target_os = "macos")))]
+    let _ = fd;
+
+    Ok(())
+}
+
+// The flags of the `sendmsg` calls: `MSG_NOSIGNAL` where it's supported (see `set_no_sigpipe`).
+#[cfg(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_os = "openbsd",
+))]
+fn send_flags() -> MsgFlags {
+    // SAFETY: `MSG_NOSIGNAL` is a valid flag of `sendmsg` on these platforms, nix just doesn't
+    // provide it.
+    unsafe { MsgFlags::from_bits_unchecked(nix::libc::MSG_NOSIGNAL) }
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_os = "openbsd",
+)))]
+fn send_flags() -> MsgFlags {
+    MsgFlags::empty()
 }
 
 impl Socket for Box<dyn Socket> {
@@ -81,6 +184,10 @@ impl Socket for Box<dyn Socket> {
     fn try_clone(&self) -> io::Result<Self> {
         (**self).try_clone()
     }
+
+    fn can_pass_unix_fd(&self) -> bool {
+        (**self).can_pass_unix_fd()
+    }
 }
 
 impl AsRawFd for Box<dyn Socket> {
@@ -94,12 +201,18 @@ impl Socket for UnixStream {
         let iov = [IoVec::from_mut_slice(buffer)];
         let mut cmsgspace = cmsg_space!([RawFd; FDS_MAX]);
 
-        match recvmsg(
-            self.as_raw_fd(),
-            &iov,
-            Some(&mut cmsgspace),
-            MsgFlags::empty(),
-        ) {
+        let res = loop {
+            match recvmsg(
+                self.as_raw_fd(),
+                &iov,
+                Some(&mut cmsgspace),
+                MsgFlags::empty(),
+            ) {
+                Err(nix::Error::Sys(Errno::EINTR)) => continue,
+                res => break res,
+            }
+        };
+        match res {
             Ok(msg) => {
                 let mut fds = vec![];
                 for cmsg in msg.cmsgs() {
@@ -130,7 +243,13 @@ impl Socket for UnixStream {
             vec![]
         };
         let iov = [IoVec::from_slice(buffer)];
-        match sendmsg(self.as_raw_fd(), &iov, &cmsg, MsgFlags::empty(), None) {
+        let res = loop {
+            match sendmsg(self.as_raw_fd(), &iov, &cmsg, send_flags(), None) {
+                Err(nix::Error::Sys(Errno::EINTR)) => continue,
+                res => break res,
+            }
+        };
+        match res {
             // can it really happen?
             Ok(0) => Err(io::Error::new(
                 io::ErrorKind::WriteZero,
@@ -151,6 +270,36 @@ impl Socket for UnixStream {
     }
 }
 
+impl Socket for TcpStream {
+    fn recvmsg(&mut self, buffer: &mut [u8]) -> io::Result<(usize, Vec<OwnedFd>)> {
+        retry_interrupted(|| self.read(buffer)).map(|n| (n, vec![]))
+    }
+
+    fn sendmsg(&mut self, buffer: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+        if !fds.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "file descriptors can't be sent over tcp",
+            ));
+        }
+
+        // `TcpStream` passes `MSG_NOSIGNAL` itself.
+        retry_interrupted(|| self.write(buffer))
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.shutdown(std::net::Shutdown::Both)
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Socket>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+
+    fn can_pass_unix_fd(&self) -> bool {
+        false
+    }
+}
+
 impl<S> Socket for Async<S>
 where
     S: Socket + AsRawFd,
@@ -170,4 +319,8 @@ where
     fn try_clone(&self) -> io::Result<Box<dyn Socket>> {
         Ok(Box::new(Async::new(self.get_ref().try_clone()?)?))
     }
+
+    fn can_pass_unix_fd(&self) -> bool {
+        self.get_ref().can_pass_unix_fd()
+    }
 }
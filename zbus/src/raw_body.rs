@@ -0,0 +1,121 @@
+use std::{convert::TryFrom, os::unix::io::RawFd, sync::Arc};
+
+use static_assertions::assert_impl_all;
+use zvariant::{
+    EncodingContext, EncodingFormat, Error as VariantError, Signature, StructureBuilder, Type,
+    Value,
+};
+
+use crate::{MessageError, Result};
+
+/// A message body, serialized ahead of time.
+///
+/// This is useful for sending the same body in many messages, e.g calling the same method on a lot
+/// of peers: the body is then serialized only once, instead of once per message. See
+/// [`Connection::call_method_raw_body`].
+///
+/// The encoded bytes are reference-counted, so cloning a `RawBody` is cheap.
+///
+/// As with the bodies passed to [`Message`] constructors, the file descriptors in the body are not
+/// duplicated and hence must remain open until all the messages using it are sent.
+///
+/// [`Connection::call_method_raw_body`]: struct.Connection.html#method.call_method_raw_body
+/// [`Message`]: struct.Message.html
+#[derive(Debug, Clone)]
+pub struct RawBody {
+    bytes: Arc<[u8]>,
+    fds: Arc<[RawFd]>,
+    signature: Signature<'static>,
+}
+
+assert_impl_all!(RawBody: Send, Sync, Unpin);
+
+impl RawBody {
+    /// Serialize `body`.
+    pub fn new<B>(body: &B) -> Result<Self>
+    where
+        B: serde::ser::Serialize + Type,
+    {
+        let ctxt = EncodingContext::<byteorder::NativeEndian>::new_dbus(0);
+        let (bytes, fds) = zvariant::to_bytes_fds(ctxt, body)?;
+
+        Self::from_bytes(ctxt, B::signature(), bytes, fds)
+    }
+
+    /// Serialize `args`, each as an argument of the body.
+    ///
+    /// This is for bodies only known at runtime, e.g the arguments of a method call typed in by a
+    /// user. The signature of the body is that of the values, one after the other: `args` are not
+    /// wrapped in variants, nor in a structure. E.g a string and a `u32` give a body of signature
+    /// `su`, rather than `av` or `(su)` for a `Vec<Value>` or a [`Structure`].
+    ///
+    /// [`Structure`]: https://docs.rs/zvariant/2/zvariant/struct.Structure.html
+    pub fn from_values(args: &[Value<'_>]) -> Result<Self> {
+        if args.is_empty() {
+            return Self::new(&());
+        }
+
+        let body = args
+            .iter()
+            .cloned()
+            .fold(StructureBuilder::new(), StructureBuilder::append_field)
+            .build();
+        let ctxt = EncodingContext::<byteorder::NativeEndian>::new_dbus(0);
+        let signature = body.signature();
+        let (bytes, fds) = zvariant::to_bytes_fds_for_signature(ctxt, &signature, &body)?;
+
+        Self::from_bytes(ctxt, signature, bytes, fds)
+    }
+
+    /// Create a body from its encoding, as produced by [`zvariant::to_bytes_fds`] for example.
+    ///
+    /// The body must be encoded in the D-Bus format, native byte order and from an 8-byte aligned
+    /// position, just like it would be in a message. Otherwise, an error is returned. The bytes
+    /// themselves are not checked against `signature`.
+    ///
+    /// A `signature` of a structure, i-e starting with `(`, is taken as the list of its fields, just
+    /// like the signature of a tuple when creating a message.
+    ///
+    /// [`zvariant::to_bytes_fds`]: https://docs.rs/zvariant/2/zvariant/fn.to_bytes_fds.html
+    pub fn from_bytes(
+        ctxt: EncodingContext<byteorder::NativeEndian>,
+        signature: Signature<'_>,
+        bytes: impl Into<Arc<[u8]>>,
+        fds: impl Into<Arc<[RawFd]>>,
+    ) -> Result<Self> {
+        let signature = signature.into_owned();
+        if ctxt.format() != EncodingFormat::DBus {
+            return Err(VariantError::IncompatibleFormat(signature, ctxt.format()).into());
+        }
+        if ctxt.position() % 8 != 0 {
+            return Err(VariantError::Message(format!(
+                "body serialized from unaligned position {}",
+                ctxt.position(),
+            ))
+            .into());
+        }
+        let bytes = bytes.into();
+        u32::try_from(bytes.len()).map_err(|_| MessageError::ExcessData)?;
+
+        Ok(Self {
+            bytes,
+            fds: fds.into(),
+            signature,
+        })
+    }
+
+    /// The encoded body.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The file descriptors referred to in the body.
+    pub fn fds(&self) -> &[RawFd] {
+        &self.fds
+    }
+
+    /// The signature of the body.
+    pub fn signature(&self) -> &Signature<'static> {
+        &self.signature
+    }
+}
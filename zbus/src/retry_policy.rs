@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use static_assertions::assert_impl_all;
+
+use crate::Error;
+
+// The backoff stops doubling past this, unless the initial one is longer already.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Policy for retrying failed method calls made through a proxy.
+///
+/// A retry policy is set on a proxy with [`ProxyBuilder::retry_policy`] and applies only to the
+/// methods the caller declares idempotent: the ones called with [`Proxy::call_idempotent`] and the
+/// proxy trait methods with the `idempotent` [`dbus_proxy`] attribute. This is mostly useful for
+/// calls to bus-activated services, which can fail while the service is still starting up.
+///
+/// Each attempt is a new method call, with its own serial number. Before the first retry, the
+/// proxy waits for the [`backoff`] duration and it doubles the wait for each subsequent retry, up
+/// to a minute (or the initial backoff, if longer).
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use zbus::{Connection, ProxyBuilder, RetryPolicy};
+/// let connection = Connection::new_session()?;
+/// let proxy: zbus::Proxy<'_> = ProxyBuilder::new_bare(&connection)
+///     .destination("org.freedesktop.zbus.Activatable")
+///     .path("/org/freedesktop/zbus/Activatable")?
+///     .interface("org.freedesktop.zbus.Activatable")
+///     .retry_policy(
+///         RetryPolicy::new(5)
+///             .backoff(Duration::from_millis(200))
+///             .deadline(Duration::from_secs(10)),
+///     )
+///     .build()?;
+/// let version: String = proxy.call_idempotent("Version", &())?;
+/// # Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+/// ```
+///
+/// [`ProxyBuilder::retry_policy`]: struct.ProxyBuilder.html#method.retry_policy
+/// [`Proxy::call_idempotent`]: struct.Proxy.html#method.call_idempotent
+/// [`dbus_proxy`]: attr.dbus_proxy.html
+/// [`backoff`]: #method.backoff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Duration,
+    retry_on: Vec<String>,
+    deadline: Option<Duration>,
+}
+
+assert_impl_all!(RetryPolicy: Send, Sync, Unpin);
+
+impl RetryPolicy {
+    /// Create a policy making at most `max_attempts` attempts, including the first call.
+    ///
+    /// By default, the backoff is 100ms, there is no overall deadline and the calls are retried on
+    /// these errors:
+    ///
+    /// * `org.freedesktop.DBus.Error.TimedOut`
+    /// * `org.freedesktop.DBus.Error.NoReply`
+    /// * `org.freedesktop.DBus.Error.ServiceUnknown`
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            backoff: Duration::from_millis(100),
+            retry_on: vec![
+                "org.freedesktop.DBus.Error.TimedOut".into(),
+                "org.freedesktop.DBus.Error.NoReply".into(),
+                "org.freedesktop.DBus.Error.ServiceUnknown".into(),
+            ],
+            deadline: None,
+        }
+    }
+
+    /// Set the delay before the first retry.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set the names of the D-Bus errors to retry on, replacing the default ones.
+    pub fn retry_on<I, N>(mut self, error_names: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<String>,
+    {
+        self.retry_on = error_names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the deadline for all the attempts together, starting from the first call.
+    ///
+    /// If the deadline is reached while an attempt is in progress, the call fails with an
+    /// [`Error::Io`] of kind [`TimedOut`]. No retry is made if its backoff would end after the
+    /// deadline.
+    ///
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    /// [`TimedOut`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.TimedOut
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// The maximum number of attempts, including the first call.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    pub(crate) fn backoff_for(&self, retry: u32) -> Duration {
+        let max = self.backoff.max(MAX_BACKOFF);
+
+        self.backoff
+            .checked_mul(2u32.saturating_pow(retry))
+            .map_or(max, |backoff| backoff.min(max))
+    }
+
+    pub(crate) fn overall_deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    // Whether `error` is worth retrying on.
+    pub(crate) fn should_retry(&self, error: &Error) -> bool {
+        match error {
+            Error::MethodError(name, _, _) => self.retry_on.iter().any(|n| n == name),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{RetryPolicy, MAX_BACKOFF};
+
+    #[test]
+    fn backoff_for() {
+        let policy = RetryPolicy::new(u32::MAX).backoff(Duration::from_millis(100));
+        assert_eq!(policy.backoff_for(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff_for(3), Duration::from_millis(800));
+        assert_eq!(policy.backoff_for(10), MAX_BACKOFF);
+        assert_eq!(policy.backoff_for(u32::MAX), MAX_BACKOFF);
+
+        let long = Duration::from_secs(u64::MAX);
+        let policy = RetryPolicy::new(u32::MAX).backoff(long);
+        assert_eq!(policy.backoff_for(0), long);
+        assert_eq!(policy.backoff_for(1), long);
+    }
+}
@@ -0,0 +1,220 @@
+#![cfg(feature = "sd-notify")]
+
+//! Notifying the service manager (`sd-notify` feature)
+//!
+//! This module implements the client side of the [`sd_notify`] protocol of systemd, to tell the
+//! service manager when the service is ready and that it's still alive:
+//!
+//! * [`ready`] sends `READY=1`, for services of `Type=notify`.
+//! * [`Watchdog`] sends `WATCHDOG=1` periodically, for services with `WatchdogSec=` set.
+//!
+//! The messages go to the socket in `$NOTIFY_SOCKET`. Without it, e.g when not run by systemd,
+//! nothing is sent and no error is returned.
+//!
+//! Services of `Type=dbus` are considered ready once they own their bus name instead, so the name
+//! should only be requested once all the objects are served. See
+//! [`ConnectionBuilder::request_name_on_build`] and the `activatable-service` example for this.
+//!
+//! [`sd_notify`]: https://www.freedesktop.org/software/systemd/man/sd_notify.html
+//! [`ready`]: fn.ready.html
+//! [`Watchdog`]: struct.Watchdog.html
+//! [`ConnectionBuilder::request_name_on_build`]: ../struct.ConnectionBuilder.html#method.request_name_on_build
+
+use std::{env, os::unix::net::UnixDatagram, time::Duration};
+
+use async_task::Task;
+use static_assertions::assert_impl_all;
+
+use crate::{azync, Result};
+
+/// Send `state` to the service manager, e.g `"READY=1\nSTATUS=Serving"`.
+///
+/// Returns `false` if `$NOTIFY_SOCKET` isn't set, in which case nothing is sent.
+pub fn notify(state: &str) -> Result<bool> {
+    let path = match env::var_os("NOTIFY_SOCKET") {
+        Some(path) => path,
+        None => return Ok(false),
+    };
+    let socket = UnixDatagram::unbound()?;
+
+    // A leading `@` denotes an abstract socket, which std doesn't support.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    {
+        use std::os::unix::{ffi::OsStrExt, io::AsRawFd};
+
+        use nix::sys::socket::{sendto, MsgFlags, SockAddr, UnixAddr};
+
+        if let Some(name) = path.as_bytes().strip_prefix(b"@") {
+            let addr = SockAddr::Unix(UnixAddr::new_abstract(name)?);
+            sendto(
+                socket.as_raw_fd(),
+                state.as_bytes(),
+                &addr,
+                MsgFlags::empty(),
+            )?;
+
+            return Ok(true);
+        }
+    }
+    socket.send_to(state.as_bytes(), path)?;
+
+    Ok(true)
+}
+
+/// Tell the service manager that the service is ready, by sending `READY=1`.
+///
+/// Returns `false` if `$NOTIFY_SOCKET` isn't set, in which case nothing is sent.
+pub fn ready() -> Result<bool> {
+    notify("READY=1")
+}
+
+/// The interval the service manager expects `WATCHDOG=1` to be sent within, if any.
+///
+/// This is taken from `$WATCHDOG_USEC`, unless `$WATCHDOG_PID` is set to another process.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Some(pid) = env::var_os("WATCHDOG_PID") {
+        if pid.to_str()?.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    let usec = env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if usec == 0 {
+        return None;
+    }
+
+    Some(Duration::from_micros(usec))
+}
+
+/// A task sending `WATCHDOG=1` to the service manager periodically.
+///
+/// The task runs on the executor of the connection it was started with, at half the
+/// [`watchdog_interval`]. With the `internal-executor` feature, it therefore runs as long as the
+/// connection does, while otherwise the executor has to be run by the application, as for the rest
+/// of the connection. The task is stopped when the `Watchdog` is dropped.
+///
+/// # Example
+///
+/// ```no_run
+///# use std::error::Error;
+/// use zbus::{sd_notify::{self, Watchdog}, Connection};
+///
+/// let connection = Connection::new_session()?;
+/// let _watchdog = Watchdog::start(connection.inner());
+/// sd_notify::ready()?;
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+///
+/// [`watchdog_interval`]: fn.watchdog_interval.html
+#[derive(Debug)]
+pub struct Watchdog {
+    interval: Duration,
+    _task: Task<()>,
+}
+
+assert_impl_all!(Watchdog: Send, Sync, Unpin);
+
+impl Watchdog {
+    /// Start the task on the executor of `conn`, at half the [`watchdog_interval`].
+    ///
+    /// Returns `None` if the service manager doesn't expect `WATCHDOG=1`.
+    ///
+    /// [`watchdog_interval`]: fn.watchdog_interval.html
+    pub fn start(conn: &azync::Connection) -> Option<Self> {
+        watchdog_interval().map(|interval| Self::with_interval(conn, interval / 2))
+    }
+
+    /// Start the task on the executor of `conn`, sending `WATCHDOG=1` every `interval`.
+    ///
+    /// Unlike [`start`], this doesn't check whether the service manager expects it.
+    ///
+    /// [`start`]: #method.start
+    pub fn with_interval(conn: &azync::Connection, interval: Duration) -> Self {
+        let task = conn.executor().spawn(async move {
+            loop {
+                if let Err(e) = notify("WATCHDOG=1") {
+                    log::warn!(target: crate::logging::CONNECTION, "Failed to send WATCHDOG=1: {}", e);
+                }
+                crate::sleep(interval).await;
+            }
+        });
+
+        Self {
+            interval,
+            _task: task,
+        }
+    }
+
+    /// The interval between the notifications.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        os::unix::net::{UnixDatagram, UnixStream},
+        thread,
+        time::Duration,
+    };
+
+    use ntest::timeout;
+    use test_env_log::test;
+
+    use super::{notify, ready, watchdog_interval, Watchdog};
+    use crate::{Connection, Guid};
+
+    // All in one test, as the environment is shared by the threads of the tests.
+    #[test]
+    #[timeout(2000)]
+    fn sd_notify() {
+        env::remove_var("NOTIFY_SOCKET");
+        assert!(!ready().unwrap());
+
+        let dir = env::temp_dir().join(format!("zbus-sd-notify-{}", Guid::generate()));
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("notify");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        env::set_var("NOTIFY_SOCKET", &path);
+
+        let mut buf = [0; 64];
+        assert!(ready().unwrap());
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+        assert!(notify("STATUS=Serving").unwrap());
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"STATUS=Serving");
+
+        env::remove_var("WATCHDOG_PID");
+        env::remove_var("WATCHDOG_USEC");
+        assert_eq!(watchdog_interval(), None);
+        env::set_var("WATCHDOG_USEC", "2000000");
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(2)));
+        env::set_var("WATCHDOG_PID", std::process::id().to_string());
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(2)));
+        env::set_var("WATCHDOG_PID", "1");
+        assert_eq!(watchdog_interval(), None);
+        env::remove_var("WATCHDOG_PID");
+
+        // The watchdog task runs on the executor of the connection.
+        let (p0, p1) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+        let server_thread = thread::spawn(move || Connection::new_unix_server(p0, &guid).unwrap());
+        let conn = Connection::new_unix_client(p1, false).unwrap();
+        let _server = server_thread.join().unwrap();
+        let watchdog = Watchdog::start(conn.inner()).unwrap();
+        assert_eq!(watchdog.interval(), Duration::from_secs(1));
+        drop(watchdog);
+        let watchdog = Watchdog::with_interval(conn.inner(), Duration::from_millis(10));
+        for _ in 0..2 {
+            let len = socket.recv(&mut buf).unwrap();
+            assert_eq!(&buf[..len], b"WATCHDOG=1");
+        }
+        drop(watchdog);
+
+        env::remove_var("WATCHDOG_USEC");
+        env::remove_var("NOTIFY_SOCKET");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
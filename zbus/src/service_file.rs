@@ -0,0 +1,197 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{Error, Result};
+
+const SERVICE_GROUP: &str = "[D-Bus Service]";
+
+/// Render a D-Bus [service file], for the bus to activate the service owning the well-known
+/// `name`.
+///
+/// The bus runs `exec` to start the service, unless `systemd_service` is given and the bus is
+/// running under systemd, in which case the bus asks systemd to start this unit instead. Keep the
+/// name in a constant used by the service itself, so the service file always matches the name the
+/// service requests:
+///
+/// ```
+///# use std::error::Error;
+/// const NAME: &str = "org.zbus.Example";
+///
+/// let file = zbus::service_file(NAME, "/usr/libexec/zbus-example", Some("zbus-example.service"))?;
+/// assert_eq!(
+///     file,
+///     "[D-Bus Service]\n\
+///      Name=org.zbus.Example\n\
+///      Exec=/usr/libexec/zbus-example\n\
+///      SystemdService=zbus-example.service\n",
+/// );
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+///
+/// Fails with [`Error::InvalidName`] if `name` isn't a valid well-known name, or if `exec` or
+/// `systemd_service` are empty or span several lines.
+///
+/// Note that services activated on the system bus also need a `User` key, saying which user to
+/// run the service as. Append it to the returned string for these.
+///
+/// [service file]: https://dbus.freedesktop.org/doc/dbus-specification.html#message-bus-starting-services
+/// [`Error::InvalidName`]: enum.Error.html#variant.InvalidName
+pub fn service_file(name: &str, exec: &str, systemd_service: Option<&str>) -> Result<String> {
+    if !is_well_known_name(name) {
+        return Err(Error::InvalidName(format!(
+            "`{}` is not a valid well-known name",
+            name
+        )));
+    }
+    check_value("Exec", exec)?;
+
+    let mut file = format!("{}\nName={}\nExec={}\n", SERVICE_GROUP, name, exec);
+    if let Some(unit) = systemd_service {
+        check_value("SystemdService", unit)?;
+        file.push_str(&format!("SystemdService={}\n", unit));
+    }
+
+    Ok(file)
+}
+
+/// Write the D-Bus service file for `name` to `dir`, returning its path.
+///
+/// The file is named after the service, as the bus requires. See [`service_file`] for the
+/// arguments. This is meant to be called from build scripts, to generate the service files of a
+/// crate along with its code:
+///
+/// ```no_run
+///# use std::error::Error;
+/// // In build.rs
+/// let out_dir = std::env::var("OUT_DIR")?;
+/// zbus::write_service_file(out_dir, "org.zbus.Example", "/usr/libexec/zbus-example", None)?;
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+///
+/// [`service_file`]: fn.service_file.html
+pub fn write_service_file<P: AsRef<Path>>(
+    dir: P,
+    name: &str,
+    exec: &str,
+    systemd_service: Option<&str>,
+) -> Result<PathBuf> {
+    let file = service_file(name, exec, systemd_service)?;
+    let path = dir.as_ref().join(format!("{}.service", name));
+    fs::write(&path, file)?;
+
+    Ok(path)
+}
+
+fn check_value(key: &str, value: &str) -> Result<()> {
+    if value.trim().is_empty() || value.contains('\n') {
+        return Err(Error::InvalidName(format!(
+            "invalid `{}` value for a service file: {:?}",
+            key, value
+        )));
+    }
+
+    Ok(())
+}
+
+// Whether `name` is a valid well-known bus name, as per the specification.
+pub(crate) fn is_well_known_name(name: &str) -> bool {
+    let is_element = |e: &str| {
+        !e.is_empty()
+            && !e.starts_with(|c: char| c.is_ascii_digit())
+            && e.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    };
+
+    name.len() <= 255 && name.contains('.') && name.split('.').all(is_element)
+}
+
+// The name and the command of the service described by a service file.
+#[cfg(any(test, feature = "test-bus"))]
+pub(crate) fn parse_service_file(contents: &str) -> Result<(String, String)> {
+    let mut in_group = false;
+    let mut name = None;
+    let mut exec = None;
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            in_group = line == SERVICE_GROUP;
+
+            continue;
+        }
+        if !in_group {
+            continue;
+        }
+        let mut entry = line.splitn(2, '=');
+        match (entry.next(), entry.next()) {
+            (Some("Name"), Some(value)) => name = Some(value.to_owned()),
+            (Some("Exec"), Some(value)) => exec = Some(value.to_owned()),
+            _ => (),
+        }
+    }
+
+    match (name, exec) {
+        (Some(name), Some(exec)) if is_well_known_name(&name) => Ok((name, exec)),
+        _ => Err(Error::InvalidName(
+            "service file without a valid `Name` and an `Exec` command".into(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn well_known_names() {
+        for name in &["org.zbus.Test", "a.b", "org.zbus.test_2-x", "_a.-b"] {
+            assert!(is_well_known_name(name), "{}", name);
+        }
+        for name in &[
+            "",
+            "org",
+            ":1.42",
+            "org..zbus",
+            ".org.zbus",
+            "org.2zbus",
+            "org.zb/us",
+        ] {
+            assert!(!is_well_known_name(name), "{}", name);
+        }
+        assert!(!is_well_known_name(&format!("org.{}", "a".repeat(252))));
+    }
+
+    #[test]
+    fn service_files() {
+        let file = service_file("org.zbus.Test", "/usr/bin/zbus-test --activated", None).unwrap();
+        assert_eq!(
+            file,
+            "[D-Bus Service]\nName=org.zbus.Test\nExec=/usr/bin/zbus-test --activated\n"
+        );
+        assert_eq!(
+            parse_service_file(&file).unwrap(),
+            (
+                String::from("org.zbus.Test"),
+                String::from("/usr/bin/zbus-test --activated")
+            ),
+        );
+
+        assert!(matches!(
+            service_file("zbus", "/usr/bin/zbus-test", None),
+            Err(Error::InvalidName(_))
+        ));
+        assert!(matches!(
+            service_file("org.zbus.Test", "", None),
+            Err(Error::InvalidName(_))
+        ));
+        assert!(matches!(
+            service_file("org.zbus.Test", "/bin/true\nUser=root", None),
+            Err(Error::InvalidName(_))
+        ));
+        assert!(parse_service_file("[Unit]\nName=org.zbus.Test\nExec=/bin/true\n").is_err());
+    }
+}
@@ -0,0 +1,357 @@
+#![cfg(feature = "tcp-transport")]
+
+use async_io::Async;
+use nix::{
+    errno::Errno,
+    libc,
+    sys::socket::{self, AddressFamily, InetAddr, SockAddr, SockFlag, SockType},
+};
+use std::{
+    io::{self, ErrorKind},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
+    thread,
+    time::Duration,
+};
+
+use crate::{address::TcpOptions, Error, Result};
+
+/// The IP family a `tcp` address is restricted to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TcpFamily {
+    Ipv4,
+    Ipv6,
+}
+
+/// Connect to the first address `host` and `port` resolve to that works.
+pub(crate) async fn connect(
+    host: &str,
+    port: u16,
+    family: Option<TcpFamily>,
+    options: &TcpOptions,
+) -> Result<Async<TcpStream>> {
+    let mut last_error = None;
+    for addr in resolve(host, port).await? {
+        let family_matches = match family {
+            Some(TcpFamily::Ipv4) => addr.is_ipv4(),
+            Some(TcpFamily::Ipv6) => addr.is_ipv6(),
+            None => true,
+        };
+        // A socket bound to a source address can only reach addresses of the same family.
+        let source_matches = options
+            .source_address
+            .map_or(true, |source| source.is_ipv4() == addr.is_ipv4());
+        if !family_matches || !source_matches {
+            continue;
+        }
+
+        match connect_addr(addr, options).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        Error::Address(format!("no suitable address for the tcp host `{}`", host))
+    }))
+}
+
+async fn resolve(host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+    if let Ok(ip) = host.parse() {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+
+    // Name resolution blocks, so it's done on a thread of its own.
+    let (sender, receiver) = async_channel::bounded(1);
+    let host = host.to_owned();
+    thread::spawn(move || {
+        let addrs = (host.as_str(), port)
+            .to_socket_addrs()
+            .map(|addrs| addrs.collect::<Vec<_>>());
+        let _ = sender.try_send(addrs);
+    });
+    let addrs = receiver
+        .recv()
+        .await
+        .map_err(|_| Error::Address("tcp host name resolution failed".into()))??;
+
+    Ok(addrs)
+}
+
+async fn connect_addr(addr: SocketAddr, options: &TcpOptions) -> Result<Async<TcpStream>> {
+    let connect = async {
+        if options.source_address.is_none() && options.bind_device.is_none() {
+            return Async::<TcpStream>::connect(addr).await;
+        }
+
+        let stream = Async::new(bound_socket(addr, options)?)?;
+        let addr = SockAddr::new_inet(InetAddr::from_std(&addr));
+        match socket::connect(stream.as_raw_fd(), &addr) {
+            Ok(()) => (),
+            Err(nix::Error::Sys(Errno::EINPROGRESS)) => {
+                stream.writable().await?;
+                if let Some(e) = stream.get_ref().take_error()? {
+                    return Err(e);
+                }
+            }
+            Err(nix::Error::Sys(e)) => return Err(e.into()),
+            Err(e) => return Err(io::Error::new(ErrorKind::Other, e)),
+        }
+
+        Ok(stream)
+    };
+    let stream = match options.connect_timeout {
+        Some(timeout) => match crate::timeout(timeout, connect).await {
+            Some(res) => res?,
+            None => {
+                return Err(Error::Io(io::Error::new(
+                    ErrorKind::TimedOut,
+                    "tcp connection timed out",
+                )))
+            }
+        },
+        None => connect.await?,
+    };
+
+    let stream = stream.into_inner()?;
+    set_options(&stream, options)?;
+    let stream = match options.map_stream {
+        Some(map) => map(stream),
+        None => stream,
+    };
+
+    Ok(Async::new(stream)?)
+}
+
+// A socket for `addr`, bound as per `options`. Not connected yet.
+fn bound_socket(addr: SocketAddr, options: &TcpOptions) -> io::Result<TcpStream> {
+    let family = match addr {
+        SocketAddr::V4(_) => AddressFamily::Inet,
+        SocketAddr::V6(_) => AddressFamily::Inet6,
+    };
+    let fd =
+        socket::socket(family, SockType::Stream, SockFlag::empty(), None).map_err(nix_error)?;
+    // SAFETY: `fd` is a valid socket that we own. It's closed along with the stream on errors.
+    let stream = unsafe { TcpStream::from_raw_fd(fd) };
+
+    if let Some(device) = &options.bind_device {
+        bind_device(fd, device)?;
+    }
+    if let Some(source) = options.source_address {
+        let source = SockAddr::new_inet(InetAddr::from_std(&SocketAddr::new(source, 0)));
+        socket::bind(fd, &source).map_err(nix_error)?;
+    }
+
+    Ok(stream)
+}
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn bind_device(fd: RawFd, device: &str) -> io::Result<()> {
+    // SAFETY: the option value is the name of the device, of the given length.
+    let res = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_BINDTODEVICE,
+            device.as_ptr() as *const libc::c_void,
+            device.len() as libc::socklen_t,
+        )
+    };
+
+    if res == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+fn bind_device(_fd: RawFd, _device: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Other,
+        "binding to a device is only supported on Linux",
+    ))
+}
+
+fn set_options(stream: &TcpStream, options: &TcpOptions) -> io::Result<()> {
+    if let Some(nodelay) = options.nodelay {
+        stream.set_nodelay(nodelay)?;
+    }
+
+    if let Some(idle) = options.keepalive {
+        let fd = stream.as_raw_fd();
+        set_int_option(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE, 1)?;
+        set_keepalive_times(fd, idle, options.keepalive_interval)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "macos",
+    target_os = "ios",
+))]
+fn set_keepalive_times(fd: RawFd, idle: Duration, interval: Option<Duration>) -> io::Result<()> {
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    const TCP_KEEPIDLE: libc::c_int = libc::TCP_KEEPALIVE;
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    const TCP_KEEPIDLE: libc::c_int = libc::TCP_KEEPIDLE;
+
+    set_int_option(fd, libc::IPPROTO_TCP, TCP_KEEPIDLE, seconds(idle))?;
+    if let Some(interval) = interval {
+        set_int_option(
+            fd,
+            libc::IPPROTO_TCP,
+            libc::TCP_KEEPINTVL,
+            seconds(interval),
+        )?;
+    }
+
+    Ok(())
+}
+
+// Other platforms have no per-socket setting of the keepalive times.
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "macos",
+    target_os = "ios",
+)))]
+fn set_keepalive_times(_fd: RawFd, _idle: Duration, _interval: Option<Duration>) -> io::Result<()> {
+    Ok(())
+}
+
+// The options take whole seconds, of at least one.
+fn seconds(duration: Duration) -> libc::c_int {
+    duration.as_secs().max(1).min(libc::c_int::MAX as u64) as libc::c_int
+}
+
+fn set_int_option(
+    fd: RawFd,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+) -> io::Result<()> {
+    // SAFETY: the option value is a `c_int`, as these options expect.
+    let res = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+
+    if res == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+fn nix_error(e: nix::Error) -> io::Error {
+    match e {
+        nix::Error::Sys(e) => e.into(),
+        e => io::Error::new(ErrorKind::Other, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, TcpListener, TcpStream},
+        os::unix::io::AsRawFd,
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use async_io::{block_on, Async};
+    use nix::{
+        sys::socket::{getsockopt, sockopt},
+        unistd::Uid,
+    };
+    use ntest::timeout;
+    use test_env_log::test;
+
+    use crate::{
+        azync::{self, Authenticated},
+        raw::Socket,
+        ConnectionBuilder, Error, Guid, NATIVE_ENDIAN_SIG,
+    };
+
+    static MAPPED: AtomicBool = AtomicBool::new(false);
+
+    fn map(stream: TcpStream) -> TcpStream {
+        MAPPED.store(true, Ordering::SeqCst);
+
+        stream
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn connect() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = format!(
+            "tcp:host=localhost,port={},family=ipv4",
+            listener.local_addr().unwrap().port()
+        );
+
+        let server_thread = thread::spawn(move || {
+            let (stream, peer) = listener.accept().unwrap();
+            assert_eq!(peer.ip(), Ipv4Addr::LOCALHOST);
+
+            block_on(async {
+                let socket = Async::new(Box::new(stream) as Box<dyn Socket>)?;
+                let auth =
+                    Authenticated::server(socket, Guid::generate(), Uid::current().into()).await?;
+                let conn = azync::Connection::new(auth, false, false, NATIVE_ENDIAN_SIG).await?;
+                conn.emit_signal(None, "/", "org.zbus.Tcp", "Hello", &())
+                    .await?;
+
+                Ok::<_, Error>(conn)
+            })
+            .unwrap()
+        });
+
+        let conn = ConnectionBuilder::address(&address)
+            .p2p()
+            .tcp_nodelay(true)
+            .tcp_keepalive(Duration::from_secs(30))
+            .tcp_keepalive_interval(Duration::from_secs(5))
+            .tcp_connect_timeout(Duration::from_secs(5))
+            .tcp_source_address(Ipv4Addr::LOCALHOST.into())
+            .map_tcp_stream(map)
+            .build()
+            .unwrap();
+        assert!(MAPPED.load(Ordering::SeqCst));
+        let fd = conn.as_raw_fd();
+        assert!(getsockopt(fd, sockopt::TcpNoDelay).unwrap());
+        assert!(getsockopt(fd, sockopt::KeepAlive).unwrap());
+
+        let msg = conn.receive_message().unwrap();
+        assert_eq!(msg.header().unwrap().member().unwrap(), Some("Hello"));
+        drop(server_thread.join().unwrap());
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn no_suitable_address() {
+        let res = ConnectionBuilder::address("tcp:host=127.0.0.1,port=1,family=ipv6")
+            .p2p()
+            .build();
+        match res.map(|_| ()).unwrap_err() {
+            Error::Address(e) => {
+                assert_eq!(e, "no suitable address for the tcp host `127.0.0.1`")
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+}
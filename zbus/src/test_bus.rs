@@ -0,0 +1,1450 @@
+#![cfg(any(test, feature = "test-bus"))]
+
+//! A minimal in-process message bus, for tests.
+//!
+//! [`TestBus`] allows testing code that needs a message bus, without depending on a session bus
+//! being available. It only implements what tests typically rely on and is not meant to be used as
+//! an actual bus. For code that doesn't need a bus at all, [`p2p_pair`] gives two connections to
+//! each other.
+//!
+//! This module is only available with the `test-bus` feature.
+//!
+//! [`TestBus`]: struct.TestBus.html
+//! [`p2p_pair`]: fn.p2p_pair.html
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    env, fs,
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    process::Command,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use enumflags2::BitFlags;
+use static_assertions::assert_impl_all;
+use zvariant::{Signature, Value, Walker};
+
+use crate::{
+    fdo::{self, ReleaseNameReply, RequestNameFlags, RequestNameReply},
+    logging,
+    service_file::{is_well_known_name, parse_service_file},
+    Connection, Guid, Message, MessageFlags, MessageHeader, MessageType, Result,
+};
+
+const BUS_NAME: &str = "org.freedesktop.DBus";
+const BUS_PATH: &str = "/org/freedesktop/DBus";
+const BUS_INTERFACE: &str = "org.freedesktop.DBus";
+const PEER_INTERFACE: &str = "org.freedesktop.DBus.Peer";
+const LOCK_PANIC_MSG: &str = "lock poisoned";
+// The replies of `StartServiceByName`.
+const START_REPLY_SUCCESS: u32 = 1;
+const START_REPLY_ALREADY_RUNNING: u32 = 2;
+
+/// A message bus running in the current process, for tests.
+///
+/// The bus listens on a unix socket in the temporary directory and serves each client connection
+/// on its own thread. It supports:
+///
+/// * `Hello` and the unique names of the connections.
+/// * Name ownership through `RequestName`, `ReleaseName`, `GetNameOwner`, `NameHasOwner`,
+///   `ListNames` and `ListQueuedOwners`, including the queueing and replacement of owners.
+/// * Routing of messages by their destination.
+/// * Broadcasting of signals to the connections with a matching rule, added with `AddMatch`. Rules
+///   can match on the `type`, `sender`, `interface`, `member`, `path`, `path_namespace`,
+///   `destination` and `argN` (string arguments only) keys.
+/// * The `NameOwnerChanged`, `NameAcquired` and `NameLost` signals.
+/// * Activation of services through `StartServiceByName`, from the service files added with
+///   [`add_service_file`]. The `Exec` command is split on whitespace, without any unquoting.
+///
+/// Everything else, including the auto-starting of services on messages to their name, policies
+/// and eavesdropping, is missing.
+///
+/// The bus is stopped and all its connections closed when it's dropped.
+///
+/// # Example
+///
+/// ```
+///# use std::error::Error;
+/// use zbus::{fdo, test_bus::TestBus};
+///
+/// let bus = TestBus::new()?;
+/// let conn = bus.connect()?;
+/// let proxy = fdo::DBusProxy::new(&conn)?;
+/// proxy.request_name("org.zbus.TestBus", fdo::RequestNameFlags::DoNotQueue.into())?;
+/// assert_eq!(
+///     proxy.get_name_owner("org.zbus.TestBus")?,
+///     conn.unique_name().unwrap(),
+/// );
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+///
+/// [`add_service_file`]: #method.add_service_file
+#[derive(Debug)]
+pub struct TestBus {
+    address: String,
+    socket_path: PathBuf,
+    bus: Arc<Bus>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+assert_impl_all!(TestBus: Send, Sync, Unpin);
+
+impl TestBus {
+    /// Start a new bus.
+    pub fn new() -> Result<Self> {
+        let guid = Guid::generate();
+        let socket_path = env::temp_dir().join(format!("zbus-test-bus-{}", guid));
+        let listener = UnixListener::bind(&socket_path)?;
+        let address = format!("unix:path={},guid={}", socket_path.display(), guid);
+
+        let bus = Arc::new(Bus {
+            guid,
+            address: address.clone(),
+            stopped: AtomicBool::new(false),
+            state: Mutex::new(State::default()),
+        });
+        let acceptor = {
+            let bus = bus.clone();
+
+            thread::Builder::new()
+                .name("zbus::TestBus::accept".into())
+                .spawn(move || bus.accept(listener))
+        };
+        let acceptor = match acceptor {
+            Ok(acceptor) => acceptor,
+            Err(e) => {
+                let _ = fs::remove_file(&socket_path);
+
+                return Err(e.into());
+            }
+        };
+
+        Ok(Self {
+            address,
+            socket_path,
+            bus,
+            acceptor: Some(acceptor),
+        })
+    }
+
+    /// The address of the bus, to connect to it with [`Connection::new_for_address`].
+    ///
+    /// [`Connection::new_for_address`]: ../struct.Connection.html#method.new_for_address
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Create a new connection to the bus.
+    pub fn connect(&self) -> Result<Connection> {
+        Connection::new_for_address(&self.address, true)
+    }
+
+    /// Make the service described by the service file `contents` activatable.
+    ///
+    /// The service is started with the `DBUS_STARTER_ADDRESS` environment variable set to the
+    /// address of the bus, so it can connect with [`Connection::new_starter`]. The service file
+    /// of an already activatable name replaces the previous one.
+    ///
+    /// [`Connection::new_starter`]: ../struct.Connection.html#method.new_starter
+    pub fn add_service_file(&self, contents: &str) -> Result<()> {
+        let (name, exec) = parse_service_file(contents)?;
+        self.bus
+            .state
+            .lock()
+            .expect(LOCK_PANIC_MSG)
+            .services
+            .insert(name, exec);
+
+        Ok(())
+    }
+}
+
+impl Drop for TestBus {
+    fn drop(&mut self) {
+        self.bus.stopped.store(true, Ordering::SeqCst);
+        // Wake up the acceptor thread, so it notices we're stopping.
+        if UnixStream::connect(&self.socket_path).is_ok() {
+            if let Some(acceptor) = self.acceptor.take() {
+                let _ = acceptor.join();
+            }
+        }
+        let _ = fs::remove_file(&self.socket_path);
+
+        // The client threads exit once their socket is closed.
+        let state = self.bus.state.lock().expect(LOCK_PANIC_MSG);
+        for socket in &state.sockets {
+            let _ = socket.shutdown(std::net::Shutdown::Both);
+        }
+    }
+}
+
+/// Create a pair of peer-to-peer connections to each other, over a unix socket pair.
+///
+/// The first connection is the server side of the pair, the second one the client side. No bus is
+/// involved, so the connections have no unique name and all messages from one go to the other.
+///
+/// # Example
+///
+/// ```
+///# use std::error::Error;
+/// use zbus::test_bus::p2p_pair;
+///
+/// let (server, client) = p2p_pair()?;
+/// client.emit_signal(None, "/org/zbus/Example", "org.zbus.Example", "Hello", &())?;
+/// let msg = server.receive_message()?;
+/// assert_eq!(msg.header()?.member()?, Some("Hello"));
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+pub fn p2p_pair() -> Result<(Connection, Connection)> {
+    let (p0, p1) = UnixStream::pair()?;
+    // Both sides of the handshake have to run at the same time.
+    let server = thread::spawn(move || Connection::new_unix_server(p0, &Guid::generate()));
+    let client = Connection::new_unix_client(p1, false);
+    let server = server.join().expect("server handshake thread panicked");
+
+    Ok((server?, client?))
+}
+
+/// Check that `name` gets activated on the bus of `conn`, returning the unique name of its owner.
+///
+/// This works with [`TestBus`] and actual buses alike. The name must be activatable and not owned
+/// yet. It's then started through `StartServiceByName`, and the `NameOwnerChanged` signals are
+/// expected to show a new connection to the bus and then this connection acquiring `name`.
+///
+/// # Panics
+///
+/// If any of these expectations isn't met. Errors of the calls to the bus are returned instead.
+///
+/// # Example
+///
+/// ```no_run
+///# use std::error::Error;
+/// use zbus::{service_file, test_bus::{assert_activatable, TestBus}};
+///
+/// let bus = TestBus::new()?;
+/// bus.add_service_file(&service_file("org.zbus.Example", "/usr/libexec/zbus-example", None)?)?;
+/// let conn = bus.connect()?;
+/// let owner = assert_activatable(&conn, "org.zbus.Example")?;
+///# Ok::<_, Box<dyn Error + Send + Sync>>(())
+/// ```
+///
+/// [`TestBus`]: struct.TestBus.html
+pub fn assert_activatable(conn: &Connection, name: &str) -> Result<String> {
+    let proxy = fdo::DBusProxy::new(conn)?;
+    assert!(
+        proxy.list_activatable_names()?.iter().any(|n| n == name),
+        "`{}` is not activatable",
+        name,
+    );
+    assert!(
+        !proxy.name_has_owner(name)?,
+        "`{}` is already owned, so it can't be activated",
+        name,
+    );
+
+    let rule = format!(
+        "type='signal',sender='{}',interface='{}',member='NameOwnerChanged'",
+        BUS_NAME, BUS_INTERFACE,
+    );
+    proxy.add_match(&rule)?;
+    let owner = wait_for_activation(conn, &proxy, name);
+    proxy.remove_match(&rule)?;
+    let owner = owner?;
+    assert_eq!(proxy.get_name_owner(name)?, owner);
+
+    Ok(owner)
+}
+
+fn wait_for_activation(
+    conn: &Connection,
+    proxy: &fdo::DBusProxy<'_>,
+    name: &str,
+) -> Result<String> {
+    let reply = proxy.start_service_by_name(name, 0)?;
+    assert_eq!(
+        reply, START_REPLY_SUCCESS,
+        "`{}` was started by someone else",
+        name,
+    );
+
+    let mut connected = vec![];
+    loop {
+        let msg = conn.receive_message()?;
+        let header = msg.header()?;
+        if header.message_type()? != MessageType::Signal
+            || header.interface()? != Some(BUS_INTERFACE)
+            || header.member()? != Some("NameOwnerChanged")
+        {
+            continue;
+        }
+
+        let (changed, old_owner, new_owner): (&str, &str, &str) = msg.body()?;
+        if changed.starts_with(':') && old_owner.is_empty() {
+            connected.push(new_owner.to_owned());
+        } else if changed == name {
+            assert!(
+                old_owner.is_empty() && connected.iter().any(|c| c == new_owner),
+                "`{}` went from `{}` to `{}`, rather than to a newly connected service",
+                name,
+                old_owner,
+                new_owner,
+            );
+
+            return Ok(new_owner.to_owned());
+        }
+    }
+}
+
+// A message to send to a client.
+type Delivery = (Connection, Message);
+
+#[derive(Debug)]
+struct Bus {
+    guid: Guid,
+    address: String,
+    stopped: AtomicBool,
+    state: Mutex<State>,
+}
+
+impl Bus {
+    fn accept(self: Arc<Self>, listener: UnixListener) {
+        for stream in listener.incoming() {
+            if self.stopped.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!(
+                        target: logging::CONNECTION,
+                        "Failed to accept a test bus connection: {}",
+                        e
+                    );
+
+                    continue;
+                }
+            };
+            let bus = self.clone();
+            let spawned = thread::Builder::new()
+                .name("zbus::TestBus::client".into())
+                .spawn(move || bus.serve(stream));
+            if let Err(e) = spawned {
+                log::warn!(
+                    target: logging::CONNECTION,
+                    "Failed to start a test bus client thread: {}",
+                    e
+                );
+            }
+        }
+    }
+
+    fn serve(self: &Arc<Self>, stream: UnixStream) {
+        match stream.try_clone() {
+            Ok(socket) => self
+                .state
+                .lock()
+                .expect(LOCK_PANIC_MSG)
+                .sockets
+                .push(socket),
+            Err(e) => {
+                log::warn!(target: logging::CONNECTION, "Failed to clone a test bus socket: {}", e);
+
+                return;
+            }
+        }
+        let conn = match Connection::new_unix_server(stream, &self.guid) {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::debug!(target: logging::HANDSHAKE, "Test bus handshake failed: {}", e);
+
+                return;
+            }
+        };
+
+        let mut unique_name = None;
+        while let Ok(msg) = conn.receive_message() {
+            let deliveries = if let Some(name) = &unique_name {
+                self.route(name, &msg)
+            } else {
+                self.hello(&conn, &msg).map(|(name, deliveries)| {
+                    unique_name = name;
+
+                    deliveries
+                })
+            };
+            match deliveries {
+                Ok(deliveries) => deliver(deliveries),
+                Err(e) => log::warn!(
+                    target: logging::CONNECTION,
+                    "Failed to route a message on the test bus: {}",
+                    e
+                ),
+            }
+        }
+
+        if let Some(name) = unique_name {
+            let mut deliveries = vec![];
+            let disconnected = self
+                .state
+                .lock()
+                .expect(LOCK_PANIC_MSG)
+                .disconnect(&name, &mut deliveries);
+            match disconnected {
+                Ok(()) => deliver(deliveries),
+                Err(e) => log::warn!(
+                    target: logging::CONNECTION,
+                    "Failed to disconnect {} from the test bus: {}",
+                    name,
+                    e
+                ),
+            }
+        }
+    }
+
+    // Handle a message from a client that didn't call `Hello` yet, returning its unique name if it
+    // just did.
+    fn hello(&self, conn: &Connection, msg: &Message) -> Result<(Option<String>, Vec<Delivery>)> {
+        let header = msg.header()?;
+        if header.message_type()? != MessageType::MethodCall
+            || header.destination()? != Some(BUS_NAME)
+            || header.member()? != Some("Hello")
+        {
+            let e = fdo::Error::AccessDenied(
+                "Client tried to send a message other than Hello without being registered".into(),
+            );
+            let reply = Message::method_error(Some(BUS_NAME), msg, e.name(), &e.description())?;
+
+            return Ok((None, vec![(conn.clone(), reply)]));
+        }
+
+        let mut state = self.state.lock().expect(LOCK_PANIC_MSG);
+        state.next_id += 1;
+        let unique_name = format!(":1.{}", state.next_id);
+        state.clients.insert(
+            unique_name.clone(),
+            Client {
+                conn: conn.clone(),
+                rules: vec![],
+            },
+        );
+
+        let msg = msg.with_sender(&unique_name)?;
+        let mut deliveries = vec![(
+            conn.clone(),
+            Message::method_reply(Some(BUS_NAME), &msg, &unique_name)?,
+        )];
+        state.owner_changed(&unique_name, "", &unique_name, &mut deliveries)?;
+
+        Ok((Some(unique_name), deliveries))
+    }
+
+    fn route(self: &Arc<Self>, sender: &str, msg: &Message) -> Result<Vec<Delivery>> {
+        let msg = msg.with_sender(sender)?;
+        let header = msg.header()?;
+        let msg_type = header.message_type()?;
+        let mut state = self.state.lock().expect(LOCK_PANIC_MSG);
+        let mut deliveries = vec![];
+
+        match header.destination()? {
+            Some(BUS_NAME) | None if msg_type == MessageType::MethodCall => {
+                let reply = match self.call(&mut state, sender, &msg, &header, &mut deliveries) {
+                    Ok(reply) => reply,
+                    Err(e) => Some(Message::method_error(
+                        Some(BUS_NAME),
+                        &msg,
+                        e.name(),
+                        &e.description(),
+                    )?),
+                };
+                match reply {
+                    Some(reply) if !expects_no_reply(&msg) => {
+                        if let Some(client) = state.clients.get(sender) {
+                            deliveries.push((client.conn.clone(), reply));
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            Some(destination) => match state.client(destination) {
+                Some(client) => deliveries.push((client.conn.clone(), msg.clone())),
+                None if msg_type == MessageType::MethodCall && !expects_no_reply(&msg) => {
+                    let e = fdo::Error::ServiceUnknown(format!(
+                        "The name {} was not provided by any .service files",
+                        destination,
+                    ));
+                    let reply =
+                        Message::method_error(Some(BUS_NAME), &msg, e.name(), &e.description())?;
+                    if let Some(client) = state.clients.get(sender) {
+                        deliveries.push((client.conn.clone(), reply));
+                    }
+                }
+                None => (),
+            },
+            None if msg_type == MessageType::Signal => state.broadcast(&msg, &mut deliveries),
+            None => log::debug!(
+                target: logging::CONNECTION,
+                "Dropping message without destination on the test bus: {:?}",
+                msg
+            ),
+        }
+
+        Ok(deliveries)
+    }
+
+    // Handle a method call to the bus itself, returning the reply unless it's sent later.
+    fn call(
+        self: &Arc<Self>,
+        state: &mut State,
+        sender: &str,
+        msg: &Message,
+        header: &MessageHeader<'_>,
+        deliveries: &mut Vec<Delivery>,
+    ) -> fdo::Result<Option<Message>> {
+        let member = header.member()?.unwrap_or_default();
+        let interface = header.interface()?;
+        match interface {
+            Some(PEER_INTERFACE) if member == "Ping" => return reply(msg, &()).map(Some),
+            Some(BUS_INTERFACE) | None => (),
+            Some(interface) => {
+                return Err(fdo::Error::UnknownInterface(format!(
+                    "Unknown interface '{}'",
+                    interface,
+                )))
+            }
+        }
+
+        if member == "StartServiceByName" {
+            return self.start_service(state, sender, msg);
+        }
+
+        let result = match member {
+            "Hello" => Err(fdo::Error::Failed(
+                "Already handled an Hello message".into(),
+            )),
+            "RequestName" => {
+                let (name, flags): (&str, BitFlags<RequestNameFlags>) = msg.body()?;
+                check_well_known_name(name)?;
+                let owner = Owner {
+                    unique_name: sender.into(),
+                    flags,
+                };
+                let result = state.request_name(name, owner, deliveries)?;
+
+                reply(msg, &(result as u32))
+            }
+            "ReleaseName" => {
+                let name: &str = msg.body()?;
+                check_well_known_name(name)?;
+                let result = state.release_name(name, sender, deliveries)?;
+
+                reply(msg, &(result as u32))
+            }
+            "GetNameOwner" => {
+                let name: &str = msg.body()?;
+                let owner = state.owner(name).ok_or_else(|| {
+                    fdo::Error::NameHasNoOwner(format!(
+                        "Could not get owner of name '{}': no such name",
+                        name,
+                    ))
+                })?;
+
+                reply(msg, &owner)
+            }
+            "NameHasOwner" => {
+                let name: &str = msg.body()?;
+
+                reply(msg, &state.owner(name).is_some())
+            }
+            "ListNames" => {
+                let names: Vec<&str> = std::iter::once(BUS_NAME)
+                    .chain(state.clients.keys().map(String::as_str))
+                    .chain(state.names.keys().map(String::as_str))
+                    .collect();
+
+                reply(msg, &names)
+            }
+            "ListActivatableNames" => {
+                let names: Vec<&str> = std::iter::once(BUS_NAME)
+                    .chain(state.services.keys().map(String::as_str))
+                    .collect();
+
+                reply(msg, &names)
+            }
+            "ListQueuedOwners" => {
+                let name: &str = msg.body()?;
+                let owners: Vec<&str> = match state.names.get(name) {
+                    Some(n) => std::iter::once(&n.owner)
+                        .chain(n.queue.iter())
+                        .map(|o| o.unique_name.as_str())
+                        .collect(),
+                    None => vec![state.owner(name).ok_or_else(|| {
+                        fdo::Error::NameHasNoOwner(format!(
+                            "Could not get owners of name '{}': no such name",
+                            name,
+                        ))
+                    })?],
+                };
+
+                reply(msg, &owners)
+            }
+            "AddMatch" => {
+                let rule: MatchRule = msg.body::<&str>()?.parse()?;
+                if let Some(client) = state.clients.get_mut(sender) {
+                    client.rules.push(rule);
+                }
+
+                reply(msg, &())
+            }
+            "RemoveMatch" => {
+                let rule: MatchRule = msg.body::<&str>()?.parse()?;
+                let not_found =
+                    || fdo::Error::MatchRuleNotFound("The given match rule wasn't found".into());
+                let rules = &mut state.clients.get_mut(sender).ok_or_else(not_found)?.rules;
+                let pos = rules
+                    .iter()
+                    .position(|r| *r == rule)
+                    .ok_or_else(not_found)?;
+                rules.remove(pos);
+
+                reply(msg, &())
+            }
+            "GetId" => reply(msg, &self.guid.as_str()),
+            _ => Err(fdo::Error::UnknownMethod(format!(
+                "Unknown method '{}' on interface '{}'",
+                member, BUS_INTERFACE,
+            ))),
+        };
+
+        result.map(Some)
+    }
+
+    // Start the service for the name in the `StartServiceByName` call `msg`. The reply is sent once
+    // the service owns the name.
+    fn start_service(
+        self: &Arc<Self>,
+        state: &mut State,
+        sender: &str,
+        msg: &Message,
+    ) -> fdo::Result<Option<Message>> {
+        let (name, _flags): (&str, u32) = msg.body()?;
+        if state.owner(name).is_some() {
+            return reply(msg, &START_REPLY_ALREADY_RUNNING).map(Some);
+        }
+        let exec = state.services.get(name).cloned().ok_or_else(|| {
+            fdo::Error::ServiceUnknown(format!(
+                "The name {} was not provided by any .service files",
+                name,
+            ))
+        })?;
+
+        let starting = state.activations.contains_key(name);
+        let waiting = state.activations.entry(name.into()).or_default();
+        if let Some(client) = state.clients.get(sender) {
+            waiting.push((client.conn.clone(), msg.clone()));
+        }
+        if !starting {
+            if let Err(e) = self.spawn_service(name, &exec) {
+                state.activations.remove(name);
+
+                return Err(e);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn spawn_service(self: &Arc<Self>, name: &str, exec: &str) -> fdo::Result<()> {
+        let mut args = exec.split_whitespace();
+        let program = args.next().unwrap_or_default();
+        let mut child = Command::new(program)
+            .args(args)
+            .env("DBUS_STARTER_ADDRESS", &self.address)
+            .env_remove("DBUS_STARTER_BUS_TYPE")
+            .spawn()
+            .map_err(|e| {
+                fdo::Error::SpawnExecFailed(format!("Failed to execute program {}: {}", program, e))
+            })?;
+
+        let bus = self.clone();
+        let name = name.to_owned();
+        let spawned = thread::Builder::new()
+            .name("zbus::TestBus::service".into())
+            .spawn(move || {
+                let status = child.wait();
+                // Fail the calls still waiting for the name, if the service didn't get to own it.
+                let waiting = bus
+                    .state
+                    .lock()
+                    .expect(LOCK_PANIC_MSG)
+                    .activations
+                    .remove(&name);
+                let e = fdo::Error::SpawnChildExited(match status {
+                    Ok(status) => format!("Activated service '{}' exited: {}", name, status),
+                    Err(e) => format!("Failed to wait for activated service '{}': {}", name, e),
+                });
+                let deliveries = waiting
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|(_, call)| !expects_no_reply(call))
+                    .filter_map(|(conn, call)| {
+                        Message::method_error(Some(BUS_NAME), &call, e.name(), &e.description())
+                            .map(|reply| (conn, reply))
+                            .ok()
+                    })
+                    .collect();
+                deliver(deliveries);
+            });
+        if let Err(e) = spawned {
+            log::warn!(
+                target: logging::CONNECTION,
+                "Failed to start a test bus service thread: {}",
+                e
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn reply<B>(call: &Message, body: &B) -> fdo::Result<Message>
+where
+    B: serde::ser::Serialize + zvariant::Type,
+{
+    Message::method_reply(Some(BUS_NAME), call, body).map_err(Into::into)
+}
+
+fn check_well_known_name(name: &str) -> fdo::Result<()> {
+    if name == BUS_NAME || !is_well_known_name(name) {
+        return Err(fdo::Error::InvalidArgs(format!(
+            "Invalid well-known name '{}'",
+            name,
+        )));
+    }
+
+    Ok(())
+}
+
+fn expects_no_reply(msg: &Message) -> bool {
+    msg.primary_header()
+        .flags()
+        .contains(MessageFlags::NoReplyExpected)
+}
+
+fn deliver(deliveries: Vec<Delivery>) {
+    for (conn, msg) in deliveries {
+        if let Err(e) = conn.send_message(msg) {
+            log::debug!(
+                target: logging::CONNECTION,
+                "Failed to send a message from the test bus: {}",
+                e
+            );
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    next_id: u64,
+    // By unique name.
+    clients: HashMap<String, Client>,
+    // By well-known name.
+    names: HashMap<String, Name>,
+    // All the sockets, for closing them when the bus is stopped.
+    sockets: Vec<UnixStream>,
+    // The `Exec` commands of the activatable services, by name.
+    services: BTreeMap<String, String>,
+    // The `StartServiceByName` calls waiting for their service to own its name, with the
+    // connections to reply on.
+    activations: HashMap<String, Vec<Delivery>>,
+}
+
+#[derive(Debug)]
+struct Client {
+    conn: Connection,
+    rules: Vec<MatchRule>,
+}
+
+#[derive(Debug)]
+struct Name {
+    owner: Owner,
+    queue: VecDeque<Owner>,
+}
+
+#[derive(Debug, Clone)]
+struct Owner {
+    unique_name: String,
+    flags: BitFlags<RequestNameFlags>,
+}
+
+impl State {
+    // The unique name of the owner of `name`.
+    fn owner(&self, name: &str) -> Option<&str> {
+        if name == BUS_NAME {
+            Some(BUS_NAME)
+        } else if name.starts_with(':') {
+            self.clients.get_key_value(name).map(|(k, _)| k.as_str())
+        } else {
+            self.names.get(name).map(|n| n.owner.unique_name.as_str())
+        }
+    }
+
+    fn client(&self, name: &str) -> Option<&Client> {
+        self.owner(name).and_then(|owner| self.clients.get(owner))
+    }
+
+    fn request_name(
+        &mut self,
+        name: &str,
+        requester: Owner,
+        deliveries: &mut Vec<Delivery>,
+    ) -> Result<RequestNameReply> {
+        let entry = match self.names.get_mut(name) {
+            Some(entry) => entry,
+            None => {
+                let new_owner = requester.unique_name.clone();
+                self.names.insert(
+                    name.into(),
+                    Name {
+                        owner: requester,
+                        queue: VecDeque::new(),
+                    },
+                );
+                self.owner_changed(name, "", &new_owner, deliveries)?;
+                for (conn, call) in self.activations.remove(name).unwrap_or_default() {
+                    if !expects_no_reply(&call) {
+                        let reply =
+                            Message::method_reply(Some(BUS_NAME), &call, &START_REPLY_SUCCESS)?;
+                        deliveries.push((conn, reply));
+                    }
+                }
+
+                return Ok(RequestNameReply::PrimaryOwner);
+            }
+        };
+
+        if entry.owner.unique_name == requester.unique_name {
+            entry.owner.flags = requester.flags;
+
+            return Ok(RequestNameReply::AlreadyOwner);
+        }
+
+        let is_requester = |o: &Owner| o.unique_name == requester.unique_name;
+        if entry
+            .owner
+            .flags
+            .contains(RequestNameFlags::AllowReplacement)
+            && requester.flags.contains(RequestNameFlags::ReplaceExisting)
+        {
+            entry.queue.retain(|o| !is_requester(o));
+            let new_owner = requester.unique_name.clone();
+            let old_owner = std::mem::replace(&mut entry.owner, requester);
+            let old_name = old_owner.unique_name.clone();
+            if !old_owner.flags.contains(RequestNameFlags::DoNotQueue) {
+                entry.queue.push_front(old_owner);
+            }
+            self.owner_changed(name, &old_name, &new_owner, deliveries)?;
+
+            Ok(RequestNameReply::PrimaryOwner)
+        } else if requester.flags.contains(RequestNameFlags::DoNotQueue) {
+            entry.queue.retain(|o| !is_requester(o));
+
+            Ok(RequestNameReply::Exists)
+        } else {
+            match entry.queue.iter_mut().find(|o| is_requester(o)) {
+                Some(queued) => queued.flags = requester.flags,
+                None => entry.queue.push_back(requester.clone()),
+            }
+
+            Ok(RequestNameReply::InQueue)
+        }
+    }
+
+    fn release_name(
+        &mut self,
+        name: &str,
+        unique_name: &str,
+        deliveries: &mut Vec<Delivery>,
+    ) -> Result<ReleaseNameReply> {
+        let entry = match self.names.get_mut(name) {
+            Some(entry) => entry,
+            None => return Ok(ReleaseNameReply::NonExistent),
+        };
+
+        if entry.owner.unique_name == unique_name {
+            let new_owner = match entry.queue.pop_front() {
+                Some(next) => {
+                    let new_owner = next.unique_name.clone();
+                    entry.owner = next;
+
+                    new_owner
+                }
+                None => {
+                    self.names.remove(name);
+
+                    String::new()
+                }
+            };
+            self.owner_changed(name, unique_name, &new_owner, deliveries)?;
+
+            Ok(ReleaseNameReply::Released)
+        } else if let Some(pos) = entry
+            .queue
+            .iter()
+            .position(|o| o.unique_name == unique_name)
+        {
+            entry.queue.remove(pos);
+
+            Ok(ReleaseNameReply::Released)
+        } else {
+            Ok(ReleaseNameReply::NotOwner)
+        }
+    }
+
+    // Forget about a disconnected client, releasing all its names.
+    fn disconnect(&mut self, unique_name: &str, deliveries: &mut Vec<Delivery>) -> Result<()> {
+        self.clients.remove(unique_name);
+
+        let names: Vec<String> = self
+            .names
+            .iter()
+            .filter(|(_, n)| {
+                std::iter::once(&n.owner)
+                    .chain(n.queue.iter())
+                    .any(|o| o.unique_name == unique_name)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in names {
+            self.release_name(&name, unique_name, deliveries)?;
+        }
+
+        self.owner_changed(unique_name, unique_name, "", deliveries)
+    }
+
+    // Emit the signals for `name` changing hands.
+    fn owner_changed(
+        &self,
+        name: &str,
+        old_owner: &str,
+        new_owner: &str,
+        deliveries: &mut Vec<Delivery>,
+    ) -> Result<()> {
+        if let Some(client) = self.clients.get(old_owner) {
+            let msg = bus_signal(Some(old_owner), "NameLost", &name)?;
+            deliveries.push((client.conn.clone(), msg));
+        }
+        let msg = bus_signal(None, "NameOwnerChanged", &(name, old_owner, new_owner))?;
+        self.broadcast(&msg, deliveries);
+        if let Some(client) = self.clients.get(new_owner) {
+            let msg = bus_signal(Some(new_owner), "NameAcquired", &name)?;
+            deliveries.push((client.conn.clone(), msg));
+        }
+
+        Ok(())
+    }
+
+    // Send the signal `msg` to all the clients with a matching rule.
+    fn broadcast(&self, msg: &Message, deliveries: &mut Vec<Delivery>) {
+        let header = match msg.header() {
+            Ok(header) => header,
+            Err(_) => return,
+        };
+        let mut args = None;
+
+        for client in self.clients.values() {
+            if client
+                .rules
+                .iter()
+                .any(|rule| rule.matches(self, msg, &header, &mut args))
+            {
+                deliveries.push((client.conn.clone(), msg.clone()));
+            }
+        }
+    }
+}
+
+fn bus_signal<B>(destination: Option<&str>, signal_name: &str, body: &B) -> Result<Message>
+where
+    B: serde::ser::Serialize + zvariant::Type,
+{
+    Message::signal(
+        Some(BUS_NAME),
+        destination,
+        BUS_PATH,
+        BUS_INTERFACE,
+        signal_name,
+        body,
+    )
+    .map_err(Into::into)
+}
+
+// A parsed match rule, as passed to `AddMatch`.
+#[derive(Debug, PartialEq)]
+struct MatchRule(BTreeMap<String, String>);
+
+impl FromStr for MatchRule {
+    type Err = fdo::Error;
+
+    fn from_str(rule: &str) -> fdo::Result<Self> {
+        let invalid = |reason: &str| {
+            fdo::Error::MatchRuleInvalid(format!("Invalid match rule '{}': {}", rule, reason))
+        };
+        let mut keys = BTreeMap::new();
+        let mut chars = rule.chars().peekable();
+
+        while chars.peek().is_some() {
+            let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+            let key = key.trim().to_string();
+
+            let mut value = String::new();
+            let mut quoted = false;
+            loop {
+                match chars.next() {
+                    Some('\'') => quoted = !quoted,
+                    Some('\\') if !quoted && chars.peek() == Some(&'\'') => {
+                        value.push('\'');
+                        chars.next();
+                    }
+                    Some(',') if !quoted => break,
+                    Some(c) => value.push(c),
+                    None if quoted => return Err(invalid("unterminated quote")),
+                    None => break,
+                }
+            }
+
+            let valid_key = match key.as_str() {
+                "type" | "sender" | "interface" | "member" | "path" | "path_namespace"
+                | "destination" | "eavesdrop" => true,
+                arg => arg
+                    .strip_prefix("arg")
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .map(|n| n < 64)
+                    .unwrap_or(false),
+            };
+            if !valid_key {
+                return Err(invalid(&format!("unsupported key '{}'", key)));
+            }
+            if keys.insert(key, value).is_some() {
+                return Err(invalid("duplicate key"));
+            }
+        }
+
+        Ok(Self(keys))
+    }
+}
+
+impl MatchRule {
+    // `args` caches the string arguments of `msg`, so they are only decoded once per message.
+    fn matches(
+        &self,
+        state: &State,
+        msg: &Message,
+        header: &MessageHeader<'_>,
+        args: &mut Option<Vec<Option<String>>>,
+    ) -> bool {
+        let path = header.path().ok().flatten().map(|p| p.as_str());
+
+        self.0.iter().all(|(key, value)| match key.as_str() {
+            "type" => {
+                let msg_type = match msg.primary_header().msg_type() {
+                    MessageType::MethodCall => "method_call",
+                    MessageType::MethodReturn => "method_return",
+                    MessageType::Error => "error",
+                    MessageType::Signal => "signal",
+                    MessageType::Invalid => "",
+                };
+
+                msg_type == value
+            }
+            "sender" => match header.sender() {
+                Ok(Some(sender)) => sender == value || state.owner(value) == Some(sender),
+                _ => false,
+            },
+            "interface" => header.interface() == Ok(Some(value.as_str())),
+            "member" => header.member() == Ok(Some(value.as_str())),
+            "destination" => header.destination() == Ok(Some(value.as_str())),
+            "path" => path == Some(value.as_str()),
+            "path_namespace" => match path {
+                Some(path) => {
+                    value == "/"
+                        || path == value
+                        || (path.starts_with(value.as_str())
+                            && path[value.len()..].starts_with('/'))
+                }
+                None => false,
+            },
+            "eavesdrop" => true,
+            arg => {
+                let index: usize = match arg[3..].parse() {
+                    Ok(index) => index,
+                    Err(_) => return false,
+                };
+                let args = args.get_or_insert_with(|| string_args(msg).unwrap_or_default());
+
+                args.get(index).and_then(Option::as_deref) == Some(value.as_str())
+            }
+        })
+    }
+}
+
+// The arguments of `msg`, by index, for the ones that are strings or object paths.
+fn string_args(msg: &Message) -> Result<Vec<Option<String>>> {
+    let mut args = StringArgs::default();
+    msg.walk_body(&mut args)?;
+
+    Ok(args.args)
+}
+
+#[derive(Debug, Default)]
+struct StringArgs {
+    depth: usize,
+    args: Vec<Option<String>>,
+}
+
+impl StringArgs {
+    fn start(&mut self) -> zvariant::Result<()> {
+        // The whole body is walked as a structure, so its arguments are at depth 1.
+        if self.depth == 1 {
+            self.args.push(None);
+        }
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    fn end(&mut self) -> zvariant::Result<()> {
+        self.depth -= 1;
+
+        Ok(())
+    }
+}
+
+impl<'de> Walker<'de> for StringArgs {
+    fn basic(&mut self, value: Value<'de>) -> zvariant::Result<()> {
+        if self.depth == 1 {
+            self.args.push(match value {
+                Value::Str(s) => Some(s.as_str().into()),
+                Value::ObjectPath(p) => Some(p.as_str().into()),
+                _ => None,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn array_start(&mut self, _: &Signature<'_>) -> zvariant::Result<()> {
+        self.start()
+    }
+
+    fn array_end(&mut self) -> zvariant::Result<()> {
+        self.end()
+    }
+
+    fn dict_start(&mut self, _: &Signature<'_>, _: &Signature<'_>) -> zvariant::Result<()> {
+        self.start()
+    }
+
+    fn dict_end(&mut self) -> zvariant::Result<()> {
+        self.end()
+    }
+
+    fn struct_start(&mut self, _: &Signature<'_>) -> zvariant::Result<()> {
+        self.start()
+    }
+
+    fn struct_end(&mut self) -> zvariant::Result<()> {
+        self.end()
+    }
+
+    fn variant_start(&mut self, _: &Signature<'_>) -> zvariant::Result<()> {
+        self.start()
+    }
+
+    fn variant_end(&mut self) -> zvariant::Result<()> {
+        self.end()
+    }
+
+    #[cfg(feature = "gvariant")]
+    fn maybe_start(&mut self, _: &Signature<'_>, _: bool) -> zvariant::Result<()> {
+        self.start()
+    }
+
+    #[cfg(feature = "gvariant")]
+    fn maybe_end(&mut self) -> zvariant::Result<()> {
+        self.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use enumflags2::BitFlags;
+    use ntest::timeout;
+    use test_env_log::test;
+
+    use super::{assert_activatable, MatchRule, TestBus};
+    use crate::{
+        fdo::{self, ReleaseNameReply, RequestNameFlags, RequestNameReply},
+        service_file, Connection, Error,
+    };
+
+    const ACTIVATED_NAME: &str = "org.zbus.TestBus.Activated";
+
+    #[test]
+    fn match_rule_parsing() {
+        let rule: MatchRule = "type='signal',member='Changed',arg0='it'\\''s'"
+            .parse()
+            .unwrap();
+        let reordered: MatchRule = "arg0=it\\'s, member='Changed',type='signal'"
+            .parse()
+            .unwrap();
+        assert_eq!(rule, reordered);
+        assert_eq!(rule.0["arg0"], "it's");
+
+        assert!("sender='a',sender='b'".parse::<MatchRule>().is_err());
+        assert!("unknown='a'".parse::<MatchRule>().is_err());
+        assert!("arg64='a'".parse::<MatchRule>().is_err());
+        assert!("member='a".parse::<MatchRule>().is_err());
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn name_ownership() {
+        let bus = TestBus::new().unwrap();
+        let conn1 = bus.connect().unwrap();
+        let conn2 = bus.connect().unwrap();
+        let unique1 = conn1.unique_name().unwrap().to_string();
+        let unique2 = conn2.unique_name().unwrap().to_string();
+        assert_ne!(unique1, unique2);
+        let proxy1 = fdo::DBusProxy::new(&conn1).unwrap();
+        let proxy2 = fdo::DBusProxy::new(&conn2).unwrap();
+
+        let name = "org.zbus.TestBus.Name";
+        let reply = proxy1
+            .request_name(name, RequestNameFlags::AllowReplacement.into())
+            .unwrap();
+        assert_eq!(reply, RequestNameReply::PrimaryOwner);
+        let reply = proxy1
+            .request_name(name, RequestNameFlags::AllowReplacement.into())
+            .unwrap();
+        assert_eq!(reply, RequestNameReply::AlreadyOwner);
+
+        let reply = proxy2.request_name(name, BitFlags::empty()).unwrap();
+        assert_eq!(reply, RequestNameReply::InQueue);
+        assert_eq!(
+            proxy2.list_queued_owners(name).unwrap(),
+            vec![unique1.clone(), unique2.clone()],
+        );
+        let reply = proxy2
+            .request_name(name, RequestNameFlags::DoNotQueue.into())
+            .unwrap();
+        assert_eq!(reply, RequestNameReply::Exists);
+        assert_eq!(
+            proxy2.list_queued_owners(name).unwrap(),
+            vec![unique1.clone()]
+        );
+
+        // The current owner allows replacement and is queued again when replaced.
+        let reply = proxy2
+            .request_name(name, RequestNameFlags::ReplaceExisting.into())
+            .unwrap();
+        assert_eq!(reply, RequestNameReply::PrimaryOwner);
+        assert_eq!(proxy1.get_name_owner(name).unwrap(), unique2);
+        assert!(proxy1.list_names().unwrap().contains(&name.to_string()));
+
+        assert_eq!(
+            proxy2.release_name(name).unwrap(),
+            ReleaseNameReply::Released
+        );
+        assert_eq!(
+            proxy2.release_name(name).unwrap(),
+            ReleaseNameReply::NotOwner
+        );
+        assert_eq!(proxy2.get_name_owner(name).unwrap(), unique1);
+        assert_eq!(
+            proxy1.release_name(name).unwrap(),
+            ReleaseNameReply::Released
+        );
+        assert_eq!(
+            proxy1.release_name(name).unwrap(),
+            ReleaseNameReply::NonExistent
+        );
+        assert!(!proxy1.name_has_owner(name).unwrap());
+        assert!(matches!(
+            proxy1.get_name_owner(name),
+            Err(fdo::Error::NameHasNoOwner(_))
+        ));
+
+        assert!(matches!(
+            proxy1.request_name(&unique2, BitFlags::empty()),
+            Err(fdo::Error::InvalidArgs(_))
+        ));
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn signal_routing() {
+        let bus = TestBus::new().unwrap();
+        let emitter = bus.connect().unwrap();
+        let receiver = bus.connect().unwrap();
+        let proxy = fdo::DBusProxy::new(&receiver).unwrap();
+
+        let rule = "type='signal',interface='org.zbus.Test',path_namespace='/org/zbus',arg0='yes'";
+        proxy.add_match(rule).unwrap();
+        for (path, arg) in &[
+            ("/org/zbus/a", "no"),
+            ("/org", "yes"),
+            ("/org/zbus/a", "yes"),
+        ] {
+            emitter
+                .emit_signal(None, *path, "org.zbus.Test", "Signal", arg)
+                .unwrap();
+        }
+
+        // The signals are delivered in order, so the first one received must be the matching one.
+        loop {
+            let m = receiver.receive_message().unwrap();
+            let header = m.header().unwrap();
+            if header.member().unwrap() != Some("Signal") {
+                continue;
+            }
+            assert_eq!(header.path().unwrap().unwrap().as_str(), "/org/zbus/a");
+            assert_eq!(header.sender().unwrap(), emitter.unique_name());
+            assert_eq!(m.body::<&str>().unwrap(), "yes");
+            break;
+        }
+
+        proxy.remove_match(rule).unwrap();
+        assert!(matches!(
+            proxy.remove_match(rule),
+            Err(fdo::Error::MatchRuleNotFound(_))
+        ));
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn method_routing() {
+        let bus = TestBus::new().unwrap();
+        let service = bus.connect().unwrap();
+        let client = bus.connect().unwrap();
+        let service_name = service.unique_name().unwrap().to_string();
+
+        let service_thread = thread::spawn(move || loop {
+            let m = service.receive_message().unwrap();
+            if m.header().unwrap().member().unwrap() == Some("Echo") {
+                let arg: String = m.body().unwrap();
+                service.reply(&m, &arg).unwrap();
+
+                break;
+            }
+        });
+
+        let reply = client
+            .call_method(
+                Some(&service_name),
+                "/org/zbus/Test",
+                Some("org.zbus.Test"),
+                "Echo",
+                &"hello",
+            )
+            .unwrap();
+        assert_eq!(reply.body::<&str>().unwrap(), "hello");
+        assert_eq!(
+            reply.header().unwrap().sender().unwrap(),
+            Some(service_name.as_str())
+        );
+        service_thread.join().unwrap();
+
+        let e = client
+            .call_method(Some("org.zbus.Nobody"), "/", None, "Call", &())
+            .unwrap_err();
+        assert!(
+            matches!(&e, Error::MethodError(name, _, _) if name == "org.freedesktop.DBus.Error.ServiceUnknown"),
+            "{:?}",
+            e
+        );
+    }
+
+    #[test]
+    #[timeout(15000)]
+    fn activation() {
+        let bus = TestBus::new().unwrap();
+        // The service is this very test binary, running `activated_service` below.
+        let exec = format!(
+            "{} --exact test_bus::tests::activated_service --ignored --nocapture",
+            std::env::current_exe().unwrap().display(),
+        );
+        bus.add_service_file(&service_file(ACTIVATED_NAME, &exec, None).unwrap())
+            .unwrap();
+        let failing = service_file("org.zbus.TestBus.Failing", "/bin/false", None).unwrap();
+        bus.add_service_file(&failing).unwrap();
+        assert!(bus
+            .add_service_file("[D-Bus Service]\nName=zbus\n")
+            .is_err());
+
+        let conn = bus.connect().unwrap();
+        let proxy = fdo::DBusProxy::new(&conn).unwrap();
+        let mut names = proxy.list_activatable_names().unwrap();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "org.freedesktop.DBus",
+                "org.zbus.TestBus.Activated",
+                "org.zbus.TestBus.Failing"
+            ],
+        );
+
+        let owner = assert_activatable(&conn, ACTIVATED_NAME).unwrap();
+        // Already running now.
+        assert_eq!(proxy.start_service_by_name(ACTIVATED_NAME, 0).unwrap(), 2);
+
+        let e = proxy
+            .start_service_by_name("org.zbus.TestBus.Failing", 0)
+            .unwrap_err();
+        assert!(matches!(e, fdo::Error::SpawnChildExited(_)), "{:?}", e);
+        let e = proxy
+            .start_service_by_name("org.zbus.TestBus.Unknown", 0)
+            .unwrap_err();
+        assert!(matches!(e, fdo::Error::ServiceUnknown(_)), "{:?}", e);
+
+        let reply = conn
+            .call_method(Some(ACTIVATED_NAME), "/", None, "Quit", &())
+            .unwrap();
+        assert_eq!(
+            reply.header().unwrap().sender().unwrap(),
+            Some(owner.as_str())
+        );
+    }
+
+    // Only run by `activation`, as the activated service.
+    #[test]
+    #[ignore]
+    fn activated_service() {
+        if std::env::var_os("DBUS_STARTER_ADDRESS").is_none() {
+            return;
+        }
+
+        let conn = Connection::new_starter().unwrap();
+        fdo::DBusProxy::new(&conn)
+            .unwrap()
+            .request_name(ACTIVATED_NAME, RequestNameFlags::DoNotQueue.into())
+            .unwrap();
+        loop {
+            let msg = conn.receive_message().unwrap();
+            if msg.header().unwrap().member().unwrap() == Some("Quit") {
+                conn.reply(&msg, &()).unwrap();
+
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+#![cfg(any(test, feature = "test-util"))]
+
+// Injection points for deterministic tests, set up through the `test-util` methods of
+// `ConnectionBuilder`. With all of them in use, the bytes a connection writes to the socket are
+// the same from one run of a scenario to the next, so they can be compared with golden files.
+
+use std::{cell::RefCell, fmt, future::Future, sync::Mutex};
+
+use futures_util::future::poll_fn;
+use rand::{
+    distributions::{Distribution, Standard},
+    Rng, RngCore,
+};
+
+use crate::Guid;
+
+/// The hooks set up on a `ConnectionBuilder`.
+#[derive(Debug, Default)]
+pub(crate) struct Hooks {
+    pub(crate) initial_serial: Option<u32>,
+    pub(crate) serial_allocator: Option<SerialAllocator>,
+    pub(crate) server_guid: Option<Guid>,
+    pub(crate) handshake_rng_seed: Option<u64>,
+}
+
+/// Where serial numbers come from, rather than the built-in counter.
+pub(crate) struct SerialAllocator(Mutex<Box<dyn FnMut() -> u32 + Send>>);
+
+impl SerialAllocator {
+    pub(crate) fn new<F>(allocator: F) -> Self
+    where
+        F: FnMut() -> u32 + Send + 'static,
+    {
+        Self(Mutex::new(Box::new(allocator)))
+    }
+
+    pub(crate) fn next(&self) -> u32 {
+        (self.0.lock().expect("lock poisoned"))()
+    }
+}
+
+impl fmt::Debug for SerialAllocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SerialAllocator")
+    }
+}
+
+/// The RNG of handshakes, seeded through `ConnectionBuilder::handshake_rng_seed`.
+///
+/// This is [SplitMix64], rather than one of the generators of `rand`, which don't promise to give
+/// the same numbers from one release to the next. Golden files have to stay valid across releases.
+///
+/// [SplitMix64]: https://prng.di.unimi.it/splitmix64.c
+#[derive(Debug)]
+pub(crate) struct HandshakeRng(u64);
+
+impl HandshakeRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+}
+
+impl RngCore for HandshakeRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+
+        Ok(())
+    }
+}
+
+thread_local! {
+    // The RNG of the handshake being polled on this thread, if seeded.
+    static HANDSHAKE_RNG: RefCell<Option<HandshakeRng>> = RefCell::new(None);
+}
+
+/// A random value from the RNG of the handshake being polled on this thread, if seeded.
+pub(crate) fn random<T>() -> Option<T>
+where
+    Standard: Distribution<T>,
+{
+    HANDSHAKE_RNG.with(|rng| rng.borrow_mut().as_mut().map(|rng| rng.gen()))
+}
+
+/// Poll `future` with `rng` as the RNG of the handshake, if any.
+///
+/// The handshake mechanisms are called synchronously from the poll of the handshake, so the RNG is
+/// set up on the polling thread for the duration of each poll, and put aside in between.
+pub(crate) async fn with_handshake_rng<F>(rng: Option<HandshakeRng>, future: F) -> F::Output
+where
+    F: Future,
+{
+    let mut rng = rng;
+    futures_util::pin_mut!(future);
+    poll_fn(|cx| {
+        let outer = HANDSHAKE_RNG.with(|cell| cell.replace(rng.take()));
+        let poll = future.as_mut().poll(cx);
+        rng = HANDSHAKE_RNG.with(|cell| cell.replace(outer));
+
+        poll
+    })
+    .await
+}
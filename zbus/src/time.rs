@@ -0,0 +1,157 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use futures_util::future::{select, Either};
+
+/// Wait until `duration` has elapsed.
+///
+/// This is the timer zbus uses for all its own delays and timeouts, so they behave the same
+/// whatever the executor. Custom [`Socket`] implementations, or anything else run by a
+/// [`Connection`], can use it to sleep without depending on a specific runtime.
+///
+/// By default, the timers are those of [`async-io`]. With the `tokio` feature, a timer first
+/// polled within a tokio runtime uses the tokio time driver instead, which the runtime must then
+/// have enabled. Either way, the resolution is about a millisecond, and the sleep never ends before
+/// `duration` has elapsed. Dropping the returned future cancels the timer.
+///
+/// [`Socket`]: raw/trait.Socket.html
+/// [`Connection`]: struct.Connection.html
+/// [`async-io`]: https://docs.rs/async-io
+pub async fn sleep(duration: Duration) {
+    sleep_until(Instant::now() + duration).await
+}
+
+/// Wait until `deadline`.
+///
+/// See [`sleep`] for the timer used.
+///
+/// [`sleep`]: fn.sleep.html
+pub async fn sleep_until(deadline: Instant) {
+    #[cfg(feature = "tokio")]
+    {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return tokio::time::sleep_until(deadline.into()).await;
+        }
+    }
+
+    async_io::Timer::at(deadline).await;
+}
+
+/// Run `future` for at most `duration`.
+///
+/// Returns the output of `future`, or `None` if it didn't complete in time, in which case it's
+/// dropped, and so cancelled. `future` is always polled before the timer, so a future that's ready
+/// when the time is up still completes.
+///
+/// See [`sleep`] for the timer used.
+///
+/// [`sleep`]: fn.sleep.html
+pub async fn timeout<F>(duration: Duration, future: F) -> Option<F::Output>
+where
+    F: Future,
+{
+    timeout_at(Instant::now() + duration, future).await
+}
+
+/// Run `future` until `deadline` at the latest.
+///
+/// Same as [`timeout`], but with a deadline.
+///
+/// [`timeout`]: fn.timeout.html
+pub async fn timeout_at<F>(deadline: Instant, future: F) -> Option<F::Output>
+where
+    F: Future,
+{
+    let timer = sleep_until(deadline);
+    futures_util::pin_mut!(future);
+    futures_util::pin_mut!(timer);
+
+    match select(future, timer).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::pending,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    use async_io::block_on;
+    use ntest::timeout;
+    use test_env_log::test;
+
+    use super::{sleep, sleep_until, timeout, timeout_at};
+
+    // Sets its flag when dropped.
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    // The behavior expected of the timers, whichever is used.
+    async fn check_timers() {
+        let start = Instant::now();
+        sleep(Duration::from_millis(20)).await;
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        let deadline = Instant::now() + Duration::from_millis(20);
+        sleep_until(deadline).await;
+        assert!(Instant::now() >= deadline);
+        // A deadline in the past is no wait.
+        sleep_until(start).await;
+
+        // The future is dropped when the time is up.
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = DropFlag(dropped.clone());
+        let start = Instant::now();
+        let res = timeout(Duration::from_millis(20), async move {
+            let _flag = flag;
+            pending::<()>().await
+        })
+        .await;
+        assert_eq!(res, None);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert!(dropped.load(Ordering::SeqCst));
+
+        // A ready future wins, even without any time left.
+        assert_eq!(
+            timeout(Duration::from_secs(0), async { 42 }).await,
+            Some(42)
+        );
+        assert_eq!(timeout_at(start, async { 42 }).await, Some(42));
+        let res = timeout(Duration::from_secs(1), async {
+            sleep(Duration::from_millis(10)).await;
+            "done"
+        })
+        .await;
+        assert_eq!(res, Some("done"));
+    }
+
+    #[test]
+    #[timeout(2000)]
+    fn async_io_timers() {
+        block_on(check_timers());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    #[timeout(2000)]
+    fn tokio_timers() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(check_timers());
+    }
+}
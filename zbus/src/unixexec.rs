@@ -0,0 +1,182 @@
+#![cfg(feature = "unixexec-transport")]
+
+use async_io::Async;
+use std::{
+    ffi::OsString,
+    io::{self, Read},
+    os::unix::{
+        io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+        net::UnixStream,
+        process::CommandExt,
+    },
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    raw::{socket::retry_interrupted, Socket},
+    Error, OwnedFd, Result,
+};
+
+/// A connection to an executed process, over its stdin and stdout.
+///
+/// Like the reference implementation, the process gets one end of a socket pair as both its stdin
+/// and stdout, so we have a single socket to read from and write to. The process is reaped when the
+/// socket is closed, or when the last handle to it is dropped.
+#[derive(Debug)]
+pub(crate) struct UnixexecStream {
+    stream: UnixStream,
+    child: Arc<ChildGuard>,
+}
+
+// Kills and reaps the child, if it's still around.
+#[derive(Debug)]
+struct ChildGuard(Mutex<Option<Child>>);
+
+impl ChildGuard {
+    fn reap(&self) -> io::Result<()> {
+        let child = self.0.lock().expect("lock poisoned").take();
+        if let Some(mut child) = child {
+            // The child is expected to exit on its own when its stdin is closed, but we can't wait
+            // for it indefinitely.
+            if child.try_wait()?.is_none() {
+                // Fails if it exited in the meantime, which is fine.
+                let _ = child.kill();
+                child.wait()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let _ = self.reap();
+    }
+}
+
+/// Execute `path`, with the given `argv0` (defaulting to `path`) and arguments, and connect to it.
+pub(crate) fn connect(
+    path: &OsString,
+    argv0: Option<&OsString>,
+    args: &[OsString],
+) -> Result<Async<UnixexecStream>> {
+    let (ours, theirs) = UnixStream::pair()?;
+    let theirs_out = theirs.try_clone()?;
+
+    let mut command = Command::new(path);
+    command.arg0(argv0.unwrap_or(path)).args(args);
+    // SAFETY: we own both file descriptors, which `Stdio` takes the ownership of.
+    let child = unsafe {
+        command
+            .stdin(Stdio::from_raw_fd(theirs.into_raw_fd()))
+            .stdout(Stdio::from_raw_fd(theirs_out.into_raw_fd()))
+    }
+    .spawn()
+    .map_err(|e| {
+        Error::Address(format!(
+            "failed to execute `{}`: {}",
+            path.to_string_lossy(),
+            e
+        ))
+    })?;
+    // Drop our copies of the child's ends, so we notice when it exits.
+    drop(command);
+
+    let stream = UnixexecStream {
+        stream: ours,
+        child: Arc::new(ChildGuard(Mutex::new(Some(child)))),
+    };
+
+    Ok(Async::new(stream)?)
+}
+
+impl AsRawFd for UnixexecStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+impl Socket for UnixexecStream {
+    fn recvmsg(&mut self, buffer: &mut [u8]) -> io::Result<(usize, Vec<OwnedFd>)> {
+        retry_interrupted(|| self.stream.read(buffer)).map(|n| (n, vec![]))
+    }
+
+    fn sendmsg(&mut self, buffer: &[u8], fds: &[RawFd]) -> io::Result<usize> {
+        if !fds.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "file descriptors can't be sent over unixexec",
+            ));
+        }
+
+        // Not `write`, which would raise `SIGPIPE` once the process is gone.
+        self.stream.sendmsg(buffer, &[])
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.stream.shutdown(std::net::Shutdown::Both)?;
+
+        self.child.reap()
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Socket>> {
+        Ok(Box::new(UnixexecStream {
+            stream: self.stream.try_clone()?,
+            child: self.child.clone(),
+        }))
+    }
+
+    fn can_pass_unix_fd(&self) -> bool {
+        // The process may well be relaying the data elsewhere, e.g over ssh.
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, io::ErrorKind, os::unix::io::AsRawFd};
+
+    use async_io::block_on;
+
+    use crate::{raw::Socket, Error};
+
+    #[test]
+    fn exec() {
+        // `cat` echoes back what we send it.
+        let mut stream = super::connect(&"cat".into(), None, &[]).unwrap();
+        assert!(!stream.can_pass_unix_fd());
+        assert_eq!(stream.sendmsg(b"hello", &[]).unwrap(), 5);
+        let mut buffer = [0; 5];
+        let (n, fds) = block_on(async {
+            stream.readable().await?;
+            stream.recvmsg(&mut buffer)
+        })
+        .unwrap();
+        assert_eq!(&buffer[..n], b"hello");
+        assert!(fds.is_empty());
+
+        let fd = stream.as_raw_fd();
+        match stream.sendmsg(b"fd", &[fd]).unwrap_err() {
+            e if e.kind() == ErrorKind::InvalidInput => (),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        stream.close().unwrap();
+        assert!(stream.get_ref().child.0.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn exec_failure() {
+        let path = OsString::from("/nonexistent/zbus-unixexec");
+        match super::connect(&path, None, &[]).map(|_| ()).unwrap_err() {
+            Error::Address(e) => assert!(
+                e.starts_with("failed to execute `/nonexistent/zbus-unixexec`"),
+                "{}",
+                e
+            ),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+}
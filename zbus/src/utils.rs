@@ -2,7 +2,11 @@ use nix::{
     errno::Errno,
     poll::{poll, PollFd, PollFlags},
 };
-use std::os::unix::io::RawFd;
+use rand::distributions::{Distribution, Standard};
+use std::{convert::TryFrom, os::unix::io::RawFd};
+use zvariant::{Error as VariantError, Signature, Value};
+
+use crate::{Error, Result};
 
 pub(crate) const FDS_MAX: usize = 1024; // this is hardcoded in sdbus - nothing in the spec
 
@@ -39,3 +43,70 @@ pub(crate) fn wait_on(fd: RawFd, flags: PollFlags) -> std::io::Result<()> {
     }
     Ok(())
 }
+
+// The complete types of a valid `signature`, i-e the arguments it stands for.
+pub(crate) fn complete_types(signature: &str) -> Vec<&str> {
+    let mut types = vec![];
+    let mut start = 0;
+    let mut depth = 0;
+    for (i, c) in signature.char_indices() {
+        match c {
+            // Arrays and maybes are followed by the type of their elements.
+            'a' | 'm' => continue,
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            _ => (),
+        }
+        if depth == 0 {
+            types.push(&signature[start..=i]);
+            start = i + 1;
+        }
+    }
+
+    types
+}
+
+// Parse `args` as the arguments of `signature`, one complete type each.
+pub(crate) fn parse_args<S: AsRef<str>>(
+    signature: &str,
+    args: &[S],
+) -> Result<Vec<Value<'static>>> {
+    Signature::try_from(signature)?;
+    let types = complete_types(signature);
+    let index = types.len().min(args.len());
+    if let Some(ty) = types.get(index) {
+        let e = VariantError::Message(format!("missing argument of type `{}`", ty));
+
+        return Err(Error::Argument(index, e));
+    }
+    if index < args.len() {
+        let e = VariantError::Message(format!("unexpected argument `{}`", args[index].as_ref()));
+
+        return Err(Error::Argument(index, e));
+    }
+
+    types
+        .into_iter()
+        .zip(args)
+        .enumerate()
+        .map(|(i, (ty, arg))| {
+            Value::from_str_with_signature(arg.as_ref(), &Signature::from_str_unchecked(ty))
+                .map_err(|e| Error::Argument(i, e))
+        })
+        .collect()
+}
+
+// A random value, from the RNG of the handshake being polled if it was seeded for tests.
+pub(crate) fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    #[cfg(any(test, feature = "test-util"))]
+    {
+        if let Some(value) = crate::test_util::random() {
+            return value;
+        }
+    }
+
+    rand::random()
+}
@@ -20,9 +20,13 @@ use serde::{Deserialize, Serialize};
 use serde_xml_rs::{from_reader, from_str, to_writer};
 use static_assertions::assert_impl_all;
 use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fmt,
     io::{Read, Write},
     result::Result,
 };
+use zvariant::Signature;
 
 use crate::Error;
 
@@ -81,6 +85,13 @@ impl Arg {
         &self.r#type
     }
 
+    /// Return the argument type as a signature.
+    ///
+    /// Returns an error if the type is empty or not a valid signature.
+    pub fn signature(&self) -> Result<Signature<'_>, Error> {
+        type_signature(&self.r#type)
+    }
+
     /// Return the argument direction (should be "in" or "out"), if any.
     pub fn direction(&self) -> Option<&str> {
         self.direction.as_deref()
@@ -125,6 +136,26 @@ impl Method {
     pub fn annotations(&self) -> Vec<&Annotation> {
         get_vec!(self.elems, MethodElement::Annotation)
     }
+
+    /// Return the signature of the method call body, i.e the types of all the "in" arguments.
+    ///
+    /// Arguments without a direction are "in" arguments, as per the specification.
+    pub fn input_signature(&self) -> Result<Signature<'static>, Error> {
+        args_signature(
+            self.args()
+                .into_iter()
+                .filter(|arg| arg.direction().unwrap_or("in") == "in"),
+        )
+    }
+
+    /// Return the signature of the method reply body, i.e the types of all the "out" arguments.
+    pub fn output_signature(&self) -> Result<Signature<'static>, Error> {
+        args_signature(
+            self.args()
+                .into_iter()
+                .filter(|arg| arg.direction() == Some("out")),
+        )
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -160,6 +191,38 @@ impl Signal {
     pub fn annotations(&self) -> Vec<&Annotation> {
         get_vec!(self.elems, SignalElement::Annotation)
     }
+
+    /// Return the signature of the signal body, i.e the types of all the arguments.
+    pub fn signature(&self) -> Result<Signature<'static>, Error> {
+        args_signature(self.args().into_iter())
+    }
+}
+
+/// The access mode of a [`Property`].
+///
+/// [`Property`]: struct.Property.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PropertyAccess {
+    /// The property can only be read.
+    Read,
+    /// The property can only be written.
+    Write,
+    /// The property can be read and written.
+    ReadWrite,
+}
+
+assert_impl_all!(PropertyAccess: Send, Sync, Unpin);
+
+impl PropertyAccess {
+    /// Returns `true` if the property can be read.
+    pub fn read(self) -> bool {
+        self != PropertyAccess::Write
+    }
+
+    /// Returns `true` if the property can be written.
+    pub fn write(self) -> bool {
+        self != PropertyAccess::Read
+    }
 }
 
 /// A property
@@ -186,11 +249,32 @@ impl Property {
         &self.r#type
     }
 
+    /// Returns the property type as a signature.
+    ///
+    /// Returns an error if the type is empty or not a valid signature.
+    pub fn signature(&self) -> Result<Signature<'_>, Error> {
+        type_signature(&self.r#type)
+    }
+
     /// Returns the property access flags (should be "read", "write" or "readwrite").
     pub fn access(&self) -> &str {
         &self.access
     }
 
+    /// Returns the property access flags as a [`PropertyAccess`].
+    ///
+    /// Returns `None` if the flags are not one of the values allowed by the specification.
+    ///
+    /// [`PropertyAccess`]: enum.PropertyAccess.html
+    pub fn access_mode(&self) -> Option<PropertyAccess> {
+        match self.access.as_str() {
+            "read" => Some(PropertyAccess::Read),
+            "write" => Some(PropertyAccess::Write),
+            "readwrite" => Some(PropertyAccess::ReadWrite),
+            _ => None,
+        }
+    }
+
     /// Return the associated annotations.
     pub fn annotations(&self) -> Vec<&Annotation> {
         self.annotations.iter().collect()
@@ -242,6 +326,21 @@ impl Interface {
     pub fn annotations(&self) -> Vec<&Annotation> {
         get_vec!(self.elems, InterfaceElement::Annotation)
     }
+
+    /// Returns the method named `name`, if any.
+    pub fn lookup_method(&self, name: &str) -> Option<&Method> {
+        self.methods().into_iter().find(|m| m.name() == name)
+    }
+
+    /// Returns the signal named `name`, if any.
+    pub fn lookup_signal(&self, name: &str) -> Option<&Signal> {
+        self.signals().into_iter().find(|s| s.name() == name)
+    }
+
+    /// Returns the property named `name`, if any.
+    pub fn lookup_property(&self, name: &str) -> Option<&Property> {
+        self.properties().into_iter().find(|p| p.name() == name)
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -287,6 +386,264 @@ impl Node {
     pub fn interfaces(&self) -> Vec<&Interface> {
         get_vec!(self.elems, NodeElement::Interface)
     }
+
+    /// Compare this tree against the `other` tree.
+    ///
+    /// Members are compared by name and signature only; argument names and annotations are
+    /// ignored. Members of added or removed interfaces and nodes are not reported separately. This
+    /// is mostly useful to detect unexpected API changes in tests:
+    ///
+    /// ```
+    /// # use std::str::FromStr;
+    /// # use zbus::xml::Node;
+    /// let old = Node::from_str(r#"<node><interface name="org.zbus.Foo"/></node>"#)?;
+    /// let new = Node::from_str(r#"<node><interface name="org.zbus.Bar"/></node>"#)?;
+    ///
+    /// let diff = old.diff(&new);
+    /// assert_eq!(diff.added()[0].to_string(), "/ org.zbus.Bar");
+    /// assert_eq!(diff.removed()[0].to_string(), "/ org.zbus.Foo");
+    /// assert!(diff.changed().is_empty());
+    /// # Ok::<(), zbus::Error>(())
+    /// ```
+    pub fn diff(&self, other: &Node) -> NodeDiff {
+        let mut diff = NodeDiff::default();
+        diff_nodes(&mut diff, String::from("/"), self, other);
+
+        diff
+    }
+}
+
+/// A member of an introspection tree, as reported by [`Node::diff`].
+///
+/// Node paths are relative to the compared nodes, which are designated by `/`. The [`Display`]
+/// implementation shows the node path, followed by the member name qualified by its interface.
+///
+/// [`Node::diff`]: struct.Node.html#method.diff
+/// [`Display`]: https://doc.rust-lang.org/std/fmt/trait.Display.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Member {
+    /// A child node.
+    Node(String),
+    /// An interface on a node.
+    Interface {
+        /// The node path.
+        node: String,
+        /// The interface name.
+        name: String,
+    },
+    /// A method of an interface.
+    Method {
+        /// The node path.
+        node: String,
+        /// The interface name.
+        interface: String,
+        /// The method name.
+        name: String,
+    },
+    /// A signal of an interface.
+    Signal {
+        /// The node path.
+        node: String,
+        /// The interface name.
+        interface: String,
+        /// The signal name.
+        name: String,
+    },
+    /// A property of an interface.
+    Property {
+        /// The node path.
+        node: String,
+        /// The interface name.
+        interface: String,
+        /// The property name.
+        name: String,
+    },
+}
+
+assert_impl_all!(Member: Send, Sync, Unpin);
+
+impl fmt::Display for Member {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Member::Node(node) => write!(f, "{}", node),
+            Member::Interface { node, name } => write!(f, "{} {}", node, name),
+            Member::Method {
+                node,
+                interface,
+                name,
+            }
+            | Member::Signal {
+                node,
+                interface,
+                name,
+            }
+            | Member::Property {
+                node,
+                interface,
+                name,
+            } => write!(f, "{} {}.{}", node, interface, name),
+        }
+    }
+}
+
+/// The differences between two introspection trees, as returned by [`Node::diff`].
+///
+/// [`Node::diff`]: struct.Node.html#method.diff
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeDiff {
+    added: Vec<Member>,
+    removed: Vec<Member>,
+    changed: Vec<Member>,
+}
+
+assert_impl_all!(NodeDiff: Send, Sync, Unpin);
+
+impl NodeDiff {
+    /// Returns the members only present in the other tree.
+    pub fn added(&self) -> &[Member] {
+        &self.added
+    }
+
+    /// Returns the members only present in this tree.
+    pub fn removed(&self) -> &[Member] {
+        &self.removed
+    }
+
+    /// Returns the methods, signals and properties present in both trees, but with a different
+    /// signature (or access, for properties).
+    pub fn changed(&self) -> &[Member] {
+        &self.changed
+    }
+
+    /// Returns `true` if the trees are equivalent.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+fn type_signature(ty: &str) -> Result<Signature<'_>, Error> {
+    let signature = Signature::try_from(ty)?;
+    if signature.is_empty() {
+        return Err(zvariant::Error::IncorrectType.into());
+    }
+
+    Ok(signature)
+}
+
+fn args_signature<'a>(args: impl Iterator<Item = &'a Arg>) -> Result<Signature<'static>, Error> {
+    let mut signature = String::new();
+    for arg in args {
+        signature.push_str(arg.signature()?.as_str());
+    }
+
+    Ok(Signature::try_from(signature)?)
+}
+
+// Compare the items of `old` and `new` by name, calling `both` for items present in both.
+fn diff_by_name<'a, T, N, M, B>(
+    diff: &mut NodeDiff,
+    old: Vec<&'a T>,
+    new: Vec<&'a T>,
+    name: N,
+    member: M,
+    mut both: B,
+) where
+    N: Fn(&T) -> &str,
+    M: Fn(&str) -> Member,
+    B: FnMut(&mut NodeDiff, &'a T, &'a T),
+{
+    let mut old: BTreeMap<_, _> = old.into_iter().map(|t| (name(t), t)).collect();
+    for new in new {
+        match old.remove(name(new)) {
+            Some(old) => both(diff, old, new),
+            None => diff.added.push(member(name(new))),
+        }
+    }
+    diff.removed.extend(old.keys().map(|n| member(n)));
+}
+
+fn diff_nodes(diff: &mut NodeDiff, path: String, old: &Node, new: &Node) {
+    diff_by_name(
+        diff,
+        old.interfaces(),
+        new.interfaces(),
+        Interface::name,
+        |name| Member::Interface {
+            node: path.clone(),
+            name: name.to_owned(),
+        },
+        |diff, old, new| diff_interfaces(diff, &path, old, new),
+    );
+
+    let child_path = |name: &str| {
+        if path.ends_with('/') {
+            format!("{}{}", path, name)
+        } else {
+            format!("{}/{}", path, name)
+        }
+    };
+    diff_by_name(
+        diff,
+        old.nodes(),
+        new.nodes(),
+        |n| n.name().unwrap_or_default(),
+        |name| Member::Node(child_path(name)),
+        |diff, old, new| {
+            let name = old.name().unwrap_or_default();
+            diff_nodes(diff, child_path(name), old, new)
+        },
+    );
+}
+
+fn diff_interfaces(diff: &mut NodeDiff, path: &str, old: &Interface, new: &Interface) {
+    macro_rules! member {
+        ($kind:ident) => {
+            |name: &str| Member::$kind {
+                node: path.to_owned(),
+                interface: old.name().to_owned(),
+                name: name.to_owned(),
+            }
+        };
+    }
+
+    diff_by_name(
+        diff,
+        old.methods(),
+        new.methods(),
+        Method::name,
+        member!(Method),
+        |diff, o, n| {
+            let changed = o.input_signature().ok() != n.input_signature().ok()
+                || o.output_signature().ok() != n.output_signature().ok();
+            if changed {
+                diff.changed.push(member!(Method)(o.name()));
+            }
+        },
+    );
+    diff_by_name(
+        diff,
+        old.signals(),
+        new.signals(),
+        Signal::name,
+        member!(Signal),
+        |diff, o, n| {
+            if o.signature().ok() != n.signature().ok() {
+                diff.changed.push(member!(Signal)(o.name()));
+            }
+        },
+    );
+    diff_by_name(
+        diff,
+        old.properties(),
+        new.properties(),
+        Property::name,
+        member!(Property),
+        |diff, o, n| {
+            if o.ty() != n.ty() || o.access() != n.access() {
+                diff.changed.push(member!(Property)(o.name()));
+            }
+        },
+    );
 }
 
 impl std::str::FromStr for Node {
@@ -303,7 +660,10 @@ mod tests {
     use std::{error::Error, str::FromStr};
     use test_env_log::test;
 
-    use super::Node;
+    use super::{Member, Node, PropertyAccess};
+
+    static SYSTEMD: &str = include_str!("../tests/data/org.freedesktop.systemd1.xml");
+    static NETWORK_MANAGER: &str = include_str!("../tests/data/org.freedesktop.NetworkManager.xml");
 
     static EXAMPLE: &str = r##"
 <!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
@@ -350,4 +710,164 @@ mod tests {
         //node.to_writer(&mut writer).unwrap();
         Ok(())
     }
+
+    #[test]
+    fn signatures() -> Result<(), Box<dyn Error>> {
+        let node = Node::from_str(SYSTEMD)?;
+        let manager = node
+            .interfaces()
+            .into_iter()
+            .find(|i| i.name() == "org.freedesktop.systemd1.Manager")
+            .unwrap();
+
+        let start = manager.lookup_method("StartTransientUnit").unwrap();
+        assert_eq!(start.args()[3].signature()?, "a(sa(sv))");
+        assert_eq!(start.input_signature()?, "ssa(sv)a(sa(sv))");
+        assert_eq!(start.output_signature()?, "o");
+        let enable = manager.lookup_method("EnableUnitFiles").unwrap();
+        assert_eq!(enable.output_signature()?, "ba(sss)");
+        let reload = manager.lookup_method("Reload").unwrap();
+        assert_eq!(reload.input_signature()?, "");
+        assert_eq!(reload.output_signature()?, "");
+        assert!(manager.lookup_method("StartUnitReplace").is_none());
+
+        let startup = manager.lookup_signal("StartupFinished").unwrap();
+        assert_eq!(startup.signature()?, "tttttt");
+        assert!(manager.lookup_signal("Reload").is_none());
+
+        let version = manager.lookup_property("Version").unwrap();
+        assert_eq!(version.signature()?, "s");
+        assert_eq!(version.access_mode(), Some(PropertyAccess::Read));
+        let log_level = manager.lookup_property("LogLevel").unwrap();
+        assert_eq!(log_level.access_mode(), Some(PropertyAccess::ReadWrite));
+        assert!(log_level.access_mode().unwrap().write());
+
+        let node = Node::from_str(NETWORK_MANAGER)?;
+        let nm = node
+            .interfaces()
+            .into_iter()
+            .find(|i| i.name() == "org.freedesktop.NetworkManager")
+            .unwrap();
+        let add = nm.lookup_method("AddAndActivateConnection").unwrap();
+        assert_eq!(add.input_signature()?, "a{sa{sv}}oo");
+        assert_eq!(add.output_signature()?, "oo");
+        let dns = nm.lookup_property("GlobalDnsConfiguration").unwrap();
+        assert_eq!(dns.signature()?, "a{sv}");
+        assert!(nm
+            .lookup_signal("CheckPermissions")
+            .unwrap()
+            .args()
+            .is_empty());
+
+        // Interfaces are looked up within their own members only.
+        assert!(nm.lookup_method("GetUnit").is_none());
+
+        let invalid = Node::from_str(
+            r#"<node><interface name="org.zbus.Invalid">
+                <method name="Broken"><arg type="a(iz)" direction="in"/></method>
+                <property name="Weird" type="" access="sometimes"/>
+            </interface></node>"#,
+        )?;
+        let iface = &invalid.interfaces()[0];
+        assert!(iface
+            .lookup_method("Broken")
+            .unwrap()
+            .input_signature()
+            .is_err());
+        let weird = iface.lookup_property("Weird").unwrap();
+        assert!(weird.signature().is_err());
+        assert_eq!(weird.access_mode(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn diff() -> Result<(), Box<dyn Error>> {
+        let systemd = Node::from_str(SYSTEMD)?;
+        assert!(systemd.diff(&systemd).is_empty());
+
+        let changed = SYSTEMD
+            // Changed method signature.
+            .replace(
+                r#"<arg type="i" name="signal" direction="in"/>"#,
+                r#"<arg type="u" name="signal" direction="in"/>"#,
+            )
+            // Changed property access.
+            .replace(
+                r#"name="RuntimeWatchdogUSec" type="t" access="readwrite""#,
+                r#"name="RuntimeWatchdogUSec" type="t" access="read""#,
+            )
+            // Renamed signal.
+            .replace(
+                r#"<signal name="Reloading">"#,
+                r#"<signal name="Reloaded">"#,
+            )
+            // Argument names don't matter.
+            .replace(r#"name="machine_uuid""#, r#"name="id""#)
+            // Removed node.
+            .replace(r#"<node name="job"/>"#, "");
+        let changed = Node::from_str(&changed)?;
+        let diff = systemd.diff(&changed);
+
+        let member = |kind: fn(String, String, String) -> Member, name: &str| {
+            kind(
+                "/".into(),
+                "org.freedesktop.systemd1.Manager".into(),
+                name.into(),
+            )
+        };
+        let signal = |node, interface, name| Member::Signal {
+            node,
+            interface,
+            name,
+        };
+        let method = |node, interface, name| Member::Method {
+            node,
+            interface,
+            name,
+        };
+        let property = |node, interface, name| Member::Property {
+            node,
+            interface,
+            name,
+        };
+        assert_eq!(diff.added(), &[member(signal, "Reloaded")]);
+        assert_eq!(
+            diff.removed(),
+            &[member(signal, "Reloading"), Member::Node("/job".into())]
+        );
+        assert_eq!(
+            diff.changed(),
+            &[
+                member(method, "KillUnit"),
+                member(property, "RuntimeWatchdogUSec")
+            ]
+        );
+        assert_eq!(
+            diff.changed()[0].to_string(),
+            "/ org.freedesktop.systemd1.Manager.KillUnit"
+        );
+
+        // Added and removed interfaces are reported as a whole.
+        let network_manager = Node::from_str(NETWORK_MANAGER)?;
+        let diff = systemd.diff(&network_manager);
+        assert_eq!(
+            diff.added()[0],
+            Member::Interface {
+                node: "/".into(),
+                name: "org.freedesktop.NetworkManager".into(),
+            }
+        );
+        assert_eq!(
+            diff.removed()[0],
+            Member::Interface {
+                node: "/".into(),
+                name: "org.freedesktop.systemd1.Manager".into(),
+            }
+        );
+        assert_eq!(diff.added().len(), 1 + 8);
+        assert!(diff.changed().is_empty());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,101 @@
+//! Checks that large arrays are read from messages without copying them.
+//!
+//! This is a test binary of its own, so that the allocations of the whole process can be counted.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use zbus::{Message, MessageError};
+
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::SeqCst);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// The number of bytes allocated while running `f`.
+fn allocated_by<F: FnOnce() -> R, R>(f: F) -> (usize, R) {
+    let before = ALLOCATED.load(Ordering::SeqCst);
+    let res = f();
+
+    (ALLOCATED.load(Ordering::SeqCst) - before, res)
+}
+
+const MIB: usize = 1024 * 1024;
+
+#[test]
+fn body_array() {
+    // A byte array, as is.
+    let data: Vec<u8> = (0..16 * MIB).map(|i| i as u8).collect();
+    let msg = Message::method(None, None, "/", None, "Put", &data).unwrap();
+    let (allocated, matches) = allocated_by(|| {
+        let array = msg.body_array::<u8>().unwrap();
+        array.as_slice() == &data[..]
+    });
+    assert!(matches);
+    // Parsing the header allocates a little, but nothing close to the size of the data.
+    assert!(allocated < 4096, "{} bytes allocated", allocated);
+    // Unlike the generic path, which makes a copy.
+    let (allocated, body) = allocated_by(|| msg.body::<Vec<u8>>().unwrap());
+    assert_eq!(body, data);
+    assert!(allocated >= 16 * MIB, "{} bytes allocated", allocated);
+    drop(body);
+
+    // Arrays of other fixed-size types, including structures, through their elements.
+    let data: Vec<u64> = (0..MIB as u64).collect();
+    let msg = Message::method(None, None, "/", None, "Put", &data).unwrap();
+    let (allocated, sum) = allocated_by(|| msg.body_array::<u64>().unwrap().iter().sum::<u64>());
+    assert_eq!(sum, data.iter().sum());
+    assert!(allocated < 4096, "{} bytes allocated", allocated);
+
+    let data: Vec<(u32, i32)> = (0..MIB as u32).map(|i| (i, -(i as i32))).collect();
+    let msg = Message::method(None, None, "/", None, "Put", &data).unwrap();
+    let (allocated, matches) = allocated_by(|| {
+        let array = msg.body_array::<(u32, i32)>().unwrap();
+        array.iter().eq(data.iter().copied())
+    });
+    assert!(matches);
+    assert!(allocated < 4096, "{} bytes allocated", allocated);
+    // Structures are 8-byte aligned, which these are already.
+    assert_eq!(
+        msg.body_array::<(u32, i32)>().unwrap().as_bytes().len(),
+        8 * MIB
+    );
+
+    // Empty arrays still have the padding before their first element.
+    let msg = Message::method(None, None, "/", None, "Put", &Vec::<(u64,)>::new()).unwrap();
+    let array = msg.body_array::<(u64,)>().unwrap();
+    assert!(array.is_empty());
+    assert_eq!(array.iter().count(), 0);
+
+    // Only whole-body arrays of the exact element type are supported.
+    let msg = Message::method(None, None, "/", None, "Put", &(vec![1u8], 2u8)).unwrap();
+    assert_eq!(
+        msg.body_array::<u8>().unwrap_err(),
+        MessageError::UnmatchedBodySignature
+    );
+    let msg = Message::method(None, None, "/", None, "Put", &vec![1u16]).unwrap();
+    assert_eq!(
+        msg.body_array::<i16>().unwrap_err(),
+        MessageError::UnmatchedBodySignature
+    );
+}
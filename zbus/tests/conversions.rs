@@ -0,0 +1,175 @@
+use std::{
+    cell::Cell,
+    os::unix::{io::AsRawFd, net::UnixStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use async_io::block_on;
+use ntest::timeout;
+use test_env_log::test;
+use zbus::{azync, dbus_interface, Connection, Guid, ObjectServer, Proxy};
+
+const PATH: &str = "/org/zbus/Counter";
+const IFACE: &str = "org.zbus.Counter";
+
+struct Counter {
+    count: u32,
+    done: bool,
+}
+
+#[dbus_interface(name = "org.zbus.Counter")]
+impl Counter {
+    fn next(&mut self) -> u32 {
+        self.count += 1;
+
+        self.count
+    }
+
+    fn done(&mut self) {
+        self.done = true;
+    }
+
+    #[dbus_interface(signal)]
+    fn counted(&self, count: u32) -> zbus::Result<()>;
+}
+
+// Without the internal executor, nothing runs the executor of `conn` for us.
+fn run_executor(conn: &azync::Connection) {
+    #[cfg(not(feature = "internal-executor"))]
+    {
+        let conn = conn.clone();
+        thread::spawn(move || {
+            block_on(async move {
+                loop {
+                    conn.executor().tick().await;
+                }
+            })
+        });
+    }
+    #[cfg(feature = "internal-executor")]
+    let _ = conn;
+}
+
+// A `Proxy` counting on the `Counter` of the peer, the `Counted` signals being recorded in `counts`.
+fn counter_proxy(conn: &Connection, counts: &Arc<Mutex<Vec<u32>>>) -> Proxy<'static> {
+    let proxy = Proxy::new(conn, IFACE, PATH, IFACE).unwrap();
+    let counts = counts.clone();
+    proxy
+        .connect_signal("Counted", move |msg| {
+            counts.lock().unwrap().push(msg.body()?);
+
+            Ok(())
+        })
+        .unwrap();
+
+    proxy
+}
+
+#[test]
+#[timeout(15000)]
+fn p2p_round_trip() {
+    let (p0, p1) = UnixStream::pair().unwrap();
+    let guid = Guid::generate();
+
+    let server_thread = thread::spawn(move || {
+        // Serving from a blocking wrapper of an async connection.
+        let conn = block_on(azync::Connection::new_unix_server(p0, &guid)).unwrap();
+        run_executor(&conn);
+        let mut object_server = ObjectServer::new(&Connection::from(conn));
+        let counter = Counter {
+            count: 0,
+            done: false,
+        };
+        object_server.at(PATH, counter).unwrap();
+
+        let done = Cell::new(false);
+        while !done.get() {
+            object_server.try_handle_next().unwrap();
+            object_server
+                .with(PATH, |counter: &Counter| {
+                    done.set(counter.done);
+                    if counter.done {
+                        return Ok(());
+                    }
+
+                    counter.counted(counter.count)
+                })
+                .unwrap();
+        }
+    });
+
+    // A synchronous initialization phase..
+    let conn = Connection::new_unix_client(p1, false).unwrap();
+    run_executor(conn.inner());
+    let fd = conn.as_raw_fd();
+    let counts = Arc::new(Mutex::new(vec![]));
+    let proxy = counter_proxy(&conn, &counts);
+    assert_eq!(proxy.call::<_, u32>("Next", &()).unwrap(), 1);
+    while proxy.next_signal().unwrap().is_some() {}
+
+    // ..followed by async code, on the same connection.
+    let conn = conn.into_inner();
+    let proxy = proxy.into_inner();
+    block_on(async {
+        assert_eq!(conn.as_raw_fd().await, fd);
+        assert_eq!(proxy.call::<_, u32>("Next", &()).await.unwrap(), 2);
+        // Skipping the other messages, until the handler connected from the blocking `Proxy` is
+        // called.
+        while proxy.next_signal().await.unwrap().is_some() {}
+    });
+    assert_eq!(*counts.lock().unwrap(), [1, 2]);
+
+    // And back.
+    let conn = Connection::from(conn);
+    let proxy = Proxy::from(proxy);
+    assert_eq!(conn.as_raw_fd(), fd);
+    assert_eq!(proxy.call::<_, u32>("Next", &()).unwrap(), 3);
+    while proxy.next_signal().unwrap().is_some() {}
+    assert_eq!(*counts.lock().unwrap(), [1, 2, 3]);
+
+    conn.call_method(None, PATH, Some(IFACE), "Done", &())
+        .unwrap();
+    server_thread.join().unwrap();
+}
+
+#[test]
+#[timeout(15000)]
+#[cfg(feature = "test-bus")]
+fn bus_round_trip() {
+    use zbus::{fdo, test_bus::TestBus};
+
+    let bus = TestBus::new().unwrap();
+    let service = bus.connect().unwrap();
+    run_executor(service.inner());
+    fdo::DBusProxy::new(&service)
+        .unwrap()
+        .request_name(IFACE, Default::default())
+        .unwrap();
+    let client = bus.connect().unwrap();
+    run_executor(client.inner());
+    let counts = Arc::new(Mutex::new(vec![]));
+    let proxy = counter_proxy(&client, &counts);
+
+    // The name of the service and the match rule of the proxy outlive the conversions.
+    let service = service.into_inner();
+    let proxy = proxy.into_inner();
+    block_on(async {
+        assert!(service.is_name_owner(IFACE).await.unwrap());
+        service
+            .emit_signal(None, PATH, IFACE, "Counted", &1u32)
+            .await
+            .unwrap();
+        while proxy.next_signal().await.unwrap().is_some() {}
+    });
+    assert_eq!(*counts.lock().unwrap(), [1]);
+
+    let service = Connection::from(service);
+    let proxy = Proxy::from(proxy);
+    assert!(service.is_name_owner(IFACE).unwrap());
+    service
+        .emit_signal(None, PATH, IFACE, "Counted", &2u32)
+        .unwrap();
+    while proxy.next_signal().unwrap().is_some() {}
+    assert_eq!(*counts.lock().unwrap(), [1, 2]);
+}
@@ -0,0 +1,50 @@
+#![cfg(feature = "tcp-transport")]
+
+use std::{fs, net::TcpListener, thread};
+
+use ntest::timeout;
+use test_env_log::test;
+use zbus::{ConnectionBuilder, CookieSha1, Guid};
+
+#[test]
+#[timeout(15000)]
+fn tcp() {
+    let dir = std::env::temp_dir().join(format!("zbus-cookie-sha1-tcp-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let guid = Guid::generate();
+    let server_dir = dir.clone();
+    let server_thread = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        // Over TCP, the server only accepts `DBUS_COOKIE_SHA1` and custom mechanisms.
+        let conn = ConnectionBuilder::tcp_stream(stream)
+            .server(&guid)
+            .auth_mechanism(CookieSha1::server().keyring_dir(server_dir))
+            .build()
+            .unwrap();
+
+        let msg = conn.receive_message().unwrap();
+        assert_eq!(msg.to_string(), "Method call Ping");
+        conn.reply(&msg, &"pong").unwrap();
+    });
+
+    let conn = ConnectionBuilder::address(&format!("tcp:host=127.0.0.1,port={}", port))
+        .p2p()
+        .auth_mechanism(CookieSha1::client().keyring_dir(&dir))
+        .build()
+        .unwrap();
+    let reply = conn
+        .call_method(None, "/", Some("org.zbus.Cookie"), "Ping", &())
+        .unwrap();
+    assert_eq!(reply.body::<&str>().unwrap(), "pong");
+    server_thread.join().unwrap();
+
+    // The server created the keyring directory, private to the user.
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(&dir).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,46 @@
+//! Relays its stdin and stdout to the unix socket given as its only argument, as a proxy for the
+//! `unixexec:` transport would. Only used by the tests.
+
+use std::{
+    env,
+    fs::File,
+    io::{self, Read, Write},
+    os::unix::{io::FromRawFd, net::UnixStream},
+    process, thread,
+};
+
+// Copy everything from `from` to `to`, without any buffering on the way.
+fn relay(mut from: impl Read, mut to: impl Write) -> io::Result<()> {
+    let mut buffer = [0; 4096];
+    loop {
+        let n = from.read(&mut buffer)?;
+        if n == 0 {
+            return Ok(());
+        }
+        to.write_all(&buffer[..n])?;
+    }
+}
+
+fn main() {
+    let path = match env::args_os().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: unixexec-bridge SOCKET_PATH");
+            process::exit(1);
+        }
+    };
+    let socket = UnixStream::connect(path).expect("failed to connect");
+    let writer = socket.try_clone().expect("failed to clone the socket");
+
+    // std's stdout is line-buffered, so we write to the file descriptor directly.
+    // SAFETY: nothing else in this process uses the stdout file descriptor.
+    let stdout = unsafe { File::from_raw_fd(1) };
+    thread::spawn(move || {
+        let _ = relay(socket, stdout);
+        // The other side went away.
+        process::exit(0);
+    });
+
+    let _ = relay(io::stdin(), &writer);
+    let _ = writer.shutdown(std::net::Shutdown::Write);
+}
@@ -0,0 +1,38 @@
+#![cfg(all(feature = "test-bus", feature = "unixexec-transport"))]
+
+use ntest::timeout;
+use test_env_log::test;
+use zbus::{fdo, test_bus::TestBus, Connection};
+
+#[test]
+#[timeout(15000)]
+fn unixexec() {
+    let bus = TestBus::new().unwrap();
+    // The bridge connects to the socket of the bus, which is the `path` of its address.
+    let socket = bus
+        .address()
+        .trim_start_matches("unix:path=")
+        .split(',')
+        .next()
+        .unwrap()
+        .to_string();
+    let address = format!(
+        "unixexec:path={},argv0=bridge,argv1={}",
+        env!("CARGO_BIN_EXE_zbus-unixexec-bridge"),
+        socket
+    );
+
+    let conn = Connection::new_for_address(&address, true).unwrap();
+    assert!(conn.unique_name().is_some());
+    assert_eq!(conn.address(), Some(address.as_str()));
+
+    let dbus = fdo::DBusProxy::new(&conn).unwrap();
+    dbus.request_name("org.zbus.Unixexec", Default::default())
+        .unwrap();
+    let other = bus.connect().unwrap();
+    let owner = fdo::DBusProxy::new(&other)
+        .unwrap()
+        .get_name_owner("org.zbus.Unixexec")
+        .unwrap();
+    assert_eq!(owner, conn.unique_name().unwrap());
+}
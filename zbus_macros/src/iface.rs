@@ -2,9 +2,10 @@ use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use std::collections::{btree_map::Entry, BTreeMap};
 use syn::{
-    self, parse_quote, punctuated::Punctuated, AngleBracketedGenericArguments, AttributeArgs,
-    FnArg, ImplItem, ItemImpl, Lit::Str, Meta, Meta::NameValue, MetaList, MetaNameValue,
-    NestedMeta, PatType, PathArguments, ReturnType, Signature, Token, Type, TypePath,
+    self, parse_quote, punctuated::Punctuated, AngleBracketedGenericArguments, Attribute,
+    AttributeArgs, FnArg, GenericParam, Ident, ImplItem, ImplItemMethod, ItemImpl, ItemTrait,
+    Lit::Str, Meta, Meta::NameValue, MetaList, MetaNameValue, NestedMeta, PatType, PathArguments,
+    ReturnType, Signature, Token, Type, TypePath, Visibility,
 };
 
 use crate::utils::*;
@@ -15,6 +16,10 @@ struct Property<'a> {
     write: bool,
     ty: Option<&'a Type>,
     doc_comments: TokenStream,
+    // The call of the setter, if any.
+    set_call: Option<TokenStream>,
+    // The value of the `EmitsChangedSignal` annotation, if not the default.
+    emits_changed_signal: Option<String>,
 }
 
 impl<'a> Property<'a> {
@@ -24,8 +29,14 @@ impl<'a> Property<'a> {
             write: false,
             ty: None,
             doc_comments: quote!(),
+            set_call: None,
+            emits_changed_signal: None,
         }
     }
+
+    fn emits_changed(&self) -> bool {
+        self.emits_changed_signal.is_none()
+    }
 }
 
 pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStream> {
@@ -35,10 +46,13 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
     let mut set_dispatch = quote!();
     let mut get_dispatch = quote!();
     let mut get_all = quote!();
-    let mut call_dispatch = quote!();
-    let mut call_mut_dispatch = quote!();
+    let mut try_get_all = quote!();
+    let mut call_dispatch = vec![];
+    let mut call_mut_dispatch = vec![];
     let mut introspect = quote!();
     let mut generated_signals = quote!();
+    let mut methods_metadata = quote!();
+    let mut signals_metadata = quote!();
 
     // the impl Type
     let ty = match input.self_ty.as_ref() {
@@ -53,8 +67,39 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
     };
 
     let mut iface_name = None;
+    // The names of the other versions of the interface.
+    let mut versions = vec![];
+    let mut proxy_args = None;
+    // Whether properties can be set all at once, and the method validating them first.
+    let mut atomic_properties = false;
+    let mut validate_properties = None;
     for arg in args {
         match arg {
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("proxy") => {
+                proxy_args = Some(vec![]);
+            }
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("atomic_properties") => {
+                atomic_properties = true;
+            }
+            NestedMeta::Meta(NameValue(nv)) if nv.path.is_ident("atomic_properties") => {
+                if let Str(lit) = nv.lit {
+                    atomic_properties = true;
+                    validate_properties = Some(lit.parse::<Ident>()?);
+                } else {
+                    panic!("Invalid atomic_properties argument")
+                }
+            }
+            NestedMeta::Meta(Meta::List(l)) if l.path.is_ident("proxy") => {
+                proxy_args = Some(l.nested.into_iter().collect());
+            }
+            NestedMeta::Meta(Meta::List(l)) if l.path.is_ident("versions") => {
+                for version in l.nested {
+                    match version {
+                        NestedMeta::Lit(Str(lit)) => versions.push(lit.value()),
+                        _ => panic!("Invalid versions argument"),
+                    }
+                }
+            }
             NestedMeta::Meta(NameValue(nv)) => {
                 if nv.path.is_ident("interface") || nv.path.is_ident("name") {
                     if let Str(lit) = nv.lit {
@@ -70,6 +115,11 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
         }
     }
     let iface_name = iface_name.unwrap_or(format!("org.freedesktop.{}", ty));
+    // Generated before the methods get rewritten below, as it needs their original arguments.
+    let proxy = match proxy_args {
+        Some(args) => Some(gen_proxy(&input, ty, &iface_name, args, atomic_properties)?),
+        None => None,
+    };
 
     for method in &mut input.items {
         let mut method = match method {
@@ -77,6 +127,12 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
             _ => continue,
         };
 
+        let has_type_params = method
+            .sig
+            .generics
+            .params
+            .iter()
+            .any(|p| matches!(p, GenericParam::Type(_)));
         let Signature {
             ident,
             inputs,
@@ -133,23 +189,47 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
 
         let mut intro_args = quote!();
         intro_args.extend(introspect_input_args(&typed_inputs, is_signal));
-        let is_result_output = introspect_add_output_args(&mut intro_args, output, &out_args)?;
+        let input_signature = gen_signature_fn(if has_type_params {
+            None
+        } else {
+            args_tuple_type(&typed_inputs)
+        });
+        // A deferred method replies through its responder, with the type of the latter.
+        let deferred_ty = deferred_reply_type(typed_inputs.iter().copied())?;
+        let output_signature = gen_signature_fn(if has_type_params {
+            None
+        } else {
+            match (deferred_ty, &*output) {
+                (Some(ty), _) => Some(ty.clone()),
+                (None, ReturnType::Default) => Some(parse_quote!(())),
+                (None, output) => Some(get_property_type(output)?.clone()),
+            }
+        });
+        let is_result_output = match deferred_ty {
+            Some(ty) => {
+                if let ReturnType::Type(_, ret) = output {
+                    return Err(syn::Error::new_spanned(
+                        ret,
+                        "A method with a `deferred` argument replies through it, and can't return anything",
+                    ));
+                }
+                introspect_add_output_args(&mut intro_args, &parse_quote!(-> #ty), &out_args)?
+            }
+            None => introspect_add_output_args(&mut intro_args, output, &out_args)?,
+        };
+        let is_deferred = deferred_ty.is_some();
 
-        let (args_from_msg, args) = get_args_from_inputs(&typed_inputs, &zbus)?;
+        let (args_from_msg, args) = get_args_from_inputs(&typed_inputs)?;
 
         clean_input_args(inputs);
 
         let reply = if is_result_output {
-            let ret = quote!(r);
-
-            quote!(match reply {
-                ::std::result::Result::Ok(r) => c.reply(m, &#ret),
-                ::std::result::Result::Err(e) => {
-                    <#zbus::fdo::Error as ::std::convert::From<_>>::from(e).reply(c, m)
-                }
-            })
+            quote!(::std::result::Result::map_err(
+                reply,
+                <#zbus::fdo::Error as ::std::convert::From<_>>::from,
+            ))
         } else {
-            quote!(c.reply(m, &reply))
+            quote!(::std::result::Result::Ok(reply))
         };
 
         let member_name = attrs
@@ -170,6 +250,12 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
         if is_signal {
             introspect.extend(doc_comments);
             introspect.extend(introspect_signal(&member_name, &intro_args));
+            signals_metadata.extend(quote!(
+                #zbus::SignalMetadata {
+                    name: #member_name,
+                    signature: #input_signature,
+                },
+            ));
 
             method.block = parse_quote!({
                 #zbus::ObjectServer::local_node_emit_signal(
@@ -179,6 +265,38 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
                     &(#args),
                 )
             });
+
+            // The same, but with the connection and path given explicitly, for emitting the
+            // signal from outside of the object server.
+            let vis = &method.vis;
+            let on_ident = format_ident!("{}_on", ident);
+            let on_inputs = inputs.iter().skip(1);
+            let on_doc = format!(
+                " Emit the `{}` signal from the object at `path` on `connection`.\n\n\
+                 Unlike [`Self::{}`], this doesn't need to be called from a method call or from\n\
+                 [`ObjectServer::with`](zbus::ObjectServer::with).",
+                member_name, ident,
+            );
+            generated_signals.extend(quote! {
+                #[doc = #on_doc]
+                #vis fn #on_ident<'__p, __P, __E>(
+                    __connection: &#zbus::Connection,
+                    __path: __P,
+                    #(#on_inputs),*
+                ) -> #zbus::Result<()>
+                where
+                    __P: ::std::convert::TryInto<#zbus::export::zvariant::ObjectPath<'__p>, Error = __E>,
+                    __E: ::std::convert::Into<#zbus::MessageError>,
+                {
+                    __connection.emit_signal(
+                        ::std::option::Option::None,
+                        __path,
+                        #iface_name,
+                        #member_name,
+                        &(#args),
+                    )
+                }
+            });
         } else if is_property {
             let p = properties.entry(member_name.to_string());
             let prop_changed_method_name = format_ident!("{}_changed", snake_case(&member_name));
@@ -201,85 +319,358 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
                 generated_signals.extend(prop_changed_method);
             }
 
+            let skip_on_error = attrs.iter().any(|x| x.is_skip_on_error());
+            if skip_on_error && (has_inputs || !is_result_output) {
+                return Err(syn::Error::new_spanned(
+                    &ident,
+                    "`skip_on_error` requires a property getter returning a `Result`",
+                ));
+            }
+
             let p = p.or_insert_with(Property::new);
             p.doc_comments.extend(doc_comments);
+            if let Some(emits) = attrs.iter().find_map(|x| match x {
+                ItemAttribute::EmitsChangedSignal(e) => Some(e),
+                _ => None,
+            }) {
+                match emits.as_str() {
+                    "true" => (),
+                    "false" | "const" => p.emits_changed_signal = Some(emits.clone()),
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            &ident,
+                            "`emits_changed_signal` must be \"true\", \"false\" or \"const\"",
+                        ))
+                    }
+                }
+            }
             if has_inputs {
                 p.write = true;
 
-                let set_call = if is_result_output {
+                p.set_call = Some(if is_result_output {
                     quote!(self.#ident(val))
                 } else {
                     quote!(::std::result::Result::Ok(self.#ident(val)))
-                };
-                let q = quote!(
-                    #member_name => {
-                        let val = match ::std::convert::TryInto::try_into(value) {
-                            ::std::result::Result::Ok(val) => val,
-                            ::std::result::Result::Err(e) => {
-                                return ::std::option::Option::Some(::std::result::Result::Err(
-                                    ::std::convert::Into::into(#zbus::MessageError::Variant(e)),
-                                ));
-                            }
-                        };
-                        let result = #set_call.and_then(|set_result| {
-                            self.#prop_changed_method_name()?;
-                            ::std::result::Result::Ok(set_result)
-                        });
-                        ::std::option::Option::Some(result)
-                    }
-                );
-                set_dispatch.extend(q);
+                });
             } else {
                 p.ty = Some(get_property_type(output)?);
                 p.read = true;
 
-                let q = quote!(
-                    #member_name => {
-                        ::std::option::Option::Some(::std::result::Result::Ok(
-                            ::std::convert::Into::into(
-                                <#zbus::export::zvariant::Value as ::std::convert::From<_>>::from(
-                                    self.#ident(),
+                let into_value = quote!(::std::convert::Into::into(
+                    <#zbus::export::zvariant::Value as ::std::convert::From<_>>::from(value),
+                ));
+                let q = if is_result_output {
+                    quote!(
+                        #member_name => {
+                            ::std::option::Option::Some(match self.#ident() {
+                                ::std::result::Result::Ok(value) => {
+                                    ::std::result::Result::Ok(#into_value)
+                                }
+                                ::std::result::Result::Err(e) => ::std::result::Result::Err(
+                                    <#zbus::fdo::Error as ::std::convert::From<_>>::from(e),
                                 ),
-                            ),
-                        ))
-                    }
-                );
+                            })
+                        }
+                    )
+                } else {
+                    quote!(
+                        #member_name => {
+                            let value = self.#ident();
+                            ::std::option::Option::Some(::std::result::Result::Ok(#into_value))
+                        }
+                    )
+                };
                 get_dispatch.extend(q);
 
-                let q = quote!(
-                    props.insert(
-                        ::std::string::ToString::to_string(#member_name),
-                        ::std::convert::Into::into(
-                            <#zbus::export::zvariant::Value as ::std::convert::From<_>>::from(
-                                self.#ident(),
-                            ),
-                        ),
-                    );
+                let insert = quote!(
+                    props.insert(::std::string::ToString::to_string(#member_name), #into_value);
                 );
-                get_all.extend(q)
+                let q = if is_result_output {
+                    quote!(
+                        if let ::std::result::Result::Ok(value) = self.#ident() {
+                            #insert
+                        }
+                    )
+                } else {
+                    quote!(
+                        let value = self.#ident();
+                        #insert
+                    )
+                };
+                get_all.extend(q);
+
+                let q = if is_result_output {
+                    let on_error = if skip_on_error {
+                        quote!(#zbus::export::log::warn!(
+                            target: #zbus::logging::OBJECT_SERVER,
+                            "Omitting property '{}' of '{}' from GetAll: {}",
+                            #member_name,
+                            #iface_name,
+                            <#zbus::fdo::Error as ::std::convert::From<_>>::from(e),
+                        ))
+                    } else {
+                        quote!(
+                            return ::std::result::Result::Err(
+                                <#zbus::fdo::Error as ::std::convert::From<_>>::from(e),
+                            )
+                        )
+                    };
+                    quote!(
+                        match self.#ident() {
+                            ::std::result::Result::Ok(value) => {
+                                #insert
+                            }
+                            ::std::result::Result::Err(e) => {
+                                #on_error;
+                            }
+                        }
+                    )
+                } else {
+                    quote!(
+                        let value = self.#ident();
+                        #insert
+                    )
+                };
+                try_get_all.extend(q)
             }
         } else {
             introspect.extend(doc_comments);
             introspect.extend(introspect_method(&member_name, &intro_args));
+            methods_metadata.extend(quote!(
+                #zbus::MethodMetadata {
+                    name: #member_name,
+                    input: #input_signature,
+                    output: #output_signature,
+                },
+            ));
 
+            // Only the arguments and the reply are specific to the method, the rest is done by
+            // the dispatching code of zbus.
+            let reply_with = if is_deferred {
+                quote!(reply_deferred)
+            } else {
+                quote!(reply_with)
+            };
             let m = quote!(
-                #member_name => {
-                    #args_from_msg
-                    let reply = self.#ident(#args);
-                    ::std::option::Option::Some(#reply)
+                #zbus::export::Method {
+                    name: #member_name,
+                    call: |__self, __call| {
+                        __call.#reply_with(|| -> #zbus::fdo::Result<_> {
+                            #args_from_msg
+                            let reply = __self.#ident(#args);
+                            #reply
+                        })
+                    },
                 },
             );
 
             if is_mut {
-                call_mut_dispatch.extend(m);
+                call_mut_dispatch.push((member_name, m));
             } else {
-                call_dispatch.extend(m);
+                call_dispatch.push((member_name, m));
             }
         }
     }
 
+    // Setters and the property tracking are generated once all the property attributes are known,
+    // as they can be given on the getter or the setter.
+    let mut tracked_properties = quote!();
+    let mut quiet_set_dispatch = quote!();
+    let mut writable_properties = vec![];
+    let mut readable_properties = vec![];
+    let mut emitting_properties = vec![];
+    for (name, p) in &properties {
+        if p.write {
+            writable_properties.push(name);
+        }
+        if p.read {
+            readable_properties.push(name);
+            if p.emits_changed() {
+                emitting_properties.push(name);
+            }
+        }
+        if let Some(set_call) = &p.set_call {
+            quiet_set_dispatch.extend(quote!(
+                #name => {
+                    let val = ::std::convert::TryInto::try_into(value).map_err(|e| {
+                        #zbus::fdo::Error::InvalidArgs(::std::string::ToString::to_string(&e))
+                    })?;
+                    #set_call
+                }
+            ));
+        }
+        if let Some(set_call) = &p.set_call {
+            let set_result = if p.emits_changed() {
+                let prop_changed_method_name = format_ident!("{}_changed", snake_case(name));
+                quote!(#set_call.and_then(|set_result| {
+                    self.#prop_changed_method_name()?;
+                    ::std::result::Result::Ok(set_result)
+                }))
+            } else {
+                quote!(#set_call)
+            };
+            set_dispatch.extend(quote!(
+                #name => {
+                    let val = match ::std::convert::TryInto::try_into(value) {
+                        ::std::result::Result::Ok(val) => val,
+                        ::std::result::Result::Err(e) => {
+                            return ::std::option::Option::Some(::std::result::Result::Err(
+                                #zbus::fdo::Error::InvalidArgs(
+                                    ::std::string::ToString::to_string(&e),
+                                ),
+                            ));
+                        }
+                    };
+                    ::std::option::Option::Some(#set_result)
+                }
+            ));
+        }
+        if p.read && p.emits_changed() {
+            tracked_properties.extend(quote!(
+                if let ::std::option::Option::Some(::std::result::Result::Ok(value)) =
+                    <Self as #zbus::Interface>::get(self, #name)
+                {
+                    props.insert(#name, value);
+                }
+            ));
+        }
+    }
+
+    let properties_metadata = properties.iter().map(|(name, p)| {
+        let signature = gen_signature_fn(p.ty.cloned());
+        let Property { read, write, .. } = p;
+
+        quote!(
+            #zbus::PropertyMetadata {
+                name: #name,
+                signature: #signature,
+                read: #read,
+                write: #write,
+            }
+        )
+    });
+    let properties_metadata = quote!(#(#properties_metadata),*);
     introspect.extend(introspect_properties(properties));
 
+    let set_multiple = if atomic_properties {
+        let validate = validate_properties.map(|validate| quote!(self.#validate(properties)?;));
+        generated_signals.extend(quote!(
+            // Set a property without signaling the change, as `set_multiple` signals them all at
+            // once. Only called for the writable properties.
+            #[allow(unused_variables)]
+            fn __zbus_set_property(
+                &mut self,
+                property_name: &str,
+                value: &#zbus::export::zvariant::Value,
+            ) -> #zbus::fdo::Result<()> {
+                match property_name {
+                    #quiet_set_dispatch
+                    _ => ::std::unreachable!(),
+                }
+            }
+
+            fn __zbus_set_multiple(
+                &mut self,
+                properties: &::std::collections::HashMap<
+                    ::std::string::String,
+                    #zbus::export::zvariant::OwnedValue,
+                >,
+            ) -> #zbus::fdo::Result<()> {
+                let writable: &[&str] = &[#(#writable_properties),*];
+                let readable: &[&str] = &[#(#readable_properties),*];
+                for name in properties.keys() {
+                    if writable.contains(&name.as_str()) {
+                        continue;
+                    }
+
+                    return ::std::result::Result::Err(if readable.contains(&name.as_str()) {
+                        #zbus::fdo::Error::PropertyReadOnly(
+                            ::std::format!("Property '{}' is read-only", name),
+                        )
+                    } else {
+                        #zbus::fdo::Error::UnknownProperty(
+                            ::std::format!("Unknown property '{}'", name),
+                        )
+                    });
+                }
+                #validate
+
+                // If a setter fails, the properties set before it are restored, as far as they
+                // can be read.
+                let mut applied = ::std::vec::Vec::new();
+                for (name, value) in properties {
+                    let previous = #zbus::Interface::get(self, name)
+                        .and_then(::std::result::Result::ok);
+                    if let ::std::result::Result::Err(e) = self.__zbus_set_property(name, value) {
+                        for (name, previous) in ::std::iter::Iterator::rev(applied.into_iter()) {
+                            let previous: #zbus::export::zvariant::OwnedValue = match previous {
+                                ::std::option::Option::Some(previous) => previous,
+                                ::std::option::Option::None => continue,
+                            };
+                            if let ::std::result::Result::Err(e) =
+                                self.__zbus_set_property(name, &previous)
+                            {
+                                #zbus::export::log::warn!(
+                                    target: #zbus::logging::OBJECT_SERVER,
+                                    "Failed to restore property '{}' of '{}': {}",
+                                    name,
+                                    #iface_name,
+                                    e,
+                                );
+                            }
+                        }
+
+                        return ::std::result::Result::Err(e);
+                    }
+                    applied.push((name.as_str(), previous));
+                }
+
+                let emitting: &[&str] = &[#(#emitting_properties),*];
+                let mut values = ::std::vec::Vec::new();
+                for name in properties.keys() {
+                    if !emitting.contains(&name.as_str()) {
+                        continue;
+                    }
+                    if let ::std::option::Option::Some(::std::result::Result::Ok(value)) =
+                        #zbus::Interface::get(self, name)
+                    {
+                        values.push((name.as_str(), value));
+                    }
+                }
+                if !values.is_empty() {
+                    let changed = values
+                        .iter()
+                        .map(|(name, value)| (*name, &**value))
+                        .collect();
+                    let properties_iface = #zbus::fdo::Properties;
+                    properties_iface.properties_changed(&#iface_name, &changed, &[])?;
+                }
+
+                ::std::result::Result::Ok(())
+            }
+        ));
+
+        quote!(
+            fn set_multiple(
+                &mut self,
+                properties: &::std::collections::HashMap<
+                    ::std::string::String,
+                    #zbus::export::zvariant::OwnedValue,
+                >,
+            ) -> ::std::option::Option<#zbus::fdo::Result<()>> {
+                ::std::option::Option::Some(self.__zbus_set_multiple(properties))
+            }
+        )
+    } else {
+        quote!()
+    };
+
+    // The tables are looked up with a binary search.
+    call_dispatch.sort_by(|(a, _), (b, _)| a.cmp(b));
+    call_mut_dispatch.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let methods = call_dispatch.iter().map(|(_, m)| m);
+    let methods_mut = call_mut_dispatch.iter().map(|(_, m)| m);
+
     let self_ty = &input.self_ty;
     let generics = &input.generics;
     let where_clause = &generics.where_clause;
@@ -287,12 +678,21 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
     Ok(quote! {
         #input
 
+        #proxy
+
         impl #generics #self_ty
         #where_clause
         {
             #generated_signals
         }
 
+        impl #generics #zbus::export::MethodTable for #self_ty
+        #where_clause
+        {
+            const METHODS: &'static [#zbus::export::MethodRef<Self>] = &[#(#methods)*];
+            const METHODS_MUT: &'static [#zbus::export::MethodMut<Self>] = &[#(#methods_mut)*];
+        }
+
         impl #generics #zbus::Interface for #self_ty
         #where_clause
         {
@@ -300,6 +700,19 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
                 #iface_name
             }
 
+            fn versions() -> &'static [&'static str] {
+                &[#(#versions),*]
+            }
+
+            fn metadata(&self) -> ::std::option::Option<&'static #zbus::InterfaceMetadata> {
+                ::std::option::Option::Some(&#zbus::InterfaceMetadata {
+                    name: #iface_name,
+                    methods: &[#methods_metadata],
+                    properties: &[#properties_metadata],
+                    signals: &[#signals_metadata],
+                })
+            }
+
             fn get(
                 &self,
                 property_name: &str,
@@ -324,6 +737,31 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
                 props
             }
 
+            fn try_get_all(
+                &self,
+            ) -> #zbus::fdo::Result<
+                ::std::collections::HashMap<
+                    ::std::string::String,
+                    #zbus::export::zvariant::OwnedValue,
+                >,
+            > {
+                let mut props: ::std::collections::HashMap<
+                    ::std::string::String,
+                    #zbus::export::zvariant::OwnedValue,
+                > = ::std::collections::HashMap::new();
+                #try_get_all
+                ::std::result::Result::Ok(props)
+            }
+
+            fn tracked_properties(
+                &self,
+            ) -> ::std::collections::HashMap<&'static str, #zbus::export::zvariant::OwnedValue> {
+                #[allow(unused_mut)]
+                let mut props = ::std::collections::HashMap::new();
+                #tracked_properties
+                props
+            }
+
             fn set(
                 &mut self,
                 property_name: &str,
@@ -335,16 +773,15 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
                 }
             }
 
+            #set_multiple
+
             fn call(
                 &self,
                 c: &#zbus::Connection,
                 m: &#zbus::Message,
                 name: &str,
             ) -> ::std::option::Option<#zbus::Result<u32>> {
-                match name {
-                    #call_dispatch
-                    _ => ::std::option::Option::None,
-                }
+                #zbus::export::dispatch_call(self, c, m, name)
             }
 
             fn call_mut(
@@ -353,10 +790,7 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
                 m: &#zbus::Message,
                 name: &str,
             ) -> ::std::option::Option<#zbus::Result<u32>> {
-                match name {
-                    #call_mut_dispatch
-                    _ => ::std::option::Option::None,
-                }
+                #zbus::export::dispatch_call_mut(self, c, m, name)
             }
 
             fn introspect_to_writer(&self, writer: &mut dyn ::std::fmt::Write, level: usize) {
@@ -379,19 +813,20 @@ pub fn expand(args: AttributeArgs, mut input: ItemImpl) -> syn::Result<TokenStre
     })
 }
 
-fn get_args_from_inputs(
-    inputs: &[&PatType],
-    zbus: &TokenStream,
-) -> syn::Result<(TokenStream, TokenStream)> {
+fn get_args_from_inputs(inputs: &[&PatType]) -> syn::Result<(TokenStream, TokenStream)> {
     if inputs.is_empty() {
         Ok((quote!(), quote!()))
     } else {
         let mut header_arg_decl = None;
+        let mut credentials_arg_decl = None;
+        let mut deferred_arg_decl = None;
         let mut args = Vec::new();
         let mut tys = Vec::new();
 
         for input in inputs {
             let mut is_header = false;
+            let mut is_credentials = false;
+            let mut is_deferred = false;
 
             for attr in &input.attrs {
                 if !attr.path.is_ident("zbus") {
@@ -414,6 +849,12 @@ fn get_args_from_inputs(
                         NestedMeta::Meta(Meta::Path(p)) if p.is_ident("header") => {
                             is_header = true;
                         }
+                        NestedMeta::Meta(Meta::Path(p)) if p.is_ident("caller_credentials") => {
+                            is_credentials = true;
+                        }
+                        NestedMeta::Meta(Meta::Path(p)) if p.is_ident("deferred") => {
+                            is_deferred = true;
+                        }
                         NestedMeta::Meta(_) => {
                             return Err(syn::Error::new_spanned(
                                 item,
@@ -438,14 +879,33 @@ fn get_args_from_inputs(
                 let header_arg = &input.pat;
 
                 header_arg_decl = Some(quote! {
-                    let #header_arg = match m.header() {
-                        ::std::result::Result::Ok(r) => r,
-                        ::std::result::Result::Err(e) => {
-                            return ::std::option::Option::Some(
-                                <#zbus::fdo::Error as ::std::convert::From<_>>::from(e).reply(c, m),
-                            );
-                        }
-                    };
+                    let #header_arg = __call.header()?;
+                });
+            } else if is_credentials {
+                if credentials_arg_decl.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        input,
+                        "There can only be one caller_credentials argument",
+                    ));
+                }
+
+                let credentials_arg = &input.pat;
+
+                credentials_arg_decl = Some(quote! {
+                    let #credentials_arg = __call.caller_credentials()?;
+                });
+            } else if is_deferred {
+                if deferred_arg_decl.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        input,
+                        "There can only be one deferred argument",
+                    ));
+                }
+
+                let deferred_arg = &input.pat;
+
+                deferred_arg_decl = Some(quote! {
+                    let #deferred_arg = __call.responder()?;
                 });
             } else {
                 args.push(&input.pat);
@@ -456,15 +916,13 @@ fn get_args_from_inputs(
         let args_from_msg = quote! {
             #header_arg_decl
 
-            let (#(#args),*): (#(#tys),*) =
-                match m.body() {
-                    ::std::result::Result::Ok(r) => r,
-                    ::std::result::Result::Err(e) => {
-                        return ::std::option::Option::Some(
-                            <#zbus::fdo::Error as ::std::convert::From<_>>::from(e).reply(c, m),
-                        );
-                    }
-                };
+            let (#(#args),*): (#(#tys),*) = __call.body()?;
+
+            // Only looked up for valid calls, it may take a call to the bus.
+            #credentials_arg_decl
+
+            // Last, so that there's no responder to answer if the arguments are wrong.
+            #deferred_arg_decl
         };
 
         let all_args = inputs.iter().map(|t| &t.pat);
@@ -474,6 +932,145 @@ fn get_args_from_inputs(
     }
 }
 
+// Declare a `dbus_proxy` trait matching the interface and expand it into its proxies.
+fn gen_proxy(
+    input: &ItemImpl,
+    ty: &Ident,
+    iface_name: &str,
+    args: Vec<NestedMeta>,
+    atomic_properties: bool,
+) -> syn::Result<TokenStream> {
+    let mut proxy_name = ty.clone();
+    let mut vis: Visibility = parse_quote!(pub);
+    let mut proxy_args: Vec<NestedMeta> = vec![parse_quote!(interface = #iface_name)];
+    if atomic_properties {
+        proxy_args.push(parse_quote!(atomic_properties));
+    }
+
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(NameValue(nv)) if nv.path.is_ident("name") => match &nv.lit {
+                Str(lit) => proxy_name = lit.parse()?,
+                lit => return Err(syn::Error::new_spanned(lit, "Invalid proxy name")),
+            },
+            NestedMeta::Meta(NameValue(nv)) if nv.path.is_ident("vis") => match &nv.lit {
+                Str(lit) => vis = lit.parse()?,
+                lit => return Err(syn::Error::new_spanned(lit, "Invalid proxy visibility")),
+            },
+            NestedMeta::Meta(NameValue(nv))
+                if nv.path.is_ident("default_path")
+                    || nv.path.is_ident("default_path_template")
+                    || nv.path.is_ident("default_service") =>
+            {
+                proxy_args.push(NestedMeta::Meta(NameValue(nv)));
+            }
+            arg => return Err(syn::Error::new_spanned(arg, "Unsupported proxy argument")),
+        }
+    }
+
+    let methods = input
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            ImplItem::Method(m) => Some(gen_proxy_method(m)),
+            _ => None,
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+    let doc = format!(
+        "Proxy for the `{}` interface, as implemented by `{}`.",
+        iface_name, ty
+    );
+    let proxy_trait: ItemTrait = parse_quote! {
+        #[doc = #doc]
+        trait #proxy_name {
+            #(#methods)*
+        }
+    };
+
+    Ok(crate::proxy::expand_with_vis(
+        &proxy_args,
+        &proxy_trait,
+        &vis,
+    ))
+}
+
+// The `dbus_proxy` trait method calling the given interface method remotely.
+fn gen_proxy_method(method: &ImplItemMethod) -> syn::Result<TokenStream> {
+    let zbus = zbus_path();
+    let Signature {
+        ident,
+        inputs,
+        output,
+        ..
+    } = &method.sig;
+
+    let attrs = parse_item_attributes(&method.attrs, "dbus_interface")?;
+    let is_property = attrs.iter().any(|x| x.is_property());
+    let is_signal = attrs.iter().any(|x| x.is_signal());
+    let mut proxy_attrs = vec![];
+    if is_property {
+        proxy_attrs.push(quote!(property));
+    } else if is_signal {
+        proxy_attrs.push(quote!(signal));
+    }
+    if let Some(name) = attrs.iter().find_map(|x| match x {
+        ItemAttribute::Name(n) => Some(n),
+        _ => None,
+    }) {
+        proxy_attrs.push(quote!(name = #name));
+    }
+    let proxy_attrs = if proxy_attrs.is_empty() {
+        quote!()
+    } else {
+        quote!(#[dbus_proxy(#(#proxy_attrs),*)])
+    };
+    let docs = get_doc_attrs(&method.attrs);
+
+    // Neither the header, the caller's credentials nor the responder, the only arguments with
+    // `zbus` attributes, are sent by the caller.
+    let typed_inputs = inputs
+        .iter()
+        .filter_map(|i| match i {
+            FnArg::Typed(t) => Some(t),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let args = typed_inputs
+        .iter()
+        .filter(|t| !t.attrs.iter().any(|attr| attr.path.is_ident("zbus")))
+        .collect::<Vec<_>>();
+
+    let has_inputs = !args.is_empty();
+    let ret = match (deferred_reply_type(typed_inputs.iter().copied())?, output) {
+        (Some(ty), _) => owned_type(ty),
+        (None, ReturnType::Type(_, _)) if !is_signal && !(is_property && has_inputs) => {
+            owned_type(get_property_type(output)?)
+        }
+        _ => quote!(()),
+    };
+
+    Ok(quote! {
+        #(#docs)*
+        #proxy_attrs
+        fn #ident(&self, #(#args),*) -> #zbus::Result<#ret>;
+    })
+}
+
+// Replies are deserialized into owned values, whatever the method returns.
+fn owned_type(ty: &Type) -> TokenStream {
+    match ty {
+        Type::Reference(r) => match r.elem.as_ref() {
+            Type::Path(p) if p.path.is_ident("str") => quote!(::std::string::String),
+            Type::Slice(s) => {
+                let elem = &s.elem;
+                quote!(::std::vec::Vec<#elem>)
+            }
+            elem => quote!(#elem),
+        },
+        ty => quote!(#ty),
+    }
+}
+
 fn clean_input_args(inputs: &mut Punctuated<FnArg, Token![,]>) {
     for input in inputs {
         if let FnArg::Typed(t) = input {
@@ -511,31 +1108,10 @@ fn introspect_input_args<'a>(
     inputs
         .iter()
         .filter_map(move |PatType { pat, ty, attrs, .. }| {
-            let is_header_arg = attrs.iter().any(|attr| {
-                if !attr.path.is_ident("zbus") {
-                    return false;
-                }
-
-                let meta = match attr.parse_meta() {
-                    ::std::result::Result::Ok(meta) => meta,
-                    ::std::result::Result::Err(_) => return false,
-                };
-
-                let nested = match meta {
-                    Meta::List(MetaList { nested, .. }) => nested,
-                    _ => return false,
-                };
-
-                let res = nested.iter().any(|nested_meta| {
-                    matches!(
-                        nested_meta,
-                        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("header")
-                    )
-                });
-
-                res
-            });
-            if is_header_arg {
+            if has_zbus_arg_attr(attrs, "header")
+                || has_zbus_arg_attr(attrs, "caller_credentials")
+                || has_zbus_arg_attr(attrs, "deferred")
+            {
                 return None;
             }
 
@@ -548,6 +1124,78 @@ fn introspect_input_args<'a>(
         })
 }
 
+// The tuple of the types of the arguments given by the caller, unless some of them can't be named
+// out of the method.
+fn args_tuple_type(inputs: &[&PatType]) -> Option<Type> {
+    let mut types = vec![];
+    for PatType { ty, attrs, .. } in inputs {
+        if has_zbus_arg_attr(attrs, "header")
+            || has_zbus_arg_attr(attrs, "caller_credentials")
+            || has_zbus_arg_attr(attrs, "deferred")
+        {
+            continue;
+        }
+        if matches!(**ty, Type::ImplTrait(_)) {
+            return None;
+        }
+        types.push(ty);
+    }
+
+    Some(parse_quote!((#(#types,)*)))
+}
+
+// Whether the `zbus` attributes of an argument include `name`.
+fn has_zbus_arg_attr(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| {
+        if !attr.path.is_ident("zbus") {
+            return false;
+        }
+
+        let nested = match attr.parse_meta() {
+            Ok(Meta::List(MetaList { nested, .. })) => nested,
+            _ => return false,
+        };
+
+        let res = nested.iter().any(|nested_meta| {
+            matches!(
+                nested_meta,
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident(name)
+            )
+        });
+
+        res
+    })
+}
+
+// The `T` of the `Responder<T>` argument of a deferred method, if it is one.
+fn deferred_reply_type<'a>(
+    inputs: impl IntoIterator<Item = &'a PatType>,
+) -> syn::Result<Option<&'a Type>> {
+    let input = match inputs
+        .into_iter()
+        .find(|input| has_zbus_arg_attr(&input.attrs, "deferred"))
+    {
+        Some(input) => input,
+        None => return Ok(None),
+    };
+
+    if let Type::Path(p) = input.ty.as_ref() {
+        if let Some(PathArguments::AngleBracketed(AngleBracketedGenericArguments {
+            args, ..
+        })) = p.path.segments.last().map(|s| &s.arguments)
+        {
+            if let Some(syn::GenericArgument::Type(ty)) = args.first() {
+                return Ok(Some(ty));
+            }
+        }
+    }
+
+    Err(syn::Error::new_spanned(
+        &input.ty,
+        "A `deferred` argument must be a `Responder<T>`",
+    ))
+}
+
 fn introspect_output_arg(ty: &Type, arg_name: Option<&String>) -> TokenStream {
     let arg_name = match arg_name {
         Some(name) => format!("name=\"{}\" ", name),
@@ -659,13 +1307,31 @@ fn introspect_properties(
             .expect("Write-only properties aren't supported yet.");
 
         let doc_comments = prop.doc_comments;
+        let property = match prop.emits_changed_signal {
+            None => quote!(
+                ::std::writeln!(
+                    writer,
+                    "{:indent$}<property name=\"{}\" type=\"{}\" access=\"{}\"/>",
+                    "", #name, <#ty>::signature(), #access, indent = level,
+                ).unwrap();
+            ),
+            Some(emits) => quote!(
+                ::std::writeln!(
+                    writer,
+                    "{:indent$}<property name=\"{}\" type=\"{}\" access=\"{}\">",
+                    "", #name, <#ty>::signature(), #access, indent = level,
+                ).unwrap();
+                ::std::writeln!(
+                    writer,
+                    "{:indent$}<annotation name=\"org.freedesktop.DBus.Property.EmitsChangedSignal\" value=\"{}\"/>",
+                    "", #emits, indent = level + 2,
+                ).unwrap();
+                ::std::writeln!(writer, "{:indent$}</property>", "", indent = level).unwrap();
+            ),
+        };
         Some(quote!(
             #doc_comments
-            ::std::writeln!(
-                writer,
-                "{:indent$}<property name=\"{}\" type=\"{}\" access=\"{}\"/>",
-                "", #name, <#ty>::signature(), #access, indent = level,
-            ).unwrap();
+            #property
         ))
     })
 }
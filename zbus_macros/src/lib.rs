@@ -22,6 +22,24 @@ mod utils;
 ///
 /// Each trait method will be expanded to call to the associated D-Bus remote interface.
 ///
+/// The expected methods, properties and signals are also described in the
+/// `zbus::ProxyDefault::METADATA` of the proxies, so that they can be checked against the object at
+/// runtime, with the `validate()` method of the proxy or `zbus::ProxyBuilder::validate_on_build`
+/// (requires the `xml` feature of `zbus`).
+///
+/// For interfaces implemented by many objects at predictable paths, give a
+/// `default_path_template` with a `{}` standing for an element of the path, e.g
+/// `"/org/freedesktop/UPower/devices/{}"`. The proxies then also get a `for_name(conn, name)`
+/// constructor, for the object of the template with `{}` substituted by `name`. The name is escaped
+/// as per `zvariant::ObjectPath::escape_segment` first, so that any string gives a valid path.
+///
+/// If the interface has writable properties, the proxies also get a `properties_transaction()`
+/// method, returning a builder collecting `set_<property>` calls, sent together on `commit()`. By
+/// default, they're sent as a burst of `Set` calls. Give the `atomic_properties` argument if the
+/// service implements the interface with `#[dbus_interface(atomic_properties)]`, for them to be
+/// sent as a single `SetMultiple` call, applied all or none. See
+/// `zbus::azync::PropertiesTransaction` for details.
+///
 /// Trait methods accept `dbus_proxy` attributes:
 ///
 /// * `name` - override the D-Bus name (pascal case form by default)
@@ -38,6 +56,22 @@ mod utils;
 ///
 ///   NB: Any doc comments provided shall be appended to the ones added by the macro.
 ///
+/// * `idempotent` - declare that the method can safely be called more than once, so that failed
+///   calls are retried according to the retry policy of the proxy, if any. See
+///   `zbus::RetryPolicy` for details.
+///
+/// * `strict_signature` - on a method or a signal, require the reply or the signal to have exactly
+///   the expected arguments. By default, any arguments following the expected ones are ignored, so
+///   that the proxy keeps working with services that append arguments over time.
+///
+/// * `out_struct` - on a method returning a tuple of several out arguments, e.g
+///   `#[dbus_proxy(out_struct = "ListUnitsReply")]`, generate a struct of that name, with a public
+///   field per out argument, and return it instead of the tuple. The fields are named after the
+///   names given through `out_args("name", "description")` (converted to snake case), or `arg1`,
+///   `arg2` and so on. The struct has the same signature and encoding as the tuple. On a signal,
+///   `out_struct` renames the `<Signal>Args` type of the asynchronous proxy, and `out_args`
+///   renames its fields.
+///
 /// # Example
 ///
 /// ```
@@ -138,6 +172,43 @@ pub fn dbus_proxy(attr: TokenStream, item: TokenStream) -> TokenStream {
 /// properties or signal depending on the item attributes. It will implement the [`Interface`] trait
 /// `for T` on your behalf, to handle the message dispatching and introspection support.
 ///
+/// The members of the interface are also described by the `InterfaceMetadata` returned by
+/// `Interface::metadata`, with the signatures of the methods, properties and signals that have no
+/// generic types, and the access of the properties.
+///
+/// The macro accepts the following arguments:
+///
+/// * `name` (or `interface`) - the D-Bus interface name (`org.freedesktop.T` by default).
+///
+/// * `proxy` - also generate the client proxies for the interface, as [`dbus_proxy`] would for
+///   the matching trait: `TProxy` and `AsyncTProxy`. Methods return a `zbus::Result` of their
+///   reply (owned, and without the `Result` wrapping if the method returns one), property getters
+///   and setters become the proxy's property accessors and signals get their `connect_*` and
+///   `receive_*` methods. `header`, `caller_credentials` and `deferred` arguments are left out.
+///
+///   Use `proxy(name = "...", vis = "...", default_path = "...", default_service = "...")` to
+///   change the base name of the proxy types (the name of `T` by default), their visibility
+///   (`pub` by default), and the default path and service of the proxies. `default_path_template`
+///   is also accepted, as for [`dbus_proxy`].
+///
+/// * `atomic_properties` - support setting several properties at once, all or none of them,
+///   through the zbus-specific `SetMultiple(s interface, a{sv} properties)` method of
+///   `org.freedesktop.DBus.Properties`. Unknown and read-only properties fail the call before
+///   anything is set. If a setter fails, the properties set before it are restored through their
+///   getters (write-only ones can't be), and its error is returned. A single
+///   "PropertiesChanged" signal is emitted for all the changes.
+///
+///   Use `atomic_properties = "method"` to have the values validated first, by the `method` of
+///   `T`. It must be declared outside of the `dbus_interface` block, as
+///   `fn method(&self, properties: &HashMap<String, OwnedValue>) -> zbus::fdo::Result<()>`, and
+///   nothing is set if it fails, e.g for a combination of values that is inconsistent.
+///
+///   The generated proxies set multiple properties through `SetMultiple`.
+///
+/// * `versions("...", ...)` - the names of other versions of the interface, also served by `T`
+///   when it's registered with `zbus::ObjectServer::at_versions`. Each is introspected under its
+///   own name, with all the members of `T`. The signals are only emitted under `name`.
+///
 /// The methods accepts the `dbus_interface` attributes:
 ///
 /// * `name` - override the D-Bus name (pascal case form of the method by default)
@@ -145,12 +216,25 @@ pub fn dbus_proxy(attr: TokenStream, item: TokenStream) -> TokenStream {
 /// * `property` - expose the method as a property. If the method takes an argument, it must be a
 ///   setter, with a `set_` prefix. Otherwise, it's a getter.
 ///
+///   A getter may return a `Result`, in which case its error is returned to the caller of `Get`.
+///   By default, a failing getter also fails the whole `GetAll` call. Use `property(skip_on_error)`
+///   on the getter to have `GetAll` leave the property out of its result and log a warning
+///   instead. `Interface::get_all` always leaves the failing properties out.
+///
+/// * `emits_changed_signal` - on a property getter or setter, the value of the
+///   `org.freedesktop.DBus.Property.EmitsChangedSignal` annotation of the property: `"true"` (the
+///   default), `"false"` if changes are not signaled, or `"const"` if the property never changes.
+///   With the latter two, the setter doesn't emit "PropertiesChanged", and the property is not
+///   tracked by [`ObjectServer::with_mut_tracked`].
+///
 /// * `signal` - the method is a "signal". It must be a method declaration (without body). Its code
 ///   block will be expanded to emit the signal from the object path associated with the interface
 ///   instance.
 ///
 ///   You can call a signal method from a an interface method, or from an [`ObjectServer::with`]
-///   function.
+///   function. Elsewhere, e.g from another thread, use the generated `<signal>_on` associated
+///   function instead. It takes the [`Connection`] and object path to emit the signal on before
+///   the signal arguments, which a [`SignalEmitter`] can provide.
 ///
 /// * `struct_return` - This attribute is depcrecated and a noop. If you want to return a single
 ///   structure from a method, simply declare it to return a named structure or a tuple with a
@@ -169,13 +253,26 @@ pub fn dbus_proxy(attr: TokenStream, item: TokenStream) -> TokenStream {
 /// exists) will automatically call this method.
 /// For instance, a property setter named `set_foo` will be called to set the property "Foo", and
 /// will emit the "PropertiesChanged" signal with the new value for "Foo". Other changes to the
-/// "Foo" property can be signaled manually with the generated `foo_changed` method.
+/// "Foo" property can be signaled manually with the generated `foo_changed` method, or be
+/// signaled along with the others by changing the interface through
+/// [`ObjectServer::with_mut_tracked`].
 ///
 /// The method arguments offers some the following `zbus` attributes:
 ///
 /// * `header` - This marks the method argument to receive the message header associated with the
 /// D-Bus method call being handled.
 ///
+/// * `caller_credentials` - This marks a [`ConnectionCredentials`] argument, to receive the
+/// credentials of the caller, e.g. to check its user ID. These are looked up with
+/// [`Connection::credentials_of`], so only the first call of a given caller costs a call to the
+/// bus. The method call fails if the lookup does, which includes peer-to-peer connections.
+///
+/// * `deferred` - This marks a [`Responder<T>`] argument, for the method to reply later on. The
+/// method must not return anything. Instead, the reply (of type `T`) or an error is sent through
+/// the `Responder`, possibly from another thread once the method has returned. If the `Responder`
+/// is dropped without an answer, the caller gets a `NoReply` error. `T` is the reply type of the
+/// method in the introspection data and the proxy.
+///
 /// # Example
 ///
 /// ```
@@ -221,7 +318,14 @@ pub fn dbus_proxy(attr: TokenStream, item: TokenStream) -> TokenStream {
 /// See also [`ObjectServer`] documentation to learn how to export an interface over a `Connection`.
 ///
 /// [`ObjectServer`]: https://docs.rs/zbus/1.0.0/zbus/struct.ObjectServer.html
+/// [`dbus_proxy`]: attr.dbus_proxy.html
 /// [`ObjectServer::with`]: https://docs.rs/zbus/1.2.0/zbus/struct.ObjectServer.html#method.with
+/// [`ObjectServer::with_mut_tracked`]: https://docs.rs/zbus/latest/zbus/struct.ObjectServer.html#method.with_mut_tracked
+/// [`Connection`]: https://docs.rs/zbus/latest/zbus/struct.Connection.html
+/// [`SignalEmitter`]: https://docs.rs/zbus/latest/zbus/struct.SignalEmitter.html
+/// [`Responder<T>`]: https://docs.rs/zbus/latest/zbus/struct.Responder.html
+/// [`ConnectionCredentials`]: https://docs.rs/zbus/latest/zbus/struct.ConnectionCredentials.html
+/// [`Connection::credentials_of`]: https://docs.rs/zbus/latest/zbus/struct.Connection.html#method.credentials_of
 /// [`Connection::emit_signal()`]: https://docs.rs/zbus/1.0.0/zbus/struct.Connection.html#method.emit_signal
 /// [`Interface`]: https://docs.rs/zbus/1.0.0/zbus/trait.Interface.html
 #[proc_macro_attribute]
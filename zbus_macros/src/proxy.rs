@@ -1,9 +1,10 @@
 use proc_macro2::{Literal, Span, TokenStream};
 use quote::{format_ident, quote, quote_spanned, ToTokens};
 use regex::Regex;
+use std::collections::BTreeMap;
 use syn::{
-    self, fold::Fold, parse_quote, spanned::Spanned, AttributeArgs, FnArg, Ident, ItemTrait,
-    NestedMeta, ReturnType, TraitItemMethod, Type,
+    self, fold::Fold, parse_quote, spanned::Spanned, AttributeArgs, FnArg, GenericArgument, Ident,
+    ItemTrait, NestedMeta, PathArguments, ReturnType, TraitItemMethod, Type, Visibility,
 };
 
 use crate::utils::*;
@@ -26,25 +27,47 @@ impl AsyncOpts {
 }
 
 pub fn expand(args: AttributeArgs, input: ItemTrait) -> TokenStream {
-    let sync_proxy = create_proxy(&args, &input, false);
-    let async_proxy = create_proxy(&args, &input, true);
+    expand_with_vis(&args, &input, &parse_quote!(pub))
+}
+
+// Generate both proxies, declaring the proxy types with the given visibility.
+pub fn expand_with_vis(args: &[NestedMeta], input: &ItemTrait, vis: &Visibility) -> TokenStream {
+    let sync_proxy = create_proxy(args, input, vis, false);
+    let async_proxy = create_proxy(args, input, vis, true);
+    // Shared by both proxies.
+    let out_structs = input.items.iter().filter_map(|i| match i {
+        syn::TraitItem::Method(m) => gen_out_struct(m, vis),
+        _ => None,
+    });
 
     quote! {
         #sync_proxy
 
         #async_proxy
+
+        #(#out_structs)*
     }
 }
 
-pub fn create_proxy(args: &[NestedMeta], input: &ItemTrait, azync: bool) -> TokenStream {
+pub fn create_proxy(
+    args: &[NestedMeta],
+    input: &ItemTrait,
+    vis: &Visibility,
+    azync: bool,
+) -> TokenStream {
     let mut iface_name = None;
     let mut default_path = None;
+    let mut default_path_template = None;
     let mut default_service = None;
+    let mut atomic_properties = false;
 
     let zbus = zbus_path();
 
     for arg in args {
         match arg {
+            NestedMeta::Meta(syn::Meta::Path(p)) if p.is_ident("atomic_properties") => {
+                atomic_properties = true;
+            }
             NestedMeta::Meta(syn::Meta::NameValue(nv)) => {
                 if nv.path.is_ident("interface") || nv.path.is_ident("name") {
                     if let syn::Lit::Str(lit) = &nv.lit {
@@ -58,6 +81,12 @@ pub fn create_proxy(args: &[NestedMeta], input: &ItemTrait, azync: bool) -> Toke
                     } else {
                         panic!("Invalid path argument")
                     }
+                } else if nv.path.is_ident("default_path_template") {
+                    if let syn::Lit::Str(lit) = &nv.lit {
+                        default_path_template = Some(lit.value());
+                    } else {
+                        panic!("Invalid path template argument")
+                    }
                 } else if nv.path.is_ident("default_service") {
                     if let syn::Lit::Str(lit) = &nv.lit {
                         default_service = Some(lit.value());
@@ -85,6 +114,10 @@ pub fn create_proxy(args: &[NestedMeta], input: &ItemTrait, azync: bool) -> Toke
     let default_service = default_service.unwrap_or_else(|| name.clone());
     let mut methods = TokenStream::new();
     let mut stream_types = TokenStream::new();
+    let mut methods_metadata = TokenStream::new();
+    let mut signals_metadata = TokenStream::new();
+    let mut properties = BTreeMap::new();
+    let mut transaction_setters = TokenStream::new();
     let async_opts = AsyncOpts::new(azync);
 
     for i in input.items.iter() {
@@ -109,20 +142,79 @@ pub fn create_proxy(args: &[NestedMeta], input: &ItemTrait, azync: bool) -> Toke
                     })
                 });
             let m = if is_property {
+                let property = properties
+                    .entry(name.clone())
+                    .or_insert_with(PropertyInfo::default);
+                let ty = if has_inputs {
+                    property.write = true;
+                    m.sig.inputs.last().and_then(|arg| match arg {
+                        FnArg::Typed(value) => Some((*value.ty).clone()),
+                        _ => None,
+                    })
+                } else {
+                    property.read = true;
+                    result_ok_type(&m.sig.output)
+                };
+                if property.ty.is_none() {
+                    property.ty = ty.filter(|_| !has_type_params(m));
+                }
+                if has_inputs {
+                    transaction_setters.extend(gen_transaction_setter(&name, m));
+                }
+
                 gen_proxy_property(&name, m, &async_opts)
             } else if is_signal {
+                let signature = gen_signature_fn(args_tuple_type(m));
+                signals_metadata.extend(quote! {
+                    #zbus::SignalMetadata {
+                        name: #name,
+                        signature: #signature,
+                    },
+                });
+
                 let (method, types) =
                     gen_proxy_signal(&proxy_name, &name, &method_name, m, &async_opts);
                 stream_types.extend(types);
 
                 method
             } else {
+                let input = gen_signature_fn(args_tuple_type(m));
+                let output = if has_type_params(m) {
+                    None
+                } else if attrs.iter().any(|x| matches!(x, ItemAttribute::Object(_))) {
+                    Some(parse_quote!(#zbus::export::zvariant::OwnedObjectPath))
+                } else {
+                    result_ok_type(&m.sig.output)
+                };
+                let output = gen_signature_fn(output);
+                methods_metadata.extend(quote! {
+                    #zbus::MethodMetadata {
+                        name: #name,
+                        input: #input,
+                        output: #output,
+                    },
+                });
+
                 gen_proxy_method_call(&name, &method_name, m, &async_opts)
             };
             methods.extend(m);
         }
     }
 
+    let properties_metadata = properties.iter().map(|(name, property)| {
+        let signature = gen_signature_fn(property.ty.clone());
+        let PropertyInfo { read, write, .. } = property;
+
+        quote! {
+            #zbus::PropertyMetadata {
+                name: #name,
+                signature: #signature,
+                read: #read,
+                write: #write,
+            }
+        }
+    });
+
     let (proxy_doc, proxy_struct, connection) = if azync {
         let sync_proxy = Ident::new(&format!("{}Proxy", input.ident), Span::call_site());
         let doc = format!("Asynchronous sibling of [`{}`].", sync_proxy);
@@ -138,17 +230,115 @@ pub fn create_proxy(args: &[NestedMeta], input: &ItemTrait, azync: bool) -> Toke
         (doc, proxy, connection)
     };
 
+    // The blocking proxies wrap a `zbus::Proxy`, as made by `zbus::Proxy::first_available`.
+    let from_proxy = if azync {
+        quote!()
+    } else {
+        quote! {
+            impl<'c> ::std::convert::From<#zbus::Proxy<'c>> for #proxy_name<'c> {
+                fn from(proxy: #zbus::Proxy<'c>) -> Self {
+                    #proxy_name(proxy)
+                }
+            }
+        }
+    };
+
+    let for_name = default_path_template.map(|template| {
+        if template.matches("{}").count() != 1 {
+            panic!("The path template must contain a single `{}`");
+        }
+        let mut parts = template.splitn(2, "{}");
+        let (prefix, suffix) = (parts.next().unwrap(), parts.next().unwrap());
+        let doc = format!(
+            "Creates a new proxy with the default service, for the object at `{}`.\n\n\
+             `{{}}` is substituted by `name`, escaped as per \
+             `zvariant::ObjectPath::escape_segment`.",
+            template
+        );
+
+        quote! {
+            #[doc = #doc]
+            pub fn for_name(conn: &#connection, name: &str) -> #zbus::Result<Self> {
+                let path = ::std::format!(
+                    "{}{}{}",
+                    #prefix,
+                    #zbus::export::zvariant::ObjectPath::escape_segment(name),
+                    #suffix,
+                );
+
+                Self::builder(conn).path(path)?.build()
+            }
+        }
+    });
+
+    let (transaction, transaction_types) = if transaction_setters.is_empty() {
+        (quote! {}, quote! {})
+    } else {
+        let transaction_name = if azync {
+            format_ident!("Async{}PropertiesTransaction", input.ident)
+        } else {
+            format_ident!("{}PropertiesTransaction", input.ident)
+        };
+        let transaction_struct = if azync {
+            quote! { #zbus::azync::PropertiesTransaction }
+        } else {
+            quote! { #zbus::PropertiesTransaction }
+        };
+        let transaction_doc = format!(
+            "Property changes being collected, created by [`{}::properties_transaction`].\n\n\
+             See [`zbus::azync::PropertiesTransaction`]({}) for how they are sent.",
+            proxy_name, "https://docs.rs/zbus/latest/zbus/azync/struct.PropertiesTransaction.html",
+        );
+        let AsyncOpts { usage, wait, .. } = &async_opts;
+
+        let transaction = quote! {
+            /// Prepare setting several properties, to send the changes together.
+            pub fn properties_transaction(&self) -> #transaction_name<'_> {
+                #transaction_name(self.0.properties_transaction().atomic(#atomic_properties))
+            }
+        };
+        let transaction_types = quote! {
+            #[doc = #transaction_doc]
+            #[derive(Debug)]
+            #vis struct #transaction_name<'p>(#transaction_struct<'p>);
+
+            impl<'p> #transaction_name<'p> {
+                #transaction_setters
+
+                /// Whether to set the properties with a single `SetMultiple` call, instead of a
+                /// `Set` call per property.
+                pub fn atomic(self, atomic: bool) -> Self {
+                    Self(self.0.atomic(atomic))
+                }
+
+                /// Send the changes, returning the first error if any.
+                pub #usage fn commit(self) -> #zbus::fdo::Result<()> {
+                    self.0.commit()#wait
+                }
+            }
+        };
+
+        (transaction, transaction_types)
+    };
+
     quote! {
         impl<'a> #zbus::ProxyDefault for #proxy_name<'a> {
             const INTERFACE: &'static str = #name;
             const DESTINATION: &'static str = #default_service;
             const PATH: &'static str = #default_path;
+            const METADATA: ::std::option::Option<&'static #zbus::InterfaceMetadata> =
+                ::std::option::Option::Some(&#zbus::InterfaceMetadata {
+                    name: #name,
+                    methods: &[#methods_metadata],
+                    properties: &[#(#properties_metadata),*],
+                    signals: &[#signals_metadata],
+                });
         }
 
         #[doc = #proxy_doc]
         #(#doc)*
         #[derive(Debug)]
-        pub struct #proxy_name<'c>(#proxy_struct<'c>);
+        #vis struct #proxy_name<'c>(#proxy_struct<'c>);
 
         impl<'c> #proxy_name<'c> {
             /// Creates a new proxy with the default service & path.
@@ -156,6 +346,8 @@ pub fn create_proxy(args: &[NestedMeta], input: &ItemTrait, azync: bool) -> Toke
                 Self::builder(conn).build()
             }
 
+            #for_name
+
             /// Returns a customizable builder for this proxy.
             pub fn builder(conn: &#connection) -> #zbus::ProxyBuilder<'c, Self> {
                 #zbus::ProxyBuilder::new(conn)
@@ -171,6 +363,8 @@ pub fn create_proxy(args: &[NestedMeta], input: &ItemTrait, azync: bool) -> Toke
                 &self.0
             }
 
+            #transaction
+
             #methods
         }
 
@@ -180,6 +374,8 @@ pub fn create_proxy(args: &[NestedMeta], input: &ItemTrait, azync: bool) -> Toke
             }
         }
 
+        #from_proxy
+
         impl<'c> ::std::ops::Deref for #proxy_name<'c> {
             type Target = #proxy_struct<'c>;
 
@@ -194,6 +390,15 @@ pub fn create_proxy(args: &[NestedMeta], input: &ItemTrait, azync: bool) -> Toke
             }
         }
 
+        impl<'c> #zbus::AsyncDrop for #proxy_name<'c> {
+            fn async_drop<'d>(self) -> #zbus::export::futures_core::future::BoxFuture<'d, #zbus::Result<()>>
+            where
+                Self: 'd,
+            {
+                #zbus::AsyncDrop::async_drop(self.0)
+            }
+        }
+
         impl<'c> ::std::convert::AsRef<#proxy_struct<'c>> for #proxy_name<'c> {
             fn as_ref(&self) -> &#proxy_struct<'c> {
                 &*self
@@ -225,7 +430,198 @@ pub fn create_proxy(args: &[NestedMeta], input: &ItemTrait, azync: bool) -> Toke
         }
 
         #stream_types
+
+        #transaction_types
+    }
+}
+
+// The setter of a property transaction, for the given property setter.
+fn gen_transaction_setter(property_name: &str, m: &TraitItemMethod) -> TokenStream {
+    let doc = get_doc_attrs(&m.attrs);
+    let method = &m.sig.ident;
+    let generics = &m.sig.generics;
+    let where_clause = &generics.where_clause;
+    let value_arg = m.sig.inputs.last().unwrap();
+    let value = arg_ident(value_arg).unwrap();
+
+    quote! {
+        #(#doc)*
+        pub fn #method#generics(self, #value_arg) -> Self
+        #where_clause
+        {
+            Self(self.0.set(#property_name, #value))
+        }
+    }
+}
+
+#[derive(Default)]
+struct PropertyInfo {
+    ty: Option<Type>,
+    read: bool,
+    write: bool,
+}
+
+fn has_type_params(m: &TraitItemMethod) -> bool {
+    m.sig
+        .generics
+        .params
+        .iter()
+        .any(|p| matches!(p, syn::GenericParam::Type(_)))
+}
+
+// The `T` of a `Result<T>` return type.
+fn result_ok_type(output: &ReturnType) -> Option<Type> {
+    let path = match output {
+        ReturnType::Type(_, ty) => match &**ty {
+            Type::Path(p) => &p.path,
+            _ => return None,
+        },
+        ReturnType::Default => return None,
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(ty) => Some(ty.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// `output`, with the `T` of the `Result<T>` replaced by `ok`.
+fn with_result_ok_type(output: &ReturnType, ok: Type) -> ReturnType {
+    let mut output = output.clone();
+    let arg = match &mut output {
+        ReturnType::Type(_, ty) => match &mut **ty {
+            Type::Path(p) => p
+                .path
+                .segments
+                .last_mut()
+                .and_then(|s| match &mut s.arguments {
+                    PathArguments::AngleBracketed(args) => args.args.first_mut(),
+                    _ => None,
+                }),
+            _ => None,
+        },
+        ReturnType::Default => None,
+    };
+    match arg {
+        Some(GenericArgument::Type(ty)) => *ty = ok,
+        _ => panic!("`out_struct` requires a `Result` return type"),
+    }
+
+    output
+}
+
+// The name of the struct given through `out_struct`, if any.
+fn out_struct_name(attrs: &[ItemAttribute]) -> Option<Ident> {
+    attrs.iter().find_map(|x| match x {
+        ItemAttribute::OutStruct(name) => Some(Ident::new(name, Span::call_site())),
+        _ => None,
+    })
+}
+
+// The names of the fields of an `out_struct`, for `count` arguments.
+//
+// These are the names given through `out_args`, in snake case, or `arg1`, `arg2` and so on.
+fn out_struct_fields(attrs: &[ItemAttribute], count: usize) -> Option<Vec<Ident>> {
+    let names = attrs.iter().find_map(|x| match x {
+        ItemAttribute::OutArgs(names) => Some(names),
+        _ => None,
+    })?;
+    if names.len() != count {
+        panic!(
+            "`out_args` gives {} names, for {} arguments",
+            names.len(),
+            count
+        );
+    }
+
+    Some(
+        names
+            .iter()
+            .map(|name| format_ident!("{}", snake_case(name)))
+            .collect(),
+    )
+}
+
+// The struct returned by the method instead of the tuple of its out arguments, if it has an
+// `out_struct` attribute.
+//
+// It has the same signature and encoding as the tuple, so it's deserialized from the reply as is.
+fn gen_out_struct(m: &TraitItemMethod, vis: &Visibility) -> Option<TokenStream> {
+    let attrs = parse_item_attributes(&m.attrs, "dbus_proxy").unwrap();
+    if attrs.iter().any(|x| x.is_signal()) {
+        // The args of signals are renamed in their generated type instead.
+        return None;
+    }
+    let name = out_struct_name(&attrs)?;
+    if has_type_params(m) {
+        panic!("`out_struct` isn't supported on generic methods");
+    }
+    let types: Vec<_> = match result_ok_type(&m.sig.output) {
+        Some(Type::Tuple(tuple)) if !tuple.elems.is_empty() => tuple.elems.into_iter().collect(),
+        _ => panic!("`out_struct` requires the method to return a tuple of its out arguments"),
+    };
+    let fields = out_struct_fields(&attrs, types.len()).unwrap_or_else(|| {
+        (1..=types.len())
+            .map(|i| format_ident!("arg{}", i))
+            .collect()
+    });
+    let zbus = zbus_path();
+    let doc = format!(
+        "The out arguments of `{}`, as returned by the proxy method.",
+        m.sig.ident
+    );
+
+    Some(quote! {
+        #[doc = #doc]
+        #[derive(Debug)]
+        #vis struct #name {
+            #(pub #fields: #types,)*
+        }
+
+        impl #zbus::export::zvariant::Type for #name {
+            fn signature() -> #zbus::export::zvariant::Signature<'static> {
+                <(#(#types,)*) as #zbus::export::zvariant::Type>::signature()
+            }
+        }
+
+        impl<'de> #zbus::export::serde::de::Deserialize<'de> for #name {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: #zbus::export::serde::de::Deserializer<'de>,
+            {
+                let (#(#fields,)*) =
+                    <(#(#types,)*) as #zbus::export::serde::de::Deserialize<'de>>::deserialize(
+                        deserializer,
+                    )?;
+
+                ::std::result::Result::Ok(Self { #(#fields),* })
+            }
+        }
+    })
+}
+
+// The tuple of all the argument types, unless some of them can't be named out of the method.
+fn args_tuple_type(m: &TraitItemMethod) -> Option<Type> {
+    if has_type_params(m) {
+        return None;
+    }
+    let mut types = vec![];
+    for arg in &m.sig.inputs {
+        if let FnArg::Typed(p) = arg {
+            if matches!(*p.ty, Type::ImplTrait(_)) {
+                return None;
+            }
+            types.push(&p.ty);
+        }
     }
+
+    Some(parse_quote!((#(#types,)*)))
 }
 
 fn gen_proxy_method_call(
@@ -254,6 +650,36 @@ fn gen_proxy_method_call(
         }
         _ => None,
     });
+    let is_idempotent = attrs.iter().any(|x| x.is_idempotent());
+    let call = if is_idempotent {
+        quote! { call_idempotent }
+    } else {
+        quote! { call }
+    };
+    let call_method = if is_idempotent {
+        quote! { call_method_idempotent }
+    } else {
+        quote! { call_method }
+    };
+    // The reply of the call with `body`, ignoring any arguments the service may have appended.
+    let is_strict = attrs.iter().any(|x| x.is_strict_signature());
+    let azync_proxy = if *azync {
+        quote! { self.0 }
+    } else {
+        quote! { self.0.inner() }
+    };
+    let reply = |body: TokenStream| {
+        if is_strict {
+            quote! { self.0.#call(#method_name, #body)#wait? }
+        } else {
+            quote! {{
+                let reply = self.0.#call_method(#method_name, #body)#wait?;
+                // The FDs of the reply are the caller's, once it's gone.
+                reply.disown_fds();
+                #azync_proxy.reply_body(&reply, #method_name, true)?
+            }}
+        }
+    };
     let method = Ident::new(snake_case_name, Span::call_site());
     let inputs = &m.sig.inputs;
     let mut generics = m.sig.generics.clone();
@@ -289,6 +715,7 @@ fn gen_proxy_method_call(
 
     if let Some(proxy_name) = proxy_object {
         let proxy = Ident::new(&proxy_name, Span::call_site());
+        let object_path_reply = reply(quote! { &(#(#args),*) });
         let signature = quote! {
             fn #method#ty_generics(#inputs) -> #zbus::Result<#proxy<'c>>
             #where_clause
@@ -298,11 +725,7 @@ fn gen_proxy_method_call(
             #(#doc)*
             pub #usage #signature {
                 let object_path: #zbus::export::zvariant::OwnedObjectPath =
-                    self.0.call(
-                        #method_name,
-                        &(#(#args),*),
-                    )
-                    #wait?;
+                    #object_path_reply;
                 #proxy::builder(&self.0.connection())
                     .path(object_path)?
                     .build()
@@ -322,15 +745,19 @@ fn gen_proxy_method_call(
             }
         };
 
-        let output = &m.sig.output;
+        let output = match out_struct_name(&attrs) {
+            Some(name) => with_result_ok_type(&m.sig.output, parse_quote!(#name)),
+            None => m.sig.output.clone(),
+        };
         let signature = quote! {
             fn #method#ty_generics(#inputs) #output
             #where_clause
         };
+        let reply = reply(body);
         quote! {
             #(#doc)*
             pub #usage #signature {
-                let reply = self.0.call(#method_name, #body)#wait?;
+                let reply = #reply;
                 ::std::result::Result::Ok(reply)
             }
         }
@@ -400,6 +827,13 @@ fn gen_proxy_signal(
     let zbus = zbus_path();
     let doc = get_doc_attrs(&m.attrs);
     let method = format_ident!("connect_{}", snake_case_name);
+    // Unless told otherwise, arguments the service may have appended to the signal are ignored.
+    let attrs = parse_item_attributes(&m.attrs, "dbus_proxy").unwrap();
+    let body = if attrs.iter().any(|x| x.is_strict_signature()) {
+        quote!(body)
+    } else {
+        quote!(body_prefix)
+    };
     let input_types: Vec<Box<Type>> = m
         .sig
         .inputs
@@ -448,9 +882,10 @@ fn gen_proxy_signal(
         let (receiver_name, stream_name, signal_args, signal_name_ident) = (
             format_ident!("receive_{}", snake_case_name),
             format_ident!("{}Stream", signal_name),
-            format_ident!("{}Args", signal_name),
+            out_struct_name(&attrs).unwrap_or_else(|| format_ident!("{}Args", signal_name)),
             format_ident!("{}", signal_name),
         );
+        let fields = out_struct_fields(&attrs, args.len()).unwrap_or_else(|| args.clone());
 
         let receive_signal_link =
             "https://docs.rs/zbus/latest/zbus/azync/struct.Proxy.html#method.receive_signal";
@@ -483,9 +918,9 @@ fn gen_proxy_signal(
             quote!()
         } else {
             let arg_fields_init = if args.len() == 1 {
-                quote! { #(#args)*: args }
+                quote! { #(#fields)*: args }
             } else {
-                quote! { #(#args: args.#args_nth),* }
+                quote! { #(#fields: args.#args_nth),* }
             };
             quote! {
                 impl #signal_name_ident {
@@ -493,7 +928,7 @@ fn gen_proxy_signal(
                     pub fn args#ty_generics(&'s self) -> #zbus::Result<#signal_args #ty_generics>
                         #where_clause
                     {
-                        self.0.body::<(#(#input_types),*)>()
+                        self.0.#body::<(#(#input_types),*)>()
                             .map_err(::std::convert::Into::into)
                             .map(|args| {
                                 #signal_args {
@@ -508,7 +943,7 @@ fn gen_proxy_signal(
                 pub struct #signal_args #ty_generics {
                     phantom: std::marker::PhantomData<&'s ()>,
                     #(
-                        pub #args: #input_types_s
+                        pub #fields: #input_types_s
                      ),*
                 }
 
@@ -516,8 +951,8 @@ fn gen_proxy_signal(
                     #where_clause
                 {
                     #(
-                        pub fn #args(&self) -> &#input_types_s {
-                            &self.#args
+                        pub fn #fields(&self) -> &#input_types_s {
+                            &self.#fields
                         }
                      )*
                 }
@@ -572,6 +1007,15 @@ fn gen_proxy_signal(
                 }
             }
 
+            impl #zbus::AsyncDrop for #stream_name<'_> {
+                fn async_drop<'d>(self) -> #zbus::export::futures_core::future::BoxFuture<'d, #zbus::Result<()>>
+                where
+                    Self: 'd,
+                {
+                    #zbus::AsyncDrop::async_drop(self.0)
+                }
+            }
+
             #[doc = #args_struct_gen_doc]
             pub struct #signal_name_ident(::std::sync::Arc<#zbus::Message>);
 
@@ -638,7 +1082,7 @@ fn gen_proxy_signal(
         #where_clause,
         {
             self.0.connect_signal(#signal_name, move |m| {
-                let (#(#args),*) = m.body().expect("Incorrect signal signature");
+                let (#(#args),*) = m.#body().expect("Incorrect signal signature");
 
                 handler(#(#args),*)
             })#wait
@@ -1,8 +1,9 @@
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use proc_macro_crate::{crate_name, FoundCrate};
 use quote::{format_ident, quote};
 use syn::{
-    Attribute, FnArg, Ident, Lit, Meta, MetaList, NestedMeta, Pat, PatIdent, PatType, Result,
+    fold::Fold, Attribute, FnArg, Ident, Lit, Meta, MetaList, NestedMeta, Pat, PatIdent, PatType,
+    Result, Type,
 };
 
 pub fn zbus_path() -> TokenStream {
@@ -63,17 +64,27 @@ pub fn snake_case(s: &str) -> String {
 
 #[derive(Debug, PartialEq)]
 pub enum ItemAttribute {
-    Property,
+    Property { skip_on_error: bool },
     Signal,
     StructReturn,
     OutArgs(Vec<String>),
+    OutStruct(String),
     Name(String),
     Object(String),
+    Idempotent,
+    EmitsChangedSignal(String),
+    StrictSignature,
 }
 
 impl ItemAttribute {
     pub fn is_property(&self) -> bool {
-        self == &Self::Property
+        matches!(self, Self::Property { .. })
+    }
+
+    pub fn is_skip_on_error(&self) -> bool {
+        self == &Self::Property {
+            skip_on_error: true,
+        }
     }
 
     pub fn is_signal(&self) -> bool {
@@ -83,6 +94,14 @@ impl ItemAttribute {
     pub fn is_out_args(&self) -> bool {
         matches!(self, Self::OutArgs(_))
     }
+
+    pub fn is_idempotent(&self) -> bool {
+        self == &Self::Idempotent
+    }
+
+    pub fn is_strict_signature(&self) -> bool {
+        self == &Self::StrictSignature
+    }
 }
 
 // find the #[@attr_name] attribute in @attrs
@@ -127,6 +146,10 @@ fn parse_attribute(meta: &NestedMeta) -> (String, Vec<String>) {
                         Lit::Str(s) => values.push(s.value()),
                         _ => panic!("wrong meta type"),
                     },
+                    NestedMeta::Meta(Meta::Path(p)) => match p.get_ident() {
+                        Some(ident) => values.push(ident.to_string()),
+                        None => panic!("missing ident"),
+                    },
                     NestedMeta::Meta(_) => panic!("wrong meta type"),
                 }
             }
@@ -148,17 +171,33 @@ fn proxy_parse_item_attribute(meta: &NestedMeta) -> Result<ItemAttribute> {
 
     match ident.as_ref() {
         "name" => Ok(ItemAttribute::Name(values.remove(0))),
-        "property" => Ok(ItemAttribute::Property),
+        "property" => {
+            let mut skip_on_error = false;
+            for value in values.iter().filter(|v| !v.is_empty()) {
+                match value.as_ref() {
+                    "skip_on_error" => skip_on_error = true,
+                    s => panic!("Unknown property meta {}", s),
+                }
+            }
+
+            Ok(ItemAttribute::Property { skip_on_error })
+        }
         "signal" => Ok(ItemAttribute::Signal),
         "struct_return" => Ok(ItemAttribute::StructReturn),
         "out_args" => Ok(ItemAttribute::OutArgs(values)),
+        "out_struct" => Ok(ItemAttribute::OutStruct(values.remove(0))),
         "object" => Ok(ItemAttribute::Object(values.remove(0))),
+        "idempotent" => Ok(ItemAttribute::Idempotent),
+        "strict_signature" => Ok(ItemAttribute::StrictSignature),
+        "emits_changed_signal" => Ok(ItemAttribute::EmitsChangedSignal(values.remove(0))),
         s => panic!("Unknown item meta {}", s),
     }
 }
 
 // Parse optional item attributes such as:
 // #[dbus_proxy(name = "MyName", property)]
+// #[dbus_interface(property(skip_on_error))]
+// #[dbus_interface(property, emits_changed_signal = "false")]
 pub fn parse_item_attributes(attrs: &[Attribute], attr_name: &str) -> Result<Vec<ItemAttribute>> {
     let meta = find_attribute_meta(attrs, attr_name)?;
 
@@ -204,6 +243,36 @@ pub fn is_blank(s: &str) -> bool {
     s.trim().is_empty()
 }
 
+// An `Option<fn() -> Signature<'static>>` returning the signature of `ty`, if known.
+pub fn gen_signature_fn(ty: Option<Type>) -> TokenStream {
+    let zbus = zbus_path();
+    match ty {
+        Some(ty) => {
+            let ty = SetLifetimeStatic.fold_type(ty);
+            quote! {
+                ::std::option::Option::Some(
+                    || <#ty as #zbus::export::zvariant::Type>::signature()
+                )
+            }
+        }
+        None => quote! { ::std::option::Option::None },
+    }
+}
+
+struct SetLifetimeStatic;
+
+impl Fold for SetLifetimeStatic {
+    fn fold_type_reference(&mut self, node: syn::TypeReference) -> syn::TypeReference {
+        let mut t = syn::fold::fold_type_reference(self, node);
+        t.lifetime = Some(syn::Lifetime::new("'static", Span::call_site()));
+        t
+    }
+
+    fn fold_lifetime(&mut self, _node: syn::Lifetime) -> syn::Lifetime {
+        syn::Lifetime::new("'static", Span::call_site())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{pascal_case, snake_case};
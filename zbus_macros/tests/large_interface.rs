@@ -0,0 +1,607 @@
+//! An interface with 100 methods, to keep an eye on the size and compile time of the code
+//! generated by `dbus_interface`.
+//!
+//! Being its own test crate, it can be measured on its own, e.g with:
+//!
+//! ```text
+//! cargo build -p zbus_macros --tests --timings
+//! cargo bloat -p zbus_macros --test large_interface --filter large_interface
+//! ```
+use std::{os::unix::net::UnixStream, sync::mpsc::channel, thread};
+
+use zbus::{dbus_interface, fdo, Connection, Error, Guid, MessageHeader, ObjectServer};
+
+struct Large {
+    calls: u32,
+}
+
+#[dbus_interface(name = "org.freedesktop.zbus.Large")]
+impl Large {
+    fn method_000(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 0, a, b)
+    }
+
+    fn method_001(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 1
+    }
+
+    fn method_002(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_002: empty".into()));
+        }
+        Ok(a.len() as u64 + 2)
+    }
+
+    fn method_003(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method003"));
+        (a.0 + 3, !a.1)
+    }
+
+    fn method_004(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 4, a, b)
+    }
+
+    fn method_005(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 5
+    }
+
+    fn method_006(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_006: empty".into()));
+        }
+        Ok(a.len() as u64 + 6)
+    }
+
+    fn method_007(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method007"));
+        (a.0 + 7, !a.1)
+    }
+
+    fn method_008(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 8, a, b)
+    }
+
+    fn method_009(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 9
+    }
+
+    fn method_010(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_010: empty".into()));
+        }
+        Ok(a.len() as u64 + 10)
+    }
+
+    fn method_011(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method011"));
+        (a.0 + 11, !a.1)
+    }
+
+    fn method_012(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 12, a, b)
+    }
+
+    fn method_013(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 13
+    }
+
+    fn method_014(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_014: empty".into()));
+        }
+        Ok(a.len() as u64 + 14)
+    }
+
+    fn method_015(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method015"));
+        (a.0 + 15, !a.1)
+    }
+
+    fn method_016(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 16, a, b)
+    }
+
+    fn method_017(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 17
+    }
+
+    fn method_018(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_018: empty".into()));
+        }
+        Ok(a.len() as u64 + 18)
+    }
+
+    fn method_019(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method019"));
+        (a.0 + 19, !a.1)
+    }
+
+    fn method_020(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 20, a, b)
+    }
+
+    fn method_021(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 21
+    }
+
+    fn method_022(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_022: empty".into()));
+        }
+        Ok(a.len() as u64 + 22)
+    }
+
+    fn method_023(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method023"));
+        (a.0 + 23, !a.1)
+    }
+
+    fn method_024(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 24, a, b)
+    }
+
+    fn method_025(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 25
+    }
+
+    fn method_026(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_026: empty".into()));
+        }
+        Ok(a.len() as u64 + 26)
+    }
+
+    fn method_027(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method027"));
+        (a.0 + 27, !a.1)
+    }
+
+    fn method_028(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 28, a, b)
+    }
+
+    fn method_029(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 29
+    }
+
+    fn method_030(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_030: empty".into()));
+        }
+        Ok(a.len() as u64 + 30)
+    }
+
+    fn method_031(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method031"));
+        (a.0 + 31, !a.1)
+    }
+
+    fn method_032(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 32, a, b)
+    }
+
+    fn method_033(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 33
+    }
+
+    fn method_034(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_034: empty".into()));
+        }
+        Ok(a.len() as u64 + 34)
+    }
+
+    fn method_035(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method035"));
+        (a.0 + 35, !a.1)
+    }
+
+    fn method_036(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 36, a, b)
+    }
+
+    fn method_037(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 37
+    }
+
+    fn method_038(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_038: empty".into()));
+        }
+        Ok(a.len() as u64 + 38)
+    }
+
+    fn method_039(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method039"));
+        (a.0 + 39, !a.1)
+    }
+
+    fn method_040(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 40, a, b)
+    }
+
+    fn method_041(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 41
+    }
+
+    fn method_042(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_042: empty".into()));
+        }
+        Ok(a.len() as u64 + 42)
+    }
+
+    fn method_043(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method043"));
+        (a.0 + 43, !a.1)
+    }
+
+    fn method_044(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 44, a, b)
+    }
+
+    fn method_045(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 45
+    }
+
+    fn method_046(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_046: empty".into()));
+        }
+        Ok(a.len() as u64 + 46)
+    }
+
+    fn method_047(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method047"));
+        (a.0 + 47, !a.1)
+    }
+
+    fn method_048(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 48, a, b)
+    }
+
+    fn method_049(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 49
+    }
+
+    fn method_050(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_050: empty".into()));
+        }
+        Ok(a.len() as u64 + 50)
+    }
+
+    fn method_051(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method051"));
+        (a.0 + 51, !a.1)
+    }
+
+    fn method_052(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 52, a, b)
+    }
+
+    fn method_053(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 53
+    }
+
+    fn method_054(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_054: empty".into()));
+        }
+        Ok(a.len() as u64 + 54)
+    }
+
+    fn method_055(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method055"));
+        (a.0 + 55, !a.1)
+    }
+
+    fn method_056(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 56, a, b)
+    }
+
+    fn method_057(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 57
+    }
+
+    fn method_058(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_058: empty".into()));
+        }
+        Ok(a.len() as u64 + 58)
+    }
+
+    fn method_059(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method059"));
+        (a.0 + 59, !a.1)
+    }
+
+    fn method_060(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 60, a, b)
+    }
+
+    fn method_061(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 61
+    }
+
+    fn method_062(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_062: empty".into()));
+        }
+        Ok(a.len() as u64 + 62)
+    }
+
+    fn method_063(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method063"));
+        (a.0 + 63, !a.1)
+    }
+
+    fn method_064(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 64, a, b)
+    }
+
+    fn method_065(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 65
+    }
+
+    fn method_066(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_066: empty".into()));
+        }
+        Ok(a.len() as u64 + 66)
+    }
+
+    fn method_067(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method067"));
+        (a.0 + 67, !a.1)
+    }
+
+    fn method_068(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 68, a, b)
+    }
+
+    fn method_069(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 69
+    }
+
+    fn method_070(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_070: empty".into()));
+        }
+        Ok(a.len() as u64 + 70)
+    }
+
+    fn method_071(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method071"));
+        (a.0 + 71, !a.1)
+    }
+
+    fn method_072(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 72, a, b)
+    }
+
+    fn method_073(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 73
+    }
+
+    fn method_074(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_074: empty".into()));
+        }
+        Ok(a.len() as u64 + 74)
+    }
+
+    fn method_075(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method075"));
+        (a.0 + 75, !a.1)
+    }
+
+    fn method_076(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 76, a, b)
+    }
+
+    fn method_077(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 77
+    }
+
+    fn method_078(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_078: empty".into()));
+        }
+        Ok(a.len() as u64 + 78)
+    }
+
+    fn method_079(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method079"));
+        (a.0 + 79, !a.1)
+    }
+
+    fn method_080(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 80, a, b)
+    }
+
+    fn method_081(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 81
+    }
+
+    fn method_082(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_082: empty".into()));
+        }
+        Ok(a.len() as u64 + 82)
+    }
+
+    fn method_083(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method083"));
+        (a.0 + 83, !a.1)
+    }
+
+    fn method_084(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 84, a, b)
+    }
+
+    fn method_085(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 85
+    }
+
+    fn method_086(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_086: empty".into()));
+        }
+        Ok(a.len() as u64 + 86)
+    }
+
+    fn method_087(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method087"));
+        (a.0 + 87, !a.1)
+    }
+
+    fn method_088(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 88, a, b)
+    }
+
+    fn method_089(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 89
+    }
+
+    fn method_090(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_090: empty".into()));
+        }
+        Ok(a.len() as u64 + 90)
+    }
+
+    fn method_091(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method091"));
+        (a.0 + 91, !a.1)
+    }
+
+    fn method_092(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 92, a, b)
+    }
+
+    fn method_093(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 93
+    }
+
+    fn method_094(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_094: empty".into()));
+        }
+        Ok(a.len() as u64 + 94)
+    }
+
+    fn method_095(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method095"));
+        (a.0 + 95, !a.1)
+    }
+
+    fn method_096(&self, a: u32, b: &str) -> String {
+        format!("{} {} {}", 96, a, b)
+    }
+
+    fn method_097(&mut self, a: u64) -> u64 {
+        self.calls += 1;
+        a + 97
+    }
+
+    fn method_098(&self, a: Vec<u8>) -> fdo::Result<u64> {
+        if a.is_empty() {
+            return Err(fdo::Error::InvalidArgs("method_098: empty".into()));
+        }
+        Ok(a.len() as u64 + 98)
+    }
+
+    fn method_099(&self, #[zbus(header)] header: MessageHeader<'_>, a: (i32, bool)) -> (i32, bool) {
+        assert_eq!(header.member().unwrap(), Some("Method099"));
+        (a.0 + 99, !a.1)
+    }
+
+    fn calls(&self) -> u32 {
+        self.calls
+    }
+}
+
+#[test]
+fn large_interface() {
+    let (p0, p1) = UnixStream::pair().unwrap();
+    let guid = Guid::generate();
+    let (tx, rx) = channel::<()>();
+
+    let server_thread = thread::spawn(move || {
+        let conn = Connection::new_unix_server(p0, &guid).unwrap();
+        let mut object_server = ObjectServer::new(&conn);
+        object_server
+            .at("/zbus/test/large", Large { calls: 0 })
+            .unwrap();
+        tx.send(()).unwrap();
+
+        for _ in 0..8 {
+            assert!(object_server.try_handle_next().unwrap().is_none());
+        }
+    });
+
+    let conn = Connection::new_unix_client(p1, false).unwrap();
+    rx.recv().unwrap();
+    macro_rules! call {
+        ($method:literal, $body:expr) => {
+            conn.call_method(
+                None,
+                "/zbus/test/large",
+                Some("org.freedesktop.zbus.Large"),
+                $method,
+                $body,
+            )
+        };
+    }
+    let error_name = |err| match err {
+        Error::MethodError(name, _, _) => name,
+        e => panic!("unexpected error: {}", e),
+    };
+
+    let reply = call!("Method000", &(1u32, "first")).unwrap();
+    assert_eq!(reply.body::<String>().unwrap(), "0 1 first");
+    let reply = call!("Method099", &((1i32, true),)).unwrap();
+    assert_eq!(reply.body::<(i32, bool)>().unwrap(), (100, false));
+    let reply = call!("Method042", &(vec![1u8, 2, 3],)).unwrap();
+    assert_eq!(reply.body::<u64>().unwrap(), 45);
+    let reply = call!("Method057", &(1u64,)).unwrap();
+    assert_eq!(reply.body::<u64>().unwrap(), 58);
+    let reply = call!("Calls", &()).unwrap();
+    assert_eq!(reply.body::<u32>().unwrap(), 1);
+
+    let err = call!("Method042", &(Vec::<u8>::new(),)).unwrap_err();
+    assert_eq!(error_name(err), "org.freedesktop.DBus.Error.InvalidArgs");
+    // Wrong arguments.
+    let err = call!("Method000", &(1u32,)).unwrap_err();
+    assert_eq!(error_name(err), "org.freedesktop.DBus.Error.InvalidArgs");
+    let err = call!("Method100", &()).unwrap_err();
+    assert_eq!(error_name(err), "org.freedesktop.DBus.Error.UnknownMethod");
+
+    server_thread.join().unwrap();
+}
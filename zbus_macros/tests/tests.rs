@@ -3,7 +3,7 @@ use futures_util::{
     future::{select, Either},
     stream::StreamExt,
 };
-use std::future::ready;
+use std::{future::ready, os::unix::net::UnixStream, thread};
 use zbus::fdo;
 use zbus_macros::{dbus_interface, dbus_proxy, DBusError};
 
@@ -88,6 +88,96 @@ fn test_proxy() {
     });
 }
 
+#[test]
+fn test_proxy_path_template() {
+    #[dbus_proxy(
+        interface = "org.freedesktop.UPower.Device",
+        default_service = "org.freedesktop.UPower",
+        default_path_template = "/org/freedesktop/UPower/devices/{}"
+    )]
+    trait Device {
+        #[dbus_proxy(property)]
+        fn percentage(&self) -> zbus::Result<f64>;
+    }
+
+    let (p0, p1) = UnixStream::pair().unwrap();
+    let server_thread = thread::spawn(move || {
+        zbus::Connection::new_unix_server(p0, &zbus::Guid::generate()).unwrap()
+    });
+    let conn = zbus::Connection::new_unix_client(p1, false).unwrap();
+    let _server = server_thread.join().unwrap();
+
+    let proxy = DeviceProxy::for_name(&conn, "battery_BAT0").unwrap();
+    assert_eq!(
+        proxy.path().as_str(),
+        "/org/freedesktop/UPower/devices/battery_5fBAT0"
+    );
+    // The default destination is left out on peer-to-peer connections.
+    assert_eq!(proxy.destination(), None);
+
+    let conn = zbus::azync::Connection::from(conn);
+    let proxy = AsyncDeviceProxy::for_name(&conn, "line.power").unwrap();
+    assert_eq!(
+        proxy.path().as_str(),
+        "/org/freedesktop/UPower/devices/line_2epower"
+    );
+}
+
+#[test]
+fn test_proxy_out_struct() {
+    #[dbus_proxy(
+        interface = "org.freedesktop.systemd1.Manager",
+        default_service = "org.freedesktop.systemd1",
+        default_path = "/org/freedesktop/systemd1"
+    )]
+    trait Manager {
+        #[dbus_proxy(
+            out_struct = "UnitFileChanges",
+            out_args("carries_install_info", "changes")
+        )]
+        fn enable_unit_files(
+            &self,
+            files: &[&str],
+            runtime: bool,
+            force: bool,
+        ) -> zbus::Result<(bool, Vec<(String, String, String)>)>;
+
+        #[dbus_proxy(out_struct = "JobInfo")]
+        fn job_info(&self, id: u32) -> zbus::Result<(u32, String)>;
+
+        #[dbus_proxy(
+            signal,
+            out_struct = "JobRemovedInfo",
+            out_args("id", "job", "unit", "result")
+        )]
+        fn job_removed(
+            &self,
+            id: u32,
+            job: zvariant::ObjectPath<'_>,
+            unit: &str,
+            result: &str,
+        ) -> zbus::Result<()>;
+    }
+
+    // Only testing the build of the generated types.
+    fn _changes(changes: UnitFileChanges) -> (bool, usize) {
+        (changes.carries_install_info, changes.changes.len())
+    }
+    fn _job_info(reply: JobInfo) -> (u32, String) {
+        (reply.arg1, reply.arg2)
+    }
+    fn _job_removed(args: JobRemovedInfo<'_>) -> (u32, String, String) {
+        assert_eq!(args.id, *args.id());
+        (args.id, args.job.to_string(), args.unit.to_string())
+    }
+
+    assert_eq!(
+        <UnitFileChanges as zvariant::Type>::signature(),
+        "(ba(sss))"
+    );
+    assert_eq!(<JobInfo as zvariant::Type>::signature(), "(us)");
+}
+
 #[test]
 fn test_derive_error() {
     #[derive(Debug, DBusError)]
@@ -1,11 +1,12 @@
 use byteorder::LE;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{collections::HashMap, convert::TryFrom};
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
 use zvariant::{
-    from_slice_for_signature, to_bytes_for_signature, EncodingContext as Context, Type, Value,
+    from_slice, from_slice_for_signature, to_bytes, to_bytes_for_signature,
+    EncodingContext as Context, ObjectPath, OwnedObjectPath, Type, Value,
 };
 use zvariant_derive::Type;
 
@@ -125,5 +126,50 @@ fn big_array_ser_and_de(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, big_array_ser_and_de, fixed_size_array);
+// The cost of validating object paths, against that of decoding them into the various types.
+fn object_path_array(c: &mut Criterion) {
+    let paths: Vec<_> = (0..10_000)
+        .map(|idx| format!("/org/freedesktop/zbus/Object{}", idx))
+        .collect();
+    let paths: Vec<_> = paths
+        .iter()
+        .map(|p| ObjectPath::try_from(p.as_str()).unwrap())
+        .collect();
+    let ctxt = Context::<LE>::new_dbus(0);
+    let encoded = to_bytes(ctxt, &paths).unwrap();
+
+    let mut group = c.benchmark_group("object_path_array_de");
+    for (name, ctxt) in &[("checked", ctxt), ("trusted", ctxt.trusted())] {
+        group.bench_function(format!("borrowed_{}", name), |b| {
+            b.iter(|| {
+                let v: Vec<ObjectPath<'_>> =
+                    from_slice(black_box(&encoded), black_box(*ctxt)).unwrap();
+                black_box(v);
+            })
+        });
+        group.bench_function(format!("owned_{}", name), |b| {
+            b.iter(|| {
+                let v: Vec<OwnedObjectPath> =
+                    from_slice(black_box(&encoded), black_box(*ctxt)).unwrap();
+                black_box(v);
+            })
+        });
+    }
+    // The baseline: plain strings, which aren't validated.
+    let encoded = to_bytes(ctxt, &paths.iter().map(|p| p.as_str()).collect::<Vec<_>>()).unwrap();
+    group.bench_function("str", |b| {
+        b.iter(|| {
+            let v: Vec<&str> = from_slice(black_box(&encoded), black_box(ctxt)).unwrap();
+            black_box(v);
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    big_array_ser_and_de,
+    fixed_size_array,
+    object_path_array
+);
 criterion_main!(benches);
@@ -2,7 +2,111 @@ use serde::ser::{Serialize, SerializeSeq, Serializer};
 use static_assertions::assert_impl_all;
 use std::convert::TryFrom;
 
-use crate::{Error, Result, Signature, Type, Value};
+use crate::{Error, PathSegment, Result, Signature, Type, Value};
+
+/// Use this to build an [`Array`] of values of a signature only known at runtime.
+///
+/// The element signature is either given upfront, or taken from the first element pushed. Either
+/// way, pushing an element of a different signature fails right away, with an
+/// [`Error::SignatureMismatch`] pointing at that element, rather than at serialization time.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+/// use zvariant::{ArrayBuilder, Error, PathSegment, Value};
+///
+/// let mut populated = HashMap::new();
+/// populated.insert("answer", Value::from(42u32));
+///
+/// let mut builder = ArrayBuilder::new();
+/// builder.push(Value::from(populated)).unwrap();
+/// // An empty map of another value type gets a different signature.
+/// let err = builder
+///     .push(Value::from(HashMap::<&str, u32>::new()))
+///     .unwrap_err();
+/// assert_eq!(err.path(), &[PathSegment::Element(1)]);
+/// assert!(matches!(&err, Error::InPath(e, _) if matches!(**e, Error::SignatureMismatch(..))));
+///
+/// builder.push(Value::from(HashMap::<&str, Value<'_>>::new())).unwrap();
+/// let array = builder.build().unwrap();
+/// assert_eq!(array.full_signature(), "aa{sv}");
+/// ```
+///
+/// [`Array`]: struct.Array.html
+/// [`Error::SignatureMismatch`]: enum.Error.html#variant.SignatureMismatch
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ArrayBuilder<'a> {
+    element_signature: Option<Signature<'a>>,
+    elements: Vec<Value<'a>>,
+}
+
+assert_impl_all!(ArrayBuilder<'_>: Send, Sync, Unpin);
+
+impl<'a> ArrayBuilder<'a> {
+    /// Create a new `ArrayBuilder`, taking the element signature from the first element pushed.
+    ///
+    /// Same as `ArrayBuilder::default()`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new `ArrayBuilder`, for elements of the given signature.
+    pub fn with_element_signature<'s: 'a>(element_signature: Signature<'s>) -> Self {
+        Self {
+            element_signature: Some(element_signature),
+            elements: vec![],
+        }
+    }
+
+    /// Append `element`.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::SignatureMismatch`] in the path of the element, if `element`'s signature doesn't
+    /// match the element signature. `self` is left unchanged then.
+    ///
+    /// [`Error::SignatureMismatch`]: enum.Error.html#variant.SignatureMismatch
+    pub fn push<'e: 'a>(&mut self, element: Value<'e>) -> Result<()> {
+        let signature = element.value_signature();
+        match &self.element_signature {
+            Some(expected) if *expected != signature => {
+                let e = Error::SignatureMismatch(expected.to_owned(), signature.to_owned());
+
+                return Err(e.in_path(PathSegment::Element(self.elements.len())));
+            }
+            Some(_) => (),
+            None => self.element_signature = Some(signature.to_owned()),
+        }
+        self.elements.push(element);
+
+        Ok(())
+    }
+
+    /// The signature of the elements, if given or already taken from the first element.
+    pub fn element_signature(&self) -> Option<&Signature<'a>> {
+        self.element_signature.as_ref()
+    }
+
+    /// Build the `Array`.
+    ///
+    /// # Errors
+    ///
+    /// if no element signature was given and no element pushed, since the signature of the array
+    /// can't be known then.
+    pub fn build(self) -> Result<Array<'a>> {
+        let element_signature = self.element_signature.ok_or_else(|| {
+            Error::Message("empty array built without an element signature".into())
+        })?;
+        let signature = create_signature(&element_signature);
+
+        Ok(Array {
+            element_signature,
+            elements: self.elements,
+            signature,
+        })
+    }
+}
 
 /// A helper type to wrap arrays in a [`Value`].
 ///
@@ -30,6 +134,13 @@ impl<'a> Array<'a> {
         }
     }
 
+    /// Create a new [`ArrayBuilder`], checking the signature of each element as it's pushed.
+    ///
+    /// [`ArrayBuilder`]: struct.ArrayBuilder.html
+    pub fn builder() -> ArrayBuilder<'a> {
+        ArrayBuilder::new()
+    }
+
     pub(crate) fn new_full_signature(signature: Signature<'_>) -> Array<'_> {
         let element_signature = signature.slice(1..);
         Array {
@@ -86,6 +197,10 @@ impl<'a> Array<'a> {
         &self.element_signature
     }
 
+    pub(crate) fn into_elements(self) -> Vec<Value<'a>> {
+        self.elements
+    }
+
     pub(crate) fn to_owned(&self) -> Array<'static> {
         Array {
             element_signature: self.element_signature.to_owned(),
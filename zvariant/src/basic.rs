@@ -1,17 +1,23 @@
-// FIXME: Drop this when the deprecated `Basic::ALIGNMENT` is dropped in the next API break.
+// FIXME: Drop this when the deprecated `Basic::ALIGNMENT` and `Basic::SIGNATURE_STR` are dropped
+// in the next API break.
 #![allow(deprecated)]
 
-use crate::{EncodingFormat, Signature, Type};
+use crate::{EncodingFormat, Signature, StaticType, Type};
 
 /// Trait for basic types.
 ///
-/// All basic types are also [`Type`] implementers.
+/// All basic types are also [`Type`] and [`StaticType`] implementers.
 ///
 /// [`Type`]: trait.Type.html
+/// [`StaticType`]: trait.StaticType.html
 /// [`Value`]: enum.Value.html
 pub trait Basic: Type {
     /// The type signature, as a character.
     const SIGNATURE_CHAR: char;
+    #[deprecated(
+        since = "2.8.0",
+        note = "Please use the `SIGNATURE_STR` constant of `StaticType` instead"
+    )]
     /// The type signature, as a string.
     const SIGNATURE_STR: &'static str;
     #[deprecated(since = "2.0.2", note = "Please use the `alignment` function instead")]
@@ -39,9 +45,13 @@ macro_rules! impl_type {
     ($for:ty) => {
         impl Type for $for {
             fn signature() -> Signature<'static> {
-                Signature::from_str_unchecked(<$for>::SIGNATURE_STR)
+                Signature::from_str_unchecked(<$for as StaticType>::SIGNATURE_STR)
             }
         }
+
+        impl StaticType for $for {
+            const SIGNATURE_STR: &'static str = <$for as Basic>::SIGNATURE_STR;
+        }
     };
 }
 
@@ -72,7 +82,7 @@ impl_type!(u8);
 // No i8 type in D-Bus/GVariant, let's pretend it's i16
 impl Basic for i8 {
     const SIGNATURE_CHAR: char = i16::SIGNATURE_CHAR;
-    const SIGNATURE_STR: &'static str = i16::SIGNATURE_STR;
+    const SIGNATURE_STR: &'static str = <i16 as Basic>::SIGNATURE_STR;
     const ALIGNMENT: usize = i16::ALIGNMENT;
 
     alignment_method!(Self::ALIGNMENT);
@@ -145,7 +155,7 @@ impl_type!(u64);
 // No f32 type in D-Bus/GVariant, let's pretend it's f64
 impl Basic for f32 {
     const SIGNATURE_CHAR: char = f64::SIGNATURE_CHAR;
-    const SIGNATURE_STR: &'static str = f64::SIGNATURE_STR;
+    const SIGNATURE_STR: &'static str = <f64 as Basic>::SIGNATURE_STR;
     const ALIGNMENT: usize = f64::ALIGNMENT;
 
     alignment_method!(Self::ALIGNMENT);
@@ -181,7 +191,7 @@ impl_type!(String);
 
 impl Basic for char {
     const SIGNATURE_CHAR: char = <&str>::SIGNATURE_CHAR;
-    const SIGNATURE_STR: &'static str = <&str>::SIGNATURE_STR;
+    const SIGNATURE_STR: &'static str = <&str as Basic>::SIGNATURE_STR;
     const ALIGNMENT: usize = <&str>::ALIGNMENT;
 
     alignment_method!(Self::ALIGNMENT, 1);
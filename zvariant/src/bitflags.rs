@@ -0,0 +1,157 @@
+#![cfg(feature = "enumflags2")]
+
+use enumflags2::{BitFlags, RawBitFlags};
+use serde::{
+    de::{Deserialize, Deserializer},
+    ser::{Serialize, Serializer},
+};
+use std::{convert::TryFrom, fmt};
+
+use crate::{Error, OwnedValue, Signature, StaticType, Type, Value};
+
+/// A set of flags that ignores the unknown bits when decoded.
+///
+/// [`BitFlags`] is encoded as its underlying integer. When decoding it, unknown bits (i-e not
+/// corresponding to any of the flags) are an error, so that nothing goes unnoticed. However, peers
+/// are often allowed to set flags we don't know about yet, e.g newer versions of a service adding
+/// flags. Use `TruncatedBitFlags` where such bits are to be dropped instead:
+///
+/// ```
+/// use std::convert::TryFrom;
+/// use enumflags2::BitFlags;
+/// use zvariant::{from_slice, to_bytes, EncodingContext, TruncatedBitFlags, Value};
+///
+/// #[repr(u32)]
+/// #[derive(BitFlags, Copy, Clone, Debug, PartialEq)]
+/// enum Flags {
+///     A = 0x1,
+///     B = 0x2,
+/// }
+///
+/// let ctxt = EncodingContext::<byteorder::LE>::new_dbus(0);
+/// let encoded = to_bytes(ctxt, &0x13u32).unwrap();
+///
+/// assert!(from_slice::<_, BitFlags<Flags>>(&encoded, ctxt).is_err());
+/// let flags: TruncatedBitFlags<Flags> = from_slice(&encoded, ctxt).unwrap();
+/// assert_eq!(flags.0, Flags::A | Flags::B);
+///
+/// let flags = TruncatedBitFlags::<Flags>::try_from(Value::from(0x11u32)).unwrap();
+/// assert_eq!(flags.0, Flags::A);
+/// ```
+///
+/// [`BitFlags`]: https://docs.rs/enumflags2/0.6/enumflags2/struct.BitFlags.html
+#[derive(Copy, Clone)]
+pub struct TruncatedBitFlags<F: RawBitFlags>(pub BitFlags<F>);
+
+impl<F: RawBitFlags> TruncatedBitFlags<F> {
+    /// Create a flag set from `bits`, dropping the unknown ones.
+    pub fn from_bits(bits: F::Type) -> Self {
+        Self(BitFlags::from_bits_truncate(bits))
+    }
+}
+
+impl<F> fmt::Debug for TruncatedBitFlags<F>
+where
+    F: RawBitFlags + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TruncatedBitFlags").field(&self.0).finish()
+    }
+}
+
+impl<F: RawBitFlags> PartialEq for TruncatedBitFlags<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<F: RawBitFlags> From<BitFlags<F>> for TruncatedBitFlags<F> {
+    fn from(flags: BitFlags<F>) -> Self {
+        Self(flags)
+    }
+}
+
+impl<F: RawBitFlags> From<TruncatedBitFlags<F>> for BitFlags<F> {
+    fn from(flags: TruncatedBitFlags<F>) -> Self {
+        flags.0
+    }
+}
+
+impl<F> Type for TruncatedBitFlags<F>
+where
+    F: RawBitFlags,
+    F::Type: Type,
+{
+    #[inline]
+    fn signature() -> Signature<'static> {
+        F::Type::signature()
+    }
+}
+
+impl<F> StaticType for TruncatedBitFlags<F>
+where
+    F: RawBitFlags,
+    F::Type: StaticType,
+{
+    const SIGNATURE_STR: &'static str = <F::Type as StaticType>::SIGNATURE_STR;
+}
+
+impl<F> Serialize for TruncatedBitFlags<F>
+where
+    F: RawBitFlags,
+    F::Type: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.bits().serialize(serializer)
+    }
+}
+
+impl<'de, F> Deserialize<'de> for TruncatedBitFlags<F>
+where
+    F: RawBitFlags,
+    F::Type: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        F::Type::deserialize(deserializer).map(Self::from_bits)
+    }
+}
+
+impl<'a, F> From<TruncatedBitFlags<F>> for Value<'a>
+where
+    F: RawBitFlags,
+    F::Type: Into<Value<'a>>,
+{
+    fn from(flags: TruncatedBitFlags<F>) -> Self {
+        flags.0.bits().into()
+    }
+}
+
+impl<'a, F> TryFrom<Value<'a>> for TruncatedBitFlags<F>
+where
+    F: RawBitFlags,
+    F::Type: TryFrom<Value<'a>, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        F::Type::try_from(value).map(Self::from_bits)
+    }
+}
+
+impl<F> TryFrom<OwnedValue> for TruncatedBitFlags<F>
+where
+    F: RawBitFlags,
+    F::Type: TryFrom<Value<'static>, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: OwnedValue) -> Result<Self, Self::Error> {
+        Self::try_from(Value::from(value))
+    }
+}
@@ -0,0 +1,206 @@
+use std::{convert::TryFrom, mem::size_of};
+
+use crate::Value;
+
+/// Types that a numeric [`Value`] can be coerced to, regardless of its exact type.
+///
+/// Implemented for the numeric types of the D-Bus type system: `u8`, `i16`, `u16`, `i32`, `u32`,
+/// `i64`, `u64` and `f64`. Values of other types (strings and booleans included) are never
+/// coerced. See [`Value::coerce`] and [`Value::coerce_lossy`] for the conversions done.
+///
+/// [`Value`]: enum.Value.html
+/// [`Value::coerce`]: enum.Value.html#method.coerce
+/// [`Value::coerce_lossy`]: enum.Value.html#method.coerce_lossy
+pub trait Coerce: Sized {
+    /// Convert `value` without losing information, if possible.
+    fn coerce_from(value: &Value<'_>) -> Option<Self>;
+
+    /// Convert `value` the same way an `as` cast does.
+    fn coerce_lossy_from(value: &Value<'_>) -> Option<Self>;
+}
+
+// A numeric value, with the size in bits of its type for integers.
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Unsigned(u64, usize),
+    Signed(i64, usize),
+    Float(f64),
+}
+
+impl Number {
+    fn of(value: &Value<'_>) -> Option<Self> {
+        let number = match value {
+            Value::U8(v) => Number::Unsigned(u64::from(*v), 8),
+            Value::U16(v) => Number::Unsigned(u64::from(*v), 16),
+            Value::U32(v) => Number::Unsigned(u64::from(*v), 32),
+            Value::U64(v) => Number::Unsigned(*v, 64),
+            Value::I16(v) => Number::Signed(i64::from(*v), 16),
+            Value::I32(v) => Number::Signed(i64::from(*v), 32),
+            Value::I64(v) => Number::Signed(*v, 64),
+            Value::F64(v) => Number::Float(*v),
+            Value::Value(v) => return Number::of(v),
+            _ => return None,
+        };
+
+        Some(number)
+    }
+}
+
+macro_rules! coerce_integer {
+    ($($to:ty)*) => {
+        $(
+            impl Coerce for $to {
+                fn coerce_from(value: &Value<'_>) -> Option<Self> {
+                    // The type of the value must not be wider, so a value doesn't convert only
+                    // when it happens to be small enough.
+                    let bits = size_of::<$to>() * 8;
+                    match Number::of(value)? {
+                        Number::Unsigned(v, b) if b <= bits => Self::try_from(v).ok(),
+                        Number::Signed(v, b) if b <= bits => Self::try_from(v).ok(),
+                        _ => None,
+                    }
+                }
+
+                // The casts from the same type are no-ops.
+                #[allow(clippy::unnecessary_cast)]
+                fn coerce_lossy_from(value: &Value<'_>) -> Option<Self> {
+                    match Number::of(value)? {
+                        Number::Unsigned(v, _) => Some(v as Self),
+                        Number::Signed(v, _) => Some(v as Self),
+                        Number::Float(v) => Some(v as Self),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+coerce_integer!(u8 i16 u16 i32 u32 i64 u64);
+
+impl Coerce for f64 {
+    fn coerce_from(value: &Value<'_>) -> Option<Self> {
+        // All the integers of up to 53 bits are exactly representable.
+        match Number::of(value)? {
+            Number::Unsigned(v, b) if b <= 32 => Some(v as f64),
+            Number::Signed(v, b) if b <= 32 => Some(v as f64),
+            Number::Float(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn coerce_lossy_from(value: &Value<'_>) -> Option<Self> {
+        match Number::of(value)? {
+            Number::Unsigned(v, _) => Some(v as f64),
+            Number::Signed(v, _) => Some(v as f64),
+            Number::Float(v) => Some(v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use crate::{Error, OwnedValue, Value};
+
+    // The value coerced to `u8`, `i16`, `u16`, `i32`, `u32`, `i64`, `u64` and `f64`, as `f64`.
+    #[allow(clippy::unnecessary_cast)]
+    fn coerced(value: &Value<'_>, lossy: bool) -> Vec<Option<f64>> {
+        macro_rules! coerce {
+            ($($to:ty)*) => {
+                vec![$({
+                    let coerced = if lossy {
+                        value.coerce_lossy::<$to>()
+                    } else {
+                        value.coerce::<$to>()
+                    };
+
+                    coerced.ok().map(|v| v as f64)
+                },)*]
+            };
+        }
+
+        coerce!(u8 i16 u16 i32 u32 i64 u64 f64)
+    }
+
+    #[test]
+    fn coerce() {
+        let values = [
+            Value::U8(200),
+            Value::U16(5),
+            Value::U16(60000),
+            Value::I16(5),
+            Value::I16(-5),
+            Value::U32(5),
+            Value::U32(4_000_000_000),
+            Value::I32(-5),
+            Value::U64(5),
+            Value::U64(u64::MAX),
+            Value::I64(-5),
+            Value::F64(1.5),
+            Value::new(Value::U32(5)),
+            Value::Bool(true),
+            Value::from("5"),
+        ];
+        // Whether each value coerces to `u8`, `i16`, `u16`, `i32`, `u32`, `i64`, `u64` and `f64`.
+        #[rustfmt::skip]
+        let table = [
+            [1, 1, 1, 1, 1, 1, 1, 1],
+            [0, 1, 1, 1, 1, 1, 1, 1],
+            [0, 0, 1, 1, 1, 1, 1, 1],
+            [0, 1, 1, 1, 1, 1, 1, 1],
+            [0, 1, 0, 1, 0, 1, 0, 1],
+            [0, 0, 0, 1, 1, 1, 1, 1],
+            [0, 0, 0, 0, 1, 1, 1, 1],
+            [0, 0, 0, 1, 0, 1, 0, 1],
+            [0, 0, 0, 0, 0, 1, 1, 0],
+            [0, 0, 0, 0, 0, 0, 1, 0],
+            [0, 0, 0, 0, 0, 1, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 1],
+            [0, 0, 0, 1, 1, 1, 1, 1],
+            [0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0],
+        ];
+
+        for (value, expected) in values.iter().zip(table.iter()) {
+            let exact = value.coerce_lossy::<f64>().ok();
+            let expected: Vec<_> = expected
+                .iter()
+                .map(|e| if *e == 1 { exact } else { None })
+                .collect();
+            assert_eq!(coerced(value, false), expected, "{:?}", value);
+
+            // Any number converts lossily.
+            let numeric = exact.is_some();
+            for coerced in coerced(value, true) {
+                assert_eq!(coerced.is_some(), numeric, "{:?}", value);
+            }
+        }
+
+        // `as` casts.
+        assert_eq!(
+            Value::U32(4_000_000_000).coerce_lossy::<i32>(),
+            Ok(-294_967_296)
+        );
+        assert_eq!(Value::I16(-5).coerce_lossy::<u16>(), Ok(65531));
+        assert_eq!(Value::U16(257).coerce_lossy::<u8>(), Ok(1));
+        assert_eq!(Value::F64(-1.5).coerce_lossy::<u8>(), Ok(0));
+        assert_eq!(Value::F64(300.7).coerce_lossy::<u8>(), Ok(255));
+        assert_eq!(Value::F64(-2.9).coerce_lossy::<i64>(), Ok(-2));
+        assert_eq!(Value::F64(f64::NAN).coerce_lossy::<i32>(), Ok(0));
+        assert_eq!(
+            Value::U64(u64::MAX).coerce_lossy::<f64>(),
+            Ok(u64::MAX as f64)
+        );
+        assert_eq!(
+            Value::Bool(true).coerce_lossy::<u8>(),
+            Err(Error::IncorrectType)
+        );
+
+        // `TryFrom` stays strict.
+        assert_eq!(u64::try_from(Value::U32(5)), Err(Error::IncorrectType));
+
+        let owned = OwnedValue::from(Value::U16(42));
+        assert_eq!(owned.coerce::<u64>(), Ok(42));
+    }
+}
@@ -0,0 +1,96 @@
+use std::{fmt, slice, str};
+
+/// The maximum length of a signature, as per the D-Bus specification.
+const MAX_LEN: usize = 255;
+
+/// A signature assembled at compile time, from the signatures of other types.
+///
+/// This is how the [`StaticType::SIGNATURE_STR`] of container types, such as generic structs, is
+/// built from those of the types they contain, in a const context. A signature longer than the 255
+/// bytes allowed by the D-Bus specification fails to compile.
+///
+/// The string must be taken from a reference to the builder, so that the builder lives as long as
+/// the constant:
+///
+/// ```
+/// use zvariant::{ConstSignature, Signature, StaticType, Type};
+///
+/// struct Pair<T>(T, T);
+///
+/// impl<T: Type> Type for Pair<T> {
+///     fn signature() -> Signature<'static> {
+///         Signature::from_string_unchecked(format!("({}{})", T::signature(), T::signature()))
+///     }
+/// }
+///
+/// impl<T: StaticType> StaticType for Pair<T> {
+///     const SIGNATURE_STR: &'static str = (&ConstSignature::new()
+///         .push("(")
+///         .push(T::SIGNATURE_STR)
+///         .push(T::SIGNATURE_STR)
+///         .push(")"))
+///         .as_str();
+/// }
+///
+/// assert_eq!(<Pair<u32>>::SIGNATURE_STR, "(uu)");
+/// assert_eq!(<Pair<Vec<u8>>>::signature(), "(ayay)");
+/// ```
+///
+/// [`StaticType::SIGNATURE_STR`]: trait.StaticType.html#associatedconstant.SIGNATURE_STR
+#[derive(Clone, Copy)]
+pub struct ConstSignature {
+    bytes: [u8; MAX_LEN],
+    len: usize,
+}
+
+impl ConstSignature {
+    /// An empty signature.
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; MAX_LEN],
+            len: 0,
+        }
+    }
+
+    /// Append `signature`.
+    pub const fn push(self, signature: &str) -> Self {
+        self.push_repeated(signature, 1)
+    }
+
+    /// Append `signature`, `count` times.
+    pub const fn push_repeated(mut self, signature: &str, count: usize) -> Self {
+        let bytes = signature.as_bytes();
+        let mut n = 0;
+        while n < count {
+            let mut i = 0;
+            while i < bytes.len() {
+                self.bytes[self.len] = bytes[i];
+                self.len += 1;
+                i += 1;
+            }
+            n += 1;
+        }
+
+        self
+    }
+
+    /// The signature assembled so far.
+    pub const fn as_str(&self) -> &str {
+        // SAFETY: Only whole strings are appended, so the bytes up to `len` are valid UTF-8.
+        unsafe { str::from_utf8_unchecked(slice::from_raw_parts(self.bytes.as_ptr(), self.len)) }
+    }
+}
+
+impl Default for ConstSignature {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ConstSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ConstSignature")
+            .field(&self.as_str())
+            .finish()
+    }
+}
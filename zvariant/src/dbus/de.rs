@@ -6,8 +6,10 @@ use static_assertions::assert_impl_all;
 use std::{marker::PhantomData, os::unix::io::RawFd, str};
 
 use crate::{
-    de::ValueParseStage, signature_parser::SignatureParser, utils::*, Basic, EncodingContext,
-    EncodingFormat, Error, Fd, ObjectPath, Result, Signature,
+    de::{ValidatedStrVisitor, ValueParseStage},
+    signature_parser::SignatureParser,
+    utils::*,
+    Basic, EncodingContext, EncodingFormat, Error, Fd, ObjectPath, PathSegment, Result, Signature,
 };
 
 /// Our D-Bus deserialization implementation.
@@ -35,6 +37,7 @@ where
             sig_parser,
             bytes,
             fds,
+            budget: crate::de::Budget::for_context(ctxt),
             pos: 0,
             b: PhantomData,
         })
@@ -181,7 +184,8 @@ where
     where
         V: Visitor<'de>,
     {
-        let len = match self.0.sig_parser.next_char() {
+        let c = self.0.sig_parser.next_char();
+        let len = match c {
             Signature::SIGNATURE_CHAR | VARIANT_SIGNATURE_CHAR => {
                 let len_slice = self.0.next_slice(1)?;
 
@@ -197,9 +201,9 @@ where
             c => {
                 let expected = format!(
                     "`{}`, `{}`, `{}` or `{}`",
-                    <&str>::SIGNATURE_STR,
-                    Signature::SIGNATURE_STR,
-                    ObjectPath::SIGNATURE_STR,
+                    <&str>::SIGNATURE_CHAR,
+                    Signature::SIGNATURE_CHAR,
+                    ObjectPath::SIGNATURE_CHAR,
                     VARIANT_SIGNATURE_CHAR,
                 );
                 return Err(de::Error::invalid_type(
@@ -208,6 +212,7 @@ where
                 ));
             }
         };
+        self.0.check_declared_len(len)?;
         let slice = self.0.next_slice(len)?;
         if slice.contains(&0) {
             return Err(serde::de::Error::invalid_value(
@@ -215,8 +220,10 @@ where
                 &"D-Bus string type must not contain interior null bytes",
             ));
         }
-        self.0.pos += 1; // skip trailing null byte
+        // skip trailing null byte
+        self.0.next_slice(1)?;
         let s = str::from_utf8(slice).map_err(Error::Utf8)?;
+        self.0.validate_str(c, s)?;
         self.0.sig_parser.skip_char()?;
 
         visitor.visit_borrowed_str(s)
@@ -243,10 +250,14 @@ where
         visitor.visit_unit()
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if self.0.is_validated_str(name) {
+            return self.deserialize_str(ValidatedStrVisitor(visitor));
+        }
+
         visitor.visit_newtype_struct(self)
     }
 
@@ -278,7 +289,7 @@ where
 
                 self.0.sig_parser.skip_char()?;
 
-                visitor.visit_seq(StructureDeserializer { de: self })
+                visitor.visit_seq(StructureDeserializer { de: self, index: 0 })
             }
             c => Err(de::Error::invalid_type(
                 de::Unexpected::Char(c),
@@ -330,6 +341,10 @@ struct ArrayDeserializer<'d, 'de, 'sig, 'f, B> {
     element_alignment: usize,
     // where value signature starts
     element_signature_len: usize,
+    // index of the next element
+    index: usize,
+    // start of the last element (the key, for dict entries)
+    element_start: usize,
 }
 
 impl<'d, 'de, 'sig, 'f, B> ArrayDeserializer<'d, 'de, 'sig, 'f, B>
@@ -340,6 +355,9 @@ where
         de.0.parse_padding(ARRAY_ALIGNMENT_DBUS)?;
 
         let len = B::read_u32(de.0.next_slice(4)?) as usize;
+        if len > MAX_ARRAY_LEN_DBUS {
+            return Err(Error::ArrayTooLong(len));
+        }
         let element_signature = de.0.sig_parser.next_signature()?;
         let element_alignment = alignment_for_signature(&element_signature, EncodingFormat::DBus);
         let mut element_signature_len = element_signature.len();
@@ -347,6 +365,7 @@ where
         // D-Bus requires padding for the first element even when there is no first element
         // (i-e empty array) so we parse padding already.
         de.0.parse_padding(element_alignment)?;
+        de.0.check_declared_len(len)?;
         let start = de.0.pos;
 
         if de.0.sig_parser.next_char() == DICT_ENTRY_SIG_START_CHAR {
@@ -360,6 +379,8 @@ where
             start,
             element_alignment,
             element_signature_len,
+            index: 0,
+            element_start: start,
         })
     }
 
@@ -367,13 +388,18 @@ where
     where
         T: DeserializeSeed<'de>,
     {
-        let ctxt = EncodingContext::new_dbus(self.de.0.ctxt.position() + self.de.0.pos);
+        let ctxt = self
+            .de
+            .0
+            .ctxt
+            .with_position(self.de.0.ctxt.position() + self.de.0.pos);
 
         let mut de = Deserializer::<B>(crate::DeserializerCommon {
             ctxt,
             sig_parser,
             bytes: &self.de.0.bytes[self.de.0.pos..],
             fds: self.de.0.fds,
+            budget: self.de.0.budget.clone(),
             pos: 0,
             b: PhantomData,
         });
@@ -408,8 +434,14 @@ where
         }
 
         self.de.0.parse_padding(self.element_alignment)?;
-
-        self.next(seed, sig_parser).map(Some)
+        self.de.0.budget.charge()?;
+        let index = self.index;
+        self.index += 1;
+        self.element_start = self.de.0.pos;
+
+        self.next(seed, sig_parser)
+            .map(Some)
+            .map_err(|e| e.in_path(PathSegment::Element(index)))
     }
 
     fn done(&self) -> bool {
@@ -471,13 +503,21 @@ where
         let mut sig_parser = self.0.de.0.sig_parser.clone();
         // Skip key signature (always 1 char)
         sig_parser.skip_char()?;
-        self.0.next(seed, sig_parser)
+        let key_end = self.0.de.0.pos;
+
+        self.0.next(seed, sig_parser).map_err(|e| {
+            let de = &self.0.de.0;
+
+            e.in_path(de.key_segment(self.0.element_start, key_end, self.0.index - 1))
+        })
     }
 }
 
 #[derive(Debug)]
 struct StructureDeserializer<'d, 'de, 'sig, 'f, B> {
     de: &'d mut Deserializer<'de, 'sig, 'f, B>,
+    // index of the next field
+    index: usize,
 }
 
 impl<'d, 'de, 'sig, 'f, B> SeqAccess<'de> for StructureDeserializer<'d, 'de, 'sig, 'f, B>
@@ -490,7 +530,12 @@ where
     where
         T: DeserializeSeed<'de>,
     {
-        let v = seed.deserialize(&mut *self.de).map(Some);
+        let index = self.index;
+        self.index += 1;
+        let v = seed
+            .deserialize(&mut *self.de)
+            .map(Some)
+            .map_err(|e| e.in_path(PathSegment::Field(index)));
 
         if self.de.0.sig_parser.next_char() == STRUCT_SIG_END_CHAR {
             // Last item in the struct
@@ -553,20 +598,26 @@ where
                 let signature = Signature::try_from(slice)?;
                 let sig_parser = SignatureParser::new(signature);
 
-                let ctxt = EncodingContext::new(
-                    EncodingFormat::DBus,
-                    self.de.0.ctxt.position() + value_start,
-                );
+                let ctxt = self
+                    .de
+                    .0
+                    .ctxt
+                    .with_position(self.de.0.ctxt.position() + value_start);
+                self.de.0.budget.charge()?;
                 let mut de = Deserializer::<B>(crate::DeserializerCommon {
                     ctxt,
                     sig_parser,
                     bytes: &self.de.0.bytes[value_start..],
                     fds: self.de.0.fds,
+                    budget: self.de.0.budget.clone(),
                     pos: 0,
                     b: PhantomData,
                 });
 
-                let v = seed.deserialize(&mut de).map(Some);
+                let v = seed
+                    .deserialize(&mut de)
+                    .map(Some)
+                    .map_err(|e| e.in_path(PathSegment::Variant));
                 self.de.0.pos += de.0.pos;
 
                 v
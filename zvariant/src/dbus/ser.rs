@@ -10,7 +10,7 @@ use std::{
 
 use crate::{
     signature_parser::SignatureParser, utils::*, Basic, EncodingContext, EncodingFormat, Error,
-    ObjectPath, Result, Signature,
+    ObjectPath, PathSegment, Result, Signature,
 };
 
 /// Our D-Bus serialization implementation.
@@ -138,9 +138,9 @@ where
             _ => {
                 let expected = format!(
                     "`{}`, `{}`, `{}` or `{}`",
-                    <&str>::SIGNATURE_STR,
-                    Signature::SIGNATURE_STR,
-                    ObjectPath::SIGNATURE_STR,
+                    <&str>::SIGNATURE_CHAR,
+                    Signature::SIGNATURE_CHAR,
+                    ObjectPath::SIGNATURE_CHAR,
                     VARIANT_SIGNATURE_CHAR,
                 );
                 return Err(serde::de::Error::invalid_type(
@@ -237,6 +237,7 @@ where
             element_alignment,
             element_signature_len,
             first_padding,
+            index: 0,
         })
     }
 
@@ -298,6 +299,7 @@ where
         Ok(StructSerializer {
             ser: self,
             end_parens,
+            index: 0,
         })
     }
 
@@ -324,6 +326,8 @@ pub struct SeqSerializer<'ser, 'sig, 'b, B, W> {
     element_signature_len: usize,
     // First element's padding
     first_padding: usize,
+    // index of the next element
+    index: usize,
 }
 
 impl<'ser, 'sig, 'b, B, W> SeqSerializer<'ser, 'sig, 'b, B, W>
@@ -373,8 +377,12 @@ where
         // disposable clone.
         let sig_parser = self.ser.0.sig_parser.clone();
         self.ser.0.sig_parser = sig_parser.clone();
+        let index = self.index;
+        self.index += 1;
 
-        value.serialize(&mut *self.ser)?;
+        value
+            .serialize(&mut *self.ser)
+            .map_err(|e| e.in_path(PathSegment::Element(index)))?;
         self.ser.0.sig_parser = sig_parser;
 
         Ok(())
@@ -389,6 +397,8 @@ where
 pub struct StructSerializer<'ser, 'sig, 'b, B, W> {
     ser: &'b mut Serializer<'ser, 'sig, B, W>,
     end_parens: bool,
+    // index of the next field
+    index: usize,
 }
 
 impl<'ser, 'sig, 'b, B, W> StructSerializer<'ser, 'sig, 'b, B, W>
@@ -423,13 +433,23 @@ where
                     value_sign: None,
                     b: PhantomData,
                 });
-                value.serialize(&mut ser)?;
+                value
+                    .serialize(&mut ser)
+                    .map_err(|e| e.in_path(PathSegment::Variant))?;
                 self.ser.0.bytes_written = ser.0.bytes_written;
                 self.ser.0.fds.extend(fds.iter());
 
                 Ok(())
             }
-            _ => value.serialize(&mut *self.ser),
+            Some("zvariant::Value::Signature") => value.serialize(&mut *self.ser),
+            _ => {
+                let index = self.index;
+                self.index += 1;
+
+                value
+                    .serialize(&mut *self.ser)
+                    .map_err(|e| e.in_path(PathSegment::Field(index)))
+            }
         }
     }
 
@@ -490,13 +510,31 @@ where
 
         // skip `{`
         self.ser.0.sig_parser.skip_char()?;
+        let index = self.index;
+        self.index += 1;
 
-        key.serialize(&mut *self.ser)?;
+        key.serialize(&mut *self.ser)
+            .map_err(|e| e.in_path(PathSegment::Element(index)))?;
         self.ser.0.sig_parser = sig_parser;
 
         Ok(())
     }
 
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<()>
+    where
+        K: ?Sized + Serialize,
+        V: ?Sized + Serialize,
+    {
+        let entry_parser = self.ser.0.sig_parser.clone();
+        self.serialize_key(key)?;
+
+        self.serialize_value(value).map_err(|e| {
+            let segment = self.ser.0.key_segment(entry_parser, key, self.index - 1);
+
+            e.in_path(segment)
+        })
+    }
+
     fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
@@ -4,13 +4,23 @@ use serde::{
 };
 use static_assertions::assert_impl_all;
 
-use std::{marker::PhantomData, os::unix::io::RawFd, str};
+use std::{
+    convert::TryFrom,
+    marker::PhantomData,
+    os::unix::io::RawFd,
+    str,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 #[cfg(feature = "gvariant")]
 use crate::gvariant::Deserializer as GVDeserializer;
 use crate::{
-    dbus::Deserializer as DBusDeserializer, signature_parser::SignatureParser, utils::*, Basic,
-    EncodingContext, EncodingFormat, Error, Fd, ObjectPath, Result, Signature, Type,
+    dbus::Deserializer as DBusDeserializer, object_path::OBJECT_PATH_NEWTYPE,
+    signature::SIGNATURE_NEWTYPE, signature_parser::SignatureParser, utils::*, Basic,
+    EncodingContext, EncodingFormat, Error, Fd, ObjectPath, PathSegment, Result, Signature, Type,
 };
 
 /// Deserialize `T` from a given slice of bytes, containing file descriptor indices.
@@ -174,6 +184,7 @@ pub(crate) struct DeserializerCommon<'de, 'sig, 'f, B> {
     pub(crate) ctxt: EncodingContext<B>,
     pub(crate) bytes: &'de [u8],
     pub(crate) fds: Option<&'f [RawFd]>,
+    pub(crate) budget: Budget,
     pub(crate) pos: usize,
 
     pub(crate) sig_parser: SignatureParser<'sig>,
@@ -181,6 +192,36 @@ pub(crate) struct DeserializerCommon<'de, 'sig, 'f, B> {
     pub(crate) b: PhantomData<B>,
 }
 
+// What is left of the value budget of a context, if any. The deserializers of nested values share
+// it with the deserializer of the data as a whole.
+#[derive(Debug, Clone)]
+pub(crate) struct Budget(Option<(usize, Arc<AtomicUsize>)>);
+
+impl Budget {
+    pub(crate) fn for_context<B>(ctxt: EncodingContext<B>) -> Self
+    where
+        B: byteorder::ByteOrder,
+    {
+        Budget(
+            ctxt.value_budget()
+                .map(|budget| (budget, Arc::new(AtomicUsize::new(budget)))),
+        )
+    }
+
+    // Take a value off the budget, failing if it's spent already.
+    pub(crate) fn charge(&self) -> Result<()> {
+        match &self.0 {
+            Some((budget, left)) => left
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                    left.checked_sub(1)
+                })
+                .map(drop)
+                .map_err(|_| Error::BudgetExceeded(*budget)),
+            None => Ok(()),
+        }
+    }
+}
+
 /// Our deserialization implementation.
 ///
 /// Using this deserializer involves an redirection to the actual deserializer. It's best
@@ -227,6 +268,30 @@ where
             .ok_or(Error::UnknownFd)
     }
 
+    // Check `s`, read for the signature character `c`, against the grammar of its type, unless the
+    // data is trusted.
+    pub(crate) fn validate_str(&self, c: char, s: &str) -> Result<()> {
+        if self.ctxt.is_trusted() {
+            return Ok(());
+        }
+
+        match c {
+            ObjectPath::SIGNATURE_CHAR => ObjectPath::try_from(s).map(drop),
+            Signature::SIGNATURE_CHAR => Signature::try_from(s).map(drop),
+            _ => Ok(()),
+        }
+    }
+
+    // Whether the newtype struct `name` is one of ours, made of a string that `deserialize_str`
+    // validates already, for the signature at hand.
+    pub(crate) fn is_validated_str(&self, name: &str) -> bool {
+        match name {
+            OBJECT_PATH_NEWTYPE => self.sig_parser.next_char() == ObjectPath::SIGNATURE_CHAR,
+            SIGNATURE_NEWTYPE => self.sig_parser.next_char() == Signature::SIGNATURE_CHAR,
+            _ => false,
+        }
+    }
+
     pub fn parse_padding(&mut self, alignment: usize) -> Result<usize> {
         let padding = padding_for_n_bytes(self.abs_pos(), alignment);
         if padding > 0 {
@@ -259,6 +324,16 @@ where
         Ok(())
     }
 
+    // Check the length `len` some data declares against the bytes left, before relying on it.
+    pub(crate) fn check_declared_len(&self, len: usize) -> Result<()> {
+        let left = self.bytes.len() - self.pos;
+        if len > left {
+            return Err(Error::LengthExceedsData(len, left));
+        }
+
+        Ok(())
+    }
+
     pub fn next_slice(&mut self, len: usize) -> Result<&'de [u8]> {
         if self.pos + len > self.bytes.len() {
             return Err(serde::de::Error::invalid_length(
@@ -285,6 +360,20 @@ where
     pub fn abs_pos(&self) -> usize {
         self.ctxt.position() + self.pos
     }
+
+    // The path segment for the value of the `index`th entry of the dictionary being deserialized,
+    // with its key encoded in `bytes[key_start..key_end]`.
+    pub fn key_segment(&self, key_start: usize, key_end: usize, index: usize) -> PathSegment {
+        let ctxt = EncodingContext::new(self.ctxt.format(), self.ctxt.position() + key_start);
+
+        PathSegment::for_encoded_key(
+            &self.bytes[key_start..key_end],
+            self.fds,
+            ctxt,
+            self.sig_parser.next_char(),
+            index,
+        )
+    }
 }
 
 macro_rules! deserialize_method {
@@ -392,6 +481,29 @@ where
     }
 }
 
+// Hands a string over to the visitor of an `ObjectPath` or a `Signature` as bytes, which these
+// visitors take as validated. Only the deserializers that validate the string themselves use it,
+// after their `is_validated_str` said so.
+pub(crate) struct ValidatedStrVisitor<V>(pub(crate) V);
+
+impl<'de, V> Visitor<'de> for ValidatedStrVisitor<V>
+where
+    V: Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.expecting(formatter)
+    }
+
+    fn visit_borrowed_str<E>(self, value: &'de str) -> std::result::Result<V::Value, E>
+    where
+        E: de::Error,
+    {
+        self.0.visit_borrowed_bytes(value.as_bytes())
+    }
+}
+
 pub(crate) trait GetDeserializeCommon<'de, 'sig, 'f, B>
 where
     B: byteorder::ByteOrder,
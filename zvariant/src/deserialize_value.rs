@@ -4,7 +4,7 @@ use std::marker::PhantomData;
 use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
 use static_assertions::assert_impl_all;
 
-use crate::{Signature, Type, Value};
+use crate::{Signature, StaticType, Type, Value};
 
 /// A wrapper to deserialize a value to `T: Type + Deserialize`.
 ///
@@ -80,3 +80,7 @@ impl<'de, T: Type + Deserialize<'de>> Type for DeserializeValue<'de, T> {
         Value::signature()
     }
 }
+
+impl<'de, T: Type + Deserialize<'de>> StaticType for DeserializeValue<'de, T> {
+    const SIGNATURE_STR: &'static str = <Value<'static> as StaticType>::SIGNATURE_STR;
+}
@@ -112,6 +112,14 @@ impl<'k, 'v> Dict<'k, 'v> {
         &self.signature
     }
 
+    // The size of the entries, for `Value::serialized_size_hint`.
+    pub(crate) fn entries_size_hint(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|e| e.key.serialized_size_hint() + e.value.serialized_size_hint())
+            .sum()
+    }
+
     pub(crate) fn to_owned(&self) -> Dict<'static, 'static> {
         Dict {
             key_signature: self.key_signature.to_owned(),
@@ -122,6 +130,24 @@ impl<'k, 'v> Dict<'k, 'v> {
     }
 
     /// Create a new empty `Dict`, given the complete signature.
+    pub(crate) fn key_signature(&self) -> &Signature<'k> {
+        &self.key_signature
+    }
+
+    pub(crate) fn value_signature(&self) -> &Signature<'v> {
+        &self.value_signature
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&Value<'k>, &Value<'v>)> {
+        self.entries.iter().map(|entry| (&entry.key, &entry.value))
+    }
+
+    pub(crate) fn into_entries(self) -> impl Iterator<Item = (Value<'k>, Value<'v>)> {
+        self.entries
+            .into_iter()
+            .map(|entry| (entry.key, entry.value))
+    }
+
     pub(crate) fn new_full_signature<'s: 'k + 'v>(signature: Signature<'s>) -> Self {
         let key_signature = signature.slice(2..3);
         let value_signature = signature.slice(3..signature.len() - 1);
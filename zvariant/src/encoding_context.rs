@@ -58,6 +58,34 @@ impl std::fmt::Display for EncodingFormat {
 /// assert_eq!(decoded, "World");
 /// ```
 ///
+/// # Embedding values in other data
+///
+/// The padding required by the alignment of each value is computed from its position in the
+/// entire message, in both D-Bus and GVariant formats. When encoding a value that is to be embedded
+/// at an arbitrary offset inside some other container, pass that offset as the position: the
+/// encoding then starts with the padding required at that offset. The same position is then
+/// needed for decoding, from the slice of the container that starts at that offset, so there is no
+/// need to copy the value to a buffer of its own:
+///
+/// ```
+/// use byteorder::LE;
+///
+/// use zvariant::EncodingContext as Context;
+/// use zvariant::{from_slice, to_bytes};
+///
+/// // Some header of our own protocol, followed by a D-Bus encoded value.
+/// let mut data = b"HDR".to_vec();
+/// let ctxt = Context::<LE>::new_dbus(0).with_position(data.len());
+/// data.extend(to_bytes(ctxt, &(42u64, "answer")).unwrap());
+///
+/// let decoded: (u64, &str) = from_slice(&data[3..], ctxt).unwrap();
+/// assert_eq!(decoded, (42, "answer"));
+/// ```
+///
+/// Since the largest alignment is 8 bytes, the encoding of a value is the same for any position
+/// that is a multiple of 8. An encoded value can therefore be moved by a multiple of 8 bytes
+/// without encoding it again, but not by any other amount.
+///
 /// [serialization and deserialization]: index.html#functions
 /// [ByteOrder]: https://docs.rs/byteorder/1.3.4/byteorder/trait.ByteOrder.html
 /// [specify]: #method.new
@@ -65,6 +93,9 @@ impl std::fmt::Display for EncodingFormat {
 pub struct EncodingContext<B> {
     format: EncodingFormat,
     position: usize,
+    lenient: bool,
+    trusted: bool,
+    value_budget: Option<usize>,
 
     b: PhantomData<B>,
 }
@@ -80,6 +111,9 @@ where
         Self {
             format,
             position,
+            lenient: false,
+            trusted: false,
+            value_budget: None,
             b: PhantomData,
         }
     }
@@ -110,4 +144,162 @@ where
     pub fn position(self) -> usize {
         self.position
     }
+
+    /// A context of the same format and byte order, for a value at byte `position` instead.
+    pub fn with_position(self, position: usize) -> Self {
+        Self { position, ..self }
+    }
+
+    /// The same context, tolerating GVariant data that isn't in normal form, as GLib does.
+    ///
+    /// GLib never fails to read GVariant data: the parts of the data that aren't in [normal form]
+    /// read as the default value of their type instead. Data written by GLib itself is always in
+    /// normal form but that's not the case of data from untrusted sources, e.g. dconf databases,
+    /// which a lenient context decodes into the same values as GLib does:
+    ///
+    /// * Out of bounds framing offsets make for a default value of the element or field they frame.
+    ///   An array whose own framing offsets can't be found is empty.
+    /// * Fixed-size data that is too short reads as the default value of its type, while the extra
+    ///   bytes of data that is too long are ignored. An array of fixed-size elements ending in the
+    ///   middle of an element is empty.
+    /// * Strings that aren't nul-terminated, have interior nul bytes, or aren't valid UTF-8 read as
+    ///   empty strings. Invalid object paths read as `/` and invalid signatures as empty ones.
+    /// * A maybe of fixed-size type with too little data is `Nothing` and the trailing byte of maybes
+    ///   of other types is ignored.
+    /// * Padding bytes are ignored.
+    ///
+    /// The default values are `0`, `false`, empty strings, arrays and dictionaries, `Nothing` and
+    /// structures of the default values of their fields. File descriptors and variants have no
+    /// default value, so data that is missing for these is still an error, as is a variant
+    /// without a valid signature.
+    ///
+    /// This only affects decoding, and only of GVariant data.
+    ///
+    /// ```
+    /// use byteorder::LE;
+    ///
+    /// use zvariant::EncodingContext as Context;
+    /// use zvariant::from_slice;
+    ///
+    /// // A `(su)` structure with a string that is not nul-terminated.
+    /// let data = b"hi!\0\x07\0\0\0\x03";
+    /// let ctxt = Context::<LE>::new_gvariant(0);
+    /// let res: zvariant::Result<(&str, u32)> = from_slice(data, ctxt);
+    /// assert!(res.is_err());
+    ///
+    /// let decoded: (&str, u32) = from_slice(data, ctxt.gvariant_lenient()).unwrap();
+    /// assert_eq!(decoded, ("", 7));
+    /// ```
+    ///
+    /// [normal form]: https://people.gnome.org/~desrt/gvariant-serialisation.pdf
+    #[cfg(feature = "gvariant")]
+    pub fn gvariant_lenient(self) -> Self {
+        Self {
+            lenient: true,
+            ..self
+        }
+    }
+
+    /// Whether GVariant data that isn't in normal form is tolerated.
+    ///
+    /// See [`gvariant_lenient`] for details.
+    ///
+    /// [`gvariant_lenient`]: #method.gvariant_lenient
+    pub fn is_lenient(self) -> bool {
+        self.lenient
+    }
+
+    /// The same context, for data that is known to be valid already.
+    ///
+    /// The strings decoded as object paths (`o`) or signatures (`g`) are checked against their
+    /// grammar, whatever type they are decoded into: a `Vec<String>` can only be decoded from an
+    /// array of valid paths, just as a `Vec<OwnedObjectPath>` can. This checking is redundant for
+    /// data that was validated before, e.g. messages relayed by a bus daemon, which validates all
+    /// the messages it relays. A trusted context skips it.
+    ///
+    /// Invalid data decoded through a trusted context makes for invalid [`ObjectPath`] and
+    /// [`Signature`] values, so only use it for data of a trusted source.
+    ///
+    /// ```
+    /// use byteorder::LE;
+    ///
+    /// use zvariant::EncodingContext as Context;
+    /// use std::convert::TryFrom;
+    /// use zvariant::{from_slice_for_signature, to_bytes, ObjectPath, Signature};
+    ///
+    /// let ctxt = Context::<LE>::new_dbus(0);
+    /// // Not a valid object path, but it's encoded the same as a string.
+    /// let encoded = to_bytes(ctxt, &vec!["not/a/path"]).unwrap();
+    /// let signature = Signature::try_from("ao").unwrap();
+    /// let res: zvariant::Result<Vec<String>> =
+    ///     from_slice_for_signature(&encoded, ctxt, &signature);
+    /// assert!(res.is_err());
+    ///
+    /// let decoded: Vec<ObjectPath<'_>> =
+    ///     from_slice_for_signature(&encoded, ctxt.trusted(), &signature).unwrap();
+    /// assert_eq!(decoded[0].as_str(), "not/a/path");
+    /// ```
+    ///
+    /// [`ObjectPath`]: struct.ObjectPath.html
+    /// [`Signature`]: struct.Signature.html
+    pub fn trusted(self) -> Self {
+        Self {
+            trusted: true,
+            ..self
+        }
+    }
+
+    /// Whether the object paths and signatures decoded are trusted, rather than checked.
+    ///
+    /// See [`trusted`] for details.
+    ///
+    /// [`trusted`]: #method.trusted
+    pub fn is_trusted(self) -> bool {
+        self.trusted
+    }
+
+    /// The same context, limiting the number of values decoded to `budget`.
+    ///
+    /// The length of arrays is always checked against the data before decoding any of its
+    /// elements, and D-Bus arrays can't be longer than the 64 MiB the specification allows, but
+    /// that still leaves a small message of untrusted data decode into a lot of values, e.g. an
+    /// array of empty arrays, each of which the decoded type may allocate for. The budget bounds
+    /// these allocations: each element of an array (or entry of a dictionary) and the value of each
+    /// variant counts as one value, for the data as a whole, however deep the values are nested.
+    /// Decoding fails with [`Error::BudgetExceeded`] once the budget is spent.
+    ///
+    /// This only affects decoding.
+    ///
+    /// ```
+    /// use byteorder::LE;
+    ///
+    /// use zvariant::EncodingContext as Context;
+    /// use zvariant::{from_slice, to_bytes, Error};
+    ///
+    /// let ctxt = Context::<LE>::new_dbus(0);
+    /// let encoded = to_bytes(ctxt, &vec![vec![0u8; 2]; 3]).unwrap();
+    /// // 3 arrays of 2 bytes, for 9 values.
+    /// let decoded: Vec<Vec<u8>> = from_slice(&encoded, ctxt.with_value_budget(9)).unwrap();
+    /// assert_eq!(decoded.len(), 3);
+    ///
+    /// let res: zvariant::Result<Vec<Vec<u8>>> = from_slice(&encoded, ctxt.with_value_budget(8));
+    /// assert_eq!(res.unwrap_err(), Error::BudgetExceeded(8));
+    /// ```
+    ///
+    /// [`Error::BudgetExceeded`]: enum.Error.html#variant.BudgetExceeded
+    pub fn with_value_budget(self, budget: usize) -> Self {
+        Self {
+            value_budget: Some(budget),
+            ..self
+        }
+    }
+
+    /// The maximum number of values to decode, if limited.
+    ///
+    /// See [`with_value_budget`] for details.
+    ///
+    /// [`with_value_budget`]: #method.with_value_budget
+    pub fn value_budget(self) -> Option<usize> {
+        self.value_budget
+    }
 }
@@ -1,6 +1,99 @@
 use serde::{de, ser};
 use static_assertions::assert_impl_all;
-use std::{convert::Infallible, error, fmt, result};
+use std::{
+    convert::{Infallible, TryFrom},
+    error, fmt,
+    os::unix::io::RawFd,
+    result,
+};
+
+use crate::{EncodingContext, Signature, Value};
+
+/// A step on the path to a value nested in containers.
+///
+/// See [`Error::InPath`](enum.Error.html#variant.InPath).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// The field of a structure, by index.
+    Field(usize),
+    /// The element of an array (or the entry of a dictionary), by index.
+    Element(usize),
+    /// The value of a dictionary entry, by its key.
+    Key(String),
+    /// The value of a variant.
+    Variant,
+}
+
+assert_impl_all!(PathSegment: Send, Sync, Unpin);
+
+impl PathSegment {
+    // The segment for the value of the `index`th entry of a dictionary, from the encoded key of the
+    // entry. Falls back to the index if the key can't be decoded.
+    pub(crate) fn for_encoded_key<B>(
+        key: &[u8],
+        fds: Option<&[RawFd]>,
+        ctxt: EncodingContext<B>,
+        signature: char,
+        index: usize,
+    ) -> Self
+    where
+        B: byteorder::ByteOrder,
+    {
+        let key = Signature::try_from(signature.to_string())
+            .and_then(|s| crate::from_slice_fds_for_signature::<B, Value<'_>>(key, fds, ctxt, &s));
+        match key {
+            Ok(key) => Self::for_key(&key, index),
+            Err(_) => PathSegment::Element(index),
+        }
+    }
+
+    // The segment for the value of the `index`th entry of a dictionary, from its key. Falls back to
+    // the index if the key isn't basic.
+    pub(crate) fn for_key(key: &Value<'_>, index: usize) -> Self {
+        let key = match key {
+            Value::U8(v) => v.to_string(),
+            Value::Bool(v) => v.to_string(),
+            Value::I16(v) => v.to_string(),
+            Value::U16(v) => v.to_string(),
+            Value::I32(v) => v.to_string(),
+            Value::U32(v) => v.to_string(),
+            Value::I64(v) => v.to_string(),
+            Value::U64(v) => v.to_string(),
+            Value::F64(v) => v.to_string(),
+            Value::Str(v) => format!("'{}'", v.as_str()),
+            Value::Signature(v) => format!("'{}'", v.as_str()),
+            Value::ObjectPath(v) => format!("'{}'", v.as_str()),
+            _ => return PathSegment::Element(index),
+        };
+
+        PathSegment::Key(key)
+    }
+
+    /// The key of a [`Key`] segment, without the quotes around string keys.
+    ///
+    /// [`Key`]: #variant.Key
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            PathSegment::Key(k) => Some(
+                k.strip_prefix('\'')
+                    .and_then(|k| k.strip_suffix('\''))
+                    .unwrap_or(k),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Field(i) => write!(f, "field {}", i),
+            PathSegment::Element(i) => write!(f, "element {}", i),
+            PathSegment::Key(k) => write!(f, "key {}", k),
+            PathSegment::Variant => write!(f, "variant"),
+        }
+    }
+}
 
 /// Error type used by zvariant API.
 #[derive(Debug)]
@@ -25,6 +118,30 @@ pub enum Error {
     /// Only exists to allow `TryFrom<T> for T` conversions. You should never actually be getting
     /// this error from any API.
     Infallible,
+    /// The length of a D-Bus array (as argument), in bytes, is beyond the 64 MiB maximum of the
+    /// specification.
+    ArrayTooLong(usize),
+    /// The length some data declares (first argument) is beyond the bytes that remain (second
+    /// argument).
+    LengthExceedsData(usize, usize),
+    /// The data holds more values than the [budget] (as argument) of the context.
+    ///
+    /// The budget being for the data as a whole, this error is never in a path.
+    ///
+    /// [budget]: struct.EncodingContext.html#method.with_value_budget
+    BudgetExceeded(usize),
+    /// An error (de)serializing a value nested in containers, with the path from the outermost
+    /// container to that value.
+    ///
+    /// The path is only tracked when an error occurs, so it comes at no cost otherwise.
+    InPath(Box<Error>, Vec<PathSegment>),
+    /// A value's signature (second argument) doesn't match the expected one (first argument).
+    ///
+    /// The builders of containers, e.g [`ArrayBuilder`], put this error in a path, to point at the
+    /// mismatching value.
+    ///
+    /// [`ArrayBuilder`]: struct.ArrayBuilder.html
+    SignatureMismatch(Signature<'static>, Signature<'static>),
 }
 
 assert_impl_all!(Error: Send, Sync, Unpin);
@@ -38,6 +155,17 @@ impl PartialEq for Error {
             (Error::Utf8(msg), Error::Utf8(other)) => msg == other,
             (Error::PaddingNot0(p), Error::PaddingNot0(other)) => p == other,
             (Error::UnknownFd, Error::UnknownFd) => true,
+            (Error::ArrayTooLong(len), Error::ArrayTooLong(other)) => len == other,
+            (Error::LengthExceedsData(len, left), Error::LengthExceedsData(other, other_left)) => {
+                len == other && left == other_left
+            }
+            (Error::BudgetExceeded(b), Error::BudgetExceeded(other)) => b == other,
+            (Error::InPath(e, path), Error::InPath(other, other_path)) => {
+                e == other && path == other_path
+            }
+            (Error::SignatureMismatch(e, s), Error::SignatureMismatch(other_e, other_s)) => {
+                e == other_e && s == other_s
+            }
             (_, _) => false,
         }
     }
@@ -48,6 +176,7 @@ impl error::Error for Error {
         match self {
             Error::Io(e) => Some(e),
             Error::Utf8(e) => Some(e),
+            Error::InPath(e, _) => e.source(),
             _ => None,
         }
     }
@@ -72,6 +201,77 @@ impl fmt::Display for Error {
                 sig, format,
             ),
             Error::Infallible => write!(f, "Infallible conversion failed"),
+            Error::ArrayTooLong(len) => write!(
+                f,
+                "Array of {} bytes exceeds the maximum length of {} bytes",
+                len,
+                crate::utils::MAX_ARRAY_LEN_DBUS,
+            ),
+            Error::LengthExceedsData(len, left) => write!(
+                f,
+                "Declared length of {} bytes exceeds the {} bytes left",
+                len, left,
+            ),
+            Error::BudgetExceeded(budget) => {
+                write!(f, "Data holds more than the budget of {} values", budget)
+            }
+            Error::InPath(e, path) => {
+                write!(f, "{} (at ", e)?;
+                for (i, segment) in path.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " → ")?;
+                    }
+                    write!(f, "{}", segment)?;
+                }
+                write!(f, ")")
+            }
+            Error::SignatureMismatch(expected, signature) => {
+                write!(
+                    f,
+                    "Signature `{}` doesn't match the expected `{}`",
+                    signature, expected,
+                )?;
+                // Point at where they diverge, for long signatures differing deep inside.
+                let same = expected
+                    .bytes()
+                    .zip(signature.bytes())
+                    .take_while(|(e, s)| e == s)
+                    .count();
+                if same > 0 {
+                    write!(
+                        f,
+                        ": `{}` instead of `{}` after `{}`",
+                        &signature[same..],
+                        &expected[same..],
+                        &expected[..same],
+                    )?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error {
+    /// The path to the value the error is about, if it's nested in containers.
+    pub fn path(&self) -> &[PathSegment] {
+        match self {
+            Error::InPath(_, path) => path,
+            _ => &[],
+        }
+    }
+
+    // Prepend `segment` to the path of the error, as it propagates out of a container.
+    pub(crate) fn in_path(self, segment: PathSegment) -> Self {
+        match self {
+            Error::BudgetExceeded(_) => self,
+            Error::InPath(e, mut path) => {
+                path.insert(0, segment);
+
+                Error::InPath(e, path)
+            }
+            e => Error::InPath(Box::new(e), vec![segment]),
         }
     }
 }
@@ -5,7 +5,7 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use static_assertions::assert_impl_all;
 use std::os::unix::io;
 
-use crate::{Basic, EncodingFormat, Signature, Type};
+use crate::{Basic, EncodingFormat, Signature, StaticType, Type};
 
 /// A [`RawFd`](https://doc.rust-lang.org/std/os/unix/io/type.RawFd.html) wrapper.
 ///
@@ -13,6 +13,17 @@ use crate::{Basic, EncodingFormat, Signature, Type};
 /// File descriptors are serialized in a special way and you need to use specific [serializer] and
 /// [deserializer] API when file descriptors are or could be involved.
 ///
+/// # Ownership
+///
+/// `Fd` never owns the file descriptor it wraps. Creating one from a reference to an
+/// [`AsRawFd`] implementor, serializing or deserializing it, never duplicates (`dup`) or closes
+/// the file descriptor. The serializer only records the raw value in the returned list of file
+/// descriptors, once per distinct value, so the file descriptor must be kept open for as long as the
+/// serialized data is in use. If you need an `Fd` to own the file descriptor, keep the original
+/// owner (e.g a [`File`]) around instead.
+///
+/// [`AsRawFd`]: https://doc.rust-lang.org/std/os/unix/io/trait.AsRawFd.html
+/// [`File`]: https://doc.rust-lang.org/std/fs/struct.File.html
 /// [`Serialize`]: https://docs.serde.rs/serde/trait.Serialize.html
 /// [`Deserialize`]: https://docs.serde.rs/serde/de/trait.Deserialize.html
 /// [deserializer]: fn.from_slice_fds.html
@@ -40,10 +51,14 @@ impl io::AsRawFd for Fd {
 
 impl Type for Fd {
     fn signature() -> Signature<'static> {
-        Signature::from_str_unchecked(Self::SIGNATURE_STR)
+        Signature::from_str_unchecked(<Self as StaticType>::SIGNATURE_STR)
     }
 }
 
+impl StaticType for Fd {
+    const SIGNATURE_STR: &'static str = "h";
+}
+
 impl Serialize for Fd {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -15,13 +15,21 @@ impl FramingOffsets {
         Self(VecDeque::new())
     }
 
-    pub fn from_encoded_array(container: &[u8]) -> (Self, usize) {
+    // The framing offsets of the encoded array `container` and their total length, or `None` if
+    // the offsets don't fit in the container.
+    pub fn from_encoded_array(container: &[u8]) -> Option<(Self, usize)> {
         let offset_size = FramingOffsetSize::for_encoded_container(container.len());
+        let slice_len = offset_size as usize;
+        if !container.is_empty() && container.len() < slice_len {
+            return None;
+        }
 
         // The last offset tells us the start of offsets.
         let mut i = offset_size.read_last_offset_from_buffer(container);
+        if i > container.len() || (container.len() - i) % slice_len != 0 {
+            return None;
+        }
         let offsets_len = container.len() - i;
-        let slice_len = offset_size as usize;
         let mut offsets = Self::new();
         while i < container.len() {
             let end = i + slice_len;
@@ -31,7 +39,7 @@ impl FramingOffsets {
             i += slice_len;
         }
 
-        (offsets, offsets_len)
+        Some((offsets, offsets_len))
     }
 
     pub fn push(&mut self, offset: usize) {
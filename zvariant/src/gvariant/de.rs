@@ -6,9 +6,13 @@ use static_assertions::assert_impl_all;
 use std::{ffi::CStr, marker::PhantomData, os::unix::io::RawFd, str};
 
 use crate::{
-    de::ValueParseStage, framing_offset_size::FramingOffsetSize, framing_offsets::FramingOffsets,
-    signature_parser::SignatureParser, utils::*, EncodingContext, EncodingFormat, Error, Result,
-    Signature,
+    de::{ValidatedStrVisitor, ValueParseStage},
+    framing_offset_size::FramingOffsetSize,
+    framing_offsets::FramingOffsets,
+    gvariant::lenient::{fixed_size, is_fixed_array_len, DefaultDeserializer},
+    signature_parser::SignatureParser,
+    utils::*,
+    Basic, EncodingContext, EncodingFormat, Error, ObjectPath, PathSegment, Result, Signature,
 };
 
 /// Our GVariant deserialization implementation.
@@ -36,10 +40,41 @@ where
             sig_parser,
             bytes,
             fds,
+            budget: crate::de::Budget::for_context(ctxt),
             pos: 0,
             b: PhantomData,
         })
     }
+
+    fn lenient(&self) -> bool {
+        self.0.ctxt.is_lenient()
+    }
+
+    // Lenient decoding ignores the padding bytes, and any that are missing.
+    fn parse_padding(&mut self, alignment: usize) -> Result<usize> {
+        if !self.lenient() {
+            return self.0.parse_padding(alignment);
+        }
+
+        let remaining = self.0.bytes.len().saturating_sub(self.0.pos);
+        let padding = padding_for_n_bytes(self.0.abs_pos(), alignment).min(remaining);
+        self.0.pos += padding;
+
+        Ok(padding)
+    }
+
+    // Deserialize the default value of the next signature, in place of the rest of the data.
+    fn deserialize_default<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.0.pos = self.0.bytes.len();
+        let mut default = DefaultDeserializer(self.0.sig_parser.clone());
+        let v = de::Deserializer::deserialize_any(&mut default, visitor)?;
+        self.0.sig_parser = default.0;
+
+        Ok(v)
+    }
 }
 
 macro_rules! deserialize_basic {
@@ -49,6 +84,16 @@ macro_rules! deserialize_basic {
         where
             V: Visitor<'de>,
         {
+            if self.lenient() {
+                let signature = self.0.sig_parser.next_signature()?;
+                if let Some((size, alignment)) = fixed_size(&signature)? {
+                    self.parse_padding(alignment)?;
+                    if self.0.bytes.len() - self.0.pos < size {
+                        return self.deserialize_default(visitor);
+                    }
+                }
+            }
+
             let ctxt = EncodingContext::new_dbus(self.0.ctxt.position() + self.0.pos);
 
             let mut dbus_de = crate::dbus::Deserializer::<B>(crate::DeserializerCommon::<B> {
@@ -56,6 +101,7 @@ macro_rules! deserialize_basic {
                 sig_parser: self.0.sig_parser.clone(),
                 bytes: &self.0.bytes[self.0.pos..],
                 fds: self.0.fds,
+                budget: self.0.budget.clone(),
                 pos: 0,
                 b: PhantomData,
             });
@@ -153,15 +199,33 @@ where
             // GVariant decided to skip the trailing nul at the end of signature string
             str::from_utf8(slice).map_err(Error::Utf8)?
         } else {
+            if self.lenient() {
+                let bytes = &self.0.bytes[self.0.pos..];
+                let valid = match CStr::from_bytes_with_nul(bytes).map(CStr::to_str) {
+                    Ok(Ok(s)) => match self.0.sig_parser.next_char() {
+                        ObjectPath::SIGNATURE_CHAR => ObjectPath::try_from(s).is_ok(),
+                        Signature::SIGNATURE_CHAR => Signature::try_from(s).is_ok(),
+                        _ => true,
+                    },
+                    _ => false,
+                };
+                if !valid {
+                    return self.deserialize_default(visitor);
+                }
+            }
+
             let cstr =
                 CStr::from_bytes_with_nul(&self.0.bytes[self.0.pos..]).map_err(|_| -> Error {
-                    let c = self.0.bytes[self.0.bytes.len() - 1] as char;
+                    let c = self.0.bytes.last().map(|b| *b as char).unwrap_or_default();
                     de::Error::invalid_value(
                         de::Unexpected::Char(c),
                         &"nul byte expected at the end of strings",
                     )
                 })?;
             let s = cstr.to_str().map_err(Error::Utf8)?;
+            if !self.lenient() {
+                self.0.validate_str(self.0.sig_parser.next_char(), s)?;
+            }
             self.0.pos += s.len() + 1; // string and trailing null byte
 
             s
@@ -183,16 +247,25 @@ where
         let fixed_sized_child = crate::utils::is_fixed_sized_signature(&child_signature)?;
 
         self.0.sig_parser.skip_char()?;
-        self.0.parse_padding(alignment)?;
+        self.parse_padding(alignment)?;
 
-        if self.0.pos == self.0.bytes.len() {
+        // GLib reads a maybe of fixed-sized type as `Nothing`, unless it has the exact size.
+        let nothing = self.lenient()
+            && match fixed_size(&child_signature)? {
+                Some((size, _)) => self.0.bytes.len() - self.0.pos != size,
+                None => false,
+            };
+        if self.0.pos == self.0.bytes.len() || nothing {
             // Empty sequence means None
             self.0.sig_parser.skip_chars(child_sig_len)?;
+            self.0.pos = self.0.bytes.len();
 
             visitor.visit_none()
         } else {
-            let ctxt =
-                EncodingContext::new(self.0.ctxt.format(), self.0.ctxt.position() + self.0.pos);
+            let ctxt = self
+                .0
+                .ctxt
+                .with_position(self.0.ctxt.position() + self.0.pos);
             let end = if fixed_sized_child {
                 self.0.bytes.len()
             } else {
@@ -204,6 +277,7 @@ where
                 sig_parser: self.0.sig_parser.clone(),
                 bytes: &self.0.bytes[self.0.pos..end],
                 fds: self.0.fds,
+                budget: self.0.budget.clone(),
                 pos: 0,
                 b: PhantomData,
             });
@@ -211,7 +285,10 @@ where
             let v = visitor.visit_some(&mut de)?;
             self.0.pos += de.0.pos;
 
-            if !fixed_sized_child {
+            if self.lenient() {
+                // The trailing byte of variable-sized children is ignored too.
+                self.0.pos = self.0.bytes.len();
+            } else if !fixed_sized_child {
                 let byte = self.0.bytes[self.0.pos];
                 if byte != 0 {
                     return Err(de::Error::invalid_value(
@@ -232,16 +309,19 @@ where
     where
         V: Visitor<'de>,
     {
-        let byte = self.0.bytes[self.0.pos];
-        if byte != 0 {
-            return Err(de::Error::invalid_value(
-                de::Unexpected::Bytes(&self.0.bytes[self.0.pos..self.0.pos + 1]),
-                &"0 byte expected for empty tuples (unit type)",
-            ));
+        match self.0.bytes.get(self.0.pos) {
+            Some(&0) => self.0.pos += 1,
+            Some(_) if self.lenient() => self.0.pos += 1,
+            None if self.lenient() => (),
+            Some(_) => {
+                return Err(de::Error::invalid_value(
+                    de::Unexpected::Bytes(&self.0.bytes[self.0.pos..self.0.pos + 1]),
+                    &"0 byte expected for empty tuples (unit type)",
+                ))
+            }
+            None => return Err(de::Error::invalid_length(0, &"1 byte for the unit type")),
         }
 
-        self.0.pos += 1;
-
         visitor.visit_unit()
     }
 
@@ -252,10 +332,14 @@ where
         visitor.visit_unit()
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if self.0.is_validated_str(name) {
+            return self.deserialize_str(ValidatedStrVisitor(visitor));
+        }
+
         visitor.visit_newtype_struct(self)
     }
 
@@ -266,7 +350,7 @@ where
         match self.0.sig_parser.next_char() {
             VARIANT_SIGNATURE_CHAR => {
                 self.0.sig_parser.skip_char()?;
-                self.0.parse_padding(VARIANT_ALIGNMENT_GVARIANT)?;
+                self.parse_padding(VARIANT_ALIGNMENT_GVARIANT)?;
                 let value_de = ValueDeserializer::new(self)?;
 
                 visitor.visit_seq(value_de)
@@ -285,7 +369,17 @@ where
             STRUCT_SIG_START_CHAR => {
                 let signature = self.0.sig_parser.next_signature()?;
                 let alignment = alignment_for_signature(&signature, self.0.ctxt.format());
-                self.0.parse_padding(alignment)?;
+                let fixed_sized = crate::utils::is_fixed_sized_signature(&signature)?;
+                let size = match self.lenient() {
+                    true => fixed_size(&signature)?.map(|(size, _)| size),
+                    false => None,
+                };
+                self.parse_padding(alignment)?;
+                if let Some(size) = size {
+                    if self.0.bytes.len() - self.0.pos < size {
+                        return self.deserialize_default(visitor);
+                    }
+                }
 
                 self.0.sig_parser.skip_char()?;
 
@@ -298,6 +392,8 @@ where
                     end,
                     offsets_len: 0,
                     offset_size,
+                    index: 0,
+                    fixed_sized,
                 })
             }
             c => Err(de::Error::invalid_type(
@@ -350,12 +446,22 @@ struct ArrayDeserializer<'d, 'de, 'sig, 'f, B> {
     element_alignment: usize,
     // where value signature starts
     element_signature_len: usize,
+    // size of fixed-sized elements, when decoding leniently
+    element_size: Option<usize>,
     // All offsets (GVariant-specific)
     offsets: Option<FramingOffsets>,
     // Length of all the offsets after the array
     offsets_len: usize,
     // size of the framing offset of last dict-entry key read (GVariant-specific)
     key_offset_size: Option<FramingOffsetSize>,
+    // index of the next element
+    index: usize,
+    // start of the last element (the key, for dict entries)
+    element_start: usize,
+    // end of the last element, relative to the start of the array
+    prev_end: usize,
+    // if the framing offsets of the current dict entry are valid, when decoding leniently
+    entry_valid: bool,
 }
 
 impl<'d, 'de, 'sig, 'f, B> ArrayDeserializer<'d, 'de, 'sig, 'f, B>
@@ -364,6 +470,7 @@ where
 {
     fn new(de: &'d mut Deserializer<'de, 'sig, 'f, B>) -> Result<Self> {
         let mut len = de.0.bytes.len() - de.0.pos;
+        let lenient = de.lenient();
 
         let element_signature = de.0.sig_parser.next_signature()?;
         let element_alignment = alignment_for_signature(&element_signature, de.0.ctxt.format());
@@ -377,16 +484,25 @@ where
         } else {
             false
         };
+        let element_size = match lenient {
+            true => fixed_size(&element_signature)?.map(|(size, _)| size),
+            false => None,
+        };
 
         // D-Bus requires padding for the first element even when there is no first element
         // (i-e empty array) so we parse padding already. In case of GVariant this is just
         // the padding of the array itself since array starts with first element.
-        let padding = de.0.parse_padding(element_alignment)?;
+        let padding = de.parse_padding(element_alignment)?;
         len -= padding;
 
         let (offsets, offsets_len, key_offset_size) = if !fixed_sized_child {
             let (array_offsets, offsets_len) =
-                FramingOffsets::from_encoded_array(&de.0.bytes[de.0.pos..]);
+                match FramingOffsets::from_encoded_array(&de.0.bytes[de.0.pos..]) {
+                    Some(offsets) => offsets,
+                    // GLib reads arrays without valid framing offsets as empty ones.
+                    None if lenient => (FramingOffsets::new(), len),
+                    None => return Err(Error::MissingFramingOffset),
+                };
             len -= offsets_len;
             let key_offset_size = if !fixed_sized_key {
                 // The actual offset for keys is calculated per key later, this is just to
@@ -401,6 +517,13 @@ where
         } else {
             (None, 0, None)
         };
+        if let Some(size) = element_size {
+            // GLib reads arrays ending in the middle of an element as empty ones.
+            if !is_fixed_array_len(len, size, element_alignment) {
+                de.0.pos += len;
+                len = 0;
+            }
+        }
         let start = de.0.pos;
 
         if de.0.sig_parser.next_char() == DICT_ENTRY_SIG_START_CHAR {
@@ -413,9 +536,14 @@ where
             start,
             element_alignment,
             element_signature_len,
+            element_size,
             offsets,
             offsets_len,
             key_offset_size,
+            index: 0,
+            element_start: start,
+            prev_end: 0,
+            entry_valid: true,
         })
     }
 
@@ -434,12 +562,55 @@ where
         }
     }
 
+    // The start of the next element of variable size, as GLib finds it: right after the
+    // previous one, whatever the current position.
+    fn next_element_start(&self) -> usize {
+        self.start + self.prev_end + padding_for_n_bytes(self.prev_end, self.element_alignment)
+    }
+
+    // Check that the element framed by `start` and `end` is within the array, since framing
+    // offsets can be bogus.
+    fn check_element_bounds(&self, start: usize, end: usize) -> Result<()> {
+        if start > end || end > self.start + self.len {
+            return Err(de::Error::invalid_value(
+                de::Unexpected::Unsigned((end - self.start) as u64),
+                &format!("a framing offset <= {}", self.len).as_str(),
+            ));
+        }
+
+        Ok(())
+    }
+
     fn done(&self) -> bool {
-        match self.offsets.as_ref() {
+        match (self.offsets.as_ref(), self.element_size) {
             // If all offsets have been popped/used, we're already at the end
-            Some(offsets) => offsets.is_empty(),
-            None => self.de.0.pos == self.start + self.len,
+            (Some(offsets), _) => offsets.is_empty(),
+            // The data left after the last element is then the trailing padding (if any)
+            (None, Some(size)) => {
+                let padding = padding_for_n_bytes(self.de.0.abs_pos(), self.element_alignment);
+
+                self.de.0.pos + padding + size > self.start + self.len
+            }
+            (None, None) => self.de.0.pos == self.start + self.len,
+        }
+    }
+
+    fn skip_offsets(&mut self) {
+        if self.de.lenient() {
+            self.de.0.pos = self.start + self.len;
+        }
+        self.de.0.pos += self.offsets_len;
+    }
+
+    fn check_len(&self) -> Result<()> {
+        if !self.de.lenient() && self.de.0.pos > self.start + self.len {
+            return Err(serde::de::Error::invalid_length(
+                self.len,
+                &format!(">= {}", self.de.0.pos - self.start).as_str(),
+            ));
         }
+
+        Ok(())
     }
 }
 
@@ -458,35 +629,52 @@ where
                 .0
                 .sig_parser
                 .skip_chars(self.element_signature_len)?;
-            self.de.0.pos += self.offsets_len;
+            self.skip_offsets();
 
             return Ok(None);
         }
+        self.de.0.budget.charge()?;
 
-        let ctxt = EncodingContext::new(
-            self.de.0.ctxt.format(),
-            self.de.0.ctxt.position() + self.de.0.pos,
-        );
         let end = self.element_end(true)?;
+        let index = self.index;
+        self.index += 1;
+
+        if self.offsets.is_some() {
+            if self.de.lenient() {
+                let start = self.next_element_start();
+                self.prev_end = end - self.start;
+                if start >= end || end > self.start + self.len {
+                    let mut sig_parser = self.de.0.sig_parser.clone();
 
+                    return DefaultDeserializer::deserialize_next(&mut sig_parser, seed).map(Some);
+                }
+                self.de.0.pos = start;
+            } else {
+                self.check_element_bounds(self.de.0.pos, end)?;
+            }
+        }
+
+        let ctxt = self
+            .de
+            .0
+            .ctxt
+            .with_position(self.de.0.ctxt.position() + self.de.0.pos);
         let mut de = Deserializer::<B>(crate::DeserializerCommon {
             ctxt,
             sig_parser: self.de.0.sig_parser.clone(),
             bytes: &self.de.0.bytes[self.de.0.pos..end],
             fds: self.de.0.fds,
+            budget: self.de.0.budget.clone(),
             pos: 0,
             b: PhantomData,
         });
 
-        let v = seed.deserialize(&mut de).map(Some);
+        let v = seed
+            .deserialize(&mut de)
+            .map(Some)
+            .map_err(|e| e.in_path(PathSegment::Element(index)));
         self.de.0.pos += de.0.pos;
-
-        if self.de.0.pos > self.start + self.len {
-            return Err(serde::de::Error::invalid_length(
-                self.len,
-                &format!(">= {}", self.de.0.pos - self.start).as_str(),
-            ));
-        }
+        self.check_len()?;
 
         v
     }
@@ -508,49 +696,86 @@ where
                 .0
                 .sig_parser
                 .skip_chars(self.element_signature_len - 1)?;
-            self.de.0.pos += self.offsets_len;
+            self.skip_offsets();
 
             return Ok(None);
         }
+        self.de.0.budget.charge()?;
 
-        self.de.0.parse_padding(self.element_alignment)?;
+        let lenient = self.de.lenient();
+        if lenient && self.offsets.is_some() {
+            self.de.0.pos = self.next_element_start();
+        } else {
+            self.de.parse_padding(self.element_alignment)?;
+        }
 
-        let ctxt = EncodingContext::new(
-            self.de.0.ctxt.format(),
-            self.de.0.ctxt.position() + self.de.0.pos,
-        );
         let element_end = self.element_end(false)?;
+        if self.offsets.is_some() {
+            if lenient {
+                self.entry_valid =
+                    self.de.0.pos < element_end && element_end <= self.start + self.len;
+            } else {
+                self.check_element_bounds(self.de.0.pos, element_end)?;
+            }
+        }
 
         let key_end = match self.key_offset_size {
-            Some(_) => {
-                let offset_size =
-                    FramingOffsetSize::for_encoded_container(element_end - self.de.0.pos);
+            Some(_) if self.entry_valid => {
+                let entry_len = element_end - self.de.0.pos;
+                let offset_size = FramingOffsetSize::for_encoded_container(entry_len);
                 self.key_offset_size.replace(offset_size);
 
-                self.de.0.pos
-                    + offset_size
-                        .read_last_offset_from_buffer(&self.de.0.bytes[self.de.0.pos..element_end])
+                let key_end = if entry_len < offset_size as usize {
+                    None
+                } else {
+                    let offset = offset_size
+                        .read_last_offset_from_buffer(&self.de.0.bytes[self.de.0.pos..element_end]);
+
+                    Some(self.de.0.pos + offset)
+                        .filter(|end| *end + offset_size as usize <= element_end)
+                };
+                match key_end {
+                    Some(key_end) => key_end,
+                    None if lenient => {
+                        self.entry_valid = false;
+
+                        element_end
+                    }
+                    None => return Err(Error::MissingFramingOffset),
+                }
             }
-            None => element_end,
+            _ => element_end,
         };
+        let index = self.index;
+        self.index += 1;
+        self.element_start = self.de.0.pos;
+
+        if !self.entry_valid {
+            let mut sig_parser = self.de.0.sig_parser.clone();
+
+            return DefaultDeserializer::deserialize_next(&mut sig_parser, seed).map(Some);
+        }
 
+        let ctxt = self
+            .de
+            .0
+            .ctxt
+            .with_position(self.de.0.ctxt.position() + self.de.0.pos);
         let mut de = Deserializer::<B>(crate::DeserializerCommon {
             ctxt,
             sig_parser: self.de.0.sig_parser.clone(),
             bytes: &self.de.0.bytes[self.de.0.pos..key_end],
             fds: self.de.0.fds,
+            budget: self.de.0.budget.clone(),
             pos: 0,
             b: PhantomData,
         });
-        let v = seed.deserialize(&mut de).map(Some);
+        let v = seed
+            .deserialize(&mut de)
+            .map(Some)
+            .map_err(|e| e.in_path(PathSegment::Element(index)));
         self.de.0.pos += de.0.pos;
-
-        if self.de.0.pos > self.start + self.len {
-            return Err(serde::de::Error::invalid_length(
-                self.len,
-                &format!(">= {}", self.de.0.pos - self.start).as_str(),
-            ));
-        }
+        self.check_len()?;
 
         v
     }
@@ -559,40 +784,53 @@ where
     where
         V: DeserializeSeed<'de>,
     {
-        let ctxt = EncodingContext::new(
-            self.de.0.ctxt.format(),
-            self.de.0.ctxt.position() + self.de.0.pos,
-        );
         let element_end = self.element_end(true)?;
+        if self.offsets.is_some() {
+            self.prev_end = element_end - self.start;
+        }
+        let mut sig_parser = self.de.0.sig_parser.clone();
+        // Skip key signature (always 1 char)
+        sig_parser.skip_char()?;
+
+        if !self.entry_valid {
+            self.entry_valid = true;
+
+            return DefaultDeserializer::deserialize_next(&mut sig_parser, seed);
+        }
+
         let value_end = match self.key_offset_size {
             Some(key_offset_size) => element_end - key_offset_size as usize,
             None => element_end,
         };
-        let mut sig_parser = self.de.0.sig_parser.clone();
-        // Skip key signature (always 1 char)
-        sig_parser.skip_char()?;
+        let key_end = self.de.0.pos;
 
+        let ctxt = self
+            .de
+            .0
+            .ctxt
+            .with_position(self.de.0.ctxt.position() + self.de.0.pos);
         let mut de = Deserializer::<B>(crate::DeserializerCommon {
             ctxt,
             sig_parser,
             bytes: &self.de.0.bytes[self.de.0.pos..value_end],
             fds: self.de.0.fds,
+            budget: self.de.0.budget.clone(),
             pos: 0,
             b: PhantomData,
         });
-        let v = seed.deserialize(&mut de);
+        let v = seed.deserialize(&mut de).map_err(|e| {
+            e.in_path(
+                self.de
+                    .0
+                    .key_segment(self.element_start, key_end, self.index - 1),
+            )
+        });
         self.de.0.pos += de.0.pos;
 
         if let Some(key_offset_size) = self.key_offset_size {
             self.de.0.pos += key_offset_size as usize;
         }
-
-        if self.de.0.pos > self.start + self.len {
-            return Err(serde::de::Error::invalid_length(
-                self.len,
-                &format!(">= {}", self.de.0.pos - self.start).as_str(),
-            ));
-        }
+        self.check_len()?;
 
         v
     }
@@ -607,6 +845,10 @@ struct StructureDeserializer<'d, 'de, 'sig, 'f, B> {
     offsets_len: usize,
     // size of the framing offset
     offset_size: FramingOffsetSize,
+    // index of the next field
+    index: usize,
+    // if the structure is of fixed size, and hence not framed by the container
+    fixed_sized: bool,
 }
 
 impl<'d, 'de, 'sig, 'f, B> SeqAccess<'de> for StructureDeserializer<'d, 'de, 'sig, 'f, B>
@@ -619,18 +861,28 @@ where
     where
         T: DeserializeSeed<'de>,
     {
-        let ctxt = EncodingContext::new(
-            self.de.0.ctxt.format(),
-            self.de.0.ctxt.position() + self.de.0.pos,
-        );
+        let lenient = self.de.lenient();
+        let ctxt = self
+            .de
+            .0
+            .ctxt
+            .with_position(self.de.0.ctxt.position() + self.de.0.pos);
         let element_signature = self.de.0.sig_parser.next_signature()?;
         let fixed_sized_element = crate::utils::is_fixed_sized_signature(&element_signature)?;
+        let mut valid = true;
         let element_end = if !fixed_sized_element {
             let next_sig_pos = element_signature.len();
             let parser = self.de.0.sig_parser.slice(next_sig_pos..);
             if !parser.done() && parser.next_char() == STRUCT_SIG_END_CHAR {
                 // This is the last item then and in GVariant format, we don't have offset for it
                 // even if it's non-fixed-sized.
+                self.end
+            } else if self.end - self.start < self.offset_size as usize {
+                if !lenient {
+                    return Err(Error::MissingFramingOffset);
+                }
+                valid = false;
+
                 self.end
             } else {
                 let end = self
@@ -646,28 +898,65 @@ where
             self.end
         };
 
-        let sig_parser = self.de.0.sig_parser.clone();
-        let mut de = Deserializer::<B>(crate::DeserializerCommon {
-            ctxt,
-            sig_parser,
-            bytes: &self.de.0.bytes[self.de.0.pos..element_end],
-            fds: self.de.0.fds,
-            pos: 0,
-            b: PhantomData,
-        });
-        let v = seed.deserialize(&mut de).map(Some);
-        self.de.0.pos += de.0.pos;
+        if lenient {
+            // A field is there if it fits in the structure, or its default value is used instead.
+            let alignment = alignment_for_signature(&element_signature, ctxt.format());
+            let start = self.de.0.pos + padding_for_n_bytes(ctxt.position(), alignment);
+            valid &= match fixed_size(&element_signature)? {
+                Some((size, _)) => start + size <= element_end,
+                None => start <= element_end && element_end <= self.end,
+            };
+        } else if self.de.0.pos > element_end || element_end > self.end {
+            return Err(de::Error::invalid_value(
+                de::Unexpected::Unsigned((element_end - self.start) as u64),
+                &format!("a framing offset <= {}", self.end - self.start).as_str(),
+            ));
+        }
+
+        let index = self.index;
+        self.index += 1;
 
-        if de.0.sig_parser.next_char() == STRUCT_SIG_END_CHAR {
+        let v = if valid {
+            let sig_parser = self.de.0.sig_parser.clone();
+            let mut de = Deserializer::<B>(crate::DeserializerCommon {
+                ctxt,
+                sig_parser,
+                bytes: &self.de.0.bytes[self.de.0.pos..element_end],
+                fds: self.de.0.fds,
+                budget: self.de.0.budget.clone(),
+                pos: 0,
+                b: PhantomData,
+            });
+            let v = seed
+                .deserialize(&mut de)
+                .map(Some)
+                .map_err(|e| e.in_path(PathSegment::Field(index)));
+            self.de.0.pos += de.0.pos;
+            self.de.0.sig_parser = de.0.sig_parser;
+            if lenient && !fixed_sized_element {
+                // The next field starts right after the framing offset, as for GLib.
+                self.de.0.pos = element_end;
+            }
+
+            v
+        } else {
+            self.de.0.pos = element_end.min(self.end).max(self.de.0.pos);
+
+            DefaultDeserializer::deserialize_next(&mut self.de.0.sig_parser, seed).map(Some)
+        };
+
+        if self.de.0.sig_parser.next_char() == STRUCT_SIG_END_CHAR {
             // Last item in the struct
-            de.0.sig_parser.skip_char()?;
+            self.de.0.sig_parser.skip_char()?;
 
+            if lenient && !self.fixed_sized {
+                // Whatever is left of the structure is ignored
+                self.de.0.pos = self.end;
+            }
             // Skip over the framing offsets (if any)
             self.de.0.pos += self.offsets_len;
         }
 
-        self.de.0.sig_parser = de.0.sig_parser;
-
         v
     }
 }
@@ -691,7 +980,7 @@ where
         let mut separator_pos = None;
 
         // Search for the nul byte separator
-        for i in (de.0.pos..de.0.bytes.len() - 1).rev() {
+        for i in (de.0.pos..de.0.bytes.len().saturating_sub(1)).rev() {
             if de.0.bytes[i] == b'\0' {
                 separator_pos = Some(i);
 
@@ -743,6 +1032,7 @@ where
                     sig_parser,
                     bytes: &self.de.0.bytes[self.sig_start..self.sig_end],
                     fds: self.de.0.fds,
+                    budget: self.de.0.budget.clone(),
                     pos: 0,
                     b: PhantomData,
                 });
@@ -757,20 +1047,26 @@ where
                 let signature = Signature::try_from(slice)?;
                 let sig_parser = SignatureParser::new(signature);
 
-                let ctxt = EncodingContext::new(
-                    self.de.0.ctxt.format(),
-                    self.de.0.ctxt.position() + self.value_start,
-                );
+                let ctxt = self
+                    .de
+                    .0
+                    .ctxt
+                    .with_position(self.de.0.ctxt.position() + self.value_start);
+                self.de.0.budget.charge()?;
                 let mut de = Deserializer::<B>(crate::DeserializerCommon {
                     ctxt,
                     sig_parser,
                     bytes: &self.de.0.bytes[self.value_start..self.value_end],
                     fds: self.de.0.fds,
+                    budget: self.de.0.budget.clone(),
                     pos: 0,
                     b: PhantomData,
                 });
 
-                let v = seed.deserialize(&mut de).map(Some);
+                let v = seed
+                    .deserialize(&mut de)
+                    .map(Some)
+                    .map_err(|e| e.in_path(PathSegment::Variant));
 
                 self.de.0.pos = self.sig_end;
 
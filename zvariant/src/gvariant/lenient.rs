@@ -0,0 +1,178 @@
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+
+use crate::{
+    signature_parser::SignatureParser, utils::*, Basic, EncodingFormat, Error, ObjectPath, Result,
+    Signature,
+};
+
+// Used internally by the lenient GVariant decoding, for data that isn't in normal form.
+
+// The size and alignment of values of `signature`, if of fixed size.
+//
+// The size is that of the data we write, i-e without the trailing padding of structures that GLib
+// adds (and doesn't require for reading anyway).
+pub(crate) fn fixed_size(signature: &Signature<'_>) -> Result<Option<(usize, usize)>> {
+    let alignment = alignment_for_signature(signature, EncodingFormat::GVariant);
+    let c = signature
+        .as_bytes()
+        .first()
+        .map(|b| *b as char)
+        .ok_or_else(|| -> Error { de::Error::invalid_length(0, &">= 1 character") })?;
+    let size = match c {
+        STRUCT_SIG_START_CHAR | DICT_ENTRY_SIG_START_CHAR => {
+            let mut parser = SignatureParser::new(signature.slice(1..signature.len() - 1));
+            let mut size = 0;
+            while !parser.done() {
+                let field = parser.parse_next_signature()?;
+                match fixed_size(&field)? {
+                    Some((field_size, field_alignment)) => {
+                        size += padding_for_n_bytes(size, field_alignment) + field_size;
+                    }
+                    None => return Ok(None),
+                }
+            }
+
+            // Empty structures (unit) are encoded as a single 0 byte.
+            size.max(1)
+        }
+        _ if is_fixed_sized_signature(signature)? => alignment,
+        _ => return Ok(None),
+    };
+
+    Ok(Some((size, alignment)))
+}
+
+// The size of the elements of fixed size in an array, with the padding between them.
+pub(crate) fn fixed_stride(size: usize, alignment: usize) -> usize {
+    size + padding_for_n_bytes(size, alignment)
+}
+
+// Whether an array of fixed-size elements is `len` bytes long, with or without the trailing
+// padding of its last element.
+pub(crate) fn is_fixed_array_len(len: usize, size: usize, alignment: usize) -> bool {
+    let stride = fixed_stride(size, alignment);
+
+    len % stride == 0 || (len >= size && (len - size) % stride == 0)
+}
+
+// A deserializer of the default value of a signature, for data that can't be decoded.
+pub(crate) struct DefaultDeserializer<'sig>(pub(crate) SignatureParser<'sig>);
+
+impl<'sig> DefaultDeserializer<'sig> {
+    // Deserialize the default value of the next complete signature of `parser`, skipping it.
+    pub(crate) fn deserialize_next<'de, T>(
+        parser: &mut SignatureParser<'sig>,
+        seed: T,
+    ) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let mut de = DefaultDeserializer(parser.clone());
+        let v = seed.deserialize(&mut de)?;
+        *parser = de.0;
+
+        Ok(v)
+    }
+}
+
+impl<'de, 'd, 'sig> de::Deserializer<'de> for &'d mut DefaultDeserializer<'sig> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let c = self.0.next_char();
+        let mut parser = self.0.clone();
+        // Visitors don't necessarily visit all the fields of structures, so the signature is
+        // skipped as a whole, whatever the visitor does.
+        let len = self.0.next_signature()?.len();
+        self.0.skip_chars(len)?;
+
+        match c {
+            ARRAY_SIGNATURE_CHAR if parser.slice(1..).next_char() == DICT_ENTRY_SIG_START_CHAR => {
+                visitor.visit_map(Empty)
+            }
+            ARRAY_SIGNATURE_CHAR => visitor.visit_seq(Empty),
+            MAYBE_SIGNATURE_CHAR => visitor.visit_none(),
+            STRUCT_SIG_START_CHAR => {
+                parser.skip_char()?;
+
+                visitor.visit_seq(Fields(DefaultDeserializer(parser)))
+            }
+            u8::SIGNATURE_CHAR => visitor.visit_u8(0),
+            bool::SIGNATURE_CHAR => visitor.visit_bool(false),
+            i16::SIGNATURE_CHAR => visitor.visit_i16(0),
+            u16::SIGNATURE_CHAR => visitor.visit_u16(0),
+            i32::SIGNATURE_CHAR => visitor.visit_i32(0),
+            u32::SIGNATURE_CHAR => visitor.visit_u32(0),
+            i64::SIGNATURE_CHAR => visitor.visit_i64(0),
+            u64::SIGNATURE_CHAR => visitor.visit_u64(0),
+            f64::SIGNATURE_CHAR => visitor.visit_f64(0.),
+            <&str>::SIGNATURE_CHAR | Signature::SIGNATURE_CHAR => visitor.visit_borrowed_str(""),
+            ObjectPath::SIGNATURE_CHAR => visitor.visit_borrowed_str("/"),
+            c => Err(de::Error::invalid_type(
+                de::Unexpected::Char(c),
+                &"a type with a default value",
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+// The fields of a default structure, up to its closing parenthesis.
+struct Fields<'sig>(DefaultDeserializer<'sig>);
+
+impl<'de, 'sig> SeqAccess<'de> for Fields<'sig> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.0 .0.next_char() == STRUCT_SIG_END_CHAR {
+            self.0 .0.skip_char()?;
+
+            return Ok(None);
+        }
+
+        seed.deserialize(&mut self.0).map(Some)
+    }
+}
+
+// The elements of an empty array or dictionary.
+struct Empty;
+
+impl<'de> SeqAccess<'de> for Empty {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, _seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        Ok(None)
+    }
+}
+
+impl<'de> MapAccess<'de> for Empty {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, _seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        Ok(None)
+    }
+
+    fn next_value_seed<V>(&mut self, _seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        unreachable!("no key, so no value either")
+    }
+}
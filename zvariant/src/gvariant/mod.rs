@@ -1,4 +1,5 @@
 mod de;
 pub use de::*;
+mod lenient;
 mod ser;
 pub use ser::*;
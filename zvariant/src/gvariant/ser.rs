@@ -9,8 +9,8 @@ use std::{
 
 use crate::{
     framing_offset_size::FramingOffsetSize, framing_offsets::FramingOffsets,
-    signature_parser::SignatureParser, utils::*, EncodingContext, EncodingFormat, Error, Result,
-    Signature,
+    signature_parser::SignatureParser, utils::*, EncodingContext, EncodingFormat, Error,
+    PathSegment, Result, Signature,
 };
 
 /// Our serialization implementation.
@@ -264,6 +264,7 @@ where
             element_signature_len,
             offsets,
             key_start,
+            index: 0,
         })
     }
 
@@ -334,6 +335,7 @@ where
             start,
             end_parens,
             offsets,
+            index: 0,
         })
     }
 
@@ -362,6 +364,8 @@ pub struct SeqSerializer<'ser, 'sig, 'b, B, W> {
     offsets: Option<FramingOffsets>,
     // start of last dict-entry key written
     key_start: Option<usize>,
+    // index of the next element
+    index: usize,
 }
 
 impl<'ser, 'sig, 'b, B, W> SeqSerializer<'ser, 'sig, 'b, B, W>
@@ -409,8 +413,12 @@ where
         // disposable clone.
         let sig_parser = self.ser.0.sig_parser.clone();
         self.ser.0.sig_parser = sig_parser.clone();
+        let index = self.index;
+        self.index += 1;
 
-        value.serialize(&mut *self.ser)?;
+        value
+            .serialize(&mut *self.ser)
+            .map_err(|e| e.in_path(PathSegment::Element(index)))?;
         self.ser.0.sig_parser = sig_parser;
 
         if let Some(ref mut offsets) = self.offsets {
@@ -434,6 +442,8 @@ pub struct StructSerializer<'ser, 'sig, 'b, B, W> {
     end_parens: bool,
     // All offsets
     offsets: Option<FramingOffsets>,
+    // index of the next field
+    index: usize,
 }
 
 impl<'ser, 'sig, 'b, B, W> StructSerializer<'ser, 'sig, 'b, B, W>
@@ -468,7 +478,9 @@ where
                     value_sign: None,
                     b: PhantomData,
                 });
-                value.serialize(&mut ser)?;
+                value
+                    .serialize(&mut ser)
+                    .map_err(|e| e.in_path(PathSegment::Variant))?;
                 self.ser.0.bytes_written = ser.0.bytes_written;
                 self.ser.0.fds.extend(fds.iter());
 
@@ -485,7 +497,16 @@ where
                 let fixed_sized_element =
                     crate::utils::is_fixed_sized_signature(&element_signature)?;
 
-                value.serialize(&mut *self.ser)?;
+                if name == Some("zvariant::Value::Signature") {
+                    value.serialize(&mut *self.ser)?;
+                } else {
+                    let index = self.index;
+                    self.index += 1;
+
+                    value
+                        .serialize(&mut *self.ser)
+                        .map_err(|e| e.in_path(PathSegment::Field(index)))?;
+                }
 
                 if let Some(ref mut offsets) = self.offsets {
                     if !fixed_sized_element {
@@ -576,13 +597,31 @@ where
 
         // skip `{`
         self.ser.0.sig_parser.skip_char()?;
+        let index = self.index;
+        self.index += 1;
 
-        key.serialize(&mut *self.ser)?;
+        key.serialize(&mut *self.ser)
+            .map_err(|e| e.in_path(PathSegment::Element(index)))?;
         self.ser.0.sig_parser = sig_parser;
 
         Ok(())
     }
 
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<()>
+    where
+        K: ?Sized + Serialize,
+        V: ?Sized + Serialize,
+    {
+        let entry_parser = self.ser.0.sig_parser.clone();
+        self.serialize_key(key)?;
+
+        self.serialize_value(value).map_err(|e| {
+            let segment = self.ser.0.key_segment(entry_parser, key, self.index - 1);
+
+            e.in_path(segment)
+        })
+    }
+
     fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
@@ -105,6 +105,17 @@ impl<'v> From<&'v String> for Value<'v> {
     }
 }
 
+#[cfg(feature = "enumflags2")]
+impl<'a, F> From<enumflags2::BitFlags<F>> for Value<'a>
+where
+    F: enumflags2::RawBitFlags,
+    F::Type: Into<Value<'a>>,
+{
+    fn from(flags: enumflags2::BitFlags<F>) -> Self {
+        flags.bits().into()
+    }
+}
+
 #[cfg(feature = "gvariant")]
 impl<'v, V> From<Option<V>> for Value<'v>
 where
@@ -92,7 +92,8 @@
 //! | Feature | Description |
 //! | ---     | ----------- |
 //! | arrayvec | Implement `Type` for [`arrayvec::ArrayVec`] and [`arrayvec::ArrayString`] |
-//! | enumflags2 | Implement `Type` for [`struct@enumflags2::BitFlags<F>`] |
+//! | enumflags2 | Support [`struct@enumflags2::BitFlags<F>`], encoded as its integer representation, and [`TruncatedBitFlags`] |
+//! | time | Timestamps as [`time::OffsetDateTime`], encoded in seconds, milliseconds or microseconds since the Unix epoch ([`UnixSeconds`], [`UnixMillis`], [`UnixMicros`]) or as RFC 3339 strings ([`Rfc3339Timestamp`]) |
 //!
 //! # Portability
 //!
@@ -118,6 +119,7 @@
 //! [`arrayvec::ArrayVec`]: https://docs.rs/arrayvec/0.5.1/arrayvec/struct.ArrayVec.html
 //! [`arrayvec::ArrayString`]: https://docs.rs/arrayvec/0.5.1/arrayvec/struct.ArrayString.html
 //! [`Value` module documentation]: enum.Value.html
+//! [`time::OffsetDateTime`]: https://docs.rs/time/0.3/time/struct.OffsetDateTime.html
 
 #[macro_use]
 mod utils;
@@ -181,15 +183,41 @@ pub use error::*;
 mod r#type;
 pub use r#type::*;
 
+mod static_type;
+pub use static_type::*;
+
+mod const_signature;
+pub use const_signature::*;
+
 mod from_value;
 pub use from_value::*;
 
+mod coerce;
+pub use coerce::*;
+
 mod into_value;
 pub use into_value::*;
 
 mod owned_value;
 pub use owned_value::*;
 
+mod bitflags;
+pub use bitflags::*;
+
+mod timestamp;
+pub use timestamp::*;
+
+mod walk;
+pub use walk::*;
+
+mod transform;
+pub use transform::*;
+
+mod pretty;
+pub use pretty::*;
+
+mod parse;
+
 #[cfg(feature = "gvariant")]
 mod framing_offset_size;
 #[cfg(feature = "gvariant")]
@@ -197,8 +225,12 @@ mod framing_offsets;
 mod signature_parser;
 
 // FIXME: Re-export derive macros from the crate root with the next breaking-change release.
+#[cfg(feature = "derive")]
 pub mod derive {
-    pub use zvariant_derive::{DeserializeDict, OwnedValue, SerializeDict, Type, TypeDict, Value};
+    pub use zvariant_derive::{
+        DeserializeDict, DeserializeNewtype, OwnedValue, SerializeDict, StaticType, Type, TypeDict,
+        Value,
+    };
 }
 
 // Required for the macros to function within this crate.
@@ -214,8 +246,12 @@ pub mod export {
 #[allow(clippy::blacklisted_name)]
 mod tests {
     use std::{
+        borrow::Cow,
         collections::HashMap,
         convert::{TryFrom, TryInto},
+        os::unix::io::AsRawFd,
+        rc::Rc,
+        sync::Arc,
     };
 
     #[cfg(feature = "arrayvec")]
@@ -228,6 +264,7 @@ mod tests {
     use glib::{Bytes, FromVariant, Variant};
     use serde::{Deserialize, Serialize};
 
+    #[cfg(feature = "derive")]
     use zvariant_derive::{DeserializeDict, SerializeDict, Type, TypeDict};
 
     use crate::{
@@ -236,8 +273,8 @@ mod tests {
     };
 
     use crate::{
-        Array, Basic, DeserializeValue, Dict, EncodingContext as Context, EncodingFormat, Error,
-        Fd, ObjectPath, Result, SerializeValue, Signature, Str, Structure, Type, Value,
+        Array, Dict, EncodingContext as Context, EncodingFormat, Error, Fd, ObjectPath, Result,
+        Signature, StaticType, Str, Structure, Type, Value,
     };
 
     // Test through both generic and specific API (wrt byte order)
@@ -407,6 +444,19 @@ mod tests {
         basic_type_test!(LE, GVariant, Fd::from(42), 4, Fd, 4, Fd, 6);
     }
 
+    #[test]
+    fn fd_not_duplicated() {
+        let ctxt = Context::<LE>::new_dbus(0);
+        let stdout = std::io::stdout();
+        let fd = Fd::from(&stdout);
+        let (encoded, fds) = to_bytes_fds(ctxt, &(fd, fd)).unwrap();
+        // The same raw FD is referred to twice but only recorded once.
+        assert_eq!(fds, vec![stdout.as_raw_fd()]);
+
+        let decoded: (Fd, Fd) = from_slice_fds(&encoded, Some(&fds), ctxt).unwrap();
+        assert_eq!(decoded, (fd, fd));
+    }
+
     #[test]
     fn u16_value() {
         let encoded = basic_type_test!(BE, DBus, 0xABBA_u16, 2, u16, 2, U16, 6);
@@ -556,6 +606,42 @@ mod tests {
         assert_eq!(&decoded, "hello world!");
     }
 
+    #[test]
+    fn smart_ptr_value() {
+        let string: Arc<str> = Arc::from("hello world");
+        basic_type_test!(LE, DBus, string, 16, Arc<str>, 4);
+        basic_type_test!(LE, DBus, string, 16, String, 4);
+        let string: Rc<str> = Rc::from("hello world");
+        basic_type_test!(LE, DBus, string, 16, Rc<str>, 4);
+        let string: Box<str> = Box::from("hello world");
+        basic_type_test!(LE, DBus, string, 16, Box<str>, 4);
+        let string: Cow<'_, str> = Cow::Borrowed("hello world");
+        basic_type_test!(LE, DBus, string, 16, Cow<'_, str>, 4);
+        #[cfg(feature = "gvariant")]
+        basic_type_test!(LE, GVariant, string, 12, Arc<str>, 1);
+
+        let bytes: Arc<[u8]> = Arc::from(&b"hello"[..]);
+        assert_eq!(<Arc<[u8]>>::signature(), "ay");
+        basic_type_test!(LE, DBus, bytes, 9, Arc<[u8]>, 4);
+        basic_type_test!(LE, DBus, bytes, 9, Vec<u8>, 4);
+
+        // Arguments in method calls are typically passed by reference.
+        let strings: Vec<Arc<str>> = vec![Arc::from("hello"), Arc::from("world")];
+        assert_eq!(<&Vec<Arc<str>>>::signature(), "as");
+        assert_eq!(<&Arc<str>>::signature(), "s");
+        let ctxt = Context::<LE>::new_dbus(0);
+        let encoded = to_bytes(ctxt, &strings).unwrap();
+        assert_eq!(encoded, to_bytes(ctxt, &["hello", "world"][..]).unwrap());
+        let decoded: Vec<Arc<str>> = from_slice(&encoded, ctxt).unwrap();
+        assert_eq!(decoded, strings);
+
+        let nested = Box::new(Rc::new((42u32, Arc::<str>::from("hi"))));
+        assert_eq!(<Box<Rc<(u32, Arc<str>)>>>::signature(), "(us)");
+        let encoded = to_bytes(ctxt, &nested).unwrap();
+        let decoded: (u32, String) = from_slice(&encoded, ctxt).unwrap();
+        assert_eq!(decoded, (42, String::from("hi")));
+    }
+
     #[test]
     fn signature_value() {
         let sig = Signature::try_from("yys").unwrap();
@@ -620,6 +706,75 @@ mod tests {
         }
     }
 
+    #[test]
+    fn object_path_macro() {
+        const PATH: ObjectPath<'static> = crate::object_path!("/org/zbus/Test_1");
+        static ROOT: ObjectPath<'static> = crate::object_path!("/");
+        assert_eq!(PATH, ObjectPath::try_from("/org/zbus/Test_1").unwrap());
+        assert_eq!(ROOT.as_str(), "/");
+
+        // `is_valid` agrees with the `TryFrom` implementations.
+        for path in &[
+            "/", "/a", "/a/b_0", "", "a", "//", "/a/", "/a//b", "/a.b", "/a-b", "/é",
+        ] {
+            assert_eq!(
+                ObjectPath::is_valid(path),
+                ObjectPath::try_from(*path).is_ok(),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn validated_strings() {
+        let ao = Signature::try_from("ao").unwrap();
+        let ag = Signature::try_from("ag").unwrap();
+        let invalid = vec!["/valid", "in/valid"];
+
+        let mut ctxts = vec![Context::<LE>::new_dbus(0)];
+        #[cfg(feature = "gvariant")]
+        ctxts.push(Context::<LE>::new_gvariant(0));
+        for ctxt in ctxts {
+            // Whatever the type decoded into, invalid paths and signatures are rejected.
+            let encoded = to_bytes(ctxt, &invalid).unwrap();
+            let res: Result<Vec<String>> = from_slice_for_signature(&encoded, ctxt, &ao);
+            assert!(res.is_err());
+            let res: Result<Vec<&str>> = from_slice_for_signature(&encoded, ctxt, &ao);
+            assert!(res.is_err());
+            let res: Result<Vec<crate::OwnedObjectPath>> =
+                from_slice_for_signature(&encoded, ctxt, &ao);
+            assert!(res.is_err());
+            let res: Result<Vec<String>> = from_slice_for_signature(&encoded, ctxt, &ag);
+            assert!(res.is_err());
+            let res: Result<Vec<Signature<'_>>> = from_slice_for_signature(&encoded, ctxt, &ag);
+            assert!(res.is_err());
+            let v = Value::from(ObjectPath::from_str_unchecked("in/valid"));
+            let encoded = to_bytes(ctxt, &v).unwrap();
+            assert!(from_slice::<_, Value<'_>>(&encoded, ctxt).is_err());
+
+            // Unless the data is trusted.
+            let encoded = to_bytes(ctxt, &invalid).unwrap();
+            let paths: Vec<ObjectPath<'_>> =
+                from_slice_for_signature(&encoded, ctxt.trusted(), &ao).unwrap();
+            assert_eq!(paths[1].as_str(), "in/valid");
+            let strings: Vec<String> =
+                from_slice_for_signature(&encoded, ctxt.trusted(), &ao).unwrap();
+            assert_eq!(strings, invalid);
+
+            // Valid ones are decoded the same, trusted or not.
+            let valid = vec![
+                ObjectPath::try_from("/a").unwrap(),
+                "/b/c".try_into().unwrap(),
+            ];
+            let encoded = to_bytes(ctxt, &valid).unwrap();
+            let paths: Vec<crate::OwnedObjectPath> = from_slice(&encoded, ctxt).unwrap();
+            assert_eq!(paths[1].as_str(), "/b/c");
+            let paths: Vec<ObjectPath<'_>> = from_slice(&encoded, ctxt.trusted()).unwrap();
+            assert_eq!(paths, valid);
+        }
+    }
+
     #[test]
     fn unit() {
         let ctxt = Context::<BE>::new_dbus(0);
@@ -1032,16 +1187,138 @@ mod tests {
         assert_eq!(inner.0, 1);
         assert_eq!(inner.1, 2);
 
-        #[derive(Serialize, Deserialize, Type, PartialEq, Debug)]
-        struct Foo {
-            val: u32,
+        #[cfg(feature = "derive")]
+        {
+            use crate::{DeserializeValue, SerializeValue};
+
+            #[derive(Serialize, Deserialize, Type, PartialEq, Debug)]
+            struct Foo {
+                val: u32,
+            }
+
+            let foo = Foo { val: 99 };
+            let v = SerializeValue(&foo);
+            let encoded = to_bytes(ctxt, &v).unwrap();
+            let decoded: DeserializeValue<'_, Foo> = from_slice(&encoded, ctxt).unwrap();
+            assert_eq!(decoded.0, foo);
         }
+    }
 
-        let foo = Foo { val: 99 };
-        let v = SerializeValue(&foo);
-        let encoded = to_bytes(ctxt, &v).unwrap();
-        let decoded: DeserializeValue<'_, Foo> = from_slice(&encoded, ctxt).unwrap();
-        assert_eq!(decoded.0, foo);
+    #[test]
+    fn structure_seed() {
+        use serde::de::DeserializeSeed;
+
+        let ctxt = Context::<LE>::new_dbus(0);
+        let mut map = HashMap::new();
+        map.insert("one", 1u8);
+        let encoded = to_bytes(ctxt, &("a", vec![1u32, 2], (true, 3i64), map.clone())).unwrap();
+
+        let signature = Signature::try_from("(saub(bx)a{sy})").unwrap();
+        let seed = crate::StructureSeed::try_from(signature.clone()).unwrap();
+        let mut de = crate::Deserializer::new(&encoded, None, &signature, ctxt);
+        let s = seed.deserialize(&mut de).unwrap();
+        assert_eq!(s.full_signature(), &signature);
+        assert_eq!(
+            s.into_fields(),
+            vec![
+                Value::from("a"),
+                Value::from(vec![1u32, 2]),
+                Value::from((true, 3i64)),
+                Value::from(map),
+            ]
+        );
+
+        for signature in &["", "s", "(s", "a(s)"] {
+            let signature = Signature::from_str_unchecked(signature);
+            let err = crate::StructureSeed::try_from(signature).unwrap_err();
+            assert_eq!(err, Error::IncorrectType);
+        }
+    }
+
+    #[test]
+    fn checked_builders() {
+        use crate::{ArrayBuilder, OwnedValue, PathSegment, StructureBuilder};
+
+        let mismatch = |expected: &str, signature: &str| {
+            Error::SignatureMismatch(
+                Signature::try_from(expected).unwrap().to_owned(),
+                Signature::try_from(signature).unwrap().to_owned(),
+            )
+        };
+        let props = |populated: bool| {
+            let mut props = HashMap::new();
+            if populated {
+                props.insert("Version", Value::from(2u32));
+            }
+            props
+        };
+
+        // The element signature is locked by the first element.
+        let mut builder = ArrayBuilder::new();
+        assert_eq!(builder.element_signature(), None);
+        builder.push(Value::from(vec!["a", "b"])).unwrap();
+        assert_eq!(builder.element_signature().unwrap(), "as");
+        // An empty `Vec<Value>` is an array of variants, whatever it's meant to hold.
+        let e = builder
+            .push(Value::from(Vec::<Value<'_>>::new()))
+            .unwrap_err();
+        assert_eq!(e, mismatch("as", "av").in_path(PathSegment::Element(1)));
+        assert_eq!(
+            e.to_string(),
+            "Signature `av` doesn't match the expected `as`: `v` instead of `s` after `a` \
+             (at element 1)",
+        );
+        // A value wrapped in a variant twice.
+        let mut builder = ArrayBuilder::with_element_signature(Signature::try_from("u").unwrap());
+        builder.push(Value::from(1u32)).unwrap();
+        let e = builder.push(Value::new(Value::from(2u32))).unwrap_err();
+        assert_eq!(e, mismatch("u", "v").in_path(PathSegment::Element(1)));
+        assert_eq!(builder.build().unwrap().len(), 1);
+
+        // An array of structures, where one has an empty dictionary of the wrong value type.
+        let signature = Signature::try_from("(sa{sv})").unwrap();
+        let dicts = vec![
+            Value::from(props(true)),
+            Value::from(props(false)),
+            Value::from(HashMap::<&str, &str>::new()),
+        ];
+        let mut builder = Array::builder();
+        for (i, dict) in dicts.into_iter().enumerate() {
+            let mut structure = Structure::builder();
+            assert_eq!(structure.push(Value::from("name")), "(s)");
+            structure.push(dict);
+            match structure.build_checked(&signature) {
+                Ok(structure) => builder.push(Value::Structure(structure)).unwrap(),
+                Err(e) => {
+                    assert_eq!(i, 2);
+                    assert_eq!(e, mismatch("a{sv}", "a{ss}").in_path(PathSegment::Field(1)));
+                }
+            }
+        }
+        let array = builder.build().unwrap();
+        assert_eq!(array.full_signature(), "a(sa{sv})");
+        let ctxt = Context::<LE>::new_dbus(0);
+        let encoded = to_bytes_for_signature(ctxt, array.full_signature(), &array).unwrap();
+        let decoded: Vec<(String, HashMap<String, OwnedValue>)> =
+            from_slice(&encoded, ctxt).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].1["Version"].downcast_ref::<u32>(), Some(&2));
+        assert!(decoded[1].1.is_empty());
+
+        // Too few fields, with all of them matching, is a mismatch of the whole structure.
+        let e = StructureBuilder::new()
+            .add_field("a")
+            .build_checked(&signature)
+            .unwrap_err();
+        assert_eq!(e, mismatch("(sa{sv})", "(s)"));
+        assert!(e.path().is_empty());
+
+        // An empty array needs an element signature.
+        ArrayBuilder::new().build().unwrap_err();
+        let array = ArrayBuilder::with_element_signature(signature.clone())
+            .build()
+            .unwrap();
+        assert_eq!(array.full_signature(), "a(sa{sv})");
     }
 
     #[test]
@@ -1161,60 +1438,65 @@ mod tests {
             panic!();
         }
 
-        #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
-        struct Test {
-            process_id: Option<u32>,
-            group_id: Option<u32>,
-            user: String,
-        }
-        let test = Test {
-            process_id: Some(42),
-            group_id: None,
-            user: "me".to_string(),
-        };
-
-        let encoded = to_bytes(ctxt, &test).unwrap();
-        assert_eq!(encoded.len(), 51);
-
-        let decoded: HashMap<&str, Value<'_>> = from_slice(&encoded, ctxt).unwrap();
-        assert_eq!(decoded["process_id"], Value::U32(42));
-        assert_eq!(decoded["user"], Value::new("me"));
-        assert!(!decoded.contains_key("group_id"));
-
-        let decoded: Test = from_slice(&encoded, ctxt).unwrap();
-        assert_eq!(decoded, test);
-
-        #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
-        struct TestMissing {
-            process_id: Option<u32>,
-            group_id: Option<u32>,
-            user: String,
-            quota: u8,
-        }
-        let decoded: Result<TestMissing> = from_slice(&encoded, ctxt);
-        assert_eq!(
-            decoded.unwrap_err(),
-            Error::Message("missing field `quota`".to_string())
-        );
+        #[cfg(feature = "derive")]
+        {
+            #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
+            struct Test {
+                process_id: Option<u32>,
+                group_id: Option<u32>,
+                user: String,
+            }
+            let test = Test {
+                process_id: Some(42),
+                group_id: None,
+                user: "me".to_string(),
+            };
+
+            let encoded = to_bytes(ctxt, &test).unwrap();
+            assert_eq!(encoded.len(), 51);
+
+            let decoded: HashMap<&str, Value<'_>> = from_slice(&encoded, ctxt).unwrap();
+            assert_eq!(decoded["process_id"], Value::U32(42));
+            assert_eq!(decoded["user"], Value::new("me"));
+            assert!(!decoded.contains_key("group_id"));
+
+            let decoded: Test = from_slice(&encoded, ctxt).unwrap();
+            assert_eq!(decoded, test);
+
+            #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
+            struct TestMissing {
+                process_id: Option<u32>,
+                group_id: Option<u32>,
+                user: String,
+                quota: u8,
+            }
+            let decoded: Result<TestMissing> = from_slice(&encoded, ctxt);
+            assert_eq!(
+                decoded.unwrap_err(),
+                Error::Message("missing field `quota`".to_string())
+            );
 
-        #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
-        struct TestSkipUnknown {
-            process_id: Option<u32>,
-            group_id: Option<u32>,
-        }
-        let _: TestSkipUnknown = from_slice(&encoded, ctxt).unwrap();
+            #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
+            struct TestSkipUnknown {
+                process_id: Option<u32>,
+                group_id: Option<u32>,
+            }
+            let _: TestSkipUnknown = from_slice(&encoded, ctxt).unwrap();
 
-        #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
-        #[zvariant(deny_unknown_fields)]
-        struct TestUnknown {
-            process_id: Option<u32>,
-            group_id: Option<u32>,
+            #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
+            #[zvariant(deny_unknown_fields)]
+            struct TestUnknown {
+                process_id: Option<u32>,
+                group_id: Option<u32>,
+            }
+            let decoded: Result<TestUnknown> = from_slice(&encoded, ctxt);
+            assert_eq!(
+                decoded.unwrap_err(),
+                Error::Message(
+                    "unknown field `user`, expected `process_id` or `group_id`".to_string()
+                )
+            );
         }
-        let decoded: Result<TestUnknown> = from_slice(&encoded, ctxt);
-        assert_eq!(
-            decoded.unwrap_err(),
-            Error::Message("unknown field `user`, expected `process_id` or `group_id`".to_string())
-        );
     }
 
     #[test]
@@ -1261,6 +1543,133 @@ mod tests {
         assert_eq!(v, Value::U64(0xFEFE));
     }
 
+    #[cfg(feature = "enumflags2")]
+    #[test]
+    fn bitflags() {
+        use crate::{OwnedValue, TruncatedBitFlags};
+        use enumflags2::BitFlags;
+
+        #[repr(u16)]
+        #[derive(BitFlags, Copy, Clone, Debug, PartialEq)]
+        enum Flags {
+            A = 0x1,
+            B = 0x4,
+        }
+
+        assert_eq!(<BitFlags<Flags>>::signature(), "q");
+        assert_eq!(<TruncatedBitFlags<Flags>>::signature(), "q");
+
+        let ctxt = Context::<LE>::new_dbus(0);
+        let flags = Flags::A | Flags::B;
+        let encoded = to_bytes(ctxt, &flags).unwrap();
+        assert_eq!(encoded, [0x5, 0]);
+        let decoded: BitFlags<Flags> = from_slice(&encoded, ctxt).unwrap();
+        assert_eq!(decoded, flags);
+
+        // Unknown bits are an error, unless truncated.
+        let encoded = to_bytes(ctxt, &0x7u16).unwrap();
+        assert!(from_slice::<_, BitFlags<Flags>>(&encoded, ctxt).is_err());
+        let decoded: TruncatedBitFlags<Flags> = from_slice(&encoded, ctxt).unwrap();
+        assert_eq!(decoded.0, flags);
+        let encoded = to_bytes(ctxt, &decoded).unwrap();
+        assert_eq!(encoded, [0x5, 0]);
+
+        let v = Value::from(flags);
+        assert_eq!(v, Value::U16(0x5));
+        assert_eq!(<BitFlags<Flags>>::try_from(v).unwrap(), flags);
+        assert_eq!(Value::from(TruncatedBitFlags(flags)), Value::U16(0x5));
+        assert!(<BitFlags<Flags>>::try_from(Value::U16(0x7)).is_err());
+        let truncated = <TruncatedBitFlags<Flags>>::try_from(Value::U16(0x7)).unwrap();
+        assert_eq!(truncated.0, flags);
+        let ov = OwnedValue::from(Value::U16(0x7));
+        assert_eq!(<TruncatedBitFlags<Flags>>::try_from(ov).unwrap().0, flags);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn timestamps() {
+        use crate::{OwnedValue, Rfc3339Timestamp, UnixMicros, UnixMillis, UnixSeconds};
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+        use time::OffsetDateTime;
+
+        assert_eq!(UnixSeconds::signature(), "t");
+        assert_eq!(UnixMillis::signature(), "t");
+        assert_eq!(UnixMicros::signature(), "t");
+        assert_eq!(Rfc3339Timestamp::signature(), "s");
+
+        let ctxt = Context::<LE>::new_dbus(0);
+        let time = OffsetDateTime::from_unix_timestamp_nanos(1_634_205_600_123_456_789).unwrap();
+        let encoded = to_bytes(ctxt, &UnixSeconds(time)).unwrap();
+        assert_eq!(from_slice::<_, u64>(&encoded, ctxt).unwrap(), 1_634_205_600);
+        let encoded = to_bytes(ctxt, &UnixMillis(time)).unwrap();
+        assert_eq!(
+            from_slice::<_, u64>(&encoded, ctxt).unwrap(),
+            1_634_205_600_123
+        );
+        let decoded: UnixMillis = from_slice(&encoded, ctxt).unwrap();
+        assert_eq!(decoded.0.unix_timestamp_nanos(), 1_634_205_600_123_000_000);
+        let encoded = to_bytes(ctxt, &UnixMicros(time)).unwrap();
+        assert_eq!(
+            from_slice::<_, u64>(&encoded, ctxt).unwrap(),
+            1_634_205_600_123_456
+        );
+        let decoded: UnixMicros = from_slice(&encoded, ctxt).unwrap();
+        assert_eq!(decoded.units().unwrap(), 1_634_205_600_123_456);
+        let system_time = SystemTime::from(decoded);
+        assert_eq!(
+            system_time,
+            UNIX_EPOCH + Duration::from_micros(1_634_205_600_123_456)
+        );
+        assert_eq!(UnixMicros::from(system_time), decoded);
+
+        // Out-of-range values are errors, in both directions.
+        let encoded = to_bytes(ctxt, &u64::MAX).unwrap();
+        assert!(from_slice::<_, UnixMicros>(&encoded, ctxt).is_err());
+        assert!(UnixSeconds::from_units(u64::MAX).is_err());
+        let before_epoch = OffsetDateTime::from_unix_timestamp(-1).unwrap();
+        assert!(to_bytes(ctxt, &UnixMicros(before_epoch)).is_err());
+        assert!(Value::try_from(UnixSeconds(before_epoch)).is_err());
+        assert_eq!(
+            UnixMicros::from_units(0).unwrap().0,
+            OffsetDateTime::UNIX_EPOCH
+        );
+
+        let v = Value::try_from(UnixMicros(time)).unwrap();
+        assert_eq!(v, Value::U64(1_634_205_600_123_456));
+        assert_eq!(UnixMicros::try_from(v).unwrap(), decoded);
+        assert!(UnixMicros::try_from(Value::from("now")).is_err());
+
+        let time = Rfc3339Timestamp(time);
+        let encoded = to_bytes(ctxt, &time).unwrap();
+        let s: String = from_slice(&encoded, ctxt).unwrap();
+        assert_eq!(s, "2021-10-14T10:00:00.123456789Z");
+        let decoded: Rfc3339Timestamp = from_slice(&encoded, ctxt).unwrap();
+        assert_eq!(decoded, time);
+        let v = Value::try_from(time).unwrap();
+        assert_eq!(
+            Rfc3339Timestamp::try_from(OwnedValue::from(v)).unwrap(),
+            time
+        );
+        let encoded = to_bytes(ctxt, "yesterday").unwrap();
+        assert!(from_slice::<_, Rfc3339Timestamp>(&encoded, ctxt).is_err());
+
+        // The timestamp properties of systemd's `org.freedesktop.systemd1.Manager`, as returned by
+        // `GetAll`.
+        let encoded = std::fs::read("../test-data/systemd-manager-timestamps.dump").unwrap();
+        let props: HashMap<String, OwnedValue> = from_slice(&encoded, ctxt).unwrap();
+        let timestamp = |name: &str| UnixMicros::try_from(props[name].clone()).unwrap();
+        assert_eq!(
+            timestamp("KernelTimestamp").units().unwrap(),
+            1_634_205_600_123_456
+        );
+        assert_eq!(
+            timestamp("UserspaceTimestamp").0,
+            OffsetDateTime::from_unix_timestamp_nanos(1_634_205_602_345_678_000).unwrap()
+        );
+        assert!(timestamp("FinishTimestamp") > timestamp("UserspaceTimestamp"));
+        assert!(UnixMicros::try_from(props["Version"].clone()).is_err());
+    }
+
     #[test]
     fn enums() {
         // TODO: Document enum handling.
@@ -1306,6 +1715,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "derive")]
     fn derive() {
         use serde::{Deserialize, Serialize};
         use serde_repr::{Deserialize_repr, Serialize_repr};
@@ -1412,10 +1822,47 @@ mod tests {
         let v = vec![1, 2];
         let l = crate::serialized_size(ctxt, &('a', "abc", &v)).unwrap();
         assert_eq!(l, 28);
+
+        // Without the padding.
+        assert_eq!(Value::U8(1).serialized_size_hint(), 1);
+        assert_eq!(Value::Bool(true).serialized_size_hint(), 4);
+        assert_eq!(Value::U64(1).serialized_size_hint(), 8);
+        assert_eq!(Value::from(Fd::from(&stdout)).serialized_size_hint(), 4);
+        assert_eq!(Value::from("abc").serialized_size_hint(), 8);
+        let path = ObjectPath::try_from("/a").unwrap();
+        assert_eq!(Value::from(path).serialized_size_hint(), 7);
+        let v = Value::from(vec![1_u32, 2]);
+        assert_eq!(v.serialized_size_hint(), 12);
+        // The signature `au` and the array.
+        assert_eq!(Value::new(v).serialized_size_hint(), 16);
+        let mut dict = Dict::new(<&str>::signature(), u32::signature());
+        dict.add("a", 1_u32).unwrap();
+        assert_eq!(Value::from(dict).serialized_size_hint(), 4 + 6 + 4);
+        let s = Value::Structure(Structure::from((1_u8, "ab")));
+        assert_eq!(s.serialized_size_hint(), 1 + 7);
+        // As much as it takes to serialize in the D-Bus format, if not for the padding.
+        let v = Value::from(vec!["a", "bc"]);
+        let ctxt = Context::<LE>::new_dbus(0);
+        assert_eq!(
+            crate::serialized_size(ctxt, &v).unwrap(),
+            4 + 4 + 4 + 2 + 2 + 4 + 3
+        );
+        assert_eq!(v.serialized_size_hint(), 4 + 6 + 7);
+        #[cfg(feature = "gvariant")]
+        {
+            use crate::Maybe;
+
+            assert_eq!(
+                Value::Maybe(Maybe::just(1_u8.into())).serialized_size_hint(),
+                1
+            );
+            let nothing = Maybe::nothing(u8::signature());
+            assert_eq!(Value::Maybe(nothing).serialized_size_hint(), 0);
+        }
     }
 
     #[test]
-    #[cfg(feature = "serde_bytes")]
+    #[cfg(all(feature = "serde_bytes", feature = "derive"))]
     fn serde_bytes() {
         use serde::{Deserialize, Serialize};
         use serde_bytes::*;
@@ -1447,7 +1894,7 @@ mod tests {
     }
 
     #[test]
-    #[cfg(all(feature = "serde_bytes", feature = "gvariant"))]
+    #[cfg(all(feature = "serde_bytes", feature = "gvariant", feature = "derive"))]
     fn serde_bytes_gvariant() {
         use serde::{Deserialize, Serialize};
         use serde_bytes::*;
@@ -1634,6 +2081,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "derive")]
     fn struct_with_hashmap() {
         use serde::{Deserialize, Serialize};
 
@@ -1654,6 +2102,105 @@ mod tests {
         assert_eq!(f, foo);
     }
 
+    #[test]
+    fn error_path() {
+        use crate::PathSegment;
+
+        let mut config = HashMap::new();
+        config.insert("ip4config", vec!["a", "b"]);
+        let value = (0u32, config);
+        let path = vec![
+            PathSegment::Field(1),
+            PathSegment::Key("'ip4config'".into()),
+            PathSegment::Element(1),
+        ];
+
+        let mut ctxts = vec![Context::<LE>::new_dbus(0)];
+        #[cfg(feature = "gvariant")]
+        ctxts.push(Context::<LE>::new_gvariant(0));
+        for ctxt in ctxts {
+            // Make the second string invalid UTF-8.
+            let mut encoded = to_bytes(ctxt, &value).unwrap();
+            let pos = encoded.iter().position(|b| *b == b'b').unwrap();
+            encoded[pos] = 0xff;
+
+            let e = from_slice::<_, (u32, HashMap<&str, Vec<&str>>)>(&encoded, ctxt).unwrap_err();
+            assert_eq!(e.path(), path.as_slice());
+            assert!(matches!(&e, Error::InPath(e, _) if matches!(**e, Error::Utf8(_))));
+            assert!(e
+                .to_string()
+                .ends_with(" (at field 1 → key 'ip4config' → element 1)"));
+        }
+
+        // Mismatch with the signature given for serialization.
+        let ctxt = Context::<LE>::new_dbus(0);
+        let signature = Signature::try_from("(ua{sau})").unwrap();
+        let e = to_bytes_for_signature(ctxt, &signature, &value).unwrap_err();
+        assert_eq!(e.path()[..2], path[..2]);
+        assert_eq!(e.path()[2], PathSegment::Element(0));
+
+        // A value failing to decode in a variant, with a bool of 2.
+        let mut encoded = to_bytes(ctxt, &(7u8, Value::from(2u32))).unwrap();
+        assert_eq!(encoded[2], b'u');
+        encoded[2] = b'b';
+        let e = from_slice::<_, (u8, Value<'_>)>(&encoded, ctxt).unwrap_err();
+        assert_eq!(e.path(), &[PathSegment::Field(1), PathSegment::Variant]);
+    }
+
+    #[test]
+    fn non_zero_position() {
+        fn check<T>(value: T)
+        where
+            T: Serialize + serde::de::DeserializeOwned + Type + PartialEq + std::fmt::Debug,
+        {
+            let mut formats = vec![EncodingFormat::DBus];
+            #[cfg(feature = "gvariant")]
+            formats.push(EncodingFormat::GVariant);
+
+            for format in formats {
+                let ctxt = Context::<LE>::new(format, 0);
+                let at_0 = to_bytes(ctxt, &value).unwrap();
+
+                for position in 0..=24 {
+                    let ctxt = ctxt.with_position(position);
+                    let encoded = to_bytes(ctxt, &value).unwrap();
+                    assert_eq!(crate::serialized_size(ctxt, &value).unwrap(), encoded.len());
+                    if position % 8 == 0 {
+                        assert_eq!(encoded, at_0, "{} at {}", T::signature(), position);
+                    }
+
+                    // Decode in place, after some unrelated data.
+                    let mut data = vec![0xff; position];
+                    data.extend(&encoded);
+                    let decoded: T = from_slice(&data[position..], ctxt).unwrap();
+                    assert_eq!(decoded, value, "{} at {}", T::signature(), position);
+                }
+            }
+        }
+
+        let mut dict = HashMap::new();
+        dict.insert(String::from("hi"), 0xdead_beef_u32);
+        dict.insert(String::from("bye"), 7);
+
+        check(0xfe_u8);
+        check(true);
+        check(-3_i16);
+        check(0xdead_beef_u32);
+        check(u64::MAX);
+        check(2.5_f64);
+        check(String::from("zbus"));
+        check((7_u8, u64::MAX));
+        check((String::from("zbus"), 7_u8, -1_i64));
+        check(vec![1_u64, 2, 3]);
+        check(Vec::<u64>::new());
+        check(vec![String::from("Hello"), String::from("World")]);
+        check(dict);
+        check((
+            3_u8,
+            vec![(1_u16, String::from("one")), (2, String::from("two"))],
+        ));
+    }
+
     #[test]
     fn issue_59() {
         // Ensure we don't panic on deserializing tuple of smaller than expected length.
@@ -1664,7 +2211,72 @@ mod tests {
     }
 
     #[test]
-    #[cfg(feature = "gvariant")]
+    fn fuzz_regressions() {
+        use crate::PathSegment;
+
+        fn read(name: &str) -> Vec<u8> {
+            std::fs::read(format!("../test-data/fuzz/{}.bin", name)).unwrap()
+        }
+        let ctxt = Context::<LE>::new_dbus(0);
+
+        // Variants of arrays of variants, each array declaring close to 64 MiB. The first length
+        // is checked against the data before anything else is decoded.
+        let encoded = read("nested-variant-arrays");
+        let e = from_slice::<_, Value<'_>>(&encoded, ctxt).unwrap_err();
+        assert_eq!(
+            e,
+            Error::InPath(
+                Box::new(Error::LengthExceedsData(64 * 1024 * 1024 - 8, 20)),
+                vec![PathSegment::Variant],
+            )
+        );
+
+        // An array beyond the 64 MiB maximum of the specification.
+        let encoded = read("array-too-long");
+        let e = from_slice::<_, Vec<u8>>(&encoded, ctxt).unwrap_err();
+        assert_eq!(e, Error::ArrayTooLong(64 * 1024 * 1024 + 1));
+
+        // A string declaring more than 4 GiB.
+        let encoded = read("string-length");
+        let e = from_slice::<_, String>(&encoded, ctxt).unwrap_err();
+        assert_eq!(e, Error::LengthExceedsData(u32::MAX as usize, 3));
+
+        // A variant whose signature ends the data, without its nul byte, made the decoding of its
+        // value panic.
+        let encoded = read("variant-signature-at-end");
+        assert!(from_slice::<_, Value<'_>>(&encoded, ctxt).is_err());
+    }
+
+    #[test]
+    fn value_budget() {
+        // Arrays of empty arrays, the most values for the least data.
+        let value = vec![Vec::<u8>::new(); 100];
+        let mut ctxts = vec![Context::<LE>::new_dbus(0)];
+        #[cfg(feature = "gvariant")]
+        ctxts.push(Context::<LE>::new_gvariant(0));
+        for ctxt in ctxts {
+            let encoded = to_bytes(ctxt, &value).unwrap();
+            let decoded: Vec<Vec<u8>> = from_slice(&encoded, ctxt.with_value_budget(100)).unwrap();
+            assert_eq!(decoded, value);
+
+            let e =
+                from_slice::<_, Vec<Vec<u8>>>(&encoded, ctxt.with_value_budget(99)).unwrap_err();
+            assert_eq!(e, Error::BudgetExceeded(99));
+            assert!(e.path().is_empty());
+
+            // The values of variants are counted, however deep they're nested: the outermost
+            // variant holds 2 more here.
+            let nested = Value::new(Value::new(Value::new(1u8)));
+            let encoded = to_bytes(ctxt, &nested).unwrap();
+            let decoded: Value<'_> = from_slice(&encoded, ctxt.with_value_budget(3)).unwrap();
+            assert_eq!(decoded, nested);
+            let e = from_slice::<_, Value<'_>>(&encoded, ctxt.with_value_budget(2)).unwrap_err();
+            assert_eq!(e, Error::BudgetExceeded(2));
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "gvariant", feature = "derive"))]
     fn issue_99() {
         #[derive(Deserialize, Serialize, Type, PartialEq, Debug)]
         struct ZVStruct<'s>(#[serde(borrow)] HashMap<&'s str, Value<'s>>);
@@ -1682,7 +2294,288 @@ mod tests {
         let _: ZVStruct<'_> = from_slice_for_signature(&encoded, ctxt, &signature).unwrap();
     }
 
-    #[cfg(feature = "ostree-tests")]
+    #[derive(Default)]
+    struct EventRecorder(Vec<String>);
+
+    impl<'de> crate::Walker<'de> for EventRecorder {
+        fn basic(&mut self, value: Value<'de>) -> Result<()> {
+            let event = match value {
+                Value::U32(v) => format!("u32 {}", v),
+                Value::Str(v) => format!("str {}", v.as_str()),
+                Value::ObjectPath(v) => format!("path {}", v.as_str()),
+                v => format!("other {}", v.value_signature().as_str()),
+            };
+            self.0.push(event);
+
+            Ok(())
+        }
+
+        fn array_start(&mut self, element_signature: &Signature<'_>) -> Result<()> {
+            self.0.push(format!("array {}", element_signature.as_str()));
+
+            Ok(())
+        }
+
+        fn array_end(&mut self) -> Result<()> {
+            self.0.push("array end".into());
+
+            Ok(())
+        }
+
+        fn dict_start(
+            &mut self,
+            key_signature: &Signature<'_>,
+            value_signature: &Signature<'_>,
+        ) -> Result<()> {
+            self.0.push(format!(
+                "dict {} {}",
+                key_signature.as_str(),
+                value_signature.as_str()
+            ));
+
+            Ok(())
+        }
+
+        fn dict_entry_start(&mut self) -> Result<()> {
+            self.0.push("entry".into());
+
+            Ok(())
+        }
+
+        fn dict_entry_end(&mut self) -> Result<()> {
+            self.0.push("entry end".into());
+
+            Ok(())
+        }
+
+        fn dict_end(&mut self) -> Result<()> {
+            self.0.push("dict end".into());
+
+            Ok(())
+        }
+
+        fn struct_start(&mut self, signature: &Signature<'_>) -> Result<()> {
+            self.0.push(format!("struct {}", signature.as_str()));
+
+            Ok(())
+        }
+
+        fn struct_end(&mut self) -> Result<()> {
+            self.0.push("struct end".into());
+
+            Ok(())
+        }
+
+        fn variant_start(&mut self, signature: &Signature<'_>) -> Result<()> {
+            self.0.push(format!("variant {}", signature.as_str()));
+
+            Ok(())
+        }
+
+        fn variant_end(&mut self) -> Result<()> {
+            self.0.push("variant end".into());
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn walk() {
+        use crate::{walk_slice, walk_value};
+
+        let mut inner = HashMap::new();
+        inner.insert("count", Value::from(7u32));
+        let mut map = HashMap::new();
+        map.insert(
+            "props",
+            Value::from((
+                42u32,
+                vec!["a", "b"],
+                ObjectPath::try_from("/org/zbus").unwrap(),
+            )),
+        );
+        map.insert("inner", Value::from(inner));
+        let ctxt = Context::<LE>::new_dbus(0);
+        let encoded = to_bytes(ctxt, &map).unwrap();
+        let signature = Signature::try_from("a{sv}").unwrap();
+
+        let mut recorder = EventRecorder::default();
+        walk_slice(&encoded, ctxt, &signature, &mut recorder).unwrap();
+        let events = recorder.0;
+
+        // Entries are walked in the encoded order, which depends on the hash map.
+        let props = [
+            "entry",
+            "str props",
+            "variant (uaso)",
+            "struct (uaso)",
+            "u32 42",
+            "array s",
+            "str a",
+            "str b",
+            "array end",
+            "path /org/zbus",
+            "struct end",
+            "variant end",
+            "entry end",
+        ];
+        let inner = [
+            "entry",
+            "str inner",
+            "variant a{sv}",
+            "dict s v",
+            "entry",
+            "str count",
+            "variant u",
+            "u32 7",
+            "variant end",
+            "entry end",
+            "dict end",
+            "variant end",
+            "entry end",
+        ];
+        assert_eq!(events.first().unwrap(), "dict s v");
+        assert_eq!(events.last().unwrap(), "dict end");
+        let entries = &events[1..events.len() - 1];
+        if entries[1] == "str props" {
+            assert_eq!(entries, [&props[..], &inner[..]].concat().as_slice());
+        } else {
+            assert_eq!(entries, [&inner[..], &props[..]].concat().as_slice());
+        }
+
+        // Walking the decoded value gives the same events.
+        let decoded: Value<'_> = from_slice_for_signature(&encoded, ctxt, &signature).unwrap();
+        let mut recorder = EventRecorder::default();
+        walk_value(&decoded, &mut recorder).unwrap();
+        assert_eq!(recorder.0, events);
+
+        // Errors from the walker are returned as is.
+        struct Failing;
+
+        impl<'de> crate::Walker<'de> for Failing {
+            fn variant_start(&mut self, _signature: &Signature<'_>) -> Result<()> {
+                Err(Error::IncorrectType)
+            }
+        }
+
+        let err = walk_slice(&encoded, ctxt, &signature, &mut Failing).unwrap_err();
+        assert!(matches!(err, Error::IncorrectType), "{:?}", err);
+    }
+
+    #[test]
+    #[cfg(feature = "gvariant")]
+    fn gvariant_lenient() {
+        let ctxt = Context::<LE>::new_gvariant(0);
+        let lenient = ctxt.gvariant_lenient();
+
+        // A string that isn't nul-terminated, in a structure.
+        let encoded = b"hi!\0\x07\0\0\0\x03";
+        assert!(from_slice::<_, (&str, u32)>(encoded, ctxt).is_err());
+        let decoded: (&str, u32) = from_slice(encoded, lenient).unwrap();
+        assert_eq!(decoded, ("", 7));
+        let glib = decode_with_gvariant::<_, (String, u32)>(encoded.to_vec());
+        assert_eq!(glib, (String::new(), 7));
+
+        // A field that doesn't fit in the structure.
+        let encoded = b"hi\0\0\x07\0\x03";
+        assert!(from_slice::<_, (&str, u32)>(encoded, ctxt).is_err());
+        let decoded: (&str, u32) = from_slice(encoded, lenient).unwrap();
+        assert_eq!(decoded, ("hi", 0));
+        let glib = decode_with_gvariant::<_, (String, u32)>(encoded.to_vec());
+        assert_eq!(glib, (String::from("hi"), 0));
+
+        // Padding that isn't made of zeros.
+        let encoded = b"\x01\xff\xff\xff\x02\0\0\0";
+        assert!(matches!(
+            from_slice::<_, (u8, u32)>(encoded, ctxt),
+            Err(Error::PaddingNot0(0xff))
+        ));
+        let decoded: (u8, u32) = from_slice(encoded, lenient).unwrap();
+        assert_eq!(decoded, (1, 2));
+        assert_eq!(
+            decode_with_gvariant::<_, (u8, u32)>(encoded.to_vec()),
+            (1, 2)
+        );
+
+        // The last framing offset of an array, pointing beyond its end.
+        let encoded = b"ab\0c\0\x03\x09";
+        assert!(from_slice::<_, Vec<&str>>(encoded, ctxt).is_err());
+        let decoded: Vec<&str> = from_slice(encoded, lenient).unwrap();
+        assert!(decoded.is_empty());
+        assert!(decode_with_gvariant::<_, Vec<String>>(encoded.to_vec()).is_empty());
+
+        // Framing offsets of elements that are out of bounds, or before the element start.
+        let encoded = b"ab\0c\0\x06\x05";
+        assert!(from_slice::<_, Vec<&str>>(encoded, ctxt).is_err());
+        let decoded: Vec<&str> = from_slice(encoded, lenient).unwrap();
+        assert_eq!(decoded, ["", ""]);
+        let glib = decode_with_gvariant::<_, Vec<String>>(encoded.to_vec());
+        assert_eq!(glib, ["", ""]);
+
+        // A string with an interior nul byte.
+        let encoded = b"a\0b\0\x04";
+        assert!(from_slice::<_, Vec<&str>>(encoded, ctxt).is_err());
+        let decoded: Vec<&str> = from_slice(encoded, lenient).unwrap();
+        assert_eq!(decoded, [""]);
+        assert_eq!(
+            decode_with_gvariant::<_, Vec<String>>(encoded.to_vec()),
+            [""]
+        );
+
+        // An array of fixed-sized elements, ending in the middle of one.
+        let encoded = b"\x01\0\0\0\x02\0";
+        assert!(from_slice::<_, Vec<u32>>(encoded, ctxt).is_err());
+        let decoded: Vec<u32> = from_slice(encoded, lenient).unwrap();
+        assert!(decoded.is_empty());
+        assert!(decode_with_gvariant::<_, Vec<u32>>(encoded.to_vec()).is_empty());
+
+        // A dictionary entry with its key framing offset out of bounds.
+        let encoded = b"k\0\0\0\x07\0\0\0\x0f\x09";
+        assert!(from_slice::<_, HashMap<&str, u32>>(encoded, ctxt).is_err());
+        let decoded: HashMap<&str, u32> = from_slice(encoded, lenient).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[""], 0);
+        let glib = decode_with_gvariant::<_, HashMap<String, u32>>(encoded.to_vec());
+        assert_eq!(glib.len(), 1);
+        assert_eq!(glib[""], 0);
+
+        // A maybe of fixed-sized type that is too short, and one of variable-sized type with a
+        // trailing byte that isn't 0.
+        let encoded = b"\x01\0\0";
+        assert!(from_slice::<_, Option<u32>>(encoded, ctxt).is_err());
+        let decoded: Option<u32> = from_slice(encoded, lenient).unwrap();
+        assert_eq!(decoded, None);
+        assert_eq!(
+            decode_with_gvariant::<_, Option<u32>>(encoded.to_vec()),
+            None
+        );
+        let encoded = b"hi\0\x01";
+        assert!(from_slice::<_, Option<&str>>(encoded, ctxt).is_err());
+        let decoded: Option<&str> = from_slice(encoded, lenient).unwrap();
+        assert_eq!(decoded, Some("hi"));
+        let glib = decode_with_gvariant::<_, Option<String>>(encoded.to_vec());
+        assert_eq!(glib, Some(String::from("hi")));
+
+        // An invalid object path.
+        let encoded = b"foo\0";
+        assert!(from_slice::<_, ObjectPath<'_>>(encoded, ctxt).is_err());
+        let decoded: ObjectPath<'_> = from_slice(encoded, lenient).unwrap();
+        assert_eq!(decoded.as_str(), "/");
+
+        // Fixed-sized data that is too long. GLib reads the default value here, since it also
+        // expects the trailing padding of fixed-sized structures, which we don't write. So we
+        // ignore the extra bytes instead.
+        let encoded = b"\x07\0\0\0\0";
+        let decoded: u32 = from_slice(encoded, lenient).unwrap();
+        assert_eq!(decoded, 7);
+
+        // Data that is missing entirely reads as the default value, except for variants.
+        let decoded: (u32, Vec<&str>, (&str, u8)) = from_slice(b"", lenient).unwrap();
+        assert_eq!(decoded, (0, vec![], ("", 0)));
+        assert!(from_slice::<_, Value<'_>>(b"", lenient).is_err());
+    }
+
+    #[cfg(all(feature = "ostree-tests", feature = "derive"))]
     #[test]
     fn ostree_de() {
         #[derive(Deserialize, Serialize, Type, PartialEq, Debug)]
@@ -94,6 +94,10 @@ impl<'a> Maybe<'a> {
         &self.value_signature
     }
 
+    pub(crate) fn into_inner(self) -> Option<Value<'a>> {
+        *self.value
+    }
+
     pub(crate) fn to_owned(&self) -> Maybe<'static> {
         Maybe {
             value_signature: self.value_signature.to_owned(),
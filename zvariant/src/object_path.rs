@@ -9,7 +9,7 @@ use serde::{
 use static_assertions::assert_impl_all;
 use std::borrow::Cow;
 
-use crate::{Basic, EncodingFormat, Error, Result, Signature, Type};
+use crate::{Basic, EncodingFormat, Error, Result, Signature, StaticType, Type};
 
 /// String that identifies objects at a given destination on the D-Bus bus.
 ///
@@ -69,6 +69,41 @@ impl<'a> ObjectPath<'a> {
         Self::from_bytes_unchecked(path.as_bytes())
     }
 
+    /// Create a new `ObjectPath` from a static string, in a const context.
+    ///
+    /// Since the passed string is not checked for correctness, prefer using the [`object_path!`]
+    /// macro, which checks it at compile time.
+    ///
+    /// [`object_path!`]: macro.object_path.html
+    pub const fn from_static_str_unchecked(path: &'static str) -> ObjectPath<'static> {
+        ObjectPath(Cow::Borrowed(path.as_bytes()))
+    }
+
+    /// Whether `path` is a valid object path.
+    ///
+    /// This is the same check as the `TryFrom` implementations, as a `const fn`.
+    pub const fn is_valid(path: &str) -> bool {
+        let path = path.as_bytes();
+        if path.is_empty() || path[0] != b'/' {
+            return false;
+        }
+
+        let mut i = 1;
+        while i < path.len() {
+            let c = path[i];
+            if c == b'/' {
+                if path[i - 1] == b'/' || i == path.len() - 1 {
+                    return false;
+                }
+            } else if !c.is_ascii_alphanumeric() && c != b'_' {
+                return false;
+            }
+            i += 1;
+        }
+
+        true
+    }
+
     /// Same as `from_str_unchecked`, except it takes an owned `String`.
     ///
     /// Since the passed string is not checked for correctness, prefer using the
@@ -97,6 +132,78 @@ impl<'a> ObjectPath<'a> {
     pub fn into_owned(self) -> ObjectPath<'static> {
         ObjectPath(Cow::Owned(self.0.into_owned()))
     }
+
+    /// Escape any string into a valid element (segment) of an object path.
+    ///
+    /// This is the escaping of systemd's `sd_bus_path_encode`, which many services use for the
+    /// objects named after arbitrary strings: all bytes except ASCII alphanumerics are written as
+    /// `_` followed by their two-digit lowercase hex value, and so is a leading digit. The empty
+    /// string is escaped as `_`.
+    ///
+    /// ```
+    /// use zvariant::ObjectPath;
+    ///
+    /// assert_eq!(ObjectPath::escape_segment("sshd.service"), "sshd_2eservice");
+    /// assert_eq!(ObjectPath::escape_segment("1-wlan"), "_31_2dwlan");
+    /// assert_eq!(ObjectPath::escape_segment(""), "_");
+    /// ```
+    pub fn escape_segment(segment: &str) -> String {
+        if segment.is_empty() {
+            return String::from("_");
+        }
+
+        let mut escaped = String::with_capacity(segment.len());
+        for (i, b) in segment.bytes().enumerate() {
+            if b.is_ascii_alphabetic() || (i > 0 && b.is_ascii_digit()) {
+                escaped.push(b as char);
+            } else {
+                escaped.push_str(&format!("_{:02x}", b));
+            }
+        }
+
+        escaped
+    }
+
+    /// Reverse the escaping of [`ObjectPath::escape_segment`].
+    ///
+    /// Fails if `segment` isn't a validly escaped one, or doesn't unescape to UTF-8.
+    ///
+    /// ```
+    /// use zvariant::ObjectPath;
+    ///
+    /// assert_eq!(ObjectPath::unescape_segment("sshd_2eservice").unwrap(), "sshd.service");
+    /// assert_eq!(ObjectPath::unescape_segment("_").unwrap(), "");
+    /// ObjectPath::unescape_segment("sshd_2").unwrap_err();
+    /// ```
+    ///
+    /// [`ObjectPath::escape_segment`]: struct.ObjectPath.html#method.escape_segment
+    pub fn unescape_segment(segment: &str) -> Result<String> {
+        let invalid = || Error::Message(format!("invalid escaped path segment `{}`", segment));
+        if segment == "_" {
+            return Ok(String::new());
+        }
+
+        let mut unescaped = Vec::with_capacity(segment.len());
+        let mut bytes = segment.bytes();
+        while let Some(b) = bytes.next() {
+            if b.is_ascii_alphanumeric() {
+                unescaped.push(b);
+
+                continue;
+            } else if b != b'_' {
+                return Err(invalid());
+            }
+
+            let hex: Vec<u8> = bytes.by_ref().take(2).collect();
+            let hex = str::from_utf8(&hex).map_err(|_| invalid())?;
+            if hex.len() != 2 || !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+            unescaped.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+        }
+
+        String::from_utf8(unescaped).map_err(|_| invalid())
+    }
 }
 
 impl std::default::Default for ObjectPath<'_> {
@@ -121,10 +228,14 @@ impl<'a> Basic for ObjectPath<'a> {
 
 impl<'a> Type for ObjectPath<'a> {
     fn signature() -> Signature<'static> {
-        Signature::from_str_unchecked(Self::SIGNATURE_STR)
+        Signature::from_str_unchecked(<Self as StaticType>::SIGNATURE_STR)
     }
 }
 
+impl<'a> StaticType for ObjectPath<'a> {
+    const SIGNATURE_STR: &'static str = "o";
+}
+
 impl<'a> TryFrom<&'a [u8]> for ObjectPath<'a> {
     type Error = Error;
 
@@ -206,9 +317,58 @@ impl<'de: 'a, 'a> Deserialize<'de> for ObjectPath<'a> {
     where
         D: Deserializer<'de>,
     {
-        let visitor = ObjectPathVisitor;
+        let visitor = ObjectPathNewtypeVisitor;
+
+        deserializer.deserialize_newtype_struct(OBJECT_PATH_NEWTYPE, visitor)
+    }
+}
+
+// The name of the newtype struct an `ObjectPath` is deserialized as. Our deserializers validate the
+// string themselves and then hand it over as bytes, for it not to be validated twice. Other
+// deserializers see a newtype struct of a string.
+pub(crate) const OBJECT_PATH_NEWTYPE: &str = "zvariant::ObjectPath";
+
+struct ObjectPathNewtypeVisitor;
+
+impl<'de> Visitor<'de> for ObjectPathNewtypeVisitor {
+    type Value = ObjectPath<'de>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        ObjectPathVisitor.expecting(formatter)
+    }
+
+    fn visit_newtype_struct<D>(
+        self,
+        deserializer: D,
+    ) -> core::result::Result<ObjectPath<'de>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(ObjectPathVisitor)
+    }
+
+    #[inline]
+    fn visit_borrowed_bytes<E>(self, value: &'de [u8]) -> core::result::Result<ObjectPath<'de>, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(ObjectPath::from_bytes_unchecked(value))
+    }
+
+    #[inline]
+    fn visit_borrowed_str<E>(self, value: &'de str) -> core::result::Result<ObjectPath<'de>, E>
+    where
+        E: serde::de::Error,
+    {
+        ObjectPathVisitor.visit_borrowed_str(value)
+    }
 
-        deserializer.deserialize_str(visitor)
+    #[inline]
+    fn visit_str<E>(self, value: &str) -> core::result::Result<ObjectPath<'de>, E>
+    where
+        E: serde::de::Error,
+    {
+        ObjectPathVisitor.visit_str(value)
     }
 }
 
@@ -282,8 +442,43 @@ fn ensure_correct_object_path_str(path: &[u8]) -> Result<()> {
     Ok(())
 }
 
+/// Create an [`ObjectPath<'static>`] from a string literal, checked at compile time.
+///
+/// Unlike the `TryFrom` implementations, this is usable in consts and statics, and there is
+/// nothing left to check at runtime:
+///
+/// ```
+/// use zvariant::{object_path, ObjectPath};
+///
+/// const DBUS_PATH: ObjectPath<'static> = object_path!("/org/freedesktop/DBus");
+/// assert_eq!(DBUS_PATH, "/org/freedesktop/DBus");
+/// ```
+///
+/// An invalid path fails the build:
+///
+/// ```compile_fail
+/// let path = zvariant::object_path!("/end/with/slash/");
+/// ```
+///
+/// ```compile_fail
+/// let path = zvariant::object_path!("no/leading/slash");
+/// ```
+///
+/// ```compile_fail
+/// let path = zvariant::object_path!("/ha.d");
+/// ```
+///
+/// [`ObjectPath<'static>`]: struct.ObjectPath.html
+#[macro_export]
+macro_rules! object_path {
+    ($path:literal) => {{
+        const _: () = ::core::assert!($crate::ObjectPath::is_valid($path), "invalid object path");
+        $crate::ObjectPath::from_static_str_unchecked($path)
+    }};
+}
+
 /// Owned [`ObjectPath`](struct.ObjectPath.html)
-#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, serde::Serialize, zvariant_derive::Type)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, serde::Serialize)]
 pub struct OwnedObjectPath(ObjectPath<'static>);
 
 assert_impl_all!(OwnedObjectPath: Send, Sync, Unpin);
@@ -294,6 +489,16 @@ impl OwnedObjectPath {
     }
 }
 
+impl Type for OwnedObjectPath {
+    fn signature() -> Signature<'static> {
+        ObjectPath::signature()
+    }
+}
+
+impl StaticType for OwnedObjectPath {
+    const SIGNATURE_STR: &'static str = <ObjectPath<'static> as StaticType>::SIGNATURE_STR;
+}
+
 impl std::ops::Deref for OwnedObjectPath {
     type Target = ObjectPath<'static>;
 
@@ -347,10 +552,6 @@ impl<'de> Deserialize<'de> for OwnedObjectPath {
     where
         D: Deserializer<'de>,
     {
-        let visitor = ObjectPathVisitor;
-
-        deserializer
-            .deserialize_string(visitor)
-            .map(|v| OwnedObjectPath(v.to_owned()))
+        ObjectPath::deserialize(deserializer).map(|v| OwnedObjectPath(v.to_owned()))
     }
 }
@@ -3,8 +3,8 @@ use static_assertions::assert_impl_all;
 use std::{collections::HashMap, convert::TryFrom, hash::BuildHasher};
 
 use crate::{
-    Array, Dict, Fd, ObjectPath, OwnedObjectPath, OwnedSignature, Signature, Str, Structure, Type,
-    Value,
+    Array, Dict, Fd, ObjectPath, OwnedObjectPath, OwnedSignature, Signature, StaticType, Str,
+    Structure, Type, Value,
 };
 
 #[cfg(feature = "gvariant")]
@@ -204,6 +204,10 @@ impl<'a> Type for OwnedValue {
     }
 }
 
+impl StaticType for OwnedValue {
+    const SIGNATURE_STR: &'static str = <Value<'static> as StaticType>::SIGNATURE_STR;
+}
+
 impl std::ops::Deref for OwnedValue {
     type Target = Value<'static>;
 
@@ -0,0 +1,394 @@
+use std::{convert::TryFrom, fmt::Display, str::FromStr};
+
+use crate::{
+    signature_parser::SignatureParser, Array, Dict, Error, ObjectPath, PathSegment, Result,
+    Signature, Str, StructureBuilder, Value,
+};
+
+// Parse `text` as a value of the single complete type `signature`. See
+// `Value::from_str_with_signature` for the syntax.
+pub(crate) fn parse(text: &str, signature: &Signature<'_>) -> Result<Value<'static>> {
+    let mut parser = SignatureParser::new(signature.clone());
+    let len = parser.parse_next_signature()?.len();
+    if !parser.done() {
+        return Err(Error::Message(format!(
+            "`{}` is not a single complete type",
+            signature
+        )));
+    }
+
+    parse_value(text, &signature.slice(..len))
+}
+
+fn parse_value(text: &str, signature: &Signature<'_>) -> Result<Value<'static>> {
+    let value = match signature.as_bytes()[0] {
+        b'y' => Value::U8(number(text, signature)?),
+        b'b' => Value::Bool(match text {
+            "true" | "yes" | "on" | "1" => true,
+            "false" | "no" | "off" | "0" => false,
+            _ => return Err(invalid(text, signature, "not a boolean")),
+        }),
+        b'n' => Value::I16(number(text, signature)?),
+        b'q' => Value::U16(number(text, signature)?),
+        b'i' => Value::I32(number(text, signature)?),
+        b'u' => Value::U32(number(text, signature)?),
+        b'x' => Value::I64(number(text, signature)?),
+        b't' => Value::U64(number(text, signature)?),
+        b'd' => Value::F64(number(text, signature)?),
+        b's' => Value::Str(Str::from(unescape(text)?)),
+        b'o' => Value::ObjectPath(
+            ObjectPath::try_from(unescape(text)?).map_err(|e| invalid(text, signature, e))?,
+        ),
+        b'g' => Value::Signature(
+            Signature::try_from(unescape(text)?).map_err(|e| invalid(text, signature, e))?,
+        ),
+        b'v' => {
+            let colon = text
+                .find(':')
+                .ok_or_else(|| invalid(text, signature, "no `:` after the signature"))?;
+            let value_signature =
+                Signature::try_from(&text[..colon]).map_err(|e| invalid(text, signature, e))?;
+            let value = parse(&text[colon + 1..], &value_signature)
+                .map_err(|e| e.in_path(PathSegment::Variant))?;
+
+            Value::Value(Box::new(value))
+        }
+        b'a' if signature.as_bytes()[1] == b'{' => parse_dict(text, signature)?,
+        b'a' => parse_array(text, signature)?,
+        b'(' => parse_structure(text, signature)?,
+        _ => {
+            return Err(invalid(
+                text,
+                signature,
+                "the type can't be given as a string",
+            ))
+        }
+    };
+
+    Ok(value)
+}
+
+fn parse_array(text: &str, signature: &Signature<'_>) -> Result<Value<'static>> {
+    let element_signature = signature.slice(1..).to_owned();
+    let text = enclosed(text, '[', ']').unwrap_or(text);
+    let mut array = Array::new(element_signature.clone());
+    if !text.is_empty() {
+        for (i, element) in split(text, ',')?.into_iter().enumerate() {
+            let element = parse_value(element, &element_signature)
+                .map_err(|e| e.in_path(PathSegment::Element(i)))?;
+            array.append(element)?;
+        }
+    }
+
+    Ok(Value::Array(array))
+}
+
+fn parse_dict(text: &str, signature: &Signature<'_>) -> Result<Value<'static>> {
+    // The key is a basic type, so a single character.
+    let key_signature = signature.slice(2..3).to_owned();
+    let value_signature = signature.slice(3..signature.len() - 1).to_owned();
+    let text = enclosed(text, '{', '}').unwrap_or(text);
+    let mut dict = Dict::new(key_signature.clone(), value_signature.clone());
+    if !text.is_empty() {
+        for (i, entry) in split(text, ',')?.into_iter().enumerate() {
+            let equal = separators(entry, '=')?.first().copied().ok_or_else(|| {
+                Error::Message(format!("no `=` in the dictionary entry `{}`", entry))
+                    .in_path(PathSegment::Element(i))
+            })?;
+            let key = parse_value(&entry[..equal], &key_signature)
+                .map_err(|e| e.in_path(PathSegment::Element(i)))?;
+            let segment = PathSegment::for_key(&key, i);
+            let value = parse_value(&entry[equal + 1..], &value_signature)
+                .map_err(|e| e.in_path(segment))?;
+            dict.append(key, value)?;
+        }
+    }
+
+    Ok(Value::Dict(dict))
+}
+
+fn parse_structure(text: &str, signature: &Signature<'_>) -> Result<Value<'static>> {
+    let fields = enclosed(text, '(', ')')
+        .ok_or_else(|| invalid(text, signature, "a structure must be in parentheses"))?;
+    let fields = split(fields, ',')?;
+    let mut parser = SignatureParser::new(signature.slice(1..signature.len() - 1));
+    let mut field_signatures = vec![];
+    while !parser.done() {
+        field_signatures.push(parser.parse_next_signature()?.to_owned());
+    }
+    if fields.len() != field_signatures.len() {
+        return Err(invalid(
+            text,
+            signature,
+            format!(
+                "{} fields instead of {}",
+                fields.len(),
+                field_signatures.len()
+            ),
+        ));
+    }
+
+    let mut builder = StructureBuilder::new();
+    for (i, (field, field_signature)) in fields.into_iter().zip(&field_signatures).enumerate() {
+        let field =
+            parse_value(field, field_signature).map_err(|e| e.in_path(PathSegment::Field(i)))?;
+        builder = builder.append_field(field);
+    }
+
+    Ok(Value::Structure(builder.build()))
+}
+
+fn number<T>(text: &str, signature: &Signature<'_>) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    text.parse().map_err(|e| invalid(text, signature, e))
+}
+
+fn invalid(text: &str, signature: &Signature<'_>, reason: impl Display) -> Error {
+    Error::Message(format!(
+        "invalid `{}` value `{}`: {}",
+        signature, text, reason
+    ))
+}
+
+// `text` without its escaping backslashes.
+fn unescape(text: &str) -> Result<String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next().ok_or_else(|| trailing_backslash(text))?),
+            c => unescaped.push(c),
+        }
+    }
+
+    Ok(unescaped)
+}
+
+// `text` split at the `separator`s that are neither escaped nor nested in brackets.
+fn split(text: &str, separator: char) -> Result<Vec<&str>> {
+    let mut parts = vec![];
+    let mut start = 0;
+    for offset in separators(text, separator)? {
+        parts.push(&text[start..offset]);
+        start = offset + separator.len_utf8();
+    }
+    parts.push(&text[start..]);
+
+    Ok(parts)
+}
+
+// The offsets of the `separator`s in `text` that are neither escaped nor nested in brackets.
+fn separators(text: &str, separator: char) -> Result<Vec<usize>> {
+    let mut offsets = vec![];
+    let mut depth = 0usize;
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next().ok_or_else(|| trailing_backslash(text))?;
+            }
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth = depth.checked_sub(1).ok_or_else(|| unbalanced(text))?;
+            }
+            c if c == separator && depth == 0 => offsets.push(i),
+            _ => (),
+        }
+    }
+    if depth != 0 {
+        return Err(unbalanced(text));
+    }
+
+    Ok(offsets)
+}
+
+// The contents of `text`, if it's enclosed in the brackets `open` and `close`.
+fn enclosed(text: &str, open: char, close: char) -> Option<&str> {
+    let inner = text.strip_prefix(open)?.strip_suffix(close)?;
+    // Not for `(1),(2)`, nor `(1\)`.
+    separators(inner, close).ok()?;
+
+    Some(inner)
+}
+
+fn trailing_backslash(text: &str) -> Error {
+    Error::Message(format!("trailing backslash in `{}`", text))
+}
+
+fn unbalanced(text: &str) -> Error {
+    Error::Message(format!("unbalanced brackets in `{}`", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, convert::TryFrom};
+
+    use crate::{Error, ObjectPath, PathSegment, Signature, StructureBuilder, Value};
+
+    fn parse(text: &str, signature: &str) -> crate::Result<Value<'static>> {
+        Value::from_str_with_signature(text, &Signature::try_from(signature).unwrap())
+    }
+
+    #[test]
+    fn basic() {
+        assert_eq!(parse("42", "y").unwrap(), Value::U8(42));
+        assert_eq!(parse("-42", "n").unwrap(), Value::I16(-42));
+        assert_eq!(parse("42", "q").unwrap(), Value::U16(42));
+        assert_eq!(parse("-42", "i").unwrap(), Value::I32(-42));
+        assert_eq!(parse("42", "u").unwrap(), Value::U32(42));
+        assert_eq!(parse("-42", "x").unwrap(), Value::I64(-42));
+        assert_eq!(parse("42", "t").unwrap(), Value::U64(42));
+        assert_eq!(parse("4.2", "d").unwrap(), Value::F64(4.2));
+        for (text, value) in &[("true", true), ("yes", true), ("0", false), ("off", false)] {
+            assert_eq!(parse(text, "b").unwrap(), Value::Bool(*value));
+        }
+        assert_eq!(
+            parse("hello world", "s").unwrap(),
+            Value::from("hello world")
+        );
+        assert_eq!(parse("", "s").unwrap(), Value::from(""));
+        assert_eq!(parse("a\\,b\\\\c", "s").unwrap(), Value::from("a,b\\c"));
+        assert_eq!(
+            parse("/org/zbus", "o").unwrap(),
+            Value::ObjectPath(ObjectPath::try_from("/org/zbus").unwrap())
+        );
+        assert_eq!(
+            parse("a{sv}", "g").unwrap(),
+            Value::Signature(Signature::try_from("a{sv}").unwrap())
+        );
+
+        assert_eq!(
+            parse("256", "y").unwrap_err(),
+            Error::Message(
+                "invalid `y` value `256`: number too large to fit in target type".into()
+            )
+        );
+        assert!(parse("maybe", "b").is_err());
+        assert!(parse("org/zbus", "o").is_err());
+        assert!(parse("3", "h").is_err());
+    }
+
+    #[test]
+    fn variant() {
+        assert_eq!(parse("u:42", "v").unwrap(), Value::new(Value::U32(42)));
+        assert_eq!(
+            parse("as:a,b", "v").unwrap(),
+            Value::new(Value::from(vec!["a", "b"]))
+        );
+        assert_eq!(parse("s:a:b", "v").unwrap(), Value::new(Value::from("a:b")));
+
+        assert!(parse("42", "v").is_err());
+        let err = parse("u:-1", "v").unwrap_err();
+        assert_eq!(err.path(), [PathSegment::Variant]);
+    }
+
+    #[test]
+    fn array() {
+        assert_eq!(parse("1,2,3", "au").unwrap(), Value::from(vec![1u32, 2, 3]));
+        assert_eq!(
+            parse("[1,2,3]", "au").unwrap(),
+            Value::from(vec![1u32, 2, 3])
+        );
+        assert_eq!(parse("", "au").unwrap(), Value::from(Vec::<u32>::new()));
+        assert_eq!(parse("[]", "as").unwrap(), Value::from(Vec::<&str>::new()));
+        assert_eq!(
+            parse("a\\,b,c", "as").unwrap(),
+            Value::from(vec!["a,b", "c"])
+        );
+        // Nested arrays are enclosed in brackets.
+        assert_eq!(
+            parse("[1,2],[],[3]", "aai").unwrap(),
+            Value::from(vec![vec![1i32, 2], vec![], vec![3]])
+        );
+
+        let err = parse("1,x,3", "au").unwrap_err();
+        assert_eq!(err.path(), [PathSegment::Element(1)]);
+        assert_eq!(
+            err.to_string(),
+            "invalid `u` value `x`: invalid digit found in string (at element 1)"
+        );
+        assert!(parse("[1,2", "au").is_err());
+    }
+
+    fn props(value: Value<'static>) -> HashMap<String, Value<'static>> {
+        match value {
+            Value::Dict(dict) => HashMap::try_from(dict).unwrap(),
+            _ => panic!("not a dictionary: {:?}", value),
+        }
+    }
+
+    #[test]
+    fn dict() {
+        let mut expected = HashMap::new();
+        expected.insert("name".to_string(), Value::new(Value::from("zbus")));
+        expected.insert("count".to_string(), Value::new(Value::U32(3)));
+        expected.insert("tags".to_string(), Value::new(Value::from(vec!["a", "b"])));
+        let value = parse("name=s:zbus,count=u:3,tags=as:[a,b]", "a{sv}").unwrap();
+        assert_eq!(value.value_signature(), "a{sv}");
+        assert_eq!(props(value), expected);
+        let value = parse("{name=s:zbus,count=u:3,tags=as:[a,b]}", "a{sv}").unwrap();
+        assert_eq!(props(value), expected);
+        let mut expected = HashMap::new();
+        expected.insert(1u8, "a=b");
+        assert_eq!(parse("1=a=b", "a{ys}").unwrap(), Value::from(expected));
+        assert_eq!(
+            parse("", "a{ys}").unwrap(),
+            Value::from(HashMap::<u8, &str>::new())
+        );
+
+        let err = parse("name=s:zbus,count=u:x", "a{sv}").unwrap_err();
+        assert_eq!(
+            err.path(),
+            [PathSegment::Key("'count'".into()), PathSegment::Variant]
+        );
+        let err = parse("1=a,b", "a{ys}").unwrap_err();
+        assert_eq!(err.path(), [PathSegment::Element(1)]);
+    }
+
+    #[test]
+    fn structure() {
+        assert_eq!(
+            parse("(1,hello)", "(us)").unwrap(),
+            Value::from(
+                StructureBuilder::new()
+                    .add_field(1u32)
+                    .add_field("hello")
+                    .build()
+            )
+        );
+        assert_eq!(
+            parse("(1,one),(2,two)", "a(is)").unwrap(),
+            Value::from(vec![(1i32, "one"), (2, "two")])
+        );
+        assert_eq!(
+            parse("(x,[1,2],(true))", "(sai(b))").unwrap(),
+            Value::from(
+                StructureBuilder::new()
+                    .add_field("x")
+                    .add_field(vec![1i32, 2])
+                    .add_field(StructureBuilder::new().add_field(true).build())
+                    .build()
+            )
+        );
+
+        assert!(parse("1,hello", "(us)").is_err());
+        assert_eq!(
+            parse("(1)", "(us)").unwrap_err().to_string(),
+            "invalid `(us)` value `(1)`: 1 fields instead of 2"
+        );
+        let err = parse("(x,[1,y])", "(sai)").unwrap_err();
+        assert_eq!(err.path(), [PathSegment::Field(1), PathSegment::Element(1)]);
+    }
+
+    #[test]
+    fn signature() {
+        let err = parse("1", "uu").unwrap_err();
+        assert_eq!(
+            err,
+            Error::Message("`uu` is not a single complete type".into())
+        );
+    }
+}
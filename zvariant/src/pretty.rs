@@ -0,0 +1,402 @@
+use std::fmt::{self, Write};
+
+use crate::{walk_value, Error, Result, Signature, Value, Walker};
+
+const INDENT: &str = "   ";
+const BYTES_PER_LINE: usize = 16;
+
+/// Options for the human-readable rendering of values by [`PrettyPrinter`].
+///
+/// By default, nothing is truncated.
+///
+/// [`PrettyPrinter`]: struct.PrettyPrinter.html
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrettyOptions {
+    max_array_len: Option<usize>,
+    max_bytes: Option<usize>,
+}
+
+impl PrettyOptions {
+    /// Create the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only render the first `max` elements of arrays (and entries of dictionaries).
+    ///
+    /// Byte arrays are limited by [`max_bytes`] instead.
+    ///
+    /// [`max_bytes`]: #method.max_bytes
+    pub fn max_array_len(mut self, max: usize) -> Self {
+        self.max_array_len = Some(max);
+        self
+    }
+
+    /// Only render the first `max` bytes of byte arrays.
+    pub fn max_bytes(mut self, max: usize) -> Self {
+        self.max_bytes = Some(max);
+        self
+    }
+}
+
+#[derive(Debug)]
+enum Frame {
+    // The fields of a message body, rendered without the enclosing structure.
+    Body,
+    Array {
+        len: usize,
+    },
+    Bytes {
+        len: usize,
+        shown: Vec<u8>,
+    },
+    Dict {
+        len: usize,
+    },
+    DictEntry,
+    Struct,
+    Variant,
+    #[cfg(feature = "gvariant")]
+    Maybe,
+}
+
+/// A [`Walker`] rendering values in the human-readable format of `dbus-monitor`.
+///
+/// Each value is on its own line, starting with its type, and the contents of containers are
+/// indented:
+///
+/// ```
+/// use std::collections::HashMap;
+/// use zvariant::{PrettyOptions, Value};
+///
+/// let mut dict = HashMap::new();
+/// dict.insert("answer", Value::from(42u32));
+/// let value = Value::from((Value::from("hello"), vec![1u8, 2, 3], dict));
+///
+/// assert_eq!(
+///     value.to_pretty_string(),
+///     r#"struct {
+///    variant string "hello"
+///    array of bytes [
+///       01 02 03
+///    ]
+///    array [
+///       dict entry(
+///          string "answer"
+///          variant uint32 42
+///       )
+///    ]
+/// }
+/// "#,
+/// );
+///
+/// let bytes = Value::from(vec![0u8; 100]);
+/// assert_eq!(
+///     bytes.to_pretty_string_with(PrettyOptions::new().max_bytes(4)),
+///     "array of bytes [\n   00 00 00 00\n   ... 96 more bytes\n]\n",
+/// );
+/// ```
+///
+/// Use [`walk_slice`] with a `PrettyPrinter` to render encoded values, without decoding them first.
+///
+/// [`Walker`]: trait.Walker.html
+/// [`walk_slice`]: fn.walk_slice.html
+#[derive(Debug)]
+pub struct PrettyPrinter<'w, W> {
+    out: &'w mut W,
+    options: PrettyOptions,
+    frames: Vec<Frame>,
+    level: usize,
+    // The next value goes on the current line, after the `variant` or `just` prefix.
+    inline: bool,
+    // The depth of the containers being left out, if any.
+    skipping: usize,
+}
+
+impl<'w, W> PrettyPrinter<'w, W>
+where
+    W: Write,
+{
+    /// Create a printer writing to `out`.
+    pub fn new(out: &'w mut W, options: PrettyOptions) -> Self {
+        Self {
+            out,
+            options,
+            frames: vec![],
+            level: 0,
+            inline: false,
+            skipping: 0,
+        }
+    }
+
+    /// Create a printer for the fields of a message body, walked as a structure.
+    ///
+    /// The fields are rendered indented once, without the enclosing structure, as `dbus-monitor`
+    /// does.
+    pub fn for_body(out: &'w mut W, options: PrettyOptions) -> Self {
+        let mut printer = Self::new(out, options);
+        printer.frames.push(Frame::Body);
+
+        printer
+    }
+
+    // Account for a new value, returning whether it's rendered.
+    fn element(&mut self) -> bool {
+        if self.skipping > 0 {
+            return false;
+        }
+
+        let (len, max) = match self.frames.last_mut() {
+            Some(Frame::Array { len }) | Some(Frame::Dict { len }) => {
+                (len, self.options.max_array_len)
+            }
+            Some(Frame::Bytes { len, .. }) => (len, self.options.max_bytes),
+            _ => return true,
+        };
+        *len += 1;
+
+        max.map(|max| *len <= max).unwrap_or(true)
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> Result<()> {
+        self.out.write_fmt(args).map_err(fmt_error)
+    }
+
+    fn start_line(&mut self) -> Result<()> {
+        if self.inline {
+            self.inline = false;
+
+            return Ok(());
+        }
+
+        for _ in 0..self.level {
+            self.out.write_str(INDENT).map_err(fmt_error)?;
+        }
+
+        Ok(())
+    }
+
+    fn open(&mut self, header: &str, frame: Frame) -> Result<()> {
+        if !self.element() {
+            self.skipping += 1;
+
+            return Ok(());
+        }
+
+        self.start_line()?;
+        writeln!(self, "{}", header)?;
+        self.frames.push(frame);
+        self.level += 1;
+
+        Ok(())
+    }
+
+    fn close(&mut self, footer: &str) -> Result<()> {
+        if self.skipping > 0 {
+            self.skipping -= 1;
+
+            return Ok(());
+        }
+
+        let frame = self.frames.pop();
+        match frame {
+            Some(Frame::Array { len }) | Some(Frame::Dict { len }) => {
+                self.write_omitted(len, self.options.max_array_len, "elements")?;
+            }
+            Some(Frame::Bytes { len, shown }) => {
+                for line in shown.chunks(BYTES_PER_LINE) {
+                    self.start_line()?;
+                    let hex: Vec<_> = line.iter().map(|b| format!("{:02x}", b)).collect();
+                    writeln!(self, "{}", hex.join(" "))?;
+                }
+                self.write_omitted(len, self.options.max_bytes, "bytes")?;
+            }
+            _ => (),
+        }
+        self.level -= 1;
+        self.start_line()?;
+        writeln!(self, "{}", footer)
+    }
+
+    fn write_omitted(&mut self, len: usize, max: Option<usize>, what: &str) -> Result<()> {
+        match max {
+            Some(max) if len > max => {
+                self.start_line()?;
+                writeln!(self, "... {} more {}", len - max, what)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'w, 'de, W> Walker<'de> for PrettyPrinter<'w, W>
+where
+    W: Write,
+{
+    fn basic(&mut self, value: Value<'de>) -> Result<()> {
+        if !self.element() {
+            return Ok(());
+        }
+        if let (Some(Frame::Bytes { shown, .. }), Value::U8(b)) = (self.frames.last_mut(), &value) {
+            shown.push(*b);
+
+            return Ok(());
+        }
+
+        self.start_line()?;
+        match value {
+            Value::U8(v) => writeln!(self, "byte {}", v),
+            Value::Bool(v) => writeln!(self, "boolean {}", v),
+            Value::I16(v) => writeln!(self, "int16 {}", v),
+            Value::U16(v) => writeln!(self, "uint16 {}", v),
+            Value::I32(v) => writeln!(self, "int32 {}", v),
+            Value::U32(v) => writeln!(self, "uint32 {}", v),
+            Value::I64(v) => writeln!(self, "int64 {}", v),
+            Value::U64(v) => writeln!(self, "uint64 {}", v),
+            Value::F64(v) => writeln!(self, "double {}", v),
+            Value::Str(v) => writeln!(self, "string {:?}", v.as_str()),
+            Value::Signature(v) => writeln!(self, "signature {:?}", v.as_str()),
+            Value::ObjectPath(v) => writeln!(self, "object path {:?}", v.as_str()),
+            Value::Fd(v) => writeln!(self, "file descriptor {}", v),
+            v => Err(Error::Message(format!(
+                "`{}` is not a basic value",
+                v.value_signature()
+            ))),
+        }
+    }
+
+    fn array_start(&mut self, element_signature: &Signature<'_>) -> Result<()> {
+        if element_signature == "y" {
+            let frame = Frame::Bytes {
+                len: 0,
+                shown: vec![],
+            };
+            self.open("array of bytes [", frame)
+        } else {
+            self.open("array [", Frame::Array { len: 0 })
+        }
+    }
+
+    fn array_end(&mut self) -> Result<()> {
+        self.close("]")
+    }
+
+    fn dict_start(&mut self, _key: &Signature<'_>, _value: &Signature<'_>) -> Result<()> {
+        self.open("array [", Frame::Dict { len: 0 })
+    }
+
+    fn dict_entry_start(&mut self) -> Result<()> {
+        self.open("dict entry(", Frame::DictEntry)
+    }
+
+    fn dict_entry_end(&mut self) -> Result<()> {
+        self.close(")")
+    }
+
+    fn dict_end(&mut self) -> Result<()> {
+        self.close("]")
+    }
+
+    fn struct_start(&mut self, _signature: &Signature<'_>) -> Result<()> {
+        if self.skipping == 0 && self.frames.len() == 1 && matches!(self.frames[0], Frame::Body) {
+            // The body fields go right under the header line.
+            self.frames.push(Frame::Struct);
+            self.level += 1;
+
+            return Ok(());
+        }
+
+        self.open("struct {", Frame::Struct)
+    }
+
+    fn struct_end(&mut self) -> Result<()> {
+        if self.skipping == 0 && self.frames.len() == 2 && matches!(self.frames[0], Frame::Body) {
+            self.frames.pop();
+            self.level -= 1;
+
+            return Ok(());
+        }
+
+        self.close("}")
+    }
+
+    fn variant_start(&mut self, _signature: &Signature<'_>) -> Result<()> {
+        if !self.element() {
+            self.skipping += 1;
+
+            return Ok(());
+        }
+
+        self.start_line()?;
+        write!(self, "variant ")?;
+        self.frames.push(Frame::Variant);
+        self.inline = true;
+
+        Ok(())
+    }
+
+    fn variant_end(&mut self) -> Result<()> {
+        if self.skipping > 0 {
+            self.skipping -= 1;
+        } else {
+            self.frames.pop();
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "gvariant")]
+    fn maybe_start(&mut self, _signature: &Signature<'_>, present: bool) -> Result<()> {
+        if !self.element() {
+            self.skipping += 1;
+
+            return Ok(());
+        }
+
+        self.start_line()?;
+        if present {
+            write!(self, "just ")?;
+            self.inline = true;
+        } else {
+            writeln!(self, "nothing")?;
+        }
+        self.frames.push(Frame::Maybe);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "gvariant")]
+    fn maybe_end(&mut self) -> Result<()> {
+        if self.skipping > 0 {
+            self.skipping -= 1;
+        } else {
+            self.frames.pop();
+        }
+
+        Ok(())
+    }
+}
+
+fn fmt_error(_: fmt::Error) -> Error {
+    Error::Message("failed to write the rendered value".into())
+}
+
+impl<'a> Value<'a> {
+    /// Render the value in a human-readable form.
+    ///
+    /// See [`PrettyPrinter`] for the format.
+    ///
+    /// [`PrettyPrinter`]: struct.PrettyPrinter.html
+    pub fn to_pretty_string(&self) -> String {
+        self.to_pretty_string_with(PrettyOptions::default())
+    }
+
+    /// Render the value in a human-readable form, with the given `options`.
+    pub fn to_pretty_string_with(&self, options: PrettyOptions) -> String {
+        let mut s = String::new();
+        walk_value(self, &mut PrettyPrinter::new(&mut s, options))
+            .expect("rendering a value into a string can't fail");
+
+        s
+    }
+}
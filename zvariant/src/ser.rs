@@ -2,6 +2,7 @@ use byteorder::WriteBytesExt;
 use serde::{ser, Serialize};
 use static_assertions::assert_impl_all;
 use std::{
+    convert::TryFrom,
     io::{Seek, Write},
     marker::PhantomData,
     os::unix::io::RawFd,
@@ -14,7 +15,7 @@ use crate::{
     dbus::{self, Serializer as DBusSerializer},
     signature_parser::SignatureParser,
     utils::*,
-    Basic, EncodingContext, EncodingFormat, Error, Result, Signature, Type,
+    Basic, EncodingContext, EncodingFormat, Error, PathSegment, Result, Signature, Type,
 };
 
 struct NullWriteSeek;
@@ -365,6 +366,33 @@ where
     B: byteorder::ByteOrder,
     W: Write + Seek,
 {
+    // The path segment for the value of the `index`th entry of the dictionary being serialized.
+    // `entry_parser` is positioned at the start of the dict-entry signature.
+    pub(crate) fn key_segment<T>(
+        &self,
+        mut entry_parser: SignatureParser<'_>,
+        key: &T,
+        index: usize,
+    ) -> PathSegment
+    where
+        T: ?Sized + Serialize,
+    {
+        let ctxt = EncodingContext::<B>::new(self.ctxt.format(), 0);
+        let encoded = entry_parser.skip_char().and_then(|_| {
+            let c = entry_parser.next_char();
+            let signature = Signature::try_from(c.to_string())?;
+
+            to_bytes_fds_for_signature(ctxt, &signature, key).map(|encoded| (c, encoded))
+        });
+
+        match encoded {
+            Ok((c, (bytes, fds))) => {
+                PathSegment::for_encoded_key(&bytes, Some(&fds), ctxt, c, index)
+            }
+            Err(_) => PathSegment::Element(index),
+        }
+    }
+
     pub(crate) fn add_fd(&mut self, fd: RawFd) -> u32 {
         if let Some(idx) = self.fds.iter().position(|&x| x == fd) {
             return idx as u32;
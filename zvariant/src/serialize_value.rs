@@ -1,7 +1,7 @@
 use serde::ser::{Serialize, SerializeStruct, Serializer};
 use static_assertions::assert_impl_all;
 
-use crate::{Signature, Type, Value};
+use crate::{Signature, StaticType, Type, Value};
 
 /// A wrapper to serialize `T: Type + Serialize` as a value.
 ///
@@ -41,3 +41,7 @@ impl<'a, T: Type + Serialize> Type for SerializeValue<'a, T> {
         Value::signature()
     }
 }
+
+impl<'a, T: Type + Serialize> StaticType for SerializeValue<'a, T> {
+    const SIGNATURE_STR: &'static str = <Value<'static> as StaticType>::SIGNATURE_STR;
+}
@@ -13,7 +13,9 @@ use std::{
     sync::Arc,
 };
 
-use crate::{signature_parser::SignatureParser, Basic, EncodingFormat, Error, Result, Type};
+use crate::{
+    signature_parser::SignatureParser, Basic, EncodingFormat, Error, Result, StaticType, Type,
+};
 
 // A data type similar to Cow and [`bytes::Bytes`] but unlike the former won't allow us to only keep
 // the owned bytes in Arc and latter doesn't have a notion of borrowed data and would require API
@@ -201,6 +203,46 @@ impl<'a> Signature<'a> {
 
         clone
     }
+
+    /// Whether `other` starts with all the complete types of `self`, possibly followed by more.
+    ///
+    /// The types are compared as a whole, rather than character by character, so a type that got
+    /// more fields (e.g `(su)` becoming `(sus)`) is not a match. Invalid signatures never are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zvariant::Signature;
+    ///
+    /// let sig = Signature::from_str_unchecked("sa(su)");
+    /// assert!(sig.is_prefix_of(&Signature::from_str_unchecked("sa(su)")));
+    /// assert!(sig.is_prefix_of(&Signature::from_str_unchecked("sa(su)a{sv}")));
+    /// assert!(!sig.is_prefix_of(&Signature::from_str_unchecked("sa(sus)")));
+    /// assert!(!sig.is_prefix_of(&Signature::from_str_unchecked("s")));
+    /// ```
+    pub fn is_prefix_of(&self, other: &Signature<'_>) -> bool {
+        let mut parser = SignatureParser::new(self.clone());
+        let mut other_parser = SignatureParser::new(other.clone());
+
+        while !parser.done() {
+            match (
+                parser.parse_next_signature(),
+                other_parser.parse_next_signature(),
+            ) {
+                (Ok(ty), Ok(other_ty)) if ty == other_ty => (),
+                _ => return false,
+            }
+        }
+
+        // The rest must be valid too.
+        while !other_parser.done() {
+            if other_parser.parse_next_signature().is_err() {
+                return false;
+            }
+        }
+
+        true
+    }
 }
 
 impl<'a> Debug for Signature<'a> {
@@ -230,10 +272,14 @@ impl<'a> Basic for Signature<'a> {
 
 impl<'a> Type for Signature<'a> {
     fn signature() -> Signature<'static> {
-        Signature::from_str_unchecked(Self::SIGNATURE_STR)
+        Signature::from_str_unchecked(<Self as StaticType>::SIGNATURE_STR)
     }
 }
 
+impl<'a> StaticType for Signature<'a> {
+    const SIGNATURE_STR: &'static str = "g";
+}
+
 impl<'a> TryFrom<&'a [u8]> for Signature<'a> {
     type Error = Error;
 
@@ -321,9 +367,56 @@ impl<'de: 'a, 'a> Deserialize<'de> for Signature<'a> {
     where
         D: Deserializer<'de>,
     {
-        let visitor = SignatureVisitor;
+        let visitor = SignatureNewtypeVisitor;
 
-        deserializer.deserialize_str(visitor)
+        deserializer.deserialize_newtype_struct(SIGNATURE_NEWTYPE, visitor)
+    }
+}
+
+// The newtype struct name of `Signature`, see `OBJECT_PATH_NEWTYPE`.
+pub(crate) const SIGNATURE_NEWTYPE: &str = "zvariant::Signature";
+
+struct SignatureNewtypeVisitor;
+
+impl<'de> Visitor<'de> for SignatureNewtypeVisitor {
+    type Value = Signature<'de>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        SignatureVisitor.expecting(formatter)
+    }
+
+    fn visit_newtype_struct<D>(
+        self,
+        deserializer: D,
+    ) -> core::result::Result<Signature<'de>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(SignatureVisitor)
+    }
+
+    #[inline]
+    fn visit_borrowed_bytes<E>(self, value: &'de [u8]) -> core::result::Result<Signature<'de>, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(Signature::from_bytes_unchecked(value))
+    }
+
+    #[inline]
+    fn visit_borrowed_str<E>(self, value: &'de str) -> core::result::Result<Signature<'de>, E>
+    where
+        E: serde::de::Error,
+    {
+        SignatureVisitor.visit_borrowed_str(value)
+    }
+
+    #[inline]
+    fn visit_str<E>(self, value: &str) -> core::result::Result<Signature<'de>, E>
+    where
+        E: serde::de::Error,
+    {
+        SignatureVisitor.visit_str(value)
     }
 }
 
@@ -375,7 +468,7 @@ fn ensure_correct_signature_str(signature: &[u8]) -> Result<()> {
 }
 
 /// Owned [`Signature`](struct.Signature.html)
-#[derive(Debug, Clone, PartialEq, serde::Serialize, zvariant_derive::Type)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
 pub struct OwnedSignature(Signature<'static>);
 
 assert_impl_all!(OwnedSignature: Send, Sync, Unpin);
@@ -386,6 +479,16 @@ impl OwnedSignature {
     }
 }
 
+impl Type for OwnedSignature {
+    fn signature() -> Signature<'static> {
+        Signature::signature()
+    }
+}
+
+impl StaticType for OwnedSignature {
+    const SIGNATURE_STR: &'static str = <Signature<'static> as StaticType>::SIGNATURE_STR;
+}
+
 impl std::ops::Deref for OwnedSignature {
     type Target = Signature<'static>;
 
@@ -417,11 +520,7 @@ impl<'de> Deserialize<'de> for OwnedSignature {
     where
         D: Deserializer<'de>,
     {
-        let visitor = SignatureVisitor;
-
-        deserializer
-            .deserialize_string(visitor)
-            .map(|v| OwnedSignature(v.to_owned()))
+        Signature::deserialize(deserializer).map(|v| OwnedSignature(v.to_owned()))
     }
 }
 
@@ -445,4 +544,22 @@ mod tests {
         assert_eq!(slice, "t");
         assert_eq!(slice.slice(1..), "");
     }
+
+    #[test]
+    fn signature_prefix() {
+        let sig = Signature::from_str_unchecked;
+
+        assert!(sig("").is_prefix_of(&sig("")));
+        assert!(sig("").is_prefix_of(&sig("as")));
+        assert!(sig("su").is_prefix_of(&sig("sua{sv}")));
+        assert!(sig("a{sv}").is_prefix_of(&sig("a{sv}(ay)")));
+
+        // Nothing but whole types match.
+        assert!(!sig("a{sv}").is_prefix_of(&sig("a{sa{sv}}")));
+        assert!(!sig("(su)").is_prefix_of(&sig("(sua{sv})")));
+        assert!(!sig("a(su)").is_prefix_of(&sig("a(su)(")));
+        assert!(!sig("su").is_prefix_of(&sig("su}")));
+        assert!(!sig("us").is_prefix_of(&sig("su")));
+        assert!(!sig("sus").is_prefix_of(&sig("su")));
+    }
 }
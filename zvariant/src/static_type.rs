@@ -0,0 +1,230 @@
+use crate::{utils::*, ConstSignature, Type};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    hash::{BuildHasher, Hash},
+    rc::Rc,
+    sync::Arc,
+};
+
+/// Types whose signature is known at compile time.
+///
+/// This is a companion of [`Type`], for the types whose signature doesn't depend on anything but
+/// the type itself, which is the case of all the types that implement [`Type`] in this crate.
+/// Being a constant, the signature can be compared or combined in `const` contexts, e.g to build
+/// static tables of signatures. The signatures of container types are built from those of the
+/// types they contain, with [`ConstSignature`].
+///
+/// Use the `StaticType` derive macro from [zvariant_derive] for your own structures and enums.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+/// use zvariant::StaticType;
+///
+/// const SIGNATURE: &str = <HashMap<&str, (u32, Vec<u8>)>>::SIGNATURE_STR;
+/// assert_eq!(SIGNATURE, "a{s(uay)}");
+/// ```
+///
+/// The [`Basic`] types have a deprecated `SIGNATURE_STR` constant of the same name. Where both
+/// traits are in scope, use the fully qualified form: `<u32 as StaticType>::SIGNATURE_STR`.
+///
+/// [`Type`]: trait.Type.html
+/// [`Basic`]: trait.Basic.html
+/// [`ConstSignature`]: struct.ConstSignature.html
+/// [zvariant_derive]: https://docs.rs/zvariant_derive/2.0.0/zvariant_derive/
+pub trait StaticType: Type {
+    /// The signature for the implementing type, as a string.
+    ///
+    /// It must be the same as the one [`Type::signature`] returns.
+    ///
+    /// [`Type::signature`]: trait.Type.html#tymethod.signature
+    const SIGNATURE_STR: &'static str;
+}
+
+macro_rules! array_static_type {
+    ($arr:ty) => {
+        impl<T> StaticType for $arr
+        where
+            T: StaticType,
+        {
+            const SIGNATURE_STR: &'static str = (&ConstSignature::new()
+                .push(ARRAY_SIGNATURE_STR)
+                .push(T::SIGNATURE_STR))
+                .as_str();
+        }
+    };
+}
+
+array_static_type!([T]);
+array_static_type!(Vec<T>);
+
+#[cfg(feature = "arrayvec")]
+impl<A, T> StaticType for arrayvec::ArrayVec<A>
+where
+    A: arrayvec::Array<Item = T>,
+    T: StaticType,
+{
+    const SIGNATURE_STR: &'static str = <[T]>::SIGNATURE_STR;
+}
+
+#[cfg(feature = "arrayvec")]
+impl<A> StaticType for arrayvec::ArrayString<A>
+where
+    A: arrayvec::Array<Item = u8> + Copy,
+{
+    const SIGNATURE_STR: &'static str = <&str as StaticType>::SIGNATURE_STR;
+}
+
+impl StaticType for () {
+    const SIGNATURE_STR: &'static str = "";
+}
+
+impl<T> StaticType for &T
+where
+    T: ?Sized + StaticType,
+{
+    const SIGNATURE_STR: &'static str = T::SIGNATURE_STR;
+}
+
+macro_rules! smart_ptr_static_type {
+    ($ptr:ident) => {
+        impl<T> StaticType for $ptr<T>
+        where
+            T: ?Sized + StaticType,
+        {
+            const SIGNATURE_STR: &'static str = T::SIGNATURE_STR;
+        }
+    };
+}
+
+smart_ptr_static_type!(Box);
+smart_ptr_static_type!(Rc);
+smart_ptr_static_type!(Arc);
+
+impl<'a, T> StaticType for Cow<'a, T>
+where
+    T: ?Sized + ToOwned + StaticType,
+{
+    const SIGNATURE_STR: &'static str = T::SIGNATURE_STR;
+}
+
+#[cfg(feature = "gvariant")]
+impl<T> StaticType for Option<T>
+where
+    T: StaticType,
+{
+    const SIGNATURE_STR: &'static str = (&ConstSignature::new()
+        .push(MAYBE_SIGNATURE_STR)
+        .push(T::SIGNATURE_STR))
+        .as_str();
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+macro_rules! tuple_static_impls {
+    ($($len:expr => ($($n:tt $name:ident)+))+) => {
+        $(
+            impl<$($name),+> StaticType for ($($name,)+)
+            where
+                $($name: StaticType,)+
+            {
+                const SIGNATURE_STR: &'static str = (&ConstSignature::new()
+                    .push(STRUCT_SIG_START_STR)
+                    $(
+                        .push($name::SIGNATURE_STR)
+                    )+
+                    .push(STRUCT_SIG_END_STR))
+                    .as_str();
+            }
+        )+
+    }
+}
+
+tuple_static_impls! {
+    1 => (0 T0)
+    2 => (0 T0 1 T1)
+    3 => (0 T0 1 T1 2 T2)
+    4 => (0 T0 1 T1 2 T2 3 T3)
+    5 => (0 T0 1 T1 2 T2 3 T3 4 T4)
+    6 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5)
+    7 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6)
+    8 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7)
+    9 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8)
+    10 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9)
+    11 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10)
+    12 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11)
+    13 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12)
+    14 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13)
+    15 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14)
+    16 => (0 T0 1 T1 2 T2 3 T3 4 T4 5 T5 6 T6 7 T7 8 T8 9 T9 10 T10 11 T11 12 T12 13 T13 14 T14 15 T15)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+// Like for `Type`, arrays are treated as structures.
+macro_rules! array_static_impls {
+    ($($len:tt)+) => {
+        $(
+            impl<T> StaticType for [T; $len]
+            where
+                T: StaticType,
+            {
+                const SIGNATURE_STR: &'static str = (&ConstSignature::new()
+                    .push(STRUCT_SIG_START_STR)
+                    .push_repeated(T::SIGNATURE_STR, $len)
+                    .push(STRUCT_SIG_END_STR))
+                    .as_str();
+            }
+        )+
+    }
+}
+
+array_static_impls! {
+    0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+macro_rules! map_static_impl {
+    ($ty:ident < K $(: $kbound1:ident $(+ $kbound2:ident)*)*, V $(, $typaram:ident : $bound:ident)* >) => {
+        impl<K, V $(, $typaram)*> StaticType for $ty<K, V $(, $typaram)*>
+        where
+            K: StaticType $(+ $kbound1 $(+ $kbound2)*)*,
+            V: StaticType,
+            $($typaram: $bound,)*
+        {
+            const SIGNATURE_STR: &'static str = (&ConstSignature::new()
+                .push(ARRAY_SIGNATURE_STR)
+                .push(DICT_ENTRY_SIG_START_STR)
+                .push(K::SIGNATURE_STR)
+                .push(V::SIGNATURE_STR)
+                .push(DICT_ENTRY_SIG_END_STR))
+                .as_str();
+        }
+    }
+}
+
+map_static_impl!(BTreeMap<K: Ord, V>);
+map_static_impl!(HashMap<K: Eq + Hash, V, H: BuildHasher>);
+
+// BitFlags
+#[cfg(feature = "enumflags2")]
+impl<F> StaticType for enumflags2::BitFlags<F>
+where
+    F: enumflags2::RawBitFlags,
+    F::Type: StaticType,
+{
+    const SIGNATURE_STR: &'static str = <F::Type as StaticType>::SIGNATURE_STR;
+}
+
+#[cfg(feature = "serde_bytes")]
+impl StaticType for serde_bytes::Bytes {
+    const SIGNATURE_STR: &'static str = "ay";
+}
+
+#[cfg(feature = "serde_bytes")]
+impl StaticType for serde_bytes::ByteBuf {
+    const SIGNATURE_STR: &'static str = "ay";
+}
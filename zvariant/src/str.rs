@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 use static_assertions::assert_impl_all;
 use std::{borrow::Cow, str};
 
-use crate::{Basic, EncodingFormat, Signature, Type};
+use crate::{Basic, EncodingFormat, Signature, StaticType, Type};
 
 /// A string wrapper.
 ///
@@ -36,7 +36,7 @@ impl<'a> Str<'a> {
 
 impl<'a> Basic for Str<'a> {
     const SIGNATURE_CHAR: char = <&str>::SIGNATURE_CHAR;
-    const SIGNATURE_STR: &'static str = <&str>::SIGNATURE_STR;
+    const SIGNATURE_STR: &'static str = <&str as Basic>::SIGNATURE_STR;
     const ALIGNMENT: usize = <&str>::ALIGNMENT;
 
     fn alignment(format: EncodingFormat) -> usize {
@@ -46,10 +46,14 @@ impl<'a> Basic for Str<'a> {
 
 impl<'a> Type for Str<'a> {
     fn signature() -> Signature<'static> {
-        Signature::from_str_unchecked(Self::SIGNATURE_STR)
+        Signature::from_str_unchecked(<Self as StaticType>::SIGNATURE_STR)
     }
 }
 
+impl<'a> StaticType for Str<'a> {
+    const SIGNATURE_STR: &'static str = <&str as StaticType>::SIGNATURE_STR;
+}
+
 impl<'a> From<&'a str> for Str<'a> {
     fn from(value: &'a str) -> Self {
         Self(Cow::from(value))
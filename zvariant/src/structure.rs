@@ -1,16 +1,65 @@
-use serde::ser::{Serialize, SerializeTupleStruct, Serializer};
+use std::convert::TryFrom;
+
+use serde::{
+    de::{DeserializeSeed, Deserializer},
+    ser::{Serialize, SerializeTupleStruct, Serializer},
+};
 use static_assertions::assert_impl_all;
 
-use crate::{OwnedValue, Signature, Type, Value};
+use crate::{
+    signature_parser::SignatureParser, value::ValueSeed, Error, OwnedValue, PathSegment, Signature,
+    Type, Value,
+};
 
 /// Use this to efficiently build a [`Structure`].
 ///
+/// The signature of the structure is computed as fields are appended, so it's available at any
+/// point, e.g to be checked against the expected one with [`build_checked`].
+///
+/// # Example
+///
+/// ```
+/// use std::{collections::HashMap, convert::TryFrom};
+/// use zvariant::{Error, PathSegment, Signature, StructureBuilder, Value};
+///
+/// let mut builder = StructureBuilder::new();
+/// assert_eq!(builder.push(Value::from("name")), "(s)");
+/// // An empty map of strings, where the peer expects a map of variants.
+/// assert_eq!(builder.push(Value::from(HashMap::<&str, &str>::new())), "(sa{ss})");
+///
+/// let expected = Signature::try_from("(sa{sv})").unwrap();
+/// let err = builder.build_checked(&expected).unwrap_err();
+/// assert_eq!(err.path(), &[PathSegment::Field(1)]);
+/// match err {
+///     Error::InPath(e, _) => assert_eq!(
+///         e.to_string(),
+///         "Signature `a{ss}` doesn't match the expected `a{sv}`: \
+///          `s}` instead of `v}` after `a{s`",
+///     ),
+///     _ => panic!("unexpected error: {}", err),
+/// }
+/// ```
+///
 /// [`Structure`]: struct.Structure.html
-#[derive(Debug, Default, Clone, PartialEq)]
-pub struct StructureBuilder<'a>(Vec<Value<'a>>);
+/// [`build_checked`]: #method.build_checked
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureBuilder<'a> {
+    fields: Vec<Value<'a>>,
+    // The signature of the fields so far, enclosed in parentheses.
+    signature: String,
+}
 
 assert_impl_all!(StructureBuilder<'_>: Send, Sync, Unpin);
 
+impl<'a> Default for StructureBuilder<'a> {
+    fn default() -> Self {
+        Self {
+            fields: vec![],
+            signature: String::from("()"),
+        }
+    }
+}
+
 impl<'a> StructureBuilder<'a> {
     /// Create a new `StructureBuilder`.
     ///
@@ -34,27 +83,79 @@ impl<'a> StructureBuilder<'a> {
     ///
     /// Identical to `add_field`, except the field must be in the form of a `Value`.
     pub fn append_field<'e: 'a>(mut self, field: Value<'e>) -> Self {
-        self.0.push(field);
+        self.push(field);
 
         self
     }
 
+    /// Append `field` to `self`, returning the signature of the structure so far.
+    ///
+    /// Identical to `append_field`, except it doesn't consume `self`, e.g for building a structure
+    /// in a loop.
+    pub fn push<'e: 'a>(&mut self, field: Value<'e>) -> Signature<'_> {
+        self.signature.pop();
+        self.signature.push_str(&field.value_signature());
+        self.signature.push(')');
+        self.fields.push(field);
+
+        self.signature()
+    }
+
+    /// The signature of the structure so far.
+    pub fn signature(&self) -> Signature<'_> {
+        Signature::from_str_unchecked(&self.signature)
+    }
+
     /// Build the `Structure`.
     ///
     /// [`Structure`]: struct.Structure.html
     pub fn build(self) -> Structure<'a> {
-        let signature = create_signature_from_fields(&self.0);
-
         Structure {
-            fields: self.0,
-            signature,
+            fields: self.fields,
+            signature: Signature::from_string_unchecked(self.signature),
+        }
+    }
+
+    /// Build the `Structure`, if its signature is `expected`.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::SignatureMismatch`] in the path of the first field not matching its signature in
+    /// `expected`, or for the whole structure if the number of fields differs.
+    ///
+    /// [`Error::SignatureMismatch`]: enum.Error.html#variant.SignatureMismatch
+    pub fn build_checked(self, expected: &Signature<'_>) -> Result<Structure<'a>, Error> {
+        if *expected == self.signature() {
+            return Ok(self.build());
+        }
+
+        if expected.len() >= 2 && expected.starts_with('(') && expected.ends_with(')') {
+            let mut parser = SignatureParser::new(expected.slice(1..expected.len() - 1));
+            for (i, field) in self.fields.iter().enumerate() {
+                let expected_field = match parser.parse_next_signature() {
+                    Ok(signature) => signature,
+                    Err(_) => break,
+                };
+                let signature = field.value_signature();
+                if signature != expected_field {
+                    let e =
+                        Error::SignatureMismatch(expected_field.to_owned(), signature.to_owned());
+
+                    return Err(e.in_path(PathSegment::Field(i)));
+                }
+            }
         }
+
+        Err(Error::SignatureMismatch(
+            expected.to_owned(),
+            Signature::from_string_unchecked(self.signature),
+        ))
     }
 
     /// Same as `build` except Signature is provided.
     pub(crate) fn build_with_signature<'s: 'a>(self, signature: Signature<'s>) -> Structure<'a> {
         Structure {
-            fields: self.0,
+            fields: self.fields,
             signature,
         }
     }
@@ -79,6 +180,13 @@ impl<'a> Structure<'a> {
         &self.fields
     }
 
+    /// Create a new [`StructureBuilder`].
+    ///
+    /// [`StructureBuilder`]: struct.StructureBuilder.html
+    pub fn builder() -> StructureBuilder<'a> {
+        StructureBuilder::new()
+    }
+
     /// Converts `self` to a `Vec` containing all its fields.
     pub fn into_fields(self) -> Vec<Value<'a>> {
         self.fields
@@ -173,6 +281,72 @@ impl<'a> Serialize for Structure<'a> {
     }
 }
 
+/// Use this to deserialize a [`Structure`] of a signature only known at runtime.
+///
+/// Unlike [`Value`], which is decoded from a variant, i-e along with its signature, the structure
+/// is decoded from the encoding of its fields alone, as a tuple of the same signature would be.
+/// The signature must be that of a structure, i-e start with `(` and end with `)`.
+///
+/// # Example
+///
+/// ```
+/// use std::convert::TryFrom;
+/// use serde::de::DeserializeSeed;
+/// use zvariant::{to_bytes, EncodingContext, Signature, StructureSeed, Value};
+///
+/// let ctxt = EncodingContext::<byteorder::LE>::new_dbus(0);
+/// let encoded = to_bytes(ctxt, &("hello", 42u32)).unwrap();
+///
+/// let signature = Signature::try_from("(su)").unwrap();
+/// let seed = StructureSeed::try_from(signature).unwrap();
+/// let mut deserializer = zvariant::Deserializer::new(&encoded, None, &seed.signature(), ctxt);
+/// let structure = seed.deserialize(&mut deserializer).unwrap();
+/// assert_eq!(structure.fields(), [Value::from("hello"), Value::from(42u32)]);
+/// ```
+///
+/// [`Structure`]: struct.Structure.html
+/// [`Value`]: enum.Value.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureSeed<'a> {
+    signature: Signature<'a>,
+}
+
+assert_impl_all!(StructureSeed<'_>: Send, Sync, Unpin);
+
+impl<'a> StructureSeed<'a> {
+    /// The signature of the structure to deserialize.
+    pub fn signature(&self) -> Signature<'a> {
+        self.signature.clone()
+    }
+}
+
+impl<'a> TryFrom<Signature<'a>> for StructureSeed<'a> {
+    type Error = Error;
+
+    fn try_from(signature: Signature<'a>) -> Result<Self, Error> {
+        if signature.len() < 2 || !signature.starts_with('(') || !signature.ends_with(')') {
+            return Err(Error::IncorrectType);
+        }
+
+        Ok(Self { signature })
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for StructureSeed<'de> {
+    type Value = Structure<'de>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match ValueSeed::new(self.signature).deserialize(deserializer)? {
+            Value::Structure(structure) => Ok(structure),
+            // The signature of a structure gives a structure.
+            _ => unreachable!("non-structure value for a structure signature"),
+        }
+    }
+}
+
 macro_rules! tuple_impls {
     ($($len:expr => ($($n:tt $name:ident)+))+) => {
         $(
@@ -0,0 +1,288 @@
+#![cfg(feature = "time")]
+
+use serde::{
+    de::{self, Deserialize, Deserializer},
+    ser::{self, Serialize, Serializer},
+};
+use std::{convert::TryFrom, time::SystemTime};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{Error, OwnedValue, Signature, StaticType, Type, Value};
+
+macro_rules! unix_timestamp {
+    ($(#[$attr:meta])* $name:ident, $nanos_per_unit:expr, $unit:expr) => {
+        $(#[$attr])*
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(pub OffsetDateTime);
+
+        impl $name {
+            /// Create a timestamp from the number of units since the Unix epoch.
+            ///
+            /// Fails if the time can't be represented by an `OffsetDateTime`.
+            pub fn from_units(units: u64) -> Result<Self, Error> {
+                let nanos = i128::from(units) * $nanos_per_unit;
+
+                OffsetDateTime::from_unix_timestamp_nanos(nanos)
+                    .map(Self)
+                    .map_err(|_| {
+                        Error::Message(format!(
+                            "timestamp of {} {} is out of range",
+                            units, $unit
+                        ))
+                    })
+            }
+
+            /// The number of units since the Unix epoch, losing any more precision.
+            ///
+            /// Fails for times before the epoch, or too far in the future for a `u64`.
+            pub fn units(&self) -> Result<u64, Error> {
+                let units = self.0.unix_timestamp_nanos().div_euclid($nanos_per_unit);
+
+                u64::try_from(units).map_err(|_| {
+                    Error::Message(format!("`{}` is out of the range of the timestamp", self.0))
+                })
+            }
+        }
+
+        impl From<OffsetDateTime> for $name {
+            fn from(time: OffsetDateTime) -> Self {
+                Self(time)
+            }
+        }
+
+        impl From<$name> for OffsetDateTime {
+            fn from(time: $name) -> Self {
+                time.0
+            }
+        }
+
+        impl From<SystemTime> for $name {
+            fn from(time: SystemTime) -> Self {
+                Self(time.into())
+            }
+        }
+
+        impl From<$name> for SystemTime {
+            fn from(time: $name) -> Self {
+                time.0.into()
+            }
+        }
+
+        impl Type for $name {
+            #[inline]
+            fn signature() -> Signature<'static> {
+                u64::signature()
+            }
+        }
+
+        impl StaticType for $name {
+            const SIGNATURE_STR: &'static str = u64::SIGNATURE_STR;
+        }
+
+        impl Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                self.units()
+                    .map_err(ser::Error::custom)?
+                    .serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                u64::deserialize(deserializer)
+                    .and_then(|units| Self::from_units(units).map_err(de::Error::custom))
+            }
+        }
+
+        impl<'a> TryFrom<Value<'a>> for $name {
+            type Error = Error;
+
+            fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+                u64::try_from(value).and_then(Self::from_units)
+            }
+        }
+
+        impl TryFrom<OwnedValue> for $name {
+            type Error = Error;
+
+            fn try_from(value: OwnedValue) -> Result<Self, Self::Error> {
+                Self::try_from(Value::from(value))
+            }
+        }
+
+        impl<'a> TryFrom<$name> for Value<'a> {
+            type Error = Error;
+
+            fn try_from(time: $name) -> Result<Self, Self::Error> {
+                time.units().map(Value::U64)
+            }
+        }
+    };
+}
+
+unix_timestamp!(
+    /// A point in time, encoded as the number of seconds since the Unix epoch.
+    ///
+    /// See [`UnixMicros`] for the handling of out-of-range values.
+    ///
+    /// [`UnixMicros`]: struct.UnixMicros.html
+    UnixSeconds,
+    1_000_000_000,
+    "seconds"
+);
+
+unix_timestamp!(
+    /// A point in time, encoded as the number of milliseconds since the Unix epoch.
+    ///
+    /// See [`UnixMicros`] for the handling of out-of-range values.
+    ///
+    /// [`UnixMicros`]: struct.UnixMicros.html
+    UnixMillis,
+    1_000_000,
+    "milliseconds"
+);
+
+unix_timestamp!(
+    /// A point in time, encoded as the number of microseconds since the Unix epoch.
+    ///
+    /// This is how systemd and many other services send wall-clock (`CLOCK_REALTIME`) timestamps,
+    /// as a `u64`. Use it for the type of such fields, rather than converting the integers by hand:
+    ///
+    /// ```
+    /// use zvariant::{from_slice, to_bytes, EncodingContext, UnixMicros};
+    /// use time::OffsetDateTime;
+    ///
+    /// let ctxt = EncodingContext::<byteorder::LE>::new_dbus(0);
+    /// let encoded = to_bytes(ctxt, &1_634_205_600_000_123u64).unwrap();
+    ///
+    /// let time: UnixMicros = from_slice(&encoded, ctxt).unwrap();
+    /// let expected = OffsetDateTime::from_unix_timestamp_nanos(1_634_205_600_000_123_000).unwrap();
+    /// assert_eq!(time.0, expected);
+    /// assert_eq!(to_bytes(ctxt, &time).unwrap(), encoded);
+    /// ```
+    ///
+    /// # Out-of-range values
+    ///
+    /// * Decoding a value that `OffsetDateTime` can't represent (i-e after the year 9999) fails,
+    ///   rather than clamping it. Notably, this includes the `u64::MAX` used by systemd to mean
+    ///   "infinity": keep a plain `u64` for fields where that is expected.
+    /// * `0` is decoded as the epoch. Services often use it for "never", which is left for the
+    ///   caller to interpret.
+    /// * Encoding a time before the epoch fails, as it can't be represented. Any precision finer
+    ///   than a microsecond is discarded.
+    ///
+    /// Monotonic (`CLOCK_MONOTONIC`) timestamps are durations since boot, not points in time, and
+    /// hence are to be kept as integers.
+    UnixMicros,
+    1_000,
+    "microseconds"
+);
+
+/// A point in time, encoded as an [RFC 3339] (ISO 8601) string.
+///
+/// Strings that aren't valid RFC 3339 timestamps fail to decode. Encoding fails for times that
+/// can't be formatted as such, i-e with a year before 0 or after 9999.
+///
+/// ```
+/// use zvariant::{from_slice, to_bytes, EncodingContext, Rfc3339Timestamp};
+///
+/// let ctxt = EncodingContext::<byteorder::LE>::new_dbus(0);
+/// let encoded = to_bytes(ctxt, "2021-10-14T10:00:00.5+02:00").unwrap();
+///
+/// let time: Rfc3339Timestamp = from_slice(&encoded, ctxt).unwrap();
+/// assert_eq!(time.0.unix_timestamp(), 1_634_198_400);
+/// assert_eq!(time.0.millisecond(), 500);
+/// ```
+///
+/// [RFC 3339]: https://datatracker.ietf.org/doc/html/rfc3339
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rfc3339Timestamp(pub OffsetDateTime);
+
+impl Rfc3339Timestamp {
+    fn parse(s: &str) -> Result<Self, Error> {
+        OffsetDateTime::parse(s, &Rfc3339)
+            .map(Self)
+            .map_err(|e| Error::Message(format!("invalid RFC 3339 timestamp `{}`: {}", s, e)))
+    }
+
+    fn format(&self) -> Result<String, Error> {
+        self.0
+            .format(&Rfc3339)
+            .map_err(|e| Error::Message(format!("can't format `{}` as RFC 3339: {}", self.0, e)))
+    }
+}
+
+impl From<OffsetDateTime> for Rfc3339Timestamp {
+    fn from(time: OffsetDateTime) -> Self {
+        Self(time)
+    }
+}
+
+impl From<Rfc3339Timestamp> for OffsetDateTime {
+    fn from(time: Rfc3339Timestamp) -> Self {
+        time.0
+    }
+}
+
+impl Type for Rfc3339Timestamp {
+    #[inline]
+    fn signature() -> Signature<'static> {
+        <&str>::signature()
+    }
+}
+
+impl StaticType for Rfc3339Timestamp {
+    const SIGNATURE_STR: &'static str = <&str>::SIGNATURE_STR;
+}
+
+impl Serialize for Rfc3339Timestamp {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.format()
+            .map_err(ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rfc3339Timestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+
+        Self::parse(&s).map_err(de::Error::custom)
+    }
+}
+
+impl<'a> TryFrom<Value<'a>> for Rfc3339Timestamp {
+    type Error = Error;
+
+    fn try_from(value: Value<'a>) -> Result<Self, Self::Error> {
+        Self::parse(<&str>::try_from(&value)?)
+    }
+}
+
+impl TryFrom<OwnedValue> for Rfc3339Timestamp {
+    type Error = Error;
+
+    fn try_from(value: OwnedValue) -> Result<Self, Self::Error> {
+        Self::try_from(Value::from(value))
+    }
+}
+
+impl<'a> TryFrom<Rfc3339Timestamp> for Value<'a> {
+    type Error = Error;
+
+    fn try_from(time: Rfc3339Timestamp) -> Result<Self, Self::Error> {
+        time.format().map(Value::from)
+    }
+}
@@ -0,0 +1,397 @@
+use std::{fmt, ops::Deref};
+
+use static_assertions::assert_impl_all;
+
+use crate::{Array, Dict, Error, PathSegment, Result, Signature, StructureBuilder, Value};
+
+#[cfg(feature = "gvariant")]
+use crate::Maybe;
+
+/// The path from a value to a value nested in it.
+///
+/// This is what [`Value::visit`] and [`Value::try_map`] pass along with each value. The path of
+/// the outermost value is empty. The value of a GVariant [`Maybe`] has the path of the `Maybe`.
+///
+/// [`Value::visit`]: enum.Value.html#method.visit
+/// [`Value::try_map`]: enum.Value.html#method.try_map
+/// [`Maybe`]: struct.Maybe.html
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValuePath(Vec<PathSegment>);
+
+assert_impl_all!(ValuePath: Send, Sync, Unpin);
+
+impl ValuePath {
+    /// The segments of the path, from the outermost container.
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.0
+    }
+
+    /// The key of the dictionary entry holding the value, if it's the value of one.
+    ///
+    /// See [`PathSegment::key`] for the format of the keys.
+    ///
+    /// [`PathSegment::key`]: enum.PathSegment.html#method.key
+    pub fn key(&self) -> Option<&str> {
+        self.0.last().and_then(PathSegment::key)
+    }
+
+    fn push(&mut self, segment: PathSegment) {
+        self.0.push(segment);
+    }
+
+    fn pop(&mut self) {
+        self.0.pop();
+    }
+}
+
+impl Deref for ValuePath {
+    type Target = [PathSegment];
+
+    fn deref(&self) -> &Self::Target {
+        self.segments()
+    }
+}
+
+impl fmt::Display for ValuePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " → ")?;
+            }
+            write!(f, "{}", segment)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn visit<F>(value: &Value<'_>, path: &mut ValuePath, f: &mut F)
+where
+    F: FnMut(&ValuePath, &Value<'_>),
+{
+    f(path, value);
+
+    let mut visit_child = |child: &Value<'_>, segment| {
+        path.push(segment);
+        visit(child, path, f);
+        path.pop();
+    };
+    match value {
+        Value::Value(v) => visit_child(v, PathSegment::Variant),
+        Value::Array(a) => {
+            for (i, element) in a.get().iter().enumerate() {
+                visit_child(element, PathSegment::Element(i));
+            }
+        }
+        Value::Dict(d) => {
+            for (i, (key, value)) in d.iter().enumerate() {
+                visit_child(value, PathSegment::for_key(key, i));
+            }
+        }
+        Value::Structure(s) => {
+            for (i, field) in s.fields().iter().enumerate() {
+                visit_child(field, PathSegment::Field(i));
+            }
+        }
+        #[cfg(feature = "gvariant")]
+        Value::Maybe(m) => {
+            if let Some(v) = m.inner() {
+                visit(v, path, f);
+            }
+        }
+        _ => (),
+    }
+}
+
+pub(crate) fn try_map<'a, F>(
+    value: Value<'a>,
+    path: &mut ValuePath,
+    f: &mut F,
+) -> Result<Option<Value<'a>>>
+where
+    F: FnMut(&ValuePath, Value<'a>) -> Option<Value<'a>>,
+{
+    let value = match f(path, value) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+
+    let mut map_child = |child, segment: PathSegment| {
+        path.push(segment.clone());
+        let res = try_map(child, path, f).map_err(|e| e.in_path(segment));
+        path.pop();
+
+        res
+    };
+    let value = match value {
+        // A variant can't be empty, so it goes away with its value.
+        Value::Value(v) => match map_child(*v, PathSegment::Variant)? {
+            Some(v) => Value::Value(Box::new(v)),
+            None => return Ok(None),
+        },
+        Value::Array(a) => {
+            let element_signature = a.element_signature().to_owned();
+            let mut elements = vec![];
+            for (i, element) in a.into_elements().into_iter().enumerate() {
+                if let Some(element) = map_child(element, PathSegment::Element(i))? {
+                    elements.push(fit(&element_signature, element));
+                }
+            }
+
+            let element_signature = common_signature(element_signature, elements.iter())?;
+            let mut array: Array<'a> = Array::new(element_signature);
+            for element in elements {
+                array.append(element)?;
+            }
+
+            Value::Array(array)
+        }
+        Value::Dict(d) => {
+            let key_signature = d.key_signature().to_owned();
+            let value_signature = d.value_signature().to_owned();
+            let mut entries = vec![];
+            for (i, (key, value)) in d.into_entries().enumerate() {
+                let segment = PathSegment::for_key(&key, i);
+                if let Some(value) = map_child(value, segment)? {
+                    entries.push((key, fit(&value_signature, value)));
+                }
+            }
+
+            let value_signature = common_signature(value_signature, entries.iter().map(|e| &e.1))?;
+            let mut dict: Dict<'a, 'a> = Dict::new(key_signature, value_signature);
+            for (key, value) in entries {
+                dict.append(key, value)?;
+            }
+
+            Value::Dict(dict)
+        }
+        Value::Structure(s) => {
+            let mut builder = StructureBuilder::new();
+            let mut empty = true;
+            for (i, field) in s.into_fields().into_iter().enumerate() {
+                if let Some(field) = map_child(field, PathSegment::Field(i))? {
+                    builder = builder.append_field(field);
+                    empty = false;
+                }
+            }
+            if empty {
+                return Err(Error::Message(
+                    "all the fields of a structure were removed".into(),
+                ));
+            }
+
+            Value::Structure(builder.build())
+        }
+        #[cfg(feature = "gvariant")]
+        Value::Maybe(m) => {
+            let value_signature = m.value_signature().to_owned();
+            let value = match m.into_inner() {
+                Some(v) => try_map(v, path, f)?,
+                None => None,
+            };
+
+            Value::Maybe(match value {
+                Some(v) => Maybe::just(fit(&value_signature, v)),
+                None => Maybe::nothing(value_signature),
+            })
+        }
+        value => value,
+    };
+
+    Ok(Some(value))
+}
+
+// Wrap `value` into a variant if it replaced one, so an `a{sv}` stays one.
+fn fit<'a>(signature: &Signature<'_>, value: Value<'a>) -> Value<'a> {
+    if *signature == "v" && value.value_signature() != "v" {
+        return Value::new(value);
+    }
+
+    value
+}
+
+// The signature of the elements of a container, from the elements left after mapping them. It's
+// unchanged for an empty container.
+fn common_signature<'e, 'a: 'e, I>(
+    signature: Signature<'static>,
+    mut elements: I,
+) -> Result<Signature<'static>>
+where
+    I: Iterator<Item = &'e Value<'a>>,
+{
+    let first = match elements.next() {
+        Some(element) => element.value_signature(),
+        None => return Ok(signature),
+    };
+    for element in elements {
+        let other = element.value_signature();
+        if other != first {
+            return Err(Error::Message(format!(
+                "elements of a container mapped to different types: `{}` and `{}`",
+                first, other
+            )));
+        }
+    }
+
+    Ok(first.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, convert::TryFrom};
+
+    use crate::{Error, PathSegment, StructureBuilder, Value};
+
+    // An `a{sv}` of settings, with an `a{sv}` of account settings in it.
+    fn settings() -> Value<'static> {
+        let mut account = HashMap::new();
+        account.insert("user", Value::from("alice"));
+        account.insert("password", Value::from("hunter2"));
+        account.insert("avatar", Value::from(vec![1u8, 2, 3, 4, 5, 6]));
+        let mut settings = HashMap::new();
+        settings.insert("account", Value::from(account));
+        settings.insert("retries", Value::U32(3));
+
+        Value::from(settings)
+    }
+
+    // The entries of a dictionary, possibly in a variant.
+    fn entries(value: Value<'_>) -> HashMap<String, Value<'_>> {
+        match value {
+            Value::Dict(d) => HashMap::try_from(d).unwrap(),
+            Value::Value(v) => entries(*v),
+            v => panic!("not a dictionary: {:?}", v),
+        }
+    }
+
+    #[test]
+    fn visit() {
+        let mut paths = vec![];
+        settings().visit(&mut |path, value| {
+            if let Value::Str(s) = value {
+                paths.push(format!("{}: {}", path, s.as_str()));
+            }
+        });
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                "key 'account' → variant → key 'password' → variant: hunter2",
+                "key 'account' → variant → key 'user' → variant: alice",
+            ]
+        );
+
+        // The root has an empty path.
+        let mut count = 0;
+        Value::U8(1).visit(&mut |path, value| {
+            assert!(path.is_empty());
+            assert_eq!(value, &Value::U8(1));
+            count += 1;
+        });
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn try_map() {
+        // Redact the passwords and truncate the byte arrays, keeping the `a{sv}` types.
+        let redacted = settings()
+            .try_map(|path, value| match value {
+                _ if path.key() == Some("password") => Some(Value::from("***")),
+                Value::Array(a) if a.element_signature() == "y" => {
+                    let mut bytes = Vec::<u8>::try_from(a).unwrap();
+                    bytes.truncate(4);
+
+                    Some(Value::from(bytes))
+                }
+                value => Some(value),
+            })
+            .unwrap();
+        assert_eq!(redacted.value_signature(), "a{sv}");
+        let account = entries(entries(redacted).remove("account").unwrap());
+        assert_eq!(account["password"], Value::new(Value::from("***")));
+        assert_eq!(account["user"], Value::new(Value::from("alice")));
+        assert_eq!(
+            account["avatar"],
+            Value::new(Value::from(vec![1u8, 2, 3, 4]))
+        );
+
+        // Removing the value of a variant removes the variant, and so the dictionary entry.
+        let numbers = settings()
+            .try_map(|_, value| match value {
+                Value::Str(_) | Value::Array(_) => None,
+                value => Some(value),
+            })
+            .unwrap();
+        let mut numbers = entries(numbers);
+        assert_eq!(numbers["retries"], Value::new(Value::U32(3)));
+        let account = entries(numbers.remove("account").unwrap());
+        assert!(account.is_empty());
+
+        // Dropping elements of a typed array.
+        let array = Value::from(vec![1u32, 20, 3, 40]);
+        let small = array
+            .clone()
+            .try_map(|_, value| match value {
+                Value::U32(v) if v >= 10 => None,
+                value => Some(value),
+            })
+            .unwrap();
+        assert_eq!(small, Value::from(vec![1u32, 3]));
+        // The signature of empty arrays is kept.
+        let none = array
+            .clone()
+            .try_map(|_, value| match value {
+                Value::U32(_) => None,
+                value => Some(value),
+            })
+            .unwrap();
+        assert_eq!(none.value_signature(), "au");
+
+        // Replacing all the elements with another type changes the element signature.
+        let strings = array
+            .clone()
+            .try_map(|_, value| match value {
+                Value::U32(v) => Some(Value::from(v.to_string())),
+                value => Some(value),
+            })
+            .unwrap();
+        assert_eq!(strings.value_signature(), "as");
+
+        // But not only some of them.
+        let err = array
+            .try_map(|path, value| match value {
+                Value::U32(v) if path.segments() == [PathSegment::Element(1)] => {
+                    Some(Value::from(v.to_string()))
+                }
+                value => Some(value),
+            })
+            .unwrap_err();
+        assert_eq!(
+            err,
+            Error::Message("elements of a container mapped to different types: `u` and `s`".into())
+        );
+
+        // The path of the errors in nested containers is tracked.
+        let structure = Value::from(
+            StructureBuilder::new()
+                .add_field(1u8)
+                .add_field(StructureBuilder::new().add_field(2u8).build())
+                .build(),
+        );
+        let err = structure
+            .clone()
+            .try_map(|_, value| match value {
+                Value::U8(2) => None,
+                value => Some(value),
+            })
+            .unwrap_err();
+        assert_eq!(err.path(), [PathSegment::Field(1)]);
+        let fields = structure
+            .try_map(|_, value| match value {
+                Value::U8(1) => None,
+                value => Some(value),
+            })
+            .unwrap();
+        assert_eq!(fields.value_signature(), "((y))");
+    }
+}
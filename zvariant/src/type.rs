@@ -1,4 +1,5 @@
 use crate::{utils::*, Signature};
+use std::{borrow::Cow, rc::Rc, sync::Arc};
 
 /// Trait implemented by all serializable types.
 ///
@@ -10,9 +11,16 @@ use crate::{utils::*, Signature};
 /// container types, such as, arrays, slices, tuples, [`Vec`] and [`HashMap`]. For easy
 /// implementation for custom types, use `Type` derive macro from [zvariant_derive] crate.
 ///
+/// Smart pointers ([`Box`], [`Rc`], [`Arc`] and [`Cow`]) have the same signature as the type they
+/// point to, so for example `Arc<str>` and `Vec<Arc<str>>` are encoded as `s` and `as`,
+/// respectively.
+///
 /// Please note, that API is [also provided] to serialize and deserialize types that do not
 /// implement this trait but then you have to provide the correct signature yourself.
 ///
+/// Types whose signature is known at compile time can also implement [`StaticType`], which gives
+/// the signature as a constant.
+///
 /// [D-Bus type system]: https://dbus.freedesktop.org/doc/dbus-specification.html#type-system
 /// [serialization and deserialization]: index.html#functions
 /// [`Serialize`]: https://docs.serde.rs/serde/trait.Serialize.html
@@ -20,8 +28,13 @@ use crate::{utils::*, Signature};
 /// [basic types]: trait.Basic.html
 /// [`Vec`]: https://doc.rust-lang.org/std/vec/struct.Vec.html
 /// [`HashMap`]: https://doc.rust-lang.org/std/collections/struct.HashMap.html
+/// [`Box`]: https://doc.rust-lang.org/std/boxed/struct.Box.html
+/// [`Rc`]: https://doc.rust-lang.org/std/rc/struct.Rc.html
+/// [`Arc`]: https://doc.rust-lang.org/std/sync/struct.Arc.html
+/// [`Cow`]: https://doc.rust-lang.org/std/borrow/enum.Cow.html
 /// [zvariant_derive]: https://docs.rs/zvariant_derive/2.0.0/zvariant_derive/
 /// [also provided]: fn.to_bytes_for_signature.html
+/// [`StaticType`]: trait.StaticType.html
 pub trait Type {
     /// Get the signature for the implementing type.
     ///
@@ -98,6 +111,34 @@ where
     }
 }
 
+macro_rules! smart_ptr_type {
+    ($ptr:ident) => {
+        impl<T> Type for $ptr<T>
+        where
+            T: ?Sized + Type,
+        {
+            #[inline]
+            fn signature() -> Signature<'static> {
+                T::signature()
+            }
+        }
+    };
+}
+
+smart_ptr_type!(Box);
+smart_ptr_type!(Rc);
+smart_ptr_type!(Arc);
+
+impl<'a, T> Type for Cow<'a, T>
+where
+    T: ?Sized + ToOwned + Type,
+{
+    #[inline]
+    fn signature() -> Signature<'static> {
+        T::signature()
+    }
+}
+
 #[cfg(feature = "gvariant")]
 impl<T> Type for Option<T>
 where
@@ -220,11 +261,12 @@ map_impl!(HashMap<K: Eq + Hash, V, H: BuildHasher>);
 #[cfg(feature = "enumflags2")]
 impl<F> Type for enumflags2::BitFlags<F>
 where
-    F: Type + enumflags2::RawBitFlags,
+    F: enumflags2::RawBitFlags,
+    F::Type: Type,
 {
     #[inline]
     fn signature() -> Signature<'static> {
-        F::signature()
+        F::Type::signature()
     }
 }
 
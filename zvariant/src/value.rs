@@ -13,8 +13,8 @@ use static_assertions::assert_impl_all;
 #[cfg(feature = "gvariant")]
 use crate::Maybe;
 use crate::{
-    signature_parser::SignatureParser, utils::*, Array, Basic, Dict, Fd, ObjectPath, OwnedValue,
-    Signature, Str, Structure, StructureBuilder, Type,
+    signature_parser::SignatureParser, utils::*, Array, Coerce, Dict, Fd, ObjectPath, OwnedValue,
+    Signature, StaticType, Str, Structure, StructureBuilder, Type, ValuePath,
 };
 
 /// A generic container, in the form of an enum that holds exactly one value of any of the other
@@ -354,6 +354,261 @@ impl<'a> Value<'a> {
     }
 }
 
+impl<'a> Value<'a> {
+    /// Convert a numeric value to the type `T`, without losing information.
+    ///
+    /// Unlike [`TryFrom<Value>`], which requires the exact type, this succeeds if the value is
+    /// representable in `T` and its type isn't wider than `T`:
+    ///
+    /// * integers are widened, e.g. a `u32` to `u64` or an `i16` to `i64`.
+    /// * the signedness can change if the value fits, e.g. a `u32` of 5 to `i32` or `i64`, but not
+    ///   an `i32` of -1 to `u32` or `u64`.
+    /// * integers of up to 32 bits convert to `f64`, but no `f64` converts to an integer.
+    ///
+    /// A narrowing conversion, e.g. a `u64` of 5 to `u8`, is rejected even though the value fits,
+    /// so the result doesn't depend on the value when its type is too wide. [`Value::Value`] is
+    /// looked through. Strings and booleans are never coerced, not even to numbers.
+    ///
+    /// Returns [`Error::IncorrectType`] if the value can't be coerced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zvariant::{Error, Value};
+    ///
+    /// assert_eq!(Value::U32(42).coerce::<u64>().unwrap(), 42);
+    /// assert_eq!(Value::U16(42).coerce::<i32>().unwrap(), 42);
+    /// assert_eq!(Value::I32(-1).coerce::<u64>().unwrap_err(), Error::IncorrectType);
+    /// assert_eq!(Value::U64(42).coerce::<u32>().unwrap_err(), Error::IncorrectType);
+    /// assert_eq!(Value::from("42").coerce::<u32>().unwrap_err(), Error::IncorrectType);
+    /// ```
+    ///
+    /// [`TryFrom<Value>`]: https://doc.rust-lang.org/std/convert/trait.TryFrom.html
+    /// [`Value::Value`]: enum.Value.html#variant.Value
+    /// [`Error::IncorrectType`]: enum.Error.html#variant.IncorrectType
+    pub fn coerce<T: Coerce>(&self) -> crate::Result<T> {
+        T::coerce_from(self).ok_or(crate::Error::IncorrectType)
+    }
+
+    /// Convert a numeric value to the type `T`, the same way an `as` cast does.
+    ///
+    /// Any numeric value converts to any numeric type, possibly losing information:
+    ///
+    /// * integers are truncated to the size of `T`, e.g. a `u32` of 256 is 0 as `u8` and an
+    ///   `i32` of -1 is `u32::MAX` as `u32`.
+    /// * floats are rounded toward zero and saturated, e.g. an `f64` of -1.5 is 0 as `u8`. NaN
+    ///   is 0.
+    /// * integers are rounded to the nearest `f64`.
+    ///
+    /// As with [`coerce`], [`Value::Value`] is looked through, and only numeric values are
+    /// coerced. Returns [`Error::IncorrectType`] for the others.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zvariant::Value;
+    ///
+    /// assert_eq!(Value::U32(256).coerce_lossy::<u8>().unwrap(), 0);
+    /// assert_eq!(Value::F64(41.9).coerce_lossy::<u32>().unwrap(), 41);
+    /// ```
+    ///
+    /// [`coerce`]: enum.Value.html#method.coerce
+    /// [`Value::Value`]: enum.Value.html#variant.Value
+    /// [`Error::IncorrectType`]: enum.Error.html#variant.IncorrectType
+    pub fn coerce_lossy<T: Coerce>(&self) -> crate::Result<T> {
+        T::coerce_lossy_from(self).ok_or(crate::Error::IncorrectType)
+    }
+
+    /// An estimate of the size of the value once serialized, e.g for accounting the memory that
+    /// values take.
+    ///
+    /// It's the size of the data of the value in the D-Bus format, without the padding that
+    /// aligns them: a string counts its length prefix and nul byte, an array or a dict its length
+    /// prefix and elements, and a [`Value::Value`] its signature. The values of a GVariant
+    /// [`Maybe`] count as in the D-Bus format, and nothing takes 0 bytes. Unlike
+    /// [`serialized_size`], this doesn't serialize the value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zvariant::Value;
+    ///
+    /// assert_eq!(Value::U32(42).serialized_size_hint(), 4);
+    /// // The length prefix, the 5 bytes of the string and the nul byte.
+    /// assert_eq!(Value::from("hello").serialized_size_hint(), 10);
+    /// // The signature `u` with its length prefix and nul byte, and the `u32`.
+    /// assert_eq!(Value::new(Value::U32(42)).serialized_size_hint(), 7);
+    /// ```
+    ///
+    /// [`Value::Value`]: enum.Value.html#variant.Value
+    /// [`Maybe`]: struct.Maybe.html
+    /// [`serialized_size`]: fn.serialized_size.html
+    pub fn serialized_size_hint(&self) -> usize {
+        match self {
+            Value::U8(_) => 1,
+            Value::I16(_) | Value::U16(_) => 2,
+            Value::Bool(_) | Value::I32(_) | Value::U32(_) | Value::Fd(_) => 4,
+            Value::I64(_) | Value::U64(_) | Value::F64(_) => 8,
+            Value::Str(s) => 4 + s.as_str().len() + 1,
+            Value::Signature(s) => 1 + s.len() + 1,
+            Value::ObjectPath(p) => 4 + p.len() + 1,
+            Value::Value(v) => 1 + v.value_signature().len() + 1 + v.serialized_size_hint(),
+            Value::Array(a) => {
+                4 + a
+                    .get()
+                    .iter()
+                    .map(Value::serialized_size_hint)
+                    .sum::<usize>()
+            }
+            Value::Dict(d) => 4 + d.entries_size_hint(),
+            Value::Structure(s) => s.fields().iter().map(Value::serialized_size_hint).sum(),
+            #[cfg(feature = "gvariant")]
+            Value::Maybe(m) => m.inner().as_ref().map_or(0, Value::serialized_size_hint),
+        }
+    }
+
+    /// Call `f` on the value and all the values nested in it, along with their path.
+    ///
+    /// The values are visited depth-first, each container before the values it holds: the
+    /// elements of arrays, the values of dictionaries (but not their keys), the fields of
+    /// structures and the values of variants.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use zvariant::Value;
+    ///
+    /// let mut props = HashMap::new();
+    /// props.insert("name", Value::from("zbus"));
+    /// let props = Value::from(props);
+    ///
+    /// let mut strings = vec![];
+    /// props.visit(&mut |path, value| {
+    ///     if let Value::Str(s) = value {
+    ///         strings.push(format!("{}: {}", path, s.as_str()));
+    ///     }
+    /// });
+    /// assert_eq!(strings, ["key 'name' → variant: zbus"]);
+    /// ```
+    pub fn visit<F>(&self, f: &mut F)
+    where
+        F: FnMut(&ValuePath, &Value<'_>),
+    {
+        crate::transform::visit(self, &mut ValuePath::default(), f)
+    }
+
+    /// Transform the value and all the values nested in it, through `f`.
+    ///
+    /// `f` gets each value along with its path, in the same order as [`visit`], and returns the
+    /// value to put in its place, or `None` to remove it. The values nested in the returned value
+    /// are then transformed in turn. Removing the value of a variant removes the variant as well,
+    /// and the value of a GVariant [`Maybe`] makes it nothing.
+    ///
+    /// The signatures of the containers are kept valid:
+    ///
+    /// * a value replacing a variant is wrapped in a variant, so e.g an `a{sv}` stays one.
+    /// * if all the elements of an array, or the values of a dictionary, are replaced with values
+    ///   of another type, the signature of the container changes to match.
+    /// * the signature of a structure changes with its fields.
+    ///
+    /// # Errors
+    ///
+    /// * if the elements of an array, or the values of a dictionary, end up of different types.
+    /// * if all the fields of a structure are removed.
+    /// * if the value itself is removed.
+    ///
+    /// The errors about nested containers are in the path of the container, as with
+    /// [`Error::InPath`].
+    ///
+    /// # Examples
+    ///
+    /// Redacting the passwords in an `a{sv}`:
+    ///
+    /// ```
+    /// use std::{collections::HashMap, convert::TryFrom};
+    /// use zvariant::Value;
+    ///
+    /// let mut props = HashMap::new();
+    /// props.insert("user", Value::from("alice"));
+    /// props.insert("password", Value::from("hunter2"));
+    ///
+    /// let props = Value::from(props)
+    ///     .try_map(|path, value| match path.key() {
+    ///         Some("password") => Some(Value::from("***")),
+    ///         _ => Some(value),
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(props.value_signature(), "a{sv}");
+    /// if let Value::Dict(dict) = props {
+    ///     let props = HashMap::<String, Value<'_>>::try_from(dict).unwrap();
+    ///     assert_eq!(props["password"], Value::new(Value::from("***")));
+    /// }
+    /// ```
+    ///
+    /// [`visit`]: enum.Value.html#method.visit
+    /// [`Maybe`]: struct.Maybe.html
+    /// [`Error::InPath`]: enum.Error.html#variant.InPath
+    pub fn try_map<F>(self, mut f: F) -> crate::Result<Value<'a>>
+    where
+        F: FnMut(&ValuePath, Value<'a>) -> Option<Value<'a>>,
+    {
+        crate::transform::try_map(self, &mut ValuePath::default(), &mut f)?
+            .ok_or_else(|| crate::Error::Message("the value itself was removed".into()))
+    }
+
+    /// Parse `text` as a value of type `signature`, with the syntax of `busctl` arguments.
+    ///
+    /// This is meant for values given by users, e.g on the command line, where the type is only
+    /// known at runtime. `signature` must be a single complete type:
+    ///
+    /// * basic types are given as is. Booleans can also be `yes`/`no`, `on`/`off` or `1`/`0`.
+    /// * a variant is the signature of its value, a `:` and the value, e.g `u:42`.
+    /// * array elements are separated by commas, optionally within brackets, e.g `1,2,3` or
+    ///   `[1,2,3]`. Arrays nested in arrays must be within brackets.
+    /// * dictionary entries are `key=value` pairs separated by commas, optionally within braces.
+    /// * structure fields are separated by commas, within parentheses, e.g `(1,one)`.
+    ///
+    /// A backslash escapes the character after it, e.g `\,` is a comma within a string.
+    ///
+    /// # Errors
+    ///
+    /// If `signature` isn't a single complete type, or `text` isn't a valid value of that type.
+    /// File descriptors can't be given as text either. The errors about nested values are in
+    /// their path, as with [`Error::InPath`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::{collections::HashMap, convert::TryFrom};
+    /// use zvariant::{Signature, Value};
+    ///
+    /// let signature = Signature::try_from("a{sv}").unwrap();
+    /// let props = Value::from_str_with_signature("name=s:zbus,tags=as:[a,b]", &signature)
+    ///     .unwrap();
+    /// if let Value::Dict(dict) = props {
+    ///     let props = HashMap::<String, Value<'_>>::try_from(dict).unwrap();
+    ///     assert_eq!(props["name"], Value::new(Value::from("zbus")));
+    ///     assert_eq!(props["tags"], Value::new(Value::from(vec!["a", "b"])));
+    /// }
+    ///
+    /// let signature = Signature::try_from("(ub)").unwrap();
+    /// let err = Value::from_str_with_signature("(1,maybe)", &signature).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "invalid `b` value `maybe`: not a boolean (at field 1)",
+    /// );
+    /// ```
+    ///
+    /// [`Error::InPath`]: enum.Error.html#variant.InPath
+    pub fn from_str_with_signature(
+        text: &str,
+        signature: &Signature<'_>,
+    ) -> crate::Result<Value<'static>> {
+        crate::parse::parse(text, signature)
+    }
+}
+
 impl<'a> Serialize for Value<'a> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -432,11 +687,21 @@ impl<'de> Visitor<'de> for ValueVisitor {
     }
 }
 
-struct ValueSeed<'de, T> {
+pub(crate) struct ValueSeed<'de, T> {
     signature: Signature<'de>,
     phantom: PhantomData<T>,
 }
 
+impl<'de> ValueSeed<'de, Value<'de>> {
+    // A seed for the value alone, of the given signature, rather than for a variant.
+    pub(crate) fn new(signature: Signature<'de>) -> Self {
+        Self {
+            signature,
+            phantom: PhantomData,
+        }
+    }
+}
+
 impl<'de, T> ValueSeed<'de, T>
 where
     T: Deserialize<'de>,
@@ -695,3 +960,7 @@ impl<'a> Type for Value<'a> {
         Signature::from_str_unchecked(VARIANT_SIGNATURE_STR)
     }
 }
+
+impl<'a> StaticType for Value<'a> {
+    const SIGNATURE_STR: &'static str = VARIANT_SIGNATURE_STR;
+}
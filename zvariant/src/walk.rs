@@ -0,0 +1,554 @@
+use std::{marker::PhantomData, os::unix::io::RawFd};
+
+use serde::de::{DeserializeSeed, Error as _, MapAccess, SeqAccess, Unexpected, Visitor};
+
+#[cfg(feature = "gvariant")]
+use crate::gvariant::Deserializer as GVDeserializer;
+use crate::{
+    dbus::Deserializer as DBusDeserializer, signature_parser::SignatureParser, Deserializer,
+    EncodingContext, EncodingFormat, Error, Fd, ObjectPath, Result, Signature, StaticType, Str,
+    Value,
+};
+
+/// Callbacks for walking an encoded value whose type is only known at runtime.
+///
+/// A walker is driven by [`walk_slice`] or [`walk_value`], which go through the value in the order
+/// of its signature and call back into the walker for each node. Basic values are passed as a
+/// [`Value`], while containers get a pair of start and end calls, with everything they contain
+/// walked in between. All methods do nothing by default, so implementations only need to override
+/// the ones they care about.
+///
+/// Any error returned by the walker stops the walk and is returned as is, to the caller of the walk
+/// function.
+///
+/// # Examples
+///
+/// Counting the entries of all the dictionaries in a value, without decoding it into a type:
+///
+/// ```
+/// use std::collections::HashMap;
+/// use zvariant::{to_bytes, walk_slice, EncodingContext, Result, Signature, Walker};
+///
+/// #[derive(Default)]
+/// struct EntryCounter(usize);
+///
+/// impl<'de> Walker<'de> for EntryCounter {
+///     fn dict_entry_start(&mut self) -> Result<()> {
+///         self.0 += 1;
+///
+///         Ok(())
+///     }
+/// }
+///
+/// let ctxt = EncodingContext::<byteorder::LE>::new_dbus(0);
+/// let mut map = HashMap::new();
+/// map.insert("one", 1u32);
+/// map.insert("two", 2u32);
+/// let encoded = to_bytes(ctxt, &map).unwrap();
+///
+/// let mut counter = EntryCounter::default();
+/// let signature = Signature::try_from("a{su}").unwrap();
+/// walk_slice(&encoded, ctxt, &signature, &mut counter).unwrap();
+/// assert_eq!(counter.0, 2);
+/// # use std::convert::TryFrom;
+/// ```
+///
+/// [`walk_slice`]: fn.walk_slice.html
+/// [`walk_value`]: fn.walk_value.html
+/// [`Value`]: enum.Value.html
+pub trait Walker<'de> {
+    /// A basic value, including file descriptors.
+    fn basic(&mut self, _value: Value<'de>) -> Result<()> {
+        Ok(())
+    }
+
+    /// The start of an array of elements of type `element_signature`.
+    ///
+    /// Arrays of dictionary entries are dictionaries, for which [`dict_start`] is called instead.
+    ///
+    /// [`dict_start`]: #method.dict_start
+    fn array_start(&mut self, _element_signature: &Signature<'_>) -> Result<()> {
+        Ok(())
+    }
+
+    /// The end of an array.
+    fn array_end(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The start of a dictionary.
+    fn dict_start(
+        &mut self,
+        _key_signature: &Signature<'_>,
+        _value_signature: &Signature<'_>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// The start of a dictionary entry, followed by its key and then its value.
+    fn dict_entry_start(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The end of a dictionary entry.
+    fn dict_entry_end(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The end of a dictionary.
+    fn dict_end(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The start of a structure of type `signature`, followed by its fields.
+    fn struct_start(&mut self, _signature: &Signature<'_>) -> Result<()> {
+        Ok(())
+    }
+
+    /// The end of a structure.
+    fn struct_end(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The start of a variant, holding a value of type `signature`.
+    fn variant_start(&mut self, _signature: &Signature<'_>) -> Result<()> {
+        Ok(())
+    }
+
+    /// The end of a variant.
+    fn variant_end(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The start of a maybe of type `signature`, followed by its value if `present` is `true`.
+    #[cfg(feature = "gvariant")]
+    fn maybe_start(&mut self, _signature: &Signature<'_>, _present: bool) -> Result<()> {
+        Ok(())
+    }
+
+    /// The end of a maybe.
+    #[cfg(feature = "gvariant")]
+    fn maybe_end(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Walk the value of type `signature`, encoded in `bytes`.
+///
+/// This is the runtime-typed counterpart of [`from_slice_for_signature`]: instead of decoding into a
+/// type, the value is reported node by node to `walker`. Borrowed strings, signatures and object
+/// paths are passed without copying them.
+///
+/// [`from_slice_for_signature`]: fn.from_slice_for_signature.html
+pub fn walk_slice<'de, B, W>(
+    bytes: &'de [u8],
+    ctxt: EncodingContext<B>,
+    signature: &Signature<'_>,
+    walker: &mut W,
+) -> Result<()>
+where
+    B: byteorder::ByteOrder,
+    W: Walker<'de> + ?Sized,
+{
+    walk_slice_fds(bytes, None, ctxt, signature, walker)
+}
+
+/// Walk the value of type `signature`, encoded in `bytes` and containing file descriptor indices.
+///
+/// The file descriptors are passed to the walker as [`Fd`] values. See [`walk_slice`] for details.
+///
+/// [`Fd`]: struct.Fd.html
+/// [`walk_slice`]: fn.walk_slice.html
+pub fn walk_slice_fds<'de, B, W>(
+    bytes: &'de [u8],
+    fds: Option<&[RawFd]>,
+    ctxt: EncodingContext<B>,
+    signature: &Signature<'_>,
+    walker: &mut W,
+) -> Result<()>
+where
+    B: byteorder::ByteOrder,
+    W: Walker<'de> + ?Sized,
+{
+    let mut de = match ctxt.format() {
+        #[cfg(feature = "gvariant")]
+        EncodingFormat::GVariant => {
+            Deserializer::GVariant(GVDeserializer::new(bytes, fds, signature, ctxt))
+        }
+        EncodingFormat::DBus => {
+            Deserializer::DBus(DBusDeserializer::new(bytes, fds, signature, ctxt))
+        }
+    };
+    let mut state = WalkState {
+        walker,
+        error: None,
+        phantom: PhantomData,
+    };
+    let seed = WalkSeed {
+        signature: signature.to_owned(),
+        state: &mut state,
+    };
+
+    match seed.deserialize(&mut de) {
+        Ok(()) => Ok(()),
+        // Report the walker's own error rather than the one the deserializer wrapped it in.
+        Err(e) => Err(state.error.take().unwrap_or(e)),
+    }
+}
+
+/// Walk a decoded `value`, in the same order as [`walk_slice`] would walk its encoding.
+///
+/// [`walk_slice`]: fn.walk_slice.html
+pub fn walk_value<'a, W>(value: &Value<'a>, walker: &mut W) -> Result<()>
+where
+    W: Walker<'a> + ?Sized,
+{
+    match value {
+        Value::Array(array) => {
+            walker.array_start(array.element_signature())?;
+            for element in array.get() {
+                walk_value(element, walker)?;
+            }
+            walker.array_end()
+        }
+        Value::Dict(dict) => {
+            walker.dict_start(dict.key_signature(), dict.value_signature())?;
+            for (key, value) in dict.iter() {
+                walker.dict_entry_start()?;
+                walk_value(key, walker)?;
+                walk_value(value, walker)?;
+                walker.dict_entry_end()?;
+            }
+            walker.dict_end()
+        }
+        Value::Structure(structure) => {
+            walker.struct_start(structure.full_signature())?;
+            for field in structure.fields() {
+                walk_value(field, walker)?;
+            }
+            walker.struct_end()
+        }
+        Value::Value(inner) => {
+            walker.variant_start(&inner.value_signature())?;
+            walk_value(inner, walker)?;
+            walker.variant_end()
+        }
+        #[cfg(feature = "gvariant")]
+        Value::Maybe(maybe) => {
+            walker.maybe_start(maybe.full_signature(), maybe.inner().is_some())?;
+            if let Some(inner) = maybe.inner() {
+                walk_value(inner, walker)?;
+            }
+            walker.maybe_end()
+        }
+        basic => walker.basic(basic.clone()),
+    }
+}
+
+struct WalkState<'w, 'de, W: ?Sized> {
+    walker: &'w mut W,
+    // The first error returned by `walker`, if any.
+    error: Option<Error>,
+    phantom: PhantomData<&'de ()>,
+}
+
+impl<'w, 'de, W> WalkState<'w, 'de, W>
+where
+    W: Walker<'de> + ?Sized,
+{
+    // Run a walker callback, turning its error into one of the deserializer's type.
+    fn call<F, E>(&mut self, f: F) -> std::result::Result<(), E>
+    where
+        F: FnOnce(&mut W) -> Result<()>,
+        E: serde::de::Error,
+    {
+        f(&mut *self.walker).map_err(|e| {
+            let de_error = E::custom(&e);
+            self.error.get_or_insert(e);
+
+            de_error
+        })
+    }
+}
+
+struct WalkSeed<'s, 'w, 'de, W: ?Sized> {
+    signature: Signature<'de>,
+    state: &'s mut WalkState<'w, 'de, W>,
+}
+
+impl<'s, 'w, 'de, W> WalkSeed<'s, 'w, 'de, W>
+where
+    W: Walker<'de> + ?Sized,
+{
+    fn nested<'n>(&'n mut self, signature: Signature<'de>) -> WalkSeed<'n, 'w, 'de, W> {
+        WalkSeed {
+            signature,
+            state: &mut *self.state,
+        }
+    }
+
+    fn basic<E>(self, value: Value<'de>) -> std::result::Result<(), E>
+    where
+        E: serde::de::Error,
+    {
+        self.state.call(|w| w.basic(value))
+    }
+
+    #[inline]
+    fn visit_array<V>(mut self, mut visitor: V) -> std::result::Result<(), V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let element_signature = self.signature.slice(1..);
+        self.state.call(|w| w.array_start(&element_signature))?;
+        while visitor
+            .next_element_seed(self.nested(element_signature.clone()))?
+            .is_some()
+        {}
+
+        self.state.call(|w| w.array_end())
+    }
+
+    #[inline]
+    fn visit_struct<V>(mut self, mut visitor: V) -> std::result::Result<(), V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let signature = self.signature.clone();
+        self.state.call(|w| w.struct_start(&signature))?;
+
+        let mut i = 1;
+        let signature_end = signature.len() - 1;
+        while i < signature_end {
+            let fields_signature = signature.slice(i..signature_end);
+            let parser = SignatureParser::new(fields_signature.clone());
+            let len = parser.next_signature().map_err(V::Error::custom)?.len();
+            let field_signature = fields_signature.slice(0..len);
+            i += field_signature.len();
+
+            visitor.next_element_seed(self.nested(field_signature))?;
+        }
+
+        self.state.call(|w| w.struct_end())
+    }
+
+    #[inline]
+    fn visit_variant<V>(mut self, mut visitor: V) -> std::result::Result<(), V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let signature = visitor.next_element::<Signature<'_>>()?.ok_or_else(|| {
+            V::Error::invalid_value(Unexpected::Other("nothing"), &"a Value signature")
+        })?;
+        self.state.call(|w| w.variant_start(&signature))?;
+        visitor
+            .next_element_seed(self.nested(signature))?
+            .ok_or_else(|| {
+                V::Error::invalid_value(Unexpected::Other("nothing"), &"a Value value")
+            })?;
+
+        self.state.call(|w| w.variant_end())
+    }
+}
+
+macro_rules! walk_seed_basic_method {
+    ($name:ident, $type:ty) => {
+        #[inline]
+        fn $name<E>(self, value: $type) -> std::result::Result<(), E>
+        where
+            E: serde::de::Error,
+        {
+            self.basic(value.into())
+        }
+    };
+}
+
+macro_rules! walk_seed_str_method {
+    ($name:ident, $type:ty, $constructor:ident) => {
+        #[inline]
+        fn $name<E>(self, value: $type) -> std::result::Result<(), E>
+        where
+            E: serde::de::Error,
+        {
+            let value = match self.signature.as_str() {
+                <&str>::SIGNATURE_STR => Value::Str(Str::from(value)),
+                Signature::SIGNATURE_STR => Value::Signature(Signature::$constructor(value)),
+                ObjectPath::SIGNATURE_STR => Value::ObjectPath(ObjectPath::$constructor(value)),
+                _ => {
+                    let expected = format!(
+                        "`{}`, `{}` or `{}`",
+                        <&str>::SIGNATURE_STR,
+                        Signature::SIGNATURE_STR,
+                        ObjectPath::SIGNATURE_STR,
+                    );
+                    return Err(E::invalid_type(
+                        Unexpected::Str(self.signature.as_str()),
+                        &expected.as_str(),
+                    ));
+                }
+            };
+
+            self.basic(value)
+        }
+    };
+}
+
+impl<'s, 'w, 'de, W> Visitor<'de> for WalkSeed<'s, 'w, 'de, W>
+where
+    W: Walker<'de> + ?Sized,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "a value of type `{}`", self.signature.as_str())
+    }
+
+    walk_seed_basic_method!(visit_bool, bool);
+    walk_seed_basic_method!(visit_i16, i16);
+    walk_seed_basic_method!(visit_i64, i64);
+    walk_seed_basic_method!(visit_u8, u8);
+    walk_seed_basic_method!(visit_u16, u16);
+    walk_seed_basic_method!(visit_u32, u32);
+    walk_seed_basic_method!(visit_u64, u64);
+    walk_seed_basic_method!(visit_f64, f64);
+
+    #[inline]
+    fn visit_i32<E>(self, value: i32) -> std::result::Result<(), E>
+    where
+        E: serde::de::Error,
+    {
+        let value = match self.signature.as_bytes().first() {
+            Some(b'h') => Fd::from(value).into(),
+            _ => value.into(),
+        };
+
+        self.basic(value)
+    }
+
+    #[inline]
+    fn visit_str<E>(self, value: &str) -> std::result::Result<(), E>
+    where
+        E: serde::de::Error,
+    {
+        self.visit_string(String::from(value))
+    }
+
+    walk_seed_str_method!(visit_string, String, from_string_unchecked);
+    walk_seed_str_method!(visit_borrowed_str, &'de str, from_str_unchecked);
+
+    #[inline]
+    fn visit_seq<V>(self, visitor: V) -> std::result::Result<(), V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        match self.signature.as_bytes().first().ok_or_else(|| {
+            V::Error::invalid_value(
+                Unexpected::Other("nothing"),
+                &"Array or Struct signature character",
+            )
+        })? {
+            b'a' => self.visit_array(visitor),
+            b'(' => self.visit_struct(visitor),
+            b'v' => self.visit_variant(visitor),
+            b => Err(V::Error::invalid_value(
+                Unexpected::Char(*b as char),
+                &"a Value signature",
+            )),
+        }
+    }
+
+    #[inline]
+    fn visit_map<V>(mut self, mut visitor: V) -> std::result::Result<(), V::Error>
+    where
+        V: MapAccess<'de>,
+    {
+        let key_signature = self.signature.slice(2..3);
+        let signature_end = self.signature.len() - 1;
+        let value_signature = self.signature.slice(3..signature_end);
+        self.state
+            .call(|w| w.dict_start(&key_signature, &value_signature))?;
+
+        loop {
+            // The entry only starts once we know there is one, i.e once the key is read.
+            let key = EntryKeySeed(self.nested(key_signature.clone()));
+            if visitor.next_key_seed(key)?.is_none() {
+                break;
+            }
+            visitor.next_value_seed(self.nested(value_signature.clone()))?;
+            self.state.call(|w| w.dict_entry_end())?;
+        }
+
+        self.state.call(|w| w.dict_end())
+    }
+
+    #[cfg(feature = "gvariant")]
+    fn visit_some<D>(mut self, deserializer: D) -> std::result::Result<(), D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        let signature = self.signature.clone();
+        self.state.call(|w| w.maybe_start(&signature, true))?;
+        deserializer.deserialize_any(self.nested(signature.slice(1..)))?;
+
+        self.state.call(|w| w.maybe_end())
+    }
+
+    #[cfg(not(feature = "gvariant"))]
+    fn visit_some<D>(self, _deserializer: D) -> std::result::Result<(), D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        panic!("`Maybe` type is only supported for GVariant format but it's disabled");
+    }
+
+    #[cfg(feature = "gvariant")]
+    fn visit_none<E>(self) -> std::result::Result<(), E>
+    where
+        E: serde::de::Error,
+    {
+        let signature = self.signature.clone();
+        self.state.call(|w| w.maybe_start(&signature, false))?;
+
+        self.state.call(|w| w.maybe_end())
+    }
+
+    #[cfg(not(feature = "gvariant"))]
+    fn visit_none<E>(self) -> std::result::Result<(), E>
+    where
+        E: serde::de::Error,
+    {
+        panic!("`Maybe` type is only supported for GVariant format but it's disabled");
+    }
+}
+
+impl<'s, 'w, 'de, W> DeserializeSeed<'de> for WalkSeed<'s, 'w, 'de, W>
+where
+    W: Walker<'de> + ?Sized,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<(), D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+// Seed for the key of a dictionary entry, starting the entry before walking the key.
+struct EntryKeySeed<'s, 'w, 'de, W: ?Sized>(WalkSeed<'s, 'w, 'de, W>);
+
+impl<'s, 'w, 'de, W> DeserializeSeed<'de> for EntryKeySeed<'s, 'w, 'de, W>
+where
+    W: Walker<'de> + ?Sized,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<(), D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        self.0.state.call(|w| w.dict_entry_start())?;
+        deserializer.deserialize_any(self.0)
+    }
+}
@@ -1,7 +1,8 @@
 use proc_macro2::{Span, TokenStream};
 use quote::{format_ident, quote};
 use syn::{
-    punctuated::Punctuated, Data, DeriveInput, Meta::Path, NestedMeta::Meta, Type, TypePath,
+    punctuated::Punctuated, Data, DeriveInput, ExprPath, Field, GenericArgument, Ident, Meta::Path,
+    NestedMeta::Meta, PathArguments, Type, TypePath,
 };
 
 use crate::utils::*;
@@ -21,12 +22,110 @@ pub fn expand_type_derive(input: DeriveInput) -> TokenStream {
         #where_clause
         {
             fn signature() -> #zv::Signature<'static> {
-                #zv::Signature::from_str_unchecked("a{sv}")
+                #zv::Signature::from_str_unchecked(<Self as #zv::StaticType>::SIGNATURE_STR)
             }
         }
+
+        impl #impl_generics #zv::StaticType for #name #ty_generics
+        #where_clause
+        {
+            const SIGNATURE_STR: &'static str = "a{sv}";
+        }
+    }
+}
+
+// How a struct field maps to a dictionary entry.
+struct Entry<'f> {
+    ident: &'f Ident,
+    dict_name: String,
+    // Only present in the dictionary if `Some`.
+    optional: bool,
+    // Entered as is, rather than wrapped in a `SerializeValue`/`DeserializeValue`.
+    variant: bool,
+    // The value of the field if the dictionary has no entry for it, if it may be missing.
+    default: Option<Option<ExprPath>>,
+}
+
+impl<'f> Entry<'f> {
+    fn new(f: &'f Field) -> Self {
+        let ident = f.ident.as_ref().expect("Only works with named fields");
+        let mut dict_name = ident.to_string();
+        let mut optional = is_option(&f.ty);
+        let mut variant = is_value(option_inner(&f.ty).unwrap_or(&f.ty));
+        let mut default = None;
+
+        for attr in parse_item_attributes(&f.attrs).unwrap() {
+            match attr {
+                ItemAttribute::Rename(n) => dict_name = n,
+                ItemAttribute::Default(path) => {
+                    default = Some(path.map(|p| {
+                        syn::parse_str(&p)
+                            .unwrap_or_else(|_| panic!("invalid `default` function `{}`", p))
+                    }))
+                }
+                ItemAttribute::SkipSerializingIfNone => optional = true,
+                ItemAttribute::Variant(v) => variant = v,
+            }
+        }
+
+        Self {
+            ident,
+            dict_name,
+            optional,
+            variant,
+            default,
+        }
+    }
+}
+
+fn entries(fields: &syn::Fields) -> Vec<Entry<'_>> {
+    let entries: Vec<_> = fields.iter().map(Entry::new).collect();
+    for (i, e) in entries.iter().enumerate() {
+        if entries[..i]
+            .iter()
+            .any(|other| other.dict_name == e.dict_name)
+        {
+            panic!("duplicate dictionary entry `{}`", e.dict_name);
+        }
+    }
+
+    entries
+}
+
+fn last_segment(ty: &Type) -> Option<&syn::PathSegment> {
+    match ty {
+        Type::Path(TypePath {
+            path: syn::Path { segments, .. },
+            ..
+        }) => segments.last(),
+        _ => None,
     }
 }
 
+fn is_option(ty: &Type) -> bool {
+    last_segment(ty).map_or(false, |s| s.ident == "Option")
+}
+
+// The `T` of an `Option<T>`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    if !is_option(ty) {
+        return None;
+    }
+
+    match &last_segment(ty)?.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+// Whether `ty` is already a variant, which would end up in a variant of its own otherwise.
+fn is_value(ty: &Type) -> bool {
+    last_segment(ty).map_or(false, |s| s.ident == "Value" || s.ident == "OwnedValue")
+}
+
 pub fn expand_serialize_derive(input: DeriveInput) -> TokenStream {
     let (name, data) = match input.data {
         Data::Struct(data) => (input.ident, data),
@@ -36,33 +135,33 @@ pub fn expand_serialize_derive(input: DeriveInput) -> TokenStream {
     let zv = zvariant_path();
     let mut entries = quote! {};
 
-    for f in &data.fields {
-        let attrs = parse_item_attributes(&f.attrs).unwrap();
-        let name = &f.ident;
-        let dict_name = attrs
-            .iter()
-            .find_map(|x| match x {
-                ItemAttribute::Rename(n) => Some(n.to_string()),
-            })
-            .unwrap_or_else(|| f.ident.as_ref().unwrap().to_string());
-
-        let is_option = match &f.ty {
-            Type::Path(TypePath {
-                path: syn::Path { segments, .. },
-                ..
-            }) => segments.last().unwrap().ident == "Option",
-            _ => false,
+    for e in self::entries(&data.fields) {
+        let Entry {
+            ident,
+            dict_name,
+            optional,
+            variant,
+            ..
+        } = &e;
+        let value = |v: TokenStream| {
+            if *variant {
+                v
+            } else {
+                quote! { &#zv::SerializeValue(#v) }
+            }
         };
 
-        let e = if is_option {
+        let e = if *optional {
+            let value = value(quote! { v });
             quote! {
-                if self.#name.is_some() {
-                    map.serialize_entry(#dict_name, &#zv::SerializeValue(self.#name.as_ref().unwrap()))?;
+                if let ::std::option::Option::Some(v) = &self.#ident {
+                    map.serialize_entry(#dict_name, #value)?;
                 }
             }
         } else {
+            let value = value(quote! { &self.#ident });
             quote! {
-                map.serialize_entry(#dict_name, &#zv::SerializeValue(&self.#name))?;
+                map.serialize_entry(#dict_name, #value)?;
             }
         };
 
@@ -109,50 +208,58 @@ pub fn expand_deserialize_derive(input: DeriveInput) -> TokenStream {
 
     let visitor = format_ident!("{}Visitor", name);
     let zv = zvariant_path();
+    let entries = self::entries(&data.fields);
+    // The values are collected in locals of their own, not to clash with those of the visitor.
+    let vars: Vec<_> = (0..entries.len())
+        .map(|i| format_ident!("__field{}", i))
+        .collect();
     let mut fields = Vec::new();
-    let mut req_fields = Vec::new();
     let mut dict_names = Vec::new();
-    let mut entries = Vec::new();
+    let mut arms = Vec::new();
 
-    for f in &data.fields {
-        let attrs = parse_item_attributes(&f.attrs).unwrap();
-        let name = &f.ident;
-        let dict_name = attrs
-            .iter()
-            .find_map(|x| match x {
-                ItemAttribute::Rename(n) => Some(n.to_string()),
-            })
-            .unwrap_or_else(|| f.ident.as_ref().unwrap().to_string());
-
-        let is_option = match &f.ty {
-            Type::Path(TypePath {
-                path: syn::Path { segments, .. },
-                ..
-            }) => segments.last().unwrap().ident == "Option",
-            _ => false,
-        };
+    for (e, var) in entries.iter().zip(&vars) {
+        let dict_name = &e.dict_name;
+        let ident = e.ident;
 
-        entries.push(quote! {
+        let value = if e.variant {
+            quote! { __access.next_value()? }
+        } else {
+            quote! { __access.next_value::<#zv::DeserializeValue<_>>()?.0 }
+        };
+        arms.push(quote! {
             #dict_name => {
-                // FIXME: add an option about strict parsing (instead of silently skipping the field)
-                #name = access.next_value::<#zv::DeserializeValue<_>>().map(|v| v.0).ok();
+                #var = ::std::option::Option::Some(#value);
             }
         });
 
+        let value = match (&e.default, e.optional) {
+            (Some(Some(default)), true) => quote! { #var.or_else(#default) },
+            (_, true) => quote! { #var },
+            (Some(Some(default)), false) => quote! { #var.unwrap_or_else(#default) },
+            (Some(None), false) => {
+                quote! { #var.unwrap_or_else(::std::default::Default::default) }
+            }
+            (None, false) => quote! {
+                match #var {
+                    ::std::option::Option::Some(val) => val,
+                    ::std::option::Option::None => {
+                        return ::std::result::Result::Err(
+                            <M::Error as #zv::export::serde::de::Error>::missing_field(#dict_name),
+                        );
+                    }
+                }
+            },
+        };
+        fields.push(quote! { #ident: #value });
         dict_names.push(dict_name);
-        fields.push(name);
-
-        if !is_option {
-            req_fields.push(name);
-        }
     }
 
     let fallback = if deny_unknown_fields {
         quote! {
-            field => {
+            __field => {
                 return ::std::result::Result::Err(
                     <M::Error as #zv::export::serde::de::Error>::unknown_field(
-                        field,
+                        __field,
                         &[#(#dict_names),*],
                     ),
                 );
@@ -160,12 +267,12 @@ pub fn expand_deserialize_derive(input: DeriveInput) -> TokenStream {
         }
     } else {
         quote! {
-            unknown => {
-                let _ = access.next_value::<#zv::Value>();
+            _ => {
+                let _ = __access.next_value::<#zv::Value>();
             }
         }
     };
-    entries.push(fallback);
+    arms.push(fallback);
 
     let (_, ty_generics, _) = input.generics.split_for_impl();
     let mut generics = input.generics.clone();
@@ -201,30 +308,20 @@ pub fn expand_deserialize_derive(input: DeriveInput) -> TokenStream {
 
                     fn visit_map<M>(
                         self,
-                        mut access: M,
+                        mut __access: M,
                     ) -> ::std::result::Result<Self::Value, M::Error>
                     where
                         M: #zv::export::serde::de::MapAccess<'de>,
                     {
-                        #( let mut #fields = ::std::default::Default::default(); )*
+                        #( let mut #vars = ::std::option::Option::None; )*
 
                         // does not check duplicated fields, since those shouldn't exist in stream
-                        while let ::std::option::Option::Some(key) = access.next_key::<&str>()? {
-                            match key {
-                                #(#entries)*
+                        while let ::std::option::Option::Some(__key) = __access.next_key::<&str>()? {
+                            match __key {
+                                #(#arms)*
                             }
                         }
 
-                        #(let #req_fields = if let ::std::option::Option::Some(val) = #req_fields {
-                            val
-                        } else {
-                            return ::std::result::Result::Err(
-                                <M::Error as #zv::export::serde::de::Error>::missing_field(
-                                    ::std::stringify!(#req_fields),
-                                ),
-                            );
-                        };)*
-
                         ::std::result::Result::Ok(#name { #(#fields),* })
                     }
                 }
@@ -9,6 +9,7 @@ use proc_macro::TokenStream;
 use syn::{self, DeriveInput};
 
 mod dict;
+mod newtype;
 mod r#type;
 mod utils;
 mod value;
@@ -96,6 +97,43 @@ pub fn type_macro_derive(input: TokenStream) -> TokenStream {
     r#type::expand_derive(ast).into()
 }
 
+/// Derive macro to add [`StaticType`] implementation to structs and enums.
+///
+/// The signature is the same as the one the [`Type`] derive macro gives, but as a constant, built
+/// at compile time from those of the fields. So all the fields must be [`StaticType`] as well, and
+/// so must be the type parameters of generic types.
+///
+/// # Examples
+///
+/// ```
+/// use zvariant::StaticType;
+/// use zvariant_derive::{StaticType, Type};
+///
+/// #[derive(Type, StaticType)]
+/// struct Pair<K: zvariant::Type, V: zvariant::Type> {
+///     key: K,
+///     value: V,
+/// }
+///
+/// #[repr(u8)]
+/// #[derive(Type, StaticType)]
+/// enum Enum {
+///     Variant1,
+///     Variant2,
+/// }
+///
+/// const SIGNATURE: &str = <Pair<String, Vec<Enum>>>::SIGNATURE_STR;
+/// assert_eq!(SIGNATURE, "(say)");
+/// ```
+///
+/// [`StaticType`]: ../zvariant/trait.StaticType.html
+/// [`Type`]: derive.Type.html
+#[proc_macro_derive(StaticType)]
+pub fn static_type_macro_derive(input: TokenStream) -> TokenStream {
+    let ast: DeriveInput = syn::parse(input).unwrap();
+    r#type::expand_static_derive(ast).into()
+}
+
 /// Derive macro to add [`Type`] implementation to structs serialized as `a{sv}` type.
 ///
 /// # Examples
@@ -112,8 +150,16 @@ pub fn type_macro_derive(input: TokenStream) -> TokenStream {
 /// assert_eq!(Struct::signature(), Signature::from_str_unchecked("a{sv}"));
 /// ```
 ///
+/// The [`StaticType`] implementation, with the same signature, is generated as well.
+///
+/// The signature is always `a{sv}`, whatever the `zvariant` attributes of the struct and its
+/// fields (see [`SerializeDict`] and [`DeserializeDict`]).
+///
 /// [`Type`]: ../zvariant/trait.Type.html
-#[proc_macro_derive(TypeDict)]
+/// [`StaticType`]: ../zvariant/trait.StaticType.html
+/// [`SerializeDict`]: derive.SerializeDict.html
+/// [`DeserializeDict`]: derive.DeserializeDict.html
+#[proc_macro_derive(TypeDict, attributes(zvariant))]
 pub fn type_dict_macro_derive(input: TokenStream) -> TokenStream {
     let ast: DeriveInput = syn::parse(input).unwrap();
     dict::expand_type_derive(ast).into()
@@ -146,7 +192,20 @@ pub fn type_dict_macro_derive(input: TokenStream) -> TokenStream {
 /// The serialized D-Bus version of `Struct {42, 77, None}`
 /// will be `{"field1": Value::U16(42), "another-name": Value::I64(77)}`.
 ///
+/// # Field attributes
+///
+/// * `rename = "name"`: the key of the entry, instead of the name of the field.
+/// * `skip_serializing_if_none`: the field is an `Option`, with no entry when `None`. This is
+///   implied for fields of type `Option<T>` and only needed when the type is spelled differently,
+///   e.g. through an alias.
+/// * `variant`: the field is serialized as the variant of the entry as is, rather than wrapped
+///   in one. This is implied for fields of type [`Value`] and [`OwnedValue`] (or options of
+///   these), which would end up in a variant of their own otherwise.
+/// * `wrap`: the opposite of `variant`, to wrap a `Value` in another variant.
+///
 /// [`Serialize`]: https://docs.serde.rs/serde/trait.Serialize.html
+/// [`Value`]: ../zvariant/enum.Value.html
+/// [`OwnedValue`]: ../zvariant/struct.OwnedValue.html
 #[proc_macro_derive(SerializeDict, attributes(zvariant))]
 pub fn serialize_dict_macro_derive(input: TokenStream) -> TokenStream {
     let input: DeriveInput = syn::parse(input).unwrap();
@@ -180,7 +239,65 @@ pub fn serialize_dict_macro_derive(input: TokenStream) -> TokenStream {
 /// The deserialized D-Bus dictionary `{"field1": Value::U16(42), "another-name": Value::I64(77)}`
 /// will be `Struct {42, 77, None}`.
 ///
+/// Missing entries are an error, except for `Option` fields. Entries of an unexpected type are
+/// always an error.
+///
+/// # Attributes
+///
+/// The field attributes of [`SerializeDict`] apply, along with:
+///
+/// * `default`: a missing entry gives the [`Default`] value of the field.
+/// * `default = "path::to::function"`: a missing entry gives the value returned by this function,
+///   e.g. to keep accepting the dictionaries of older versions of a protocol. For `Option` fields,
+///   the function returns an `Option`.
+///
+/// Unknown entries are ignored, unless the struct has the `deny_unknown_fields` attribute:
+///
+/// ```
+/// use zvariant::{EncodingContext, from_slice, to_bytes};
+/// use zvariant_derive::{DeserializeDict, SerializeDict, TypeDict};
+/// use byteorder::LE;
+///
+/// #[derive(SerializeDict, TypeDict)]
+/// struct V2 {
+///     #[zvariant(rename = "Name")]
+///     name: String,
+///     #[zvariant(rename = "Timeout")]
+///     timeout: u32,
+/// }
+///
+/// fn default_timeout() -> u32 {
+///     25
+/// }
+///
+/// #[derive(DeserializeDict, TypeDict, Debug)]
+/// #[zvariant(deny_unknown_fields)]
+/// struct V1 {
+///     #[zvariant(rename = "Name")]
+///     name: String,
+///     #[zvariant(rename = "Timeout", default = "default_timeout")]
+///     timeout: u32,
+/// }
+///
+/// let ctxt = EncodingContext::<LE>::new_dbus(0);
+/// let v2 = V2 { name: "zbus".into(), timeout: 5 };
+/// let encoded = to_bytes(ctxt, &v2).unwrap();
+/// let v1: V1 = from_slice(&encoded, ctxt).unwrap();
+/// assert_eq!(v1.timeout, 5);
+///
+/// #[derive(SerializeDict, TypeDict)]
+/// struct Unnamed {
+///     #[zvariant(rename = "Timeout")]
+///     timeout: u32,
+/// }
+/// let encoded = to_bytes(ctxt, &Unnamed { timeout: 5 }).unwrap();
+/// let res: zvariant::Result<V1> = from_slice(&encoded, ctxt);
+/// assert_eq!(res.unwrap_err().to_string(), "missing field `Name`");
+/// ```
+///
 /// [`Deserialize`]: https://docs.serde.rs/serde/de/trait.Deserialize.html
+/// [`SerializeDict`]: derive.SerializeDict.html
+/// [`Default`]: https://doc.rust-lang.org/std/default/trait.Default.html
 #[proc_macro_derive(DeserializeDict, attributes(zvariant))]
 pub fn deserialize_dict_macro_derive(input: TokenStream) -> TokenStream {
     let input: DeriveInput = syn::parse(input).unwrap();
@@ -189,7 +306,9 @@ pub fn deserialize_dict_macro_derive(input: TokenStream) -> TokenStream {
 
 /// Implements conversions for your type to/from [`Value`].
 ///
-/// Implements `TryFrom<Value>` and `Into<Value>` for your type.
+/// Implements `TryFrom<Value>` and `Into<Value>` for your type. Newtype structures are converted
+/// as their inner value, and `TryFrom<&Value>` is implemented for them as well, so that they can be
+/// the type of a writable property of a D-Bus interface.
 ///
 /// # Examples
 ///
@@ -293,8 +412,43 @@ pub fn deserialize_dict_macro_derive(input: TokenStream) -> TokenStream {
 /// assert_eq!(e, Enum::Variant2);
 /// ```
 ///
+/// # Validation
+///
+/// The inner value of a newtype structure can be validated with
+/// `#[zvariant(validate = "path::to::function")]`, to uphold the invariants of the type. The
+/// function takes a reference to the inner value and returns a `Result<(), E>`, with `E`
+/// implementing [`Display`]. The conversion fails with an [`Error::Message`] of the error returned.
+/// Combined with [`DeserializeNewtype`], values of the type can't be created without validation:
+///
+/// ```
+///# use std::convert::TryFrom;
+///# use zvariant::{OwnedValue, Value};
+/// use serde::Serialize;
+/// use zvariant_derive::{DeserializeNewtype, OwnedValue, Type, Value};
+///
+/// #[derive(Debug, Serialize, DeserializeNewtype, Type, Value, OwnedValue)]
+/// #[zvariant(validate = "Percent::validate")]
+/// struct Percent(u8);
+///
+/// impl Percent {
+///     fn validate(value: &u8) -> Result<(), String> {
+///         match value {
+///             0..=100 => Ok(()),
+///             _ => Err(format!("{} isn't a percentage", value)),
+///         }
+///     }
+/// }
+///
+/// assert_eq!(Percent::try_from(&Value::U8(42)).unwrap().0, 42);
+/// let e = Percent::try_from(OwnedValue::from(101u8)).unwrap_err();
+/// assert_eq!(e.to_string(), "101 isn't a percentage");
+/// ```
+///
 /// [`Value`]: https://docs.rs/zvariant/2.0.0/zvariant/enum.Value.html
-#[proc_macro_derive(Value)]
+/// [`Display`]: https://doc.rust-lang.org/std/fmt/trait.Display.html
+/// [`Error::Message`]: https://docs.rs/zvariant/2.0.0/zvariant/enum.Error.html#variant.Message
+/// [`DeserializeNewtype`]: derive.DeserializeNewtype.html
+#[proc_macro_derive(Value, attributes(zvariant))]
 pub fn value_macro_derive(input: TokenStream) -> TokenStream {
     let ast: DeriveInput = syn::parse(input).unwrap();
     value::expand_derive(ast, value::ValueType::Value).into()
@@ -304,11 +458,52 @@ pub fn value_macro_derive(input: TokenStream) -> TokenStream {
 ///
 /// Implements `TryFrom<OwnedValue>` and `Into<OwnedValue>` for your type.
 ///
-/// See [`Value`] documentation for examples.
+/// See [`Value`] documentation for examples, and the validation of newtype structures.
 ///
 /// [`OwnedValue`]: https://docs.rs/zvariant/2.0.0/zvariant/struct.OwnedValue.html
-#[proc_macro_derive(OwnedValue)]
+#[proc_macro_derive(OwnedValue, attributes(zvariant))]
 pub fn owned_value_macro_derive(input: TokenStream) -> TokenStream {
     let ast: DeriveInput = syn::parse(input).unwrap();
     value::expand_derive(ast, value::ValueType::OwnedValue).into()
 }
+
+/// Adds [`Deserialize`] implementation to newtype structures, validating their inner value.
+///
+/// The structure is deserialized as its inner value, like with serde's `transparent` attribute,
+/// which is then passed to the function given with `#[zvariant(validate = "path::to::function")]`,
+/// if any. See [`Value`] for the validation function, which the conversions from [`Value`] use as
+/// well. A validation failure is a deserialization error:
+///
+/// ```
+/// use zvariant::{from_slice, to_bytes, EncodingContext};
+/// use zvariant_derive::{DeserializeNewtype, Type};
+/// use byteorder::LE;
+///
+/// #[derive(Debug, DeserializeNewtype, Type)]
+/// #[zvariant(validate = "validate_percent")]
+/// struct Percent(u8);
+///
+/// fn validate_percent(value: &u8) -> Result<(), String> {
+///     match value {
+///         0..=100 => Ok(()),
+///         _ => Err(format!("{} isn't a percentage", value)),
+///     }
+/// }
+///
+/// let ctxt = EncodingContext::<LE>::new_dbus(0);
+/// let encoded = to_bytes(ctxt, &42u8).unwrap();
+/// let percent: Percent = from_slice(&encoded, ctxt).unwrap();
+/// assert_eq!(percent.0, 42);
+///
+/// let encoded = to_bytes(ctxt, &101u8).unwrap();
+/// let res: zvariant::Result<Percent> = from_slice(&encoded, ctxt);
+/// assert_eq!(res.unwrap_err().to_string(), "101 isn't a percentage");
+/// ```
+///
+/// [`Deserialize`]: https://docs.serde.rs/serde/de/trait.Deserialize.html
+/// [`Value`]: derive.Value.html
+#[proc_macro_derive(DeserializeNewtype, attributes(zvariant))]
+pub fn deserialize_newtype_macro_derive(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = syn::parse(input).unwrap();
+    newtype::expand_deserialize_derive(input).into()
+}
@@ -0,0 +1,59 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{parse_quote, Data, DeriveInput, Fields, GenericParam, Lifetime, LifetimeDef};
+
+use crate::utils::*;
+
+pub fn expand_deserialize_derive(input: DeriveInput) -> TokenStream {
+    let field = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0],
+            _ => panic!("Only works with newtype structures"),
+        },
+        _ => panic!("Only works with newtype structures"),
+    };
+    let name = &input.ident;
+    let ty = &field.ty;
+    let zv = zvariant_path();
+    let validate = parse_validate_attribute(&input.attrs)
+        .unwrap()
+        .map(|validate| {
+            quote! {
+                if let ::std::result::Result::Err(e) = #validate(&inner) {
+                    return ::std::result::Result::Err(
+                        <D::Error as #zv::export::serde::de::Error>::custom(e),
+                    );
+                }
+            }
+        });
+
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let mut generics = input.generics.clone();
+    let de = LifetimeDef::new(Lifetime::new("'de", Span::call_site()));
+    generics.params = Some(GenericParam::Lifetime(de))
+        .into_iter()
+        .chain(generics.params)
+        .collect();
+    generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote!(#ty: #zv::export::serde::de::Deserialize<'de>));
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #zv::export::serde::de::Deserialize<'de> for #name #ty_generics
+        #where_clause
+        {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: #zv::export::serde::de::Deserializer<'de>,
+            {
+                let inner =
+                    <#ty as #zv::export::serde::de::Deserialize<'de>>::deserialize(deserializer)?;
+                #validate
+
+                ::std::result::Result::Ok(Self(inner))
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
-use syn::{self, Attribute, Data, DataEnum, DeriveInput, Fields, Generics, Ident};
+use syn::{self, parse_quote, Attribute, Data, DataEnum, DeriveInput, Fields, Generics, Ident};
 
 use crate::utils::zvariant_path;
 
@@ -80,6 +80,20 @@ fn impl_enum(
     data: DataEnum,
     zv: &TokenStream,
 ) -> TokenStream {
+    let repr = enum_repr(&attrs, &data);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #zv::Type for #name #ty_generics #where_clause {
+            #[inline]
+            fn signature() -> #zv::Signature<'static> {
+                <#repr as #zv::Type>::signature()
+            }
+        }
+    }
+}
+
+fn enum_repr(attrs: &[Attribute], data: &DataEnum) -> TokenStream {
     let repr: TokenStream = match attrs.iter().find(|attr| attr.path.is_ident("repr")) {
         Some(repr_attr) => repr_attr
             .parse_args()
@@ -87,7 +101,7 @@ fn impl_enum(
         None => quote! { u32 },
     };
 
-    for variant in data.variants {
+    for variant in &data.variants {
         // Ensure all variants of the enum are unit type
         match variant.fields {
             Fields::Unit => (),
@@ -95,14 +109,68 @@ fn impl_enum(
         }
     }
 
-    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    repr
+}
+
+pub fn expand_static_derive(mut ast: DeriveInput) -> TokenStream {
+    let zv = zvariant_path();
+
+    let signature = match &ast.data {
+        Data::Struct(ds) => match &ds.fields {
+            Fields::Named(_) | Fields::Unnamed(_) => static_signature_for_struct(&ds.fields, &zv),
+            Fields::Unit => quote! { "" },
+        },
+        Data::Enum(data) => {
+            let repr = enum_repr(&ast.attrs, data);
+
+            quote! { <#repr as #zv::StaticType>::SIGNATURE_STR }
+        }
+        _ => panic!("Only structures and enums supported at the moment"),
+    };
+
+    // The signature of a generic type is only known at compile time if those of its type
+    // parameters are.
+    let params: Vec<_> = ast
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    let predicates = &mut ast.generics.make_where_clause().predicates;
+    for param in params {
+        predicates.push(parse_quote! { #param: #zv::StaticType });
+    }
+
+    let name = ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
 
     quote! {
-        impl #impl_generics #zv::Type for #name #ty_generics #where_clause {
-            #[inline]
-            fn signature() -> #zv::Signature<'static> {
-                <#repr as #zv::Type>::signature()
-            }
+        impl #impl_generics #zv::StaticType for #name #ty_generics #where_clause {
+            const SIGNATURE_STR: &'static str = #signature;
+        }
+    }
+}
+
+fn static_signature_for_struct(fields: &Fields, zv: &TokenStream) -> TokenStream {
+    let field_types = fields.iter().map(|field| field.ty.to_token_stream());
+    let new_type = match fields {
+        Fields::Unnamed(_) => field_types.len() == 1,
+        _ => false,
+    };
+    if new_type {
+        quote! {
+            #(
+                <#field_types as #zv::StaticType>::SIGNATURE_STR
+             )*
+        }
+    } else {
+        quote! {
+            (&#zv::ConstSignature::new()
+                .push("(")
+                #(
+                    .push(<#field_types as #zv::StaticType>::SIGNATURE_STR)
+                )*
+                .push(")"))
+                .as_str()
         }
     }
 }
@@ -1,7 +1,7 @@
 use proc_macro2::TokenStream;
 use proc_macro_crate::{crate_name, FoundCrate};
 use quote::{format_ident, quote};
-use syn::{Attribute, Lit, Meta, Meta::List, MetaList, NestedMeta, Result};
+use syn::{Attribute, ExprPath, Lit, Meta, Meta::List, NestedMeta, Result};
 
 pub fn zvariant_path() -> TokenStream {
     if let Ok(FoundCrate::Name(name)) = crate_name("zvariant") {
@@ -12,18 +12,6 @@ pub fn zvariant_path() -> TokenStream {
     }
 }
 
-// find the #[@attr_name] attribute in @attrs
-fn find_attribute_meta(attrs: &[Attribute], attr_name: &str) -> Result<Option<MetaList>> {
-    let meta = match attrs.iter().find(|a| a.path.is_ident(attr_name)) {
-        Some(a) => a.parse_meta(),
-        _ => return Ok(None),
-    };
-    match meta? {
-        Meta::List(n) => Ok(Some(n)),
-        _ => panic!("wrong meta type"),
-    }
-}
-
 // parse a single meta like: ident = "value"
 fn parse_attribute(meta: &NestedMeta) -> (String, String) {
     let meta = match &meta {
@@ -51,6 +39,11 @@ fn parse_attribute(meta: &NestedMeta) -> (String, String) {
 #[derive(Debug, PartialEq)]
 pub enum ItemAttribute {
     Rename(String),
+    // The function giving the value of a missing entry, `Default::default` if `None`.
+    Default(Option<String>),
+    SkipSerializingIfNone,
+    // Whether the field is the variant itself, rather than the value to wrap in one.
+    Variant(bool),
 }
 
 fn parse_item_attribute(meta: &NestedMeta) -> Result<ItemAttribute> {
@@ -58,23 +51,26 @@ fn parse_item_attribute(meta: &NestedMeta) -> Result<ItemAttribute> {
 
     match ident.as_ref() {
         "rename" => Ok(ItemAttribute::Rename(v)),
+        "default" if v.is_empty() => Ok(ItemAttribute::Default(None)),
+        "default" => Ok(ItemAttribute::Default(Some(v))),
+        "skip_serializing_if_none" => Ok(ItemAttribute::SkipSerializingIfNone),
+        "variant" => Ok(ItemAttribute::Variant(true)),
+        "wrap" => Ok(ItemAttribute::Variant(false)),
         s => panic!("Unknown item meta {}", s),
     }
 }
 
 // Parse optional item attributes such as:
 // #[zvariant(rename = "MyName")]
+//
+// All the `zvariant` attributes of the item are parsed, not only the first one.
 pub fn parse_item_attributes(attrs: &[Attribute]) -> Result<Vec<ItemAttribute>> {
-    let meta = find_attribute_meta(attrs, "zvariant")?;
-
-    let v = match meta {
-        Some(meta) => meta
-            .nested
-            .iter()
-            .map(|m| parse_item_attribute(m).unwrap())
-            .collect(),
-        None => Vec::new(),
-    };
+    let mut v = Vec::new();
+    for attr in attrs {
+        for meta in get_meta_items(attr)? {
+            v.push(parse_item_attribute(&meta)?);
+        }
+    }
 
     Ok(v)
 }
@@ -89,3 +85,24 @@ pub fn get_meta_items(attr: &Attribute) -> Result<Vec<NestedMeta>> {
         _ => panic!("unsupported attribute"),
     }
 }
+
+// Parse the function validating the inner value of a newtype structure, if any:
+// #[zvariant(validate = "path::to::function")]
+//
+// The other `zvariant` attributes are left to the derives they're for.
+pub fn parse_validate_attribute(attrs: &[Attribute]) -> Result<Option<ExprPath>> {
+    for attr in attrs {
+        for meta in get_meta_items(attr)? {
+            if let NestedMeta::Meta(Meta::NameValue(n)) = &meta {
+                if n.path.is_ident("validate") {
+                    return match &n.lit {
+                        Lit::Str(s) => s.parse().map(Some),
+                        _ => panic!("`validate` must be the path of a function, as a string"),
+                    };
+                }
+            }
+        }
+    }
+
+    Ok(None)
+}
@@ -1,11 +1,11 @@
 use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens};
 use syn::{
-    self, Attribute, Data, DataEnum, DeriveInput, Expr, Fields, Generics, Ident, Lifetime,
-    LifetimeDef,
+    self, Attribute, Data, DataEnum, DeriveInput, Expr, ExprPath, Fields, GenericParam, Generics,
+    Ident, Lifetime, LifetimeDef,
 };
 
-use crate::utils::zvariant_path;
+use crate::utils::{parse_validate_attribute, zvariant_path};
 
 pub enum ValueType {
     Value,
@@ -14,14 +14,26 @@ pub enum ValueType {
 
 pub fn expand_derive(ast: DeriveInput, value_type: ValueType) -> TokenStream {
     let zv = zvariant_path();
+    let validate = parse_validate_attribute(&ast.attrs).unwrap();
 
     match ast.data {
         Data::Struct(ds) => match ds.fields {
-            Fields::Named(_) | Fields::Unnamed(_) => {
-                impl_struct(value_type, ast.ident, ast.generics, ds.fields, &zv)
-            }
+            Fields::Unnamed(_) if ds.fields.len() == 1 => impl_newtype(
+                value_type,
+                ast.ident,
+                ast.generics,
+                ds.fields,
+                validate,
+                &zv,
+            ),
+            _ if validate.is_some() => panic!("`validate` is only supported on newtype structures"),
+            Fields::Named(_) => impl_struct(value_type, ast.ident, ast.generics, ds.fields, &zv),
+            Fields::Unnamed(_) => panic!("Tuple structures not supported"),
             Fields::Unit => panic!("Unit structures not supported"),
         },
+        Data::Enum(_) if validate.is_some() => {
+            panic!("`validate` is only supported on newtype structures")
+        }
         Data::Enum(data) => impl_enum(value_type, ast.ident, ast.generics, ast.attrs, data, &zv),
         _ => panic!("Only structures and enums supported at the moment"),
     }
@@ -114,32 +126,105 @@ fn impl_struct(
                 }
             }
         }
-        Fields::Unnamed(_) if fields.iter().next().is_some() => {
-            // Newtype struct.
-            quote! {
-                impl #impl_generics ::std::convert::TryFrom<#value_type> for #name #ty_generics
-                    #from_value_where_clause
-                {
-                    type Error = #zv::Error;
+        Fields::Unnamed(_) => panic!("impl_struct must not be called for tuples"),
+        Fields::Unit => panic!("impl_struct must not be called for unit structures"),
+    }
+}
 
-                    #[inline]
-                    fn try_from(value: #value_type) -> #zv::Result<Self> {
-                        ::std::convert::TryInto::try_into(value).map(Self)
-                    }
-                }
+fn impl_newtype(
+    value_type: ValueType,
+    name: Ident,
+    generics: Generics,
+    fields: Fields,
+    validate: Option<ExprPath>,
+    zv: &TokenStream,
+) -> TokenStream {
+    let ty = &fields.iter().next().unwrap().ty;
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let mut impl_generics = generics.clone();
+    let mut lifetimes = generics.lifetimes();
+    let value_type = match value_type {
+        // Unlike for other structures, the conversion from a `Value` of any lifetime is possible,
+        // if that of the inner value is. Property setters need it.
+        ValueType::Value => {
+            let value_lifetime = match lifetimes.next() {
+                Some(lifetime) => lifetime.lifetime.clone(),
+                None => {
+                    let lifetime = Lifetime::new("'__v", Span::call_site());
+                    impl_generics.params.insert(
+                        0,
+                        GenericParam::Lifetime(LifetimeDef::new(lifetime.clone())),
+                    );
 
-                impl #impl_generics From<#name #ty_generics> for #value_type
-                    #into_value_where_clause
-                {
-                    #[inline]
-                    fn from(s: #name) -> Self {
-                        s.0.into()
-                    }
+                    lifetime
                 }
+            };
+            if lifetimes.next().is_some() {
+                panic!("Type with more than 1 lifetime not supported");
+            }
+
+            quote! { #zv::Value<#value_lifetime> }
+        }
+        ValueType::OwnedValue => quote! { #zv::OwnedValue },
+    };
+    let validate = validate.map(|validate| {
+        quote! {
+            #validate(&inner)
+                .map_err(|e| #zv::Error::Message(::std::string::ToString::to_string(&e)))?;
+        }
+    });
+
+    let where_clause = generics.type_params().next().map(|_| {
+        quote! {
+            where
+                #ty: ::std::convert::TryFrom<#value_type, Error = #zv::Error>
+                    + ::std::convert::Into<#value_type>
+        }
+    });
+    let mut ref_generics = impl_generics.clone();
+    let ref_lifetime = Lifetime::new("'__r", Span::call_site());
+    ref_generics.params.insert(
+        0,
+        GenericParam::Lifetime(LifetimeDef::new(ref_lifetime.clone())),
+    );
+    let (ref_generics, _, _) = ref_generics.split_for_impl();
+    let (impl_generics, _, _) = impl_generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::std::convert::TryFrom<#value_type> for #name #ty_generics
+            #where_clause
+        {
+            type Error = #zv::Error;
+
+            #[inline]
+            fn try_from(value: #value_type) -> #zv::Result<Self> {
+                let inner = <#ty as ::std::convert::TryFrom<#value_type>>::try_from(value)?;
+                #validate
+
+                ::std::result::Result::Ok(Self(inner))
+            }
+        }
+
+        impl #ref_generics ::std::convert::TryFrom<&#ref_lifetime #value_type>
+            for #name #ty_generics
+            #where_clause
+        {
+            type Error = #zv::Error;
+
+            #[inline]
+            fn try_from(value: &#ref_lifetime #value_type) -> #zv::Result<Self> {
+                ::std::convert::TryFrom::try_from(::std::clone::Clone::clone(value))
+            }
+        }
+
+        impl #impl_generics From<#name #ty_generics> for #value_type
+            #where_clause
+        {
+            #[inline]
+            fn from(s: #name #ty_generics) -> Self {
+                s.0.into()
             }
         }
-        Fields::Unnamed(_) => panic!("impl_struct must not be called for tuples"),
-        Fields::Unit => panic!("impl_struct must not be called for unit structures"),
     }
 }
 
@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 
-use zvariant::Type;
-use zvariant_derive::{DeserializeDict, SerializeDict, Type, TypeDict};
+use zvariant::{StaticType, Type};
+use zvariant_derive::{DeserializeDict, SerializeDict, StaticType, Type, TypeDict};
 
 #[test]
 fn derive_unit_struct() {
@@ -47,5 +47,454 @@ fn derive_dict() {
         field_c: Vec<u8>,
     }
 
-    assert_eq!(Test::signature(), "a{sv}")
+    assert_eq!(Test::signature(), "a{sv}");
+    assert_eq!(Test::SIGNATURE_STR, "a{sv}");
+}
+
+#[test]
+fn derive_static_type() {
+    use std::collections::HashMap;
+
+    #[derive(Type, StaticType)]
+    struct Pair<K: Type, V: Type> {
+        key: K,
+        value: V,
+    }
+
+    #[derive(Type, StaticType)]
+    struct Wrapper<T: Type>(T);
+
+    #[derive(Type, StaticType)]
+    struct Nested<T: Type> {
+        id: u64,
+        pairs: Vec<Pair<String, T>>,
+        index: HashMap<u32, Wrapper<Pair<T, [u8; 2]>>>,
+    }
+
+    #[derive(Type, StaticType)]
+    struct Unit;
+
+    #[repr(u8)]
+    #[derive(Type, StaticType)]
+    enum Enum {
+        Variant1,
+        Variant2,
+    }
+
+    // Usable where only constants are, such as in a static.
+    static SIGNATURES: [&str; 5] = [
+        <Pair<u8, Vec<String>>>::SIGNATURE_STR,
+        <Wrapper<Pair<i32, bool>>>::SIGNATURE_STR,
+        <Nested<Wrapper<f64>>>::SIGNATURE_STR,
+        <(Unit, Nested<()>)>::SIGNATURE_STR,
+        <Vec<Enum>>::SIGNATURE_STR,
+    ];
+    assert_eq!(
+        SIGNATURES,
+        [
+            "(yas)",
+            "(ib)",
+            "(ta(sd)a{u(d(yy))})",
+            "((ta(s)a{u((yy))}))",
+            "ay"
+        ]
+    );
+
+    // Same as the signatures of the `Type` implementations.
+    assert_eq!(<Pair<u8, Vec<String>>>::signature(), SIGNATURES[0]);
+    assert_eq!(<Wrapper<Pair<i32, bool>>>::signature(), SIGNATURES[1]);
+    assert_eq!(<Nested<Wrapper<f64>>>::signature(), SIGNATURES[2]);
+    assert_eq!(<(Unit, Nested<()>)>::signature(), SIGNATURES[3]);
+    assert_eq!(<Vec<Enum>>::signature(), SIGNATURES[4]);
+}
+
+#[test]
+fn derive_newtype() {
+    use byteorder::LE;
+    use std::convert::TryFrom;
+    use zvariant::{from_slice, to_bytes, EncodingContext, OwnedValue, Str, Value};
+    use zvariant_derive::{DeserializeNewtype, OwnedValue, Value};
+
+    #[derive(Debug, PartialEq, serde::Serialize, DeserializeNewtype, Type, Value, OwnedValue)]
+    #[zvariant(validate = "Percent::validate")]
+    struct Percent(u8);
+
+    impl Percent {
+        fn validate(value: &u8) -> Result<(), String> {
+            match value {
+                0..=100 => Ok(()),
+                _ => Err(format!("{}% is out of range", value)),
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, DeserializeNewtype, Type, Value)]
+    struct Name<'a>(Str<'a>);
+
+    assert_eq!(Percent::signature(), "y");
+    let ctxt = EncodingContext::<LE>::new_dbus(0);
+    let encoded = to_bytes(ctxt, &(42u8, 101u8)).unwrap();
+    let decoded: (Percent, u8) = from_slice(&encoded, ctxt).unwrap();
+    assert_eq!(decoded.0, Percent(42));
+    let res: zvariant::Result<(u8, Percent)> = from_slice(&encoded, ctxt);
+    assert_eq!(res.unwrap_err().to_string(), "101% is out of range");
+
+    assert_eq!(Value::from(Percent(42)), Value::U8(42));
+    assert_eq!(Percent::try_from(Value::U8(42)).unwrap(), Percent(42));
+    assert_eq!(Percent::try_from(&Value::U8(42)).unwrap(), Percent(42));
+    assert_eq!(
+        Percent::try_from(OwnedValue::from(42u8)).unwrap(),
+        Percent(42)
+    );
+    let err = Percent::try_from(&Value::U8(101)).unwrap_err();
+    assert_eq!(err, zvariant::Error::Message("101% is out of range".into()));
+    let err = Percent::try_from(OwnedValue::from(101u8)).unwrap_err();
+    assert_eq!(err, zvariant::Error::Message("101% is out of range".into()));
+    assert_eq!(
+        Percent::try_from(Value::from("42")).unwrap_err(),
+        zvariant::Error::IncorrectType
+    );
+
+    let encoded = to_bytes(ctxt, &"zbus").unwrap();
+    let name: Name<'_> = from_slice(&encoded, ctxt).unwrap();
+    assert_eq!(name, Name(Str::from("zbus")));
+    assert_eq!(Value::from(name), Value::from("zbus"));
+}
+
+mod dict {
+    use byteorder::LE;
+    use std::collections::HashMap;
+    use zvariant::{from_slice, to_bytes, EncodingContext, OwnedValue, Type, Value};
+    use zvariant_derive::{DeserializeDict, SerializeDict, TypeDict};
+
+    fn encode<T: serde::Serialize + Type>(value: &T) -> Vec<u8> {
+        to_bytes(EncodingContext::<LE>::new_dbus(0), value).unwrap()
+    }
+
+    fn decode<T>(encoded: &[u8]) -> zvariant::Result<T>
+    where
+        T: for<'de> serde::Deserialize<'de> + Type,
+    {
+        from_slice(encoded, EncodingContext::<LE>::new_dbus(0))
+    }
+
+    fn entries(encoded: &[u8]) -> HashMap<String, OwnedValue> {
+        decode(encoded).unwrap()
+    }
+
+    // The encoding of a dictionary with the given entries.
+    fn dict(entries: &[(&str, Value<'_>)]) -> Vec<u8> {
+        let map: HashMap<&str, Value<'_>> = entries.iter().map(|(k, v)| (*k, v.clone())).collect();
+
+        encode(&map)
+    }
+
+    fn unknown_error(field: &str, expected: &str) -> String {
+        format!("unknown field `{}`, expected {}", field, expected)
+    }
+
+    #[test]
+    fn rename_and_deny_unknown_fields() {
+        #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
+        #[zvariant(deny_unknown_fields)]
+        struct Renamed {
+            #[zvariant(rename = "First-Name")]
+            first: String,
+            #[zvariant(rename = "Last-Name")]
+            last: Option<String>,
+        }
+        assert_eq!(Renamed::signature(), "a{sv}");
+
+        let r = Renamed {
+            first: "Ferris".into(),
+            last: None,
+        };
+        let encoded = encode(&r);
+        let e = entries(&encoded);
+        assert_eq!(e.len(), 1);
+        assert_eq!(&*e["First-Name"], &Value::from("Ferris"));
+        assert_eq!(decode::<Renamed>(&encoded).unwrap(), r);
+
+        let encoded = dict(&[("first", Value::from("Ferris"))]);
+        assert_eq!(
+            decode::<Renamed>(&encoded).unwrap_err().to_string(),
+            unknown_error("first", "`First-Name` or `Last-Name`"),
+        );
+
+        let encoded = dict(&[("Last-Name", Value::from("Crab"))]);
+        assert_eq!(
+            decode::<Renamed>(&encoded).unwrap_err().to_string(),
+            "missing field `First-Name`",
+        );
+    }
+
+    fn zero() -> u32 {
+        0
+    }
+
+    fn answer() -> u32 {
+        42
+    }
+
+    fn some_answer() -> Option<u32> {
+        Some(42)
+    }
+
+    #[test]
+    fn defaults() {
+        #[derive(DeserializeDict, TypeDict, PartialEq, Debug)]
+        struct Defaults {
+            #[zvariant(default)]
+            plain: u32,
+            #[zvariant(default = "answer")]
+            function: u32,
+            #[zvariant(default)]
+            option: Option<u32>,
+            #[zvariant(default = "some_answer")]
+            option_function: Option<u32>,
+            #[zvariant(rename = "Renamed", default = "self::zero")]
+            renamed: u32,
+        }
+        assert_eq!(Defaults::signature(), "a{sv}");
+
+        let missing = Defaults {
+            plain: 0,
+            function: 42,
+            option: None,
+            option_function: Some(42),
+            renamed: 0,
+        };
+        assert_eq!(decode::<Defaults>(&dict(&[])).unwrap(), missing);
+
+        let encoded = dict(&[
+            ("plain", Value::U32(1)),
+            ("function", Value::U32(2)),
+            ("option", Value::U32(3)),
+            ("option_function", Value::U32(4)),
+            ("Renamed", Value::U32(5)),
+        ]);
+        let present = Defaults {
+            plain: 1,
+            function: 2,
+            option: Some(3),
+            option_function: Some(4),
+            renamed: 5,
+        };
+        assert_eq!(decode::<Defaults>(&encoded).unwrap(), present);
+    }
+
+    #[test]
+    fn wrong_type() {
+        #[derive(DeserializeDict, TypeDict, Debug)]
+        struct Typed {
+            option: Option<u32>,
+            #[zvariant(default)]
+            defaulted: u32,
+        }
+
+        // Entries of another type are not taken for missing ones.
+        for encoded in &[
+            dict(&[("option", Value::from("42"))]),
+            dict(&[("defaulted", Value::U64(42))]),
+        ] {
+            assert!(decode::<Typed>(encoded).is_err());
+        }
+    }
+
+    #[test]
+    fn skip_serializing_if_none() {
+        type Maybe = Option<u32>;
+
+        #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
+        struct Skip {
+            #[zvariant(skip_serializing_if_none)]
+            alias: Maybe,
+            #[zvariant(skip_serializing_if_none)]
+            option: Option<u32>,
+            implied: Option<u32>,
+        }
+
+        let none = Skip {
+            alias: None,
+            option: None,
+            implied: None,
+        };
+        let encoded = encode(&none);
+        assert!(entries(&encoded).is_empty());
+        assert_eq!(decode::<Skip>(&encoded).unwrap(), none);
+
+        let some = Skip {
+            alias: Some(1),
+            option: Some(2),
+            implied: Some(3),
+        };
+        let encoded = encode(&some);
+        let e = entries(&encoded);
+        assert_eq!(&*e["alias"], &Value::U32(1));
+        assert_eq!(&*e["option"], &Value::U32(2));
+        assert_eq!(&*e["implied"], &Value::U32(3));
+        assert_eq!(decode::<Skip>(&encoded).unwrap(), some);
+    }
+
+    #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
+    struct Inner {
+        id: u32,
+        label: Option<String>,
+    }
+
+    #[test]
+    fn variants() {
+        #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
+        struct Variants {
+            owned: OwnedValue,
+            option: Option<OwnedValue>,
+            #[zvariant(wrap)]
+            wrapped: OwnedValue,
+            nested: Inner,
+            nested_option: Option<Inner>,
+        }
+        assert_eq!(Variants::signature(), "a{sv}");
+
+        let v = Variants {
+            owned: Value::U32(1).into(),
+            option: Some(Value::from("two").into()),
+            wrapped: Value::U8(3).into(),
+            nested: Inner {
+                id: 4,
+                label: Some("four".into()),
+            },
+            nested_option: None,
+        };
+        let encoded = encode(&v);
+        let e = entries(&encoded);
+        assert_eq!(&*e["owned"], &Value::U32(1));
+        assert_eq!(&*e["option"], &Value::from("two"));
+        assert_eq!(&*e["wrapped"], &Value::Value(Box::new(Value::U8(3))));
+        assert!(!e.contains_key("nested_option"));
+        assert_eq!(e["nested"].value_signature(), "a{sv}");
+        assert_eq!(decode::<Variants>(&encoded).unwrap(), v);
+
+        // Fields of type `Value` are not wrapped either, and `variant` covers the other spellings.
+        type Any<'a> = Value<'a>;
+
+        #[derive(SerializeDict, TypeDict)]
+        struct Borrowed<'a> {
+            value: Value<'a>,
+            #[zvariant(variant)]
+            alias: Any<'a>,
+        }
+        let b = Borrowed {
+            value: Value::from("five"),
+            alias: Value::U32(6),
+        };
+        let e = entries(&encode(&b));
+        assert_eq!(&*e["value"], &Value::from("five"));
+        assert_eq!(&*e["alias"], &Value::U32(6));
+    }
+
+    #[test]
+    fn attributes() {
+        // The `zvariant` attributes of a field are all taken into account, and fields named like
+        // the locals of the generated code are fine.
+        #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
+        #[zvariant(deny_unknown_fields)]
+        struct Attributes {
+            #[zvariant(rename = "Key")]
+            #[zvariant(default)]
+            key: u32,
+            access: Option<u32>,
+            field: String,
+        }
+        assert_eq!(Attributes::signature(), "a{sv}");
+
+        let a = Attributes {
+            key: 1,
+            access: Some(2),
+            field: "three".into(),
+        };
+        assert_eq!(decode::<Attributes>(&encode(&a)).unwrap(), a);
+        let encoded = dict(&[("field", Value::from("three"))]);
+        assert_eq!(decode::<Attributes>(&encoded).unwrap().key, 0);
+
+        // `TypeDict` alone accepts the attributes as well.
+        #[derive(TypeDict)]
+        #[zvariant(deny_unknown_fields)]
+        struct TypeOnly {
+            #[zvariant(rename = "Field", default)]
+            field: u32,
+        }
+        assert_eq!(TypeOnly::signature(), "a{sv}");
+    }
+
+    // The properties of a transient unit, as passed to systemd's `StartTransientUnit`.
+    #[derive(SerializeDict, DeserializeDict, TypeDict, PartialEq, Debug)]
+    #[zvariant(deny_unknown_fields)]
+    struct TransientUnit {
+        #[zvariant(rename = "Description")]
+        description: String,
+        #[zvariant(rename = "Slice", default = "default_slice")]
+        slice: String,
+        #[zvariant(rename = "Environment", default)]
+        environment: Vec<String>,
+        #[zvariant(rename = "CPUWeight")]
+        cpu_weight: Option<u64>,
+        #[zvariant(rename = "MemoryMax")]
+        memory_max: Option<u64>,
+        #[zvariant(rename = "CollectMode")]
+        collect_mode: Option<OwnedValue>,
+    }
+
+    fn default_slice() -> String {
+        "app.slice".into()
+    }
+
+    #[test]
+    fn transient_unit() {
+        let unit = TransientUnit {
+            description: "zbus test".into(),
+            slice: "zbus.slice".into(),
+            environment: vec!["ZBUS=1".into()],
+            cpu_weight: Some(50),
+            memory_max: None,
+            collect_mode: Some(Value::from("inactive-or-failed").into()),
+        };
+        let encoded = encode(&unit);
+        let e = entries(&encoded);
+        let mut keys: Vec<_> = e.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(
+            keys,
+            [
+                "CPUWeight",
+                "CollectMode",
+                "Description",
+                "Environment",
+                "Slice"
+            ]
+        );
+        assert_eq!(&*e["CPUWeight"], &Value::U64(50));
+        assert_eq!(&*e["CollectMode"], &Value::from("inactive-or-failed"));
+        assert_eq!(decode::<TransientUnit>(&encoded).unwrap(), unit);
+
+        // As sent by an older client.
+        let encoded = dict(&[("Description", Value::from("zbus test"))]);
+        let unit = decode::<TransientUnit>(&encoded).unwrap();
+        assert_eq!(unit.slice, "app.slice");
+        assert!(unit.environment.is_empty());
+        assert_eq!(unit.cpu_weight, None);
+
+        // And a newer one.
+        let encoded = dict(&[
+            ("Description", Value::from("zbus test")),
+            ("IOWeight", Value::U64(10)),
+        ]);
+        assert_eq!(
+            decode::<TransientUnit>(&encoded).unwrap_err().to_string(),
+            unknown_error(
+                "IOWeight",
+                "one of `Description`, `Slice`, `Environment`, `CPUWeight`, `MemoryMax`, \
+                 `CollectMode`",
+            ),
+        );
+    }
 }